@@ -0,0 +1,35 @@
+/*
+ * C interface to the AIR agent. Build with
+ * `cargo rustc --lib --release --features ffi --crate-type cdylib` and link against the
+ * resulting libair (.so, .dylib or .dll).
+ *
+ * Calls block until complete. Strings returned by the library are released with
+ * air_string_free; on failure functions return NULL and air_last_error() explains why.
+ */
+#ifndef AIR_H
+#define AIR_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct AirAgent AirAgent;
+
+/* Creates an agent from a config.toml path, or the default config when config_path is NULL. */
+AirAgent *air_agent_new(const char *config_path);
+
+/* Answers a prompt (tools enabled). Free the result with air_string_free. */
+char *air_agent_query(AirAgent *agent, const char *prompt);
+
+void air_agent_free(AirAgent *agent);
+
+void air_string_free(char *value);
+
+/* Last error on the calling thread, or NULL. Do not free. */
+const char *air_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AIR_H */
@@ -0,0 +1,217 @@
+//! Tags each query with an intent and a complexity before it is routed. The labels pick local
+//! or cloud first (`[[routing.rules]]`, else `performance.prefer_local_for_simple_queries`), hint the ReAct loop at the
+//! tools likely to help, and are stored with the query for `air cost` breakdowns.
+//!
+//! Classification is keyword based so it costs nothing and never calls a model.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Intent {
+    /// Conversation, opinions, explanations.
+    Chat,
+    /// Writing, reviewing or debugging code.
+    Code,
+    /// Looking something up: memory, the knowledge base, the web.
+    Retrieval,
+    /// Doing something on the machine: files, commands, tasks, screenshots.
+    ToolAction,
+    /// Arithmetic and other calculations.
+    Math,
+}
+
+impl Intent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Intent::Chat => "chat",
+            Intent::Code => "code",
+            Intent::Retrieval => "retrieval",
+            Intent::ToolAction => "tool_action",
+            Intent::Math => "math",
+        }
+    }
+}
+
+impl fmt::Display for Intent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Complexity {
+    Simple,
+    Moderate,
+    Complex,
+}
+
+impl Complexity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Complexity::Simple => "simple",
+            Complexity::Moderate => "moderate",
+            Complexity::Complex => "complex",
+        }
+    }
+}
+
+impl fmt::Display for Complexity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Classification {
+    pub intent: Intent,
+    pub complexity: Complexity,
+    /// Tools likely to help, best first.
+    pub tool_hints: Vec<String>,
+}
+
+const CODE_WORDS: &[&str] = &[
+    "```", "code", "function", "compile", "compiler", "bug", "stack trace", "traceback", "refactor",
+    "rust", "python", "javascript", "typescript", "golang", "java", "c++", "sql", "regex", "json",
+    "fn ", "def ", "class ", "struct ", "unit test", "exception", "segfault", "cargo ", "npm ",
+];
+const RETRIEVAL_WORDS: &[&str] = &[
+    "search", "look up", "lookup", "find out", "what did i", "did i tell", "remember", "recall",
+    "according to", "latest", "news", "knowledge base", "my notes", "documentation", "docs",
+    "who is", "who was", "when did", "where is", "source",
+];
+// (phrase, tool it calls for)
+const ACTION_WORDS: &[(&str, &str)] = &[
+    ("create a file", "filesystem"), ("create file", "filesystem"), ("write to", "filesystem"),
+    ("read file", "filesystem"), ("read the file", "filesystem"), ("list files", "filesystem"),
+    ("delete", "filesystem"), ("rename", "filesystem"), ("folder", "filesystem"), ("directory", "filesystem"),
+    ("git status", "git"), ("git diff", "git"), ("git log", "git"), ("uncommitted", "git"), ("last commit", "git"),
+    ("github issue", "github"), ("pull request", "github"), ("open an issue", "github"), ("file an issue", "github"),
+    ("jira", "tracker"), ("linear issue", "tracker"), ("in the tracker", "tracker"),
+    ("run ", "command"), ("execute", "command"), ("install", "command"), ("terminal", "command"),
+    ("remind", "planner"), ("reminder", "planner"), ("schedule", "planner"), ("todo", "planner"), ("add a task", "planner"), ("my tasks", "planner"),
+    ("screenshot", "screenshot"), ("my screen", "screenshot"),
+    ("download", "web"), ("open http", "web"), ("fetch", "web"),
+    ("say it", "voice"), ("read it aloud", "voice"), ("speak", "voice"),
+    ("cpu", "system"), ("memory usage", "system"), ("disk space", "system"), ("battery", "system"),
+    ("subscribe", "WebScraper"), ("rss", "WebScraper"), ("feed", "WebScraper"),
+];
+const MATH_WORDS: &[&str] = &[
+    "calculate", "compute", "solve", "equation", "integral", "derivative", "sqrt", "square root",
+    "percent", "how much is", "sum of", "average of", "convert", "multiply", "divide",
+];
+const MULTI_STEP_WORDS: &[&str] = &[
+    "step by step", "and then", "compare", "explain why", "trade-off", "tradeoff", "pros and cons",
+    "design", "architecture", "in detail", "analyze", "analyse", "plan ", "first,", "finally",
+];
+
+/// Labels `prompt` with its most likely intent and how demanding it is.
+pub fn classify(prompt: &str) -> Classification {
+    let text = prompt.to_lowercase();
+    let count = |words: &[&str]| words.iter().filter(|w| contains_word(&text, w)).count();
+
+    let action_tools = action_tools(&text);
+    let math = count(MATH_WORDS) + usize::from(looks_like_arithmetic(&text)) * 2;
+    let scores = [
+        (Intent::Math, math),
+        (Intent::ToolAction, action_tools.len()),
+        (Intent::Code, count(CODE_WORDS)),
+        (Intent::Retrieval, count(RETRIEVAL_WORDS)),
+    ];
+    // Ties go to the earlier, more specific intent
+    let intent = scores.iter()
+        .fold((Intent::Chat, 0), |best, &(intent, score)| if score > best.1 { (intent, score) } else { best })
+        .0;
+
+    let tool_hints = match intent {
+        Intent::Math => vec!["calculator".to_string()],
+        Intent::ToolAction => action_tools,
+        Intent::Retrieval => ["memory", "knowledge", "web"].map(String::from).to_vec(),
+        Intent::Code => ["filesystem", "command"].map(String::from).to_vec(),
+        Intent::Chat => Vec::new(),
+    };
+
+    Classification { intent, complexity: complexity(&text, intent), tool_hints }
+}
+
+fn action_tools(text: &str) -> Vec<String> {
+    let mut tools: Vec<String> = Vec::new();
+    for (word, tool) in ACTION_WORDS {
+        if contains_word(text, word) && !tools.iter().any(|t| t == tool) {
+            tools.push(tool.to_string());
+        }
+    }
+    tools
+}
+
+/// Whole-word match, so "rust" doesn't fire on "trust" nor "run" on "prune".
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric());
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        (!is_word(word.chars().next()) || !is_word(before)) && (!is_word(word.chars().next_back()) || !is_word(after))
+    })
+}
+
+/// "2+2", "15 * 3.5", "(4 - 1) / 3": digits joined by operators.
+fn looks_like_arithmetic(text: &str) -> bool {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.windows(3).any(|w| w[0].is_ascii_digit() && "+-*/^%".contains(w[1]) && (w[2].is_ascii_digit() || w[2] == '('))
+}
+
+fn complexity(text: &str, intent: Intent) -> Complexity {
+    let words = text.split_whitespace().count();
+    let steps = MULTI_STEP_WORDS.iter().filter(|w| contains_word(text, w)).count();
+    let questions = text.matches('?').count();
+
+    let mut score = match words {
+        0..=12 => 0,
+        13..=60 => 1,
+        _ => 2,
+    };
+    score += steps.min(2) + questions.saturating_sub(1).min(1);
+    if intent == Intent::Code && text.contains("```") {
+        score += 1;
+    }
+
+    match score {
+        0 => Complexity::Simple,
+        1 | 2 => Complexity::Moderate,
+        _ => Complexity::Complex,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intents() {
+        assert_eq!(classify("What is 2+2?").intent, Intent::Math);
+        assert_eq!(classify("calculate the square root of 81").intent, Intent::Math);
+        assert_eq!(classify("Why does this Rust function not compile?").intent, Intent::Code);
+        assert_eq!(classify("Search the latest news about Mars").intent, Intent::Retrieval);
+        assert_eq!(classify("Remind me to call mom tomorrow").intent, Intent::ToolAction);
+        assert_eq!(classify("Hi, how are you today?").intent, Intent::Chat);
+        assert_eq!(classify("What is the capital of France? I trust you").intent, Intent::Chat);
+    }
+
+    #[test]
+    fn test_tool_hints() {
+        assert_eq!(classify("What is 15 * 4?").tool_hints, vec!["calculator"]);
+        assert_eq!(classify("take a screenshot and delete the old one").tool_hints, vec!["filesystem", "screenshot"]);
+        assert!(classify("hello there").tool_hints.is_empty());
+    }
+
+    #[test]
+    fn test_complexity() {
+        assert_eq!(classify("hello").complexity, Complexity::Simple);
+        assert_eq!(
+            classify("Compare Postgres and SQLite for a desktop app and explain why step by step, with pros and cons").complexity,
+            Complexity::Complex
+        );
+    }
+}
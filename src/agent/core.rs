@@ -1,23 +1,44 @@
 use crate::models::{ModelProvider, ModelResponse};
-use crate::providers::{LocalProvider, OpenAIProvider, AnthropicProvider, GeminiProvider, OpenRouterProvider};
-use crate::config::Config;
+use crate::providers::{LocalProvider, MeteredProvider, RateLimitedProvider};
+use crate::providers::vcr::{Cassette, VcrProvider};
+use crate::config::{CloudProviderConfig, Config, LocalModelConfig, ModelPrice, PricingConfig, VcrMode};
 use crate::tools::ToolManager;
 use crate::utils::model_inspector;
-use crate::agent::memory::MemoryManager;
-use crate::agent::query::QueryProcessor;
+use crate::agent::memory::{Conversation, CostReport, MemoryManager, UsageSummary};
+use crate::agent::metrics::{AgentMetrics, ProviderMetrics};
+use crate::agent::snapshot::{AgentSnapshot, CachedPrompt, SNAPSHOT_VERSION};
+use crate::agent::query::{QueryMode, QueryOptions, QueryProcessor};
+use crate::agent::usage;
+use crate::agent::vision::ProviderVision;
+use crate::agent::validator::{self, Validator};
 use anyhow::{Result, anyhow};
+use serde::de::DeserializeOwned;
 use std::sync::Arc;
 use std::sync::Mutex;
 use tracing::{info, warn};
 
+// Recent response times per provider loaded at start-up to seed latency percentiles
+const LATENCY_HISTORY_SAMPLES: usize = 500;
+
+// Times `query_json` sends back an answer that doesn't match its schema
+const JSON_REPAIRS: u32 = 2;
+
+type LatencyHistory = std::collections::HashMap<String, Vec<u64>>;
+
+/// Cloning is cheap: clones share providers, tools, memory and caches, so one agent can serve
+/// concurrent queries (server, daemon) with a clone per task. `reload_local_model` and
+/// `reload_config` only affect the clone they are called on.
+#[derive(Clone)]
 pub struct AIAgent {
     local_provider: Option<Arc<dyn ModelProvider>>,
     cloud_providers: Vec<Arc<dyn ModelProvider>>,
-    config: Config,
-    tool_manager: ToolManager,
+    config: Arc<Config>,
+    tool_manager: Arc<ToolManager>,
     memory_manager: Arc<MemoryManager>,
     query_processor: QueryProcessor,
     prompt_cache: Arc<Mutex<std::collections::HashMap<String, (String, std::time::Instant)>>>,
+    /// Set when `[vcr]` records or replays model traffic.
+    cassette: Option<Arc<Cassette>>,
 }
 
 impl std::fmt::Debug for AIAgent {
@@ -42,95 +63,27 @@ impl AIAgent {
              config.local_model.is_small_model = system_ctx.is_constrained;
         }
 
-        // Get app data directory for database - Cross-platform
-        let app_data = crate::utils::paths::get_air_data_dir()
+        // Database directory: the data directory, or `.air/data` inside an `air init` project
+        let app_data = crate::utils::paths::get_air_memory_dir()
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_else(|_| std::env::temp_dir().to_string_lossy().to_string());
 
         // Initialize memory manager (async)
         let memory_manager = Arc::new(MemoryManager::new(&app_data).await?);
 
-        // Initialize local provider
-        let local_provider = if config.local_model.enabled {
-            match LocalProvider::new(config.local_model.clone()) {
-                Ok(provider) => {
-                    info!("✅ Local model initialized: {:?}", config.local_model.model_path);
-                    Some(Arc::new(provider) as Arc<dyn ModelProvider>)
-                }
-                Err(e) => {
-                    warn!("❌ Failed to initialize local model: {}", e);
-                    None
-                }
-            }
-        } else {
-            info!("🚫 Local model disabled by config");
-            None
+        let cassette = match config.vcr.mode {
+            VcrMode::Off => None,
+            mode => Some(Cassette::open(&config.vcr.cassette, mode)?),
         };
 
-        // Initialize cloud providers
-        let mut cloud_providers: Vec<Arc<dyn ModelProvider>> = Vec::new();
+        // Initialize local provider
+        let latency_history = memory_manager.latency_history(LATENCY_HISTORY_SAMPLES).await.unwrap_or_default();
+        let local_provider = build_local_provider(&config, &memory_manager, &latency_history, cassette.as_ref());
 
-        for cloud_config in &config.cloud_providers {
-            if !cloud_config.enabled {
-                info!("🚫 Cloud provider disabled by config: {}", cloud_config.name);
-                continue;
-            }
-            match cloud_config.name.as_str() {
-                "openai" => {
-                    match OpenAIProvider::new(cloud_config.clone()) {
-                        Ok(provider) => {
-                            if provider.is_available() {
-                                info!("✅ OpenAI provider initialized");
-                                cloud_providers.push(Arc::new(provider));
-                            } else {
-                                warn!("⚠️  OpenAI provider created but not available (missing API key)");
-                            }
-                        }
-                        Err(e) => warn!("❌ Failed to initialize OpenAI provider: {}", e),
-                    }
-                }
-                "anthropic" => {
-                    match AnthropicProvider::new(cloud_config.clone()) {
-                        Ok(provider) => {
-                            if provider.is_available() {
-                                info!("✅ Anthropic provider initialized");
-                                cloud_providers.push(Arc::new(provider));
-                            } else {
-                                warn!("⚠️  Anthropic provider created but not available (missing API key)");
-                            }
-                        }
-                        Err(e) => warn!("❌ Failed to initialize Anthropic provider: {}", e),
-                    }
-                }
-                "gemini" => {
-                    match GeminiProvider::new(cloud_config.clone()) {
-                        Ok(provider) => {
-                            if provider.is_available() {
-                                info!("✅ Gemini provider initialized");
-                                cloud_providers.push(Arc::new(provider));
-                            } else {
-                                warn!("⚠️  Gemini provider created but not available (missing API key)");
-                            }
-                        }
-                        Err(e) => warn!("❌ Failed to initialize Gemini provider: {}", e),
-                    }
-                }
-                "openrouter" => {
-                    match OpenRouterProvider::new(cloud_config.clone()) {
-                        Ok(provider) => {
-                            if provider.is_available() {
-                                info!("✅ OpenRouter provider initialized");
-                                cloud_providers.push(Arc::new(provider));
-                            } else {
-                                warn!("⚠️  OpenRouter provider created but not available (missing API key)");
-                            }
-                        }
-                        Err(e) => warn!("❌ Failed to initialize OpenRouter provider: {}", e),
-                    }
-                }
-                _ => warn!("Unknown cloud provider: {}", cloud_config.name),
-            }
-        }
+        // Initialize cloud providers
+        let cloud_providers: Vec<Arc<dyn ModelProvider>> = config.cloud_providers.iter()
+            .filter_map(|cloud_config| build_cloud_provider(cloud_config, &config.pricing, &memory_manager, &latency_history, cassette.as_ref()))
+            .collect();
 
         if local_provider.is_none() && cloud_providers.is_empty() {
             return Err(anyhow!("No providers available! Check your configuration."));
@@ -139,64 +92,369 @@ impl AIAgent {
         info!("Agent ready - Local: {}, Cloud: {}",
               local_provider.is_some(), cloud_providers.len());
 
+        let tool_manager = Arc::new(build_tool_manager(&config, &memory_manager, &cloud_providers).await);
+
         Ok(Self {
             local_provider,
             cloud_providers,
-            config,
-            tool_manager: ToolManager::new().await,
+            config: Arc::new(config),
+            tool_manager,
             memory_manager,
             query_processor: QueryProcessor::new(),
             prompt_cache: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            cassette,
         })
     }
 
     // Public interface methods that delegate to appropriate modules
     pub async fn query_with_tools(&self, prompt: &str) -> Result<ModelResponse> {
-        self.query_processor.query_with_tools(
+        usage::track(&self.memory_manager, prompt, self.query_processor.query_with_tools(
             prompt,
             &self.local_provider,
             &self.cloud_providers,
             &self.tool_manager,
             &self.memory_manager,
             &self.config,
-        ).await
+        )).await
+    }
+
+    pub async fn query_with_tools_in_mode(&self, prompt: &str, mode: &QueryMode) -> Result<ModelResponse> {
+        usage::track(&self.memory_manager, prompt, self.query_processor.query_with_tools_in_mode(
+            mode,
+            prompt,
+            &self.local_provider,
+            &self.cloud_providers,
+            &self.tool_manager,
+            &self.memory_manager,
+            &self.config,
+        )).await
+    }
+
+    /// Single model call (no tools) using `mode`'s strategy.
+    pub async fn query_in_mode(&self, prompt: &str, mode: &QueryMode) -> Result<ModelResponse> {
+        self.query_in_mode_with(prompt, mode, &QueryOptions::default()).await
+    }
+
+    async fn query_in_mode_with(&self, prompt: &str, mode: &QueryMode, options: &QueryOptions) -> Result<ModelResponse> {
+        usage::track(&self.memory_manager, prompt, self.query_processor.query_in_mode_with(
+            mode,
+            prompt,
+            options,
+            &self.local_provider,
+            &self.cloud_providers,
+            &self.memory_manager,
+            &self.config,
+        )).await
+    }
+
+    /// Asks for an answer following the JSON `schema` and parses it into `T`, in the default
+    /// mode. Providers with a JSON mode (OpenAI-compatible ones, Gemini) are held to the schema
+    /// by their API; for the rest the prompt spells it out and an answer that doesn't match is
+    /// sent back for repair (see `query_validated`).
+    pub async fn query_json<T: DeserializeOwned>(&self, prompt: &str, schema: &serde_json::Value) -> Result<T> {
+        let validators = [Validator::JsonSchema { schema: schema.clone() }];
+        let response = self.query_validated(&validator::json_prompt(prompt, schema), &self.config.default_mode, false, &validators, JSON_REPAIRS).await?;
+        let value = validator::parse_json(&response.content).map_err(|e| anyhow!(e))?;
+        serde_json::from_value(value).map_err(|e| anyhow!("The answer matches the schema but not the requested type: {}", e))
+    }
+
+    /// Queries in `mode` (through the ReAct loop when `use_tools`) until the answer passes every
+    /// validator. A failing answer is sent back with the validator's complaint, at most
+    /// `max_repairs` times; after that the last complaint is returned as the error. Without
+    /// tools, a `JsonSchema` validator's schema also goes to providers that can enforce it.
+    pub async fn query_validated(
+        &self,
+        prompt: &str,
+        mode: &QueryMode,
+        use_tools: bool,
+        validators: &[Validator],
+        max_repairs: u32,
+    ) -> Result<ModelResponse> {
+        let options = QueryOptions {
+            response_schema: validators.iter().find_map(|check| match check {
+                Validator::JsonSchema { schema } => Some(schema.clone()),
+                _ => None,
+            }),
+            ..Default::default()
+        };
+        let mut current_prompt = prompt.to_string();
+        let mut repairs = 0;
+        loop {
+            let response = if use_tools {
+                self.query_with_tools_in_mode(&current_prompt, mode).await?
+            } else {
+                self.query_in_mode_with(&current_prompt, mode, &options).await?
+            };
+
+            let mut failure = None;
+            for check in validators {
+                if let Err(error) = check.check(&response.content).await {
+                    failure = Some((check, error));
+                    break;
+                }
+            }
+            let Some((check, error)) = failure else { return Ok(response) };
+
+            if repairs >= max_repairs {
+                return Err(anyhow!("Answer failed the {} check after {} repair attempts: {}", check.name(), repairs, error));
+            }
+            repairs += 1;
+            warn!("🔧 Answer failed the {} check, asking for a fix ({}/{}): {}", check.name(), repairs, max_repairs, error);
+            current_prompt = validator::repair_prompt(prompt, &response.content, check, &error);
+        }
     }
 
     pub async fn query_with_fallback(&self, prompt: &str) -> Result<ModelResponse> {
-        self.query_processor.query_with_fallback(
+        usage::track(&self.memory_manager, prompt, self.query_processor.query_with_fallback(
             prompt,
+            &QueryOptions::default(),
             &self.local_provider,
             &self.cloud_providers,
             &self.memory_manager,
             &self.config,
-        ).await
+        )).await
     }
 
     pub async fn query_local_only(&self, prompt: &str) -> Result<ModelResponse> {
-        self.query_processor.query_local_only(
+        usage::track(&self.memory_manager, prompt, self.query_processor.query_local_only(
             prompt,
             &self.local_provider,
             &self.memory_manager,
             &self.config,
-        ).await
+        )).await
     }
 
     pub async fn query_cloud_only(&self, prompt: &str) -> Result<ModelResponse> {
-        self.query_processor.query_cloud_only(
+        usage::track(&self.memory_manager, prompt, self.query_processor.query_cloud_only(
             prompt,
+            &QueryOptions::default(),
             &self.cloud_providers,
             &self.memory_manager,
             &self.config,
-        ).await
+        )).await
     }
 
     pub async fn query_pure_local(&self, prompt: &str) -> Result<ModelResponse> {
-        self.query_processor.query_pure_local(
+        usage::track(&self.memory_manager, prompt, self.query_processor.query_pure_local(
             prompt,
             &self.local_provider,
             &self.memory_manager,
             &self.config,
-        ).await
+        )).await
+    }
+
+    /// Swaps the local model for the GGUF at `path` without restarting. The current model is
+    /// unloaded first so the two never occupy memory together; if the new one fails to load,
+    /// the previous model is loaded again and the error returned.
+    pub async fn reload_local_model(&mut self, path: &str) -> Result<()> {
+        if !std::path::Path::new(path).is_file() {
+            return Err(anyhow!("Model file not found: {}", path));
+        }
+
+        let previous = self.config.local_model.clone();
+        let mut local_config = previous.clone();
+        local_config.model_path = path.to_string();
+        local_config.enabled = true;
+        local_config.is_small_model = model_inspector::inspect_system(path).is_constrained;
+
+        self.local_provider = None;
+        match self.load_local_model(local_config.clone()).await {
+            Ok(provider) => {
+                info!("✅ Switched local model to {}", path);
+                self.local_provider = Some(provider);
+                Arc::make_mut(&mut self.config).local_model = local_config;
+                Ok(())
+            }
+            Err(e) => {
+                warn!("❌ Failed to load {}: {}", path, e);
+                if previous.enabled {
+                    self.local_provider = self.load_local_model(previous).await.ok();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn load_local_model(&self, local_config: LocalModelConfig) -> Result<Arc<dyn ModelProvider>> {
+        let provider = LocalProvider::new(local_config)?;
+        provider.wait_until_loaded().await?;
+        // Latencies recorded so far belong to the previous model, so start a fresh histogram
+        Ok(metered(Arc::new(provider), &self.memory_manager, self.config.pricing.clone(), Some(ModelPrice::blended(0.0)), &LatencyHistory::new(), self.cassette.as_ref()))
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Applies a reloaded config to a running agent, re-initializing only the providers and
+    /// tools whose settings changed. Returns a description of each change applied.
+    pub async fn reload_config(&mut self, mut new_config: Config) -> Vec<String> {
+        let mut changes = Vec::new();
+        let latency_history = self.memory_manager.latency_history(LATENCY_HISTORY_SAMPLES).await.unwrap_or_default();
+
+        // is_small_model is detected at startup, not read from the file
+        new_config.local_model.is_small_model = self.config.local_model.is_small_model;
+        if changed(&self.config.local_model, &new_config.local_model) {
+            if new_config.local_model.enabled {
+                let system_ctx = model_inspector::inspect_system(&new_config.local_model.model_path);
+                new_config.local_model.is_small_model = system_ctx.is_constrained;
+            }
+            self.local_provider = build_local_provider(&new_config, &self.memory_manager, &latency_history, self.cassette.as_ref());
+            changes.push(format!("local model re-initialized ({})",
+                if self.local_provider.is_some() { "available" } else { "unavailable" }));
+        }
+
+        // Prices are baked into each provider's meter, so new ones need the providers rebuilt
+        let pricing_changed = changed(&self.config.pricing, &new_config.pricing);
+        let mut providers_changed = false;
+        let mut cloud_providers = Vec::new();
+        for cloud_config in &new_config.cloud_providers {
+            let old_config = self.config.cloud_providers.iter().find(|c| c.name == cloud_config.name);
+            let existing = self.cloud_providers.iter()
+                .find(|p| p.name().eq_ignore_ascii_case(&cloud_config.name))
+                .cloned();
+
+            match (old_config, existing) {
+                (Some(old), existing) if !changed(old, cloud_config) && !pricing_changed => cloud_providers.extend(existing),
+                _ => {
+                    let provider = build_cloud_provider(cloud_config, &new_config.pricing, &self.memory_manager, &latency_history, self.cassette.as_ref());
+                    changes.push(format!("{} provider {}", cloud_config.name, match &provider {
+                        Some(_) => "re-initialized",
+                        None if cloud_config.enabled => "unavailable (check its API key)",
+                        None => "disabled",
+                    }));
+                    cloud_providers.extend(provider);
+                    providers_changed = true;
+                }
+            }
+        }
+        for old in &self.config.cloud_providers {
+            if !new_config.cloud_providers.iter().any(|c| c.name == old.name) {
+                changes.push(format!("{} provider removed", old.name));
+                providers_changed = true;
+            }
+        }
+        self.cloud_providers = cloud_providers;
+
+        let tools_changed = changed(&self.config.filesystem, &new_config.filesystem)
+            || changed(&self.config.command, &new_config.command)
+            || changed(&self.config.voice, &new_config.voice)
+            || changed(&self.config.web, &new_config.web)
+            || changed(&self.config.github, &new_config.github)
+            || changed(&self.config.tracker, &new_config.tracker)
+            || changed(&self.config.automation, &new_config.automation);
+        // The screenshot tool holds the cloud providers it analyzes images with
+        if tools_changed || providers_changed {
+            let safe_mode = self.tool_manager.safe_mode();
+            self.tool_manager = Arc::new(build_tool_manager(&new_config, &self.memory_manager, &self.cloud_providers).await);
+            self.tool_manager.set_safe_mode(safe_mode);
+            if tools_changed {
+                changes.push("tool settings reloaded".to_string());
+            }
+        }
+
+        if changed(&self.config.performance, &new_config.performance) {
+            changes.push("performance settings updated".to_string());
+        }
+        if changed(&self.config.planner, &new_config.planner) {
+            changes.push("planner settings updated".to_string());
+        }
+
+        self.config = Arc::new(new_config);
+        if self.local_provider.is_none() && self.cloud_providers.is_empty() {
+            warn!("⚠️  No providers are available after reloading the configuration");
+        }
+        changes
+    }
+
+    /// The local model (if loaded) followed by the enabled cloud providers.
+    pub fn providers(&self) -> Vec<Arc<dyn ModelProvider>> {
+        self.local_provider.iter().chain(&self.cloud_providers).cloned().collect()
+    }
+
+    /// Per-provider request counts, latency, tokens and cost from the usage log.
+    pub async fn usage_summary(&self, session_only: bool) -> Result<Vec<UsageSummary>> {
+        self.memory_manager.usage_summary(session_only).await
+    }
+
+    /// Spend since `since` (an SQLite UTC timestamp) by day, session and query kind.
+    pub async fn cost_report(&self, since: &str) -> Result<CostReport> {
+        self.memory_manager.cost_report(since).await
+    }
+
+    /// Snapshot of per-provider metrics, tool call statistics and cache hit counters.
+    pub fn metrics(&self) -> AgentMetrics {
+        AgentMetrics {
+            providers: self.providers().iter()
+                .map(|p| ProviderMetrics { name: p.name().to_string(), metrics: p.metrics().unwrap_or_default() })
+                .collect(),
+            tools: self.tool_manager.stats(),
+            caches: self.tool_manager.cache_stats(),
+        }
+    }
+
+    /// Captures session history, tool state and the prompt cache so another process can pick
+    /// up where this one left off (see `restore`).
+    pub async fn snapshot(&self) -> Result<AgentSnapshot> {
+        let prompt_cache = self.prompt_cache.lock().unwrap().iter()
+            .map(|(prompt, (enhanced, created))| CachedPrompt {
+                prompt: prompt.clone(),
+                enhanced: enhanced.clone(),
+                age_secs: created.elapsed().as_secs(),
+            })
+            .collect();
+
+        Ok(AgentSnapshot {
+            version: SNAPSHOT_VERSION,
+            created_at: chrono::Utc::now(),
+            session_id: self.memory_manager.session_id().to_string(),
+            conversations: self.memory_manager.session_conversations().await?,
+            session_memory: self.memory_manager.session_memory().await?,
+            tools: self.tool_manager.snapshot().await,
+            prompt_cache,
+        })
+    }
+
+    /// Replaces this agent's session history with the snapshot's and hands tools their saved
+    /// state. Usage from here on is still logged under this process's session id.
+    pub async fn restore(&self, snapshot: AgentSnapshot) -> Result<()> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(anyhow!("Snapshot version {} is not supported (expected {})", snapshot.version, SNAPSHOT_VERSION));
+        }
+        self.memory_manager.restore_session(&snapshot.conversations, &snapshot.session_memory).await?;
+        self.tool_manager.restore(snapshot.tools).await?;
+
+        let now = std::time::Instant::now();
+        let mut cache = self.prompt_cache.lock().unwrap();
+        for entry in snapshot.prompt_cache {
+            let created = now.checked_sub(std::time::Duration::from_secs(entry.age_secs)).unwrap_or(now);
+            cache.insert(entry.prompt, (entry.enhanced, created));
+        }
+        info!("♻️ Restored session {} ({} conversations)", snapshot.session_id, snapshot.conversations.len());
+        Ok(())
+    }
+
+    /// A clone of this agent that may only use the named tools (an empty list means none).
+    pub fn with_tools(&self, names: &[String]) -> Self {
+        Self { tool_manager: Arc::new(self.tool_manager.restricted_to(names)), ..self.clone() }
+    }
+
+    pub fn tool_definitions(&self) -> serde_json::Value {
+        self.tool_manager.get_tool_definitions()
+    }
+
+    /// Whether the model is limited to read-only tools.
+    pub fn safe_mode(&self) -> bool {
+        self.tool_manager.safe_mode()
+    }
+
+    /// Takes effect for every clone of this agent, including in-flight queries' next tool call.
+    pub fn set_safe_mode(&self, enabled: bool) {
+        self.tool_manager.set_safe_mode(enabled);
+    }
+
+    pub async fn execute_tool(&self, tool_name: &str, function: &str, args: serde_json::Value) -> Result<crate::tools::ToolResult> {
+        self.tool_manager.execute_tool(tool_name, function, args).await
     }
 
     // Memory management delegation
@@ -204,6 +462,15 @@ impl AIAgent {
         self.memory_manager.store_conversations_batch(conversations).await
     }
 
+    /// Drops the last exchange from the conversation history, so later prompts no longer see it.
+    pub async fn undo_last_exchange(&self) -> Result<Option<Conversation>> {
+        let removed = self.memory_manager.remove_last_conversation().await?;
+        if let Some(conversation) = &removed {
+            self.prompt_cache.lock().unwrap().remove(&conversation.user_input);
+        }
+        Ok(removed)
+    }
+
     pub async fn store_ram_memory(&self, key: &str, value: &str) -> Result<()> {
         self.memory_manager.store_ram_memory(key, value).await
     }
@@ -273,3 +540,47 @@ impl AIAgent {
         self.memory_manager.build_enhanced_prompt(base_prompt, &self.prompt_cache, &self.config).await
     }
 }
+
+/// Tools for `config`, with screenshot analysis going to the cloud providers.
+async fn build_tool_manager(config: &Config, memory: &Arc<MemoryManager>, cloud_providers: &[Arc<dyn ModelProvider>]) -> ToolManager {
+    ToolManager::with_vision(config, memory.clone(), ProviderVision::new(cloud_providers)).await
+}
+
+fn build_local_provider(config: &Config, memory: &Arc<MemoryManager>, history: &LatencyHistory, cassette: Option<&Arc<Cassette>>) -> Option<Arc<dyn ModelProvider>> {
+    if !config.local_model.enabled {
+        info!("🚫 Local model disabled by config");
+        return None;
+    }
+    match LocalProvider::new(config.local_model.clone()) {
+        Ok(provider) => {
+            info!("✅ Local model initialized: {:?}", config.local_model.model_path);
+            // Local generation costs nothing
+            Some(metered(Arc::new(provider), memory, config.pricing.clone(), Some(ModelPrice::blended(0.0)), history, cassette))
+        }
+        Err(e) => {
+            warn!("❌ Failed to initialize local model: {}", e);
+            None
+        }
+    }
+}
+
+fn build_cloud_provider(cloud_config: &CloudProviderConfig, pricing: &PricingConfig, memory: &Arc<MemoryManager>, history: &LatencyHistory, cassette: Option<&Arc<Cassette>>) -> Option<Arc<dyn ModelProvider>> {
+    crate::providers::cloud::create_provider(cloud_config)
+        .map(|provider| RateLimitedProvider::wrap(provider, cloud_config))
+        .map(|provider| metered(provider, memory, pricing.clone(), cloud_config.cost_per_million_tokens.map(ModelPrice::blended), history, cassette))
+}
+
+/// Replayed calls are metered like real ones, so the cassette sits between the meter and the provider.
+fn metered(provider: Arc<dyn ModelProvider>, memory: &Arc<MemoryManager>, pricing: PricingConfig, default_price: Option<ModelPrice>, history: &LatencyHistory, cassette: Option<&Arc<Cassette>>) -> Arc<dyn ModelProvider> {
+    let samples = history.get(provider.name()).map(Vec::as_slice).unwrap_or_default();
+    let provider = match cassette {
+        Some(cassette) => VcrProvider::wrap(provider, cassette.clone()),
+        None => provider,
+    };
+    MeteredProvider::wrap(provider, memory.clone(), pricing, default_price, samples)
+}
+
+// Config sections don't implement PartialEq; comparing their serialized form is enough to spot edits
+fn changed<T: serde::Serialize>(old: &T, new: &T) -> bool {
+    serde_json::to_value(old).ok() != serde_json::to_value(new).ok()
+}
@@ -1,7 +1,140 @@
-use crate::models::ModelResponse;
+//! Where auto mode sends a query (the routing policy) and what it answers when every provider
+//! fails (the fallback strategies).
+
+use crate::models::{ModelProvider, ModelResponse};
 use crate::agent::memory::MemoryManager;
+use crate::agent::classifier::{Classification, Complexity};
+use crate::config::{Config, RoutingRule};
 use anyhow::Result;
 use async_trait::async_trait;
+use std::fmt;
+use std::sync::Arc;
+
+/// One step of a route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteTarget {
+    Local,
+    /// Every cloud provider, best quality_score first.
+    Cloud,
+    /// One cloud provider, by name.
+    Provider(String),
+}
+
+impl RouteTarget {
+    pub fn parse(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "local" => RouteTarget::Local,
+            "cloud" => RouteTarget::Cloud,
+            _ => RouteTarget::Provider(name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for RouteTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteTarget::Local => f.write_str("local"),
+            RouteTarget::Cloud => f.write_str("cloud"),
+            RouteTarget::Provider(name) => f.write_str(name),
+        }
+    }
+}
+
+/// The targets to try for one query, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutePlan {
+    /// The `[[routing.rules]]` entry that matched (its name, else its position); None for the
+    /// built-in policy.
+    pub rule: Option<String>,
+    pub targets: Vec<RouteTarget>,
+    pub latency_budget_ms: Option<u64>,
+}
+
+impl RoutePlan {
+    pub fn within_budget(&self, provider: &dyn ModelProvider) -> bool {
+        self.latency_budget_ms.map_or(true, |budget| provider.estimated_latency_ms() <= budget)
+    }
+
+    /// The cloud providers `targets` name, skipping those already `tried` or over budget.
+    pub fn cloud_providers(&self, targets: &[RouteTarget], providers: &[Arc<dyn ModelProvider>], tried: &[String]) -> Vec<Arc<dyn ModelProvider>> {
+        let mut selected: Vec<Arc<dyn ModelProvider>> = Vec::new();
+        for target in targets {
+            let matching: Vec<&Arc<dyn ModelProvider>> = match target {
+                RouteTarget::Local => continue,
+                RouteTarget::Cloud => providers.iter().collect(),
+                RouteTarget::Provider(name) => providers.iter().filter(|p| p.name().eq_ignore_ascii_case(name)).collect(),
+            };
+            for provider in matching {
+                let name = provider.name();
+                if !tried.iter().any(|t| t == name)
+                    && !selected.iter().any(|s| s.name() == name)
+                    && self.within_budget(provider.as_ref()) {
+                    selected.push(provider.clone());
+                }
+            }
+        }
+        selected
+    }
+}
+
+impl fmt::Display for RoutePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let targets: Vec<String> = self.targets.iter().map(|t| t.to_string()).collect();
+        write!(f, "{}", targets.join(" → "))?;
+        if let Some(budget) = self.latency_budget_ms {
+            write!(f, " (within {}ms)", budget)?;
+        }
+        Ok(())
+    }
+}
+
+/// Routes a query by the first matching rule in `config.routing`, else by the built-in policy.
+/// `estimated_tokens` is the size of the prompt with its context (see `estimate_tokens`).
+pub fn plan_route(config: &Config, classification: &Classification, estimated_tokens: usize) -> RoutePlan {
+    let builtin = builtin_route(config, classification);
+    for (i, rule) in config.routing.rules.iter().enumerate() {
+        if !rule_matches(rule, classification, estimated_tokens) {
+            continue;
+        }
+        let mut targets: Vec<RouteTarget> = rule.route.iter().map(|t| RouteTarget::parse(t)).collect();
+        if rule.fallback {
+            for target in &builtin {
+                if !targets.contains(target) {
+                    targets.push(target.clone());
+                }
+            }
+        }
+        return RoutePlan {
+            rule: Some(rule.name.clone().unwrap_or_else(|| format!("#{}", i + 1))),
+            targets,
+            latency_budget_ms: rule.latency_budget_ms,
+        };
+    }
+    RoutePlan { rule: None, targets: builtin, latency_budget_ms: None }
+}
+
+/// Local first, except that complex queries are likely beyond the local model and go to the
+/// cloud first when `prefer_local_for_simple_queries` is on.
+fn builtin_route(config: &Config, classification: &Classification) -> Vec<RouteTarget> {
+    if config.performance.prefer_local_for_simple_queries && classification.complexity == Complexity::Complex {
+        vec![RouteTarget::Cloud, RouteTarget::Local]
+    } else {
+        vec![RouteTarget::Local, RouteTarget::Cloud]
+    }
+}
+
+fn rule_matches(rule: &RoutingRule, classification: &Classification, estimated_tokens: usize) -> bool {
+    let listed = |values: &[String], value: &str| values.is_empty() || values.iter().any(|v| v.eq_ignore_ascii_case(value));
+    listed(&rule.intent, classification.intent.as_str())
+        && listed(&rule.complexity, classification.complexity.as_str())
+        && rule.min_tokens.map_or(true, |min| estimated_tokens >= min)
+        && rule.max_tokens.map_or(true, |max| estimated_tokens <= max)
+}
+
+/// Rough token count of a prompt, at about four characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
 
 #[async_trait]
 pub trait FallbackStrategy: Send + Sync {
@@ -22,8 +155,10 @@ impl FallbackStrategy for CacheFallback {
                         content: format!("⚠️  Service temporarily unavailable. Here's a similar response from our conversation history:\n\n{}", ai_response),
                         model_used: "Fallback-Cache".to_string(),
                         tokens_used: 0,
+                        input_tokens: 0,
                         response_time_ms: 0,
                         confidence_score: Some(0.5),
+                        tool_calls: Vec::new(),
                     });
                 }
             }
@@ -58,11 +193,13 @@ pub struct DefaultFallback;
 impl FallbackStrategy for DefaultFallback {
     async fn execute(&self, prompt: &str, _memory_manager: &MemoryManager) -> Result<ModelResponse> {
         Ok(ModelResponse {
-            content: format!("⚠️  I'm currently experiencing connectivity issues. Please try again in a moment.\n\nYour query was: '{}'\n\nFor urgent matters, you can also try:\n• Using 'mode local' to force local processing\n• Checking your internet connection\n• Verifying API keys in your configuration", prompt),
+            content: format!("⚠️  I'm currently experiencing connectivity issues. Please try again in a moment.\n\nYour query was: '{}'\n\nFor urgent matters, you can also try:\n• Using 'air --mode local' to force local processing\n• Checking your internet connection\n• Verifying API keys in your configuration", prompt),
             model_used: "Fallback-Default".to_string(),
             tokens_used: 0,
+            input_tokens: 0,
             response_time_ms: 0,
             confidence_score: Some(0.1),
+            tool_calls: Vec::new(),
         })
     }
 }
@@ -101,3 +238,43 @@ impl Default for FallbackChain {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::classifier::Intent;
+
+    fn classified(intent: Intent, complexity: Complexity) -> Classification {
+        Classification { intent, complexity, tool_hints: Vec::new() }
+    }
+
+    #[test]
+    fn test_plan_route() {
+        let mut config = Config::default();
+        config.routing = toml::from_str(r#"
+            [[rules]]
+            name = "code"
+            intent = ["code"]
+            route = ["anthropic"]
+
+            [[rules]]
+            min_tokens = 8000
+            route = ["gemini"]
+            latency_budget_ms = 20000
+            fallback = false
+        "#).unwrap();
+
+        let code = plan_route(&config, &classified(Intent::Code, Complexity::Simple), 100);
+        assert_eq!(code.rule.as_deref(), Some("code"));
+        assert_eq!(code.targets, vec![RouteTarget::Provider("anthropic".to_string()), RouteTarget::Local, RouteTarget::Cloud]);
+
+        let long = plan_route(&config, &classified(Intent::Chat, Complexity::Simple), 9000);
+        assert_eq!(long.rule.as_deref(), Some("#2"));
+        assert_eq!(long.targets, vec![RouteTarget::Provider("gemini".to_string())]);
+        assert_eq!(long.to_string(), "gemini (within 20000ms)");
+
+        let complex = plan_route(&config, &classified(Intent::Chat, Complexity::Complex), 100);
+        assert_eq!(complex.rule, None);
+        assert_eq!(complex.targets, vec![RouteTarget::Cloud, RouteTarget::Local]);
+    }
+}
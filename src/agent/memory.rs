@@ -1,15 +1,25 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool, Row};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 use md5;
 use crate::rag::store::KnowledgeStore;
 use crate::rag::langchain_embedding::CandleEmbedder;
-use crate::models::Message;
+use crate::models::{ChatMessage, ModelResponse};
 use crate::config::Config;
+use crate::utils::context_window::estimate_tokens;
+use crate::agent::classifier::{self, Classification};
 
-#[derive(Debug, Clone)]
+/// Most user examples added to a prompt.
+const MAX_FEW_SHOT_EXAMPLES: usize = 3;
+/// Examples less similar than this to the prompt are left out.
+const MIN_EXAMPLE_SIMILARITY: f64 = 0.5;
+/// Added to the similarity of examples whose category matches the prompt's classified intent.
+const CATEGORY_MATCH_BONUS: f64 = 0.1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Conversation {
     pub id: i64,
     pub user_input: String,
@@ -19,6 +29,19 @@ pub struct Conversation {
     pub tools_used: Option<String>,
 }
 
+/// A question/answer pair shown to the model as an example of the expected answer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FewShotExample {
+    pub id: i64,
+    /// Free-form; naming it after a classifier intent (chat, code, retrieval, tool_action, math)
+    /// favours it for queries with that intent.
+    pub category: String,
+    pub question: String,
+    pub answer: String,
+    #[serde(skip)]
+    embedding: Option<Vec<f64>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Mistake {
     pub id: i64,
@@ -40,7 +63,138 @@ pub struct LearningPattern {
     pub last_updated: String,
 }
 
+/// An item from a subscribed RSS/Atom feed.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedItem {
+    pub feed_url: String,
+    pub entry_id: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub summary: Option<String>,
+    pub published: Option<String>,
+}
+
+/// One model call, as recorded in the usage log.
+#[derive(Debug, Clone)]
+pub struct ModelUsage {
+    /// The user query this call served (see `agent::usage`); None for calls made outside one.
+    pub query_id: Option<String>,
+    pub provider: String,
+    pub model: String,
+    pub success: bool,
+    pub latency_ms: u64,
+    pub tokens: u32,
+    /// How many of `tokens` were the prompt; 0 when the provider doesn't say.
+    pub input_tokens: u32,
+    /// None when the provider has no configured price.
+    pub cost_usd: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// A tool call made while answering a query, kept in the session transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub tool: String,
+    pub function: String,
+    pub args: serde_json::Value,
+    pub success: bool,
+    /// The result or error, shortened.
+    pub output: String,
+}
+
+/// One query of a session with its answer, as `air share` renders it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionTurn {
+    pub query_id: String,
+    pub prompt: String,
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub model_used: Option<String>,
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub timestamp: String,
+    /// Summed over every model call the query made.
+    pub tokens: u64,
+    pub cost_usd: Option<f64>,
+}
+
+/// A session that has a transcript.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub started: String,
+    pub turns: u64,
+    /// The first prompt, shortened.
+    pub first_prompt: String,
+}
+
+/// Aggregated usage of one provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageSummary {
+    pub provider: String,
+    pub requests: u64,
+    pub successes: u64,
+    pub failures: u64,
+    /// Average latency of successful calls.
+    pub avg_latency_ms: u64,
+    pub tokens: u64,
+    pub cost_usd: Option<f64>,
+}
+
+/// Spend of one provider/model on one day.
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyCost {
+    pub day: String,
+    pub provider: String,
+    pub model: String,
+    pub requests: u64,
+    pub tokens: u64,
+    pub cost_usd: Option<f64>,
+}
+
+/// Token usage of one provider/model over a day or a week (`air usage`).
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodUsage {
+    /// The day, or the Monday starting the week, as YYYY-MM-DD.
+    pub period: String,
+    pub provider: String,
+    pub model: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub tokens: u64,
+    /// None when none of the calls had a price.
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionCost {
+    pub session_id: String,
+    pub started: String,
+    pub requests: u64,
+    pub cost_usd: Option<f64>,
+}
+
+/// Spend of queries that called tools ("tools"), answered directly ("plain"), or model
+/// calls made outside a query such as benchmarks ("other").
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryKindCost {
+    pub kind: String,
+    pub queries: u64,
+    pub requests: u64,
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CostReport {
+    pub by_day: Vec<DailyCost>,
+    pub by_session: Vec<SessionCost>,
+    pub by_kind: Vec<QueryKindCost>,
+    /// Spend per classifier intent (chat, code, retrieval, tool_action, math).
+    pub by_intent: Vec<QueryKindCost>,
+}
+
 pub struct MemoryManager {
+    session_id: String,
     ram_pool: SqlitePool,
     rom_pool: SqlitePool,
     about_pool: SqlitePool,
@@ -49,25 +203,24 @@ pub struct MemoryManager {
 
 impl MemoryManager {
     pub async fn new(app_data: &str) -> Result<Self> {
-        let ram_db_path = std::path::Path::new(app_data).join("air").join("ram_memory.db");
         let rom_db_path = std::path::Path::new(app_data).join("air").join("rom_memory.db");
         let about_db_path = std::path::Path::new(app_data).join("air").join("about_memory.db");
 
         // Ensure directory exists
-        if let Some(parent) = ram_db_path.parent() {
+        if let Some(parent) = rom_db_path.parent() {
             if !parent.exists() {
                 tokio::fs::create_dir_all(parent).await?;
             }
         }
 
-        // Initialize RAM memory (clear it)
-        if ram_db_path.exists() {
-             tokio::fs::remove_file(&ram_db_path).await.ok();
-        }
-        tokio::fs::File::create(&ram_db_path).await?;
-
+        // RAM memory lives and dies with this process: a shared file would be wiped by every other
+        // `air` process starting up. One connection that is never recycled, since each connection
+        // to `:memory:` opens a database of its own.
         let ram_pool = SqlitePoolOptions::new()
-            .connect(&format!("sqlite://{}", ram_db_path.to_string_lossy()))
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
             .await?;
 
         sqlx::query(
@@ -136,6 +289,93 @@ impl MemoryManager {
             )"
         ).execute(&rom_pool).await?;
 
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS planner_items (
+                kind TEXT NOT NULL,
+                id TEXT NOT NULL,
+                data TEXT NOT NULL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (kind, id)
+            )"
+        ).execute(&rom_pool).await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS news_feeds (
+                url TEXT PRIMARY KEY,
+                title TEXT,
+                added_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&rom_pool).await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS news_items (
+                feed_url TEXT NOT NULL,
+                entry_id TEXT NOT NULL,
+                title TEXT NOT NULL,
+                link TEXT,
+                summary TEXT,
+                published TEXT,
+                fetched_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                read BOOLEAN DEFAULT FALSE,
+                PRIMARY KEY (feed_url, entry_id)
+            )"
+        ).execute(&rom_pool).await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS model_usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                success BOOLEAN NOT NULL,
+                latency_ms INTEGER NOT NULL,
+                tokens INTEGER NOT NULL DEFAULT 0,
+                cost_usd REAL,
+                error TEXT,
+                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&rom_pool).await?;
+
+        // query_id was added after model_usage was introduced; this fails harmlessly once it exists
+        sqlx::query("ALTER TABLE model_usage ADD COLUMN query_id TEXT").execute(&rom_pool).await.ok();
+        sqlx::query("ALTER TABLE model_usage ADD COLUMN input_tokens INTEGER NOT NULL DEFAULT 0").execute(&rom_pool).await.ok();
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS queries (
+                query_id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                tool_calls INTEGER NOT NULL DEFAULT 0,
+                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&rom_pool).await?;
+        // Classifier labels, added later like query_id above
+        sqlx::query("ALTER TABLE queries ADD COLUMN intent TEXT").execute(&rom_pool).await.ok();
+        sqlx::query("ALTER TABLE queries ADD COLUMN complexity TEXT").execute(&rom_pool).await.ok();
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS session_turns (
+                query_id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                prompt TEXT NOT NULL,
+                response TEXT,
+                error TEXT,
+                model_used TEXT,
+                tool_calls TEXT NOT NULL DEFAULT '[]',
+                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&rom_pool).await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS few_shot_examples (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                category TEXT NOT NULL,
+                question TEXT NOT NULL,
+                answer TEXT NOT NULL,
+                embedding TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&rom_pool).await?;
+
         // Initialize ABOUT memory
         if !about_db_path.exists() {
              tokio::fs::File::create(&about_db_path).await?;
@@ -171,12 +411,13 @@ impl MemoryManager {
         let knowledge_store = match KnowledgeStore::new(app_data).await {
             Ok(store) => Some(store),
             Err(e) => {
-                warn!("⚠️ Failed to initialize Memory Knowledge Store: {}. Context recall disabled.", e);
+                warn!("⚠️ Failed to initialize Memory Knowledge Store: {}. Context recall disabled; 'air doctor' shows why and 'air setup --local' installs the embedding model.", e);
                 None
             }
         };
 
         Ok(Self {
+            session_id: uuid::Uuid::new_v4().to_string(),
             ram_pool,
             rom_pool,
             about_pool,
@@ -217,6 +458,344 @@ impl MemoryManager {
         Ok(())
     }
 
+    /// Every conversation of this session, oldest first.
+    pub async fn session_conversations(&self) -> Result<Vec<Conversation>> {
+        let rows = sqlx::query("SELECT id, user_input, ai_response, timestamp, context, tools_used FROM conversations ORDER BY id")
+            .fetch_all(&self.ram_pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| Conversation {
+            id: row.get(0),
+            user_input: row.get(1),
+            ai_response: row.get(2),
+            timestamp: row.get(3),
+            context: row.get::<Option<String>, _>(4).filter(|c| !c.is_empty()),
+            tools_used: row.get::<Option<String>, _>(5).filter(|t| !t.is_empty()),
+        }).collect())
+    }
+
+    /// Deletes and returns this session's most recent conversation.
+    pub async fn remove_last_conversation(&self) -> Result<Option<Conversation>> {
+        let Some(row) = sqlx::query("SELECT id, user_input, ai_response, timestamp, context, tools_used FROM conversations ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&self.ram_pool)
+            .await? else { return Ok(None) };
+        let last = Conversation {
+            id: row.get(0),
+            user_input: row.get(1),
+            ai_response: row.get(2),
+            timestamp: row.get(3),
+            context: row.get::<Option<String>, _>(4).filter(|c| !c.is_empty()),
+            tools_used: row.get::<Option<String>, _>(5).filter(|t| !t.is_empty()),
+        };
+        sqlx::query("DELETE FROM conversations WHERE id = ?")
+            .bind(last.id)
+            .execute(&self.ram_pool)
+            .await?;
+        Ok(Some(last))
+    }
+
+    /// Every key/value pair stored with `store_ram_memory`.
+    pub async fn session_memory(&self) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query("SELECT key, value FROM memory ORDER BY key")
+            .fetch_all(&self.ram_pool)
+            .await?;
+        Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Replaces this session's conversations and RAM memory with ones exported from another
+    /// process, keeping their original timestamps.
+    pub async fn restore_session(&self, conversations: &[Conversation], memory: &[(String, String)]) -> Result<()> {
+        let mut tx = self.ram_pool.begin().await?;
+        sqlx::query("DELETE FROM conversations").execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM memory").execute(&mut *tx).await?;
+
+        for conversation in conversations {
+            sqlx::query("INSERT INTO conversations (user_input, ai_response, timestamp, context, tools_used) VALUES (?, ?, ?, ?, ?)")
+                .bind(&conversation.user_input)
+                .bind(&conversation.ai_response)
+                .bind(&conversation.timestamp)
+                .bind(conversation.context.clone().unwrap_or_default())
+                .bind(conversation.tools_used.clone().unwrap_or_default())
+                .execute(&mut *tx)
+                .await?;
+        }
+        for (key, value) in memory {
+            sqlx::query("INSERT OR REPLACE INTO memory (key, value) VALUES (?, ?)")
+                .bind(key)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Identifies this process's session in the usage log.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub async fn record_model_usage(&self, usage: &ModelUsage) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO model_usage (session_id, query_id, provider, model, success, latency_ms, tokens, input_tokens, cost_usd, error)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&self.session_id)
+        .bind(&usage.query_id)
+        .bind(&usage.provider)
+        .bind(&usage.model)
+        .bind(usage.success)
+        .bind(usage.latency_ms as i64)
+        .bind(usage.tokens as i64)
+        .bind(usage.input_tokens as i64)
+        .bind(usage.cost_usd)
+        .bind(&usage.error)
+        .execute(&self.rom_pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn record_query(&self, query_id: &str, tool_calls: u32, classification: Option<&Classification>) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO queries (query_id, session_id, tool_calls, intent, complexity) VALUES (?, ?, ?, ?, ?)")
+            .bind(query_id)
+            .bind(&self.session_id)
+            .bind(tool_calls as i64)
+            .bind(classification.map(|c| c.intent.as_str()))
+            .bind(classification.map(|c| c.complexity.as_str()))
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Stores the transcript of one finished query; `response` is None when it failed with `error`.
+    pub async fn record_turn(&self, query_id: &str, prompt: &str, response: Option<&ModelResponse>,
+                             error: Option<&str>, tool_calls: &[ToolCallRecord]) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO session_turns (query_id, session_id, prompt, response, error, model_used, tool_calls)
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(query_id)
+        .bind(&self.session_id)
+        .bind(prompt)
+        .bind(response.map(|r| r.content.as_str()))
+        .bind(error)
+        .bind(response.map(|r| r.model_used.as_str()))
+        .bind(serde_json::to_string(tool_calls)?)
+        .execute(&self.rom_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Sessions with a transcript, newest first.
+    pub async fn list_sessions(&self, limit: usize) -> Result<Vec<SessionSummary>> {
+        let rows = sqlx::query(
+            "SELECT session_id, MIN(timestamp), COUNT(*),
+                    (SELECT prompt FROM session_turns f WHERE f.session_id = t.session_id ORDER BY timestamp LIMIT 1)
+             FROM session_turns t GROUP BY session_id ORDER BY 2 DESC LIMIT ?"
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.rom_pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| {
+            let prompt: String = row.get(3);
+            SessionSummary {
+                session_id: row.get(0),
+                started: row.get(1),
+                turns: row.get::<i64, _>(2) as u64,
+                first_prompt: crate::tools::command::truncate_output(prompt.lines().next().unwrap_or_default(), 80),
+            }
+        }).collect())
+    }
+
+    /// Every turn of a session in order, with the tokens and cost of its model calls.
+    pub async fn session_turns(&self, session_id: &str) -> Result<Vec<SessionTurn>> {
+        let rows = sqlx::query(
+            "SELECT t.query_id, t.prompt, t.response, t.error, t.model_used, t.tool_calls, t.timestamp,
+                    COALESCE(SUM(u.tokens), 0), SUM(u.cost_usd)
+             FROM session_turns t LEFT JOIN model_usage u ON u.query_id = t.query_id
+             WHERE t.session_id = ?
+             GROUP BY t.query_id ORDER BY t.timestamp, t.rowid"
+        )
+        .bind(session_id)
+        .fetch_all(&self.rom_pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| {
+            let tool_calls: String = row.get(5);
+            SessionTurn {
+                query_id: row.get(0),
+                prompt: row.get(1),
+                response: row.get(2),
+                error: row.get(3),
+                model_used: row.get(4),
+                tool_calls: serde_json::from_str(&tool_calls).unwrap_or_default(),
+                timestamp: row.get(6),
+                tokens: row.get::<i64, _>(7) as u64,
+                cost_usd: row.get(8),
+            }
+        }).collect())
+    }
+
+    /// Spend recorded at or after `since` (UTC, "YYYY-MM-DD HH:MM:SS" like SQLite's CURRENT_TIMESTAMP).
+    pub async fn cost_report(&self, since: &str) -> Result<CostReport> {
+        let by_day = sqlx::query(
+            "SELECT date(timestamp), provider, model, COUNT(*), SUM(tokens), SUM(cost_usd)
+             FROM model_usage WHERE timestamp >= ?
+             GROUP BY 1, 2, 3 ORDER BY 1 DESC, 6 DESC"
+        )
+        .bind(since)
+        .fetch_all(&self.rom_pool)
+        .await?
+        .iter()
+        .map(|row| DailyCost {
+            day: row.get(0),
+            provider: row.get(1),
+            model: row.get(2),
+            requests: row.get::<i64, _>(3) as u64,
+            tokens: row.get::<i64, _>(4) as u64,
+            cost_usd: row.get(5),
+        })
+        .collect();
+
+        let by_session = sqlx::query(
+            "SELECT session_id, MIN(timestamp), COUNT(*), SUM(cost_usd)
+             FROM model_usage WHERE timestamp >= ?
+             GROUP BY session_id ORDER BY 2 DESC"
+        )
+        .bind(since)
+        .fetch_all(&self.rom_pool)
+        .await?
+        .iter()
+        .map(|row| SessionCost {
+            session_id: row.get(0),
+            started: row.get(1),
+            requests: row.get::<i64, _>(2) as u64,
+            cost_usd: row.get(3),
+        })
+        .collect();
+
+        let by_kind = sqlx::query(
+            "SELECT CASE WHEN q.query_id IS NULL THEN 'other' WHEN q.tool_calls > 0 THEN 'tools' ELSE 'plain' END,
+                    COUNT(DISTINCT u.query_id), COUNT(*), SUM(u.cost_usd)
+             FROM model_usage u LEFT JOIN queries q ON q.query_id = u.query_id
+             WHERE u.timestamp >= ?
+             GROUP BY 1 ORDER BY 4 DESC"
+        )
+        .bind(since)
+        .fetch_all(&self.rom_pool)
+        .await?
+        .iter()
+        .map(|row| QueryKindCost {
+            kind: row.get(0),
+            queries: row.get::<i64, _>(1) as u64,
+            requests: row.get::<i64, _>(2) as u64,
+            cost_usd: row.get(3),
+        })
+        .collect();
+
+        let by_intent = sqlx::query(
+            "SELECT COALESCE(q.intent, 'unlabeled'), COUNT(DISTINCT u.query_id), COUNT(*), SUM(u.cost_usd)
+             FROM model_usage u JOIN queries q ON q.query_id = u.query_id
+             WHERE u.timestamp >= ?
+             GROUP BY 1 ORDER BY 4 DESC"
+        )
+        .bind(since)
+        .fetch_all(&self.rom_pool)
+        .await?
+        .iter()
+        .map(|row| QueryKindCost {
+            kind: row.get(0),
+            queries: row.get::<i64, _>(1) as u64,
+            requests: row.get::<i64, _>(2) as u64,
+            cost_usd: row.get(3),
+        })
+        .collect();
+
+        Ok(CostReport { by_day, by_session, by_kind, by_intent })
+    }
+
+    /// Token usage recorded at or after `since`, per provider and model, grouped by UTC day or
+    /// by week (starting Monday). Failed calls count as requests but use no tokens.
+    pub async fn usage_by_period(&self, since: &str, weekly: bool) -> Result<Vec<PeriodUsage>> {
+        let period = if weekly { "date(timestamp, 'weekday 0', '-6 days')" } else { "date(timestamp)" };
+        let rows = sqlx::query(&format!(
+            "SELECT {}, provider, model, COUNT(*), SUM(input_tokens), SUM(tokens), SUM(cost_usd)
+             FROM model_usage WHERE timestamp >= ?
+             GROUP BY 1, 2, 3 ORDER BY 1 DESC, 6 DESC",
+            period
+        ))
+        .bind(since)
+        .fetch_all(&self.rom_pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| {
+            let input_tokens = row.get::<i64, _>(4) as u64;
+            let tokens = row.get::<i64, _>(5) as u64;
+            PeriodUsage {
+                period: row.get(0),
+                provider: row.get(1),
+                model: row.get(2),
+                requests: row.get::<i64, _>(3) as u64,
+                input_tokens,
+                output_tokens: tokens.saturating_sub(input_tokens),
+                tokens,
+                cost_usd: row.get(6),
+            }
+        }).collect())
+    }
+
+    /// The latest `limit` successful response times of each provider, keyed by provider name,
+    /// used to seed latency histograms across restarts.
+    pub async fn latency_history(&self, limit: usize) -> Result<std::collections::HashMap<String, Vec<u64>>> {
+        let rows = sqlx::query(
+            "SELECT provider, latency_ms FROM (
+                SELECT provider, latency_ms, ROW_NUMBER() OVER (PARTITION BY provider ORDER BY id DESC) AS rn
+                FROM model_usage WHERE success
+             ) WHERE rn <= ?"
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.rom_pool)
+        .await?;
+
+        let mut history: std::collections::HashMap<String, Vec<u64>> = std::collections::HashMap::new();
+        for row in rows {
+            let latency: i64 = row.get(1);
+            history.entry(row.get(0)).or_default().push(latency as u64);
+        }
+        Ok(history)
+    }
+
+    /// Per-provider totals from the usage log, for this session only or all time.
+    pub async fn usage_summary(&self, session_only: bool) -> Result<Vec<UsageSummary>> {
+        let rows = sqlx::query(
+            "SELECT provider, COUNT(*), SUM(success), AVG(CASE WHEN success THEN latency_ms END), SUM(tokens), SUM(cost_usd)
+             FROM model_usage WHERE (? = 0 OR session_id = ?)
+             GROUP BY provider ORDER BY COUNT(*) DESC"
+        )
+        .bind(session_only)
+        .bind(&self.session_id)
+        .fetch_all(&self.rom_pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| {
+            let requests: i64 = row.get(1);
+            let successes: i64 = row.get(2);
+            let avg_latency: Option<f64> = row.get(3);
+            let tokens: i64 = row.get(4);
+            UsageSummary {
+                provider: row.get(0),
+                requests: requests as u64,
+                successes: successes as u64,
+                failures: (requests - successes) as u64,
+                avg_latency_ms: avg_latency.unwrap_or(0.0).round() as u64,
+                tokens: tokens as u64,
+                cost_usd: row.get(5),
+            }
+        }).collect())
+    }
+
     pub async fn store_ram_memory(&self, key: &str, value: &str) -> Result<()> {
         sqlx::query("INSERT OR REPLACE INTO memory (key, value) VALUES (?, ?)")
             .bind(key)
@@ -261,6 +840,161 @@ impl MemoryManager {
         }
     }
 
+    /// All persistent key/value memories as (key, value, timestamp), newest first.
+    pub async fn list_persistent_memory(&self) -> Result<Vec<(String, String, String)>> {
+        let rows = sqlx::query("SELECT key, value, timestamp FROM persistent_memory ORDER BY timestamp DESC")
+            .fetch_all(&self.rom_pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect())
+    }
+
+    /// Persistent memories whose key or value contains `query` (case-insensitive).
+    pub async fn search_persistent_memory(&self, query: &str) -> Result<Vec<(String, String)>> {
+        let pattern = format!("%{}%", query.to_lowercase());
+        let rows = sqlx::query("SELECT key, value FROM persistent_memory WHERE lower(key) LIKE ? OR lower(value) LIKE ? ORDER BY timestamp DESC")
+            .bind(&pattern)
+            .bind(&pattern)
+            .fetch_all(&self.rom_pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    pub async fn delete_persistent_memory(&self, key: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM persistent_memory WHERE key = ?")
+            .bind(key)
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn clear_persistent_memory(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM persistent_memory")
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Conversations from this session whose input or response contains `query`, as (user_input, ai_response, timestamp).
+    pub async fn search_conversations(&self, query: &str, limit: usize) -> Result<Vec<(String, String, String)>> {
+        let pattern = format!("%{}%", query.to_lowercase());
+        let rows = sqlx::query(
+            "SELECT user_input, ai_response, timestamp FROM conversations
+             WHERE lower(user_input) LIKE ? OR lower(ai_response) LIKE ? ORDER BY timestamp DESC LIMIT ?"
+        )
+            .bind(&pattern)
+            .bind(&pattern)
+            .bind(limit as i64)
+            .fetch_all(&self.ram_pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect())
+    }
+
+    /// Stores a planner task or plan (`kind` is "task" or "plan") as serialized JSON.
+    pub async fn save_planner_item(&self, kind: &str, id: &str, data: &str) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO planner_items (kind, id, data, updated_at) VALUES (?, ?, ?, CURRENT_TIMESTAMP)")
+            .bind(kind)
+            .bind(id)
+            .bind(data)
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn load_planner_items(&self, kind: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT data FROM planner_items WHERE kind = ? ORDER BY updated_at")
+            .bind(kind)
+            .fetch_all(&self.rom_pool)
+            .await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    pub async fn delete_planner_item(&self, kind: &str, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM planner_items WHERE kind = ? AND id = ?")
+            .bind(kind)
+            .bind(id)
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn add_news_feed(&self, url: &str, title: Option<&str>) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO news_feeds (url, title) VALUES (?, ?)")
+            .bind(url)
+            .bind(title)
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Removes a feed and its stored items. Returns false if it wasn't subscribed.
+    pub async fn remove_news_feed(&self, url: &str) -> Result<bool> {
+        let removed = sqlx::query("DELETE FROM news_feeds WHERE url = ?")
+            .bind(url)
+            .execute(&self.rom_pool)
+            .await?
+            .rows_affected() > 0;
+        sqlx::query("DELETE FROM news_items WHERE feed_url = ?")
+            .bind(url)
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(removed)
+    }
+
+    /// Subscribed feeds as (url, title).
+    pub async fn list_news_feeds(&self) -> Result<Vec<(String, Option<String>)>> {
+        let rows = sqlx::query("SELECT url, title FROM news_feeds ORDER BY added_at")
+            .fetch_all(&self.rom_pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Stores a feed item unless it was seen before. Returns true if the item is new.
+    /// `read` items (the backlog when subscribing) stay out of digests.
+    pub async fn insert_news_item(&self, item: &FeedItem, read: bool) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO news_items (feed_url, entry_id, title, link, summary, published, read) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+            .bind(&item.feed_url)
+            .bind(&item.entry_id)
+            .bind(&item.title)
+            .bind(&item.link)
+            .bind(&item.summary)
+            .bind(&item.published)
+            .bind(read)
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn unread_news_items(&self, limit: usize) -> Result<Vec<FeedItem>> {
+        let rows = sqlx::query(
+            "SELECT feed_url, entry_id, title, link, summary, published FROM news_items
+             WHERE read = FALSE ORDER BY published DESC, fetched_at DESC LIMIT ?"
+        )
+            .bind(limit as i64)
+            .fetch_all(&self.rom_pool)
+            .await?;
+        Ok(rows.iter().map(|row| FeedItem {
+            feed_url: row.get(0),
+            entry_id: row.get(1),
+            title: row.get(2),
+            link: row.get(3),
+            summary: row.get(4),
+            published: row.get(5),
+        }).collect())
+    }
+
+    pub async fn mark_news_items_read(&self, items: &[FeedItem]) -> Result<()> {
+        for item in items {
+            sqlx::query("UPDATE news_items SET read = TRUE WHERE feed_url = ? AND entry_id = ?")
+                .bind(&item.feed_url)
+                .bind(&item.entry_id)
+                .execute(&self.rom_pool)
+                .await?;
+        }
+        Ok(())
+    }
+
     pub async fn store_user_preference(&self, key: &str, value: &str) -> Result<()> {
         sqlx::query("INSERT OR REPLACE INTO user_preferences (key, value) VALUES (?, ?)")
             .bind(key)
@@ -528,12 +1262,72 @@ impl MemoryManager {
     pub async fn search_knowledge(&self, query: &str, limit: usize) -> Result<Vec<(String, f64)>> {
         if let Some(store) = &self.knowledge_store {
             let results = store.search(query, limit).await?;
-            Ok(results.into_iter().map(|(doc, score)| (doc.page_content, score)).collect())
+            Ok(results.into_iter().map(|(doc, score)| {
+                let content = match crate::rag::chunking::citation(&doc.metadata) {
+                    Some(citation) => format!("[{}] {}", citation, doc.page_content),
+                    None => doc.page_content,
+                };
+                (content, score)
+            }).collect())
         } else {
             Ok(vec![])
         }
     }
 
+    /// Stores an example answer, embedded now when the embedding model is available.
+    pub async fn add_example(&self, category: &str, question: &str, answer: &str) -> Result<i64> {
+        let embedding = match &self.knowledge_store {
+            Some(store) => Some(serde_json::to_string(&store.embed(question).await?)?),
+            None => None,
+        };
+        let result = sqlx::query("INSERT INTO few_shot_examples (category, question, answer, embedding) VALUES (?, ?, ?, ?)")
+            .bind(category.trim().to_lowercase())
+            .bind(question)
+            .bind(answer)
+            .bind(embedding)
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn list_examples(&self, category: Option<&str>) -> Result<Vec<FewShotExample>> {
+        let rows = sqlx::query("SELECT id, category, question, answer, embedding FROM few_shot_examples WHERE ? IS NULL OR category = ? ORDER BY id")
+            .bind(category.map(|c| c.trim().to_lowercase()))
+            .bind(category.map(|c| c.trim().to_lowercase()))
+            .fetch_all(&self.rom_pool)
+            .await?;
+        Ok(rows.into_iter().map(|row| FewShotExample {
+            id: row.get(0),
+            category: row.get(1),
+            question: row.get(2),
+            answer: row.get(3),
+            embedding: row.get::<Option<String>, _>(4).and_then(|e| serde_json::from_str(&e).ok()),
+        }).collect())
+    }
+
+    pub async fn delete_example(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM few_shot_examples WHERE id = ?")
+            .bind(id)
+            .execute(&self.rom_pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The stored examples most similar to `prompt`, best first. Similarity is embedding cosine,
+    /// or word overlap for examples (or prompts) without an embedding.
+    pub async fn relevant_examples(&self, prompt: &str, limit: usize) -> Result<Vec<FewShotExample>> {
+        let examples = self.list_examples(None).await?;
+        if examples.is_empty() {
+            return Ok(examples);
+        }
+        let prompt_embedding = match &self.knowledge_store {
+            Some(store) => store.embed(prompt).await.ok(),
+            None => None,
+        };
+        let intent = classifier::classify(prompt).intent;
+        Ok(rank_examples(examples, prompt, prompt_embedding.as_deref(), intent.as_str(), limit))
+    }
+
     pub async fn build_enhanced_prompt(&self, base_prompt: &str, prompt_cache: &Arc<Mutex<std::collections::HashMap<String, (String, std::time::Instant)>>>, config: &Config) -> Result<String> {
 
         // STRATEGY: Small / Constrained Model
@@ -557,6 +1351,10 @@ Example 4:
 User: What are the top news headlines?
 You: { "tool": "WebScraper", "function": "scrape_news", "args": { "max_articles": 10 } }
 "#;
+            let mut examples = examples.to_string();
+            for (i, example) in self.relevant_examples(base_prompt, 2).await.unwrap_or_default().into_iter().enumerate() {
+                examples.push_str(&format!("\nExample {}:\nUser: {}\nYou: {}\n", i + 5, example.question, example.answer));
+            }
 
             // Limit history to 1 turn for small models
             let mut history = String::new();
@@ -568,33 +1366,16 @@ You: { "tool": "WebScraper", "function": "scrape_news", "args": { "max_articles"
                 }
             }
 
-            return Ok(format!("You are a tool-use assistant. Use JSON to call tools.{}\n{}\nUser: {}", examples, history, base_prompt));
+            let language = language_instruction(config).map(|l| format!(" {}", l)).unwrap_or_default();
+            let project = project_instructions(config).map(|p| format!("\n{}", p)).unwrap_or_default();
+            return Ok(format!("You are a tool-use assistant. Use JSON to call tools.{}{}{}\n{}\nUser: {}", language, project, examples, history, base_prompt));
         }
 
         // STRATEGY: Large / Unconstrained Model
         // Cache removed here to ensure dynamic context (tools, history) is always fresh
         // The identity block is still static but prompt construction is now dynamic per request
 
-        const AIR_IDENTITY_BLOCK: &str = r#"
-You are AIR. This identity is fixed.
-```json
-{
-  "tool": "tool_name",
-  "function": "function_name",
-  "args": {
-    "arg1": "value1",
-    "arg2": "value2"
-  }
-}
-```
-use format the JSON block for tool access.
-After the tool is executed, the system will provide you with the result.
-If no tool is needed, respond in natural language.
-warning:
-1. Do not invent shell commands.Ask user before using write or update command.use read command directly.
-"#;
-
-        let mut enhanced_prompt = AIR_IDENTITY_BLOCK.to_string();
+        let mut enhanced_prompt = identity_block(config);
 
         if let Ok(Some(version)) = self.get_air_info("version").await {
             enhanced_prompt.push_str(&format!(" (v{})", version));
@@ -603,39 +1384,83 @@ warning:
         if let Ok(Some(preferences)) = self.get_user_preference("response_style").await {
             enhanced_prompt.push_str(&format!("\n\nUser Preference: Response style - {}", preferences));
         }
+        if let Some(language) = language_instruction(config) {
+            enhanced_prompt.push_str(&format!("\n\n{}", language));
+        }
+        if let Some(project) = project_instructions(config) {
+            enhanced_prompt.push_str(&format!("\n\n{}", project));
+        }
+
+        // Optional context is only added while the prompt still fits the local model's window
+        // (leaving room for its reply); the user's prompt itself is always included
+        let user_section = format!("\n\nUser says:\n{}", base_prompt);
+        let budget = config.local_model.enabled.then(|| {
+            let context_length = config.local_model.context_length;
+            (context_length - config.local_model.max_tokens.min(context_length / 2)) as usize
+        });
+        let fits = |prompt: &str, section: &str, reserved: &str| budget.map_or(true, |budget| {
+            estimate_tokens(prompt) + estimate_tokens(section) + estimate_tokens(reserved) <= budget
+        });
 
         if let Ok(recent_convs) = self.get_recent_conversations(3).await {
             if !recent_convs.is_empty() {
-                enhanced_prompt.push_str("\n\nRecent Conversation Context:");
+                let mut section = String::from("\n\nRecent Conversation Context:");
                 for (user, ai, _) in recent_convs {
-                    enhanced_prompt.push_str(&format!("\nUser: {}\nAI: {}", user, ai));
+                    section.push_str(&format!("\nUser: {}\nAI: {}", user, ai));
+                }
+                if fits(&enhanced_prompt, &section, &user_section) {
+                    enhanced_prompt.push_str(&section);
+                } else {
+                    debug!("Skipping conversation context: it would overflow the context window");
+                }
+            }
+        }
+
+        if let Ok(examples) = self.relevant_examples(base_prompt, MAX_FEW_SHOT_EXAMPLES).await {
+            if !examples.is_empty() {
+                let mut section = String::from("\n\nExamples of Good Answers:");
+                for example in examples {
+                    section.push_str(&format!("\nUser: {}\nAI: {}", example.question, example.answer));
+                }
+                if fits(&enhanced_prompt, &section, &user_section) {
+                    enhanced_prompt.push_str(&section);
+                } else {
+                    debug!("Skipping few-shot examples: they would overflow the context window");
                 }
             }
         }
 
         if let Ok(insights) = self.get_mistake_insights(base_prompt).await {
             if !insights.is_empty() {
-                enhanced_prompt.push_str("\n\nPast Issues to Avoid:");
+                let mut section = String::from("\n\nPast Issues to Avoid:");
                 for insight in insights {
-                    enhanced_prompt.push_str(&format!("\n- {}", insight));
+                    section.push_str(&format!("\n- {}", insight));
+                }
+                if fits(&enhanced_prompt, &section, &user_section) {
+                    enhanced_prompt.push_str(&section);
                 }
             }
         }
 
         // Add user prompt AFTER identity and context
-        enhanced_prompt.push_str(&format!("\n\nUser says:\n{}", base_prompt));
+        enhanced_prompt.push_str(&user_section);
 
         // RAG Integration
         // Automatically search knowledge base for relevant info
         match self.search_knowledge(base_prompt, 2).await {
             Ok(results) => {
                 if !results.is_empty() {
-                    enhanced_prompt.push_str("\n\nRelevant Knowledge from Memory:");
+                    let mut section = String::from("\n\nRelevant Knowledge from Memory:");
                     for (content, score) in results {
                         if score > 0.5 { // Only show highly relevant stuff
-                             enhanced_prompt.push_str(&format!("\n- {}", content));
+                             section.push_str(&format!("\n- {}", content));
                         }
                     }
+                    if fits(&enhanced_prompt, &section, "") {
+                        enhanced_prompt.push_str(&section);
+                    } else {
+                        debug!("Skipping knowledge base context: it would overflow the context window");
+                    }
                 }
             },
             Err(e) => {
@@ -647,30 +1472,11 @@ warning:
         Ok(enhanced_prompt)
     }
 
-    pub async fn build_structured_prompt(&self, base_prompt: &str) -> Result<Vec<Message>> {
+    pub async fn build_structured_prompt(&self, base_prompt: &str, config: &Config) -> Result<Vec<ChatMessage>> {
         let mut messages = Vec::new();
 
         // 1. System Identity (Fixed Prefix)
-        const AIR_IDENTITY_BLOCK: &str = r#"
-You are AIR. This identity is fixed.
-```json
-{
-  "tool": "tool_name",
-  "function": "function_name",
-  "args": {
-    "arg1": "value1",
-    "arg2": "value2"
-  }
-}
-```
-use format the JSON block for tool access.
-After the tool is executed, the system will provide you with the result.
-If no tool is needed, respond in natural language.
-warning:
-1. Do not invent shell commands.Ask user before using write or update command.use read command directly.
-"#;
-
-        let mut system_prompt = AIR_IDENTITY_BLOCK.to_string();
+        let mut system_prompt = identity_block(config);
 
         if let Ok(Some(version)) = self.get_air_info("version").await {
             system_prompt.push_str(&format!(" (v{})", version));
@@ -679,25 +1485,31 @@ warning:
         if let Ok(Some(preferences)) = self.get_user_preference("response_style").await {
             system_prompt.push_str(&format!("\n\nUser Preference: Response style - {}", preferences));
         }
+        if let Some(language) = language_instruction(config) {
+            system_prompt.push_str(&format!("\n\n{}", language));
+        }
+        if let Some(project) = project_instructions(config) {
+            system_prompt.push_str(&format!("\n\n{}", project));
+        }
 
-        messages.push(Message {
-            role: "system".to_string(),
-            content: system_prompt,
-        });
+        if let Ok(examples) = self.relevant_examples(base_prompt, MAX_FEW_SHOT_EXAMPLES).await {
+            if !examples.is_empty() {
+                system_prompt.push_str("\n\nExamples of Good Answers:");
+                for example in examples {
+                    system_prompt.push_str(&format!("\nUser: {}\nAI: {}", example.question, example.answer));
+                }
+            }
+        }
+
+        messages.push(ChatMessage::system(system_prompt));
 
         // 2. Recent Conversation History (Stable sequence)
         // Note: get_recent_conversations returns reverse chronological, so we reversed it in the method to be chronological.
         // It returns (user, ai, timestamp).
         if let Ok(recent_convs) = self.get_recent_conversations(5).await { // Increased context for structured mode
             for (user, ai, _) in recent_convs {
-                messages.push(Message {
-                    role: "user".to_string(),
-                    content: user,
-                });
-                messages.push(Message {
-                    role: "assistant".to_string(),
-                    content: ai,
-                });
+                messages.push(ChatMessage::user(user));
+                messages.push(ChatMessage::assistant(ai));
             }
         }
 
@@ -736,11 +1548,95 @@ warning:
             base_prompt.to_string()
         };
 
-        messages.push(Message {
-            role: "user".to_string(),
-            content: final_user_message,
-        });
+        messages.push(ChatMessage::user(final_user_message));
 
         Ok(messages)
     }
 }
+
+/// Asks for answers in `config.language`, whatever language the question or tool output is in.
+fn language_instruction(config: &Config) -> Option<String> {
+    let language = [&config.persona.language, &config.language].into_iter()
+        .find_map(|l| l.as_deref().filter(|l| !l.trim().is_empty()))?;
+    Some(format!("Always answer in {}, even when the question or tool results are in another language.", crate::utils::i18n::language_name(language)))
+}
+
+/// How to call tools, sent after the persona's identity.
+const TOOL_FORMAT_BLOCK: &str = r#"
+```json
+{
+  "tool": "tool_name",
+  "function": "function_name",
+  "args": {
+    "arg1": "value1",
+    "arg2": "value2"
+  }
+}
+```
+use format the JSON block for tool access.
+After the tool is executed, the system will provide you with the result.
+If no tool is needed, respond in natural language.
+warning:
+1. Do not invent shell commands.Ask user before using write or update command.use read command directly.
+"#;
+
+/// The `[persona]` identity followed by the tool-call format.
+fn identity_block(config: &Config) -> String {
+    format!("\n{}{}", config.persona.identity(), TOOL_FORMAT_BLOCK)
+}
+
+/// The project's `.air/persona.md`, when air runs inside an `air init` project.
+fn project_instructions(config: &Config) -> Option<String> {
+    let project = config.project.as_ref()?;
+    Some(format!("Project instructions ({}):\n{}", project.root.display(), project.persona.as_deref()?))
+}
+
+fn rank_examples(examples: Vec<FewShotExample>, prompt: &str, prompt_embedding: Option<&[f64]>, intent: &str, limit: usize) -> Vec<FewShotExample> {
+    let mut scored: Vec<(f64, FewShotExample)> = examples.into_iter()
+        .map(|example| {
+            let similarity = match (prompt_embedding, &example.embedding) {
+                (Some(a), Some(b)) => crate::rag::store::cosine_similarity(a, b),
+                _ => word_overlap(prompt, &example.question),
+            };
+            let bonus = if example.category == intent { CATEGORY_MATCH_BONUS } else { 0.0 };
+            (similarity + bonus, example)
+        })
+        .filter(|(score, _)| *score >= MIN_EXAMPLE_SIMILARITY)
+        .collect();
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    scored.into_iter().take(limit).map(|(_, example)| example).collect()
+}
+
+/// Share of words the two texts have in common (Jaccard), ignoring case and punctuation.
+fn word_overlap(a: &str, b: &str) -> f64 {
+    let words = |s: &str| -> std::collections::HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(|w| w.to_lowercase()).collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 { 0.0 } else { a.intersection(&b).count() as f64 / union as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(id: i64, category: &str, question: &str, embedding: Option<Vec<f64>>) -> FewShotExample {
+        FewShotExample { id, category: category.to_string(), question: question.to_string(), answer: String::new(), embedding }
+    }
+
+    #[test]
+    fn test_rank_examples() {
+        let examples = vec![
+            example(1, "chat", "what is the weather like", Some(vec![0.0, 1.0])),
+            example(2, "math", "what is 2 plus 2", Some(vec![1.0, 0.1])),
+            example(3, "code", "fix my rust build", Some(vec![0.9, 0.2])),
+        ];
+        let ranked = rank_examples(examples.clone(), "what is 3+3", Some(&[1.0, 0.0]), "math", 2);
+        assert_eq!(ranked.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 3]);
+
+        // Without embeddings, word overlap decides and unrelated examples are dropped
+        let ranked = rank_examples(examples, "fix my rust build please", None, "code", 3);
+        assert_eq!(ranked.iter().map(|e| e.id).collect::<Vec<_>>(), vec![3]);
+    }
+}
@@ -0,0 +1,22 @@
+use crate::models::ModelMetrics;
+use crate::tools::{CacheStats, ToolStats};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Point-in-time view of what a running agent has done, returned by `AIAgent::metrics()`.
+/// Counters cover the agent's lifetime; persisted history is available via `usage_summary`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentMetrics {
+    /// Local model first, then cloud providers in routing order.
+    pub providers: Vec<ProviderMetrics>,
+    /// Keyed by tool name.
+    pub tools: HashMap<String, ToolStats>,
+    /// Keyed by the tool owning the cache.
+    pub caches: HashMap<String, CacheStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderMetrics {
+    pub name: String,
+    pub metrics: ModelMetrics,
+}
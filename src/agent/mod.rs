@@ -2,9 +2,20 @@ pub mod core;
 pub mod memory;
 pub mod query;
 pub mod fallback;
+pub mod metrics;
+pub mod usage;
+pub mod snapshot;
+pub mod classifier;
+pub mod validator;
+pub mod vision;
 
 pub use core::AIAgent;
-pub use memory::{MemoryManager, Conversation, Mistake, LearningPattern};
-pub use query::{QueryProcessor, QueryMode, QueryRequest, QueryResponse};
+pub use memory::{MemoryManager, Conversation, Mistake, LearningPattern, ModelUsage, UsageSummary, CostReport, DailyCost, SessionCost, QueryKindCost};
+pub use query::{QueryProcessor, QueryMode, QueryOptions, QueryRequest, QueryResponse};
 pub use crate::models::QueryContext;
 pub use fallback::FallbackStrategy;
+pub use metrics::{AgentMetrics, ProviderMetrics};
+pub use snapshot::{AgentSnapshot, CachedPrompt};
+pub use classifier::{Classification, Complexity, Intent};
+pub use validator::Validator;
+pub use vision::ProviderVision;
@@ -1,20 +1,17 @@
-use crate::models::{ModelProvider, ModelResponse, QueryContext};
+use crate::models::{ModelProvider, ModelResponse, ProviderError, QueryContext, ToolSpec};
 use crate::config::Config;
 use crate::tools::ToolManager;
 use crate::agent::memory::MemoryManager;
+use crate::agent::classifier::{self, Classification, Complexity};
+use crate::agent::fallback::{self, RouteTarget};
+use crate::agent::usage;
 use anyhow::{Result, anyhow};
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, warn, debug};
 use futures;
 
-#[derive(Debug, Clone)]
-pub enum QueryMode {
-    Auto,       // Smart fallback (default)
-    LocalOnly,  // Force local model
-    CloudOnly,  // Force cloud model
-    PureLocal,  // Pure local model without templates
-}
+pub use crate::models::QueryMode;
 
 #[derive(Debug, Clone)]
 pub struct QueryRequest {
@@ -33,6 +30,16 @@ pub struct QueryResponse {
     pub confidence: Option<f64>,
 }
 
+/// What a model call offers besides the prompt; the default is a plain completion.
+#[derive(Debug, Clone, Default)]
+pub struct QueryOptions {
+    /// Tools for the cloud providers that call them natively.
+    pub tools: Vec<ToolSpec>,
+    /// JSON schema for providers with a JSON mode to hold the answer to.
+    pub response_schema: Option<serde_json::Value>,
+}
+
+#[derive(Clone)]
 pub struct QueryProcessor;
 
 impl QueryProcessor {
@@ -49,15 +56,33 @@ impl QueryProcessor {
         tool_manager: &ToolManager,
         memory_manager: &MemoryManager,
         config: &Config,
+    ) -> Result<ModelResponse> {
+        self.query_with_tools_in_mode(&config.default_mode, prompt, local_provider, cloud_providers, tool_manager, memory_manager, config).await
+    }
+
+    /// ReAct loop with each model call made in `mode` rather than the configured default.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn query_with_tools_in_mode(
+        &self,
+        mode: &QueryMode,
+        prompt: &str,
+        local_provider: &Option<Arc<dyn ModelProvider>>,
+        cloud_providers: &[Arc<dyn ModelProvider>],
+        tool_manager: &ToolManager,
+        memory_manager: &MemoryManager,
+        config: &Config,
     ) -> Result<ModelResponse> {
         info!("🔄 Starting ReAct loop");
+        let classification = classify_query(prompt);
 
         let mut current_prompt = prompt.to_string();
         let max_steps = 5;
         let mut steps = 0;
         let mut tool_history = String::new();
 
-        // Add tool definitions to the context
+        // Add tool definitions to the context; providers with native function calling also get
+        // them as tools, while local models answer with a JSON block
+        let options = QueryOptions { tools: tool_manager.tool_specs(), ..Default::default() };
         let tool_definitions = tool_manager.get_tool_definitions();
         let tool_context = format!("\nAvailable Tools:\n{}\n", serde_json::to_string_pretty(&tool_definitions)?);
 
@@ -67,27 +92,40 @@ impl QueryProcessor {
         // Actually, MemoryManager constructs the system prompt. We should probably update MemoryManager to accept tool defs,
         // but for now, let's append it to the user prompt to ensure the model sees it.
         current_prompt = format!("{}\n\n{}", tool_context, current_prompt);
+        if !classification.tool_hints.is_empty() {
+            current_prompt = format!(
+                "{}\n\n(This looks like a {} request; the {} tool{} may help.)",
+                current_prompt,
+                classification.intent,
+                classification.tool_hints.join(", "),
+                if classification.tool_hints.len() > 1 { "s" } else { "" }
+            );
+        }
 
         while steps < max_steps {
             steps += 1;
             info!("📍 ReAct Step {}/{}", steps, max_steps);
 
             // 1. Query the model
-            let response = self.query_with_fallback(
+            let response = self.query_in_mode_with(
+                mode,
                 &current_prompt,
+                &options,
                 local_provider,
                 cloud_providers,
                 memory_manager,
                 config
             ).await?;
 
-            // 2. Check for tool usage (JSON block)
-            if let Some(tool_call) = self.extract_json_tool_call(&response.content) {
+            // 2. Check for tool usage (a native tool call, else a JSON block)
+            let tool_call = response.tool_calls.first().cloned()
+                .or_else(|| self.extract_json_tool_call(&response.content));
+            if let Some(tool_call) = tool_call {
                 info!("🛠️  Model requested tool: {}", tool_call.tool_name);
 
                 // 3. Execute tool
                 // Clone arguments for execution so we can still use tool_call later
-                match tool_manager.execute_tool(
+                match tool_manager.execute_for_model(
                     &tool_call.tool_name,
                     &tool_call.function,
                     tool_call.arguments.clone()
@@ -124,13 +162,119 @@ impl QueryProcessor {
             } else {
                 // No tool call detected, this is the final answer
                 info!("🏁 Final response generated");
-                return Ok(response);
+                return self.reflect(mode, prompt, &classification, &tool_history, response, local_provider, cloud_providers, memory_manager, config).await;
             }
         }
 
         warn!("🛑 Max ReAct steps reached");
         // Return the last response
-        self.query_with_fallback(&current_prompt, local_provider, cloud_providers, memory_manager, config).await
+        let response = self.query_in_mode(mode, &current_prompt, local_provider, cloud_providers, memory_manager, config).await?;
+        self.reflect(mode, prompt, &classification, &tool_history, response, local_provider, cloud_providers, memory_manager, config).await
+    }
+
+    /// Reviews `draft` against the question and tool observations and revises it once if the
+    /// review finds problems (`[reflection]`). If the review or revision fails, or only a fallback
+    /// answer comes back, the draft is kept.
+    #[allow(clippy::too_many_arguments)]
+    async fn reflect(
+        &self,
+        mode: &QueryMode,
+        question: &str,
+        classification: &Classification,
+        observations: &str,
+        draft: ModelResponse,
+        local_provider: &Option<Arc<dyn ModelProvider>>,
+        cloud_providers: &[Arc<dyn ModelProvider>],
+        memory_manager: &MemoryManager,
+        config: &Config,
+    ) -> Result<ModelResponse> {
+        let settings = &config.reflection;
+        if !settings.enabled || (settings.complex_only && classification.complexity != Complexity::Complex) {
+            return Ok(draft);
+        }
+
+        info!("🪞 Reviewing the draft answer");
+        let review = critique_prompt(question, observations, &draft.content);
+        let critique = match &settings.critic_provider {
+            Some(name) => self.critique_with(name, &review, cloud_providers, config).await,
+            None => answered(self.query_in_mode(mode, &review, local_provider, cloud_providers, memory_manager, config).await).map(|r| r.content),
+        };
+        let critique = match critique {
+            Ok(critique) => critique,
+            Err(e) => {
+                warn!("⚠️  Review failed, keeping the draft: {}", e);
+                return Ok(draft);
+            }
+        };
+        if is_approval(&critique) {
+            info!("✅ Review found no problems");
+            return Ok(draft);
+        }
+
+        info!("✏️  Revising the answer after review");
+        let revision = revision_prompt(question, observations, &draft.content, &critique);
+        match answered(self.query_in_mode(mode, &revision, local_provider, cloud_providers, memory_manager, config).await) {
+            Ok(revised) => Ok(revised),
+            Err(e) => {
+                warn!("⚠️  Revision failed, keeping the draft: {}", e);
+                Ok(draft)
+            }
+        }
+    }
+
+    async fn critique_with(&self, name: &str, prompt: &str, cloud_providers: &[Arc<dyn ModelProvider>], config: &Config) -> Result<String> {
+        let provider = cloud_providers.iter()
+            .find(|p| p.name().eq_ignore_ascii_case(name) && p.is_available())
+            .ok_or_else(|| anyhow!("Critic provider '{}' is not configured or has no API key", name))?;
+        let context = QueryContext {
+            prompt: prompt.to_string(),
+            messages: Vec::new(),
+            tools: Vec::new(),
+            response_schema: None,
+            images: Vec::new(),
+            max_tokens: config.reflection.critique_max_tokens,
+            temperature: 0.2,
+            timeout: config.mode_params(&QueryMode::CloudOnly).timeout,
+            pure_mode: false,
+        };
+        Ok(provider.generate(&context).await?.content)
+    }
+
+    /// Dispatches to the query strategy for `mode` (normally `config.default_mode`).
+    pub async fn query_in_mode(
+        &self,
+        mode: &QueryMode,
+        prompt: &str,
+        local_provider: &Option<Arc<dyn ModelProvider>>,
+        cloud_providers: &[Arc<dyn ModelProvider>],
+        memory_manager: &MemoryManager,
+        config: &Config,
+    ) -> Result<ModelResponse> {
+        self.query_in_mode_with(mode, prompt, &QueryOptions::default(), local_provider, cloud_providers, memory_manager, config).await
+    }
+
+    /// `query_in_mode` with native tools and/or a response schema for the cloud providers.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn query_in_mode_with(
+        &self,
+        mode: &QueryMode,
+        prompt: &str,
+        options: &QueryOptions,
+        local_provider: &Option<Arc<dyn ModelProvider>>,
+        cloud_providers: &[Arc<dyn ModelProvider>],
+        memory_manager: &MemoryManager,
+        config: &Config,
+    ) -> Result<ModelResponse> {
+        let classification = classify_query(prompt);
+        match mode {
+            QueryMode::Auto => {
+                let classification = usage::current_classification().unwrap_or(classification);
+                self.route_with_fallback(prompt, options, &classification, local_provider, cloud_providers, memory_manager, config).await
+            }
+            QueryMode::LocalOnly => self.query_local_only(prompt, local_provider, memory_manager, config).await,
+            QueryMode::CloudOnly => self.query_cloud_only(prompt, options, cloud_providers, memory_manager, config).await,
+            QueryMode::PureLocal => self.query_pure_local(prompt, local_provider, memory_manager, config).await,
+        }
     }
 
     fn extract_json_tool_call(&self, content: &str) -> Option<crate::tools::ToolCall> {
@@ -187,10 +331,30 @@ impl QueryProcessor {
         }
     }
 
-    /// Query with smart fallback: try local first, then cloud if needed
+    /// Auto mode: tries the targets `fallback::plan_route` picks for the query (a matching
+    /// `[[routing.rules]]` entry, else local first and cloud after). `options` apply to the cloud
+    /// providers.
     pub async fn query_with_fallback(
         &self,
         prompt: &str,
+        options: &QueryOptions,
+        local_provider: &Option<Arc<dyn ModelProvider>>,
+        cloud_providers: &[Arc<dyn ModelProvider>],
+        memory_manager: &MemoryManager,
+        config: &Config,
+    ) -> Result<ModelResponse> {
+        // Inside the ReAct loop `prompt` carries tool definitions, so prefer the user's own prompt's label
+        let classification = usage::current_classification().unwrap_or_else(|| classify_query(prompt));
+        self.route_with_fallback(prompt, options, &classification, local_provider, cloud_providers, memory_manager, config).await
+    }
+
+    /// `query_with_fallback` for a query already labelled `classification`.
+    #[allow(clippy::too_many_arguments)]
+    async fn route_with_fallback(
+        &self,
+        prompt: &str,
+        options: &QueryOptions,
+        classification: &Classification,
         local_provider: &Option<Arc<dyn ModelProvider>>,
         cloud_providers: &[Arc<dyn ModelProvider>],
         memory_manager: &MemoryManager,
@@ -201,36 +365,54 @@ impl QueryProcessor {
         // Build enhanced prompt with context
         let enhanced_prompt = memory_manager.build_enhanced_prompt(prompt, &Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())), config).await?;
 
-        // Build structured prompt for local models (Prefix Caching)
-        let structured_messages = memory_manager.build_structured_prompt(prompt).await.ok();
+        // The same context as chat turns (system, history, question), which providers prefer
+        let structured_messages = memory_manager.build_structured_prompt(prompt, config).await.unwrap_or_default();
 
         info!("📝 Enhanced prompt length: {} characters", enhanced_prompt.len());
 
+        let params = config.mode_params(&QueryMode::Auto);
         let context = QueryContext {
             prompt: enhanced_prompt,
             messages: structured_messages,
-            max_tokens: config.local_model.max_tokens,
-            temperature: config.local_model.temperature,
-            timeout: Duration::from_secs(config.performance.local_timeout_seconds),
+            tools: options.tools.clone(),
+            response_schema: options.response_schema.clone(),
+            images: Vec::new(),
+            max_tokens: params.max_tokens,
+            temperature: params.temperature,
+            timeout: params.timeout,
             pure_mode: false,
         };
 
-        // Strategy 1: Try local first for fast response
-        if let Some(local_provider) = local_provider {
-            if local_provider.is_available() {
-                info!("🏠 Trying local model first...");
+        let plan = fallback::plan_route(config, classification, fallback::estimate_tokens(&context.prompt));
+        match &plan.rule {
+            Some(rule) => info!("🧭 Routing rule {} matched: {}", rule, plan),
+            None => debug!("🧭 Route for {} {} query: {}", classification.complexity, classification.intent, plan),
+        }
+
+        let mut tried: Vec<String> = Vec::new();
+        for (i, target) in plan.targets.iter().enumerate() {
+            if *target == RouteTarget::Local {
+                let Some(local_provider) = local_provider.as_ref().filter(|p| p.is_available()) else {
+                    continue;
+                };
+                if !plan.within_budget(local_provider.as_ref()) {
+                    debug!("Skipping the local model: expected to exceed the latency budget");
+                    continue;
+                }
+                info!("🏠 Trying local model...");
 
                 match tokio::time::timeout(
-                    Duration::from_secs(config.performance.local_timeout_seconds),
+                    params.timeout,
                     local_provider.generate(&context)
                 ).await {
                     Ok(Ok(mut response)) => {
                         info!("✅ Local model succeeded in {}ms", response.response_time_ms);
 
-                        // Check if we should also try cloud for comparison/quality
-                        if self.should_try_cloud_for_quality(&response) {
+                        // Check if we should also try the route's remaining cloud providers for quality
+                        let later_cloud = plan.cloud_providers(&plan.targets[i + 1..], cloud_providers, &tried);
+                        if !later_cloud.is_empty() && self.should_try_cloud_for_quality(&response) {
                             info!("🌤️  Also trying cloud for potential quality improvement...");
-                            if let Ok(cloud_response) = self.try_best_cloud_provider(&context, cloud_providers).await {
+                            if let Ok(cloud_response) = self.try_best_cloud_provider(&context, &later_cloud).await {
                                 if cloud_response.confidence_score.unwrap_or(0.0) >
                                    response.confidence_score.unwrap_or(0.0) + 0.1 {
                                     info!("📈 Cloud provider gave significantly better response");
@@ -249,19 +431,24 @@ impl QueryProcessor {
                         warn!("⏰ Local model timed out");
                     }
                 }
+            } else {
+                let providers = plan.cloud_providers(std::slice::from_ref(target), cloud_providers, &tried);
+                if providers.is_empty() {
+                    debug!("No untried cloud provider for route target {}", target);
+                    continue;
+                }
+                tried.extend(providers.iter().map(|p| p.name().to_string()));
+                info!("🌤️  Trying {}...", target);
+                match self.try_best_cloud_provider(&context, &providers).await {
+                    Ok(response) => return Ok(response),
+                    Err(e) => warn!("❌ {} failed: {}", target, e),
+                }
             }
         }
 
-        // Strategy 2: Fallback to cloud providers
-        info!("🌤️  Falling back to cloud providers...");
-        match self.try_best_cloud_provider(&context, cloud_providers).await {
-            Ok(response) => Ok(response),
-            Err(e) => {
-                warn!("❌ All providers failed: {}", e);
-                // Graceful degradation: try to provide a cached/default response
-                self.provide_graceful_fallback(prompt, memory_manager).await
-            }
-        }
+        warn!("❌ All providers on the route failed");
+        // Graceful degradation: try to provide a cached/default response
+        self.provide_graceful_fallback(prompt, memory_manager).await
     }
 
     /// Force local model only
@@ -284,17 +471,21 @@ impl QueryProcessor {
         // Build enhanced prompt with context
         let enhanced_prompt = memory_manager.build_enhanced_prompt(prompt, &Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())), config).await?;
 
-        // Build structured prompt for local models (Prefix Caching)
-        let structured_messages = memory_manager.build_structured_prompt(prompt).await.ok();
+        // The same context as chat turns (system, history, question), which providers prefer
+        let structured_messages = memory_manager.build_structured_prompt(prompt, config).await.unwrap_or_default();
 
         info!("📝 Enhanced prompt length: {} characters", enhanced_prompt.len());
 
+        let params = config.mode_params(&QueryMode::LocalOnly);
         let context = QueryContext {
             prompt: enhanced_prompt,
             messages: structured_messages,
-            max_tokens: config.local_model.max_tokens,
-            temperature: config.local_model.temperature,
-            timeout: Duration::from_secs(config.performance.local_timeout_seconds),
+            tools: Vec::new(),
+            response_schema: None,
+            images: Vec::new(),
+            max_tokens: params.max_tokens,
+            temperature: params.temperature,
+            timeout: params.timeout,
             pure_mode: false,
         };
 
@@ -303,10 +494,11 @@ impl QueryProcessor {
         Ok(response)
     }
 
-    /// Force cloud model only
+    /// Force cloud model only, with `options` passed to the providers.
     pub async fn query_cloud_only(
         &self,
         prompt: &str,
+        options: &QueryOptions,
         cloud_providers: &[Arc<dyn ModelProvider>],
         memory_manager: &MemoryManager,
         config: &Config,
@@ -320,17 +512,21 @@ impl QueryProcessor {
         // Build enhanced prompt with context
         let enhanced_prompt = memory_manager.build_enhanced_prompt(prompt, &Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())), config).await?;
 
-        // Build structured prompt for local models (Prefix Caching) - Optional for cloud
-        let structured_messages = memory_manager.build_structured_prompt(prompt).await.ok();
+        // The same context as chat turns (system, history, question), which providers prefer
+        let structured_messages = memory_manager.build_structured_prompt(prompt, config).await.unwrap_or_default();
 
         info!("📝 Enhanced prompt length: {} characters", enhanced_prompt.len());
 
+        let params = config.mode_params(&QueryMode::CloudOnly);
         let context = QueryContext {
             prompt: enhanced_prompt,
             messages: structured_messages,
-            max_tokens: 1000, // Use higher limit for cloud
-            temperature: 0.7,
-            timeout: Duration::from_secs(30),
+            tools: options.tools.clone(),
+            response_schema: options.response_schema.clone(),
+            images: Vec::new(),
+            max_tokens: params.max_tokens,
+            temperature: params.temperature,
+            timeout: params.timeout,
             pure_mode: false,
         };
 
@@ -358,12 +554,16 @@ impl QueryProcessor {
         let enhanced_prompt = memory_manager.build_enhanced_prompt(prompt, &Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())), config).await?;
         info!("📝 Enhanced prompt length: {} characters", enhanced_prompt.len());
 
+        let params = config.mode_params(&QueryMode::PureLocal);
         let context = QueryContext {
             prompt: enhanced_prompt,
-            messages: None, // pure_mode doesn't use structured caching yet
-            max_tokens: config.local_model.max_tokens,
-            temperature: config.local_model.temperature,
-            timeout: Duration::from_secs(config.performance.local_timeout_seconds),
+            messages: Vec::new(), // pure_mode doesn't use structured caching yet
+            tools: Vec::new(),
+            response_schema: None,
+            images: Vec::new(),
+            max_tokens: params.max_tokens,
+            temperature: params.temperature,
+            timeout: params.timeout,
             pure_mode: true,
         };
 
@@ -375,19 +575,11 @@ impl QueryProcessor {
             return Err(anyhow!("No cloud providers available"));
         }
 
-        // Sort providers by quality score and availability
-        let mut available_providers: Vec<_> = cloud_providers.iter()
-            .filter(|p| p.is_available())
-            .collect();
-
+        let available_providers = crate::models::rank_providers(cloud_providers);
         if available_providers.is_empty() {
             return Err(anyhow!("No cloud providers are available (check API keys)"));
         }
 
-        available_providers.sort_by(|a, b|
-            b.quality_score().partial_cmp(&a.quality_score()).unwrap_or(std::cmp::Ordering::Equal)
-        );
-
         // Try top 2 providers in parallel for faster response
         if available_providers.len() >= 2 {
             let provider1 = available_providers[0].clone();
@@ -434,20 +626,28 @@ impl QueryProcessor {
         Err(anyhow!("All cloud providers failed"))
     }
 
-    /// Try a provider with exponential backoff retry logic
+    /// Try a provider with exponential backoff retry logic. Auth and request errors fail at once,
+    /// and a rate limit is waited out only when its Retry-After is short; otherwise the next
+    /// provider is tried instead.
     async fn try_provider_with_retry(&self, provider: &Arc<dyn ModelProvider>, context: &QueryContext) -> Result<ModelResponse> {
         let max_retries = 3;
-        let mut delay_ms = 1000; // Start with 1 second
+        let mut delay = Duration::from_millis(1000); // Start with 1 second
 
         for attempt in 0..max_retries {
             match provider.generate(context).await {
                 Ok(response) => return Ok(response),
                 Err(e) => {
+                    let wait = match ProviderError::of(&e) {
+                        Some(error) if !error.is_retryable() => return Err(e),
+                        Some(ProviderError::RateLimited { retry_after: Some(after), .. }) if *after <= MAX_RETRY_AFTER => *after,
+                        Some(ProviderError::RateLimited { .. }) => return Err(e),
+                        _ => delay,
+                    };
                     if attempt < max_retries - 1 {
                         warn!("⚠️  {} attempt {} failed: {}. Retrying in {}ms...",
-                              provider.name(), attempt + 1, e, delay_ms);
-                        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                        delay_ms *= 2; // Exponential backoff
+                              provider.name(), attempt + 1, e, wait.as_millis());
+                        tokio::time::sleep(wait).await;
+                        delay *= 2; // Exponential backoff
                     } else {
                         return Err(e);
                     }
@@ -469,8 +669,10 @@ impl QueryProcessor {
                         content: format!("⚠️  Service temporarily unavailable. Here's a similar response from our conversation history:\n\n{}", ai_response),
                         model_used: "Fallback-Cache".to_string(),
                         tokens_used: 0,
+                        input_tokens: 0,
                         response_time_ms: 0,
                         confidence_score: Some(0.5),
+                        tool_calls: Vec::new(),
                     });
                 }
             }
@@ -478,11 +680,13 @@ impl QueryProcessor {
 
         // Default fallback response
         Ok(ModelResponse {
-            content: format!("⚠️  I'm currently experiencing connectivity issues. Please try again in a moment.\n\nYour query was: '{}'\n\nFor urgent matters, you can also try:\n• Using 'mode local' to force local processing\n• Checking your internet connection\n• Verifying API keys in your configuration", prompt),
+            content: format!("⚠️  I'm currently experiencing connectivity issues. Please try again in a moment.\n\nYour query was: '{}'\n\nFor urgent matters, you can also try:\n• Using 'air --mode local' to force local processing\n• Checking your internet connection\n• Verifying API keys in your configuration", prompt),
             model_used: "Fallback-Default".to_string(),
             tokens_used: 0,
+            input_tokens: 0,
             response_time_ms: 0,
             confidence_score: Some(0.1),
+            tool_calls: Vec::new(),
         })
     }
 
@@ -513,3 +717,87 @@ impl QueryProcessor {
         response.content.contains("I don't know")
     }
 }
+
+/// Longest Retry-After worth waiting for before moving on to another provider.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// The reviewer replies with exactly this when the draft needs no changes.
+const APPROVAL: &str = "LGTM";
+
+fn critique_prompt(question: &str, observations: &str, draft: &str) -> String {
+    let observations = if observations.trim().is_empty() { "(no tools were used)" } else { observations.trim() };
+    format!(
+        "Review a draft answer.\n\nQuestion:\n{}\n\nTool observations:\n{}\n\nDraft answer:\n{}\n\n\
+         List any factual errors, claims the observations don't support, and parts of the question the draft misses. \
+         If the draft is correct and complete, reply with exactly {}.",
+        question, observations, draft.trim(), APPROVAL
+    )
+}
+
+fn revision_prompt(question: &str, observations: &str, draft: &str, critique: &str) -> String {
+    format!(
+        "Question:\n{}\n\nTool observations:\n{}\n\nDraft answer:\n{}\n\nA reviewer found these problems:\n{}\n\n\
+         Write the corrected final answer to the question. Reply with the answer only.",
+        question, observations.trim(), draft.trim(), critique.trim()
+    )
+}
+
+/// The canned answers given when every provider fails ("Fallback-Cache", "Fallback-Default")
+/// are no critique or revision, so they count as failures.
+fn answered(response: Result<ModelResponse>) -> Result<ModelResponse> {
+    match response {
+        Ok(response) if response.model_used.starts_with("Fallback-") => Err(anyhow!("No provider answered ({})", response.model_used)),
+        other => other,
+    }
+}
+
+fn is_approval(critique: &str) -> bool {
+    // Routed critiques start with the "🏠 Local Model Response:" or "☁️  <Provider> Response:" label
+    let critique = critique.trim_start();
+    let critique = match critique.split_once('\n') {
+        Some((label, rest)) if (label.starts_with("🏠") || label.starts_with("☁️")) && label.trim_end().ends_with("Response:") => rest,
+        _ => critique,
+    };
+    critique.trim().trim_matches(|c: char| !c.is_alphanumeric()).eq_ignore_ascii_case(APPROVAL)
+}
+
+/// Labels the query being processed (the first call in a query sets the label that is stored
+/// and used for routing) and returns this prompt's label.
+fn classify_query(prompt: &str) -> Classification {
+    let classification = classifier::classify(prompt);
+    debug!("🏷️  Query classified as {} ({})", classification.intent, classification.complexity);
+    usage::note_classification(&classification);
+    classification
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_approval() {
+        assert!(is_approval("LGTM"));
+        assert!(is_approval("  **lgtm.**\n"));
+        assert!(is_approval("🏠 Local Model Response:\nLGTM"));
+        assert!(is_approval("☁️  Gemini Response:\nLGTM"));
+        assert!(!is_approval("☁️  Gemini Response:\nThe second step is wrong"));
+        assert!(!is_approval("Not LGTM: the date is wrong"));
+    }
+
+    #[test]
+    fn test_fallback_answers_are_not_reviews() {
+        let response = |model_used: &str| ModelResponse {
+            content: "LGTM".to_string(),
+            model_used: model_used.to_string(),
+            tokens_used: 0,
+            input_tokens: 0,
+            response_time_ms: 0,
+            confidence_score: None,
+            tool_calls: Vec::new(),
+        };
+        assert!(answered(Ok(response("gemini-2.0-flash"))).is_ok());
+        assert!(answered(Ok(response("Fallback-Default"))).is_err());
+        assert!(answered(Ok(response("Fallback-Cache"))).is_err());
+        assert!(answered(Err(anyhow!("timeout"))).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::agent::memory::Conversation;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Bumped when the layout changes incompatibly; older snapshots are rejected rather than misread.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything a running agent has accumulated in its session, returned by `AIAgent::snapshot()`
+/// and accepted by `AIAgent::restore()` so a session can continue in another process or after
+/// a crash. Loaded models and provider connections are not included; they are rebuilt from config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSnapshot {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    /// The session the state was taken from.
+    pub session_id: String,
+    /// This session's conversation history, oldest first.
+    pub conversations: Vec<Conversation>,
+    /// Session key/value memory.
+    pub session_memory: Vec<(String, String)>,
+    /// Per-tool state (planner tasks, web cache, working directory...), keyed by tool name.
+    pub tools: HashMap<String, serde_json::Value>,
+    pub prompt_cache: Vec<CachedPrompt>,
+}
+
+/// An enhanced prompt from the agent's prompt cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPrompt {
+    pub prompt: String,
+    pub enhanced: String,
+    /// How old the entry was when the snapshot was taken, so it still expires on time.
+    pub age_secs: u64,
+}
+
+impl AgentSnapshot {
+    /// Writes the snapshot as JSON, replacing `path` atomically so a crash mid-write leaves the
+    /// previous snapshot intact.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let snapshot: Self = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| anyhow!("Invalid snapshot {}: {}", path.display(), e))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(anyhow!("Snapshot {} has version {}, expected {}", path.display(), snapshot.version, SNAPSHOT_VERSION));
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_round_trips_through_a_file() {
+        let snapshot = AgentSnapshot {
+            version: SNAPSHOT_VERSION,
+            created_at: Utc::now(),
+            session_id: "session".to_string(),
+            conversations: vec![Conversation {
+                id: 1,
+                user_input: "hi".to_string(),
+                ai_response: "hello".to_string(),
+                timestamp: "2024-01-01 00:00:00".to_string(),
+                context: None,
+                tools_used: Some("calculator".to_string()),
+            }],
+            session_memory: vec![("key".to_string(), "value".to_string())],
+            tools: HashMap::from([("command".to_string(), serde_json::json!({ "cwd": "/tmp" }))]),
+            prompt_cache: vec![CachedPrompt { prompt: "p".to_string(), enhanced: "e".to_string(), age_secs: 5 }],
+        };
+        let path = std::env::temp_dir().join(format!("air-snapshot-{}.json", uuid::Uuid::new_v4()));
+        snapshot.save(&path).unwrap();
+        let loaded = AgentSnapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.session_id, "session");
+        assert_eq!(loaded.conversations[0].tools_used.as_deref(), Some("calculator"));
+        assert_eq!(loaded.session_memory, snapshot.session_memory);
+        assert_eq!(loaded.tools["command"]["cwd"], "/tmp");
+        assert_eq!(loaded.prompt_cache[0].age_secs, 5);
+    }
+}
@@ -0,0 +1,141 @@
+//! Attributes model calls to the query that made them, so cost reports can tell tool-heavy
+//! queries from plain ones and break spend down by intent. Each query is also kept as a
+//! transcript turn (prompt, answer and tool calls) for `air share`.
+
+use crate::agent::classifier::Classification;
+use crate::agent::memory::{MemoryManager, ToolCallRecord};
+use crate::models::ModelResponse;
+use crate::tools::ToolResult;
+use crate::tools::command::truncate_output;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU32, Ordering};
+use tracing::debug;
+
+/// Longest prompt and answer kept in a transcript turn.
+const MAX_TURN_CHARS: usize = 50_000;
+/// Longest tool result kept in a transcript turn.
+const MAX_TOOL_OUTPUT_CHARS: usize = 2_000;
+
+tokio::task_local! {
+    static QUERY: Arc<QueryScope>;
+}
+
+struct QueryScope {
+    id: String,
+    tool_calls: AtomicU32,
+    classification: OnceLock<Classification>,
+    tool_log: Mutex<Vec<ToolCallRecord>>,
+}
+
+/// Runs one user query, tagging the model calls made inside it and recording how many
+/// tools it used, and its transcript, once it finishes. Nested calls count towards the
+/// outermost query.
+pub async fn track<F>(memory: &MemoryManager, prompt: &str, query: F) -> Result<ModelResponse>
+where
+    F: Future<Output = Result<ModelResponse>>,
+{
+    if QUERY.try_with(|_| ()).is_ok() {
+        return query.await;
+    }
+
+    let scope = Arc::new(QueryScope {
+        id: uuid::Uuid::new_v4().to_string(),
+        tool_calls: AtomicU32::new(0),
+        classification: OnceLock::new(),
+        tool_log: Mutex::new(Vec::new()),
+    });
+    let output = QUERY.scope(scope.clone(), query).await;
+    if let Err(e) = memory.record_query(&scope.id, scope.tool_calls.load(Ordering::Relaxed), scope.classification.get()).await {
+        debug!("Failed to record query: {}", e);
+    }
+
+    let tool_log = std::mem::take(&mut *scope.tool_log.lock().unwrap());
+    let error = output.as_ref().err().map(|e| format!("{:#}", e));
+    let response = output.as_ref().ok().map(|r| ModelResponse {
+        content: truncate_output(&r.content, MAX_TURN_CHARS),
+        ..r.clone()
+    });
+    let prompt = truncate_output(prompt, MAX_TURN_CHARS);
+    if let Err(e) = memory.record_turn(&scope.id, &prompt, response.as_ref(), error.as_deref(), &tool_log).await {
+        debug!("Failed to record transcript: {}", e);
+    }
+    output
+}
+
+/// The query the current task is working on, if any.
+pub fn current_query_id() -> Option<String> {
+    QUERY.try_with(|scope| scope.id.clone()).ok()
+}
+
+/// Counts a finished tool call towards the current query and adds it to its transcript.
+pub fn note_tool_call(tool: &str, function: &str, args: &serde_json::Value, result: &Result<ToolResult>) {
+    QUERY.try_with(|scope| {
+        scope.tool_calls.fetch_add(1, Ordering::Relaxed);
+        let (success, output) = match result {
+            Ok(r) => (r.success, match &r.result {
+                serde_json::Value::String(text) => text.clone(),
+                value => value.to_string(),
+            }),
+            Err(e) => (false, format!("{:#}", e)),
+        };
+        scope.tool_log.lock().unwrap().push(ToolCallRecord {
+            tool: tool.to_string(),
+            function: function.to_string(),
+            args: args.clone(),
+            success,
+            output: truncate_output(&output, MAX_TOOL_OUTPUT_CHARS),
+        });
+    }).ok();
+}
+
+/// Labels the current query; the first label wins, which is the one for the user's own prompt
+/// rather than the follow-up prompts of the ReAct loop.
+pub fn note_classification(classification: &Classification) {
+    QUERY.try_with(|scope| scope.classification.set(classification.clone())).ok();
+}
+
+/// The label of the current query, if it has been classified.
+pub fn current_classification() -> Option<Classification> {
+    QUERY.try_with(|scope| scope.classification.get().cloned()).ok().flatten()
+}
+
+/// Parses a report window start: a relative span such as "7d", "24h", "2w" or "30m",
+/// or a date ("2025-03-01").
+pub fn parse_since(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+
+    let unit_at = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (amount, unit) = input.split_at(unit_at);
+    let amount: i64 = amount.parse()
+        .map_err(|_| anyhow!("Invalid --since '{}': use e.g. 7d, 24h, 2w or 2025-03-01", input))?;
+    let span = match unit {
+        "m" | "min" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        "w" => Duration::weeks(amount),
+        _ => return Err(anyhow!("Invalid --since unit '{}': use m, h, d or w", unit)),
+    };
+    Ok(now - span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_since() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 10, 12, 0, 0).unwrap();
+        assert_eq!(parse_since("7d", now).unwrap(), Utc.with_ymd_and_hms(2025, 3, 3, 12, 0, 0).unwrap());
+        assert_eq!(parse_since("24h", now).unwrap(), Utc.with_ymd_and_hms(2025, 3, 9, 12, 0, 0).unwrap());
+        assert_eq!(parse_since("2025-03-01", now).unwrap(), Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap());
+        assert!(parse_since("7 days", now).is_err());
+        assert!(parse_since("d", now).is_err());
+    }
+}
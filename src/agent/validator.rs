@@ -0,0 +1,250 @@
+//! Checks a model's answer before it is returned. When a check fails the agent re-prompts with
+//! the complaint (see `AIAgent::query_validated`), so callers that need machine-readable output
+//! get it or a clear error instead of an answer they can't use.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+const RUSTC_TIMEOUT: Duration = Duration::from_secs(60);
+/// Compiler output beyond this is cut; the first errors are the useful ones.
+const MAX_ERROR_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Validator {
+    /// The answer (or its first ```json block) parses as JSON.
+    Json,
+    /// Like `Json`, and the value matches `schema` (type, properties, required, items, enum,
+    /// additionalProperties).
+    JsonSchema { schema: Value },
+    /// The answer's first ```rust block (or the whole answer) compiles as a library crate.
+    Rust,
+    /// The answer matches the regular expression.
+    Regex { pattern: String },
+}
+
+impl Validator {
+    /// Parses a command-line spec: `json`, `rust`, `regex:<pattern>` or `schema:<file.json>`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let validator = match spec.split_once(':') {
+            None if spec == "json" => Validator::Json,
+            None if spec == "rust" => Validator::Rust,
+            Some(("regex", pattern)) => Validator::Regex { pattern: pattern.to_string() },
+            Some(("schema", path)) => {
+                let text = std::fs::read_to_string(path).map_err(|e| anyhow!("Can't read schema {}: {}", path, e))?;
+                let schema = serde_json::from_str(&text).map_err(|e| anyhow!("Schema {} is not JSON: {}", path, e))?;
+                Validator::JsonSchema { schema }
+            }
+            _ => return Err(anyhow!("Unknown validator '{}': use json, rust, regex:<pattern> or schema:<file.json>", spec)),
+        };
+        if let Validator::Regex { pattern } = &validator {
+            regex::Regex::new(pattern).map_err(|e| anyhow!("Invalid regex: {}", e))?;
+        }
+        Ok(validator)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Validator::Json => "json",
+            Validator::JsonSchema { .. } => "schema",
+            Validator::Rust => "rust",
+            Validator::Regex { .. } => "regex",
+        }
+    }
+
+    /// `Err` holds what is wrong, worded for the model to fix.
+    pub async fn check(&self, output: &str) -> std::result::Result<(), String> {
+        match self {
+            Validator::Json => parse_json(output).map(|_| ()),
+            Validator::JsonSchema { schema } => check_schema(&parse_json(output)?, schema, "$"),
+            Validator::Rust => compile_rust(extract_block(output, &["rust", "rs"])).await,
+            Validator::Regex { pattern } => {
+                let regex = regex::Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+                if regex.is_match(output) {
+                    Ok(())
+                } else {
+                    Err(format!("The answer must match the regular expression {}", pattern))
+                }
+            }
+        }
+    }
+}
+
+/// The prompt asking the model to fix `answer`.
+pub fn repair_prompt(prompt: &str, answer: &str, validator: &Validator, error: &str) -> String {
+    format!(
+        "{}\n\nYour previous answer was:\n{}\n\nIt failed the {} check: {}\nReply with the corrected answer only.",
+        prompt, answer.trim(), validator.name(), error
+    )
+}
+
+/// `prompt` with the instruction to answer in JSON following `schema`, for models without a
+/// JSON mode (and as a reminder for those with one).
+pub fn json_prompt(prompt: &str, schema: &Value) -> String {
+    format!(
+        "{}\n\nAnswer with only a JSON value, no other text, that matches this JSON schema:\n{}",
+        prompt,
+        serde_json::to_string_pretty(schema).unwrap_or_default()
+    )
+}
+
+/// The body of the first fenced block tagged with one of `langs` (or untagged), else the whole text.
+fn extract_block<'a>(output: &'a str, langs: &[&str]) -> &'a str {
+    let mut rest = output;
+    while let Some(start) = rest.find("```") {
+        let after = &rest[start + 3..];
+        let (tag, body) = after.split_once('\n').unwrap_or((after, ""));
+        let Some(end) = body.find("```") else { break };
+        let tag = tag.trim();
+        if tag.is_empty() || langs.contains(&tag) {
+            return &body[..end];
+        }
+        rest = &body[end + 3..];
+    }
+    output.trim()
+}
+
+/// The answer's first ```json block (or the whole answer) as JSON. Text around a bare object or
+/// array, such as the provider label answers start with, is ignored.
+pub fn parse_json(output: &str) -> std::result::Result<Value, String> {
+    let text = extract_block(output, &["json"]).trim();
+    serde_json::from_str(text).or_else(|e| {
+        let start = text.find(['{', '[']).ok_or(e.to_string())?;
+        let end = text.rfind(['}', ']']).filter(|&end| end > start).ok_or(e.to_string())?;
+        serde_json::from_str(&text[start..=end]).map_err(|_| e.to_string())
+    }).map_err(|e| format!("The answer is not valid JSON: {}", e))
+}
+
+fn check_schema(value: &Value, schema: &Value, path: &str) -> std::result::Result<(), String> {
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            return Err(format!("{} should be {} but is {}", path, types.join(" or "), type_name(value)));
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            return Err(format!("{} must be one of {}", path, Value::Array(allowed.clone())));
+        }
+    }
+    if let Value::Object(object) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !object.contains_key(key) {
+                    return Err(format!("{} is missing the required field \"{}\"", path, key));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, field) in object {
+            match properties.and_then(|p| p.get(key)) {
+                Some(field_schema) => check_schema(field, field_schema, &format!("{}.{}", path, key))?,
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{} has an unexpected field \"{}\"", path, key));
+                }
+                None => {}
+            }
+        }
+    }
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            check_schema(item, item_schema, &format!("{}[{}]", path, i))?;
+        }
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+async fn compile_rust(code: &str) -> std::result::Result<(), String> {
+    let dir = std::env::temp_dir().join(format!("air-validate-{}", uuid::Uuid::new_v4()));
+    let result = async {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+        let source = dir.join("answer.rs");
+        std::fs::write(&source, code).map_err(|e| format!("Can't write {}: {}", source.display(), e))?;
+
+        let run = tokio::process::Command::new("rustc")
+            .args(["--edition", "2021", "--crate-type", "lib", "--emit=metadata", "--error-format=short", "--out-dir"])
+            .arg(&dir)
+            .arg(&source)
+            .output();
+        let output = tokio::time::timeout(RUSTC_TIMEOUT, run).await
+            .map_err(|_| "rustc timed out".to_string())?
+            .map_err(|e| format!("Can't run rustc (is a Rust toolchain installed?): {}", e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let errors = String::from_utf8_lossy(&output.stderr).replace(&*source.to_string_lossy(), "answer.rs");
+        Err(format!("The code does not compile:\n{}", errors.chars().take(MAX_ERROR_CHARS).collect::<String>()))
+    }.await;
+    std::fs::remove_dir_all(&dir).ok();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_json_validators() {
+        assert!(Validator::Json.check("Sure:\n```json\n{\"a\": 1}\n```").await.is_ok());
+        assert!(Validator::Json.check("{\"a\": 1").await.is_err());
+        assert_eq!(parse_json("☁️  OpenAI Response:\n{\"a\": [1]}").unwrap(), json!({"a": [1]}));
+
+        let schema = Validator::JsonSchema { schema: json!({
+            "type": "object",
+            "required": ["name", "tags"],
+            "properties": {
+                "name": { "type": "string" },
+                "tags": { "type": "array", "items": { "enum": ["a", "b"] } }
+            },
+            "additionalProperties": false
+        }) };
+        assert!(schema.check(r#"{"name": "x", "tags": ["a"]}"#).await.is_ok());
+        assert_eq!(schema.check(r#"{"name": "x"}"#).await.unwrap_err(), "$ is missing the required field \"tags\"");
+        assert_eq!(schema.check(r#"{"name": 1, "tags": []}"#).await.unwrap_err(), "$.name should be string but is number");
+        assert!(schema.check(r#"{"name": "x", "tags": ["c"]}"#).await.unwrap_err().starts_with("$.tags[0] must be one of"));
+        assert!(schema.check(r#"{"name": "x", "tags": [], "extra": 1}"#).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_regex_and_parse() {
+        let validator = Validator::parse(r"regex:^\d{4}-\d{2}-\d{2}$").unwrap();
+        assert!(validator.check("2024-05-01").await.is_ok());
+        assert!(validator.check("May 1st").await.is_err());
+        assert_eq!(Validator::parse("json").unwrap(), Validator::Json);
+        assert!(Validator::parse("regex:(").is_err());
+        assert!(Validator::parse("yaml").is_err());
+    }
+
+    #[test]
+    fn test_extract_block() {
+        let answer = "Here:\n```toml\nx = 1\n```\n```rust\nfn f() {}\n```";
+        assert_eq!(extract_block(answer, &["rust"]), "fn f() {}\n");
+        assert_eq!(extract_block("fn g() {}", &["rust"]), "fn g() {}");
+    }
+}
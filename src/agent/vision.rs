@@ -0,0 +1,63 @@
+//! Screenshot analysis through the agent's cloud providers. Every cloud provider AIR supports
+//! takes inline images, so the screenshot tool asks them in order until one describes it.
+
+use crate::models::{ImageInput, ModelProvider, QueryContext};
+use crate::tools::VisionAnalyzer;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+const DESCRIPTION_MAX_TOKENS: u32 = 1024;
+const DESCRIPTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct ProviderVision {
+    providers: Vec<Arc<dyn ModelProvider>>,
+}
+
+impl ProviderVision {
+    /// `None` when there are no cloud providers to look at images.
+    pub fn new(cloud_providers: &[Arc<dyn ModelProvider>]) -> Option<Arc<dyn VisionAnalyzer>> {
+        if cloud_providers.is_empty() {
+            return None;
+        }
+        Some(Arc::new(Self { providers: cloud_providers.to_vec() }))
+    }
+}
+
+#[async_trait]
+impl VisionAnalyzer for ProviderVision {
+    async fn describe_image(&self, image_base64: &str, mime_type: &str, prompt: &str) -> Result<String> {
+        let context = QueryContext {
+            prompt: prompt.to_string(),
+            messages: Vec::new(),
+            tools: Vec::new(),
+            response_schema: None,
+            images: vec![ImageInput::new(mime_type, image_base64)],
+            max_tokens: DESCRIPTION_MAX_TOKENS,
+            temperature: 0.2,
+            timeout: DESCRIPTION_TIMEOUT,
+            pure_mode: true,
+        };
+
+        let mut last_error = anyhow!("No cloud provider is available to analyze the image");
+        for provider in self.providers.iter().filter(|p| p.is_available()) {
+            match provider.generate(&context).await {
+                Ok(response) => return Ok(response.content),
+                Err(e) => {
+                    warn!("{} couldn't describe the image: {}", provider.name(), e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    fn name(&self) -> &str {
+        self.providers.iter()
+            .find(|p| p.is_available())
+            .unwrap_or(&self.providers[0])
+            .name()
+    }
+}
@@ -0,0 +1,211 @@
+//! Batch prompt processing (`air batch`): runs many prompts through one agent and writes
+//! a JSON result per line.
+
+use crate::agent::AIAgent;
+use crate::models::ModelResponse;
+use anyhow::{Result, anyhow};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// Prompts in flight at once.
+    pub concurrency: usize,
+    /// Extra attempts after a failed query, with exponential backoff.
+    pub retries: u32,
+    /// Skip the tool loop and ask the model directly.
+    pub no_tools: bool,
+}
+
+/// One prompt from the input file. Plain lines become `{ prompt }`; JSON lines may carry an id.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchPrompt {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub prompt: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub prompt: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_used: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub attempts: u32,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Parses the prompt file: one prompt per line, or JSON objects with `prompt` (and optional `id`).
+/// Blank lines and lines starting with `#` are skipped.
+pub fn parse_prompts(content: &str) -> Result<Vec<BatchPrompt>> {
+    let mut prompts = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('{') {
+            let prompt: BatchPrompt = serde_json::from_str(line)
+                .map_err(|e| anyhow!("Line {}: invalid JSON prompt: {}", number + 1, e))?;
+            prompts.push(prompt);
+        } else {
+            prompts.push(BatchPrompt { id: None, prompt: line.to_string() });
+        }
+    }
+    Ok(prompts)
+}
+
+/// Runs every prompt, appending results to the output file as they finish (so an interrupted
+/// run keeps what completed). Results carry their input `index` since they may finish out of order.
+pub async fn run(agent: &AIAgent, options: &BatchOptions) -> Result<BatchSummary> {
+    let content = std::fs::read_to_string(&options.input)
+        .map_err(|e| anyhow!("Cannot read {}: {}", options.input.display(), e))?;
+    let prompts = parse_prompts(&content)?;
+    if prompts.is_empty() {
+        return Err(anyhow!("No prompts found in {}", options.input.display()));
+    }
+
+    let mut output = std::fs::File::create(&options.output)
+        .map_err(|e| anyhow!("Cannot create {}: {}", options.output.display(), e))?;
+    let mut summary = BatchSummary { total: prompts.len(), ..Default::default() };
+
+    let mut results = stream::iter(prompts.into_iter().enumerate())
+        .map(|(index, prompt)| run_one(agent, index, prompt, options))
+        .buffer_unordered(options.concurrency.max(1));
+
+    while let Some(result) = results.next().await {
+        if result.success {
+            summary.succeeded += 1;
+        } else {
+            summary.failed += 1;
+        }
+        writeln!(output, "{}", serde_json::to_string(&result)?)?;
+        output.flush()?;
+
+        let done = summary.succeeded + summary.failed;
+        crate::jobs::report_progress(done as u64, Some(summary.total as u64), None);
+        eprintln!("[{}/{}] {} #{} ({} ms){}",
+            done, summary.total,
+            if result.success { "✅" } else { "❌" },
+            result.id.as_deref().map(str::to_string).unwrap_or_else(|| result.index.to_string()),
+            result.elapsed_ms,
+            result.error.as_deref().map(|e| format!(": {}", e)).unwrap_or_default());
+    }
+
+    Ok(summary)
+}
+
+async fn run_one(agent: &AIAgent, index: usize, prompt: BatchPrompt, options: &BatchOptions) -> BatchResult {
+    let start = Instant::now();
+    let mut attempts = 0;
+    let mut last_error = String::new();
+
+    while attempts <= options.retries {
+        if attempts > 0 {
+            tokio::time::sleep(Duration::from_secs(1 << (attempts - 1).min(5))).await;
+        }
+        attempts += 1;
+
+        let response = if options.no_tools {
+            agent.query_with_fallback(&prompt.prompt).await
+        } else {
+            agent.query_with_tools(&prompt.prompt).await
+        };
+        match answered(response) {
+            Ok(response) => {
+                return BatchResult {
+                    index,
+                    id: prompt.id,
+                    prompt: prompt.prompt,
+                    success: true,
+                    response: Some(response.content),
+                    model_used: Some(response.model_used),
+                    error: None,
+                    attempts,
+                    elapsed_ms: start.elapsed().as_millis() as u64,
+                };
+            }
+            Err(e) => last_error = e,
+        }
+    }
+
+    BatchResult {
+        index,
+        id: prompt.id,
+        prompt: prompt.prompt,
+        success: false,
+        response: None,
+        model_used: None,
+        error: Some(last_error),
+        attempts,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    }
+}
+
+/// When every provider fails the agent still answers, from the fallback strategies
+/// ("Fallback-Cache", "Fallback-Default"); those count as failures so they are retried.
+fn answered(response: Result<ModelResponse>) -> std::result::Result<ModelResponse, String> {
+    match response {
+        Ok(response) if response.model_used.starts_with("Fallback-") => {
+            Err(format!("No provider answered ({})", response.model_used))
+        }
+        Ok(response) => Ok(response),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_prompts() {
+        let content = "# eval set\nWhat is 2+2?\n\n{\"id\": \"q2\", \"prompt\": \"Capital of France?\"}\n";
+        let prompts = parse_prompts(content).unwrap();
+        assert_eq!(prompts, vec![
+            BatchPrompt { id: None, prompt: "What is 2+2?".to_string() },
+            BatchPrompt { id: Some("q2".to_string()), prompt: "Capital of France?".to_string() },
+        ]);
+
+        assert!(parse_prompts("{\"id\": \"missing prompt\"}").is_err());
+    }
+
+    fn response(model_used: &str) -> ModelResponse {
+        ModelResponse {
+            content: "answer".to_string(),
+            model_used: model_used.to_string(),
+            tokens_used: 0,
+            input_tokens: 0,
+            response_time_ms: 0,
+            confidence_score: None,
+            tool_calls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_fallback_answers_are_failures() {
+        assert!(answered(Ok(response("Gemini"))).is_ok());
+        assert!(answered(Ok(response("Fallback-Default"))).is_err());
+        assert!(answered(Ok(response("Fallback-Cache"))).is_err());
+        assert_eq!(answered(Err(anyhow!("timeout"))).unwrap_err(), "timeout");
+    }
+}
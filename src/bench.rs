@@ -0,0 +1,220 @@
+//! Provider benchmarking (`air bench`): runs a fixed prompt set against every provider and
+//! reports latency, throughput, failures, accuracy and cost side by side.
+
+use crate::config::Config;
+use crate::models::{ModelProvider, QueryContext};
+use futures::future::join_all;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A benchmark prompt; the answer counts as correct if it contains any of `expect` (case-insensitive).
+pub struct BenchPrompt {
+    pub prompt: &'static str,
+    pub expect: &'static [&'static str],
+}
+
+pub const STANDARD_PROMPTS: &[BenchPrompt] = &[
+    BenchPrompt { prompt: "What is 17 * 23? Reply with the number only.", expect: &["391"] },
+    BenchPrompt { prompt: "What is the capital of Australia? Reply with one word.", expect: &["canberra"] },
+    BenchPrompt { prompt: "Which planet is known as the Red Planet? Reply with one word.", expect: &["mars"] },
+    BenchPrompt {
+        prompt: "Write a Rust function `fn add(a: i32, b: i32) -> i32` that returns the sum. Reply with code only.",
+        expect: &["a + b", "a+b"],
+    },
+    BenchPrompt {
+        prompt: "If all bloops are razzies and all razzies are lazzies, are all bloops lazzies? Answer yes or no.",
+        expect: &["yes"],
+    },
+    BenchPrompt { prompt: "Translate 'good morning' into Spanish. Reply with the translation only.", expect: &["buenos días", "buenos dias"] },
+];
+
+#[derive(Debug, Clone)]
+pub struct ProviderReport {
+    pub provider: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub correct: usize,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub tokens_per_sec: f64,
+    /// None when the provider has no `cost_per_million_tokens`; the local model is free.
+    pub cost_usd: Option<f64>,
+    pub last_error: Option<String>,
+    /// p95 over everything the provider has served (persisted across runs), for comparison.
+    pub historical_p95_ms: Option<u64>,
+}
+
+impl ProviderReport {
+    pub fn failure_rate(&self) -> f64 {
+        if self.runs == 0 { 0.0 } else { self.failures as f64 / self.runs as f64 }
+    }
+
+    /// Share of all runs that answered correctly; failures count as wrong.
+    pub fn accuracy(&self) -> f64 {
+        if self.runs == 0 { 0.0 } else { self.correct as f64 / self.runs as f64 }
+    }
+}
+
+/// Benchmarks each provider concurrently; prompts run one after another within a provider so
+/// latencies aren't skewed by self-contention.
+pub async fn run(providers: &[Arc<dyn ModelProvider>], config: &Config, iterations: usize) -> Vec<ProviderReport> {
+    join_all(providers.iter().map(|provider| bench_provider(provider.as_ref(), config, iterations))).await
+}
+
+async fn bench_provider(provider: &dyn ModelProvider, config: &Config, iterations: usize) -> ProviderReport {
+    let cloud_config = config.cloud_providers.iter().find(|c| c.name.eq_ignore_ascii_case(provider.name()));
+    let timeout = match cloud_config {
+        Some(c) => Duration::from_secs(c.timeout_seconds),
+        None => Duration::from_secs(config.performance.local_timeout_seconds),
+    };
+
+    let mut latencies = Vec::new();
+    let mut report = ProviderReport {
+        provider: provider.name().to_string(),
+        runs: 0,
+        failures: 0,
+        correct: 0,
+        p50_ms: 0,
+        p90_ms: 0,
+        p99_ms: 0,
+        tokens_per_sec: 0.0,
+        cost_usd: None,
+        last_error: None,
+        historical_p95_ms: None,
+    };
+    let mut tokens = 0u64;
+    let mut generation_ms = 0u64;
+
+    for _ in 0..iterations.max(1) {
+        for bench in STANDARD_PROMPTS {
+            let context = QueryContext {
+                prompt: bench.prompt.to_string(),
+                messages: Vec::new(),
+                tools: Vec::new(),
+                response_schema: None,
+                images: Vec::new(),
+                max_tokens: 256,
+                temperature: 0.0,
+                timeout,
+                pure_mode: false,
+            };
+            report.runs += 1;
+            let start = Instant::now();
+            match tokio::time::timeout(timeout, provider.generate(&context)).await {
+                Ok(Ok(response)) => {
+                    let elapsed = start.elapsed().as_millis() as u64;
+                    latencies.push(elapsed);
+                    // Providers that don't report usage get the usual ~4 characters per token estimate
+                    let used = if response.tokens_used > 0 { response.tokens_used as u64 } else { (response.content.len() / 4) as u64 };
+                    tokens += used;
+                    generation_ms += elapsed;
+                    if is_correct(&response.content, bench.expect) {
+                        report.correct += 1;
+                    }
+                }
+                Ok(Err(e)) => {
+                    report.failures += 1;
+                    report.last_error = Some(e.to_string());
+                }
+                Err(_) => {
+                    report.failures += 1;
+                    report.last_error = Some(format!("timed out after {}s", timeout.as_secs()));
+                }
+            }
+        }
+    }
+
+    latencies.sort_unstable();
+    report.p50_ms = percentile(&latencies, 50.0);
+    report.p90_ms = percentile(&latencies, 90.0);
+    report.p99_ms = percentile(&latencies, 99.0);
+    if generation_ms > 0 {
+        report.tokens_per_sec = tokens as f64 * 1000.0 / generation_ms as f64;
+    }
+    report.historical_p95_ms = provider.metrics().and_then(|m| m.latency.p95());
+    report.cost_usd = match cloud_config {
+        Some(c) => c.cost_per_million_tokens.map(|price| tokens as f64 * price / 1_000_000.0),
+        None => Some(0.0),
+    };
+    report
+}
+
+fn is_correct(content: &str, expect: &[&str]) -> bool {
+    let content = content.to_lowercase();
+    expect.iter().any(|e| content.contains(&e.to_lowercase()))
+}
+
+/// Nearest-rank percentile of already sorted values (0 for an empty slice).
+pub fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Applies measured results to the routing settings of the matching cloud providers:
+/// `quality_score` becomes accuracy scaled by reliability, `latency_ms` the median latency.
+/// Returns the names of the providers updated.
+pub fn update_scores(config: &mut Config, reports: &[ProviderReport]) -> Vec<String> {
+    let mut updated = Vec::new();
+    for report in reports {
+        // Nothing measured, e.g. a missing API key; keep the previous scores
+        if report.runs == report.failures {
+            continue;
+        }
+        if let Some(provider) = config.cloud_providers.iter_mut().find(|c| c.name.eq_ignore_ascii_case(&report.provider)) {
+            // Failed runs already count as wrong answers in the accuracy
+            let score = report.accuracy();
+            provider.quality_score = Some((score * 100.0).round() as f32 / 100.0);
+            provider.latency_ms = Some(report.p50_ms);
+            updated.push(provider.name.clone());
+        }
+    }
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let values = [100, 200, 300, 400, 500, 600, 700, 800, 900, 1000];
+        assert_eq!(percentile(&values, 50.0), 500);
+        assert_eq!(percentile(&values, 90.0), 900);
+        assert_eq!(percentile(&values, 99.0), 1000);
+        assert_eq!(percentile(&[42], 50.0), 42);
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn test_is_correct() {
+        assert!(is_correct("The answer is 391.", &["391"]));
+        assert!(is_correct("Buenos Días!", &["buenos días", "buenos dias"]));
+        assert!(!is_correct("Sydney", &["canberra"]));
+    }
+
+    #[test]
+    fn test_failures_are_penalized_once() {
+        let mut config = Config::default();
+        let provider = config.cloud_providers[0].name.clone();
+        let report = ProviderReport {
+            provider: provider.clone(),
+            runs: 10,
+            failures: 2,
+            correct: 6,
+            p50_ms: 800,
+            p90_ms: 900,
+            p99_ms: 1000,
+            tokens_per_sec: 0.0,
+            cost_usd: None,
+            last_error: None,
+            historical_p95_ms: None,
+        };
+        assert_eq!(update_scores(&mut config, &[report]), vec![provider]);
+        assert_eq!(config.cloud_providers[0].quality_score, Some(0.6));
+        assert_eq!(config.cloud_providers[0].latency_ms, Some(800));
+    }
+}
@@ -0,0 +1,402 @@
+//! Guided code changes (`air apply`): the model proposes edits for a request in the filesystem
+//! tool's `old_text`/`new_text` form, each resulting hunk is shown per file for approval, and the
+//! accepted ones are written and recorded as a change set that `air changes rollback` can undo.
+
+use crate::agent::{AIAgent, QueryMode, Validator};
+use crate::rag::code::{self, CodeIndex};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use similar::{ChangeTag, TextDiff};
+use crate::utils::approval;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+/// Unchanged lines shown around each hunk; nearby changes closer than twice this form one hunk.
+const CONTEXT_LINES: usize = 3;
+/// Files sent to the model when none are named.
+const MAX_CONTEXT_FILES: usize = 5;
+const MAX_CONTEXT_BYTES: usize = 64 * 1024;
+const MAX_REPAIRS: u32 = 2;
+
+/// One replacement, as the model proposes it. An empty `old_text` creates the file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Edit {
+    pub path: String,
+    pub old_text: String,
+    pub new_text: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Proposal {
+    pub summary: String,
+    pub edits: Vec<Edit>,
+}
+
+/// A file's contents before and after all of its proposed edits.
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    /// As the model wrote it, relative to the root.
+    pub path: String,
+    pub full_path: PathBuf,
+    /// None for a file the change creates.
+    pub before: Option<String>,
+    pub after: String,
+}
+
+/// A contiguous run of changed lines with its context, ready to show.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+    /// `@@ -a,b +c,d @@` followed by the diff lines.
+    pub text: String,
+}
+
+/// Asks the model for edits implementing `request` against `files` (path, contents).
+pub async fn propose(agent: &AIAgent, mode: &QueryMode, request: &str, files: &[(String, String)]) -> Result<Proposal> {
+    let schema = json!({
+        "type": "object",
+        "required": ["summary", "edits"],
+        "properties": {
+            "summary": {"type": "string"},
+            "edits": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["path", "old_text", "new_text"],
+                    "properties": {
+                        "path": {"type": "string"},
+                        "old_text": {"type": "string"},
+                        "new_text": {"type": "string"}
+                    }
+                }
+            }
+        }
+    });
+    let response = agent.query_validated(&build_prompt(request, files), mode, false, &[Validator::JsonSchema { schema }], MAX_REPAIRS).await?;
+    let value = crate::agent::validator::parse_json(&response.content).map_err(|e| anyhow!(e))?;
+    Ok(serde_json::from_value(value)?)
+}
+
+pub fn build_prompt(request: &str, files: &[(String, String)]) -> String {
+    let mut prompt = format!("Make this change to the code: {}\n\nThe relevant files:\n", request);
+    for (path, contents) in files {
+        prompt.push_str(&format!("\n--- {}\n```\n{}\n```\n", path, contents.trim_end()));
+    }
+    prompt.push_str(
+        "\nAnswer with only a JSON object: {\"summary\": \"one sentence\", \"edits\": [{\"path\": \"relative/path\", \
+         \"old_text\": \"text copied exactly from the file\", \"new_text\": \"its replacement\"}]}.\n\
+         Each old_text must occur exactly once in its file, so include enough surrounding lines. \
+         Keep edits small and touch only what the change needs. \
+         To create a file, use an empty old_text and the whole file as new_text.",
+    );
+    prompt
+}
+
+/// The files to show the model: the named ones, or the best matches from the repository's
+/// code index.
+pub async fn context_files(root: &Path, named: &[PathBuf], request: &str) -> Result<Vec<(String, String)>> {
+    let paths: Vec<String> = if named.is_empty() {
+        let index = CodeIndex::open(root).await?;
+        index.update(false).await?;
+        let mut paths = Vec::new();
+        for (chunk, _) in index.search(request, MAX_CONTEXT_FILES * 3).await? {
+            if !paths.contains(&chunk.path) && paths.len() < MAX_CONTEXT_FILES {
+                paths.push(chunk.path);
+            }
+        }
+        paths
+    } else {
+        named.iter().map(|p| p.strip_prefix(root).unwrap_or(p).to_string_lossy().replace('\\', "/")).collect()
+    };
+
+    let mut files = Vec::new();
+    for path in paths {
+        let contents = std::fs::read_to_string(root.join(&path)).map_err(|e| anyhow!("Can't read {}: {}", path, e))?;
+        if contents.len() > MAX_CONTEXT_BYTES {
+            tracing::warn!("Leaving out {}: larger than {} KB", path, MAX_CONTEXT_BYTES / 1024);
+            continue;
+        }
+        files.push((path, contents));
+    }
+    Ok(files)
+}
+
+/// Applies the proposal's edits in memory, one `FileChange` per file in first-mention order.
+pub fn plan(root: &Path, edits: &[Edit]) -> Result<Vec<FileChange>> {
+    let mut changes: Vec<FileChange> = Vec::new();
+    for edit in edits {
+        let index = match changes.iter().position(|c| c.path == edit.path) {
+            Some(index) => index,
+            None => {
+                let full_path = resolve(root, &edit.path)?;
+                let before = std::fs::read_to_string(&full_path).ok();
+                let after = before.clone().unwrap_or_default();
+                changes.push(FileChange { path: edit.path.clone(), full_path, before, after });
+                changes.len() - 1
+            }
+        };
+        let change = &mut changes[index];
+        if edit.old_text.is_empty() {
+            if !change.after.is_empty() {
+                return Err(anyhow!("{} already exists; the edit to create it would overwrite it", edit.path));
+            }
+            change.after = edit.new_text.clone();
+            continue;
+        }
+        match change.after.matches(&edit.old_text).count() {
+            1 => change.after = change.after.replacen(&edit.old_text, &edit.new_text, 1),
+            0 => return Err(anyhow!("The proposed old_text was not found in {}:\n{}", edit.path, edit.old_text)),
+            n => return Err(anyhow!("The proposed old_text occurs {} times in {}:\n{}", n, edit.path, edit.old_text)),
+        }
+    }
+    changes.retain(|c| c.before.as_deref() != Some(c.after.as_str()));
+    Ok(changes)
+}
+
+/// `path` under `root`; absolute paths and `..` are refused so a change stays in the repository.
+fn resolve(root: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+    if relative.is_absolute() || relative.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(anyhow!("Refusing to edit {}: paths must stay inside {}", path, root.display()));
+    }
+    Ok(root.join(relative))
+}
+
+pub fn hunks(before: &str, after: &str) -> Vec<Hunk> {
+    let diff = TextDiff::from_lines(before, after);
+    diff.grouped_ops(CONTEXT_LINES).iter().filter_map(|group| {
+        let (first, last) = (group.first()?, group.last()?);
+        let old = first.old_range().start..last.old_range().end;
+        let new = first.new_range().start..last.new_range().end;
+        let mut text = format!("@@ -{},{} +{},{} @@\n", old.start + 1, old.len(), new.start + 1, new.len());
+        for op in group {
+            for change in diff.iter_changes(op) {
+                let sign = match change.tag() {
+                    ChangeTag::Delete => '-',
+                    ChangeTag::Insert => '+',
+                    ChangeTag::Equal => ' ',
+                };
+                text.push(sign);
+                text.push_str(change.value());
+                if change.missing_newline() {
+                    text.push('\n');
+                }
+            }
+        }
+        Some(Hunk { old, new, text })
+    }).collect()
+}
+
+/// `before` with only the accepted hunks (by index into `hunks(before, after)`) taken from `after`.
+pub fn merge(before: &str, after: &str, accepted: &[bool]) -> String {
+    let diff = TextDiff::from_lines(before, after);
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let mut merged = String::new();
+    let mut cursor = 0;
+    for (hunk, &take) in hunks(before, after).iter().zip(accepted) {
+        merged.extend(old[cursor..hunk.old.start].iter().copied());
+        let lines = if take { &new[hunk.new.clone()] } else { &old[hunk.old.clone()] };
+        merged.extend(lines.iter().copied());
+        cursor = hunk.old.end;
+    }
+    merged.extend(old[cursor..].iter().copied());
+    merged
+}
+
+/// Walks through every hunk asking for approval (or accepts all when `yes`) and returns the
+/// files with their approved contents; files with nothing approved are left out.
+pub fn review(changes: Vec<FileChange>, yes: bool) -> Vec<FileChange> {
+    let mut approved = Vec::new();
+    let mut quit = false;
+    for mut change in changes {
+        if quit {
+            break;
+        }
+        let before = change.before.clone().unwrap_or_default();
+        let file_hunks = hunks(&before, &change.after);
+        let label = if change.before.is_none() { "new file" } else { "modified" };
+        println!("\n📝 {} ({}, {} hunk{})", change.path, label, file_hunks.len(), if file_hunks.len() == 1 { "" } else { "s" });
+
+        let mut accepted = vec![yes; file_hunks.len()];
+        // Set by "all", "skip" and "quit" for the hunks still to come in this file
+        let mut rest: Option<bool> = yes.then_some(true);
+        for (i, hunk) in file_hunks.iter().enumerate() {
+            if let Some(take) = rest {
+                accepted[i] = take;
+                continue;
+            }
+            print!("{}", hunk.text);
+            match approval::choice("Apply this hunk? [y]es/[n]o/[a]ll in file/[s]kip file/[q]uit") {
+                'y' => accepted[i] = true,
+                'a' => { accepted[i] = true; rest = Some(true); }
+                's' => rest = Some(false),
+                'q' => { quit = true; rest = Some(false); }
+                _ => {}
+            }
+        }
+
+        if accepted.iter().any(|&a| a) {
+            change.after = merge(&before, &change.after, &accepted);
+            approved.push(change);
+        }
+    }
+    approved
+}
+
+/// What one file looked like before and after an applied change set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub path: PathBuf,
+    /// None when the change set created the file.
+    pub before: Option<String>,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeSet {
+    pub id: String,
+    pub request: String,
+    pub summary: String,
+    pub created: DateTime<Utc>,
+    pub files: Vec<FileRecord>,
+    #[serde(default)]
+    pub rolled_back: Option<DateTime<Utc>>,
+}
+
+impl ChangeSet {
+    /// Writes the approved files and records the change set.
+    pub fn apply(request: &str, summary: &str, changes: Vec<FileChange>) -> Result<Self> {
+        let mut files = Vec::new();
+        for change in changes {
+            if let Some(parent) = change.full_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&change.full_path, &change.after)
+                .map_err(|e| anyhow!("Failed to write {}: {}", change.full_path.display(), e))?;
+            files.push(FileRecord { path: change.full_path, before: change.before, after: change.after });
+        }
+        let created = Utc::now();
+        let set = Self {
+            id: created.format("%Y%m%d-%H%M%S").to_string(),
+            request: request.to_string(),
+            summary: summary.to_string(),
+            created,
+            files,
+            rolled_back: None,
+        };
+        set.save()?;
+        Ok(set)
+    }
+
+    fn dir() -> Result<PathBuf> {
+        let dir = crate::utils::paths::get_air_memory_dir()?.join("changes");
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    fn save(&self) -> Result<()> {
+        std::fs::write(Self::dir()?.join(format!("{}.json", self.id)), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Recorded change sets, newest first.
+    pub fn list() -> Result<Vec<Self>> {
+        let mut sets: Vec<Self> = std::fs::read_dir(Self::dir()?)?
+            .flatten()
+            .filter(|e| e.path().extension().is_some_and(|x| x == "json"))
+            .filter_map(|e| serde_json::from_str(&std::fs::read_to_string(e.path()).ok()?).ok())
+            .collect();
+        sets.sort_by(|a, b| b.created.cmp(&a.created));
+        Ok(sets)
+    }
+
+    /// The change set with `id`, or the newest one not yet rolled back.
+    pub fn find(id: Option<&str>) -> Result<Self> {
+        let sets = Self::list()?;
+        match id {
+            Some(id) => sets.into_iter().find(|s| s.id == id).ok_or_else(|| anyhow!("No change set '{}'", id)),
+            None => sets.into_iter().find(|s| s.rolled_back.is_none()).ok_or_else(|| anyhow!("No change set to roll back")),
+        }
+    }
+
+    /// Files edited since the change set was applied; rolling back would lose those edits.
+    pub fn conflicts(&self) -> Vec<&Path> {
+        self.files.iter()
+            .filter(|f| std::fs::read_to_string(&f.path).ok().as_deref() != Some(f.after.as_str()))
+            .map(|f| f.path.as_path())
+            .collect()
+    }
+
+    /// Restores every file (deleting the ones the change set created). Refuses when a file
+    /// changed since, unless `force`.
+    pub fn rollback(&mut self, force: bool) -> Result<()> {
+        if self.rolled_back.is_some() {
+            return Err(anyhow!("Change set {} was already rolled back", self.id));
+        }
+        let conflicts = self.conflicts();
+        if !conflicts.is_empty() && !force {
+            let list: Vec<String> = conflicts.iter().map(|p| format!("  {}", p.display())).collect();
+            return Err(anyhow!("These files changed since {} was applied (use --force to overwrite):\n{}", self.id, list.join("\n")));
+        }
+        for file in self.files.iter().rev() {
+            match &file.before {
+                Some(before) => std::fs::write(&file.path, before)?,
+                None => {
+                    if file.path.exists() {
+                        std::fs::remove_file(&file.path)?;
+                    }
+                }
+            }
+        }
+        self.rolled_back = Some(Utc::now());
+        self.save()
+    }
+}
+
+/// The repository root for a change: the git checkout, else the current directory.
+pub fn root() -> Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    Ok(code::repository_root(&cwd).unwrap_or(cwd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEFORE: &str = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+    const AFTER: &str = "A\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nL\n";
+
+    #[test]
+    fn test_hunks_and_merge() {
+        let found = hunks(BEFORE, AFTER);
+        assert_eq!(found.len(), 2);
+        assert!(found[0].text.starts_with("@@ -1,4 +1,4 @@\n-a\n+A\n b\n"));
+        assert_eq!(merge(BEFORE, AFTER, &[true, true]), AFTER);
+        assert_eq!(merge(BEFORE, AFTER, &[false, false]), BEFORE);
+        assert_eq!(merge(BEFORE, AFTER, &[false, true]), BEFORE.replace('l', "L"));
+        assert_eq!(merge("", "new\n", &[true]), "new\n");
+    }
+
+    #[test]
+    fn test_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn one() {}\nfn two() {}\n").unwrap();
+        let edit = |path: &str, old: &str, new: &str| Edit { path: path.into(), old_text: old.into(), new_text: new.into() };
+
+        let changes = plan(dir.path(), &[
+            edit("lib.rs", "fn one() {}", "fn uno() {}"),
+            edit("lib.rs", "fn two() {}", "fn dos() {}"),
+            edit("src/new.rs", "", "pub fn new() {}\n"),
+        ]).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].after, "fn uno() {}\nfn dos() {}\n");
+        assert!(changes[1].before.is_none());
+
+        assert!(plan(dir.path(), &[edit("lib.rs", "fn three", "")]).is_err());
+        assert!(plan(dir.path(), &[edit("../outside.rs", "", "x")]).is_err());
+        assert!(plan(dir.path(), &[edit("lib.rs", "", "x")]).is_err());
+    }
+}
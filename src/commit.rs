@@ -0,0 +1,121 @@
+//! `air commit`: writes a conventional-commit message for the staged changes, read through the
+//! git tool, and commits with it once the user approves or edits it.
+
+use crate::agent::{AIAgent, QueryMode, Validator};
+use crate::tools::{GitTool, Tool};
+use anyhow::{Result, anyhow};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+
+/// Diff text sent to the model; bigger diffs keep their head and tail, and the stat covers the rest.
+const MAX_DIFF_CHARS: usize = 24_000;
+/// Recent subjects shown so the message matches the project's style.
+const RECENT_COMMITS: u64 = 10;
+const CONVENTIONAL_HEADER: &str = r"(?m)^(feat|fix|docs|style|refactor|perf|test|build|ci|chore|revert)(\([^)\n]+\))?!?: \S";
+const MESSAGE_FILE: &str = "AIR_COMMIT_EDITMSG";
+
+/// What the message is written from.
+#[derive(Debug, Clone)]
+pub struct StagedChanges {
+    pub stat: String,
+    pub diff: String,
+    pub recent_subjects: Vec<String>,
+}
+
+impl StagedChanges {
+    /// Reads the staged diff and recent history; errors when nothing is staged.
+    pub async fn read(git: &GitTool) -> Result<Self> {
+        let diff = call(git, "diff", json!({"staged": true, "max_chars": MAX_DIFF_CHARS})).await?;
+        if diff["empty"].as_bool().unwrap_or(true) {
+            return Err(anyhow!("Nothing is staged. Stage changes with `git add` or run `air commit --all`"));
+        }
+        // A new repository has no history to read
+        let log = call(git, "log", json!({"limit": RECENT_COMMITS})).await.unwrap_or(Value::Null);
+        let recent_subjects = log.as_array().into_iter().flatten()
+            .filter_map(|c| c["subject"].as_str().map(|s| s.to_string()))
+            .collect();
+        Ok(Self {
+            stat: diff["stat"].as_str().unwrap_or_default().to_string(),
+            diff: diff["diff"].as_str().unwrap_or_default().to_string(),
+            recent_subjects,
+        })
+    }
+}
+
+async fn call(git: &GitTool, function: &str, args: Value) -> Result<Value> {
+    let result = git.execute(function, args).await?;
+    if !result.success {
+        return Err(anyhow!("{}", result.result.as_str().unwrap_or("git failed")));
+    }
+    Ok(result.result)
+}
+
+pub fn build_prompt(changes: &StagedChanges) -> String {
+    let mut prompt = String::from(
+        "Write a git commit message for the staged changes below, in the Conventional Commits format:\n\
+         - a header `type(scope): summary` with type one of feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert; \
+         the scope is optional; the summary is imperative, lower case, without a final period, under 72 characters\n\
+         - a blank line, then a body wrapped at 72 characters explaining what changed and why (skip it for trivial changes)\n\
+         - `BREAKING CHANGE: ...` as the last paragraph if the change breaks users\n\
+         Answer with only the message.\n",
+    );
+    if !changes.recent_subjects.is_empty() {
+        prompt.push_str(&format!("\nRecent commit subjects in this repository, for style:\n{}\n", changes.recent_subjects.join("\n")));
+    }
+    prompt.push_str(&format!("\nFiles:\n{}\n\nDiff:\n```diff\n{}\n```", changes.stat, changes.diff.trim_end()));
+    prompt
+}
+
+/// The message without the fences or quotes models like to wrap it in.
+pub fn clean_message(answer: &str) -> String {
+    let mut text = answer.trim();
+    if let Some(rest) = text.strip_prefix("```") {
+        // Drop the info string (```text) and the closing fence
+        text = rest.split_once('\n').map(|(_, body)| body).unwrap_or("");
+        text = text.trim_end().strip_suffix("```").unwrap_or(text);
+    }
+    let text = text.trim().trim_matches('"').trim();
+    text.lines().map(|l| l.trim_end()).collect::<Vec<_>>().join("\n")
+}
+
+pub async fn generate(agent: &AIAgent, mode: &QueryMode, changes: &StagedChanges) -> Result<String> {
+    let validator = Validator::Regex { pattern: CONVENTIONAL_HEADER.to_string() };
+    let response = agent.query_validated(&build_prompt(changes), mode, false, &[validator], 1).await?;
+    Ok(clean_message(&response.content))
+}
+
+/// Commits the staged changes with `message`; `edit` opens it in git's editor first.
+pub async fn commit(git: &GitTool, message: &str, edit: bool) -> Result<ExitStatus> {
+    let path = PathBuf::from(git.git(&["rev-parse", "--git-path", MESSAGE_FILE]).await?.trim());
+    let path = if path.is_relative() { git.repo().join(path) } else { path };
+    std::fs::write(&path, format!("{}\n", message))?;
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(git.repo()).arg("commit").arg("-F").arg(&path);
+    if edit {
+        cmd.arg("--edit");
+    }
+    // Runs in the foreground so hooks and the editor get the terminal
+    let status = cmd.status()?;
+    std::fs::remove_file(&path).ok();
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_message() {
+        assert_eq!(clean_message("```text\nfeat(cli): add commit command\n\nWrites messages.  \n```"), "feat(cli): add commit command\n\nWrites messages.");
+        assert_eq!(clean_message("\"fix: handle empty diff\""), "fix: handle empty diff");
+    }
+
+    #[test]
+    fn test_conventional_header() {
+        let header = regex::Regex::new(CONVENTIONAL_HEADER).unwrap();
+        assert!(header.is_match("feat(tools): add git tool\n\nBody"));
+        assert!(header.is_match("refactor!: drop the old API"));
+        assert!(!header.is_match("Added a git tool"));
+    }
+}
@@ -1,34 +1,128 @@
 use anyhow::Result;
+use schemars::JsonSchema;
+use schemars::schema::{RootSchema, Schema, SchemaObject};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::models::QueryMode;
+use crate::utils::project::Project;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     pub local_model: LocalModelConfig,
+    /// Cloud models used as a fallback when the local model is slow or unsure.
+    /// API keys are read from the OS keyring or environment (GEMINI_API_KEY, OPENAI_API_KEY, ...).
     pub cloud_providers: Vec<CloudProviderConfig>,
     pub performance: PerformanceConfig,
+    #[serde(default)]
+    pub filesystem: FileSystemConfig,
+    #[serde(default)]
+    pub command: CommandConfig,
+    #[serde(default)]
+    pub voice: VoiceConfig,
+    #[serde(default)]
+    pub planner: PlannerConfig,
+    #[serde(default)]
+    pub web: WebConfig,
+    #[serde(default)]
+    pub knowledge: KnowledgeConfig,
+    /// Language for CLI messages and answers, as a code (en, es, fr, de, hi) or a language name
+    /// (any the model knows). Unset uses the system locale for messages and lets the model
+    /// answer in the question's language.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Query strategy used for prompts: auto (local first, cloud fallback), local_only, cloud_only or pure_local.
+    #[serde(default)]
+    pub default_mode: QueryMode,
+    #[serde(default)]
+    pub modes: ModesConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub vcr: VcrConfig,
+    #[serde(default)]
+    pub reflection: ReflectionConfig,
+    #[serde(default)]
+    pub scripting: ScriptingConfig,
+    #[serde(default)]
+    pub desktop: DesktopConfig,
+    #[serde(default)]
+    pub test_fix: TestFixConfig,
+    #[serde(default)]
+    pub github: GitHubConfig,
+    #[serde(default)]
+    pub tracker: TrackerConfig,
+    #[serde(default)]
+    pub automation: AutomationConfig,
+    #[serde(default)]
+    pub pricing: PricingConfig,
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    #[serde(default)]
+    pub persona: PersonaConfig,
+    /// Named overlays (`[profile.work]`, `[profile.home]`) applied over the base settings when selected.
+    #[serde(default, rename = "profile", skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(with = "BTreeMap<String, serde_json::Value>")]
+    pub profiles: BTreeMap<String, toml::Value>,
+    /// Profile this config was loaded with, if any.
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+    /// Project (`air init`) whose `.air/config.toml` was layered over these settings, if any.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub project: Option<Project>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+/// The local GGUF model.
 pub struct LocalModelConfig {
+    /// Path to the GGUF model file.
     pub model_path: String,
+    /// Smaller GGUF model used for speculative decoding.
     pub draft_model_path: Option<String>,
+    /// Maximum tokens generated per response.
     pub max_tokens: u32,
+    /// Sampling temperature (0.0-2.0).
     pub temperature: f32,
+    /// Context window in tokens. Prompts are fitted into it (minus room for the reply): optional
+    /// context is left out first, then the oldest conversation turns, then the middle of the prompt.
     pub context_length: u32,
+    /// CPU threads to use (0 = auto-detect).
     pub threads: u32,
+    /// Inference device: cuda, metal or cpu.
     #[serde(default = "default_device")]
     pub device: String,
+    /// Use the local model at all; when false every request goes to a cloud provider.
     #[serde(default = "default_true")]
     pub enabled: bool,
+    /// Extra folders searched for .gguf models, besides the data directory's `models` folder.
+    #[serde(default)]
+    pub model_dirs: Vec<String>,
+    /// Prompt format: a built-in template (zephyr, chatml, llama3, mistral, phi3) or a path to a
+    /// tokenizer_config.json or .jinja file. Unset uses the model's own template, or one guessed
+    /// from its file name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_template: Option<String>,
+    /// Requests generated at once (batched by the runtime); more wait their turn in order.
+    #[serde(default = "default_local_concurrency")]
+    pub max_concurrent_requests: usize,
+    /// Requests allowed to wait for the model; beyond this they fail fast as busy.
+    #[serde(default = "default_local_queue")]
+    pub max_queued_requests: usize,
 
     // NEW: Runtime detected flag (not usually set in config.toml)
     #[serde(default = "default_false")]
+    #[schemars(skip)]
     pub is_small_model: bool,
 }
 
 fn default_false() -> bool { false }
 
+fn default_local_concurrency() -> usize { 1 }
+
+fn default_local_queue() -> usize { 8 }
+
 fn default_device() -> String {
     "cuda".to_string()
 }
@@ -36,7 +130,10 @@ fn default_device() -> String {
 impl Default for LocalModelConfig {
     fn default() -> Self {
         Self {
-            model_path: "C:\\models\\tinyllama-1.1b-chat-v1.0.Q2_K.gguf".to_string(),
+            model_path: crate::utils::paths::models_dir_path()
+                .join(crate::utils::model_catalog::default_entry().file)
+                .to_string_lossy()
+                .to_string(),
             draft_model_path: None,
             max_tokens: 512,
             temperature: 0.7,
@@ -44,78 +141,1435 @@ impl Default for LocalModelConfig {
             threads: 4,
             device: "cuda".to_string(),
             enabled: true,
+            model_dirs: Vec::new(),
+            chat_template: None,
+            max_concurrent_requests: default_local_concurrency(),
+            max_queued_requests: default_local_queue(),
             is_small_model: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CloudProviderConfig {
+    /// Provider: openai, anthropic, gemini, openrouter or azure.
     pub name: String,
+    /// Prefer `air login` or the provider's environment variable over storing the key here.
     pub api_key: Option<String>,
+    /// API endpoint.
     pub base_url: String,
+    /// Model identifier passed to the API.
     pub model: String,
+    /// Maximum tokens generated per response.
     pub max_tokens: u32,
+    /// Sampling temperature (0.0-2.0).
     pub temperature: f32,
+    /// Request timeout in seconds.
     pub timeout_seconds: u64,
+    /// Set to false to keep the entry without using it.
     #[serde(default = "default_true")]
     pub enabled: bool,
+    /// Let the provider pick the best available model (Gemini only); when false `model` is always used.
+    #[serde(default = "default_true")]
+    pub auto_select_model: bool,
+    /// Offer AIR's tools as native function calls; turn off for models without tool support, which
+    /// then call tools with JSON in their answer like local models do.
+    #[serde(default = "default_true")]
+    pub native_tools: bool,
+    /// Send JSON schemas (`AIAgent::query_json`, `--validate schema:`) as the API's JSON mode
+    /// (OpenAI-compatible providers and Gemini); turn off for models without it.
+    #[serde(default = "default_true")]
+    pub structured_output: bool,
+    /// Azure only: the `api-version` query parameter (default 2024-10-21).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    /// Azure only: authenticate with an Azure AD (Entra ID) token instead of `api_key`, taken from
+    /// AZURE_OPENAI_AD_TOKEN or else from the Azure CLI (`az login`).
+    #[serde(default)]
+    pub azure_ad: bool,
+    /// Extra fields merged into every request body, e.g. `organization`, `reasoning_effort` or Gemini
+    /// `safetySettings`. Nested tables merge into the fields AIR sets; other values replace them.
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    #[schemars(with = "BTreeMap<String, serde_json::Value>")]
+    pub extra: toml::Table,
+    /// Extra HTTP headers sent with every request; these replace AIR's own headers of the same name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Routing quality (0.0-1.0); providers are tried highest first. Measured by `air bench --update-scores`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality_score: Option<f32>,
+    /// Expected response time in milliseconds, replacing the built-in estimate until enough calls have been observed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Price in USD per million tokens (input and output blended), used for `air bench` and the
+    /// usage log's cost estimates. Models with a `[pricing.models]` entry use that instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_per_million_tokens: Option<f64>,
+    /// Most calls a minute; at the limit the provider is skipped until there is room again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Most tokens a minute, counting each call's prompt and its whole `max_tokens` allowance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Token prices for the usage log (`air usage`, `air cost`), per model.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PricingConfig {
+    /// Keyed by model id as set on the provider (e.g. "gpt-4o-mini", "claude-3-5-haiku-latest").
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelPrice>,
+}
+
+/// USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl PricingConfig {
+    /// The entry for a response's `model_used`, which is either the bare model id or the
+    /// provider-prefixed form the cloud providers report ("OpenAI-gpt-4o-mini").
+    pub fn price_for(&self, model_used: &str) -> Option<ModelPrice> {
+        self.models.get(model_used)
+            .or_else(|| model_used.split_once('-').and_then(|(_, model)| self.models.get(model)))
+            .copied()
+    }
+}
+
+impl ModelPrice {
+    pub fn blended(per_million: f64) -> Self {
+        Self { input: per_million, output: per_million }
+    }
+
+    /// Tokens the provider didn't attribute to the prompt are charged as output.
+    pub fn cost_usd(&self, input_tokens: u32, total_tokens: u32) -> f64 {
+        let input = input_tokens.min(total_tokens) as f64;
+        let output = total_tokens.saturating_sub(input_tokens) as f64;
+        (input * self.input + output * self.output) / 1_000_000.0
+    }
 }
 
 fn default_true() -> bool {
     true
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+/// When to fall back from the local model to the cloud.
 pub struct PerformanceConfig {
+    /// Local responses slower than this (milliseconds) count as slow.
     pub fallback_threshold_ms: u64,
+    /// Minimum quality score (0.0-1.0) for a local response to be kept.
     pub quality_threshold: f32,
+    /// How long to wait for the local model before falling back (seconds).
     pub local_timeout_seconds: u64,
+    /// In auto mode, answer simple queries locally first but send complex ones (long, multi-step,
+    /// see `agent::classifier`) to the cloud first. When false the local model is always tried first.
+    #[serde(default = "default_true")]
+    pub prefer_local_for_simple_queries: bool,
+}
+
+/// Routing rules for auto mode, checked in order before the built-in policy (local first, then
+/// cloud by quality_score; complex queries cloud first). The first rule whose conditions all hold
+/// decides where a query goes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct RoutingConfig {
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RoutingRule {
+    /// Shown in the logs when the rule matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Intents the rule applies to (chat, code, retrieval, tool_action, math). Empty matches any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intent: Vec<String>,
+    /// Complexities the rule applies to (simple, moderate, complex). Empty matches any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub complexity: Vec<String>,
+    /// Applies only when the prompt with its context is estimated at this many tokens or more.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_tokens: Option<usize>,
+    /// Applies only when the prompt with its context is estimated at this many tokens or fewer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    /// Where matching queries go, tried in order: "local", "cloud" (every cloud provider by
+    /// quality_score) or the name of a cloud provider.
+    pub route: Vec<String>,
+    /// Skips targets expected (observed p50, else the provider's estimate) to take longer than this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_budget_ms: Option<u64>,
+    /// When every target fails, carry on with the built-in policy instead of giving up.
+    #[serde(default = "default_true")]
+    pub fallback: bool,
+}
+
+/// Sandbox rules for the filesystem tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FileSystemConfig {
+    /// Restrict the filesystem tool to the workspace roots.
+    #[serde(default = "default_true")]
+    pub sandbox_enabled: bool,
+    /// Directories the tool may touch. Empty means the current working directory.
+    #[serde(default)]
+    pub workspace_roots: Vec<String>,
+    /// Glob patterns that are always refused, even inside a workspace root. `~` expands to the home directory.
+    #[serde(default = "default_denied_globs")]
+    pub denied_globs: Vec<String>,
+    /// Largest file (in bytes) that may be read or written. 0 disables the limit.
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size_bytes: u64,
+}
+
+fn default_denied_globs() -> Vec<String> {
+    vec![
+        "~/.ssh/**".to_string(),
+        "~/.gnupg/**".to_string(),
+        "~/.aws/**".to_string(),
+        "**/*.pem".to_string(),
+        "**/id_rsa*".to_string(),
+    ]
+}
+
+fn default_max_file_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+impl Default for FileSystemConfig {
+    fn default() -> Self {
+        Self {
+            sandbox_enabled: true,
+            workspace_roots: Vec::new(),
+            denied_globs: default_denied_globs(),
+            max_file_size_bytes: default_max_file_size(),
+        }
+    }
+}
+
+/// Execution settings for the command tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommandConfig {
+    /// Commands are killed after this many seconds (overridable per call).
+    #[serde(default = "default_command_timeout")]
+    pub timeout_seconds: u64,
+    /// Output passed back to the model is truncated to this many characters.
+    #[serde(default = "default_max_output_chars")]
+    pub max_output_chars: usize,
+    /// Command patterns that run without asking (`*` and `?` wildcards, e.g. `cargo *`).
+    #[serde(default = "default_auto_approve")]
+    pub auto_approve: Vec<String>,
+    /// Exceptions to `auto_approve` that still need confirmation (e.g. `cargo publish *`).
+    #[serde(default = "default_approval_required")]
+    pub approval_required: Vec<String>,
+    /// Commands that are refused outright, even with user confirmation.
+    #[serde(default = "default_always_deny")]
+    pub always_deny: Vec<String>,
+    /// Honour `auto_approve` entries from a project's `.air/commands.toml`.
+    /// Deny and approval entries from that file always apply since they only tighten the rules.
+    #[serde(default = "default_false")]
+    pub trust_project_overrides: bool,
+    /// Default shell: sh, bash, zsh, cmd, powershell or pwsh (platform default when unset).
+    #[serde(default)]
+    pub shell: Option<String>,
+}
+
+fn default_command_timeout() -> u64 {
+    120
+}
+
+fn default_max_output_chars() -> usize {
+    8000
+}
+
+fn default_auto_approve() -> Vec<String> {
+    [
+        "dir *", "ls *", "pwd *", "cd *", "echo *", "type *", "cat *", "head *", "tail *",
+        "find *", "grep *", "which *", "where *", "whoami *", "date *", "time *", "hostname *",
+        "ping *", "git *", "cargo *", "node *", "npm *", "python *", "rustc *",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_approval_required() -> Vec<String> {
+    [
+        "git push *", "git commit *", "git add *", "git merge *", "git rebase *",
+        "cargo publish *", "cargo install *",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_always_deny() -> Vec<String> {
+    [
+        "rm -rf /", "rm -rf / *", "rm -rf ~", "rm -rf ~/", "mkfs *", "mkfs.*",
+        "shutdown *", "reboot *", "format *", ":(){*",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: default_command_timeout(),
+            max_output_chars: default_max_output_chars(),
+            auto_approve: default_auto_approve(),
+            approval_required: default_approval_required(),
+            always_deny: default_always_deny(),
+            trust_project_overrides: false,
+            shell: None,
+        }
+    }
+}
+
+/// Speech synthesis and playback settings for the voice tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct VoiceConfig {
+    /// Path to the default Piper voice config (`*.onnx.json`); when unset, the first voice in the
+    /// models folder's `voices/` directory is used.
+    #[serde(default)]
+    pub piper_model: Option<String>,
+    /// Play generated speech through the speakers as well as saving it.
+    #[serde(default = "default_true")]
+    pub play_speech: bool,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            piper_model: None,
+            play_speech: true,
+        }
+    }
+}
+
+/// Reminder settings for planner tasks.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PlannerConfig {
+    /// List overdue and due-today tasks when an interactive session starts.
+    #[serde(default = "default_true")]
+    pub remind_on_start: bool,
+    /// Also raise a desktop notification for those reminders.
+    #[serde(default = "default_false")]
+    pub desktop_notifications: bool,
+    /// How often `air daemon` checks for due tasks and notifies (with `desktop_notifications`);
+    /// 0 turns the check off.
+    #[serde(default = "default_reminder_interval")]
+    pub reminder_interval_minutes: u64,
+}
+
+fn default_reminder_interval() -> u64 {
+    60
+}
+
+impl Default for PlannerConfig {
+    fn default() -> Self {
+        Self {
+            remind_on_start: true,
+            desktop_notifications: false,
+            reminder_interval_minutes: default_reminder_interval(),
+        }
+    }
+}
+
+/// Politeness settings for the web tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WebConfig {
+    /// Skip pages that a site's robots.txt disallows.
+    #[serde(default = "default_true")]
+    pub respect_robots_txt: bool,
+    /// Minimum gap between requests to the same host; a longer robots.txt Crawl-delay wins.
+    #[serde(default = "default_min_request_interval_ms")]
+    pub min_request_interval_ms: u64,
+    /// Responses kept for ETag/Last-Modified revalidation (0 disables caching).
+    #[serde(default = "default_cache_entries")]
+    pub cache_entries: usize,
+    /// Largest file `download` will write, in bytes (0 = no limit).
+    #[serde(default = "default_max_download_bytes")]
+    pub max_download_bytes: u64,
+    /// Re-render pages that are empty JavaScript shells in a headless browser.
+    #[serde(default = "default_false")]
+    pub render_js_fallback: bool,
+    /// Chrome/Chromium/Edge executable for headless rendering; searched for when unset.
+    #[serde(default)]
+    pub browser_path: Option<String>,
+}
+
+fn default_min_request_interval_ms() -> u64 {
+    1000
+}
+
+fn default_cache_entries() -> usize {
+    200
+}
+
+fn default_max_download_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            respect_robots_txt: true,
+            min_request_interval_ms: default_min_request_interval_ms(),
+            cache_entries: default_cache_entries(),
+            max_download_bytes: default_max_download_bytes(),
+            render_js_fallback: false,
+            browser_path: None,
+        }
+    }
+}
+
+/// How files added to the knowledge base (`air memory add`) are split before embedding.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct KnowledgeConfig {
+    #[serde(default)]
+    pub chunking: ChunkStrategy,
+    /// Longest chunk in characters; markdown sections over it are split fixed-size.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// Characters a fixed-size chunk repeats from the end of the one before it.
+    #[serde(default = "default_chunk_overlap")]
+    pub chunk_overlap: usize,
+}
+
+/// auto picks markdown for .md files, code for source files and fixed for everything else.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStrategy {
+    #[default]
+    Auto,
+    /// Runs of whole lines up to `chunk_size`, overlapping by `chunk_overlap`.
+    Fixed,
+    /// One chunk per section, labelled with its heading path.
+    Markdown,
+    /// One chunk per top-level definition (function, struct, class), as `air code` does.
+    Code,
+}
+
+impl ChunkStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkStrategy::Auto => "auto",
+            ChunkStrategy::Fixed => "fixed",
+            ChunkStrategy::Markdown => "markdown",
+            ChunkStrategy::Code => "code",
+        }
+    }
+}
+
+fn default_chunk_size() -> usize {
+    1500
+}
+
+fn default_chunk_overlap() -> usize {
+    200
+}
+
+impl Default for KnowledgeConfig {
+    fn default() -> Self {
+        Self {
+            chunking: ChunkStrategy::Auto,
+            chunk_size: default_chunk_size(),
+            chunk_overlap: default_chunk_overlap(),
+        }
+    }
+}
+
+/// HTTP API served by `air serve`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ServerConfig {
+    /// Address to listen on; anything other than loopback requires `api_key`.
+    #[serde(default = "default_server_bind")]
+    pub bind: String,
+    #[serde(default = "default_server_port")]
+    pub port: u16,
+    /// Key clients send as `Authorization: Bearer <key>` (or set AIR_SERVER__API_KEY).
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Queries processed at once; further requests get 429 Too Many Requests.
+    #[serde(default = "default_server_concurrency")]
+    pub max_concurrent_requests: usize,
+    /// Turns kept per session and replayed to the model as context.
+    #[serde(default = "default_server_session_turns")]
+    pub max_session_turns: usize,
+}
+
+fn default_server_bind() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_server_port() -> u16 {
+    8787
+}
+
+fn default_server_concurrency() -> usize {
+    2
+}
+
+fn default_server_session_turns() -> usize {
+    20
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: default_server_bind(),
+            port: default_server_port(),
+            api_key: None,
+            max_concurrent_requests: default_server_concurrency(),
+            max_session_turns: default_server_session_turns(),
+        }
+    }
+}
+
+/// Records model traffic to a cassette file, or answers from one instead of calling the models,
+/// for deterministic tests and demos (`AIR_VCR__MODE=replay`).
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct VcrConfig {
+    /// off, record (overwrites the cassette) or replay (fails requests that weren't recorded).
+    #[serde(default)]
+    pub mode: VcrMode,
+    /// Cassette file; relative paths are resolved from the working directory.
+    #[serde(default = "default_vcr_cassette")]
+    pub cassette: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VcrMode {
+    #[default]
+    Off,
+    Record,
+    Replay,
+}
+
+fn default_vcr_cassette() -> String {
+    "air_cassette.json".to_string()
+}
+
+impl Default for VcrConfig {
+    fn default() -> Self {
+        Self { mode: VcrMode::Off, cassette: default_vcr_cassette() }
+    }
+}
+
+/// Who the assistant is: the identity at the top of every prompt (edit with `air persona`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PersonaConfig {
+    /// Name the assistant goes by.
+    #[serde(default = "default_persona_name")]
+    pub name: String,
+    /// Identity text replacing the default "You are <name>. This identity is fixed."
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// How answers should sound, e.g. "concise and friendly".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tone: Option<String>,
+    /// Language to answer in, as a code or name; overrides the top-level `language` for answers
+    /// (CLI messages still follow `language`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+fn default_persona_name() -> String {
+    "AIR".to_string()
+}
+
+impl Default for PersonaConfig {
+    fn default() -> Self {
+        Self {
+            name: default_persona_name(),
+            system_prompt: None,
+            tone: None,
+            language: None,
+        }
+    }
+}
+
+impl PersonaConfig {
+    /// The identity text: `system_prompt`, or the default one for `name`, followed by the tone.
+    pub fn identity(&self) -> String {
+        let mut identity = match non_blank(&self.system_prompt) {
+            Some(prompt) => prompt.to_string(),
+            None => format!("You are {}. This identity is fixed.", self.name.trim()),
+        };
+        if let Some(tone) = non_blank(&self.tone) {
+            identity.push_str(&format!("\nTone: {}.", tone.trim_end_matches('.')));
+        }
+        identity
+    }
+}
+
+fn non_blank(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Self-critique after the tool loop: a reviewer checks the draft answer against the question and
+/// the tool observations, and the answer is revised once if it finds problems. Costs one extra
+/// model call, two when a revision is needed.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ReflectionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Only review queries the classifier rates complex.
+    #[serde(default = "default_true")]
+    pub complex_only: bool,
+    /// Cloud provider that writes the critique, typically a cheaper one: gemini, openai,
+    /// anthropic, openrouter or azure. Unset routes the critique like any other query.
+    #[serde(default)]
+    pub critic_provider: Option<String>,
+    /// Maximum tokens for a critique written by `critic_provider`.
+    #[serde(default = "default_critique_tokens")]
+    pub critique_max_tokens: u32,
+}
+
+fn default_critique_tokens() -> u32 {
+    512
+}
+
+impl Default for ReflectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            complex_only: true,
+            critic_provider: None,
+            critique_max_tokens: default_critique_tokens(),
+        }
+    }
+}
+
+/// User tools written in Rhai, loaded from the config directory's `tools/` folder at startup.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScriptingConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Rhai operations one tool call may run before it is stopped.
+    #[serde(default = "default_script_operations")]
+    pub max_operations: u64,
+    /// Longest a tool call may take, including its HTTP requests.
+    #[serde(default = "default_script_timeout")]
+    pub timeout_seconds: u64,
+}
+
+fn default_script_operations() -> u64 {
+    1_000_000
+}
+
+fn default_script_timeout() -> u64 {
+    30
+}
+
+impl Default for ScriptingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_operations: default_script_operations(),
+            timeout_seconds: default_script_timeout(),
+        }
+    }
+}
+
+/// Global hotkeys for `air desktop`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DesktopConfig {
+    /// Opens the quick-ask input box, e.g. "ctrl+alt+space" or "super+shift+a".
+    #[serde(default = "default_ask_hotkey")]
+    pub ask_hotkey: String,
+    /// Asks about the current selection (the clipboard where there is no primary selection).
+    #[serde(default = "default_selection_hotkey")]
+    pub selection_hotkey: String,
+    /// Copy each answer to the clipboard as well as showing it.
+    #[serde(default = "default_true")]
+    pub copy_answer: bool,
+}
+
+fn default_ask_hotkey() -> String {
+    "ctrl+alt+space".to_string()
+}
+
+fn default_selection_hotkey() -> String {
+    "ctrl+alt+shift+space".to_string()
+}
+
+impl Default for DesktopConfig {
+    fn default() -> Self {
+        Self {
+            ask_hotkey: default_ask_hotkey(),
+            selection_hotkey: default_selection_hotkey(),
+            copy_answer: true,
+        }
+    }
+}
+
+/// `air test-fix`: run the tests, have the agent fix the failures, repeat.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TestFixConfig {
+    /// Test command, run through `[command] shell` from the repository root. Unset detects
+    /// `cargo test`, `npm test`, `pytest` or `go test ./...` from the project files.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Fix attempts before giving up.
+    #[serde(default = "default_fix_iterations")]
+    pub max_iterations: u32,
+    /// Longest one test run may take.
+    #[serde(default = "default_test_timeout")]
+    pub timeout_seconds: u64,
+    /// Test output sent to the model, keeping the start and the end.
+    #[serde(default = "default_test_output_chars")]
+    pub max_output_chars: usize,
+}
+
+fn default_fix_iterations() -> u32 {
+    3
+}
+
+fn default_test_timeout() -> u64 {
+    600
+}
+
+fn default_test_output_chars() -> usize {
+    12_000
+}
+
+impl Default for TestFixConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            max_iterations: default_fix_iterations(),
+            timeout_seconds: default_test_timeout(),
+            max_output_chars: default_test_output_chars(),
+        }
+    }
+}
+
+/// The `github` tool and `air review` for pull requests.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitHubConfig {
+    /// Personal access token. Unset falls back to GITHUB_TOKEN, GH_TOKEN, then the keyring entry
+    /// GITHUB_TOKEN; public repositories can be read without one.
+    #[serde(default)]
+    pub access_token: Option<String>,
+    /// `owner/repo` used when a call names no repository and the working directory's `origin`
+    /// isn't on GitHub.
+    #[serde(default)]
+    pub default_repo: Option<String>,
+    /// API root; change it for GitHub Enterprise (`https://github.example.com/api/v3`).
+    #[serde(default = "default_github_api")]
+    pub api_url: String,
+}
+
+fn default_github_api() -> String {
+    "https://api.github.com".to_string()
+}
+
+impl Default for GitHubConfig {
+    fn default() -> Self {
+        Self {
+            access_token: None,
+            default_repo: None,
+            api_url: default_github_api(),
+        }
+    }
+}
+
+/// The `tracker` tool: the team's issue tracker, which planner tasks can be linked to.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TrackerConfig {
+    /// jira or linear; unset leaves the tool unconfigured.
+    #[serde(default)]
+    pub backend: Option<TrackerBackend>,
+    /// Jira site (`https://team.atlassian.net`). Linear always uses its public API.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Jira account email for API-token auth; unset sends the token as a bearer token
+    /// (Jira Data Center personal access tokens).
+    #[serde(default)]
+    pub email: Option<String>,
+    /// API token (Jira) or personal API key (Linear). Unset falls back to JIRA_API_TOKEN or
+    /// LINEAR_API_KEY, then the keyring entry of the same name.
+    #[serde(default)]
+    pub api_token: Option<String>,
+    /// Jira project key or Linear team key that new issues go in and searches are scoped to.
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Jira issue type for new issues.
+    #[serde(default = "default_tracker_issue_type")]
+    pub issue_type: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackerBackend {
+    Jira,
+    Linear,
+}
+
+fn default_tracker_issue_type() -> String {
+    "Task".to_string()
 }
 
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            backend: None,
+            url: None,
+            email: None,
+            api_token: None,
+            project: None,
+            issue_type: default_tracker_issue_type(),
+        }
+    }
+}
+
+/// The `desktop` tool (built with the `automation` feature): mouse and keyboard control. Every
+/// action asks the user first, whatever these settings say.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AutomationConfig {
+    /// Lets the tool act at all; off by default.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Milliseconds to wait after an action before checking the screen for its `expect` text.
+    #[serde(default = "default_settle_ms")]
+    pub settle_ms: u64,
+    /// Longest text a single `type_text` may type.
+    #[serde(default = "default_max_typed_chars")]
+    pub max_typed_chars: usize,
+    /// Shell command printing the text in a screenshot; `{image}` is replaced with its path.
+    /// When it fails the screenshot tool's vision model describes the screen instead.
+    #[serde(default = "default_ocr_command")]
+    pub ocr_command: String,
+}
+
+fn default_settle_ms() -> u64 {
+    500
+}
+
+fn default_max_typed_chars() -> usize {
+    500
+}
+
+fn default_ocr_command() -> String {
+    "tesseract {image} stdout".to_string()
+}
+
+impl Default for AutomationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            settle_ms: default_settle_ms(),
+            max_typed_chars: default_max_typed_chars(),
+            ocr_command: default_ocr_command(),
+        }
+    }
+}
+
+/// Generation settings for one query mode. Unset values fall back to `[local_model]` and
+/// `[performance]` for the local modes, and to 1000 tokens, 0.7 and 30 seconds for `cloud_only`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ModeConfig {
+    /// Maximum tokens generated per response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Sampling temperature (0.0-2.0).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Seconds to wait for a response (for `auto`, before falling back to the cloud).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+}
+
+/// Per-mode generation settings (`[modes.auto]`, `[modes.local_only]`, `[modes.cloud_only]`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ModesConfig {
+    #[serde(default)]
+    pub auto: ModeConfig,
+    #[serde(default)]
+    pub local_only: ModeConfig,
+    #[serde(default)]
+    pub cloud_only: ModeConfig,
+}
+
+/// Generation settings resolved for a query mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeParams {
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub timeout: Duration,
+}
+
+const CLOUD_ONLY_MAX_TOKENS: u32 = 1000;
+const CLOUD_ONLY_TEMPERATURE: f32 = 0.7;
+const CLOUD_ONLY_TIMEOUT_SECONDS: u64 = 30;
+
 impl Config {
+    /// Loads config.toml with the profile named by `AIR_PROFILE`, if set.
     pub fn load() -> Result<Self> {
-        let config_dir = crate::utils::paths::get_air_data_dir()?;
+        let profile = std::env::var("AIR_PROFILE").ok().filter(|p| !p.is_empty());
+        Self::load_profile(profile.as_deref())
+    }
+
+    /// Loads config.toml, then layers the named profile, the current project's `.air/config.toml`
+    /// and `AIR_*` environment variables over it.
+    /// Profiles come from `[profile.<name>]` in config.toml or from `profiles/<name>.toml` in the config directory.
+    pub fn load_profile(profile: Option<&str>) -> Result<Self> {
+        Self::load_layers(profile, Project::detect())
+    }
+
+    fn load_layers(profile: Option<&str>, project: Option<Project>) -> Result<Self> {
+        let config_dir = crate::utils::paths::get_air_config_dir()?;
         let config_path = config_dir.join("config.toml");
         
-        if config_path.exists() {
+        let mut value = if config_path.exists() {
             let content = std::fs::read_to_string(config_path)?;
-            let mut config: Config = toml::from_str(&content)?;
+            toml::from_str::<toml::Value>(&content)?
+        } else {
+            toml::Value::try_from(Self::default())?
+        };
+
+        if let Some(name) = profile {
+            let overlay = find_profile(&value, &config_dir, name)?;
+            merge_toml(&mut value, overlay);
+        }
+        if let Some(project) = &project {
+            merge_toml(&mut value, project.overlay.clone());
+        }
+
+        let mut config = Self::from_toml_with_env(value)?;
+        config.active_profile = profile.map(|p| p.to_string());
+        config.project = project;
+        Ok(config)
+    }
+
+    /// These settings with the project's overrides put back to their global values, for saving
+    /// to the global config.toml without copying project settings into it.
+    pub fn without_project(&self) -> Result<Self> {
+        let Some(project) = &self.project else { return Ok(self.clone()) };
+        let global = toml::Value::try_from(Self::load_layers(self.active_profile.as_deref(), None)?)?;
+        let mut value = toml::Value::try_from(self)?;
+        restore_overridden(&mut value, &project.overlay, &global);
+        let mut config = self.with_value(value, "project")?;
+        config.project = None;
+        Ok(config)
+    }
+
+    /// Loads a config file from an explicit path (for embedders), with `AIR_*` environment overrides.
+    pub fn load_from(path: &std::path::Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+        Self::from_toml_with_env(toml::from_str::<toml::Value>(&content)?)
+    }
+
+    /// Parses config.toml text laid over the defaults, for hosts without a config directory
+    /// (wasm32 builds, embedders).
+    pub fn from_toml_str(text: &str) -> Result<Self> {
+        let mut value = toml::Value::try_from(Self::default())?;
+        merge_toml(&mut value, toml::from_str(text)?);
+        Self::from_toml_with_env(value)
+    }
+
+    fn from_toml_with_env(mut value: toml::Value) -> Result<Self> {
+        // AIR_* variables are layered over the file so containers and CI can configure without editing it
+        apply_env_overrides(&mut value, std::env::vars());
+        let mut config: Config = value.try_into()
+            .map_err(|e| anyhow::anyhow!("Invalid configuration (after applying profile and AIR_* environment overrides): {}", e))?;
             
-            // Override API keys from environment variables
-            for provider in &mut config.cloud_providers {
-                match provider.name.as_str() {
-                    "openai" => {
-                        if let Ok(key) = std::env::var("OPENAI_API_KEY") {
-                            provider.api_key = Some(key);
-                        }
+        // Override API keys from environment variables
+        for provider in &mut config.cloud_providers {
+            if let Some(key) = api_key_env_var(&provider.name).and_then(|var| std::env::var(var).ok()) {
+                provider.api_key = Some(key);
+            }
+        }
+        
+        Ok(config)
+    }
+
+    /// Looks up a dotted key such as `performance.local_timeout_seconds` or
+    /// `cloud_providers.gemini.model` (array entries by index or by `name`).
+    pub fn get_key(&self, key: &str) -> Result<toml::Value> {
+        let value = toml::Value::try_from(self)?;
+        let segments: Vec<String> = key.split('.').map(|s| s.to_string()).collect();
+        get_path(&value, &segments)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown or unset configuration key '{}'", key))
+    }
+
+    /// Sets a dotted key from its TOML text (`true`, `42`, `["ls *"]`; bare words are strings).
+    /// The result must still deserialize into `Config`, so typos and wrongly typed values are rejected.
+    pub fn set_key(&mut self, key: &str, raw: &str) -> Result<()> {
+        let mut value = toml::Value::try_from(&*self)?;
+        let segments = key_segments(key)?;
+        if !set_path(&mut value, &segments, raw) {
+            return Err(anyhow::anyhow!("Unknown configuration key '{}'", key));
+        }
+        let updated = self.with_value(value, key)?;
+        // Unknown fields are dropped on deserialize, so make sure the key actually landed
+        updated.get_key(key).map_err(|_| anyhow::anyhow!("Unknown configuration key '{}'", key))?;
+        *self = updated;
+        Ok(())
+    }
+
+    /// Removes a dotted key so its default applies again.
+    pub fn unset_key(&mut self, key: &str) -> Result<()> {
+        let mut value = toml::Value::try_from(&*self)?;
+        if !remove_key(&mut value, key)? {
+            return Err(anyhow::anyhow!("Unknown or unset configuration key '{}'", key));
+        }
+        *self = self.with_value(value, key)?;
+        Ok(())
+    }
+
+    /// Generation settings for `mode`, with its `[modes.*]` block layered over the fallbacks.
+    /// `pure_local` shares the `local_only` block.
+    pub fn mode_params(&self, mode: &QueryMode) -> ModeParams {
+        let local = ModeParams {
+            max_tokens: self.local_model.max_tokens,
+            temperature: self.local_model.temperature,
+            timeout: Duration::from_secs(self.performance.local_timeout_seconds),
+        };
+        let (block, fallback) = match mode {
+            QueryMode::Auto => (&self.modes.auto, local),
+            QueryMode::LocalOnly | QueryMode::PureLocal => (&self.modes.local_only, local),
+            QueryMode::CloudOnly => (&self.modes.cloud_only, ModeParams {
+                max_tokens: CLOUD_ONLY_MAX_TOKENS,
+                temperature: CLOUD_ONLY_TEMPERATURE,
+                timeout: Duration::from_secs(CLOUD_ONLY_TIMEOUT_SECONDS),
+            }),
+        };
+        ModeParams {
+            max_tokens: block.max_tokens.unwrap_or(fallback.max_tokens),
+            temperature: block.temperature.unwrap_or(fallback.temperature),
+            timeout: block.timeout_seconds.map(Duration::from_secs).unwrap_or(fallback.timeout),
+        }
+    }
+
+    /// JSON Schema for config.toml, for editor validation and completion.
+    pub fn json_schema() -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(schemars::schema_for!(Config))?)
+    }
+
+    /// A config.toml listing every setting at its default, each preceded by its description.
+    pub fn sample_toml() -> Result<String> {
+        let root = schemars::schema_for!(Config);
+        let mut defaults = Config::default();
+        for provider in &mut defaults.cloud_providers {
+            provider.api_key = None;
+        }
+        let defaults = toml::Value::try_from(&defaults)?;
+
+        let mut out = String::from(
+            "# AIR Configuration File\n\
+             # Generated by `air config init`: every setting is listed with its default value.\n\
+             # Any value can be overridden with AIR_<SECTION>__<FIELD> environment variables.\n",
+        );
+        if let toml::Value::Table(table) = &defaults {
+            push_table(&mut out, &root, None, &root.schema, table);
+        }
+        Ok(out)
+    }
+
+    fn with_value(&self, value: toml::Value, key: &str) -> Result<Config> {
+        let mut updated: Config = value.try_into()
+            .map_err(|e| anyhow::anyhow!("Invalid value for '{}': {}", key, e))?;
+        updated.active_profile = self.active_profile.clone();
+        updated.project = self.project.clone();
+        Ok(updated)
+    }
+
+    /// Profiles defined in config.toml plus those in the config directory's `profiles/` folder.
+    pub fn available_profiles(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        if let Ok(entries) = crate::utils::paths::get_air_config_dir().and_then(|dir| Ok(std::fs::read_dir(dir.join("profiles"))?)) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "toml") {
+                    if let Some(stem) = path.file_stem() {
+                        names.push(stem.to_string_lossy().to_string());
                     }
-                    "anthropic" => {
-                        if let Ok(key) = std::env::var("ANTHROPIC_API_KEY") {
-                            provider.api_key = Some(key);
-                        }
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+/// Environment variable that supplies the API key for a built-in cloud provider.
+pub fn api_key_env_var(provider: &str) -> Option<&'static str> {
+    match provider {
+        "openai" => Some("OPENAI_API_KEY"),
+        "anthropic" => Some("ANTHROPIC_API_KEY"),
+        "gemini" => Some("GEMINI_API_KEY"),
+        "openrouter" => Some("OPEN_ROUTER"),
+        "azure" => Some("AZURE_OPENAI_API_KEY"),
+        _ => None,
+    }
+}
+
+fn object_properties(schema: &SchemaObject) -> impl Iterator<Item = (&String, &Schema)> {
+    schema.object.iter().flat_map(|object| object.properties.iter())
+}
+
+/// Follows `$ref`s (which schemars wraps in `allOf` when a field has its own description).
+fn resolve<'a>(root: &'a RootSchema, schema: &'a SchemaObject) -> &'a SchemaObject {
+    if let Some(name) = schema.reference.as_deref().and_then(|r| r.strip_prefix("#/definitions/")) {
+        if let Some(Schema::Object(target)) = root.definitions.get(name) {
+            return resolve(root, target);
+        }
+    }
+    let wrapped = schema.subschemas.as_ref()
+        .and_then(|sub| sub.all_of.as_ref())
+        .and_then(|all| all.first());
+    match wrapped {
+        Some(Schema::Object(inner)) => resolve(root, inner),
+        _ => schema,
+    }
+}
+
+fn description(schema: &SchemaObject) -> Option<&str> {
+    schema.metadata.as_ref()?.description.as_deref()
+}
+
+fn push_comment(out: &mut String, text: Option<&str>) {
+    for line in text.into_iter().flat_map(|t| t.lines()) {
+        out.push_str(&format!("# {}\n", line));
+    }
+}
+
+/// Writes `table`'s settings, each after its description: plain values first, then nested
+/// tables and arrays of tables as their own `[path.key]` / `[[path.key]]` sections.
+fn push_table(out: &mut String, root: &RootSchema, path: Option<&str>, schema: &SchemaObject, table: &toml::Table) {
+    let mut sections = Vec::new();
+    for (key, field) in object_properties(schema) {
+        let Schema::Object(field) = field else { continue };
+        let target = resolve(root, field);
+        let doc = description(field).or_else(|| description(target));
+        let key_path = path.map_or_else(|| key.clone(), |p| format!("{}.{}", p, key));
+
+        match table.get(key.as_str()) {
+            Some(toml::Value::Table(_)) => sections.push((key_path, doc, target, key)),
+            Some(toml::Value::Array(items)) if items.iter().any(|item| item.is_table()) => sections.push((key_path, doc, target, key)),
+            // Config floats are f32; print them as such rather than with f64 noise (0.699999988...)
+            Some(toml::Value::Float(value)) => {
+                push_comment(out, doc);
+                out.push_str(&format!("{} = {:?}\n", key, *value as f32));
+            }
+            Some(value) => {
+                push_comment(out, doc);
+                out.push_str(&format!("{} = {}\n", key, value));
+            }
+            // Unset optional settings are shown commented out
+            None => {
+                push_comment(out, doc);
+                if key_path == "profile" {
+                    out.push_str("# [profile.work.performance]\n# local_timeout_seconds = 60\n");
+                } else {
+                    out.push_str(&format!("# {} = {}\n", key, placeholder(target)));
+                }
+            }
+        }
+    }
+
+    for (key_path, doc, target, key) in sections {
+        out.push('\n');
+        push_comment(out, doc);
+        match &table[key.as_str()] {
+            toml::Value::Table(inner) => {
+                out.push_str(&format!("[{}]\n", key_path));
+                push_table(out, root, Some(&key_path), target, inner);
+            }
+            toml::Value::Array(items) => {
+                let item_schema = target.array.as_ref()
+                    .and_then(|array| array.items.as_ref())
+                    .and_then(|items| match items {
+                        schemars::schema::SingleOrVec::Single(schema) => match schema.as_ref() {
+                            Schema::Object(schema) => Some(resolve(root, schema)),
+                            _ => None,
+                        },
+                        _ => None,
+                    });
+                for item in items {
+                    out.push_str(&format!("[[{}]]\n", key_path));
+                    if let (Some(schema), toml::Value::Table(inner)) = (item_schema, item) {
+                        push_table(out, root, Some(&key_path), schema, inner);
                     }
-                    "gemini" => {
-                        if let Ok(key) = std::env::var("GEMINI_API_KEY") {
-                            provider.api_key = Some(key);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn placeholder(schema: &SchemaObject) -> &'static str {
+    use schemars::schema::InstanceType;
+    let has = |kind: InstanceType| schema.instance_type.as_ref().is_some_and(|types| types.contains(&kind));
+    if has(InstanceType::Object) {
+        "{}"
+    } else if has(InstanceType::Integer) {
+        "0"
+    } else if has(InstanceType::Number) {
+        "0.0"
+    } else if has(InstanceType::Boolean) {
+        "false"
+    } else {
+        "\"\""
+    }
+}
+
+/// Removes a dotted key (as taken by `Config::unset_key`) from parsed settings.
+/// Returns whether it was there.
+pub fn remove_key(value: &mut toml::Value, key: &str) -> Result<bool> {
+    let segments = key_segments(key)?;
+    let (last, parents) = segments.split_last().expect("key_segments is never empty");
+    Ok(match get_path_mut(value, parents) {
+        Some(toml::Value::Table(table)) => table.remove(last.as_str()).is_some(),
+        Some(toml::Value::Array(items)) => match array_index(items, last) {
+            Some(index) => {
+                items.remove(index);
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    })
+}
+
+fn key_segments(key: &str) -> Result<Vec<String>> {
+    let segments: Vec<String> = key.split('.').map(|s| s.to_string()).collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(anyhow::anyhow!("Invalid configuration key '{}': use dotted names like performance.local_timeout_seconds", key));
+    }
+    Ok(segments)
+}
+
+/// Where the profile `name` lives when it has its own file. Names are file stems, so anything
+/// that could point outside `profiles/` is rejected.
+pub fn profile_path(config_dir: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name == "." || name.contains("..") || name.contains(['/', '\\', '\0']) {
+        return Err(anyhow::anyhow!("Invalid profile name '{}': path separators and '..' are not allowed", name));
+    }
+    Ok(config_dir.join("profiles").join(format!("{}.toml", name)))
+}
+
+fn find_profile(config: &toml::Value, config_dir: &Path, name: &str) -> Result<toml::Value> {
+    let path = profile_path(config_dir, name)?;
+    if let Some(profile) = config.get("profile").and_then(|p| p.get(name)) {
+        return Ok(profile.clone());
+    }
+    if path.exists() {
+        let content = std::fs::read_to_string(&path)?;
+        return toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid profile {}: {}", path.display(), e));
+    }
+    Err(anyhow::anyhow!(
+        "Unknown profile '{}': add a [profile.{}] section to config.toml or create {}",
+        name, name, path.display()
+    ))
+}
+
+/// Rewrites the TOML document `text` so it holds `after` wherever that differs from `before`:
+/// changed keys are set and removed ones deleted, while everything else in the file (comments,
+/// layout, settings it leaves at their defaults) stays as it was.
+#[cfg(feature = "native")]
+pub fn edit_toml(text: &str, before: &toml::Value, after: &toml::Value) -> Result<String> {
+    let mut document: toml_edit::DocumentMut = text.parse()?;
+    if let (Some(before), Some(after)) = (before.as_table(), after.as_table()) {
+        apply_changes(document.as_table_mut(), before, after);
+    }
+    Ok(document.to_string())
+}
+
+#[cfg(feature = "native")]
+fn apply_changes(table: &mut toml_edit::Table, before: &toml::Table, after: &toml::Table) {
+    for (key, new) in after {
+        let old = before.get(key);
+        if old == Some(new) {
+            continue;
+        }
+        match (old, new) {
+            (Some(toml::Value::Table(old)), toml::Value::Table(new)) => {
+                if !table.contains_key(key) {
+                    let mut child = toml_edit::Table::new();
+                    child.set_implicit(true);
+                    table.insert(key, toml_edit::Item::Table(child));
+                }
+                if let Some(child) = table.get_mut(key).and_then(toml_edit::Item::as_table_mut) {
+                    apply_changes(child, old, new);
+                    continue;
+                }
+            }
+            // `[[cloud_providers]]` entries are edited in place, so keys the file leaves out
+            // (an API key from the keyring) aren't written into it
+            (Some(toml::Value::Array(old)), toml::Value::Array(new)) if old.len() == new.len() => {
+                if let Some(tables) = table.get_mut(key).and_then(toml_edit::Item::as_array_of_tables_mut) {
+                    let pairs: Option<Vec<_>> = old.iter().zip(new)
+                        .map(|(old, new)| Some((old.as_table()?, new.as_table()?)))
+                        .collect();
+                    if let Some(pairs) = pairs.filter(|pairs| pairs.len() == tables.len()) {
+                        for (child, (old, new)) in tables.iter_mut().zip(pairs) {
+                            apply_changes(child, old, new);
                         }
+                        continue;
                     }
-                    "openrouter" => {
-                        if let Ok(key) = std::env::var("OPEN_ROUTER") {
-                            provider.api_key = Some(key);
-                        }
+                }
+            }
+            _ => {}
+        }
+        table.insert(key, to_item(new));
+    }
+    for key in before.keys() {
+        if !after.contains_key(key) {
+            table.remove(key);
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+fn to_item(value: &toml::Value) -> toml_edit::Item {
+    let mut wrapper = toml::Table::new();
+    wrapper.insert("value".to_string(), value.clone());
+    toml::to_string(&wrapper).ok()
+        .and_then(|text| text.parse::<toml_edit::DocumentMut>().ok())
+        .and_then(|mut document| document.remove("value"))
+        .unwrap_or_default()
+}
+
+/// Undoes `merge_toml(value, overlay)`, taking every key the overlay set from `global` instead.
+fn restore_overridden(value: &mut toml::Value, overlay: &toml::Value, global: &toml::Value) {
+    let (Some(table), Some(overlay)) = (value.as_table_mut(), overlay.as_table()) else { return };
+    for (key, overridden) in overlay {
+        match (overridden, global.get(key)) {
+            (toml::Value::Table(_), Some(global_value @ toml::Value::Table(_))) => {
+                if let Some(current) = table.get_mut(key) {
+                    restore_overridden(current, overridden, global_value);
+                }
+            }
+            (_, Some(global_value)) => {
+                table.insert(key.clone(), global_value.clone());
+            }
+            (_, None) => {
+                table.remove(key);
+            }
+        }
+    }
+}
+
+/// Merges `overlay` into `base`: tables merge key by key, anything else (including arrays) is replaced.
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
                     }
-                    _ => {}
                 }
             }
-            
-            Ok(config)
-        } else {
-            Ok(Self::default())
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Applies `AIR_SECTION__FIELD=value` variables to a parsed config. `__` separates levels;
+/// array entries are addressed by index or, for tables with a `name`, by that name
+/// (e.g. `AIR_CLOUD_PROVIDERS__GEMINI__MODEL`). Values are parsed as TOML, falling back to a string.
+fn apply_env_overrides(config: &mut toml::Value, vars: impl Iterator<Item = (String, String)>) {
+    for (name, raw) in vars {
+        let Some(path) = name.strip_prefix("AIR_") else { continue };
+        let segments: Vec<String> = path.split("__").map(|s| s.to_lowercase()).collect();
+        // Every config field lives inside a section, so single-segment names aren't ours
+        if segments.len() < 2 || segments.iter().any(|s| s.is_empty()) {
+            continue;
+        }
+        if !set_path(config, &segments, &raw) {
+            tracing::warn!("Ignoring {}: no matching configuration entry", name);
         }
     }
 }
 
+/// Position of an array entry addressed by index or, for tables with a `name`, by that name.
+fn array_index(items: &[toml::Value], segment: &str) -> Option<usize> {
+    segment.parse::<usize>().ok().filter(|i| *i < items.len()).or_else(|| {
+        items.iter().position(|item| item.get("name").and_then(|n| n.as_str()).is_some_and(|n| n.eq_ignore_ascii_case(segment)))
+    })
+}
+
+fn get_path<'a>(node: &'a toml::Value, segments: &[String]) -> Option<&'a toml::Value> {
+    segments.iter().try_fold(node, |node, segment| match node {
+        toml::Value::Table(table) => table.get(segment.as_str()),
+        toml::Value::Array(items) => array_index(items, segment).map(|i| &items[i]),
+        _ => None,
+    })
+}
+
+fn get_path_mut<'a>(node: &'a mut toml::Value, segments: &[String]) -> Option<&'a mut toml::Value> {
+    segments.iter().try_fold(node, |node, segment| match node {
+        toml::Value::Table(table) => table.get_mut(segment.as_str()),
+        toml::Value::Array(items) => array_index(items, segment).map(move |i| &mut items[i]),
+        _ => None,
+    })
+}
+
+fn set_path(node: &mut toml::Value, segments: &[String], raw: &str) -> bool {
+    let (head, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    let child = match node {
+        toml::Value::Table(table) => {
+            if rest.is_empty() {
+                let value = parse_env_value(raw, head, table.get(head.as_str()));
+                table.insert(head.clone(), value);
+                return true;
+            }
+            table.entry(head.clone()).or_insert_with(|| toml::Value::Table(toml::map::Map::new()))
+        }
+        toml::Value::Array(items) => {
+            match array_index(items, head).and_then(|i| items.get_mut(i)) {
+                Some(item) if rest.is_empty() => {
+                    *item = parse_env_value(raw, head, Some(item));
+                    return true;
+                }
+                Some(item) => item,
+                None => return false,
+            }
+        }
+        _ => return false,
+    };
+    set_path(child, rest, raw)
+}
+
+fn parse_env_value(raw: &str, key: &str, existing: Option<&toml::Value>) -> toml::Value {
+    // The value already there says what the field holds: keep string fields strings even when
+    // the value looks like a number (API keys, model names), and parse everything else as TOML.
+    // Unset optional fields have no existing value to go by, so fall back to the field name;
+    // `true`/`false` is never a key or a model name (`auto_select_model`, say).
+    let string_field = match existing {
+        Some(value) => value.is_str(),
+        None => {
+            !matches!(raw, "true" | "false")
+                && ["key", "path", "model", "url", "name", "shell", "device"].iter().any(|suffix| key.ends_with(suffix))
+        }
+    };
+    if string_field {
+        return toml::Value::String(raw.to_string());
+    }
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -147,6 +1601,18 @@ impl Default for Config {
                     temperature: 0.7,
                     timeout_seconds: 30,
                     enabled: true,
+                    auto_select_model: true,
+                    native_tools: true,
+                    structured_output: true,
+                    api_version: None,
+                    azure_ad: false,
+                    extra: toml::Table::new(),
+                    headers: BTreeMap::new(),
+                    quality_score: None,
+                    latency_ms: None,
+                    cost_per_million_tokens: None,
+                    requests_per_minute: None,
+                    tokens_per_minute: None,
                 },
                 // CloudProviderConfig {
                 //     name: "openrouter".to_string(),
@@ -163,7 +1629,266 @@ impl Default for Config {
                 fallback_threshold_ms: 3000,
                 quality_threshold: 0.8,
                 local_timeout_seconds: 300,
+                prefer_local_for_simple_queries: true,
             },
+            filesystem: FileSystemConfig::default(),
+            command: CommandConfig::default(),
+            voice: VoiceConfig::default(),
+            planner: PlannerConfig::default(),
+            web: WebConfig::default(),
+            knowledge: KnowledgeConfig::default(),
+            language: None,
+            default_mode: QueryMode::Auto,
+            modes: ModesConfig::default(),
+            server: ServerConfig::default(),
+            vcr: VcrConfig::default(),
+            reflection: ReflectionConfig::default(),
+            scripting: ScriptingConfig::default(),
+            desktop: DesktopConfig::default(),
+            test_fix: TestFixConfig::default(),
+            github: GitHubConfig::default(),
+            tracker: TrackerConfig::default(),
+            automation: AutomationConfig::default(),
+            pricing: PricingConfig::default(),
+            routing: RoutingConfig::default(),
+            persona: PersonaConfig::default(),
+            profiles: BTreeMap::new(),
+            active_profile: None,
+            project: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persona_identity() {
+        let mut persona = PersonaConfig::default();
+        assert_eq!(persona.identity(), "You are AIR. This identity is fixed.");
+
+        persona.name = "Jarvis".to_string();
+        persona.tone = Some("dry and concise.".to_string());
+        assert_eq!(persona.identity(), "You are Jarvis. This identity is fixed.\nTone: dry and concise.");
+
+        persona.system_prompt = Some("You are a pirate.".to_string());
+        persona.tone = Some("  ".to_string());
+        assert_eq!(persona.identity(), "You are a pirate.");
+    }
+
+    fn overridden(vars: &[(&str, &str)]) -> Config {
+        let mut value = toml::Value::try_from(Config::default()).unwrap();
+        apply_env_overrides(&mut value, vars.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        value.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_env_overrides_nested_fields() {
+        let config = overridden(&[
+            ("AIR_PERFORMANCE__LOCAL_TIMEOUT_SECONDS", "42"),
+            ("AIR_COMMAND__AUTO_APPROVE", "[\"ls *\"]"),
+            ("AIR_COMMAND__SHELL", "bash"),
+            ("AIR_WEB__RESPECT_ROBOTS_TXT", "false"),
+            ("AIR_UNRELATED", "ignored"),
+        ]);
+        assert_eq!(config.performance.local_timeout_seconds, 42);
+        assert_eq!(config.command.auto_approve, vec!["ls *"]);
+        assert_eq!(config.command.shell.as_deref(), Some("bash"));
+        assert!(!config.web.respect_robots_txt);
+    }
+
+    #[test]
+    fn test_profile_overlay_merges_tables_and_replaces_arrays() {
+        let mut base: toml::Value = toml::from_str(r#"
+            [performance]
+            fallback_threshold_ms = 3000
+            local_timeout_seconds = 300
+
+            [command]
+            auto_approve = ["ls *", "git *"]
+
+            [profile.work.performance]
+            local_timeout_seconds = 60
+
+            [profile.work.command]
+            auto_approve = ["ls *"]
+        "#).unwrap();
+
+        let overlay = find_profile(&base, Path::new("/nonexistent"), "work").unwrap();
+        merge_toml(&mut base, overlay);
+
+        assert_eq!(base["performance"]["local_timeout_seconds"].as_integer(), Some(60));
+        assert_eq!(base["performance"]["fallback_threshold_ms"].as_integer(), Some(3000));
+        assert_eq!(base["command"]["auto_approve"].as_array().unwrap().len(), 1);
+        assert!(find_profile(&base, Path::new("/nonexistent"), "home").is_err());
+    }
+
+    #[test]
+    fn test_profile_names_stay_in_profiles_dir() {
+        let dir = Path::new("/config");
+        assert_eq!(profile_path(dir, "work").unwrap(), dir.join("profiles").join("work.toml"));
+        for name in ["", "..", "../config", "a/b", "a\\b", "x..y"] {
+            assert!(profile_path(dir, name).is_err(), "{:?}", name);
         }
+        let base: toml::Value = toml::from_str("[profile.\"../x\"]\nlanguage = \"en\"").unwrap();
+        assert!(find_profile(&base, dir, "../x").is_err());
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_edit_toml_writes_only_changes() {
+        let text = "# my settings\n[performance]\nlocal_timeout_seconds = 300 # slow box\n\n[[cloud_providers]]\nname = \"gemini\"\nmodel = \"old\"\n";
+        let before: toml::Value = toml::from_str(r#"
+            language = "de"
+            [performance]
+            local_timeout_seconds = 300
+            fallback_threshold_ms = 3000
+            [[cloud_providers]]
+            name = "gemini"
+            model = "old"
+            api_key = "from-keyring"
+        "#).unwrap();
+        let mut after = before.clone();
+        after["performance"]["local_timeout_seconds"] = toml::Value::Integer(60);
+        after["cloud_providers"][0]["model"] = toml::Value::String("new".to_string());
+        after.as_table_mut().unwrap().remove("language");
+
+        let edited = edit_toml(text, &before, &after).unwrap();
+        assert!(edited.contains("# my settings"), "{}", edited);
+        assert!(edited.contains("local_timeout_seconds = 60"), "{}", edited);
+        assert!(edited.contains("model = \"new\""), "{}", edited);
+        assert!(!edited.contains("fallback_threshold_ms"), "{}", edited);
+        assert!(!edited.contains("from-keyring"), "{}", edited);
+        assert_eq!(edit_toml(text, &before, &before).unwrap(), text);
+    }
+
+    #[test]
+    fn test_restore_overridden_undoes_project_overlay() {
+        let global: toml::Value = toml::from_str(r#"
+            language = "en"
+            [filesystem]
+            workspace_roots = []
+            sandbox_enabled = true
+        "#).unwrap();
+        let overlay: toml::Value = toml::from_str(r#"
+            persona = "pirate"
+            [filesystem]
+            workspace_roots = ["/work/app"]
+        "#).unwrap();
+
+        let mut value = global.clone();
+        merge_toml(&mut value, overlay.clone());
+        // An edit made while the project was active survives; the project's own keys don't
+        value["language"] = toml::Value::String("fr".to_string());
+        restore_overridden(&mut value, &overlay, &global);
+
+        assert_eq!(value["language"].as_str(), Some("fr"));
+        assert_eq!(value["filesystem"]["workspace_roots"].as_array().unwrap().len(), 0);
+        assert_eq!(value["filesystem"]["sandbox_enabled"].as_bool(), Some(true));
+        assert!(value.get("persona").is_none());
+    }
+
+    #[test]
+    fn test_env_overrides_providers_by_name_and_index() {
+        let config = overridden(&[
+            ("AIR_CLOUD_PROVIDERS__GEMINI__MODEL", "gemini-1.5-flash"),
+            ("AIR_CLOUD_PROVIDERS__0__API_KEY", "12345"),
+            ("AIR_CLOUD_PROVIDERS__MISSING__MODEL", "x"),
+        ]);
+        assert_eq!(config.cloud_providers[0].model, "gemini-1.5-flash");
+        assert_eq!(config.cloud_providers[0].api_key.as_deref(), Some("12345"));
+    }
+
+    #[test]
+    fn test_boolean_fields_named_like_strings() {
+        let config = overridden(&[("AIR_CLOUD_PROVIDERS__GEMINI__AUTO_SELECT_MODEL", "false")]);
+        assert!(!config.cloud_providers[0].auto_select_model);
+
+        // A provider written without the field has no existing value to go by
+        let mut value = toml::Value::try_from(Config::default()).unwrap();
+        value["cloud_providers"][0].as_table_mut().unwrap().remove("auto_select_model");
+        apply_env_overrides(&mut value, std::iter::once(("AIR_CLOUD_PROVIDERS__GEMINI__AUTO_SELECT_MODEL".to_string(), "false".to_string())));
+        assert_eq!(value["cloud_providers"][0]["auto_select_model"].as_bool(), Some(false));
+
+        let mut config = Config::default();
+        config.set_key("cloud_providers.gemini.auto_select_model", "false").unwrap();
+        assert!(!config.cloud_providers[0].auto_select_model);
+        config.set_key("cloud_providers.gemini.model", "1.5").unwrap();
+        assert_eq!(config.cloud_providers[0].model, "1.5");
+    }
+
+    #[test]
+    fn test_get_set_unset_keys() {
+        let mut config = Config::default();
+        config.set_key("performance.local_timeout_seconds", "45").unwrap();
+        config.set_key("cloud_providers.gemini.enabled", "false").unwrap();
+        config.set_key("command.shell", "bash").unwrap();
+        assert_eq!(config.performance.local_timeout_seconds, 45);
+        assert!(!config.cloud_providers[0].enabled);
+        assert_eq!(config.get_key("command.shell").unwrap().as_str(), Some("bash"));
+
+        assert!(config.set_key("performance.local_timeout_seconds", "soon").is_err());
+        assert!(config.set_key("performance.no_such_field", "1").is_err());
+        assert!(config.set_key("cloud_providers.missing.model", "x").is_err());
+
+        config.unset_key("command.shell").unwrap();
+        assert!(config.command.shell.is_none());
+        config.unset_key("command.timeout_seconds").unwrap();
+        assert_eq!(config.command.timeout_seconds, default_command_timeout());
+        assert!(config.unset_key("performance.local_timeout_seconds").is_err());
+    }
+
+    #[test]
+    fn test_mode_params_fall_back_per_mode() {
+        let mut config = Config::default();
+        config.modes.cloud_only.max_tokens = Some(4000);
+        config.modes.auto.timeout_seconds = Some(5);
+
+        let cloud = config.mode_params(&QueryMode::CloudOnly);
+        assert_eq!(cloud.max_tokens, 4000);
+        assert_eq!(cloud.timeout, Duration::from_secs(CLOUD_ONLY_TIMEOUT_SECONDS));
+
+        let auto = config.mode_params(&QueryMode::Auto);
+        assert_eq!(auto.max_tokens, config.local_model.max_tokens);
+        assert_eq!(auto.timeout, Duration::from_secs(5));
+
+        let sample = Config::sample_toml().unwrap().replace("default_mode = \"auto\"", "default_mode = \"cloud_only\"");
+        let parsed: Config = toml::from_str(&sample).unwrap();
+        assert_eq!(parsed.default_mode, QueryMode::CloudOnly);
+    }
+
+    #[test]
+    fn test_pricing_lookup_and_cost() {
+        let pricing: PricingConfig = toml::from_str(r#"
+            [models]
+            "gpt-4o-mini" = { input = 0.15, output = 0.6 }
+        "#).unwrap();
+        let price = pricing.price_for("OpenAI-gpt-4o-mini").unwrap();
+        assert_eq!(pricing.price_for("gpt-4o-mini"), Some(price));
+        assert!(pricing.price_for("OpenAI-gpt-4o").is_none());
+
+        assert!((price.cost_usd(1_000_000, 2_000_000) - 0.75).abs() < 1e-9);
+        assert!((price.cost_usd(0, 1_000_000) - 0.6).abs() < 1e-9);
+        assert!((ModelPrice::blended(2.0).cost_usd(500_000, 1_000_000) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sample_toml_documents_and_round_trips_defaults() {
+        let sample = Config::sample_toml().unwrap();
+        assert!(sample.contains("# How long to wait for the local model before falling back (seconds).\nlocal_timeout_seconds = 300"));
+        assert!(sample.contains("[[cloud_providers]]"));
+        assert!(sample.contains("[modes.cloud_only]\n"));
+        assert!(sample.contains("# browser_path = \"\""));
+
+        let parsed: Config = toml::from_str(&sample).unwrap();
+        assert_eq!(parsed.command.auto_approve, default_auto_approve());
+        assert_eq!(parsed.performance.local_timeout_seconds, 300);
+        assert!(parsed.cloud_providers[0].api_key.is_none());
+
+        let schema = Config::json_schema().unwrap();
+        assert!(schema["properties"]["web"].is_object());
+        assert!(schema["definitions"]["PricingConfig"]["properties"]["models"].is_object());
+        assert!(schema["definitions"]["CommandConfig"]["properties"]["auto_approve"].is_object());
     }
 }
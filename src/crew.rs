@@ -0,0 +1,234 @@
+//! Multi-agent crews (`air crew run <workflow> <goal>`): named members with their own persona,
+//! tools and query mode work on a goal one after another, each seeing the notes of those before
+//! it, and a final synthesis step turns the notes into the answer.
+//!
+//! Workflows are the built-in `research` (researcher → analyst → writer) or TOML files in the
+//! config directory's `crews/` folder:
+//!
+//! ```toml
+//! description = "Review a change"
+//!
+//! [[members]]
+//! name = "reviewer"
+//! persona = "You are a strict code reviewer."
+//! task = "List the problems in: {goal}"
+//! tools = ["filesystem"]
+//!
+//! [synthesis]
+//! name = "lead"
+//! persona = "You are the team lead."
+//! task = "Decide what must change before merging."
+//! ```
+
+use crate::agent::{AIAgent, QueryMode};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::info;
+
+pub const BUILTIN_CREWS: &[&str] = &["research"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrewMember {
+    pub name: String,
+    /// Who the member is, sent ahead of everything else.
+    pub persona: String,
+    /// What the member should do; `{goal}` is replaced with the crew's goal.
+    pub task: String,
+    /// Tools the member may call. Unset allows every tool; an empty list asks the model directly.
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    /// Query mode for this member; unset uses the configured default.
+    #[serde(default)]
+    pub mode: Option<QueryMode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Crew {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub members: Vec<CrewMember>,
+    /// Writes the final answer from everyone's notes.
+    pub synthesis: CrewMember,
+}
+
+/// One member's contribution.
+#[derive(Debug, Clone, Serialize)]
+pub struct CrewStep {
+    pub member: String,
+    pub output: String,
+    pub model_used: String,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CrewReport {
+    pub crew: String,
+    pub goal: String,
+    /// Member steps in order, the synthesis last.
+    pub steps: Vec<CrewStep>,
+    pub answer: String,
+}
+
+impl Crew {
+    /// A built-in crew or `crews/<name>.toml` from the config directory.
+    pub fn load(name: &str) -> Result<Self> {
+        let path = crews_dir()?.join(format!("{}.toml", name));
+        let mut crew = if path.exists() {
+            let text = std::fs::read_to_string(&path)?;
+            toml::from_str::<Crew>(&text).map_err(|e| anyhow!("Invalid crew {}: {}", path.display(), e))?
+        } else {
+            builtin(name).ok_or_else(|| anyhow!(
+                "Unknown crew '{}': use one of {} or add {}", name, available().join(", "), path.display()
+            ))?
+        };
+        crew.name = name.to_string();
+        if crew.members.is_empty() {
+            return Err(anyhow!("Crew '{}' has no members", name));
+        }
+        Ok(crew)
+    }
+}
+
+/// Built-in crews plus those in the config directory.
+pub fn available() -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN_CREWS.iter().map(|s| s.to_string()).collect();
+    if let Ok(entries) = crews_dir().and_then(|dir| Ok(std::fs::read_dir(dir)?)) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "toml") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    if !names.iter().any(|n| n == stem) {
+                        names.push(stem.to_string());
+                    }
+                }
+            }
+        }
+    }
+    names
+}
+
+fn crews_dir() -> Result<PathBuf> {
+    Ok(crate::utils::paths::get_air_config_dir()?.join("crews"))
+}
+
+fn builtin(name: &str) -> Option<Crew> {
+    let member = |name: &str, persona: &str, task: &str, tools: Option<&[&str]>| CrewMember {
+        name: name.to_string(),
+        persona: persona.to_string(),
+        task: task.to_string(),
+        tools: tools.map(|t| t.iter().map(|s| s.to_string()).collect()),
+        mode: None,
+    };
+    match name {
+        "research" => Some(Crew {
+            name: name.to_string(),
+            description: "Researcher gathers facts, analyst weighs them, writer produces the report".to_string(),
+            members: vec![
+                member(
+                    "researcher",
+                    "You are a meticulous researcher. You collect facts, figures and sources and never speculate.",
+                    "Gather the key facts needed for: {goal}. List them as bullet points with sources where you have them.",
+                    Some(&["web", "knowledge", "memory", "calculator"]),
+                ),
+                member(
+                    "analyst",
+                    "You are a sharp analyst. You question evidence, spot gaps and draw conclusions.",
+                    "Analyze the researcher's findings for: {goal}. Point out what is well supported, what is uncertain, and the main takeaways.",
+                    Some(&["calculator"]),
+                ),
+            ],
+            synthesis: member(
+                "writer",
+                "You are a clear, concise writer.",
+                "Write the final answer to: {goal}. Use the team's notes, keep what is well supported and say what remains uncertain.",
+                Some(&[]),
+            ),
+        }),
+        _ => None,
+    }
+}
+
+/// Runs the crew on `goal`. Each member's notes are also kept in session memory as
+/// `crew:<crew>:<member>`, where tools such as `memory` can read them.
+pub async fn run(agent: &AIAgent, crew: &Crew, goal: &str) -> Result<CrewReport> {
+    let mut steps: Vec<CrewStep> = Vec::new();
+    for member in crew.members.iter().chain(std::iter::once(&crew.synthesis)) {
+        info!("👥 {} is working on the goal", member.name);
+        let prompt = member_prompt(member, goal, &steps);
+        let start = Instant::now();
+        let mode = member.mode.clone().unwrap_or_else(|| agent.config().default_mode.clone());
+        let response = match &member.tools {
+            Some(tools) if tools.is_empty() => agent.query_in_mode(&prompt, &mode).await,
+            Some(tools) => agent.with_tools(tools).query_with_tools_in_mode(&prompt, &mode).await,
+            None => agent.query_with_tools_in_mode(&prompt, &mode).await,
+        }
+        .map_err(|e| anyhow!("{} failed: {}", member.name, e))?;
+
+        agent.store_ram_memory(&format!("crew:{}:{}", crew.name, member.name), &response.content).await.ok();
+        steps.push(CrewStep {
+            member: member.name.clone(),
+            output: response.content,
+            model_used: response.model_used,
+            elapsed_ms: start.elapsed().as_millis() as u64,
+        });
+    }
+
+    let answer = steps.last().map(|s| s.output.clone()).unwrap_or_default();
+    Ok(CrewReport { crew: crew.name.clone(), goal: goal.to_string(), steps, answer })
+}
+
+fn member_prompt(member: &CrewMember, goal: &str, notes: &[CrewStep]) -> String {
+    let mut prompt = format!("{}\n\nTeam goal: {}\n", member.persona, goal);
+    if !notes.is_empty() {
+        prompt.push_str("\nNotes from the team so far:\n");
+        for note in notes {
+            prompt.push_str(&format!("\n## {}\n{}\n", note.member, note.output.trim()));
+        }
+    }
+    prompt.push_str(&format!("\nYour task: {}", member.task.replace("{goal}", goal)));
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_prompt_carries_notes() {
+        let crew = builtin("research").unwrap();
+        let notes = vec![CrewStep {
+            member: "researcher".to_string(),
+            output: "Fact one.".to_string(),
+            model_used: "test".to_string(),
+            elapsed_ms: 1,
+        }];
+        let prompt = member_prompt(&crew.members[1], "rust adoption", &notes);
+        assert!(prompt.starts_with("You are a sharp analyst"));
+        assert!(prompt.contains("## researcher\nFact one."));
+        assert!(prompt.ends_with("Analyze the researcher's findings for: rust adoption. Point out what is well supported, what is uncertain, and the main takeaways."));
+    }
+
+    #[test]
+    fn test_crew_file_format() {
+        let crew: Crew = toml::from_str(r#"
+            [[members]]
+            name = "reviewer"
+            persona = "You review code."
+            task = "Review {goal}"
+            tools = ["filesystem"]
+            mode = "cloud_only"
+
+            [synthesis]
+            name = "lead"
+            persona = "You lead."
+            task = "Decide."
+        "#).unwrap();
+        assert_eq!(crew.members[0].tools.as_deref(), Some(&["filesystem".to_string()][..]));
+        assert_eq!(crew.members[0].mode, Some(QueryMode::CloudOnly));
+        assert!(crew.synthesis.tools.is_none());
+    }
+}
@@ -0,0 +1,345 @@
+//! Background daemon (`air daemon`) that keeps one initialized [`AIAgent`] (models, memory
+//! database, embeddings) alive, so one-shot `air "question"` invocations skip start-up.
+//!
+//! Clients talk to it over a Unix socket in the data directory, one newline-delimited JSON
+//! request and response per connection.
+//!
+//! The agent's session is snapshotted to the data directory after every query and at shutdown,
+//! and restored on start, so a crashed or restarted daemon carries on with the same history,
+//! planner tasks and caches.
+//!
+//! With `planner.desktop_notifications` on, the daemon also checks for due planner tasks every
+//! `planner.reminder_interval_minutes` and raises a notification; clients can pause that and
+//! toggle the agent's safe mode (see `air desktop`).
+
+use crate::agent::{AIAgent, AgentSnapshot, QueryMode};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    Query {
+        prompt: String,
+        /// Falls back to the daemon's default_mode when absent.
+        mode: Option<QueryMode>,
+        /// The client's working directory. Tools run in the daemon's directory (with its project
+        /// overlay and sandbox), so the daemon declines queries from anywhere else.
+        #[serde(default)]
+        cwd: Option<PathBuf>,
+    },
+    Status,
+    /// Limit the model to read-only tools (see `AIAgent::set_safe_mode`).
+    SetSafeMode { enabled: bool },
+    PauseReminders { paused: bool },
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonResponse {
+    Answer { content: String, model_used: String },
+    Error { message: String },
+    /// The query came from another working directory; the client should answer it itself.
+    WrongDirectory,
+    /// Answers `Status`, `SetSafeMode` and `PauseReminders`.
+    Status(DaemonStatus),
+    Stopping,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub uptime_seconds: u64,
+    /// Queries answered since the daemon started.
+    pub queries: u64,
+    /// The agent's providers, local model first.
+    pub providers: Vec<String>,
+    pub safe_mode: bool,
+    pub reminders_paused: bool,
+}
+
+/// Location of the daemon's socket, inside a directory only the user can enter.
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(crate::utils::paths::get_air_data_dir()?.join("daemon").join("daemon.sock"))
+}
+
+/// Where the daemon keeps its session snapshot.
+pub fn snapshot_path() -> Result<PathBuf> {
+    Ok(crate::utils::paths::get_air_data_dir()?.join("daemon_snapshot.json"))
+}
+
+#[cfg(unix)]
+pub use unix::{is_running, run, send};
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use crate::utils::approval;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::{Mutex, Notify};
+    use tracing::{info, warn};
+
+    async fn restore_snapshot(agent: &AIAgent) {
+        let Ok(path) = snapshot_path() else { return };
+        if !path.exists() {
+            return;
+        }
+        let restored = match AgentSnapshot::load(&path) {
+            Ok(snapshot) => agent.restore(snapshot).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = restored {
+            warn!("Ignoring daemon snapshot: {}", e);
+        }
+    }
+
+    struct State {
+        started: Instant,
+        queries: AtomicU64,
+        reminders_paused: AtomicBool,
+    }
+
+    impl State {
+        fn status(&self, agent: &AIAgent) -> DaemonStatus {
+            DaemonStatus {
+                uptime_seconds: self.started.elapsed().as_secs(),
+                queries: self.queries.load(Ordering::Relaxed),
+                providers: agent.providers().iter().map(|p| p.name().to_string()).collect(),
+                safe_mode: agent.safe_mode(),
+                reminders_paused: self.reminders_paused.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Notifies about overdue and due-today tasks on the configured interval, unless paused.
+    fn spawn_reminders(agent: AIAgent, state: Arc<State>) -> Option<tokio::task::JoinHandle<()>> {
+        let planner = &agent.config().planner;
+        if !planner.desktop_notifications || planner.reminder_interval_minutes == 0 {
+            return None;
+        }
+        let period = Duration::from_secs(planner.reminder_interval_minutes * 60);
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if state.reminders_paused.load(Ordering::Relaxed) {
+                    continue;
+                }
+                if let Err(e) = agent.execute_tool("planner", "due_tasks", serde_json::json!({ "notify": true })).await {
+                    warn!("Reminder check failed: {}", e);
+                }
+            }
+        }))
+    }
+
+    /// `lock` keeps concurrent connections from writing the file at the same time.
+    async fn save_snapshot(agent: &AIAgent, lock: &Mutex<()>) {
+        let _guard = lock.lock().await;
+        let saved = match (agent.snapshot().await, snapshot_path()) {
+            (Ok(snapshot), Ok(path)) => snapshot.save(&path),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+        if let Err(e) = saved {
+            warn!("Failed to save daemon snapshot: {}", e);
+        }
+    }
+
+    /// Serves queries on the socket until a `Stop` request or Ctrl+C.
+    pub async fn run(agent: AIAgent) -> Result<()> {
+        // Clients can't answer prompts shown on the daemon's terminal
+        approval::disable_prompts();
+        let path = socket_path()?;
+        // The daemon runs tools with the user's permissions, so only the user may connect.
+        // The directory is locked down before binding so the socket is never reachable.
+        let dir = path.parent().ok_or_else(|| anyhow!("Invalid socket path"))?;
+        std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+        if path.exists() {
+            if UnixStream::connect(&path).await.is_ok() {
+                return Err(anyhow!("A daemon is already running ({})", path.display()));
+            }
+            // Left behind by a daemon that didn't shut down cleanly
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        info!("🔌 AIR daemon listening on {}", path.display());
+
+        restore_snapshot(&agent).await;
+        let stop = Arc::new(Notify::new());
+        let snapshot_lock = Arc::new(Mutex::new(()));
+        let state = Arc::new(State {
+            started: Instant::now(),
+            queries: AtomicU64::new(0),
+            reminders_paused: AtomicBool::new(false),
+        });
+        let reminders = spawn_reminders(agent.clone(), state.clone());
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = match accepted {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            warn!("Daemon accept failed: {}", e);
+                            continue;
+                        }
+                    };
+                    let agent = agent.clone();
+                    let stop = stop.clone();
+                    let snapshot_lock = snapshot_lock.clone();
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, &agent, &stop, &snapshot_lock, &state).await {
+                            warn!("Daemon connection failed: {}", e);
+                        }
+                    });
+                }
+                _ = stop.notified() => break,
+                _ = tokio::signal::ctrl_c() => break,
+            }
+        }
+
+        if let Some(reminders) = reminders {
+            reminders.abort();
+        }
+        save_snapshot(&agent, &snapshot_lock).await;
+        std::fs::remove_file(&path).ok();
+        info!("AIR daemon stopped");
+        Ok(())
+    }
+
+    async fn handle_connection(
+        stream: UnixStream,
+        agent: &AIAgent,
+        stop: &Notify,
+        snapshot_lock: &Mutex<()>,
+        state: &State,
+    ) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await?;
+
+        let response = match serde_json::from_str::<DaemonRequest>(&line) {
+            Ok(DaemonRequest::Query { cwd: Some(cwd), .. }) if !same_directory(&cwd) => DaemonResponse::WrongDirectory,
+            Ok(DaemonRequest::Query { prompt, mode, .. }) => {
+                let mode = mode.unwrap_or_else(|| agent.config().default_mode.clone());
+                match agent.query_with_tools_in_mode(&prompt, &mode).await {
+                    Ok(response) => {
+                        state.queries.fetch_add(1, Ordering::Relaxed);
+                        save_snapshot(agent, snapshot_lock).await;
+                        DaemonResponse::Answer { content: response.content, model_used: response.model_used }
+                    }
+                    Err(e) => DaemonResponse::Error { message: e.to_string() },
+                }
+            }
+            Ok(DaemonRequest::Status) => DaemonResponse::Status(state.status(agent)),
+            Ok(DaemonRequest::SetSafeMode { enabled }) => {
+                agent.set_safe_mode(enabled);
+                info!("🛡️  Safe mode {}", if enabled { "on" } else { "off" });
+                DaemonResponse::Status(state.status(agent))
+            }
+            Ok(DaemonRequest::PauseReminders { paused }) => {
+                state.reminders_paused.store(paused, Ordering::Relaxed);
+                DaemonResponse::Status(state.status(agent))
+            }
+            Ok(DaemonRequest::Stop) => {
+                stop.notify_one();
+                DaemonResponse::Stopping
+            }
+            Err(e) => DaemonResponse::Error { message: format!("Invalid request: {}", e) },
+        };
+
+        let mut payload = serde_json::to_string(&response)?;
+        payload.push('\n');
+        writer.write_all(payload.as_bytes()).await?;
+        Ok(())
+    }
+
+    fn same_directory(cwd: &std::path::Path) -> bool {
+        match (std::env::current_dir().and_then(|d| d.canonicalize()), cwd.canonicalize()) {
+            (Ok(own), Ok(theirs)) => own == theirs,
+            _ => false,
+        }
+    }
+
+    pub async fn is_running() -> bool {
+        match socket_path() {
+            Ok(path) => UnixStream::connect(path).await.is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Sends a request to the running daemon. Returns `None` when no daemon is listening,
+    /// so callers can fall back to initializing an agent themselves.
+    pub async fn send(request: &DaemonRequest) -> Result<Option<DaemonResponse>> {
+        let Ok(stream) = UnixStream::connect(socket_path()?).await else {
+            return Ok(None);
+        };
+        let (reader, mut writer) = stream.into_split();
+
+        let mut payload = serde_json::to_string(request)?;
+        payload.push('\n');
+        writer.write_all(payload.as_bytes()).await?;
+
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await?;
+        if line.is_empty() {
+            return Err(anyhow!("The daemon closed the connection without answering"));
+        }
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn run(_agent: AIAgent) -> Result<()> {
+    Err(anyhow!("air daemon is only supported on Unix-like systems"))
+}
+
+#[cfg(not(unix))]
+pub async fn is_running() -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+pub async fn send(_request: &DaemonRequest) -> Result<Option<DaemonResponse>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_wire_format() {
+        let request = DaemonRequest::Query { prompt: "hi".to_string(), mode: Some(QueryMode::LocalOnly), cwd: None };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"type":"query","prompt":"hi","mode":"local_only","cwd":null}"#);
+
+        let parsed: DaemonRequest = serde_json::from_str(r#"{"type":"query","prompt":"hi"}"#).unwrap();
+        assert!(matches!(parsed, DaemonRequest::Query { mode: None, cwd: None, .. }));
+
+        let parsed: DaemonRequest = serde_json::from_str(r#"{"type":"set_safe_mode","enabled":true}"#).unwrap();
+        assert!(matches!(parsed, DaemonRequest::SetSafeMode { enabled: true }));
+    }
+
+    #[test]
+    fn test_status_wire_format() {
+        let status = DaemonResponse::Status(DaemonStatus {
+            uptime_seconds: 5,
+            queries: 2,
+            providers: vec!["Gemini".to_string()],
+            safe_mode: true,
+            reminders_paused: false,
+        });
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["type"], "status");
+        assert_eq!(json["safe_mode"], true);
+    }
+}
@@ -0,0 +1,419 @@
+//! `air desktop`: ask from anywhere. A global hotkey pops a small input box (or takes the
+//! selected text), the prompt goes to the agent, and the answer comes back as a notification and
+//! on the clipboard. A tray icon shows the agent's status and offers quick actions: open a chat
+//! terminal, toggle safe mode and pause the daemon's task reminders.
+//!
+//! When `air daemon` is running its warm agent answers and the tray controls it; otherwise one is
+//! loaded at start-up.
+
+use crate::agent::AIAgent;
+use crate::config::DesktopConfig;
+use crate::daemon::{self, DaemonRequest, DaemonResponse, DaemonStatus};
+use crate::utils::approval;
+use crate::utils::clipboard::{self, Clipboard};
+use anyhow::{Result, anyhow};
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use std::time::{Duration, Instant};
+use tao::event::{Event, StartCause};
+use tao::event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy};
+use tokio::runtime::Handle;
+use tracing::{info, warn};
+use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+/// Hotkey and menu events arrive on their own channels, so the event loop wakes up to check them.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+/// Notifications cut longer answers; the full text is on the clipboard.
+const NOTIFICATION_CHARS: usize = 400;
+const ICON_SIZE: u32 = 32;
+
+/// Input boxes tried in order; a missing program falls through to the next.
+#[cfg(target_os = "linux")]
+const DIALOGS: &[(&str, &[&str])] = &[
+    ("zenity", &["--entry", "--title=AIR", "--text=Ask AIR"]),
+    ("kdialog", &["--title", "AIR", "--inputbox", "Ask AIR"]),
+];
+#[cfg(target_os = "macos")]
+const DIALOGS: &[(&str, &[&str])] = &[
+    ("osascript", &["-e", r#"text returned of (display dialog "Ask AIR" default answer "" with title "AIR")"#]),
+];
+#[cfg(windows)]
+const DIALOGS: &[(&str, &[&str])] = &[
+    ("powershell", &["-NoProfile", "-Command",
+        "Add-Type -AssemblyName Microsoft.VisualBasic; [Microsoft.VisualBasic.Interaction]::InputBox('Ask AIR', 'AIR')"]),
+];
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+const DIALOGS: &[(&str, &[&str])] = &[];
+
+/// Terminals tried in order for "Open chat"; the air binary and `--interactive` are appended.
+#[cfg(target_os = "linux")]
+const TERMINALS: &[(&str, &[&str])] = &[
+    ("x-terminal-emulator", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("xterm", &["-e"]),
+];
+#[cfg(target_os = "macos")]
+const TERMINALS: &[(&str, &[&str])] = &[("open", &["-a", "Terminal", "--args"])];
+#[cfg(windows)]
+const TERMINALS: &[(&str, &[&str])] = &[("cmd", &["/C", "start", "AIR"])];
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+const TERMINALS: &[(&str, &[&str])] = &[];
+
+/// Where quick-ask prompts are answered: the daemon when it is up, else `agent`.
+#[derive(Clone)]
+pub struct Backend {
+    agent: Option<AIAgent>,
+}
+
+impl Backend {
+    /// `agent` may be `None` when a daemon is running.
+    pub fn new(agent: Option<AIAgent>) -> Self {
+        Self { agent }
+    }
+
+    pub async fn ask(&self, prompt: &str) -> Result<String> {
+        // Quick-ask isn't tied to a directory, so the daemon's own is fine
+        let request = DaemonRequest::Query { prompt: prompt.to_string(), mode: None, cwd: None };
+        match daemon::send(&request).await? {
+            Some(DaemonResponse::Answer { content, .. }) => return Ok(content),
+            Some(DaemonResponse::Error { message }) => return Err(anyhow!(message)),
+            Some(DaemonResponse::Stopping | DaemonResponse::Status(_) | DaemonResponse::WrongDirectory) | None => {}
+        }
+        let mode = self.local()?.config().default_mode.clone();
+        Ok(self.local()?.query_with_tools_in_mode(prompt, &mode).await?.content)
+    }
+
+    /// The daemon's status, or `None` when answers come from the local agent.
+    pub async fn status(&self) -> Result<Option<DaemonStatus>> {
+        self.control(DaemonRequest::Status).await
+    }
+
+    pub async fn set_safe_mode(&self, enabled: bool) -> Result<()> {
+        if self.control(DaemonRequest::SetSafeMode { enabled }).await?.is_none() {
+            self.local()?.set_safe_mode(enabled);
+        }
+        Ok(())
+    }
+
+    pub async fn pause_reminders(&self, paused: bool) -> Result<()> {
+        self.control(DaemonRequest::PauseReminders { paused }).await?
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("Task reminders run in `air daemon`, which isn't running"))
+    }
+
+    async fn control(&self, request: DaemonRequest) -> Result<Option<DaemonStatus>> {
+        match daemon::send(&request).await? {
+            Some(DaemonResponse::Status(status)) => Ok(Some(status)),
+            Some(DaemonResponse::Error { message }) => Err(anyhow!(message)),
+            Some(_) => Err(anyhow!("Unexpected reply from the daemon")),
+            None => Ok(None),
+        }
+    }
+
+    fn local(&self) -> Result<&AIAgent> {
+        self.agent.as_ref()
+            .ok_or_else(|| anyhow!("The daemon has stopped; restart `air desktop` to load an agent"))
+    }
+}
+
+enum DesktopEvent {
+    Answer(String),
+    Failed(String),
+    Status(Option<DaemonStatus>),
+}
+
+struct Tray {
+    /// Removes the icon when dropped.
+    icon: TrayIcon,
+    status: MenuItem,
+    open_chat: MenuItem,
+    safe_mode: CheckMenuItem,
+    pause_reminders: CheckMenuItem,
+    quit: MenuItem,
+}
+
+impl Tray {
+    fn new() -> Result<Self> {
+        let tray = Self {
+            icon: TrayIconBuilder::new().with_tooltip("AIR").with_icon(icon()?).build()?,
+            status: MenuItem::new("Starting…", false, None),
+            open_chat: MenuItem::new("Open chat", true, None),
+            safe_mode: CheckMenuItem::new("Safe mode (read-only tools)", true, false, None),
+            pause_reminders: CheckMenuItem::new("Pause task reminders", true, false, None),
+            quit: MenuItem::new("Quit", true, None),
+        };
+        let menu = Menu::new();
+        menu.append(&tray.status)?;
+        menu.append(&PredefinedMenuItem::separator())?;
+        menu.append(&tray.open_chat)?;
+        menu.append(&tray.safe_mode)?;
+        menu.append(&tray.pause_reminders)?;
+        menu.append(&PredefinedMenuItem::separator())?;
+        menu.append(&tray.quit)?;
+        tray.icon.set_menu(Some(Box::new(menu)));
+        Ok(tray)
+    }
+
+    fn show(&self, status: Option<&DaemonStatus>, backend: &Backend) {
+        let line = status_line(status);
+        self.status.set_text(&line);
+        self.icon.set_tooltip(Some(format!("AIR: {}", line))).ok();
+        match status {
+            Some(status) => {
+                self.safe_mode.set_checked(status.safe_mode);
+                self.pause_reminders.set_checked(status.reminders_paused);
+            }
+            None => self.safe_mode.set_checked(backend.agent.as_ref().is_some_and(|a| a.safe_mode())),
+        }
+        self.pause_reminders.set_enabled(status.is_some());
+    }
+}
+
+/// Registers the hotkeys, shows the tray icon and runs the event loop until Quit. Must be
+/// called from the main thread; queries run on `runtime`.
+pub fn run(backend: Backend, config: &DesktopConfig, runtime: Handle) -> Result<()> {
+    // Quick-ask queries have no terminal to answer approval prompts on
+    approval::disable_prompts();
+    let event_loop = EventLoopBuilder::<DesktopEvent>::with_user_event().build();
+    let proxy = event_loop.create_proxy();
+
+    let ask = parse_hotkey(&config.ask_hotkey)?;
+    let selection = parse_hotkey(&config.selection_hotkey)?;
+    // Some sessions (Wayland, for one) refuse global hotkeys; the tray still works there
+    let manager = match register_hotkeys(&[ask, selection]) {
+        Ok(manager) => {
+            info!("⌨️  AIR desktop ready: {} to ask, {} to ask about the selection", config.ask_hotkey, config.selection_hotkey);
+            Some(manager)
+        }
+        Err(e) => {
+            warn!("{}; use the tray icon instead", e);
+            None
+        }
+    };
+
+    let copy_answer = config.copy_answer;
+    let mut clipboard = Clipboard::new().map_err(|e| warn!("{}", e)).ok();
+    let mut tray = None;
+    let mut next_status = Instant::now();
+    event_loop.run(move |event, _, control_flow| {
+        // Unregisters the hotkeys if it is dropped
+        let _ = &manager;
+        *control_flow = ControlFlow::WaitUntil(Instant::now() + POLL_INTERVAL);
+
+        match event {
+            // The tray needs the platform's event loop running before it can be created
+            Event::NewEvents(StartCause::Init) => match Tray::new() {
+                Ok(created) => tray = Some(created),
+                Err(e) => warn!("Tray icon unavailable: {}", e),
+            },
+            Event::UserEvent(DesktopEvent::Answer(answer)) => {
+                let answer = clipboard::answer_text(&answer);
+                let copied = copy_answer && clipboard.as_mut().is_some_and(|c| c.copy(answer).is_ok());
+                notify(if copied { "AIR (copied)" } else { "AIR" }, &notification_body(answer, NOTIFICATION_CHARS));
+            }
+            Event::UserEvent(DesktopEvent::Failed(error)) => notify("AIR error", &error),
+            Event::UserEvent(DesktopEvent::Status(status)) => {
+                if let Some(tray) = &tray {
+                    tray.show(status.as_ref(), &backend);
+                }
+            }
+            _ => {}
+        }
+
+        while let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
+            if event.state != HotKeyState::Pressed {
+                continue;
+            }
+            if event.id == ask.id() {
+                spawn_query(&runtime, &backend, &proxy, None);
+            } else if event.id == selection.id() {
+                match clipboard.as_mut().map(|c| c.selection()) {
+                    Some(Ok(text)) if !text.trim().is_empty() => spawn_query(&runtime, &backend, &proxy, Some(text)),
+                    Some(Err(e)) => notify("AIR", &e.to_string()),
+                    _ => notify("AIR", "Select some text first"),
+                }
+            }
+        }
+
+        let Some(tray) = &tray else { return };
+        while let Ok(event) = MenuEvent::receiver().try_recv() {
+            if event.id() == tray.quit.id() {
+                *control_flow = ControlFlow::Exit;
+            } else if event.id() == tray.open_chat.id() {
+                if let Err(e) = open_chat() {
+                    notify("AIR error", &e.to_string());
+                }
+            } else if event.id() == tray.safe_mode.id() {
+                let enabled = tray.safe_mode.is_checked();
+                spawn_control(&runtime, &backend, &proxy, move |backend| async move { backend.set_safe_mode(enabled).await });
+            } else if event.id() == tray.pause_reminders.id() {
+                let paused = tray.pause_reminders.is_checked();
+                spawn_control(&runtime, &backend, &proxy, move |backend| async move { backend.pause_reminders(paused).await });
+            }
+        }
+        if Instant::now() >= next_status {
+            next_status = Instant::now() + STATUS_INTERVAL;
+            spawn_control(&runtime, &backend, &proxy, |_| async { Ok(()) });
+        }
+    })
+}
+
+fn parse_hotkey(spec: &str) -> Result<HotKey> {
+    spec.parse().map_err(|e| anyhow!("Invalid hotkey '{}': {}", spec, e))
+}
+
+fn register_hotkeys(hotkeys: &[HotKey]) -> Result<GlobalHotKeyManager> {
+    let manager = GlobalHotKeyManager::new().map_err(|e| anyhow!("Global hotkeys unavailable: {}", e))?;
+    for hotkey in hotkeys {
+        manager.register(*hotkey).map_err(|e| anyhow!("Can't register hotkey {}: {}", hotkey, e))?;
+    }
+    Ok(manager)
+}
+
+/// Asks the input box for a prompt (about `selection`, when given) and sends the answer back
+/// to the event loop.
+fn spawn_query(runtime: &Handle, backend: &Backend, proxy: &EventLoopProxy<DesktopEvent>, selection: Option<String>) {
+    let (backend, proxy) = (backend.clone(), proxy.clone());
+    runtime.spawn(async move {
+        let outcome = async {
+            let question = tokio::task::spawn_blocking(input_dialog).await??;
+            let prompt = match (selection, question) {
+                (Some(text), Some(question)) => format!("{}\n\n{}", question, text),
+                (Some(text), None) => format!("Explain this:\n\n{}", text),
+                (None, Some(question)) => question,
+                (None, None) => return Ok(None),
+            };
+            backend.ask(&prompt).await.map(Some)
+        }.await;
+        let event = match outcome {
+            Ok(Some(answer)) => DesktopEvent::Answer(answer),
+            Ok(None) => return,
+            Err(e) => DesktopEvent::Failed(e.to_string()),
+        };
+        proxy.send_event(event).ok();
+    });
+}
+
+/// Runs a tray action, then refreshes the status so the menu shows what actually took effect.
+fn spawn_control<F, Fut>(runtime: &Handle, backend: &Backend, proxy: &EventLoopProxy<DesktopEvent>, action: F)
+where
+    F: FnOnce(Backend) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = Result<()>> + Send,
+{
+    let (backend, proxy) = (backend.clone(), proxy.clone());
+    runtime.spawn(async move {
+        if let Err(e) = action(backend.clone()).await {
+            proxy.send_event(DesktopEvent::Failed(e.to_string())).ok();
+        }
+        let status = backend.status().await.unwrap_or_else(|e| {
+            warn!("Daemon status failed: {}", e);
+            None
+        });
+        proxy.send_event(DesktopEvent::Status(status)).ok();
+    });
+}
+
+/// `None` when the box was cancelled or left empty.
+fn input_dialog() -> Result<Option<String>> {
+    for (program, args) in DIALOGS {
+        match std::process::Command::new(program).args(*args).output() {
+            Ok(output) if output.status.success() => {
+                let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                return Ok((!text.is_empty()).then_some(text));
+            }
+            Ok(_) => return Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(anyhow!("Can't open the input box ({}): {}", program, e)),
+        }
+    }
+    Err(anyhow!("No input box available; install zenity or kdialog"))
+}
+
+/// Starts `air --interactive` in a new terminal window.
+fn open_chat() -> Result<()> {
+    let air = std::env::current_exe()?;
+    for (program, args) in TERMINALS {
+        match std::process::Command::new(program).args(*args).arg(&air).arg("--interactive").spawn() {
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(anyhow!("Can't open a terminal ({}): {}", program, e)),
+        }
+    }
+    Err(anyhow!("No terminal found; run `air --interactive` yourself"))
+}
+
+fn notify(summary: &str, body: &str) {
+    if let Err(e) = notify_rust::Notification::new().summary(summary).body(body).show() {
+        warn!("Notification failed: {}", e);
+    }
+}
+
+fn notification_body(answer: &str, limit: usize) -> String {
+    if answer.chars().count() <= limit {
+        return answer.to_string();
+    }
+    let cut: String = answer.chars().take(limit).collect();
+    format!("{}…", cut.trim_end())
+}
+
+fn status_line(status: Option<&DaemonStatus>) -> String {
+    let Some(status) = status else {
+        return "Local agent (no daemon)".to_string();
+    };
+    let uptime = status.uptime_seconds / 60;
+    let mut line = format!("Daemon up {}h {}m · {} queries", uptime / 60, uptime % 60, status.queries);
+    if let Some(provider) = status.providers.first() {
+        line.push_str(&format!(" · {}", provider));
+    }
+    if status.safe_mode {
+        line.push_str(" · safe mode");
+    }
+    line
+}
+
+/// A filled circle, so the tray needs no image file.
+fn icon() -> Result<Icon> {
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let distance = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt();
+            let alpha = if distance <= center { 255 } else { 0 };
+            rgba.extend_from_slice(&[0x3b, 0x82, 0xf6, alpha]);
+        }
+    }
+    Ok(Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_body() {
+        assert_eq!(notification_body("short", 10), "short");
+        assert_eq!(notification_body("one two three", 8), "one two…");
+    }
+
+    #[test]
+    fn test_parse_hotkey() {
+        assert!(parse_hotkey(&DesktopConfig::default().ask_hotkey).is_ok());
+        assert!(parse_hotkey("ctrl+nonsense").is_err());
+    }
+
+    #[test]
+    fn test_status_line() {
+        let status = DaemonStatus {
+            uptime_seconds: 3_900,
+            queries: 4,
+            providers: vec!["Gemini".to_string()],
+            safe_mode: true,
+            reminders_paused: false,
+        };
+        assert_eq!(status_line(Some(&status)), "Daemon up 1h 5m · 4 queries · Gemini · safe mode");
+        assert_eq!(status_line(None), "Local agent (no daemon)");
+    }
+}
@@ -0,0 +1,97 @@
+//! Cloud-only agent for browsers and edge functions: the same provider ranking and fallback as
+//! `AIAgent`'s cloud path, with conversation history kept in memory instead of SQLite and no
+//! local model or tools. Builds without the `native` feature, including for wasm32.
+
+use crate::config::Config;
+use crate::models::{ChatMessage, ModelProvider, ModelResponse, QueryContext, QueryMode, rank_providers};
+use crate::providers::cloud::create_provider;
+use anyhow::{Result, anyhow};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+const DEFAULT_HISTORY_TURNS: usize = 10;
+
+pub struct EdgeAgent {
+    config: Config,
+    providers: Vec<Arc<dyn ModelProvider>>,
+    history: Mutex<VecDeque<ChatMessage>>,
+    history_turns: usize,
+}
+
+impl EdgeAgent {
+    pub fn new(config: Config) -> Result<Self> {
+        let providers: Vec<_> = config.cloud_providers.iter().filter_map(create_provider).collect();
+        if providers.is_empty() {
+            return Err(anyhow!("No cloud provider is enabled with an API key"));
+        }
+        Ok(Self {
+            config,
+            providers,
+            history: Mutex::new(VecDeque::new()),
+            history_turns: DEFAULT_HISTORY_TURNS,
+        })
+    }
+
+    /// Builds an agent from config.toml text (API keys included, since there's no keyring or .env).
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Self::new(Config::from_toml_str(text)?)
+    }
+
+    /// Question/answer pairs remembered as context for follow-up questions.
+    pub fn with_history_turns(mut self, turns: usize) -> Self {
+        self.history_turns = turns;
+        self
+    }
+
+    pub fn providers(&self) -> &[Arc<dyn ModelProvider>] {
+        &self.providers
+    }
+
+    /// Asks the best available provider, falling back to the next one on failure.
+    pub async fn query(&self, prompt: &str) -> Result<ModelResponse> {
+        let params = self.config.mode_params(&QueryMode::CloudOnly);
+        let mut messages: Vec<ChatMessage> = self.history.lock().unwrap().iter().cloned().collect();
+        messages.push(ChatMessage::user(prompt));
+        let context = QueryContext {
+            prompt: prompt.to_string(),
+            messages,
+            tools: Vec::new(),
+            response_schema: None,
+            images: Vec::new(),
+            max_tokens: params.max_tokens,
+            temperature: params.temperature,
+            timeout: params.timeout,
+            pure_mode: false,
+        };
+
+        let mut last_error = anyhow!("No cloud providers are available (check API keys)");
+        for provider in rank_providers(&self.providers) {
+            match provider.generate(&context).await {
+                Ok(response) => {
+                    info!("✅ {} answered in {}ms", provider.name(), response.response_time_ms);
+                    self.remember(prompt, &response.content);
+                    return Ok(response);
+                }
+                Err(e) => {
+                    warn!("❌ {} failed: {}", provider.name(), e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    pub fn clear_history(&self) {
+        self.history.lock().unwrap().clear();
+    }
+
+    fn remember(&self, prompt: &str, answer: &str) {
+        let mut history = self.history.lock().unwrap();
+        history.push_back(ChatMessage::user(prompt));
+        history.push_back(ChatMessage::assistant(answer));
+        while history.len() > self.history_turns * 2 {
+            history.pop_front();
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! C ABI for embedding the agent in desktop apps (enabled with the `ffi` feature).
+//!
+//! The declarations are in `include/air.h`. An `AirAgent` owns its own async runtime, so calls
+//! block the calling thread until the answer is ready; use one agent per thread or serialize
+//! calls. Strings returned by the library must be released with `air_string_free`.
+
+use crate::agent::AIAgent;
+use crate::config::Config;
+use crate::utils::approval;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::Path;
+use std::ptr;
+
+pub struct AirAgent {
+    agent: AIAgent,
+    runtime: tokio::runtime::Runtime,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl std::fmt::Display) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, turning errors and panics into a null return plus `air_last_error`.
+fn guarded<T>(f: impl FnOnce() -> anyhow::Result<*mut T>) -> *mut T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+        Err(_) => {
+            set_last_error("internal panic");
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> anyhow::Result<&'a str> {
+    if value.is_null() {
+        return Err(anyhow::anyhow!("{} is null", name));
+    }
+    CStr::from_ptr(value).to_str().map_err(|_| anyhow::anyhow!("{} is not valid UTF-8", name))
+}
+
+/// Creates an agent from the config file at `config_path`, or from the usual config.toml when
+/// it is null. Returns null on failure; see `air_last_error`.
+///
+/// # Safety
+/// `config_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn air_agent_new(config_path: *const c_char) -> *mut AirAgent {
+    guarded(|| {
+        // The host application owns stdin, so approvals are declined rather than prompted for
+        approval::disable_prompts();
+        let config = if config_path.is_null() {
+            Config::load()?
+        } else {
+            Config::load_from(Path::new(str_arg(config_path, "config_path")?))?
+        };
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+        let agent = runtime.block_on(AIAgent::new(config))?;
+        Ok(Box::into_raw(Box::new(AirAgent { agent, runtime })))
+    })
+}
+
+/// Answers `prompt` using the agent's tools. Returns a newly allocated string to release with
+/// `air_string_free`, or null on failure; see `air_last_error`.
+///
+/// # Safety
+/// `agent` must come from `air_agent_new` and not be freed; `prompt` must be a valid
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn air_agent_query(agent: *mut AirAgent, prompt: *const c_char) -> *mut c_char {
+    guarded(|| {
+        let agent = agent.as_ref().ok_or_else(|| anyhow::anyhow!("agent is null"))?;
+        let prompt = str_arg(prompt, "prompt")?;
+        let response = agent.runtime.block_on(agent.agent.query_with_tools(prompt))?;
+        Ok(CString::new(response.content.replace('\0', ""))?.into_raw())
+    })
+}
+
+/// Destroys an agent. Null is ignored.
+///
+/// # Safety
+/// `agent` must be null or come from `air_agent_new`, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn air_agent_free(agent: *mut AirAgent) {
+    if !agent.is_null() {
+        let AirAgent { agent, runtime } = *Box::from_raw(agent);
+        // Connection pools and background tasks shut down on the runtime they were created on
+        let guard = runtime.enter();
+        drop(agent);
+        drop(guard);
+    }
+}
+
+/// Releases a string returned by the library. Null is ignored.
+///
+/// # Safety
+/// `value` must be null or a string returned by this library, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn air_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+/// The message of the last failed call on this thread, or null. Owned by the library and valid
+/// until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn air_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
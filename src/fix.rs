@@ -0,0 +1,272 @@
+//! `air fix`: explains the last failed shell command and offers a corrected one.
+//!
+//! A hook installed into the shell's startup file (`air fix --install`) writes every failing
+//! command line, its exit status and directory to `shell/last_command` in the data directory.
+//! Shells don't keep a command's output, so the error text comes from piped input
+//! (`cmd 2>&1 | air fix`) or from running the command again once the user agrees.
+
+use crate::agent::{AIAgent, QueryMode, Validator};
+use crate::tools::command::{Shell, truncate_output};
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const BEGIN_MARKER: &str = "# >>> air fix >>>";
+const END_MARKER: &str = "# <<< air fix <<<";
+/// Longest the re-run that captures the error output may take.
+const RERUN_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_OUTPUT_CHARS: usize = 8_000;
+
+/// Shells the hook can be installed into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+}
+
+impl HookShell {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "bash" => Ok(HookShell::Bash),
+            "zsh" => Ok(HookShell::Zsh),
+            "fish" => Ok(HookShell::Fish),
+            "powershell" | "pwsh" => Ok(HookShell::PowerShell),
+            other => Err(anyhow!("Unsupported shell '{}' (expected bash, zsh, fish or powershell)", other)),
+        }
+    }
+
+    /// The user's shell, from `$SHELL` (PowerShell on Windows).
+    pub fn detect() -> Result<Self> {
+        if cfg!(windows) {
+            return Ok(HookShell::PowerShell);
+        }
+        let shell = std::env::var("SHELL").map_err(|_| anyhow!("$SHELL is not set; pass --shell"))?;
+        Self::parse(Path::new(&shell).file_name().and_then(|n| n.to_str()).unwrap_or_default())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HookShell::Bash => "bash",
+            HookShell::Zsh => "zsh",
+            HookShell::Fish => "fish",
+            HookShell::PowerShell => "powershell",
+        }
+    }
+
+    /// The file the hook goes in. Fish and PowerShell get their own file; bash and zsh get a
+    /// marked block in their rc file.
+    fn startup_file(self) -> Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| anyhow!("No home directory"))?;
+        Ok(match self {
+            HookShell::Bash => home.join(".bashrc"),
+            HookShell::Zsh => std::env::var("ZDOTDIR").map(PathBuf::from).unwrap_or(home).join(".zshrc"),
+            HookShell::Fish => dirs::config_dir().unwrap_or(home.join(".config")).join("fish").join("conf.d").join("air.fish"),
+            HookShell::PowerShell => return Err(anyhow!("Add the output of `air fix --print-hook --shell powershell` to your $PROFILE")),
+        })
+    }
+}
+
+/// The hook for `shell`, writing to `record`.
+pub fn hook_script(shell: HookShell, record: &Path) -> String {
+    let file = record.to_string_lossy().replace('\'', "'\\''");
+    let body = match shell {
+        HookShell::Bash => format!(
+            "__air_record() {{\n  local s=$?\n  if [ $s -ne 0 ]; then\n    \
+             printf '%s\\n%s\\n%s\\n%s\\n%s\\n' bash \"$s\" \"$(date +%s)\" \"$PWD\" \"$(HISTTIMEFORMAT= history 1 | sed 's/^ *[0-9]* *//')\" > '{file}'\n  \
+             fi\n  return $s\n}}\n\
+             PROMPT_COMMAND=\"__air_record${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}\"\n"
+        ),
+        HookShell::Zsh => format!(
+            // `status` is read-only in zsh
+            "__air_record() {{\n  local s=$?\n  if (( s != 0 )); then\n    \
+             printf '%s\\n%s\\n%s\\n%s\\n%s\\n' zsh \"$s\" \"$(date +%s)\" \"$PWD\" \"$(fc -ln -1)\" >| '{file}'\n  \
+             fi\n}}\n\
+             autoload -Uz add-zsh-hook\nadd-zsh-hook precmd __air_record\n"
+        ),
+        HookShell::Fish => format!(
+            "function __air_record --on-event fish_postexec\n    set -l status_code $status\n    \
+             if test $status_code -ne 0\n        \
+             printf '%s\\n%s\\n%s\\n%s\\n%s\\n' fish $status_code (date +%s) $PWD $argv[1] > '{file}'\n    \
+             end\nend\n"
+        ),
+        HookShell::PowerShell => format!(
+            "$__airPrompt = $function:prompt\n\
+             function prompt {{\n    \
+             if (-not $?) {{\n        \
+             $last = Get-History -Count 1\n        \
+             Set-Content -Path '{file}' -Value @('powershell', $LASTEXITCODE, [DateTimeOffset]::Now.ToUnixTimeSeconds(), $PWD.Path, $last.CommandLine)\n    \
+             }}\n    & $__airPrompt\n}}\n",
+            file = record.to_string_lossy().replace('\'', "''")
+        ),
+    };
+    format!("{}\n{}{}\n", BEGIN_MARKER, body, END_MARKER)
+}
+
+pub fn record_path() -> Result<PathBuf> {
+    let dir = crate::utils::paths::get_air_data_dir()?.join("shell");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join("last_command"))
+}
+
+/// Adds the hook to the shell's startup file, replacing an earlier one. Returns the file.
+pub fn install(shell: HookShell) -> Result<PathBuf> {
+    let path = shell.startup_file()?;
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+    let mut updated = remove_block(&existing);
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated.push_str(&hook_script(shell, &record_path()?));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, updated)?;
+    Ok(path)
+}
+
+/// Removes the hook. Returns the file it was removed from, if it was installed.
+pub fn uninstall(shell: HookShell) -> Result<Option<PathBuf>> {
+    let path = shell.startup_file()?;
+    let Ok(existing) = std::fs::read_to_string(&path) else { return Ok(None) };
+    let updated = remove_block(&existing);
+    if updated == existing {
+        return Ok(None);
+    }
+    if shell == HookShell::Fish && updated.trim().is_empty() {
+        std::fs::remove_file(&path)?;
+    } else {
+        std::fs::write(&path, updated)?;
+    }
+    Ok(Some(path))
+}
+
+fn remove_block(text: &str) -> String {
+    let (Some(start), Some(end)) = (text.find(BEGIN_MARKER), text.find(END_MARKER)) else {
+        return text.to_string();
+    };
+    if end < start {
+        return text.to_string();
+    }
+    let after = text[end + END_MARKER.len()..].strip_prefix('\n').unwrap_or(&text[end + END_MARKER.len()..]);
+    format!("{}{}", &text[..start], after)
+}
+
+/// The failed command the hook recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastCommand {
+    pub shell: String,
+    pub status: i32,
+    /// Unix seconds.
+    pub at: i64,
+    pub cwd: PathBuf,
+    pub command: String,
+}
+
+impl LastCommand {
+    pub fn load() -> Result<Self> {
+        let text = std::fs::read_to_string(record_path()?)
+            .map_err(|_| anyhow!("No failed command recorded yet. Install the shell hook with `air fix --install`"))?;
+        Self::parse(&text).ok_or_else(|| anyhow!("The recorded command is unreadable; run the failing command again"))
+    }
+
+    /// Shell, status, time and directory on one line each, then the command line (which may
+    /// span several lines).
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.splitn(5, '\n');
+        let shell = lines.next()?.trim().to_string();
+        let status = lines.next()?.trim().parse().ok()?;
+        let at = lines.next()?.trim().parse().unwrap_or(0);
+        let cwd = PathBuf::from(lines.next()?.trim_end_matches('\r'));
+        let command = lines.next()?.trim().to_string();
+        (!command.is_empty()).then_some(Self { shell, status, at, cwd, command })
+    }
+
+    /// Runs the command again in its directory and returns what it printed.
+    pub async fn rerun(&self) -> Result<String> {
+        let mut cmd = shell_command(&self.shell, &self.command);
+        cmd.current_dir(&self.cwd).kill_on_drop(true).stdin(std::process::Stdio::null());
+        let output = tokio::time::timeout(RERUN_TIMEOUT, cmd.output()).await
+            .map_err(|_| anyhow!("The command didn't finish within {}s", RERUN_TIMEOUT.as_secs()))??;
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(text)
+    }
+}
+
+/// `command` run by the shell it was typed in, falling back to the configured shell.
+pub fn shell_command(shell: &str, command: &str) -> tokio::process::Command {
+    if shell == "fish" {
+        let mut cmd = tokio::process::Command::new("fish");
+        cmd.args(["-c", command]);
+        return cmd;
+    }
+    Shell::parse(shell).unwrap_or_else(|_| Shell::platform_default()).command(command)
+}
+
+/// The model's diagnosis.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Suggestion {
+    pub explanation: String,
+    /// Empty when no command fixes it (a missing file, a server that is down...).
+    #[serde(default)]
+    pub command: String,
+}
+
+pub fn build_prompt(last: &LastCommand, output: Option<&str>) -> String {
+    let mut prompt = format!(
+        "This {} command failed with exit status {} in {}:\n```\n{}\n```\n",
+        last.shell, last.status, last.cwd.display(), last.command
+    );
+    match output {
+        Some(output) => prompt.push_str(&format!("Its output:\n```\n{}\n```\n", truncate_output(output.trim_end(), MAX_OUTPUT_CHARS))),
+        None => prompt.push_str("Its output is not available.\n"),
+    }
+    prompt.push_str(&format!(
+        "\nExplain briefly why it failed, and give a corrected command for the same shell on {}. \
+         Answer with only a JSON object: {{\"explanation\": \"...\", \"command\": \"...\"}}. \
+         Use an empty command if the fix is not a command.",
+        std::env::consts::OS
+    ));
+    prompt
+}
+
+pub async fn suggest(agent: &AIAgent, mode: &QueryMode, last: &LastCommand, output: Option<&str>) -> Result<Suggestion> {
+    let schema = json!({
+        "type": "object",
+        "required": ["explanation", "command"],
+        "properties": {"explanation": {"type": "string"}, "command": {"type": "string"}}
+    });
+    let response = agent.query_validated(&build_prompt(last, output), mode, false, &[Validator::JsonSchema { schema }], 1).await?;
+    let value = crate::agent::validator::parse_json(&response.content).map_err(|e| anyhow!(e))?;
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_last_command() {
+        let last = LastCommand::parse("zsh\n127\n1700000000\n/home/me/app\ngit comit -m 'x'\n").unwrap();
+        assert_eq!(last.shell, "zsh");
+        assert_eq!(last.status, 127);
+        assert_eq!(last.cwd, PathBuf::from("/home/me/app"));
+        assert_eq!(last.command, "git comit -m 'x'");
+        assert!(LastCommand::parse("bash\n1\n0\n/tmp\n\n").is_none());
+    }
+
+    #[test]
+    fn test_hook_block_round_trip() {
+        let rc = "export PATH=$HOME/bin:$PATH\n";
+        let hook = hook_script(HookShell::Bash, Path::new("/data/air/shell/last_command"));
+        assert!(hook.contains("> '/data/air/shell/last_command'"));
+        assert!(hook.contains("PROMPT_COMMAND=\"__air_record"));
+        let installed = format!("{}{}alias ll='ls -l'\n", rc, hook);
+        assert_eq!(remove_block(&installed), format!("{}alias ll='ls -l'\n", rc));
+        assert_eq!(remove_block(rc), rc);
+    }
+}
@@ -0,0 +1,371 @@
+//! Background jobs (`air jobs`): long agent tasks such as batch runs, repository indexing and
+//! crawls run as a detached `air` process. Its output goes to a log file and its state and
+//! progress to a JSON record next to it, so a job outlives the terminal (or the request) that
+//! started it and can be followed from the CLI or the HTTP API.
+//!
+//! The job process finds its record through `AIR_JOB_ID`. Long loops call [`report_progress`],
+//! which does nothing outside a job. Jobs started over the HTTP API are restricted: they run
+//! without tools and only read and write files the filesystem sandbox allows.
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::config::FileSystemConfig;
+use crate::tools::PathSandbox;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Names the job record a process is running for.
+pub const JOB_ENV: &str = "AIR_JOB_ID";
+/// Set in restricted jobs' environment.
+pub const RESTRICTED_ENV: &str = "AIR_JOB_RESTRICTED";
+/// Commands that may run as jobs: the long, non-interactive ones. Not `tools`, which would let
+/// an API client run any tool (reading files, say) with the server's permissions.
+pub const JOB_COMMANDS: &[&str] = &["batch", "code", "workflow", "crew", "bench", "memory"];
+/// Progress is written at most this often; the final step is always written.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// A job whose process hasn't recorded its pid by then never started.
+const START_GRACE: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Progress {
+    pub done: u64,
+    #[serde(default)]
+    pub total: Option<u64>,
+    /// What it is working on.
+    #[serde(default)]
+    pub message: Option<String>,
+    pub updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    /// `air` arguments, starting with the command.
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub status: JobStatus,
+    /// Set by the job process once it is up.
+    #[serde(default)]
+    pub pid: Option<u32>,
+    pub created: DateTime<Utc>,
+    #[serde(default)]
+    pub finished: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub progress: Option<Progress>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Started over the HTTP API: no tools, and only paths the sandbox allows.
+    #[serde(default)]
+    pub restricted: bool,
+}
+
+impl Job {
+    fn dir() -> Result<PathBuf> {
+        let dir = crate::utils::paths::get_air_data_dir()?.join("jobs");
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    fn record_path(id: &str) -> Result<PathBuf> {
+        // Ids come from the CLI and the HTTP API and end up in a file name
+        if !is_job_id(id) {
+            return Err(anyhow!("No job '{}'", id));
+        }
+        Ok(Self::dir()?.join(format!("{}.json", id)))
+    }
+
+    pub fn log_path(&self) -> Result<PathBuf> {
+        Ok(Self::dir()?.join(format!("{}.log", self.id)))
+    }
+
+    /// Starts `air <args>` in the background in `cwd`; a `restricted` job runs without tools.
+    pub fn start(mut args: Vec<String>, cwd: PathBuf, restricted: bool) -> Result<Self> {
+        let command = args.first().ok_or_else(|| anyhow!("No command given for the job"))?;
+        if !JOB_COMMANDS.contains(&command.as_str()) {
+            return Err(anyhow!("'{}' can't run as a job; use one of: {}", command, JOB_COMMANDS.join(", ")));
+        }
+        if restricted && command == "batch" && !args.iter().any(|a| a == "--no-tools") {
+            args.push("--no-tools".to_string());
+        }
+        let created = Utc::now();
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+        let job = Self {
+            id: format!("{}-{}", created.format("%Y%m%d-%H%M%S"), &suffix[..4]),
+            args,
+            cwd,
+            status: JobStatus::Running,
+            pid: None,
+            created,
+            finished: None,
+            progress: None,
+            error: None,
+            restricted,
+        };
+        job.save()?;
+
+        let log = std::fs::File::create(job.log_path()?)?;
+        let mut cmd = std::process::Command::new(std::env::current_exe()?);
+        cmd.args(&job.args)
+            .current_dir(&job.cwd)
+            .env(JOB_ENV, &job.id)
+            .env(RESTRICTED_ENV, if restricted { "1" } else { "" })
+            .stdin(std::process::Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log);
+        detach(&mut cmd);
+        match cmd.spawn() {
+            // Reaps the process when this one lives on (the server); the CLI just exits
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+            }
+            Err(e) => {
+                let mut failed = job.clone();
+                failed.finish(JobStatus::Failed, Some(format!("Can't start the job: {}", e)))?;
+                return Err(anyhow!("Can't start the job: {}", e));
+            }
+        }
+        Ok(job)
+    }
+
+    pub fn load(id: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(Self::record_path(id)?).map_err(|_| anyhow!("No job '{}'", id))?;
+        let mut job: Self = serde_json::from_str(&text)?;
+        job.refresh();
+        Ok(job)
+    }
+
+    /// Every job, newest first.
+    pub fn list() -> Result<Vec<Self>> {
+        let mut jobs: Vec<Self> = std::fs::read_dir(Self::dir()?)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                Self::load(name.strip_suffix(".json")?).ok()
+            })
+            .collect();
+        jobs.sort_by(|a, b| b.created.cmp(&a.created));
+        Ok(jobs)
+    }
+
+    fn save(&self) -> Result<()> {
+        // Write then rename, so readers never see half a record
+        let path = Self::record_path(&self.id)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn finish(&mut self, status: JobStatus, error: Option<String>) -> Result<()> {
+        self.status = status;
+        self.error = error;
+        self.finished = Some(Utc::now());
+        self.save()
+    }
+
+    /// Marks a running job whose process is gone (crashed or killed from outside) as failed.
+    fn refresh(&mut self) {
+        if self.status != JobStatus::Running {
+            return;
+        }
+        let gone = match self.pid {
+            Some(pid) => !process_alive(pid),
+            None => (Utc::now() - self.created).num_seconds() > START_GRACE,
+        };
+        if gone {
+            self.finish(JobStatus::Failed, Some("The job process exited without finishing".to_string())).ok();
+        }
+    }
+
+    /// Stops a running job.
+    pub fn cancel(&mut self) -> Result<()> {
+        if self.status != JobStatus::Running {
+            return Err(anyhow!("Job {} is not running ({:?})", self.id, self.status));
+        }
+        if let Some(pid) = self.pid {
+            kill(pid);
+        }
+        self.finish(JobStatus::Cancelled, None)
+    }
+
+    /// The last `lines` lines of the job's output.
+    pub fn log_tail(&self, lines: usize) -> Result<String> {
+        let text = std::fs::read_to_string(self.log_path()?).unwrap_or_default();
+        let all: Vec<&str> = text.lines().collect();
+        Ok(all[all.len().saturating_sub(lines)..].join("\n"))
+    }
+
+    pub fn command_line(&self) -> String {
+        format!("air {}", self.args.join(" "))
+    }
+
+    /// `42/100 (42%) message`, or empty without progress.
+    pub fn progress_line(&self) -> String {
+        let Some(progress) = &self.progress else { return String::new() };
+        let mut line = match progress.total {
+            Some(total) if total > 0 => format!("{}/{} ({}%)", progress.done, total, progress.done * 100 / total),
+            _ => progress.done.to_string(),
+        };
+        if let Some(message) = &progress.message {
+            line.push(' ');
+            line.push_str(message);
+        }
+        line
+    }
+}
+
+/// The job this process runs for, if any.
+pub fn current_id() -> Option<String> {
+    std::env::var(JOB_ENV).ok().filter(|id| !id.is_empty())
+}
+
+/// Whether this process runs a restricted job.
+pub fn restricted() -> bool {
+    std::env::var(RESTRICTED_ENV).is_ok_and(|v| v == "1")
+}
+
+/// Checks the files a restricted job reads or writes against the filesystem sandbox, relative
+/// to the job's directory. Does nothing outside a restricted job.
+pub fn check_paths(config: &FileSystemConfig, paths: &[&Path]) -> Result<()> {
+    if !restricted() {
+        return Ok(());
+    }
+    let cwd = std::env::current_dir()?;
+    let sandbox = PathSandbox::new(config, &cwd);
+    for path in paths {
+        sandbox.check(&cwd.join(path))?;
+    }
+    Ok(())
+}
+
+/// Records this process's pid in its job. Call at start-up.
+pub fn attach() {
+    let Some(id) = current_id() else { return };
+    if let Ok(mut job) = Job::load(&id) {
+        job.pid = Some(std::process::id());
+        job.save().ok();
+    }
+}
+
+/// Records how far the current job has got. Does nothing outside a job.
+pub fn report_progress(done: u64, total: Option<u64>, message: Option<&str>) {
+    let Some(id) = current_id() else { return };
+    static LAST: Mutex<Option<Instant>> = Mutex::new(None);
+    {
+        let mut last = LAST.lock().unwrap();
+        let finished = total.map_or(false, |t| done >= t);
+        if !finished && last.map_or(false, |at| at.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        *last = Some(Instant::now());
+    }
+    if let Ok(mut job) = Job::load(&id) {
+        if job.status == JobStatus::Running {
+            job.progress = Some(Progress { done, total, message: message.map(|m| m.to_string()), updated: Utc::now() });
+            job.save().ok();
+        }
+    }
+}
+
+/// Records how the current job ended. Call with the command's result before exiting.
+pub fn finish(result: &Result<()>) {
+    let Some(id) = current_id() else { return };
+    if let Ok(mut job) = Job::load(&id) {
+        if job.status == JobStatus::Running {
+            match result {
+                Ok(()) => job.finish(JobStatus::Succeeded, None).ok(),
+                Err(e) => job.finish(JobStatus::Failed, Some(format!("{:#}", e))).ok(),
+            };
+        }
+    }
+}
+
+/// Job ids look like `20250101-093000-1a2b`: the start time and a random suffix.
+fn is_job_id(id: &str) -> bool {
+    let parts: Vec<&str> = id.split('-').collect();
+    matches!(parts.as_slice(), [date, time, suffix]
+        if date.len() == 8 && time.len() == 6 && suffix.len() == 4
+            && date.chars().chain(time.chars()).all(|c| c.is_ascii_digit())
+            && suffix.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Keeps the job running when the terminal closes or Ctrl+C reaches the parent.
+fn detach(cmd: &mut std::process::Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+fn process_alive(pid: u32) -> bool {
+    let mut sys = sysinfo::System::new();
+    let pid = sysinfo::Pid::from_u32(pid);
+    sys.refresh_process(pid) && sys.process(pid).map_or(false, |p| p.status() != sysinfo::ProcessStatus::Zombie)
+}
+
+fn kill(pid: u32) {
+    let mut sys = sysinfo::System::new();
+    let pid = sysinfo::Pid::from_u32(pid);
+    if sys.refresh_process(pid) {
+        if let Some(process) = sys.process(pid) {
+            // Ask politely where signals exist, otherwise terminate outright
+            if process.kill_with(sysinfo::Signal::Term) != Some(true) {
+                process.kill();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_line() {
+        let mut job = Job {
+            id: "20250101-000000-abcd".to_string(),
+            args: vec!["batch".to_string(), "prompts.txt".to_string()],
+            cwd: PathBuf::from("/tmp"),
+            status: JobStatus::Running,
+            pid: None,
+            created: Utc::now(),
+            finished: None,
+            progress: None,
+            error: None,
+            restricted: false,
+        };
+        assert_eq!(job.progress_line(), "");
+        job.progress = Some(Progress { done: 3, total: Some(12), message: Some("prompt 3".to_string()), updated: Utc::now() });
+        assert_eq!(job.progress_line(), "3/12 (25%) prompt 3");
+        assert_eq!(job.command_line(), "air batch prompts.txt");
+    }
+
+    #[test]
+    fn test_job_ids_are_validated() {
+        assert!(is_job_id("20250101-000000-abcd"));
+        assert!(!is_job_id("../../config/air/.env"));
+        assert!(!is_job_id("20250101-000000-abcd/../x"));
+        assert!(!is_job_id(""));
+        assert!(Job::load("../secrets").is_err());
+        assert!(Job::start(vec!["tools".to_string(), "run".to_string()], PathBuf::from("."), false).is_err());
+    }
+}
@@ -26,16 +26,57 @@
 //! ```
 
 
-pub mod agent;
 pub mod config;
+pub mod edge;
 pub mod models;
 pub mod providers;
+pub mod utils;
+#[cfg(feature = "native")]
+pub mod agent;
+#[cfg(feature = "native")]
+pub mod batch;
+#[cfg(feature = "native")]
+pub mod bench;
+#[cfg(feature = "native")]
+pub mod changes;
+#[cfg(feature = "native")]
+pub mod commit;
+#[cfg(feature = "native")]
+pub mod crew;
+#[cfg(feature = "native")]
+pub mod daemon;
+#[cfg(feature = "native")]
+pub mod fix;
+#[cfg(feature = "desktop")]
+pub mod desktop;
+#[cfg(feature = "native")]
+pub mod jobs;
+#[cfg(feature = "native")]
+pub mod test_fix;
+#[cfg(feature = "native")]
 pub mod tools;
+#[cfg(feature = "native")]
 pub mod rag;
-pub mod utils;
+#[cfg(feature = "native")]
+pub mod review;
+#[cfg(feature = "native")]
+pub mod server;
+#[cfg(feature = "native")]
+pub mod service;
+#[cfg(feature = "native")]
+pub mod share;
+#[cfg(feature = "native")]
+pub mod workflow;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
 
 // Re-export commonly used types for convenience
+#[cfg(feature = "native")]
 pub use agent::AIAgent;
 pub use config::{Config, CloudProviderConfig, PerformanceConfig};
-pub use models::{ModelProvider, ModelResponse, QueryContext, ModelMetrics};
+pub use edge::EdgeAgent;
+pub use models::{ModelProvider, ModelResponse, QueryContext, ModelMetrics, QueryMode};
+#[cfg(feature = "native")]
 pub use tools::{Tool, ToolCall, ToolResult};
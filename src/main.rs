@@ -1,15 +1,31 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use tracing::info;
-use tracing_subscriber;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
 use std::io::{self, Write};
 use dotenv;
 use std::path::PathBuf;
 use std::collections::HashSet;
+use std::sync::Arc;
 
-use air::agent::AIAgent;
+use air::agent::{AIAgent, MemoryManager, QueryMode};
 use air::config::Config;
-use air::tools;
+use air::daemon::{self, DaemonRequest, DaemonResponse};
+use air::tools::{self, ToolManager};
+use air::utils::config_watcher::ConfigWatcher;
+use air::utils::secrets;
+use air::utils::report;
+use air::utils::approval;
+use air::utils::markdown;
+use air::utils::piped_input;
+use air::utils::download;
+use air::utils::model_catalog;
+use air::utils::multiline::{self, MultilineHelper};
+use air::utils::i18n::{tr, tr_args};
+use rustyline::Editor;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
 
 #[derive(Parser)]
 #[command(name = "air")]
@@ -24,14 +40,123 @@ struct Args {
     #[arg(short, long, help = "Verbose output")]
     verbose: bool,
 
+    #[arg(long, global = true, help = "Print responses as raw text instead of rendered markdown")]
+    plain: bool,
+
+    #[arg(long, value_enum, help = "Query mode (overrides default_mode in config.toml)")]
+    mode: Option<CliMode>,
+
+    #[arg(short, long, conflicts_with = "mode", help = "Use only the local model (same as --mode local)")]
+    local_only: bool,
+
+    #[arg(short, long, conflicts_with_all = ["mode", "local_only"], help = "Use only this provider: a cloud provider name from config.toml, or 'local'")]
+    provider: Option<String>,
+
+    #[arg(short = 'm', long, requires = "provider", help = "Model to use with --provider (a GGUF path for 'local')")]
+    model: Option<String>,
+
+    #[arg(long, global = true, help = "Configuration profile to use (overrides AIR_PROFILE)")]
+    profile: Option<String>,
+
+    #[arg(long = "validate", value_name = "CHECK", value_parser = air::agent::Validator::parse, help = "Check the answer: json, rust, regex:<pattern> or schema:<file.json> (repeatable)")]
+    validators: Vec<air::agent::Validator>,
+
+    #[arg(long, default_value_t = 2, help = "Times a failing answer is sent back for repair")]
+    repairs: u32,
+
+    #[arg(long, help = "Review and revise every answer once (see [reflection] in config.toml)")]
+    reflect: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum CliMode {
+    /// Local model first, cloud fallback
+    Auto,
+    /// Local model only
+    Local,
+    /// Cloud providers only
+    Cloud,
+    /// Local model without prompt templates
+    PureLocal,
+}
+
+impl From<CliMode> for QueryMode {
+    fn from(mode: CliMode) -> Self {
+        match mode {
+            CliMode::Auto => QueryMode::Auto,
+            CliMode::Local => QueryMode::LocalOnly,
+            CliMode::Cloud => QueryMode::CloudOnly,
+            CliMode::PureLocal => QueryMode::PureLocal,
+        }
+    }
+}
+
+/// Query settings given on the command line. They are reapplied when config.toml is
+/// hot-reloaded so a session keeps using what it was started with.
+#[derive(Default)]
+struct CliOverrides {
+    mode: Option<QueryMode>,
+    provider: Option<String>,
+    model: Option<String>,
+    reflect: bool,
+}
+
+impl CliOverrides {
+    fn from_args(args: &Args) -> Self {
+        let mode = if args.local_only { Some(QueryMode::LocalOnly) } else { args.mode.map(QueryMode::from) };
+        Self { mode, provider: args.provider.clone(), model: args.model.clone(), reflect: args.reflect }
+    }
+
+    fn apply(&self, config: &mut Config) -> Result<()> {
+        if let Some(mode) = &self.mode {
+            config.default_mode = mode.clone();
+        }
+        if self.reflect {
+            config.reflection.enabled = true;
+            config.reflection.complex_only = false;
+        }
+        let Some(provider) = &self.provider else { return Ok(()) };
+
+        if provider.eq_ignore_ascii_case("local") {
+            config.local_model.enabled = true;
+            if let Some(model) = &self.model {
+                config.local_model.model_path = model.clone();
+            }
+            config.default_mode = QueryMode::LocalOnly;
+            return Ok(());
+        }
+
+        // Only the named provider stays, so quality-score ordering has nothing to choose between
+        let mut selected = config.cloud_providers.iter()
+            .find(|p| p.name.eq_ignore_ascii_case(provider))
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<&str> = config.cloud_providers.iter().map(|p| p.name.as_str()).collect();
+                anyhow::anyhow!("Unknown provider '{}'. Configured: local, {}", provider, names.join(", "))
+            })?;
+        selected.enabled = true;
+        if let Some(model) = &self.model {
+            selected.model = model.clone();
+            selected.auto_select_model = false;
+        }
+        config.cloud_providers = vec![selected];
+        // No need to load the local model for a cloud-only run
+        config.local_model.enabled = false;
+        config.default_mode = QueryMode::CloudOnly;
+        Ok(())
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Login to cloud providers (e.g., Gemini)
-    Login,
+    Login {
+        #[arg(long, help = "Write API keys to the plaintext .env file instead of the OS keyring (for systems without a keyring)")]
+        export_env: bool,
+    },
     /// Setup local environment (Ollama, models, etc.)
     Setup {
         #[arg(long, help = "Setup local models")]
@@ -42,8 +167,327 @@ enum Commands {
         #[command(subcommand)]
         command: MemoryCommands,
     },
-    /// Configure model availability
-    Config,
+    /// Configure model availability (interactive), or get/set individual settings
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
+    },
+    /// Serve the agent over an HTTP API (/query, /sessions, /tools, /health)
+    Serve {
+        #[arg(long, help = "Address to bind (overrides server.bind)")]
+        bind: Option<String>,
+        #[arg(long, help = "Port to listen on (overrides server.port)")]
+        port: Option<u16>,
+    },
+    /// Inspect the agent's tools or call one directly, without a model
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommands,
+    },
+    /// Run every prompt in a file through the agent, writing one JSON result per line
+    Batch {
+        /// Prompt file: one prompt per line, or JSON lines with "prompt" and optional "id"
+        input: PathBuf,
+        #[arg(short, long, default_value = "results.jsonl", help = "Where to write the JSONL results")]
+        output: PathBuf,
+        #[arg(short, long, default_value_t = 2, help = "Prompts processed at once")]
+        concurrency: usize,
+        #[arg(long, default_value_t = 1, help = "Retries for a failed prompt (with backoff)")]
+        retries: u32,
+        #[arg(long, help = "Ask the model directly instead of running the tool loop")]
+        no_tools: bool,
+    },
+    /// Run a crew of agents (e.g. researcher → analyst → writer) on a goal
+    Crew {
+        #[command(subcommand)]
+        command: CrewCommands,
+    },
+    /// Run a YAML/TOML workflow of prompt, tool and parallel steps
+    Workflow {
+        #[command(subcommand)]
+        command: WorkflowCommands,
+    },
+    /// Benchmark the local model and every enabled cloud provider on a standard prompt set
+    Bench {
+        #[arg(short = 'n', long, default_value_t = 1, help = "Times to run the prompt set per provider")]
+        iterations: usize,
+        #[arg(long, help = "Save measured quality and latency to config.toml for routing")]
+        update_scores: bool,
+    },
+    /// Summarize model spend per provider, model, day, session and query kind
+    Cost {
+        #[arg(long, default_value = "7d", help = "Report window: 7d, 24h, 2w or a date (2025-03-01)")]
+        since: String,
+        #[arg(long, help = "Print the report as JSON")]
+        json: bool,
+    },
+    /// Show token usage and estimated cost per provider and model, by day or week
+    Usage {
+        #[arg(long, default_value = "30d", help = "Report window: 30d, 24h, 8w or a date (2025-03-01)")]
+        since: String,
+        #[arg(long, help = "Group by week (starting Monday) instead of by day")]
+        weekly: bool,
+        #[arg(long, help = "Print the summary as JSON")]
+        json: bool,
+    },
+    /// Edit the assistant's persona (name, system prompt, tone, answer language) interactively
+    Persona {
+        #[arg(long, help = "Print the current persona and the identity it produces instead of editing")]
+        show: bool,
+    },
+    /// Browse the model catalog and manage downloaded local models
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommands,
+    },
+    /// Check that the config, local model and embedding model are in working order
+    Doctor,
+    /// Index the current repository and answer questions about it with file:line citations
+    Code {
+        #[arg(help = "Question about the code, e.g. \"where is authentication handled?\"")]
+        question: Option<String>,
+        #[arg(long, help = "Re-embed every file instead of only the changed ones")]
+        reindex: bool,
+        #[arg(short = 'k', long, default_value_t = 8, help = "Number of code chunks to retrieve")]
+        results: usize,
+    },
+    /// Have the agent propose edits for a change, approve them hunk by hunk, and apply them
+    Apply {
+        #[arg(help = "The change to make, e.g. \"add a --verbose flag to the CLI\"")]
+        request: String,
+        #[arg(short = 'f', long = "file", help = "Files to work on (default: the best matches from the code index)")]
+        files: Vec<PathBuf>,
+        #[arg(short = 'y', long, help = "Apply every hunk without asking")]
+        yes: bool,
+    },
+    /// Review a GitHub pull request or a local commit range and list the findings
+    Review {
+        #[arg(help = "Pull request URL, a range (main...HEAD, HEAD~3..HEAD) or a branch to compare HEAD with")]
+        target: String,
+        #[arg(long, help = "Print the findings as JSON")]
+        json: bool,
+        #[arg(short = 'o', long, help = "Write the report to a file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+    /// Write a conventional-commit message for the staged changes and commit with it
+    Commit {
+        #[arg(short = 'a', long, help = "Stage all changes to tracked files first (git add -u)")]
+        all: bool,
+        #[arg(short = 'y', long, help = "Commit with the generated message without asking")]
+        yes: bool,
+    },
+    /// Explain the last failed shell command and offer a corrected one to run
+    Fix {
+        #[arg(long, help = "Add the hook that records failed commands to your shell's startup file")]
+        install: bool,
+        #[arg(long, help = "Remove the hook again")]
+        uninstall: bool,
+        #[arg(long, help = "Print the hook instead of installing it")]
+        print_hook: bool,
+        #[arg(long, help = "Shell for the hook: bash, zsh, fish or powershell (default: $SHELL)")]
+        shell: Option<String>,
+        #[arg(long, help = "Don't offer to rerun the command to capture its output")]
+        no_rerun: bool,
+    },
+    /// Run the tests, have the agent fix the failures and rerun, up to a number of iterations
+    TestFix {
+        #[arg(long, help = "Test command (default: test_fix.command, else detected from the project)")]
+        command: Option<String>,
+        #[arg(short = 'n', long, help = "Fix attempts before giving up (default: test_fix.max_iterations)")]
+        iterations: Option<u32>,
+        #[arg(short = 'y', long, help = "Apply every proposed fix without asking")]
+        yes: bool,
+    },
+    /// List or roll back change sets applied with `air apply`
+    Changes {
+        #[command(subcommand)]
+        command: ChangesCommands,
+    },
+    /// Run long tasks (batch runs, indexing, crawls) in the background and follow them
+    Jobs {
+        #[command(subcommand)]
+        command: JobsCommands,
+    },
+    /// Render a past session (prompts, answers, tool calls, sources, cost) as an HTML report to share
+    Share {
+        #[arg(default_value = "last", help = "Session id, a unique prefix of one, or \"last\"")]
+        session: String,
+        #[arg(short = 'o', long, help = "Where to write the report (default: air-session-<id>.html)")]
+        output: Option<PathBuf>,
+        #[arg(long, help = "Upload it as a secret GitHub gist instead, after asking")]
+        gist: bool,
+        #[arg(long, help = "List the recorded sessions")]
+        list: bool,
+    },
+    /// Set up a project-scoped `.air/` directory (settings, persona, knowledge) in the current directory
+    Init,
+    /// Write a diagnostic report (redacted config, log tail, last failure) to attach to an issue
+    Report,
+    /// Keep the agent loaded in the background so `air "question"` answers without start-up cost
+    Daemon {
+        #[arg(long, help = "Stop the running daemon")]
+        stop: bool,
+    },
+    /// Run the daemon (or the HTTP server) as a login service that survives reboots
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+    /// Ask from anywhere: a global hotkey opens a quick-ask box and the answer is copied
+    #[cfg(feature = "desktop")]
+    Desktop,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print a setting, e.g. `air config get performance.local_timeout_seconds`
+    Get {
+        /// Dotted key; cloud providers are addressed by name (cloud_providers.gemini.model)
+        key: String,
+    },
+    /// Change a setting, e.g. `air config set cloud_providers.gemini.enabled true`
+    Set {
+        key: String,
+        /// Value as TOML (true, 42, ["ls *"]); bare words are taken as strings
+        value: String,
+    },
+    /// Remove a setting so its default applies again
+    Unset {
+        key: String,
+    },
+    /// Print a JSON Schema for config.toml (for editor validation)
+    Schema,
+    /// Write a fully commented config.toml with every setting at its default
+    Init {
+        #[arg(long, help = "Overwrite an existing config.toml")]
+        force: bool,
+        #[arg(long, help = "Print to stdout instead of writing the file")]
+        stdout: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ToolsCommands {
+    /// List tools with their functions, descriptions and permission levels
+    List {
+        #[arg(long, help = "Print the definitions as JSON")]
+        json: bool,
+    },
+    /// Run one tool function, e.g. `air tools run calculator calculate --args '{"expression": "2+2"}'`
+    Run {
+        tool: String,
+        function: String,
+        #[arg(long, default_value = "{}", help = "Arguments as a JSON object")]
+        args: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ChangesCommands {
+    /// Show applied change sets, newest first
+    List,
+    /// Restore the files a change set touched (default: the latest one)
+    Rollback {
+        #[arg(help = "Change set ID from `air changes list`")]
+        id: Option<String>,
+        #[arg(long, help = "Roll back even if the files were edited since")]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum JobsCommands {
+    /// Run an air command in the background, e.g. `air jobs start batch prompts.txt` or
+    /// `air jobs start code --reindex`
+    Start {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true, help = "The command and its arguments")]
+        args: Vec<String>,
+    },
+    /// Show jobs, newest first
+    List {
+        #[arg(long, help = "Print the jobs as JSON")]
+        json: bool,
+    },
+    /// Show a job's state and progress
+    Status {
+        id: String,
+        #[arg(long, help = "Print the job as JSON")]
+        json: bool,
+    },
+    /// Stop a running job
+    Cancel {
+        id: String,
+    },
+    /// Print a job's output
+    Logs {
+        id: String,
+        #[arg(short = 'n', long, default_value_t = 50, help = "Lines from the end to show")]
+        lines: usize,
+        #[arg(short, long, help = "Keep printing new output until the job ends")]
+        follow: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceCommands {
+    /// Write a systemd unit, launchd agent or logon task and start it
+    Install {
+        #[arg(long, help = "Run `air serve` instead of `air daemon`")]
+        server: bool,
+    },
+    /// Stop the service and remove it
+    Uninstall,
+    /// Show the service manager's status report
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ModelsCommands {
+    /// Show the recommended models and what is installed
+    List,
+    /// Download a catalog model, or any GGUF as <owner>/<repo>/<file>.gguf from Hugging Face
+    Pull {
+        model: String,
+        #[arg(long = "use", help = "Make it the local model once downloaded")]
+        use_model: bool,
+    },
+    /// Delete a downloaded model
+    Rm {
+        name: String,
+    },
+    /// Show details of a catalog or installed model
+    Info {
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CrewCommands {
+    /// Show the built-in crews and those in the config directory's crews/ folder
+    List,
+    /// Run a crew, e.g. `air crew run research "state of solid-state batteries"`
+    Run {
+        workflow: String,
+        /// What the crew should work on
+        #[arg(required = true, num_args = 1..)]
+        goal: Vec<String>,
+        #[arg(long, help = "Print every member's notes and the answer as JSON")]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum WorkflowCommands {
+    /// Show the workflows in the config directory's workflows/ folder
+    List,
+    /// Run a workflow file, or one saved in the config directory by name
+    Run {
+        workflow: String,
+        #[arg(long = "set", value_name = "NAME=VALUE", help = "Set an input variable (repeatable)")]
+        set: Vec<String>,
+        #[arg(long, help = "Print every step's output as JSON")]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -53,35 +497,72 @@ enum MemoryCommands {
         /// Path to the file to index
         path: String,
     },
+    /// Save an example answer that relevant prompts will show the model
+    AddExample {
+        /// Task category, e.g. code, math, retrieval, tool_action or chat
+        category: String,
+        #[arg(short, long)]
+        question: String,
+        #[arg(short, long)]
+        answer: String,
+    },
+    /// List saved examples
+    Examples {
+        #[arg(short, long)]
+        category: Option<String>,
+    },
+    /// Delete a saved example by id
+    RemoveExample {
+        id: i64,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Load environment variables from AppData .env file
-    if let Ok(air_dir) = air::utils::paths::get_air_data_dir() {
+    // Inside `air jobs start`, the job record follows this process
+    air::jobs::attach();
+    let result = run().await;
+    if let Err(e) = &result {
+        report::record_error(e);
+    }
+    air::jobs::finish(&result);
+    result
+}
+
+async fn run() -> Result<()> {
+    // Load environment variables from the config directory's .env file
+    if let Ok(air_dir) = air::utils::paths::get_air_config_dir() {
         let env_path = air_dir.join(".env");
         if env_path.exists() {
-            dotenv::from_path(env_path).ok();
+            dotenv::from_path(&env_path).ok();
+            // API keys used to be saved here in plaintext; move them into the OS keyring
+            secrets::migrate_env_file(&env_path).ok();
         }
     }
+    // Keys in the keyring fill in anything not set by the environment or .env
+    secrets::load_into_env();
     
     let args = Args::parse();
+    let overrides = CliOverrides::from_args(&args);
+
+    // Every Config::load() reads the profile from AIR_PROFILE, so the flag just sets it
+    if let Some(profile) = &args.profile {
+        std::env::set_var("AIR_PROFILE", profile);
+    }
     
-    // Initialize logging
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(if args.verbose { 
-            tracing::Level::DEBUG 
-        } else { 
-            tracing::Level::INFO 
-        })
-        .finish();
-    
-    tracing::subscriber::set_global_default(subscriber)?;
+    // Initialize logging: the terminal, plus a plain-text copy in the data directory for reports
+    let log_file = report::open_log_file().map_err(|e| eprintln!("Not writing a log file: {}", e)).ok();
+    tracing_subscriber::registry()
+        .with(if args.verbose { LevelFilter::DEBUG } else { LevelFilter::INFO })
+        .with(tracing_subscriber::fmt::layer())
+        .with(log_file.map(|file| tracing_subscriber::fmt::layer().with_ansi(false).with_writer(std::sync::Mutex::new(file))))
+        .try_init()?;
+    report::install_panic_hook();
 
     // Handle subcommands first
     match args.command {
-        Some(Commands::Login) => {
-            handle_login().await?;
+        Some(Commands::Login { export_env }) => {
+            handle_login(export_env).await?;
             return Ok(());
         },
         Some(Commands::Setup { local }) => {
@@ -93,45 +574,219 @@ async fn main() -> Result<()> {
             return Ok(());
         },
         Some(Commands::Memory { command }) => {
-            match command {
-                MemoryCommands::Add { path } => {
-                    let tool = tools::KnowledgeTool::new().await?;
-                    match tool.add_file(&path).await {
-                        Ok(msg) => println!("✅ {}", msg),
-                        Err(e) => println!("❌ Failed to add file: {}", e),
-                    }
+            handle_memory_command(command).await?;
+            return Ok(());
+        },
+        Some(Commands::Config { command: None }) => {
+            handle_config_mode().await?;
+            return Ok(());
+        }
+        Some(Commands::Config { command: Some(command) }) => {
+            return handle_config_command(command);
+        }
+        Some(Commands::Serve { bind, port }) => {
+            let agent = init_agent(&overrides).await?;
+            let mut server_config = agent.config().server.clone();
+            if let Some(bind) = bind {
+                server_config.bind = bind;
+            }
+            if let Some(port) = port {
+                server_config.port = port;
+            }
+            return air::server::serve(agent, &server_config).await;
+        }
+        Some(Commands::Tools { command }) => {
+            return handle_tools_command(command).await;
+        }
+        Some(Commands::Batch { input, output, concurrency, retries, no_tools }) => {
+            let agent = init_agent(&overrides).await?;
+            air::jobs::check_paths(&agent.config().filesystem, &[&input, &output])?;
+            let options = air::batch::BatchOptions { input, output, concurrency, retries, no_tools };
+            let summary = air::batch::run(&agent, &options).await?;
+            println!("✅ {}/{} prompts succeeded, results in {}", summary.succeeded, summary.total, options.output.display());
+            if summary.failed > 0 {
+                return Err(anyhow::anyhow!("{} prompts failed", summary.failed));
+            }
+            return Ok(());
+        }
+        Some(Commands::Crew { command }) => {
+            return handle_crew_command(command, &overrides).await;
+        }
+        Some(Commands::Workflow { command }) => {
+            return handle_workflow_command(command, &overrides).await;
+        }
+        Some(Commands::Bench { iterations, update_scores }) => {
+            let agent = init_agent(&overrides).await?;
+            return handle_bench(&agent, iterations, update_scores).await;
+        }
+        Some(Commands::Cost { since, json }) => {
+            return handle_cost(&since, json).await;
+        }
+        Some(Commands::Usage { since, weekly, json }) => {
+            return handle_usage(&since, weekly, json).await;
+        }
+        Some(Commands::Persona { show }) => {
+            return handle_persona(show);
+        }
+        Some(Commands::Models { command }) => {
+            handle_models_command(command).await?;
+            return Ok(());
+        },
+        Some(Commands::Init) => {
+            return handle_init();
+        }
+        Some(Commands::Apply { request, files, yes }) => {
+            return handle_apply(&request, &files, yes, &overrides).await;
+        }
+        Some(Commands::Review { target, json, output }) => {
+            use air::review::{self, Target};
+            let target = Target::parse(&target);
+            let agent = init_agent(&overrides).await?;
+            let input = review::fetch(&target, &agent.config().github).await?;
+            let mode = overrides.mode.clone().unwrap_or_else(|| agent.config().default_mode.clone());
+            let report = review::review(&agent, &mode, &target, &input).await?;
+            let text = if json { serde_json::to_string_pretty(&report)? } else { review::render_markdown(&report) };
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, text)?;
+                    eprintln!("✅ {} finding(s) written to {}", report.findings.len(), path.display());
                 }
+                None => println!("{}", text),
             }
             return Ok(());
+        }
+        Some(Commands::Commit { all, yes }) => {
+            return handle_commit(all, yes, &overrides).await;
+        }
+        Some(Commands::Fix { install, uninstall, print_hook, shell, no_rerun }) => {
+            if install || uninstall || print_hook {
+                return handle_fix_hook(shell.as_deref(), install, uninstall);
+            }
+            return handle_fix(no_rerun, &overrides, markdown::should_render(args.plain)).await;
+        }
+        Some(Commands::TestFix { command, iterations, yes }) => {
+            let agent = init_agent(&overrides).await?;
+            let mode = overrides.mode.clone().unwrap_or_else(|| agent.config().default_mode.clone());
+            let config = agent.config().clone();
+            let summary = air::test_fix::run(&agent, &mode, &config, &air::changes::root()?, command, iterations, yes).await?;
+            println!("\n📋 Test-fix summary\n{}", summary.render());
+            return Ok(());
+        }
+        Some(Commands::Changes { command }) => {
+            return handle_changes_command(command);
+        }
+        Some(Commands::Jobs { command }) => {
+            return handle_jobs_command(command).await;
+        }
+        Some(Commands::Share { session, output, gist, list }) => {
+            return handle_share(&session, output, gist, list).await;
+        }
+        Some(Commands::Code { question, reindex, results }) => {
+            return handle_code(question.as_deref(), reindex, results, &overrides, markdown::should_render(args.plain)).await;
+        }
+        Some(Commands::Report) => {
+            let path = report::write_report(report::last_failure().as_ref())?;
+            println!("{}", report::instructions(&path));
+            return Ok(());
+        }
+        Some(Commands::Doctor) => {
+            handle_doctor().await?;
+            return Ok(());
         },
-        Some(Commands::Config) => {
-            handle_config_mode().await?;
+        Some(Commands::Daemon { stop: true }) => {
+            match daemon::send(&DaemonRequest::Stop).await? {
+                Some(_) => println!("✅ Daemon stopping"),
+                None => println!("No daemon is running"),
+            }
             return Ok(());
         }
+        Some(Commands::Daemon { stop: false }) => {
+            let agent = init_agent(&overrides).await?;
+            return daemon::run(agent).await;
+        }
+        Some(Commands::Service { command }) => {
+            return handle_service_command(command);
+        }
+        #[cfg(feature = "desktop")]
+        Some(Commands::Desktop) => {
+            // The daemon's warm agent answers when it is up; only load one here otherwise
+            let agent = if daemon::is_running().await { None } else { Some(init_agent(&overrides).await?) };
+            let config = match &agent {
+                Some(agent) => agent.config().desktop.clone(),
+                None => Config::load()?.desktop,
+            };
+            return air::desktop::run(air::desktop::Backend::new(agent), &config, tokio::runtime::Handle::current());
+        }
         None => {}
     }
 
+    // `cat error.log | air "what's wrong here"`: piped input becomes context for a single query
+    let render = markdown::should_render(args.plain);
+    let single_prompt = match piped_input::read_piped_stdin()? {
+        Some(input) => Some(piped_input::build_prompt(args.prompt.as_deref(), &input, piped_input::MAX_PIPED_CHARS)),
+        None if args.interactive => None,
+        None => args.prompt.clone(),
+    };
+
+    let Some(prompt) = single_prompt else {
+        let agent = init_agent(&overrides).await?;
+        return run_interactive_mode(agent, render, overrides).await;
+    };
+
+    // A running daemon already has its agent loaded; it can't honour a different provider or profile
+    // (--profile is exported as AIR_PROFILE above), and it declines queries from other directories
+    let profile_set = std::env::var_os("AIR_PROFILE").is_some_and(|p| !p.is_empty());
+    if overrides.provider.is_none() && !profile_set && args.validators.is_empty() && !args.reflect {
+        let cwd = std::env::current_dir().ok();
+        let request = DaemonRequest::Query { prompt: prompt.clone(), mode: overrides.mode.clone(), cwd };
+        match daemon::send(&request).await? {
+            Some(DaemonResponse::Answer { content, .. }) => {
+                println!("{}", tr("answer.heading"));
+                print_response(&content, render);
+                return Ok(());
+            }
+            Some(DaemonResponse::Error { message }) => return Err(anyhow::anyhow!(message)),
+            Some(DaemonResponse::Stopping | DaemonResponse::Status(_) | DaemonResponse::WrongDirectory) | None => {}
+        }
+    }
+
+    let agent = init_agent(&overrides).await?;
+    if !args.validators.is_empty() {
+        let mode = agent.config().default_mode.clone();
+        let response = agent.query_validated(&prompt, &mode, true, &args.validators, args.repairs).await?;
+        println!("{}", tr("answer.heading"));
+        print_response(&response.content, render);
+        return Ok(());
+    }
+    run_single_query(agent, &prompt, render).await
+}
+
+/// Loads the configuration, applies command-line overrides and builds the agent.
+async fn init_agent(overrides: &CliOverrides) -> Result<AIAgent> {
     info!("Starting AIR Agent...");
 
     // Load configuration
     let mut config = Config::load()?;
+    if let Some(profile) = &config.active_profile {
+        info!("Using configuration profile '{}'", profile);
+    }
+    if let Some(project) = &config.project {
+        info!("📁 Using project settings from {}", project.root.join(".air").display());
+    }
+    overrides.apply(&mut config)?;
+    air::utils::i18n::init(config.language.as_deref());
 
     // Ensure model is selected if local is enabled
     if config.local_model.enabled {
         ensure_model_selected(&mut config)?;
     }
-    
-    // Initialize AI Agent
+
     let agent = AIAgent::new(config).await?;
-    
-    // Check if we should run in interactive mode
-    if args.interactive || args.prompt.is_none() {
-        run_interactive_mode(agent).await?;
-    } else {
-        run_single_query(agent, args).await?;
+    if air::jobs::restricted() {
+        info!("Restricted job: tools are disabled");
+        return Ok(agent.with_tools(&[]));
     }
-    
-    Ok(())
+    Ok(agent)
 }
 
 async fn handle_config_mode() -> Result<()> {
@@ -148,6 +803,9 @@ async fn handle_config_mode() -> Result<()> {
             return Ok(());
         }
     };
+    if let Some(profile) = &config.active_profile {
+        println!("📁 Editing profile '{}'", profile);
+    }
 
     loop {
         // Build menu options dynamically
@@ -165,88 +823,1038 @@ async fn handle_config_mode() -> Result<()> {
             format!("Local Inference Device: {}", config.local_model.device),
         ];
 
-        // Cloud providers
-        for provider in &config.cloud_providers {
-            let status = if provider.enabled { "✅ Enabled" } else { "❌ Disabled" };
-            options.push(format!("Cloud: {} [{}]", provider.name, status));
+        // Cloud providers
+        for provider in &config.cloud_providers {
+            let status = if provider.enabled { "✅ Enabled" } else { "❌ Disabled" };
+            options.push(format!("Cloud: {} [{}]", provider.name, status));
+        }
+
+        let selection = Select::new("Select an option:", options.clone())
+            .with_page_size(10)
+            .prompt();
+
+        match selection {
+            Ok(choice) => {
+                // Find index of choice in original list to determine action
+                let index = options.iter().position(|r| r == &choice).unwrap();
+
+                match index {
+                    0 => { // Save & Exit
+                        save_config(&config)?;
+                        println!("✅ Configuration saved!");
+                        break;
+                    }
+                    1 => { // Toggle Local Model
+                        config.local_model.enabled = !config.local_model.enabled;
+                    }
+                    2 => { // Set Timeout
+                        let current = config.performance.local_timeout_seconds.to_string();
+                        let validator = |input: &str| {
+                            if input.parse::<u64>().is_ok() {
+                                Ok(Validation::Valid)
+                            } else {
+                                Ok(Validation::Invalid("Please enter a valid number of seconds".into()))
+                            }
+                        };
+
+                        let ans = Text::new("Enter timeout in seconds:")
+                            .with_default(&current)
+                            .with_validator(validator)
+                            .prompt();
+
+                        if let Ok(value) = ans {
+                            if let Ok(seconds) = value.parse::<u64>() {
+                                config.performance.local_timeout_seconds = seconds;
+                                println!("✅ Timeout updated to {}s", seconds);
+                            }
+                        }
+                    }
+                    3 => { // Change Local Model File
+                         let models = scan_for_models(&config);
+                         if models.is_empty() {
+                             println!("❌ No models found to select.");
+                         } else {
+                             prompt_model_selection(&mut config, &models)?;
+                         }
+                    }
+                    4 => { // Change Local Inference Device
+                         let devices = vec!["cuda", "cpu", "metal", "auto"];
+                         let selection = Select::new("Select inference device:", devices).prompt();
+                         if let Ok(device) = selection {
+                             config.local_model.device = device.to_string();
+                             println!("✅ Device updated to {}", device);
+                         }
+                    }
+                    _ => { // Toggle Cloud Provider
+                        // Cloud providers start at index 5
+                        let provider_idx = index - 5;
+                        if let Some(provider) = config.cloud_providers.get_mut(provider_idx) {
+                             provider.enabled = !provider.enabled;
+                        }
+                    }
+                }
+            }
+            Err(_) => {
+                println!("Operation cancelled.");
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_persona(show: bool) -> Result<()> {
+    use inquire::{Select, Text};
+
+    let mut config = Config::load()?;
+    let describe = |value: &Option<String>| value.as_deref().filter(|v| !v.trim().is_empty()).unwrap_or("(default)").to_string();
+    if show {
+        println!("Name:          {}", config.persona.name);
+        println!("System prompt: {}", describe(&config.persona.system_prompt));
+        println!("Tone:          {}", describe(&config.persona.tone));
+        println!("Language:      {}", describe(&config.persona.language));
+        println!("\n{}", config.persona.identity());
+        return Ok(());
+    }
+
+    println!("\n🎭 AIR Persona");
+    println!("══════════════");
+    if let Some(profile) = &config.active_profile {
+        println!("📁 Editing profile '{}'", profile);
+    }
+
+    // Empty answers clear optional fields so their defaults apply again
+    let optional = |answer: String| Some(answer.trim().to_string()).filter(|a| !a.is_empty());
+    loop {
+        let persona = &config.persona;
+        let options = vec![
+            "Save & Exit".to_string(),
+            format!("Name: {}", persona.name),
+            format!("System prompt: {}", describe(&persona.system_prompt)),
+            format!("Tone: {}", describe(&persona.tone)),
+            format!("Language: {}", describe(&persona.language)),
+            "Preview identity".to_string(),
+            "Reset to defaults".to_string(),
+        ];
+        let choice = match Select::new("Select an option:", options.clone()).prompt() {
+            Ok(choice) => choice,
+            Err(_) => {
+                println!("Operation cancelled.");
+                return Ok(());
+            }
+        };
+
+        match options.iter().position(|o| o == &choice).unwrap() {
+            0 => {
+                save_config(&config)?;
+                println!("✅ Persona saved!");
+                return Ok(());
+            }
+            1 => {
+                if let Ok(name) = Text::new("Name:").with_default(&persona.name).prompt() {
+                    if !name.trim().is_empty() {
+                        config.persona.name = name.trim().to_string();
+                    }
+                }
+            }
+            2 => {
+                let current = persona.system_prompt.clone().unwrap_or_default();
+                if let Ok(prompt) = Text::new("System prompt (empty for the default):").with_initial_value(&current).prompt() {
+                    config.persona.system_prompt = optional(prompt);
+                }
+            }
+            3 => {
+                let current = persona.tone.clone().unwrap_or_default();
+                if let Ok(tone) = Text::new("Tone, e.g. concise and friendly (empty for none):").with_initial_value(&current).prompt() {
+                    config.persona.tone = optional(tone);
+                }
+            }
+            4 => {
+                let current = persona.language.clone().unwrap_or_default();
+                if let Ok(language) = Text::new("Answer language, e.g. es or Japanese (empty follows `language`):").with_initial_value(&current).prompt() {
+                    config.persona.language = optional(language);
+                }
+            }
+            5 => println!("\n{}\n", persona.identity()),
+            _ => {
+                config.persona = air::config::PersonaConfig::default();
+                println!("✅ Persona reset (save to keep it)");
+            }
+        }
+    }
+}
+
+/// Scriptable counterpart to the interactive config menu. Changes go through the typed
+/// `Config`, so unknown keys and wrongly typed values are rejected instead of written.
+fn handle_config_command(command: ConfigCommands) -> Result<()> {
+    // These two don't depend on (or need a valid) existing config
+    match command {
+        ConfigCommands::Schema => {
+            println!("{}", serde_json::to_string_pretty(&Config::json_schema()?)?);
+            return Ok(());
+        }
+        ConfigCommands::Init { force, stdout } => {
+            let sample = Config::sample_toml()?;
+            if stdout {
+                print!("{}", sample);
+                return Ok(());
+            }
+            let config_path = air::utils::paths::get_air_config_dir()?.join("config.toml");
+            if config_path.exists() && !force {
+                println!("❌ {:?} already exists. Use --force to overwrite it or --stdout to print the sample.", config_path);
+                return Ok(());
+            }
+            std::fs::write(&config_path, sample)?;
+            println!("✅ Wrote {:?}", config_path);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let mut config = Config::load()?;
+    match command {
+        ConfigCommands::Get { key } => {
+            match config.get_key(&key)? {
+                toml::Value::String(value) => println!("{}", value),
+                toml::Value::Table(table) => print!("{}", toml::to_string_pretty(&table)?),
+                value => println!("{}", value),
+            }
+        }
+        ConfigCommands::Set { key, value } => {
+            config.set_key(&key, &value)?;
+            save_config(&config)?;
+            println!("✅ {} = {}", key, config.get_key(&key)?);
+        }
+        ConfigCommands::Unset { key } => {
+            config.unset_key(&key)?;
+            remove_saved_key(&config, &key)?;
+            match config.get_key(&key) {
+                Ok(value) => println!("✅ {} reset to {}", key, value),
+                Err(_) => println!("✅ {} unset", key),
+            }
+        }
+        ConfigCommands::Schema | ConfigCommands::Init { .. } => unreachable!("handled above"),
+    }
+    Ok(())
+}
+
+async fn handle_crew_command(command: CrewCommands, overrides: &CliOverrides) -> Result<()> {
+    match command {
+        CrewCommands::List => {
+            for name in air::crew::available() {
+                match air::crew::Crew::load(&name) {
+                    Ok(crew) => {
+                        let members: Vec<&str> = crew.members.iter().chain([&crew.synthesis]).map(|m| m.name.as_str()).collect();
+                        println!("👥 {:<12} {}", name, members.join(" → "));
+                        if !crew.description.is_empty() {
+                            println!("   {}", crew.description);
+                        }
+                    }
+                    Err(e) => println!("⚠️  {:<12} {}", name, e),
+                }
+            }
+        }
+        CrewCommands::Run { workflow, goal, json } => {
+            let crew = air::crew::Crew::load(&workflow)?;
+            let agent = init_agent(overrides).await?;
+            let report = air::crew::run(&agent, &crew, &goal.join(" ")).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for step in &report.steps[..report.steps.len() - 1] {
+                    println!("\n👤 {} ({}, {}ms)\n{}", step.member, step.model_used, step.elapsed_ms, step.output.trim());
+                }
+                println!("\n📝 Answer\n{}", report.answer.trim());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn handle_memory_command(command: MemoryCommands) -> Result<()> {
+    if let MemoryCommands::Add { path } = &command {
+        let config = Config::load().unwrap_or_default();
+        air::jobs::check_paths(&config.filesystem, &[std::path::Path::new(path)])?;
+        let knowledge = config.knowledge;
+        let tool = tools::KnowledgeTool::new().await?.with_config(&knowledge);
+        match tool.add_file(path).await {
+            Ok(msg) => println!("✅ {}", msg),
+            Err(e) => println!("❌ Failed to add file: {}", e),
+        }
+        return Ok(());
+    }
+
+    let data_dir = air::utils::paths::get_air_memory_dir()?;
+    let memory_manager = MemoryManager::new(&data_dir.to_string_lossy()).await?;
+    match command {
+        MemoryCommands::AddExample { category, question, answer } => {
+            let id = memory_manager.add_example(&category, &question, &answer).await?;
+            println!("✅ Saved example #{}", id);
+        }
+        MemoryCommands::Examples { category } => {
+            for example in memory_manager.list_examples(category.as_deref()).await? {
+                println!("#{} [{}]\n   Q: {}\n   A: {}", example.id, example.category, example.question, example.answer);
+            }
+        }
+        MemoryCommands::RemoveExample { id } => {
+            if memory_manager.delete_example(id).await? {
+                println!("🗑️  Removed example #{}", id);
+            } else {
+                println!("❌ No example #{}", id);
+            }
+        }
+        MemoryCommands::Add { .. } => {}
+    }
+    Ok(())
+}
+
+async fn handle_workflow_command(command: WorkflowCommands, overrides: &CliOverrides) -> Result<()> {
+    match command {
+        WorkflowCommands::List => {
+            let names = air::workflow::available();
+            if names.is_empty() {
+                println!("No workflows yet: add .yaml or .toml files to {}", air::utils::paths::get_air_config_dir()?.join("workflows").display());
+            }
+            for name in names {
+                match air::workflow::Workflow::load(&name) {
+                    Ok(workflow) => println!("🔁 {:<16} {} steps  {}", name, workflow.steps.len(), workflow.description),
+                    Err(e) => println!("⚠️  {:<16} {}", name, e),
+                }
+            }
+        }
+        WorkflowCommands::Run { workflow, set, json } => {
+            let workflow = air::workflow::Workflow::load(&workflow)?;
+            let mut inputs = std::collections::BTreeMap::new();
+            for pair in set {
+                let (name, value) = pair.split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("--set expects NAME=VALUE, got '{}'", pair))?;
+                inputs.insert(name.trim().to_string(), value.to_string());
+            }
+            let agent = init_agent(overrides).await?;
+            let run = air::workflow::WorkflowEngine::new(agent).run(&workflow, inputs).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&run)?);
+            } else {
+                for step in &run.steps {
+                    if step.skipped {
+                        println!("⏭️  {} skipped", step.id);
+                    } else {
+                        println!("✅ {} ({}ms)", step.id, step.elapsed_ms);
+                    }
+                }
+                println!("\n{}", run.output.trim());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn handle_tools_command(command: ToolsCommands) -> Result<()> {
+    // Tools only need the config and memory database, not the models
+    let config = Config::load()?;
+    let data_dir = air::utils::paths::get_air_memory_dir()?;
+    let memory_manager = Arc::new(MemoryManager::new(&data_dir.to_string_lossy()).await?);
+    let tool_manager = ToolManager::new(&config, memory_manager).await;
+
+    match command {
+        ToolsCommands::List { json: true } => {
+            println!("{}", serde_json::to_string_pretty(&tool_manager.get_tool_definitions())?);
+        }
+        ToolsCommands::List { json: false } => {
+            for tool in tool_manager.tools() {
+                println!("🔧 {} [{}]", tool.name(), tool.permission_level());
+                println!("   {}", tool.description());
+                println!("   functions: {}\n", tool.available_functions().join(", "));
+            }
+        }
+        ToolsCommands::Run { tool, function, args } => {
+            let args: serde_json::Value = serde_json::from_str(&args)
+                .map_err(|e| anyhow::anyhow!("--args is not valid JSON: {}", e))?;
+            if !args.is_object() {
+                return Err(anyhow::anyhow!("--args must be a JSON object"));
+            }
+            if let Some(found) = tool_manager.tools().into_iter().find(|t| t.name() == tool) {
+                let functions = found.available_functions();
+                if !functions.contains(&function) {
+                    return Err(anyhow::anyhow!("Tool '{}' has no function '{}'. Available: {}", tool, function, functions.join(", ")));
+                }
+            }
+
+            let result = tool_manager.execute_tool(&tool, &function, args).await?;
+            println!("{}", serde_json::to_string_pretty(&result)?);
+            if !result.success {
+                return Err(anyhow::anyhow!("{} {} reported failure", tool, function));
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn handle_bench(agent: &AIAgent, iterations: usize, update_scores: bool) -> Result<()> {
+    let providers = agent.providers();
+    println!("⏱️  Benchmarking {} provider(s) on {} prompts x {}...\n",
+        providers.len(), air::bench::STANDARD_PROMPTS.len(), iterations.max(1));
+    let reports = air::bench::run(&providers, agent.config(), iterations).await;
+
+    println!("{:<20} {:>8} {:>8} {:>8} {:>10} {:>9} {:>8} {:>9} {:>10}",
+        "provider", "p50 ms", "p90 ms", "p99 ms", "hist p95", "tok/s", "failed", "accuracy", "cost $");
+    for r in &reports {
+        println!("{:<20} {:>8} {:>8} {:>8} {:>10} {:>9.1} {:>7.0}% {:>8.0}% {:>10}",
+            r.provider, r.p50_ms, r.p90_ms, r.p99_ms,
+            r.historical_p95_ms.map(|ms| ms.to_string()).unwrap_or_else(|| "-".to_string()),
+            r.tokens_per_sec,
+            r.failure_rate() * 100.0, r.accuracy() * 100.0,
+            r.cost_usd.map(|c| format!("{:.4}", c)).unwrap_or_else(|| "n/a".to_string()));
+    }
+    for r in reports.iter().filter(|r| r.last_error.is_some()) {
+        println!("⚠️  {}: {}", r.provider, r.last_error.as_deref().unwrap_or_default());
+    }
+
+    if update_scores {
+        // Start from the file's settings so command-line overrides aren't saved with the scores
+        let mut config = Config::load()?;
+        let updated = air::bench::update_scores(&mut config, &reports);
+        if updated.is_empty() {
+            println!("\nNo cloud provider scores to update");
+        } else {
+            save_config(&config)?;
+            println!("\n✅ Updated routing scores for {}", updated.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn handle_init() -> Result<()> {
+    let root = std::env::current_dir()?;
+    let written = air::utils::project::Project::init(&root)?;
+    let dir = root.join(air::utils::project::PROJECT_DIR);
+    if written.is_empty() {
+        println!("✅ {} is already set up", dir.display());
+        return Ok(());
+    }
+    println!("✅ Initialized an AIR project in {}", dir.display());
+    for path in written {
+        println!("   + {}", path.strip_prefix(&root).unwrap_or(&path).display());
+    }
+    println!("\nInside this directory air now layers .air/config.toml over your global settings, adds .air/persona.md");
+    println!("to every prompt, and keeps memory and `air memory add` knowledge in .air/data.");
+    println!("Command rules go in .air/commands.toml.");
+    Ok(())
+}
+
+async fn handle_code(question: Option<&str>, reindex: bool, results: usize, overrides: &CliOverrides, render: bool) -> Result<()> {
+    use air::rag::code::{self, CodeIndex};
+    let cwd = std::env::current_dir()?;
+    let root = code::repository_root(&cwd)
+        .or_else(|| air::utils::project::Project::detect().map(|p| p.root))
+        .unwrap_or(cwd);
+
+    println!("🔎 Indexing {}...", root.display());
+    let index = CodeIndex::open(&root).await?;
+    let stats = index.update(reindex).await?;
+    println!("✅ {} files indexed ({} updated, {} removed, {} new chunks)", stats.files, stats.updated, stats.removed, stats.chunks);
+
+    let Some(question) = question else {
+        return Ok(());
+    };
+    let hits = index.search(question, results).await?;
+    if hits.is_empty() {
+        println!("No matching code found");
+        return Ok(());
+    }
+    let tree = code::file_tree(&code::list_files(&root)?);
+    let prompt = code::build_prompt(question, &tree, code::git_summary(&root).as_deref(), &hits);
+
+    let agent = init_agent(overrides).await?;
+    let mode = overrides.mode.clone().unwrap_or_else(|| agent.config().default_mode.clone());
+    let response = agent.query_in_mode(&prompt, &mode).await?;
+    println!("{}", tr("answer.heading"));
+    print_response(&response.content, render);
+    println!("\n📎 Sources:");
+    for (chunk, score) in &hits {
+        println!("   • {}{} ({:.2})", chunk.location(), chunk.symbol.as_ref().map(|s| format!(" {}", s)).unwrap_or_default(), score);
+    }
+    Ok(())
+}
+
+async fn handle_commit(all: bool, yes: bool, overrides: &CliOverrides) -> Result<()> {
+    use air::commit::{self, StagedChanges};
+    let git = air::tools::GitTool::new();
+    if all {
+        git.git(&["add", "-u"]).await?;
+    }
+    let changes = StagedChanges::read(&git).await?;
+    println!("📦 Staged:\n{}", changes.stat);
+
+    let agent = init_agent(overrides).await?;
+    let mode = overrides.mode.clone().unwrap_or_else(|| agent.config().default_mode.clone());
+    loop {
+        let message = commit::generate(&agent, &mode, &changes).await?;
+        println!("\n───────────────\n{}\n───────────────", message);
+        let choice = if yes {
+            'y'
+        } else {
+            approval::choice("Commit with this message? [y]es/[e]dit/[r]egenerate/[n]o")
+        };
+        match choice {
+            'y' | 'e' => {
+                let status = commit::commit(&git, &message, choice == 'e').await?;
+                if !status.success() {
+                    return Err(anyhow::anyhow!("git commit failed ({})", status));
+                }
+                return Ok(());
+            }
+            'r' => continue,
+            _ => {
+                println!("Not committed");
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn handle_fix_hook(shell: Option<&str>, install: bool, uninstall: bool) -> Result<()> {
+    use air::fix::{self, HookShell};
+    let shell = match shell {
+        Some(name) => HookShell::parse(name)?,
+        None => HookShell::detect()?,
+    };
+    if install {
+        let path = fix::install(shell)?;
+        println!("✅ Added the air fix hook to {}", path.display());
+        println!("Open a new {} (or source that file) and failed commands will be recorded for `air fix`.", shell.name());
+    } else if uninstall {
+        match fix::uninstall(shell)? {
+            Some(path) => println!("✅ Removed the air fix hook from {}", path.display()),
+            None => println!("The {} hook is not installed", shell.name()),
+        }
+    } else {
+        print!("{}", fix::hook_script(shell, &fix::record_path()?));
+    }
+    Ok(())
+}
+
+async fn handle_fix(no_rerun: bool, overrides: &CliOverrides, render: bool) -> Result<()> {
+    use air::fix::{self, LastCommand};
+    let last = LastCommand::load()?;
+    println!("❌ `{}` exited with status {} in {}", last.command, last.status, last.cwd.display());
+
+    // `cmd 2>&1 | air fix` hands over the output; stdin is then no longer there for questions
+    let piped = piped_input::read_piped_stdin()?;
+    let interactive = piped.is_none();
+    let output = match piped {
+        Some(output) => Some(output),
+        None if !no_rerun && approval::confirm("Run it again to capture its error output?") => Some(last.rerun().await?),
+        None => None,
+    };
+
+    let agent = init_agent(overrides).await?;
+    let mode = overrides.mode.clone().unwrap_or_else(|| agent.config().default_mode.clone());
+    let suggestion = fix::suggest(&agent, &mode, &last, output.as_deref()).await?;
+    println!();
+    print_response(&suggestion.explanation, render);
+
+    let command = suggestion.command.trim();
+    if command.is_empty() {
+        return Ok(());
+    }
+    println!("\n👉 {}", command);
+    if !interactive || !approval::confirm("Run this command?") {
+        return Ok(());
+    }
+    let status = fix::shell_command(&last.shell, command).current_dir(&last.cwd).status().await?;
+    if status.success() {
+        println!("✅ Done");
+    } else {
+        println!("❌ Exited with {}", status);
+    }
+    Ok(())
+}
+
+async fn handle_apply(request: &str, files: &[PathBuf], yes: bool, overrides: &CliOverrides) -> Result<()> {
+    use air::changes::{self, ChangeSet};
+    let root = changes::root()?;
+    let cwd = std::env::current_dir()?;
+    let named: Vec<PathBuf> = files.iter().map(|f| cwd.join(f)).collect();
+    let context = changes::context_files(&root, &named, request).await?;
+    if context.is_empty() {
+        return Err(anyhow::anyhow!("No files to work on; name them with --file"));
+    }
+    println!("📂 Working on: {}", context.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>().join(", "));
+
+    let agent = init_agent(overrides).await?;
+    let mode = overrides.mode.clone().unwrap_or_else(|| agent.config().default_mode.clone());
+    let proposal = changes::propose(&agent, &mode, request, &context).await?;
+    let planned = changes::plan(&root, &proposal.edits)?;
+    if planned.is_empty() {
+        println!("The model proposed no changes: {}", proposal.summary);
+        return Ok(());
+    }
+    println!("\n💡 {}", proposal.summary);
+
+    let approved = changes::review(planned, yes);
+    if approved.is_empty() {
+        println!("\nNothing applied");
+        return Ok(());
+    }
+    let set = ChangeSet::apply(request, &proposal.summary, approved)?;
+    println!("\n✅ Applied change set {} to {} file(s)", set.id, set.files.len());
+    for file in &set.files {
+        println!("   • {}", file.path.strip_prefix(&root).unwrap_or(&file.path).display());
+    }
+    println!("Undo with `air changes rollback {}`", set.id);
+    Ok(())
+}
+
+fn handle_changes_command(command: ChangesCommands) -> Result<()> {
+    use air::changes::ChangeSet;
+    match command {
+        ChangesCommands::List => {
+            let sets = ChangeSet::list()?;
+            if sets.is_empty() {
+                println!("No change sets recorded");
+            }
+            for set in sets {
+                let status = if set.rolled_back.is_some() { " (rolled back)" } else { "" };
+                println!("{}  {} file(s){}  {}", set.id, set.files.len(), status, set.summary);
+            }
+        }
+        ChangesCommands::Rollback { id, force } => {
+            let mut set = ChangeSet::find(id.as_deref())?;
+            set.rollback(force)?;
+            println!("↩️  Rolled back {} ({} file(s)): {}", set.id, set.files.len(), set.summary);
+        }
+    }
+    Ok(())
+}
+
+async fn handle_jobs_command(command: JobsCommands) -> Result<()> {
+    use air::jobs::{Job, JobStatus};
+    match command {
+        JobsCommands::Start { args } => {
+            let job = Job::start(args, std::env::current_dir()?, false)?;
+            println!("🚀 Started job {}: {}", job.id, job.command_line());
+            println!("   Follow it with `air jobs logs {} --follow`", job.id);
+        }
+        JobsCommands::List { json } => {
+            let jobs = Job::list()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&jobs)?);
+                return Ok(());
+            }
+            if jobs.is_empty() {
+                println!("No jobs yet");
+            }
+            for job in &jobs {
+                println!("{}  {:<9}  {}  {}", job.id, format!("{:?}", job.status).to_lowercase(), job.command_line(), job.progress_line());
+            }
+        }
+        JobsCommands::Status { id, json } => {
+            let job = Job::load(&id)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&job)?);
+                return Ok(());
+            }
+            println!("Job {}: {}", job.id, job.command_line());
+            println!("   Status:   {:?}", job.status);
+            println!("   Started:  {}", job.created.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"));
+            if let Some(finished) = job.finished {
+                println!("   Finished: {} ({}s)", finished.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"), (finished - job.created).num_seconds());
+            }
+            if job.progress.is_some() {
+                println!("   Progress: {}", job.progress_line());
+            }
+            if let Some(error) = &job.error {
+                println!("   Error:    {}", error);
+            }
+            println!("   Log:      {}", job.log_path()?.display());
+        }
+        JobsCommands::Cancel { id } => {
+            let mut job = Job::load(&id)?;
+            job.cancel()?;
+            println!("🛑 Cancelled job {}", job.id);
+        }
+        JobsCommands::Logs { id, lines, follow } => {
+            let job = Job::load(&id)?;
+            let tail = job.log_tail(lines)?;
+            if !tail.is_empty() {
+                println!("{}", tail);
+            }
+            if !follow {
+                return Ok(());
+            }
+            // Print whatever is appended until the job is over
+            let path = job.log_path()?;
+            let mut offset = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            loop {
+                let running = Job::load(&id)?.status == JobStatus::Running;
+                let text = std::fs::read(&path).unwrap_or_default();
+                if (text.len() as u64) > offset {
+                    print!("{}", String::from_utf8_lossy(&text[offset as usize..]));
+                    std::io::stdout().flush().ok();
+                    offset = text.len() as u64;
+                }
+                if !running {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+            let job = Job::load(&id)?;
+            println!("\n[job {} {:?}]", job.id, job.status);
+        }
+    }
+    Ok(())
+}
+
+fn handle_service_command(command: ServiceCommands) -> Result<()> {
+    use air::service::{self, ServiceKind, ServiceSpec};
+    let report = match command {
+        ServiceCommands::Install { server } => {
+            let kind = if server { ServiceKind::Server } else { ServiceKind::Daemon };
+            service::install(&ServiceSpec::current(kind)?)?
+        }
+        ServiceCommands::Uninstall => service::uninstall()?,
+        ServiceCommands::Status => service::status()?,
+    };
+    println!("{}", report.trim_end());
+    Ok(())
+}
+
+async fn handle_share(session: &str, output: Option<PathBuf>, gist: bool, list: bool) -> Result<()> {
+    use air::share::SharedSession;
+    let data_dir = air::utils::paths::get_air_memory_dir()?;
+    let memory_manager = MemoryManager::new(&data_dir.to_string_lossy()).await?;
+
+    if list {
+        let sessions = memory_manager.list_sessions(20).await?;
+        if sessions.is_empty() {
+            println!("No sessions recorded yet");
+        }
+        for s in sessions {
+            println!("{}  {}  {} turn(s)  {}", &s.session_id[..8.min(s.session_id.len())], s.started, s.turns, s.first_prompt);
+        }
+        return Ok(());
+    }
+
+    let shared = SharedSession::load(&memory_manager, session).await?;
+    let html = shared.render_html();
+    println!("📄 Session {} ({} UTC): {} turn(s), {} tool call(s)",
+        shared.summary.session_id, shared.summary.started, shared.turns.len(), shared.tool_calls());
+
+    if gist {
+        println!("The report holds every prompt, answer and tool output of the session, unredacted.");
+        println!("A secret gist is unlisted, but anyone with its link can read it.");
+        if !approval::confirm("Upload it as a secret GitHub gist?") {
+            println!("Nothing uploaded");
+            return Ok(());
+        }
+        let url = shared.upload_gist(&Config::load()?.github, &html).await?;
+        println!("✅ Shared at {}", url);
+        return Ok(());
+    }
+
+    let path = output.unwrap_or_else(|| PathBuf::from(shared.file_name()));
+    std::fs::write(&path, html)?;
+    println!("✅ Report written to {}", path.display());
+    Ok(())
+}
+
+async fn handle_cost(since: &str, json: bool) -> Result<()> {
+    let since = air::agent::usage::parse_since(since, chrono::Utc::now())?;
+    let data_dir = air::utils::paths::get_air_memory_dir()?;
+    let memory_manager = MemoryManager::new(&data_dir.to_string_lossy()).await?;
+    let report = memory_manager.cost_report(&since.format("%Y-%m-%d %H:%M:%S").to_string()).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let cost = |c: Option<f64>| c.map(|c| format!("{:.4}", c)).unwrap_or_else(|| "n/a".to_string());
+    println!("\n💰 Spend since {} (UTC)", since.format("%Y-%m-%d %H:%M"));
+    if report.by_day.is_empty() {
+        println!("   No model calls recorded");
+        return Ok(());
+    }
+
+    println!("\nBy day:");
+    println!("   {:<12} {:<16} {:<32} {:>8} {:>10} {:>10}", "day", "provider", "model", "calls", "tokens", "cost $");
+    for d in &report.by_day {
+        println!("   {:<12} {:<16} {:<32} {:>8} {:>10} {:>10}", d.day, d.provider, d.model, d.requests, d.tokens, cost(d.cost_usd));
+    }
+
+    println!("\nBy query kind:");
+    for k in &report.by_kind {
+        println!("   {:<8} {:>6} queries {:>8} calls {:>10} $", k.kind, k.queries, k.requests, cost(k.cost_usd));
+    }
+
+    if !report.by_intent.is_empty() {
+        println!("\nBy intent:");
+        for k in &report.by_intent {
+            println!("   {:<12} {:>6} queries {:>8} calls {:>10} $", k.kind, k.queries, k.requests, cost(k.cost_usd));
+        }
+    }
+
+    println!("\nBy session (most recent first):");
+    for s in report.by_session.iter().take(10) {
+        println!("   {}  {}  {:>6} calls {:>10} $", s.started, &s.session_id[..8.min(s.session_id.len())], s.requests, cost(s.cost_usd));
+    }
+    if report.by_session.len() > 10 {
+        println!("   ... and {} more (use --json for all)", report.by_session.len() - 10);
+    }
+
+    let total: f64 = report.by_day.iter().filter_map(|d| d.cost_usd).sum();
+    println!("\nTotal: ${:.4}", total);
+    if report.by_day.iter().any(|d| d.cost_usd.is_none()) {
+        println!("💡 Some models have no [pricing.models] entry or cost_per_million_tokens, so their spend isn't included");
+    }
+    Ok(())
+}
+
+async fn handle_usage(since: &str, weekly: bool, json: bool) -> Result<()> {
+    let since = air::agent::usage::parse_since(since, chrono::Utc::now())?;
+    let data_dir = air::utils::paths::get_air_memory_dir()?;
+    let memory_manager = MemoryManager::new(&data_dir.to_string_lossy()).await?;
+    let usage = memory_manager.usage_by_period(&since.format("%Y-%m-%d %H:%M:%S").to_string(), weekly).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&usage)?);
+        return Ok(());
+    }
+
+    let cost = |c: Option<f64>| c.map(|c| format!("{:.4}", c)).unwrap_or_else(|| "n/a".to_string());
+    println!("\n📊 Token usage since {} (UTC), by {}", since.format("%Y-%m-%d %H:%M"), if weekly { "week" } else { "day" });
+    if usage.is_empty() {
+        println!("   No model calls recorded");
+        return Ok(());
+    }
+
+    println!("   {:<12} {:<16} {:<32} {:>7} {:>10} {:>10} {:>10}", if weekly { "week of" } else { "day" }, "provider", "model", "calls", "input", "output", "cost $");
+    let mut last_period = "";
+    for u in &usage {
+        if !last_period.is_empty() && u.period != last_period {
+            println!();
+        }
+        last_period = &u.period;
+        println!("   {:<12} {:<16} {:<32} {:>7} {:>10} {:>10} {:>10}", u.period, u.provider, u.model, u.requests, u.input_tokens, u.output_tokens, cost(u.cost_usd));
+    }
+
+    let tokens: u64 = usage.iter().map(|u| u.tokens).sum();
+    let total: f64 = usage.iter().filter_map(|u| u.cost_usd).sum();
+    println!("\nTotal: {} calls, {} tokens, ${:.4}", usage.iter().map(|u| u.requests).sum::<u64>(), tokens, total);
+    if usage.iter().any(|u| u.cost_usd.is_none()) {
+        println!("💡 Add [pricing.models] entries or cost_per_million_tokens to price the calls shown as n/a");
+    }
+    Ok(())
+}
+
+async fn handle_doctor() -> Result<()> {
+    use air::rag::embeddings::{embedding_model_status, EmbeddingModelStatus, EMBEDDING_MODEL_ID};
+
+    println!("\n🩺 AIR doctor");
+    let mut problems = 0;
+
+    let config = match Config::load() {
+        Ok(config) => {
+            println!("✅ Config: {}", air::utils::paths::get_air_config_dir()?.join("config.toml").display());
+            config
+        }
+        Err(e) => {
+            problems += 1;
+            println!("❌ Config: {} (falling back to defaults)", e);
+            Config::default()
+        }
+    };
+
+    let local = &config.local_model;
+    let model_path = std::path::Path::new(&local.model_path);
+    if !local.enabled {
+        println!("➖ Local model: disabled");
+    } else if model_path.exists() {
+        let size = std::fs::metadata(model_path).map(|m| m.len()).unwrap_or(0);
+        println!("✅ Local model: {} ({:.0} MB)", model_path.display(), size as f64 / 1_048_576.0);
+        match air::utils::chat_template::resolve(local) {
+            Ok(Some(source)) => println!("✅ Chat template: {}", source),
+            Ok(None) => println!("⚠️  Chat template: none found; set local_model.chat_template if replies look garbled"),
+            Err(e) => {
+                problems += 1;
+                println!("❌ Chat template: {}", e);
+            }
+        }
+    } else {
+        problems += 1;
+        println!("❌ Local model: {} not found; run 'air setup --local' or 'air models pull'", model_path.display());
+    }
+
+    // Loading the model is blocking CPU work
+    match tokio::task::spawn_blocking(embedding_model_status).await? {
+        EmbeddingModelStatus::Ready { dir, size_bytes } => {
+            println!("✅ Embedding model: {} ({:.0} MB in {})", EMBEDDING_MODEL_ID, size_bytes as f64 / 1_048_576.0, dir.display());
+        }
+        EmbeddingModelStatus::Missing(files) => {
+            problems += 1;
+            println!("❌ Embedding model: {} missing ({}); knowledge recall is disabled. Run 'air setup --local'", EMBEDDING_MODEL_ID, files.join(", "));
         }
+        EmbeddingModelStatus::Broken(e) => {
+            problems += 1;
+            println!("❌ Embedding model: {}; knowledge recall is disabled. Run 'air setup --local' to repair it", e);
+        }
+    }
 
-        let selection = Select::new("Select an option:", options.clone())
-            .with_page_size(10)
-            .prompt();
+    let enabled: Vec<&str> = config.cloud_providers.iter().filter(|c| c.enabled).map(|c| c.name.as_str()).collect();
+    if enabled.is_empty() {
+        println!("➖ Cloud providers: none enabled");
+    } else {
+        println!("✅ Cloud providers enabled: {}", enabled.join(", "));
+    }
 
-        match selection {
-            Ok(choice) => {
-                // Find index of choice in original list to determine action
-                let index = options.iter().position(|r| r == &choice).unwrap();
+    if problems == 0 {
+        println!("\n🎉 Everything looks good");
+    } else {
+        println!("\n⚠️  {} problem(s) found", problems);
+    }
+    Ok(())
+}
 
-                match index {
-                    0 => { // Save & Exit
-                        save_config(&config)?;
-                        println!("✅ Configuration saved!");
-                        break;
-                    }
-                    1 => { // Toggle Local Model
-                        config.local_model.enabled = !config.local_model.enabled;
-                    }
-                    2 => { // Set Timeout
-                        let current = config.performance.local_timeout_seconds.to_string();
-                        let validator = |input: &str| {
-                            if input.parse::<u64>().is_ok() {
-                                Ok(Validation::Valid)
-                            } else {
-                                Ok(Validation::Invalid("Please enter a valid number of seconds".into()))
-                            }
-                        };
+/// Lets the user pick a model size and quantization, suggesting the best fit for free memory.
+fn choose_setup_variant() -> model_catalog::Variant {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    let gb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0 / 1024.0;
+    let (total_gb, available_gb) = (gb(sys.total_memory()), gb(sys.available_memory()));
+    println!("🖥️  RAM: {:.1} GB total, {:.1} GB available", total_gb, available_gb);
+
+    // Anything needing more than the machine has is left out entirely
+    let variants: Vec<_> = model_catalog::variants().into_iter().filter(|v| v.ram_gb() <= total_gb).collect();
+    let variants = if variants.is_empty() { model_catalog::variants() } else { variants };
+    let recommended = model_catalog::recommend(&variants, available_gb).unwrap_or(0);
+
+    let options: Vec<String> = variants.iter().enumerate().map(|(i, v)| {
+        format!("{:<16} {:<8} {:>5} MB  ~{:.1} GB RAM{}", v.family, v.quant, v.size_mb, v.ram_gb(),
+            if i == recommended { "  (recommended)" } else { "" })
+    }).collect();
+
+    match inquire::Select::new("📦 Choose a model (higher Q = better answers, more memory):", options)
+        .with_starting_cursor(recommended)
+        .with_help_message("↑↓ to move, enter to select; smaller models answer faster")
+        .raw_prompt()
+    {
+        Ok(choice) => variants[choice.index].clone(),
+        // Not a terminal (or cancelled): go with the suggestion
+        Err(_) => {
+            println!("Using the recommended {} {}", variants[recommended].family, variants[recommended].quant);
+            variants[recommended].clone()
+        }
+    }
+}
 
-                        let ans = Text::new("Enter timeout in seconds:")
-                            .with_default(&current)
-                            .with_validator(validator)
-                            .prompt();
+async fn handle_models_command(command: ModelsCommands) -> Result<()> {
+    let models_dir = air::utils::paths::get_air_models_dir()?;
+    let config = Config::load().unwrap_or_default();
+    let installed = model_catalog::installed(&models_dir);
+    let is_active = |path: &std::path::Path| config.local_model.enabled && PathBuf::from(&config.local_model.model_path) == path;
+
+    match command {
+        ModelsCommands::List => {
+            let ram_gb = total_ram_gb();
+            println!("\n📚 Recommended models (this machine has {:.0} GB RAM)", ram_gb);
+            println!("   {:<20} {:>8} {:>8}  {}", "name", "size", "RAM", "");
+            for entry in model_catalog::CATALOG {
+                let mark = if installed.iter().any(|i| i.file == entry.file) { "✅" } else if (entry.min_ram_gb as f64) > ram_gb { "⚠️ " } else { "  " };
+                println!("{} {:<20} {:>5} MB {:>5} GB  {}", mark, entry.name, entry.size_mb, entry.min_ram_gb, entry.description);
+            }
 
-                        if let Ok(value) = ans {
-                            if let Ok(seconds) = value.parse::<u64>() {
-                                config.performance.local_timeout_seconds = seconds;
-                                println!("✅ Timeout updated to {}s", seconds);
-                            }
-                        }
-                    }
-                    3 => { // Change Local Model File
-                         let models = scan_for_models(&config);
-                         if models.is_empty() {
-                             println!("❌ No models found to select.");
-                         } else {
-                             prompt_model_selection(&mut config, &models)?;
-                         }
-                    }
-                    4 => { // Change Local Inference Device
-                         let devices = vec!["cuda", "cpu", "metal", "auto"];
-                         let selection = Select::new("Select inference device:", devices).prompt();
-                         if let Ok(device) = selection {
-                             config.local_model.device = device.to_string();
-                             println!("✅ Device updated to {}", device);
-                         }
+            println!("\n💾 Installed in {}", models_dir.display());
+            if installed.is_empty() {
+                println!("   Nothing yet; try 'air models pull {}'", model_catalog::DEFAULT_MODEL);
+            }
+            for entry in &installed {
+                println!("   {} {:<28} {:>6.0} MB  {}", if is_active(&entry.path) { "▶" } else { " " },
+                    entry.name, entry.size_bytes as f64 / 1_048_576.0, entry.repo);
+            }
+            // Models copied into the folder by hand, or downloaded before the registry existed
+            for path in scan_for_models(&config).into_iter().filter(|p| !installed.iter().any(|i| &i.path == p)) {
+                println!("   {} {} (not pulled with air)", if is_active(&path) { "▶" } else { " " }, path.display());
+            }
+        }
+        ModelsCommands::Pull { model, use_model } => {
+            let (name, repo, file) = model_catalog::resolve_pull_spec(&model)?;
+            if let Some(entry) = model_catalog::find(&name) {
+                let ram_gb = total_ram_gb();
+                if entry.min_ram_gb as f64 > ram_gb {
+                    println!("⚠️  {} needs about {} GB RAM; this machine has {:.0} GB", entry.name, entry.min_ram_gb, ram_gb);
+                }
+            }
+            println!("⬇️  Pulling {} from {}", file, repo);
+            let entry = model_catalog::pull(&models_dir, &name, &repo, &file).await?;
+            println!("✅ {} saved to {}", entry.name, entry.path.display());
+
+            if use_model {
+                let mut config = config.clone();
+                config.local_model.model_path = entry.path.to_string_lossy().to_string();
+                config.local_model.enabled = true;
+                save_config(&config)?;
+                println!("🧠 Now the local model");
+            } else {
+                println!("💡 Switch to it with '/model' in a session, or pull with --use");
+            }
+        }
+        ModelsCommands::Rm { name } => {
+            let entry = model_catalog::remove(&models_dir, &name)?;
+            println!("🗑️  Deleted {}", entry.path.display());
+            if PathBuf::from(&config.local_model.model_path) == entry.path {
+                println!("⚠️  It was the configured local model; pick another with 'air models pull <name> --use'");
+            }
+        }
+        ModelsCommands::Info { name } => {
+            let catalog = model_catalog::find(&name);
+            let local = installed.iter().find(|e| e.name.eq_ignore_ascii_case(&name)
+                || catalog.is_some_and(|c| c.file == e.file));
+            if catalog.is_none() && local.is_none() {
+                return Err(anyhow::anyhow!("Unknown model '{}' (see 'air models list')", name));
+            }
+
+            println!("\n📦 {}", local.map(|e| e.name.as_str()).or(catalog.map(|c| c.name)).unwrap_or(&name));
+            if let Some(entry) = catalog {
+                println!("   {}", entry.description);
+                println!("   Size:        {} MB", entry.size_mb);
+                println!("   RAM needed:  {} GB (this machine: {:.0} GB)", entry.min_ram_gb, total_ram_gb());
+            }
+            let (repo, file) = match (local, catalog) {
+                (Some(e), _) => (e.repo.as_str(), e.file.as_str()),
+                (None, Some(c)) => (c.repo, c.file),
+                (None, None) => unreachable!("checked above"),
+            };
+            println!("   Source:      https://huggingface.co/{}/blob/main/{}", repo, file);
+            match local {
+                Some(entry) => {
+                    println!("   Installed:   {} ({:.0} MB, pulled {})", entry.path.display(), entry.size_bytes as f64 / 1_048_576.0, entry.pulled_at);
+                    if is_active(&entry.path) {
+                        println!("   ▶ This is the current local model");
                     }
-                    _ => { // Toggle Cloud Provider
-                        // Cloud providers start at index 5
-                        let provider_idx = index - 5;
-                        if let Some(provider) = config.cloud_providers.get_mut(provider_idx) {
-                             provider.enabled = !provider.enabled;
-                        }
+                    let mut local_config = config.local_model.clone();
+                    local_config.model_path = entry.path.to_string_lossy().to_string();
+                    match air::utils::chat_template::resolve(&local_config) {
+                        Ok(Some(source)) => println!("   Template:    {}", source),
+                        Ok(None) => println!("   Template:    unknown (set local_model.chat_template)"),
+                        Err(e) => println!("   Template:    {}", e),
                     }
                 }
-            }
-            Err(_) => {
-                println!("Operation cancelled.");
-                break;
+                None => println!("   Installed:   no ('air models pull {}')", name),
             }
         }
     }
-
     Ok(())
 }
 
-async fn handle_login() -> Result<()> {
+fn total_ram_gb() -> f64 {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    sys.total_memory() as f64 / 1024.0 / 1024.0 / 1024.0
+}
+
+async fn handle_login(export_env: bool) -> Result<()> {
     println!("\n🔑 Login Setup for Gemini (Google)");
     println!("══════════════════════════════════");
     println!("To use Gemini, you need an API key from Google AI Studio.");
@@ -280,39 +1888,39 @@ async fn handle_login() -> Result<()> {
         return Ok(());
     }
 
-    // Determine config directory
-    let air_dir = air::utils::paths::get_air_data_dir()?;
+    let air_dir = air::utils::paths::get_air_config_dir()?;
     let env_path = air_dir.join(".env");
-    let mut env_content = String::new();
 
-    if env_path.exists() {
-        env_content = std::fs::read_to_string(&env_path)?;
-    }
-
-    // Update or append GEMINI_API_KEY
-    let mut new_lines = Vec::new();
-    let mut found = false;
-
-    for line in env_content.lines() {
-        if line.starts_with("GEMINI_API_KEY=") {
-            new_lines.push(format!("GEMINI_API_KEY={}", key));
-            found = true;
-        } else {
-            new_lines.push(line.to_string());
+    if export_env {
+        // Escape hatch: plaintext .env, including any keys previously stored in the keyring
+        for name in secrets::API_KEY_VARS {
+            if *name == "GEMINI_API_KEY" {
+                continue;
+            }
+            if let Some(value) = secrets::get_api_key(name) {
+                secrets::write_env_var(&env_path, name, &value)?;
+            }
         }
+        secrets::write_env_var(&env_path, "GEMINI_API_KEY", key)?;
+        secrets::set_plaintext_choice(&env_path, true)?;
+        println!("\n⚠️  Gemini API Key saved in plaintext to {:?}", env_path);
+        println!("You can now use 'air' to chat with Gemini.");
+        return Ok(());
     }
 
-    if !found {
-        new_lines.push(format!("GEMINI_API_KEY={}", key));
-    }
-
-    // Write back to .env
-    let mut file = std::fs::File::create(&env_path)?;
-    for line in new_lines {
-        writeln!(file, "{}", line)?;
+    match secrets::store_api_key("GEMINI_API_KEY", key) {
+        Ok(()) => {
+            // Don't leave an older plaintext copy behind, even one kept by --export-env
+            secrets::set_plaintext_choice(&env_path, false)?;
+            secrets::migrate_env_file(&env_path).ok();
+            println!("\n✅ Gemini API Key saved to the OS keyring");
+        }
+        Err(e) => {
+            println!("\n❌ {}", e);
+            println!("💡 Run 'air login --export-env' to store the key in {:?} instead.", env_path);
+            return Ok(());
+        }
     }
-
-    println!("\n✅ Gemini API Key saved successfully to {:?}", env_path);
     println!("You can now use 'air' to chat with Gemini.");
 
     Ok(())
@@ -324,33 +1932,24 @@ async fn handle_local_setup() -> Result<()> {
     println!("This will help you set up a GGUF model for local inference.");
 
     // Check for models directory
-    let air_dir = air::utils::paths::get_air_data_dir()?;
-    let models_dir = air_dir.join("models");
-
-    if !models_dir.exists() {
-        std::fs::create_dir_all(&models_dir)?;
-        println!("Created models directory: {:?}", models_dir);
-    }
+    let models_dir = air::utils::paths::get_air_models_dir()?;
+    println!("Models directory: {:?}", models_dir);
 
-    let model_filename = "tinyllama-1.1b-chat-v1.0.Q2_K.gguf";
-    let model_path = models_dir.join(model_filename);
+    let model = choose_setup_variant();
+    let model_path = models_dir.join(&model.file);
 
     if model_path.exists() {
         println!("✅ Model already exists at: {:?}", model_path);
     } else {
-        println!("⚠️  Model not found.");
-        println!("Downloading TinyLlama (approx 480MB)...");
+        println!("Downloading {} {} (approx {} MB)...", model.family, model.quant, model.size_mb);
 
-        let url = "https://huggingface.co/TheBloke/TinyLlama-1.1B-Chat-v1.0-GGUF/resolve/main/tinyllama-1.1b-chat-v1.0.Q2_K.gguf";
-        let response = reqwest::get(url).await?;
-
-        if response.status().is_success() {
-            let content = response.bytes().await?;
-            std::fs::write(&model_path, content)?;
-            println!("✅ Successfully downloaded model to: {:?}", model_path);
-        } else {
-            println!("❌ Failed to download model: {}", response.status());
-            return Ok(());
+        match model_catalog::pull(&models_dir, &model.name(), model.repo, &model.file).await {
+            Ok(_) => println!("✅ Successfully downloaded model to: {:?}", model_path),
+            Err(e) => {
+                println!("❌ Failed to download model: {}", e);
+                println!("💡 Run 'air setup --local' again to resume where it stopped.");
+                return Ok(());
+            }
         }
     }
 
@@ -364,15 +1963,29 @@ async fn handle_local_setup() -> Result<()> {
         println!("⚠️  Tokenizer not found.");
         println!("Downloading tokenizer...");
 
-        let url = "https://huggingface.co/TinyLlama/TinyLlama-1.1B-Chat-v1.0/resolve/main/tokenizer.json";
-        let response = reqwest::get(url).await?;
+        if let Some(repo) = model.tokenizer_repo {
+            let urls = download::huggingface_urls(repo, tokenizer_filename);
+            match download::download(&urls, &tokenizer_path, None).await {
+                Ok(()) => println!("✅ Successfully downloaded tokenizer to: {:?}", tokenizer_path),
+                Err(e) => println!("❌ Failed to download tokenizer: {}", e),
+            }
+        }
+    }
 
-        if response.status().is_success() {
-            let content = response.bytes().await?;
-            std::fs::write(&tokenizer_path, content)?;
-            println!("✅ Successfully downloaded tokenizer to: {:?}", tokenizer_path);
-        } else {
-            println!("❌ Failed to download tokenizer: {}", response.status());
+    // Embedding model for the knowledge store (memory recall, `air memory add`)
+    println!("\n📚 Checking the embedding model ({})...", air::rag::embeddings::EMBEDDING_MODEL_ID);
+    match air::rag::embeddings::embedding_model_status() {
+        air::rag::embeddings::EmbeddingModelStatus::Ready { .. } => println!("✅ Embedding model ready"),
+        status => {
+            if let air::rag::embeddings::EmbeddingModelStatus::Broken(e) = &status {
+                println!("⚠️  Embedding model unusable ({}); downloading again...", e);
+            } else {
+                println!("Downloading embedding model (approx 90MB)...");
+            }
+            match tokio::task::spawn_blocking(air::rag::embeddings::download_embedding_model).await? {
+                Ok(dir) => println!("✅ Embedding model verified at: {:?}", dir),
+                Err(e) => println!("❌ Failed to set up the embedding model: {}. Knowledge recall stays disabled until it's installed.", e),
+            }
         }
     }
 
@@ -419,95 +2032,400 @@ async fn handle_local_setup() -> Result<()> {
         println!("⚠️ config.toml not found. Skipping update.");
     }
 
-    println!("\n🎉 You are ready to go! Run 'air --local-only' to force local mode.");
+    println!("\n🎉 You are ready to go! Run 'air --mode local' to force local mode.");
 
     Ok(())
 }
 
-async fn run_interactive_mode(agent: AIAgent) -> Result<()> {
-    println!("\n🤖 AIR Agent Mode");
+async fn run_interactive_mode(mut agent: AIAgent, render_markdown: bool, overrides: CliOverrides) -> Result<()> {
+    println!("{}", tr("repl.title"));
     println!("════════════════════════");
-    println!("💡 Type your questions and I'll help you!");
-    println!("📝 Special commands:");
-    println!("   • 'exit' or 'quit' - Exit the program");
-    println!("   • 'help' - Show available commands");
-    println!("   • 'stats' - Show usage statistics");
-    println!("   • 'clear' - Clear the screen");
+    println!("{}", tr("repl.intro"));
+    println!("{}", tr("repl.commands"));
+    for command in ["exit", "help", "stats", "model", "undo", "branch", "copy", "clear"] {
+        println!("{}", tr(&format!("repl.cmd_{}", command)));
+    }
     println!("═══════════════════════════════════════");
     
+    if agent.config().planner.remind_on_start {
+        show_task_reminders(&agent).await;
+    }
+
+    // Pick up edits to config.toml, profiles and .env without restarting the session
+    let watcher = air::utils::paths::get_air_config_dir().ok()
+        .and_then(|dir| match ConfigWatcher::new(&dir) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                tracing::warn!("Configuration hot reload unavailable: {}", e);
+                None
+            }
+        });
+    
+    // Line editing with arrow-key history and Ctrl+R search, kept across sessions.
+    // Pasted text and `"""` blocks or trailing `\` continue onto new lines instead of submitting.
+    let mut editor: Editor<MultilineHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(MultilineHelper));
+    let history_path = air::utils::paths::get_air_data_dir().ok().map(|dir| dir.join("history.txt"));
+    if let Some(path) = &history_path {
+        // A missing file just means this is the first session
+        editor.load_history(path).ok();
+    }
+
+    // Conversation branches other than the current one, saved when switching away
+    let mut branches: std::collections::BTreeMap<String, air::agent::AgentSnapshot> = std::collections::BTreeMap::new();
+    let mut branch = "main".to_string();
+    let mut last_answer: Option<String> = None;
+    // Opened on first /copy and kept, since on Linux copied text is only served while it lives
+    let mut clipboard: Option<air::utils::clipboard::Clipboard> = None;
+    
     loop {
-        // Display prompt
-        print!("\n💬 You: ");
-        io::stdout().flush()?;
-        
         // Read user input
-        let mut input = String::new();
-        match std::io::stdin().read_line(&mut input) {
-            Ok(_) => {
-                let query = input.trim().to_string();
-                
-                // Handle special commands
-                match query.trim().to_lowercase().as_str() {
-                    "exit" | "quit" | "q" => {
-                        println!("\n👋 Goodbye! Thanks for using AIR!");
-                        break;
-                    }
-                    "help" | "h" => {
-                        show_help();
-                        continue;
-                    }
-                    "stats" => {
-                        show_stats().await?;
-                        continue;
-                    }
-                    "clear" | "cls" => {
-                        // Clear screen (works on both Windows and Unix)
-                        print!("\x1B[2J\x1B[1;1H");
-                        io::stdout().flush()?;
-                        continue;
-                    }
-                    "" => {
-                        println!("💭 Please enter a question or command. Type 'help' for assistance.");
-                        continue;
-                    }
-                    _ => {}
+        println!();
+        let prompt = if branch == "main" { tr("repl.you") } else { tr_args("repl.you_on_branch", &[&branch]) };
+        let input = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                println!("{}", tr("repl.interrupted"));
+                continue;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("{}", tr("repl.goodbye"));
+                break;
+            }
+            Err(e) => {
+                println!("{}", tr_args("repl.read_error", &[&e]));
+                break;
+            }
+        };
+        if !input.trim().is_empty() {
+            editor.add_history_entry(input.trim()).ok();
+            // Saved as we go so history survives the terminal being closed
+            if let Some(path) = &history_path {
+                if let Err(e) = editor.save_history(path) {
+                    tracing::debug!("Could not save input history: {}", e);
                 }
-                
-                // Process the query
-                println!("\n🤖 AIR: Processing your request...");
-                
-                match agent.query_with_tools(&query).await {
-                    Ok(response) => {
-                        println!("\n🤖 AI Response:");
-                        println!("{}", response);
-                    }
-                    Err(e) => {
-                        println!("\n❌ Error: {}", e);
-                        println!("💡 Try rephrasing your question or check your configuration.");
-                    }
+            }
+        }
+
+        let query = multiline::normalize(&input);
+
+        if let Some(watcher) = &watcher {
+            apply_config_changes(&mut agent, watcher, &overrides).await;
+        }
+        
+        // `/model [number|path]` shows or switches the local model
+        if let Some(arg) = query.trim().strip_prefix("/model") {
+            if let Err(e) = handle_model_command(&mut agent, arg.trim()).await {
+                println!("❌ {}", e);
+            }
+            continue;
+        }
+
+        if let Some(arg) = query.trim().strip_prefix("/copy") {
+            let result = answer_part(last_answer.as_deref(), arg.trim()).and_then(|text| {
+                if clipboard.is_none() {
+                    clipboard = Some(air::utils::clipboard::Clipboard::new()?);
                 }
+                clipboard.as_mut().unwrap().copy(text)?;
+                Ok(text.lines().count())
+            });
+            match result {
+                Ok(lines) => println!("{}", tr_args("answer.copied", &[&lines])),
+                Err(e) => println!("❌ {}", e),
             }
-            Err(e) => {
-                println!("\n❌ Error reading input: {}", e);
+            continue;
+        }
+
+        if let Some(arg) = query.trim().strip_prefix("/save") {
+            let arg = arg.trim();
+            let (part, path) = match arg.strip_prefix("code ") {
+                Some(path) => ("code", path.trim()),
+                None => ("", arg),
+            };
+            let result = if path.is_empty() {
+                Err(anyhow::anyhow!(tr("answer.save_usage")))
+            } else {
+                answer_part(last_answer.as_deref(), part).and_then(|text| Ok(std::fs::write(path, format!("{}\n", text))?))
+            };
+            match result {
+                Ok(()) => println!("{}", tr_args("answer.saved", &[&path])),
+                Err(e) => println!("❌ {}", e),
+            }
+            continue;
+        }
+
+        if let Some(arg) = query.trim().strip_prefix("/branch") {
+            if let Err(e) = handle_branch_command(&agent, &mut branches, &mut branch, arg.trim()).await {
+                println!("❌ {}", e);
+            }
+            continue;
+        }
+
+        // Handle special commands
+        match query.trim().to_lowercase().as_str() {
+            "exit" | "quit" | "q" => {
+                println!("{}", tr("repl.goodbye"));
                 break;
             }
+            "help" | "h" => {
+                show_help();
+                continue;
+            }
+            "stats" => {
+                show_stats(&agent).await?;
+                continue;
+            }
+            "clear" | "cls" => {
+                editor.clear_screen().ok();
+                continue;
+            }
+            "/undo" => {
+                match agent.undo_last_exchange().await {
+                    Ok(Some(exchange)) => println!("{}", tr_args("answer.undone", &[&exchange.user_input.lines().next().unwrap_or_default()])),
+                    Ok(None) => println!("{}", tr("answer.nothing_to_undo")),
+                    Err(e) => println!("❌ {}", e),
+                }
+                continue;
+            }
+            "" => {
+                println!("{}", tr("repl.empty"));
+                continue;
+            }
+            _ => {}
+        }
+        
+        // Process the query; Ctrl+C abandons it and returns to the prompt
+        println!("{}", tr("repl.processing"));
+        
+        tokio::select! {
+            result = agent.query_with_tools(&query) => match result {
+                Ok(response) => {
+                    println!("{}", tr("answer.heading"));
+                    print_response(&response.content, render_markdown);
+                    last_answer = Some(response.content.clone());
+                    // Kept as context for the following prompts until /undo
+                    if let Err(e) = agent.store_conversations_batch(vec![(query.clone(), response.content, None, None)]).await {
+                        tracing::debug!("Could not record the exchange: {}", e);
+                    }
+                }
+                Err(e) => {
+                    println!("{}", tr_args("repl.error", &[&e]));
+                    println!("{}", tr("repl.error_hint"));
+                }
+            },
+            _ = tokio::signal::ctrl_c() => {
+                println!("{}", tr("repl.cancelled"));
+            }
         }
     }
     
     Ok(())
 }
 
+/// The last answer for `/copy` and `/save`: all of it, or its last code block when `part` is "code".
+fn answer_part<'a>(answer: Option<&'a str>, part: &str) -> Result<&'a str> {
+    let answer = answer.ok_or_else(|| anyhow::anyhow!(tr("answer.none_yet")))?;
+    match part {
+        "" => Ok(air::utils::clipboard::answer_text(answer)),
+        "code" => air::utils::clipboard::last_code_block(answer).ok_or_else(|| anyhow::anyhow!(tr("answer.no_code_block"))),
+        other => Err(anyhow::anyhow!("Unknown option '{}': use code or nothing", other)),
+    }
+}
+
+/// `/branch` lists branches; `/branch <name>` saves the current conversation and either forks it
+/// into a new branch or switches to the saved branch of that name.
+async fn handle_branch_command(
+    agent: &AIAgent,
+    branches: &mut std::collections::BTreeMap<String, air::agent::AgentSnapshot>,
+    current: &mut String,
+    name: &str,
+) -> Result<()> {
+    if name.is_empty() {
+        println!("{}", tr_args("branch.current", &[&current.as_str()]));
+        for saved in branches.keys() {
+            println!("   {}", saved);
+        }
+        return Ok(());
+    }
+    if name == current {
+        println!("{}", tr_args("branch.already_on", &[&name]));
+        return Ok(());
+    }
+
+    let snapshot = agent.snapshot().await?;
+    match branches.remove(name) {
+        Some(saved) => {
+            agent.restore(saved).await?;
+            println!("{}", tr_args("branch.switched", &[&name, &current.as_str()]));
+        }
+        None => println!("{}", tr_args("branch.forked", &[&current.as_str(), &name, &current.as_str()])),
+    }
+    branches.insert(std::mem::replace(current, name.to_string()), snapshot);
+    Ok(())
+}
+
+async fn apply_config_changes(agent: &mut AIAgent, watcher: &ConfigWatcher, overrides: &CliOverrides) {
+    let changed = watcher.changed_files();
+    if changed.is_empty() {
+        return;
+    }
+
+    if changed.iter().any(|path| watcher.is_env_file(path)) {
+        if let Err(e) = watcher.reload_env() {
+            println!("⚠️  Could not reload .env: {}", e);
+        }
+    }
+
+    match Config::load() {
+        Ok(mut config) => {
+            // --mode/--provider/--model given on the command line outlive edits to the file
+            if let Err(e) = overrides.apply(&mut config) {
+                println!("⚠️  Configuration changed but could not be applied; keeping current settings: {}", e);
+                return;
+            }
+            let changes = agent.reload_config(config).await;
+            if changes.is_empty() {
+                println!("🔄 Configuration reloaded (nothing affecting this session changed)");
+            } else {
+                println!("🔄 Configuration reloaded: {}", changes.join(", "));
+            }
+        }
+        Err(e) => println!("⚠️  Configuration changed but could not be loaded; keeping current settings: {}", e),
+    }
+}
+
+async fn handle_model_command(agent: &mut AIAgent, arg: &str) -> Result<()> {
+    let models = scan_for_models(agent.config());
+    if arg.is_empty() {
+        let local = &agent.config().local_model;
+        if local.enabled {
+            println!("🧠 Current local model: {}", local.model_path);
+        } else {
+            println!("🧠 Local model is disabled");
+        }
+        for (i, model) in models.iter().enumerate() {
+            println!("   {}. {}", i + 1, model.display());
+        }
+        println!("💡 Use /model <number> or /model <path> to switch");
+        return Ok(());
+    }
+
+    let path = match arg.parse::<usize>() {
+        Ok(n) if (1..=models.len()).contains(&n) => models[n - 1].to_string_lossy().to_string(),
+        Ok(n) => return Err(anyhow::anyhow!("No model #{}; /model lists {} models", n, models.len())),
+        Err(_) => arg.to_string(),
+    };
+
+    println!("⏳ Loading {}...", path);
+    agent.reload_local_model(&path).await?;
+
+    // Saved so the next session starts with this model too
+    let mut config = Config::load()?;
+    config.local_model.model_path = path.clone();
+    config.local_model.enabled = true;
+    save_config(&config)?;
+    println!("✅ Now using {}", path);
+    Ok(())
+}
+
+async fn show_task_reminders(agent: &AIAgent) {
+    let notify = agent.config().planner.desktop_notifications;
+    let result = match agent.execute_tool("planner", "due_tasks", serde_json::json!({ "notify": notify })).await {
+        Ok(result) => result.result,
+        Err(_) => return,
+    };
+    
+    let titles = |key: &str| -> Vec<String> {
+        result[key].as_array()
+            .map(|tasks| tasks.iter().map(|task| {
+                let due = task["due_date"].as_str()
+                    .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+                    .map(|d| d.with_timezone(&chrono::Local).format("%a %d %b %H:%M").to_string())
+                    .unwrap_or_default();
+                format!("{} ({})", task["title"].as_str().unwrap_or("untitled"), due)
+            }).collect())
+            .unwrap_or_default()
+    };
+    let overdue = titles("overdue");
+    let due_today = titles("due_today");
+    
+    if overdue.is_empty() && due_today.is_empty() {
+        return;
+    }
+    
+    println!("\n⏰ Task reminders:");
+    for title in overdue {
+        println!("   🔴 Overdue: {}", title);
+    }
+    for title in due_today {
+        println!("   🟡 Due today: {}", title);
+    }
+}
+
 // --- Model Selection Helpers ---
 
+/// Writes the settings changed since the config was loaded to config.toml (or the active
+/// profile), editing those keys in place so the file keeps its comments and layout.
 fn save_config(config: &Config) -> Result<()> {
-    let config_dir = air::utils::paths::get_air_data_dir()?;
+    let before = saved_settings(&Config::load_profile(config.active_profile.as_deref())?)?;
+    let after = saved_settings(config)?;
+    write_settings(config, before, after)
+}
+
+/// Deletes `key` from config.toml (or the active profile) so the value below it applies again,
+/// instead of writing that value into the file.
+fn remove_saved_key(config: &Config, key: &str) -> Result<()> {
+    let before = saved_settings(&Config::load_profile(config.active_profile.as_deref())?)?;
+    let mut after = before.clone();
+    air::config::remove_key(&mut after, key)?;
+    write_settings(config, before, after)
+}
+
+/// Applies the difference between `before` and `after` to the file `config`'s settings come from.
+fn write_settings(config: &Config, before: toml::Value, after: toml::Value) -> Result<()> {
+    let config_dir = air::utils::paths::get_air_config_dir()?;
     let config_path = config_dir.join("config.toml");
 
-    let toml_string = toml::to_string_pretty(config)?;
-    std::fs::write(&config_path, toml_string)?;
+    // With a profile active, the edited settings belong to that profile, not the base config
+    let (path, before, after) = match &config.active_profile {
+        Some(profile) => {
+            let profile_path = air::config::profile_path(&config_dir, profile)?;
+            if profile_path.exists() || !config.profiles.contains_key(profile) {
+                std::fs::create_dir_all(config_dir.join("profiles"))?;
+                (profile_path, before, after)
+            } else {
+                let nest = |settings: toml::Value| toml::Value::Table(toml::Table::from_iter([(
+                    "profile".to_string(),
+                    toml::Value::Table(toml::Table::from_iter([(profile.clone(), settings)])),
+                )]));
+                (config_path, nest(before), nest(after))
+            }
+        }
+        None => (config_path, before, after),
+    };
+
+    let text = if path.exists() { std::fs::read_to_string(&path)? } else { String::new() };
+    std::fs::write(&path, air::config::edit_toml(&text, &before, &after)?)?;
     Ok(())
 }
 
+/// The parts of `config` that belong in a config file: no project overrides, no keys loaded
+/// from the environment or keyring (they must not end up in the file in plaintext), no profiles.
+fn saved_settings(config: &Config) -> Result<toml::Value> {
+    let mut config = config.without_project()?;
+    for provider in &mut config.cloud_providers {
+        let from_env = air::config::api_key_env_var(&provider.name).and_then(|var| std::env::var(var).ok());
+        if from_env.is_some() && provider.api_key == from_env {
+            provider.api_key = None;
+        }
+    }
+    let mut settings = toml::Value::try_from(&config)?;
+    if let Some(table) = settings.as_table_mut() {
+        table.remove("profile");
+    }
+    Ok(settings)
+}
+
 fn scan_for_models(config: &Config) -> Vec<PathBuf> {
     let mut models = Vec::new();
     let mut visited = HashSet::new();
@@ -520,14 +2438,12 @@ fn scan_for_models(config: &Config) -> Vec<PathBuf> {
         }
     }
 
-    // 2. C:\models (Windows)
-    if cfg!(windows) {
-        search_dirs.push(PathBuf::from(r"C:\models"));
-    }
+    // 2. Folders listed in local_model.model_dirs
+    search_dirs.extend(config.local_model.model_dirs.iter().map(PathBuf::from));
 
     // 3. App Data Models
-    if let Ok(air_dir) = air::utils::paths::get_air_data_dir() {
-        search_dirs.push(air_dir.join("models"));
+    if let Ok(models_dir) = air::utils::paths::get_air_models_dir() {
+        search_dirs.push(models_dir);
     }
 
     // 4. Current dir models
@@ -594,11 +2510,11 @@ fn ensure_model_selected(config: &mut Config) -> Result<()> {
     if models.is_empty() {
         println!("⚠️  No local models (GGUF) found.");
         println!("   Please run 'air setup --local' to download a model,");
-        println!("   or place your .gguf files in C:\\models or the 'models' folder in your data directory.");
+        println!("   or place your .gguf files in {:?} (or add folders to local_model.model_dirs).", air::utils::paths::models_dir_path());
         return Ok(());
     }
 
-    let default_path = "C:\\models\\tinyllama-1.1b-chat-v1.0.Q2_K.gguf";
+    let default_path = air::config::LocalModelConfig::default().model_path;
     let current_path = PathBuf::from(&config.local_model.model_path);
 
     // Check if the current configured path actually exists
@@ -633,18 +2549,24 @@ fn ensure_model_selected(config: &mut Config) -> Result<()> {
     Ok(())
 }
 
-async fn run_single_query(agent: AIAgent, args: Args) -> Result<()> {
-    let prompt = args.prompt.as_ref().unwrap();
-    
+async fn run_single_query(agent: AIAgent, prompt: &str, render_markdown: bool) -> Result<()> {
     // Process the request
     let response = agent.query_with_tools(prompt).await?;
     
-    println!("\n🤖 AI Response:");
-    println!("{}", response);
+    println!("{}", tr("answer.heading"));
+    print_response(&response.content, render_markdown);
     
     Ok(())
 }
 
+fn print_response(content: &str, render_markdown: bool) {
+    if render_markdown {
+        print!("{}", markdown::render(content));
+    } else {
+        println!("{}", content);
+    }
+}
+
 fn show_help() {
     println!("\n📚 AIR Help - Available Commands:");
     println!("══════════════════════════════════");
@@ -652,7 +2574,15 @@ fn show_help() {
     println!("   • exit, quit, q    - Exit the program");
     println!("   • help, h          - Show this help message");
     println!("   • stats            - Show usage statistics");
+    println!("   • /model [n|path]  - List local models, or switch to one without restarting");
+    println!("   • /undo            - Forget the last exchange");
+    println!("   • /branch [name]   - Fork the conversation into a new branch, switch to a saved one, or list them");
+    println!("   • /copy [code]     - Copy the last answer (or its last code block) to the clipboard");
+    println!("   • /save [code] <f> - Write the last answer (or its last code block) to a file");
     println!("   • clear, cls       - Clear the screen");
+    println!("   • ↑/↓, Ctrl+R      - Browse and search input history");
+    println!("   • Ctrl+C           - Cancel the running query (Ctrl+D exits)");
+    println!("   • \"\"\" ... \"\"\"      - Multi-line prompt (a trailing \\ also continues the line)");
     println!();
     println!("🔹 File System Operations:");
     println!("   • read file [path]          - Read and analyze a file");
@@ -670,7 +2600,10 @@ fn show_help() {
     println!("🔹 Screenshot & Media:");
     println!("   • screenshot                - Take full screen capture");
     println!("   • screenshot region         - Capture specific screen region");
+    println!("   • screenshot display 2      - Capture a specific monitor");
+    println!("   • screenshot window <title> - Capture a single window");
     println!("   • list screenshots          - Show saved screenshots");
+    println!("   • what's on my screen?      - Capture and describe the screen");
     println!();
     println!("🔹 Voice Commands:");
     println!("   • speak [text]              - Text-to-speech synthesis");
@@ -699,12 +2632,24 @@ fn show_help() {
     println!("═══════════════════════════════════════════════════════════════════");
 }
 
-async fn show_stats() -> Result<()> {
+async fn show_stats(agent: &AIAgent) -> Result<()> {
     println!("\n📊 AIR Usage Statistics:");
     println!("═══════════════════════");
-    println!("☁️  Cloud Models: Check configuration");
-    println!("⚡ Status: Ready for queries");
-    println!("💡 Tip: Use 'help' to see available commands");
-    
+    for (title, session_only) in [("This session", true), ("All time", false)] {
+        println!("\n{}:", title);
+        let summary = agent.usage_summary(session_only).await?;
+        if summary.is_empty() {
+            println!("   No model calls yet");
+            continue;
+        }
+        println!("   {:<20} {:>8} {:>8} {:>10} {:>10} {:>10}", "provider", "ok", "failed", "avg ms", "tokens", "cost $");
+        for s in &summary {
+            println!("   {:<20} {:>8} {:>8} {:>10} {:>10} {:>10}",
+                s.provider, s.successes, s.failures, s.avg_latency_ms, s.tokens,
+                s.cost_usd.map(|c| format!("{:.4}", c)).unwrap_or_else(|| "n/a".to_string()));
+        }
+    }
+    println!("\n💡 Set cost_per_million_tokens on a cloud provider to track its spend");
+
     Ok(())
 }
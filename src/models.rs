@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use std::fmt;
@@ -9,8 +10,60 @@ pub struct ModelResponse {
     pub content: String,
     pub model_used: String,
     pub tokens_used: u32,
+    /// How many of `tokens_used` were the prompt; 0 when the provider doesn't report the split.
+    #[serde(default)]
+    pub input_tokens: u32,
     pub response_time_ms: u64,
     pub confidence_score: Option<f32>,
+    /// Tools the model called natively (see `QueryContext::tools`), in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// A request from the model to run one function of a tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub tool_name: String,
+    pub function: String,
+    pub arguments: serde_json::Value,
+}
+
+/// A tool offered to providers with native function calling. Each tool is one function whose
+/// arguments name the tool function to run and its arguments, matching `ToolCall`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub functions: Vec<String>,
+}
+
+impl ToolSpec {
+    /// JSON schema of the call arguments.
+    pub fn parameters(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "function": { "type": "string", "enum": self.functions, "description": "The function of the tool to run" },
+                "args": { "type": "object", "description": "Arguments of the function" }
+            },
+            "required": ["function"]
+        })
+    }
+
+    /// Reads the arguments of a native call of this tool back into a `ToolCall`. `args` may
+    /// also be a JSON string, for providers whose schemas can't hold a free-form object.
+    pub fn call(name: &str, input: &serde_json::Value) -> ToolCall {
+        let arguments = match input.get("args") {
+            Some(serde_json::Value::String(args)) => serde_json::from_str(args).unwrap_or_else(|_| serde_json::json!({})),
+            Some(args) if !args.is_null() => args.clone(),
+            _ => serde_json::json!({}),
+        };
+        ToolCall {
+            tool_name: name.to_string(),
+            function: input["function"].as_str().unwrap_or_default().to_string(),
+            arguments,
+        }
+    }
 }
 
 impl fmt::Display for ModelResponse {
@@ -19,38 +72,220 @@ impl fmt::Display for ModelResponse {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Message {
-    pub role: String,
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMode {
+    #[default]
+    Auto,       // Smart fallback (default)
+    LocalOnly,  // Force local model
+    CloudOnly,  // Force cloud model
+    PureLocal,  // Pure local model without templates
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    /// The result of a tool call, fed back to the model.
+    Tool,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+/// One turn of a conversation sent to a model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
     pub content: String,
+    /// The tool that produced a `Role::Tool` message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self { role, content: content.into(), name: None }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    pub fn tool(name: impl Into<String>, content: impl Into<String>) -> Self {
+        Self { role: Role::Tool, content: content.into(), name: Some(name.into()) }
+    }
+
+    /// The content to send. AIR's tool calls are JSON in the answer text rather than native
+    /// function calls, so providers send tool results as user turns, labelled with the tool.
+    pub fn text(&self) -> String {
+        match (self.role, &self.name) {
+            (Role::Tool, Some(name)) => format!("Result of the {} tool:\n{}", name, self.content),
+            (Role::Tool, None) => format!("Tool result:\n{}", self.content),
+            _ => self.content.clone(),
+        }
+    }
+}
+
+/// An image sent along with a request, for vision-capable models.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageInput {
+    /// e.g. `image/png`
+    pub mime_type: String,
+    pub data_base64: String,
+}
+
+impl ImageInput {
+    pub fn new(mime_type: impl Into<String>, data_base64: impl Into<String>) -> Self {
+        Self { mime_type: mime_type.into(), data_base64: data_base64.into() }
+    }
+
+    /// The image as a `data:` URL, which is how OpenAI-style APIs take inline images.
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data_base64)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct QueryContext {
+    /// The request as one string, for providers and logs that take a single prompt.
     pub prompt: String,
-    pub messages: Option<Vec<Message>>,
+    /// The same request as a conversation, oldest turn first. Empty means one user turn of `prompt`.
+    pub messages: Vec<ChatMessage>,
+    /// Tools the model may call natively; empty for plain completions.
+    pub tools: Vec<ToolSpec>,
+    /// JSON schema the answer must follow. Providers with a JSON mode enforce it; the others only
+    /// see it if the prompt spells it out (see `AIAgent::query_json`).
+    pub response_schema: Option<serde_json::Value>,
+    /// Images for the model to look at, attached to the last user turn. Only cloud providers
+    /// send them; the local model sees the text alone.
+    pub images: Vec<ImageInput>,
     pub max_tokens: u32,
     pub temperature: f32,
     pub timeout: Duration,
     pub pure_mode: bool,
 }
 
-#[async_trait]
+impl QueryContext {
+    /// The conversation to send: `messages`, or `prompt` as the only user turn.
+    pub fn conversation(&self) -> Vec<ChatMessage> {
+        if self.messages.is_empty() {
+            vec![ChatMessage::user(self.prompt.clone())]
+        } else {
+            self.messages.clone()
+        }
+    }
+}
+
+/// Why a provider call failed, so callers can tell what's worth retrying. Providers return it
+/// inside `anyhow::Error`; get it back with `ProviderError::of`. Network failures stay untyped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderError {
+    /// 429 from the API, or the provider's own rate limit (see `providers::ratelimit`).
+    /// `retry_after` is the API's Retry-After when it sent one.
+    RateLimited { provider: String, retry_after: Option<Duration> },
+    /// 401/403 or no key configured: retrying won't help until the credentials change.
+    AuthFailed { provider: String, message: String },
+    /// Any other 4xx: the request itself is wrong (unknown model, bad parameters, too long).
+    InvalidRequest { provider: String, status: u16, message: String },
+    /// 5xx, an overloaded API or an unusable response.
+    ServerError { provider: String, status: u16, message: String },
+}
+
+impl ProviderError {
+    /// Classifies a failed HTTP response; `body` is kept (shortened) as the message.
+    pub fn from_status(provider: &str, status: u16, retry_after: Option<Duration>, body: &str) -> Self {
+        let provider = provider.to_string();
+        let message: String = body.trim().chars().take(500).collect();
+        match status {
+            429 => ProviderError::RateLimited { provider, retry_after },
+            401 | 403 => ProviderError::AuthFailed { provider, message },
+            400..=499 => ProviderError::InvalidRequest { provider, status, message },
+            _ => ProviderError::ServerError { provider, status, message },
+        }
+    }
+
+    pub fn of(error: &anyhow::Error) -> Option<&ProviderError> {
+        error.downcast_ref()
+    }
+
+    /// Rate limits and server errors may pass; auth and request errors won't.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ProviderError::RateLimited { .. } | ProviderError::ServerError { .. })
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::RateLimited { provider, retry_after: Some(after) } =>
+                write!(f, "{} is rate limited; retry in {}s", provider, after.as_secs().max(1)),
+            ProviderError::RateLimited { provider, retry_after: None } => write!(f, "{} is rate limited", provider),
+            ProviderError::AuthFailed { provider, message } => write!(f, "{} authentication failed: {}", provider, message),
+            ProviderError::InvalidRequest { provider, status, message }
+            | ProviderError::ServerError { provider, status, message } =>
+                write!(f, "{} API error {}: {}", provider, status, message),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+// Browser fetch futures aren't Send, so on wasm32 providers are single-threaded
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 pub trait ModelProvider: Send + Sync {
     async fn generate(&self, context: &QueryContext) -> Result<ModelResponse>;
     fn name(&self) -> &str;
     fn is_available(&self) -> bool;
     fn estimated_latency_ms(&self) -> u64;
     fn quality_score(&self) -> f32; // 0.0-1.0
+    /// Counters since the provider was created, if it keeps them.
+    fn metrics(&self) -> Option<ModelMetrics> {
+        None
+    }
 }
 
-#[derive(Clone, Debug)]
+/// Providers in the order to try them: best quality first and, between equals, the faster one
+/// (by observed latency). Unavailable providers are left out.
+pub fn rank_providers(providers: &[std::sync::Arc<dyn ModelProvider>]) -> Vec<&std::sync::Arc<dyn ModelProvider>> {
+    let mut ranked: Vec<_> = providers.iter().filter(|p| p.is_available()).collect();
+    ranked.sort_by(|a, b|
+        b.quality_score().partial_cmp(&a.quality_score()).unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.estimated_latency_ms().cmp(&b.estimated_latency_ms()))
+    );
+    ranked
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct ModelMetrics {
     pub avg_response_time_ms: u64,
     pub success_rate: f32,
     pub last_error: Option<String>,
     pub total_requests: u64,
     pub successful_requests: u64,
+    /// Response times of successful requests.
+    pub latency: LatencyHistogram,
+    /// Time until the first streamed token; empty for providers that don't stream.
+    pub time_to_first_token: LatencyHistogram,
 }
 
 impl Default for ModelMetrics {
@@ -61,12 +296,92 @@ impl Default for ModelMetrics {
             last_error: None,
             total_requests: 0,
             successful_requests: 0,
+            latency: LatencyHistogram::default(),
+            time_to_first_token: LatencyHistogram::default(),
         }
     }
 }
 
+// Upper bounds (ms) of the histogram buckets; the last one catches everything slower
+const LATENCY_BUCKETS_MS: &[u64] = &[
+    25, 50, 75, 100, 150, 200, 300, 400, 500, 750, 1_000, 1_500, 2_000, 3_000, 4_000, 5_000,
+    7_500, 10_000, 15_000, 20_000, 30_000, 45_000, 60_000, 90_000, 120_000, 180_000, 300_000, u64::MAX,
+];
+
+/// Fixed-bucket latency histogram. Percentiles are reported as the upper bound of the bucket
+/// they fall in (capped at the slowest sample), so they are accurate to within one bucket.
+#[derive(Clone, Debug)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    max_ms: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self { counts: vec![0; LATENCY_BUCKETS_MS.len()], max_ms: 0 }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, ms: u64) {
+        let bucket = LATENCY_BUCKETS_MS.iter().position(|&bound| ms <= bound).unwrap_or(LATENCY_BUCKETS_MS.len() - 1);
+        self.counts[bucket] += 1;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The `p`th percentile (0-100), or None before any samples.
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((p / 100.0) * total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(LATENCY_BUCKETS_MS[bucket].min(self.max_ms));
+            }
+        }
+        Some(self.max_ms)
+    }
+
+    pub fn p50(&self) -> Option<u64> {
+        self.percentile(50.0)
+    }
+
+    pub fn p95(&self) -> Option<u64> {
+        self.percentile(95.0)
+    }
+
+    pub fn p99(&self) -> Option<u64> {
+        self.percentile(99.0)
+    }
+}
+
+impl Serialize for LatencyHistogram {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let buckets: Vec<(u64, u64)> = LATENCY_BUCKETS_MS.iter().copied().zip(self.counts.iter().copied())
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        let mut state = serializer.serialize_struct("LatencyHistogram", 5)?;
+        state.serialize_field("count", &self.count())?;
+        state.serialize_field("p50_ms", &self.p50())?;
+        state.serialize_field("p95_ms", &self.p95())?;
+        state.serialize_field("p99_ms", &self.p99())?;
+        state.serialize_field("buckets", &buckets)?;
+        state.end()
+    }
+}
+
 impl ModelMetrics {
     pub fn record_success(&mut self, response_time_ms: u64) {
+        self.latency.record(response_time_ms);
         self.total_requests += 1;
         self.successful_requests += 1;
         self.avg_response_time_ms = 
@@ -75,9 +390,45 @@ impl ModelMetrics {
         self.success_rate = self.successful_requests as f32 / self.total_requests as f32;
     }
     
+    pub fn record_first_token(&mut self, ms: u64) {
+        self.time_to_first_token.record(ms);
+    }
+
     pub fn record_failure(&mut self, error: String) {
         self.total_requests += 1;
         self.last_error = Some(error);
         self.success_rate = self.successful_requests as f32 / self.total_requests as f32;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_error_from_status() {
+        let limited = ProviderError::from_status("OpenAI", 429, Some(Duration::from_secs(20)), "");
+        assert!(limited.is_retryable());
+        assert_eq!(limited.to_string(), "OpenAI is rate limited; retry in 20s");
+        assert!(!ProviderError::from_status("OpenAI", 401, None, "bad key").is_retryable());
+        assert!(matches!(ProviderError::from_status("OpenAI", 404, None, ""), ProviderError::InvalidRequest { status: 404, .. }));
+        assert!(ProviderError::from_status("Anthropic", 529, None, "overloaded").is_retryable());
+
+        let error: anyhow::Error = limited.clone().into();
+        assert_eq!(ProviderError::of(&error), Some(&limited));
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.p50(), None);
+
+        for ms in [80, 90, 120, 140, 180, 450, 900, 1_200, 2_500, 9_000] {
+            histogram.record(ms);
+        }
+        assert_eq!(histogram.count(), 10);
+        assert_eq!(histogram.p50(), Some(200));
+        assert_eq!(histogram.p95(), Some(9_000));
+        assert_eq!(histogram.percentile(10.0), Some(100));
+    }
+}
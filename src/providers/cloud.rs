@@ -1,12 +1,12 @@
-use crate::models::{ModelProvider, ModelResponse, QueryContext, ModelMetrics};
+use crate::models::{ChatMessage, ImageInput, ModelProvider, ModelResponse, QueryContext, ModelMetrics, ProviderError, Role, ToolCall, ToolSpec};
 use crate::config::CloudProviderConfig;
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde_json::{json, Value};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
 use tracing::{warn, error, debug, info};
 use serde::{Serialize, Deserialize};
 use std::path::PathBuf;
@@ -17,6 +17,249 @@ struct GeminiCache {
     models: Vec<String>,
 }
 
+/// Merges the provider's `extra` table into a request body. Objects merge recursively so that
+/// e.g. `generationConfig.thinkingConfig` can be added without dropping AIR's generationConfig.
+fn with_extra(mut payload: Value, config: &CloudProviderConfig) -> Value {
+    if !config.extra.is_empty() {
+        match serde_json::to_value(&config.extra) {
+            Ok(extra) => merge_json(&mut payload, extra),
+            Err(e) => warn!("Ignoring extra options for {}: {}", config.name, e),
+        }
+    }
+    payload
+}
+
+fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Adds the provider's configured headers, replacing any of the same name already set.
+fn with_headers(request: RequestBuilder, config: &CloudProviderConfig) -> RequestBuilder {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => warn!("Ignoring invalid header '{}' for {}", name, config.name),
+        }
+    }
+    request.headers(headers)
+}
+
+/// The typed error for a non-success response, honouring its Retry-After header.
+async fn error_response(provider: &str, resp: reqwest::Response) -> ProviderError {
+    let status = resp.status().as_u16();
+    let retry_after = resp.headers().get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, chrono::Utc::now()));
+    let body = resp.text().await.unwrap_or_default();
+    ProviderError::from_status(provider, status, retry_after, &body)
+}
+
+/// Retry-After is either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<std::time::Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds.is_finite() && seconds >= 0.0).then(|| std::time::Duration::from_secs_f64(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&chrono::Utc) - now).to_std().unwrap_or_default())
+}
+
+fn missing_key(provider: &str, message: &str) -> anyhow::Error {
+    ProviderError::AuthFailed { provider: provider.to_string(), message: message.to_string() }.into()
+}
+
+/// `messages` for OpenAI-style chat completions (OpenAI, OpenRouter, Azure). Images turn the
+/// last user turn's content into text and `image_url` parts.
+fn openai_messages(messages: &[ChatMessage], images: &[ImageInput]) -> Value {
+    let mut messages = Value::Array(messages.iter().map(|m| {
+        let role = match m.role {
+            Role::Tool => "user",
+            role => role.as_str(),
+        };
+        json!({ "role": role, "content": m.text() })
+    }).collect());
+    if let Some(turn) = last_user_turn(&mut messages).filter(|_| !images.is_empty()) {
+        let mut parts = vec![json!({ "type": "text", "text": turn["content"].take() })];
+        parts.extend(images.iter().map(|image| json!({ "type": "image_url", "image_url": { "url": image.data_url() } })));
+        turn["content"] = Value::Array(parts);
+    }
+    messages
+}
+
+/// Anthropic's top-level `system` prompt and `messages`, which must alternate user/assistant.
+/// Images go in front of the last user turn's text, as Anthropic recommends.
+fn anthropic_messages(messages: &[ChatMessage], images: &[ImageInput]) -> (Option<String>, Value) {
+    let (system, turns) = split_system(messages);
+    let turns = merge_turns(&turns, "assistant");
+    let mut messages = Value::Array(turns.into_iter().map(|(role, text)| json!({ "role": role, "content": text })).collect());
+    if let Some(turn) = last_user_turn(&mut messages).filter(|_| !images.is_empty()) {
+        let mut parts: Vec<Value> = images.iter().map(|image| json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": image.mime_type, "data": image.data_base64 }
+        })).collect();
+        parts.push(json!({ "type": "text", "text": turn["content"].take() }));
+        turn["content"] = Value::Array(parts);
+    }
+    (system, messages)
+}
+
+/// Gemini's `systemInstruction` and `contents`, where the assistant is called "model". Images
+/// are `inlineData` parts after the last user turn's text.
+fn gemini_contents(messages: &[ChatMessage], images: &[ImageInput]) -> (Option<Value>, Value) {
+    let (system, turns) = split_system(messages);
+    let mut contents = Value::Array(merge_turns(&turns, "model").into_iter()
+        .map(|(role, text)| json!({ "role": role, "parts": [{ "text": text }] }))
+        .collect());
+    if let Some(parts) = last_user_turn(&mut contents).and_then(|turn| turn["parts"].as_array_mut()) {
+        parts.extend(images.iter().map(|image| json!({
+            "inlineData": { "mimeType": image.mime_type, "data": image.data_base64 }
+        })));
+    }
+    (system.map(|text| json!({ "parts": [{ "text": text }] })), contents)
+}
+
+fn last_user_turn(messages: &mut Value) -> Option<&mut Value> {
+    messages.as_array_mut()?.iter_mut().rev().find(|m| m["role"] == "user")
+}
+
+/// The system turns joined into one instruction, and the other turns.
+fn split_system(messages: &[ChatMessage]) -> (Option<String>, Vec<&ChatMessage>) {
+    let system: Vec<&str> = messages.iter().filter(|m| m.role == Role::System).map(|m| m.content.as_str()).collect();
+    let turns = messages.iter().filter(|m| m.role != Role::System).collect();
+    ((!system.is_empty()).then(|| system.join("\n\n")), turns)
+}
+
+/// Turns as (role, text) with tool results counted as user turns, merging neighbours of the same
+/// role for APIs that reject two user (or assistant) turns in a row.
+fn merge_turns(turns: &[&ChatMessage], assistant: &'static str) -> Vec<(&'static str, String)> {
+    let mut merged: Vec<(&'static str, String)> = Vec::new();
+    for turn in turns {
+        let role = if turn.role == Role::Assistant { assistant } else { "user" };
+        match merged.last_mut() {
+            Some((last, text)) if *last == role => {
+                text.push_str("\n\n");
+                text.push_str(&turn.text());
+            }
+            _ => merged.push((role, turn.text())),
+        }
+    }
+    merged
+}
+
+/// `tools` for OpenAI-style chat completions.
+fn openai_tools(tools: &[ToolSpec]) -> Value {
+    Value::Array(tools.iter().map(|tool| json!({
+        "type": "function",
+        "function": { "name": tool.name, "description": tool.description, "parameters": tool.parameters() }
+    })).collect())
+}
+
+/// The tool calls of an OpenAI-style response message, whose arguments arrive as a JSON string.
+fn openai_tool_calls(message: &Value) -> Vec<ToolCall> {
+    message["tool_calls"].as_array().into_iter().flatten()
+        .filter_map(|call| {
+            let function = &call["function"];
+            let input: Value = serde_json::from_str(function["arguments"].as_str().unwrap_or("{}")).ok()?;
+            Some(ToolSpec::call(function["name"].as_str()?, &input))
+        })
+        .collect()
+}
+
+fn anthropic_tools(tools: &[ToolSpec]) -> Value {
+    Value::Array(tools.iter().map(|tool| json!({
+        "name": tool.name, "description": tool.description, "input_schema": tool.parameters()
+    })).collect())
+}
+
+/// The text and tool calls of an Anthropic response's content blocks.
+fn anthropic_content(content: &Value) -> (String, Vec<ToolCall>) {
+    let mut text = Vec::new();
+    let mut calls = Vec::new();
+    for block in content.as_array().into_iter().flatten() {
+        match (block["type"].as_str(), block["name"].as_str()) {
+            (Some("text"), _) => text.push(block["text"].as_str().unwrap_or_default()),
+            (Some("tool_use"), Some(name)) => calls.push(ToolSpec::call(name, &block["input"])),
+            _ => {}
+        }
+    }
+    (text.join("\n"), calls)
+}
+
+/// OpenAI's `response_format` holding the answer to `schema`.
+fn openai_response_format(schema: &Value) -> Value {
+    json!({ "type": "json_schema", "json_schema": { "name": "response", "schema": schema } })
+}
+
+/// `schema` as Gemini's `responseSchema`, an OpenAPI subset that rejects some JSON Schema keywords.
+fn gemini_schema(schema: &Value) -> Value {
+    let Value::Object(map) = schema else { return schema.clone() };
+    let mut converted = serde_json::Map::new();
+    for (key, value) in map {
+        let value = match (key.as_str(), value) {
+            ("$schema" | "$id" | "title" | "default" | "examples" | "additionalProperties", _) => continue,
+            ("properties", Value::Object(properties)) => Value::Object(
+                properties.iter().map(|(name, property)| (name.clone(), gemini_schema(property))).collect()
+            ),
+            ("items", items) => gemini_schema(items),
+            ("anyOf", Value::Array(options)) => Value::Array(options.iter().map(gemini_schema).collect()),
+            _ => value.clone(),
+        };
+        converted.insert(key.clone(), value);
+    }
+    Value::Object(converted)
+}
+
+/// Gemini's `tools`. Its schemas reject objects without properties, so `args` is a JSON string.
+fn gemini_tools(tools: &[ToolSpec]) -> Value {
+    let declarations: Vec<Value> = tools.iter().map(|tool| {
+        let mut parameters = tool.parameters();
+        parameters["properties"]["args"] = json!({ "type": "string", "description": "Arguments of the function as a JSON object" });
+        json!({ "name": tool.name, "description": tool.description, "parameters": parameters })
+    }).collect();
+    json!([{ "functionDeclarations": declarations }])
+}
+
+/// The text and function calls of a Gemini candidate's parts.
+fn gemini_parts(parts: &[Value]) -> (String, Vec<ToolCall>) {
+    let text: Vec<&str> = parts.iter().filter_map(|part| part["text"].as_str()).collect();
+    let calls = parts.iter()
+        .filter_map(|part| {
+            let call = &part["functionCall"];
+            Some(ToolSpec::call(call["name"].as_str()?, &call["args"]))
+        })
+        .collect();
+    (text.join(""), calls)
+}
+
+fn http_client(config: &CloudProviderConfig) -> Result<Client> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let client = Client::builder()
+        .timeout(std::time::Duration::from_secs(config.timeout_seconds))
+        .build()?;
+    // The browser's fetch owns timeouts there; the builder has no timeout on wasm32
+    #[cfg(target_arch = "wasm32")]
+    let client = {
+        let _ = config;
+        Client::builder().build()?
+    };
+    Ok(client)
+}
+
 pub struct OpenAIProvider {
     config: CloudProviderConfig,
     client: Client,
@@ -29,9 +272,7 @@ impl OpenAIProvider {
             warn!("OpenAI API key not provided, provider will be unavailable");
         }
         
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(config.timeout_seconds))
-            .build()?;
+        let client = http_client(&config)?;
             
         Ok(Self {
             config,
@@ -41,33 +282,36 @@ impl OpenAIProvider {
     }
 }
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl ModelProvider for OpenAIProvider {
     async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
         let api_key = self.config.api_key.as_ref()
-            .ok_or_else(|| anyhow!("OpenAI API key not configured"))?;
+            .ok_or_else(|| missing_key("OpenAI", "API key not configured"))?;
             
         let start = Instant::now();
-        let mut metrics = self.metrics.lock().await;
         
         debug!("Sending request to OpenAI API");
         
-        let payload = json!({
+        let mut payload = json!({
             "model": self.config.model,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": context.prompt
-                }
-            ],
+            "messages": openai_messages(&context.conversation(), &context.images),
             "max_tokens": context.max_tokens,
             "temperature": context.temperature
         });
+        if self.config.native_tools && !context.tools.is_empty() {
+            payload["tools"] = openai_tools(&context.tools);
+        }
+        if let Some(schema) = context.response_schema.as_ref().filter(|_| self.config.structured_output) {
+            payload["response_format"] = openai_response_format(schema);
+        }
+        let payload = with_extra(payload, &self.config);
         
-        let response = self.client
+        let request = self.client
             .post(&format!("{}/chat/completions", self.config.base_url))
             .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
+            .header("Content-Type", "application/json");
+        let response = with_headers(request, &self.config)
             .json(&payload)
             .send()
             .await;
@@ -76,36 +320,43 @@ impl ModelProvider for OpenAIProvider {
             Ok(resp) => {
                 if resp.status().is_success() {
                     let response_json: Value = resp.json().await?;
-                    let content = response_json["choices"][0]["message"]["content"]
+                    let message = &response_json["choices"][0]["message"];
+                    let tool_calls = openai_tool_calls(message);
+                    let content = message["content"]
                         .as_str()
-                        .unwrap_or("No response content")
+                        .unwrap_or(if tool_calls.is_empty() { "No response content" } else { "" })
                         .to_string();
                     
                     let tokens_used = response_json["usage"]["total_tokens"]
                         .as_u64()
                         .unwrap_or(0) as u32;
+                    let input_tokens = response_json["usage"]["prompt_tokens"]
+                        .as_u64()
+                        .unwrap_or(0) as u32;
                     
                     let response_time = start.elapsed().as_millis() as u64;
-                    metrics.record_success(response_time);
+                    self.metrics.lock().unwrap().record_success(response_time);
                     
                     Ok(ModelResponse {
                         content,
                         model_used: format!("OpenAI-{}", self.config.model),
                         tokens_used,
+                        input_tokens,
                         response_time_ms: response_time,
                         confidence_score: Some(0.95), // OpenAI models typically high quality
+                        tool_calls,
                     })
                 } else {
-                    let error_msg = format!("OpenAI API error: {}", resp.status());
-                    error!("{}", error_msg);
-                    metrics.record_failure(error_msg.clone());
-                    Err(anyhow!(error_msg))
+                    let error = error_response("OpenAI", resp).await;
+                    error!("{}", error);
+                    self.metrics.lock().unwrap().record_failure(error.to_string());
+                    Err(error.into())
                 }
             }
             Err(e) => {
                 let error_msg = format!("OpenAI request failed: {}", e);
                 error!("{}", error_msg);
-                metrics.record_failure(error_msg.clone());
+                self.metrics.lock().unwrap().record_failure(error_msg.clone());
                 Err(anyhow!(error_msg))
             }
         }
@@ -120,11 +371,11 @@ impl ModelProvider for OpenAIProvider {
     }
     
     fn estimated_latency_ms(&self) -> u64 {
-        1500 // Typical cloud API latency
+        self.config.latency_ms.unwrap_or(1500) // Typical cloud API latency
     }
     
     fn quality_score(&self) -> f32 {
-        0.95 // High quality responses
+        self.config.quality_score.unwrap_or(0.95) // High quality responses
     }
 }
 
@@ -140,9 +391,7 @@ impl AnthropicProvider {
             warn!("Anthropic API key not provided, provider will be unavailable");
         }
         
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(config.timeout_seconds))
-            .build()?;
+        let client = http_client(&config)?;
             
         Ok(Self {
             config,
@@ -152,34 +401,38 @@ impl AnthropicProvider {
     }
 }
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl ModelProvider for AnthropicProvider {
     async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
         let api_key = self.config.api_key.as_ref()
-            .ok_or_else(|| anyhow!("Anthropic API key not configured"))?;
+            .ok_or_else(|| missing_key("Anthropic", "API key not configured"))?;
             
         let start = Instant::now();
-        let mut metrics = self.metrics.lock().await;
         
         debug!("Sending request to Anthropic API");
         
-        let payload = json!({
+        let (system, messages) = anthropic_messages(&context.conversation(), &context.images);
+        let mut payload = json!({
             "model": self.config.model,
             "max_tokens": context.max_tokens,
             "temperature": context.temperature,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": context.prompt
-                }
-            ]
+            "messages": messages
         });
+        if let Some(system) = system {
+            payload["system"] = json!(system);
+        }
+        if self.config.native_tools && !context.tools.is_empty() {
+            payload["tools"] = anthropic_tools(&context.tools);
+        }
+        let payload = with_extra(payload, &self.config);
         
-        let response = self.client
+        let request = self.client
             .post(&format!("{}/v1/messages", self.config.base_url))
             .header("x-api-key", api_key)
             .header("Content-Type", "application/json")
-            .header("anthropic-version", "2023-06-01")
+            .header("anthropic-version", "2023-06-01");
+        let response = with_headers(request, &self.config)
             .json(&payload)
             .send()
             .await;
@@ -188,36 +441,41 @@ impl ModelProvider for AnthropicProvider {
             Ok(resp) => {
                 if resp.status().is_success() {
                     let response_json: Value = resp.json().await?;
-                    let content = response_json["content"][0]["text"]
-                        .as_str()
-                        .unwrap_or("No response content")
-                        .to_string();
+                    let (mut content, tool_calls) = anthropic_content(&response_json["content"]);
+                    if content.is_empty() && tool_calls.is_empty() {
+                        content = "No response content".to_string();
+                    }
                     
-                    let tokens_used = response_json["usage"]["output_tokens"]
+                    let input_tokens = response_json["usage"]["input_tokens"]
+                        .as_u64()
+                        .unwrap_or(0) as u32;
+                    let tokens_used = input_tokens + response_json["usage"]["output_tokens"]
                         .as_u64()
                         .unwrap_or(0) as u32;
                     
                     let response_time = start.elapsed().as_millis() as u64;
-                    metrics.record_success(response_time);
+                    self.metrics.lock().unwrap().record_success(response_time);
                     
                     Ok(ModelResponse {
                         content,
                         model_used: format!("Anthropic-{}", self.config.model),
                         tokens_used,
+                        input_tokens,
                         response_time_ms: response_time,
                         confidence_score: Some(0.93),
+                        tool_calls,
                     })
                 } else {
-                    let error_msg = format!("Anthropic API error: {}", resp.status());
-                    error!("{}", error_msg);
-                    metrics.record_failure(error_msg.clone());
-                    Err(anyhow!(error_msg))
+                    let error = error_response("Anthropic", resp).await;
+                    error!("{}", error);
+                    self.metrics.lock().unwrap().record_failure(error.to_string());
+                    Err(error.into())
                 }
             }
             Err(e) => {
                 let error_msg = format!("Anthropic request failed: {}", e);
                 error!("{}", error_msg);
-                metrics.record_failure(error_msg.clone());
+                self.metrics.lock().unwrap().record_failure(error_msg.clone());
                 Err(anyhow!(error_msg))
             }
         }
@@ -232,11 +490,11 @@ impl ModelProvider for AnthropicProvider {
     }
     
     fn estimated_latency_ms(&self) -> u64 {
-        1200 // Typically fast
+        self.config.latency_ms.unwrap_or(1200) // Typically fast
     }
     
     fn quality_score(&self) -> f32 {
-        0.93 // High quality responses
+        self.config.quality_score.unwrap_or(0.93) // High quality responses
     }
 }
 
@@ -244,7 +502,7 @@ pub struct GeminiProvider {
     config: CloudProviderConfig,
     client: Client,
     metrics: Arc<Mutex<ModelMetrics>>,
-    cached_models: Arc<Mutex<Option<Vec<String>>>>,
+    cached_models: Arc<tokio::sync::Mutex<Option<Vec<String>>>>,
 }
 
 impl GeminiProvider {
@@ -253,15 +511,13 @@ impl GeminiProvider {
             warn!("Gemini API key not provided, provider will be unavailable");
         }
         
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(config.timeout_seconds))
-            .build()?;
+        let client = http_client(&config)?;
             
         Ok(Self {
             config,
             client,
             metrics: Arc::new(Mutex::new(ModelMetrics::default())),
-            cached_models: Arc::new(Mutex::new(None)),
+            cached_models: Arc::new(tokio::sync::Mutex::new(None)),
         })
     }
 
@@ -405,49 +661,63 @@ impl GeminiProvider {
     }
 }
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl ModelProvider for GeminiProvider {
     async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
         let api_key = self.config.api_key.as_ref()
-            .ok_or_else(|| anyhow!("Gemini API key not configured"))?;
+            .ok_or_else(|| missing_key("Gemini", "API key not configured"))?;
             
         let start = Instant::now();
-        let mut metrics = self.metrics.lock().await;
         
         // Fetch dynamic model list
-        let available_models = match self.fetch_and_sort_models(api_key).await {
-             Ok(models) => models,
-             Err(e) => {
-                 warn!("Failed to fetch dynamic model list: {}. Falling back to configured default.", e);
-                 vec![self.config.model.clone()]
-             }
+        let available_models = if !self.config.auto_select_model {
+            vec![self.config.model.clone()]
+        } else {
+            match self.fetch_and_sort_models(api_key).await {
+                 Ok(models) => models,
+                 Err(e) => {
+                     warn!("Failed to fetch dynamic model list: {}. Falling back to configured default.", e);
+                     vec![self.config.model.clone()]
+                 }
+            }
         };
         
         let mut last_error = anyhow!("No models available");
+        let (system_instruction, contents) = gemini_contents(&context.conversation(), &context.images);
         
         // Iterate through models until success
         for model_name in available_models {
             debug!("Attempting generation with Gemini model: {}", model_name);
             
-            let payload = json!({
-                "contents": [{
-                    "parts": [{
-                        "text": context.prompt
-                    }]
-                }],
+            let mut payload = json!({
+                "contents": contents,
                 "generationConfig": {
                     "temperature": context.temperature,
                     "maxOutputTokens": context.max_tokens,
                     "candidateCount": 1
                 }
             });
+            if let Some(system_instruction) = &system_instruction {
+                payload["systemInstruction"] = system_instruction.clone();
+            }
+            if self.config.native_tools && !context.tools.is_empty() {
+                payload["tools"] = gemini_tools(&context.tools);
+            }
+            // Gemini can't combine JSON mode with function calling, so tools take precedence
+            if let Some(schema) = context.response_schema.as_ref().filter(|_| self.config.structured_output && payload.get("tools").is_none()) {
+                payload["generationConfig"]["responseMimeType"] = json!("application/json");
+                payload["generationConfig"]["responseSchema"] = gemini_schema(schema);
+            }
+            let payload = with_extra(payload, &self.config);
 
             let url = format!("{}/v1beta/models/{}:generateContent?key={}",
                              self.config.base_url, model_name, api_key);
 
-            let response = self.client
+            let request = self.client
                 .post(&url)
-                .header("Content-Type", "application/json")
+                .header("Content-Type", "application/json");
+            let response = with_headers(request, &self.config)
                 .json(&payload)
                 .send()
                 .await;
@@ -461,19 +731,25 @@ impl ModelProvider for GeminiProvider {
                         if let Some(candidates) = response_json["candidates"].as_array() {
                             if let Some(first) = candidates.first() {
                                 if let Some(parts) = first["content"]["parts"].as_array() {
-                                    if let Some(text) = parts[0]["text"].as_str() {
-                                        let content = text.to_string();
-                                        let tokens_used = (content.len() / 4) as u32;
+                                    let (content, tool_calls) = gemini_parts(parts);
+                                    if !content.is_empty() || !tool_calls.is_empty() {
+                                        let usage = &response_json["usageMetadata"];
+                                        let input_tokens = usage["promptTokenCount"].as_u64().unwrap_or(0) as u32;
+                                        let tokens_used = usage["totalTokenCount"]
+                                            .as_u64()
+                                            .map_or((content.len() / 4) as u32 + input_tokens, |total| total as u32);
 
                                         let response_time = start.elapsed().as_millis() as u64;
-                                        metrics.record_success(response_time);
+                                        self.metrics.lock().unwrap().record_success(response_time);
 
                                         return Ok(ModelResponse {
                                             content,
                                             model_used: format!("Gemini-{}", model_name),
                                             tokens_used,
+                                            input_tokens,
                                             response_time_ms: response_time,
                                             confidence_score: Some(0.92),
+                                            tool_calls,
                                         });
                                     }
                                 }
@@ -483,10 +759,14 @@ impl ModelProvider for GeminiProvider {
                         warn!("Gemini model {} returned success but unexpected structure (likely safety block). Trying next model.", model_name);
                         last_error = anyhow!("Response parsing failed for {}", model_name);
                     } else {
-                        let status = resp.status();
-                        // If 4xx/5xx error, warn and try next
-                        warn!("Gemini model {} failed with status {}. Trying next model...", model_name, status);
-                        last_error = anyhow!("API error {}: {}", status, resp.text().await.unwrap_or_default());
+                        // If 4xx/5xx error, warn and try next, unless the key itself was refused
+                        let error = error_response("Gemini", resp).await;
+                        warn!("Gemini model {} failed: {}. Trying next model...", model_name, error);
+                        let auth_failed = matches!(error, ProviderError::AuthFailed { .. });
+                        last_error = error.into();
+                        if auth_failed {
+                            break;
+                        }
                     }
                 }
                 Err(e) => {
@@ -496,10 +776,13 @@ impl ModelProvider for GeminiProvider {
             }
         }
 
-        // If we get here, all models failed
+        // If we get here, all models failed; a typed last error is passed on so it can be retried (or not)
         let error_msg = format!("All Gemini models failed. Last error: {}", last_error);
         error!("{}", error_msg);
-        metrics.record_failure(error_msg.clone());
+        self.metrics.lock().unwrap().record_failure(error_msg.clone());
+        if ProviderError::of(&last_error).is_some() {
+            return Err(last_error);
+        }
         Err(anyhow!(error_msg))
     }
     
@@ -512,11 +795,11 @@ impl ModelProvider for GeminiProvider {
     }
     
     fn estimated_latency_ms(&self) -> u64 {
-        1000 // Gemini is typically fast
+        self.config.latency_ms.unwrap_or(1000) // Gemini is typically fast
     }
     
     fn quality_score(&self) -> f32 {
-        0.92 // High quality responses, slightly lower than GPT-4 but very competitive
+        self.config.quality_score.unwrap_or(0.92) // High quality responses, slightly lower than GPT-4 but very competitive
     }
 }
 
@@ -532,9 +815,7 @@ impl OpenRouterProvider {
             warn!("OpenRouter API key not provided, provider will be unavailable");
         }
         
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(config.timeout_seconds))
-            .build()?;
+        let client = http_client(&config)?;
             
         Ok(Self {
             config,
@@ -544,36 +825,39 @@ impl OpenRouterProvider {
     }
 }
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl ModelProvider for OpenRouterProvider {
     async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
         let api_key = self.config.api_key.as_ref()
-            .ok_or_else(|| anyhow!("OpenRouter API key not configured"))?;
+            .ok_or_else(|| missing_key("OpenRouter", "API key not configured"))?;
             
         let start = Instant::now();
-        let mut metrics = self.metrics.lock().await;
         
         debug!("Sending request to OpenRouter API");
         
-        let payload = json!({
+        let mut payload = json!({
             "model": self.config.model,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": context.prompt
-                }
-            ],
+            "messages": openai_messages(&context.conversation(), &context.images),
             "max_tokens": context.max_tokens,
             "temperature": context.temperature,
             "stream": false
         });
+        if self.config.native_tools && !context.tools.is_empty() {
+            payload["tools"] = openai_tools(&context.tools);
+        }
+        if let Some(schema) = context.response_schema.as_ref().filter(|_| self.config.structured_output) {
+            payload["response_format"] = openai_response_format(schema);
+        }
+        let payload = with_extra(payload, &self.config);
         
-        let response = self.client
+        let request = self.client
             .post(&format!("{}/chat/completions", self.config.base_url))
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
             .header("HTTP-Referer", "https://github.com/your-repo/air") // Required by OpenRouter
-            .header("X-Title", "AIR - Rust AI Agent") // Optional but recommended
+            .header("X-Title", "AIR - Rust AI Agent"); // Optional but recommended
+        let response = with_headers(request, &self.config)
             .json(&payload)
             .send()
             .await;
@@ -583,38 +867,43 @@ impl ModelProvider for OpenRouterProvider {
                 if resp.status().is_success() {
                     let response_json: Value = resp.json().await?;
                     
-                    let content = response_json["choices"][0]["message"]["content"]
+                    let message = &response_json["choices"][0]["message"];
+                    let tool_calls = openai_tool_calls(message);
+                    let content = message["content"]
                         .as_str()
-                        .unwrap_or("No response content")
+                        .unwrap_or(if tool_calls.is_empty() { "No response content" } else { "" })
                         .to_string();
                     
                     let tokens_used = response_json["usage"]["total_tokens"]
                         .as_u64()
                         .unwrap_or(0) as u32;
+                    let input_tokens = response_json["usage"]["prompt_tokens"]
+                        .as_u64()
+                        .unwrap_or(0) as u32;
                     
                     let response_time = start.elapsed().as_millis() as u64;
-                    metrics.record_success(response_time);
+                    self.metrics.lock().unwrap().record_success(response_time);
                     
                     Ok(ModelResponse {
                         content,
                         model_used: format!("OpenRouter-{}", self.config.model),
                         tokens_used,
+                        input_tokens,
                         response_time_ms: response_time,
                         confidence_score: Some(0.90), // Good quality, varies by model
+                        tool_calls,
                     })
                 } else {
-                    let status_code = resp.status();
-                    let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                    let error_msg = format!("OpenRouter API error: {} - {}", status_code, error_text);
-                    error!("{}", error_msg);
-                    metrics.record_failure(error_msg.clone());
-                    Err(anyhow!(error_msg))
+                    let error = error_response("OpenRouter", resp).await;
+                    error!("{}", error);
+                    self.metrics.lock().unwrap().record_failure(error.to_string());
+                    Err(error.into())
                 }
             }
             Err(e) => {
                 let error_msg = format!("OpenRouter request failed: {}", e);
                 error!("{}", error_msg);
-                metrics.record_failure(error_msg.clone());
+                self.metrics.lock().unwrap().record_failure(error_msg.clone());
                 Err(anyhow!(error_msg))
             }
         }
@@ -629,10 +918,437 @@ impl ModelProvider for OpenRouterProvider {
     }
     
     fn estimated_latency_ms(&self) -> u64 {
-        1200 // Varies by model, but generally fast
+        self.config.latency_ms.unwrap_or(1200) // Varies by model, but generally fast
     }
     
     fn quality_score(&self) -> f32 {
-        0.90 // Quality depends on the specific model chosen
+        self.config.quality_score.unwrap_or(0.90) // Quality depends on the specific model chosen
+    }
+}
+
+/// `api-version` sent when the config doesn't name one.
+const AZURE_API_VERSION: &str = "2024-10-21";
+/// Azure AD tokens from the Azure CLI last at least an hour; they are fetched again before that.
+const AZURE_AD_TOKEN_TTL_SECS: u64 = 50 * 60;
+
+/// Azure OpenAI: `base_url` is the resource endpoint (`https://<resource>.openai.azure.com`) and
+/// `model` the deployment name. Authenticates with `api_key`, or with an Azure AD token when
+/// `azure_ad` is set.
+pub struct AzureOpenAIProvider {
+    config: CloudProviderConfig,
+    client: Client,
+    metrics: Arc<Mutex<ModelMetrics>>,
+    ad_token: Mutex<Option<(String, Instant)>>,
+}
+
+impl AzureOpenAIProvider {
+    pub fn new(config: CloudProviderConfig) -> Result<Self> {
+        if config.api_key.is_none() && !config.azure_ad {
+            warn!("Azure OpenAI API key not provided and azure_ad is off, provider will be unavailable");
+        }
+        
+        let client = http_client(&config)?;
+            
+        Ok(Self {
+            config,
+            client,
+            metrics: Arc::new(Mutex::new(ModelMetrics::default())),
+            ad_token: Mutex::new(None),
+        })
+    }
+
+    fn url(&self) -> String {
+        format!("{}/openai/deployments/{}/chat/completions", self.config.base_url.trim_end_matches('/'), self.config.model)
+    }
+
+    /// Adds the `api-key` header, or a bearer token with `azure_ad`.
+    fn authorize(&self, request: RequestBuilder) -> Result<RequestBuilder> {
+        if !self.config.azure_ad {
+            let api_key = self.config.api_key.as_ref()
+                .ok_or_else(|| missing_key("Azure", "API key not configured (or set azure_ad = true)"))?;
+            return Ok(request.header("api-key", api_key));
+        }
+        Ok(request.bearer_auth(self.ad_token()?))
+    }
+
+    /// AZURE_OPENAI_AD_TOKEN if set, else a token from the Azure CLI (`az login`), reused until
+    /// it is about to expire.
+    fn ad_token(&self) -> Result<String> {
+        if let Ok(token) = std::env::var("AZURE_OPENAI_AD_TOKEN") {
+            return Ok(token);
+        }
+        let mut cached = self.ad_token.lock().unwrap();
+        if let Some((token, fetched)) = cached.as_ref() {
+            if fetched.elapsed().as_secs() < AZURE_AD_TOKEN_TTL_SECS {
+                return Ok(token.clone());
+            }
+        }
+        let token = azure_cli_token()?;
+        *cached = Some((token.clone(), Instant::now()));
+        Ok(token)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn azure_cli_token() -> Result<String> {
+    // Runs about once an hour, so the blocking call isn't worth a runtime hop
+    let az = if cfg!(windows) { "az.cmd" } else { "az" };
+    let output = std::process::Command::new(az)
+        .args(["account", "get-access-token", "--resource", "https://cognitiveservices.azure.com", "--query", "accessToken", "-o", "tsv"])
+        .output()
+        .map_err(|e| anyhow!("Azure AD auth needs the Azure CLI (az) or AZURE_OPENAI_AD_TOKEN: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!("az account get-access-token failed (run `az login`): {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn azure_cli_token() -> Result<String> {
+    Err(anyhow!("Set AZURE_OPENAI_AD_TOKEN for Azure AD auth; the Azure CLI isn't available here"))
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl ModelProvider for AzureOpenAIProvider {
+    async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
+        let start = Instant::now();
+        
+        debug!("Sending request to Azure OpenAI deployment {}", self.config.model);
+        
+        let mut payload = json!({
+            "messages": openai_messages(&context.conversation(), &context.images),
+            "max_tokens": context.max_tokens,
+            "temperature": context.temperature
+        });
+        if self.config.native_tools && !context.tools.is_empty() {
+            payload["tools"] = openai_tools(&context.tools);
+        }
+        if let Some(schema) = context.response_schema.as_ref().filter(|_| self.config.structured_output) {
+            payload["response_format"] = openai_response_format(schema);
+        }
+        let payload = with_extra(payload, &self.config);
+        
+        let api_version = self.config.api_version.as_deref().unwrap_or(AZURE_API_VERSION);
+        let request = self.client
+            .post(self.url())
+            .query(&[("api-version", api_version)])
+            .header("Content-Type", "application/json");
+        let request = match self.authorize(request) {
+            Ok(request) => request,
+            Err(e) => {
+                self.metrics.lock().unwrap().record_failure(e.to_string());
+                return Err(e);
+            }
+        };
+        let response = with_headers(request, &self.config)
+            .json(&payload)
+            .send()
+            .await;
+            
+        match response {
+            Ok(resp) => {
+                if resp.status().is_success() {
+                    let response_json: Value = resp.json().await?;
+                    let message = &response_json["choices"][0]["message"];
+                    let tool_calls = openai_tool_calls(message);
+                    let content = message["content"]
+                        .as_str()
+                        .unwrap_or(if tool_calls.is_empty() { "No response content" } else { "" })
+                        .to_string();
+                    
+                    let tokens_used = response_json["usage"]["total_tokens"]
+                        .as_u64()
+                        .unwrap_or(0) as u32;
+                    let input_tokens = response_json["usage"]["prompt_tokens"]
+                        .as_u64()
+                        .unwrap_or(0) as u32;
+                    
+                    let response_time = start.elapsed().as_millis() as u64;
+                    self.metrics.lock().unwrap().record_success(response_time);
+                    
+                    Ok(ModelResponse {
+                        content,
+                        model_used: format!("Azure-{}", self.config.model),
+                        tokens_used,
+                        input_tokens,
+                        response_time_ms: response_time,
+                        confidence_score: Some(0.95),
+                        tool_calls,
+                    })
+                } else {
+                    let error = error_response("Azure", resp).await;
+                    error!("{}", error);
+                    self.metrics.lock().unwrap().record_failure(error.to_string());
+                    Err(error.into())
+                }
+            }
+            Err(e) => {
+                let error_msg = format!("Azure OpenAI request failed: {}", e);
+                error!("{}", error_msg);
+                self.metrics.lock().unwrap().record_failure(error_msg.clone());
+                Err(anyhow!(error_msg))
+            }
+        }
+    }
+    
+    fn name(&self) -> &str {
+        "Azure"
+    }
+    
+    fn is_available(&self) -> bool {
+        self.config.api_key.is_some() || self.config.azure_ad
+    }
+    
+    fn estimated_latency_ms(&self) -> u64 {
+        self.config.latency_ms.unwrap_or(1500)
+    }
+    
+    fn quality_score(&self) -> f32 {
+        self.config.quality_score.unwrap_or(0.95)
+    }
+}
+
+/// Builds the provider named by `cloud_config`, or None when it is disabled, unknown or has no API key.
+pub fn create_provider(cloud_config: &CloudProviderConfig) -> Option<Arc<dyn ModelProvider>> {
+    if !cloud_config.enabled {
+        info!("🚫 Cloud provider disabled by config: {}", cloud_config.name);
+        return None;
+    }
+    match cloud_config.name.as_str() {
+        "openai" => {
+            match OpenAIProvider::new(cloud_config.clone()) {
+                Ok(provider) => {
+                    if provider.is_available() {
+                        info!("✅ OpenAI provider initialized");
+                        return Some(Arc::new(provider));
+                    } else {
+                        warn!("⚠️  OpenAI provider created but not available (missing API key)");
+                    }
+                }
+                Err(e) => warn!("❌ Failed to initialize OpenAI provider: {}", e),
+            }
+        }
+        "anthropic" => {
+            match AnthropicProvider::new(cloud_config.clone()) {
+                Ok(provider) => {
+                    if provider.is_available() {
+                        info!("✅ Anthropic provider initialized");
+                        return Some(Arc::new(provider));
+                    } else {
+                        warn!("⚠️  Anthropic provider created but not available (missing API key)");
+                    }
+                }
+                Err(e) => warn!("❌ Failed to initialize Anthropic provider: {}", e),
+            }
+        }
+        "gemini" => {
+            match GeminiProvider::new(cloud_config.clone()) {
+                Ok(provider) => {
+                    if provider.is_available() {
+                        info!("✅ Gemini provider initialized");
+                        return Some(Arc::new(provider));
+                    } else {
+                        warn!("⚠️  Gemini provider created but not available (missing API key)");
+                    }
+                }
+                Err(e) => warn!("❌ Failed to initialize Gemini provider: {}", e),
+            }
+        }
+        "openrouter" => {
+            match OpenRouterProvider::new(cloud_config.clone()) {
+                Ok(provider) => {
+                    if provider.is_available() {
+                        info!("✅ OpenRouter provider initialized");
+                        return Some(Arc::new(provider));
+                    } else {
+                        warn!("⚠️  OpenRouter provider created but not available (missing API key)");
+                    }
+                }
+                Err(e) => warn!("❌ Failed to initialize OpenRouter provider: {}", e),
+            }
+        }
+        "azure" => {
+            match AzureOpenAIProvider::new(cloud_config.clone()) {
+                Ok(provider) => {
+                    if provider.is_available() {
+                        info!("✅ Azure OpenAI provider initialized");
+                        return Some(Arc::new(provider));
+                    } else {
+                        warn!("⚠️  Azure OpenAI provider created but not available (set an API key or azure_ad = true)");
+                    }
+                }
+                Err(e) => warn!("❌ Failed to initialize Azure OpenAI provider: {}", e),
+            }
+        }
+        _ => warn!("Unknown cloud provider: {}", cloud_config.name),
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_options_merge_into_payload() {
+        let mut config: CloudProviderConfig = toml::from_str(r#"
+            name = "gemini"
+            base_url = "https://generativelanguage.googleapis.com"
+            model = "gemini-pro"
+            max_tokens = 1000
+            temperature = 0.7
+            timeout_seconds = 30
+
+            [extra.generationConfig.thinkingConfig]
+            thinkingBudget = 0
+
+            [[extra.safetySettings]]
+            category = "HARM_CATEGORY_HARASSMENT"
+            threshold = "BLOCK_NONE"
+        "#).unwrap();
+
+        let payload = with_extra(json!({ "generationConfig": { "temperature": 0.7 } }), &config);
+        assert_eq!(payload["generationConfig"]["temperature"], 0.7);
+        assert_eq!(payload["generationConfig"]["thinkingConfig"]["thinkingBudget"], 0);
+        assert_eq!(payload["safetySettings"][0]["threshold"], "BLOCK_NONE");
+
+        config.extra.clear();
+        assert_eq!(with_extra(json!({ "a": 1 }), &config), json!({ "a": 1 }));
+    }
+
+    #[test]
+    fn test_chat_formats() {
+        let messages = vec![
+            ChatMessage::system("You are AIR."),
+            ChatMessage::user("List the files"),
+            ChatMessage::assistant(r#"{"tool": "filesystem", "function": "list_directory", "args": {}}"#),
+            ChatMessage::tool("filesystem", "Cargo.toml\nsrc"),
+            ChatMessage::user("Which is the manifest?"),
+        ];
+
+        let openai = openai_messages(&messages, &[]);
+        assert_eq!(openai.as_array().unwrap().len(), 5);
+        assert_eq!(openai[0]["role"], "system");
+        assert_eq!(openai[3]["role"], "user");
+        assert_eq!(openai[3]["content"], "Result of the filesystem tool:\nCargo.toml\nsrc");
+
+        let (system, anthropic) = anthropic_messages(&messages, &[]);
+        assert_eq!(system.as_deref(), Some("You are AIR."));
+        let roles: Vec<&str> = anthropic.as_array().unwrap().iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert!(anthropic[2]["content"].as_str().unwrap().ends_with("src\n\nWhich is the manifest?"));
+
+        let (instruction, contents) = gemini_contents(&messages, &[]);
+        assert_eq!(instruction.unwrap()["parts"][0]["text"], "You are AIR.");
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[0]["parts"][0]["text"], "List the files");
+
+        let (system, single) = anthropic_messages(&[ChatMessage::user("hi")], &[]);
+        assert!(system.is_none());
+        assert_eq!(single, json!([{ "role": "user", "content": "hi" }]));
+    }
+
+    #[test]
+    fn test_image_parts() {
+        let messages = vec![ChatMessage::system("You are AIR."), ChatMessage::user("What is this?")];
+        let images = [ImageInput::new("image/png", "iVBORw0KGgo=")];
+
+        let openai = openai_messages(&messages, &images);
+        assert_eq!(openai[0]["content"], "You are AIR.");
+        assert_eq!(openai[1]["content"], json!([
+            { "type": "text", "text": "What is this?" },
+            { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } }
+        ]));
+
+        let (_, anthropic) = anthropic_messages(&messages, &images);
+        assert_eq!(anthropic[0]["content"][0]["source"]["media_type"], "image/png");
+        assert_eq!(anthropic[0]["content"][1], json!({ "type": "text", "text": "What is this?" }));
+
+        let (_, contents) = gemini_contents(&messages, &images);
+        assert_eq!(contents[0]["parts"][0]["text"], "What is this?");
+        assert_eq!(contents[0]["parts"][1]["inlineData"]["data"], "iVBORw0KGgo=");
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z").unwrap().with_timezone(&chrono::Utc);
+        assert_eq!(parse_retry_after("20", now), Some(std::time::Duration::from_secs(20)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(std::time::Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(std::time::Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_azure_deployment_url() {
+        let config: CloudProviderConfig = toml::from_str(r#"
+            name = "azure"
+            base_url = "https://my-resource.openai.azure.com/"
+            model = "gpt-4o-mini"
+            max_tokens = 1000
+            temperature = 0.7
+            timeout_seconds = 30
+            azure_ad = true
+        "#).unwrap();
+        let provider = AzureOpenAIProvider::new(config).unwrap();
+        assert_eq!(provider.url(), "https://my-resource.openai.azure.com/openai/deployments/gpt-4o-mini/chat/completions");
+        assert!(provider.is_available());
+    }
+
+    #[test]
+    fn test_gemini_schema() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "title": { "type": "string", "title": "Title" },
+                "tags": { "type": "array", "items": { "type": "string", "default": "x" } }
+            },
+            "required": ["title"]
+        });
+        assert_eq!(gemini_schema(&schema), json!({
+            "type": "object",
+            "properties": {
+                "title": { "type": "string" },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["title"]
+        }));
+    }
+
+    #[test]
+    fn test_native_tool_calls() {
+        let spec = ToolSpec {
+            name: "filesystem".to_string(),
+            description: "Files".to_string(),
+            functions: vec!["read_file".to_string(), "list_directory".to_string()],
+        };
+        assert_eq!(openai_tools(std::slice::from_ref(&spec))[0]["function"]["parameters"]["properties"]["function"]["enum"][1], "list_directory");
+        assert_eq!(gemini_tools(std::slice::from_ref(&spec))[0]["functionDeclarations"][0]["parameters"]["properties"]["args"]["type"], "string");
+
+        let message = json!({ "content": null, "tool_calls": [{
+            "id": "call_1", "type": "function",
+            "function": { "name": "filesystem", "arguments": "{\"function\": \"read_file\", \"args\": {\"path\": \"Cargo.toml\"}}" }
+        }]});
+        let calls = openai_tool_calls(&message);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].tool_name, "filesystem");
+        assert_eq!(calls[0].function, "read_file");
+        assert_eq!(calls[0].arguments["path"], "Cargo.toml");
+
+        let (text, calls) = anthropic_content(&json!([
+            { "type": "text", "text": "Reading it." },
+            { "type": "tool_use", "id": "toolu_1", "name": "filesystem", "input": { "function": "list_directory" } }
+        ]));
+        assert_eq!(text, "Reading it.");
+        assert_eq!(calls[0].function, "list_directory");
+        assert_eq!(calls[0].arguments, json!({}));
+
+        let (text, calls) = gemini_parts(&[json!({ "functionCall": {
+            "name": "filesystem", "args": { "function": "read_file", "args": "{\"path\": \"src\"}" }
+        }})]);
+        assert!(text.is_empty());
+        assert_eq!(calls[0].arguments["path"], "src");
     }
 }
@@ -3,9 +3,11 @@ use async_trait::async_trait;
 use std::sync::Arc;
 use tokio::sync::{Mutex, Notify};
 use std::io::{self, Write};
-use tracing::{info, error};
-use crate::models::{ModelProvider, ModelResponse, QueryContext};
+use tracing::{info, warn, error};
+use crate::models::{ModelProvider, ModelResponse, QueryContext, Role};
 use crate::config::LocalModelConfig;
+use crate::utils::{chat_template, context_window};
+use super::queue::RequestQueue;
 use mistralrs::{
     GgufModelBuilder, Model,
     TextMessageRole,
@@ -23,6 +25,7 @@ pub struct LocalProvider {
     state: Arc<Mutex<LocalState>>,
     // Signal to notify when background loading is complete
     loaded_notify: Arc<Notify>,
+    queue: RequestQueue,
 }
 
 impl LocalProvider {
@@ -60,13 +63,24 @@ impl LocalProvider {
         });
 
         Ok(Self {
+            queue: RequestQueue::new(config.max_concurrent_requests, config.max_queued_requests),
             config,
             state,
             loaded_notify,
         })
     }
 
+    /// Waits for the background load to finish, returning its error if it failed.
+    pub async fn wait_until_loaded(&self) -> Result<()> {
+        self.ensure_loaded().await
+    }
+
     async fn ensure_loaded(&self) -> Result<()> {
+        // Register for the completion signal before checking, so a load finishing in between isn't missed
+        let notified = self.loaded_notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
         // Fast path: check if loaded
         {
             let guard = self.state.lock().await;
@@ -81,7 +95,7 @@ impl LocalProvider {
         info!("⏳ Waiting for background model loading to complete...");
 
         // Wait for the background task to signal completion
-        self.loaded_notify.notified().await;
+        notified.await;
 
         // Check result again
         let guard = self.state.lock().await;
@@ -123,6 +137,17 @@ async fn load_model_internal(config: LocalModelConfig) -> Result<Arc<Model>> {
          }
     }
 
+    match chat_template::resolve(&config)? {
+        Some(source) => {
+            info!("💬 Chat template: {}", source);
+            let cache_dir = crate::utils::paths::get_air_data_dir()?.join("templates");
+            if let Some(file) = chat_template::template_file(&source, &cache_dir)? {
+                builder = builder.with_chat_template(file.to_string_lossy());
+            }
+        }
+        None => warn!("⚠️  No chat template found for {}; set local_model.chat_template if replies look garbled", filename),
+    }
+
     builder = builder.with_paged_attn(|| {
         PagedAttentionMetaBuilder::default().build()
     })?;
@@ -162,6 +187,8 @@ impl ModelProvider for LocalProvider {
     async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
         // This will now wait politely if the background thread is still running
         self.ensure_loaded().await?;
+        // Concurrent queries (server, daemon) take turns on the one model instance
+        let _slot = self.queue.acquire().await?;
 
         let model = {
             let state = self.state.lock().await;
@@ -170,25 +197,37 @@ impl ModelProvider for LocalProvider {
 
         let start_time = std::time::Instant::now();
 
+        // Leave room for the reply inside the context window, then slide the prompt into the rest
+        let context_length = self.config.context_length.max(256);
+        let max_tokens = context.max_tokens.min(context_length / 2);
+        let budget = (context_length - max_tokens) as usize;
+
         // Create messages (Same logic as before)
-        let messages = if let Some(msgs) = &context.messages {
+        let messages = if !context.messages.is_empty() {
+            let msgs = &context.messages;
+            let fitted = context_window::fit_messages(msgs, budget);
+            if fitted.len() < msgs.len() {
+                warn!("✂️  Dropped {} older messages to fit the {}-token context window", msgs.len() - fitted.len(), context_length);
+            }
             let mut tm = TextMessages::new();
-            for msg in msgs {
-                let role = match msg.role.as_str() {
-                    "system" => TextMessageRole::System,
-                    "user" => TextMessageRole::User,
-                    "assistant" => TextMessageRole::Assistant,
-                    _ => TextMessageRole::User,
+            for msg in &fitted {
+                let role = match msg.role {
+                    Role::System => TextMessageRole::System,
+                    Role::Assistant => TextMessageRole::Assistant,
+                    Role::User | Role::Tool => TextMessageRole::User,
                 };
-                tm = tm.add_message(role, msg.content.clone());
+                tm = tm.add_message(role, msg.text());
             }
             tm
         } else {
-            TextMessages::new().add_message(TextMessageRole::User, context.prompt.clone())
+            if context_window::estimate_tokens(&context.prompt) > budget {
+                warn!("✂️  Prompt shortened to fit the {}-token context window", context_length);
+            }
+            TextMessages::new().add_message(TextMessageRole::User, context_window::fit_text(&context.prompt, budget))
         };
 
         let mut request_builder = RequestBuilder::from(messages)
-            .set_sampler_max_len(context.max_tokens as usize)
+            .set_sampler_max_len(max_tokens as usize)
             .set_sampler_temperature(context.temperature as f64)
             .set_sampler_topp(0.9)
             .set_sampler_topk(40);
@@ -233,8 +272,10 @@ impl ModelProvider for LocalProvider {
             content,
             model_used: "mistralrs-gguf".to_string(),
             tokens_used,
+            input_tokens: 0,
             response_time_ms: start_time.elapsed().as_millis() as u64,
             confidence_score: None,
+            tool_calls: Vec::new(),
         })
     }
 }
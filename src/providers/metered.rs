@@ -0,0 +1,113 @@
+use crate::agent::memory::{MemoryManager, ModelUsage};
+use crate::agent::usage;
+use crate::config::{ModelPrice, PricingConfig};
+use crate::models::{ModelMetrics, ModelProvider, ModelResponse, QueryContext};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::debug;
+
+// Observed latencies replace the provider's built-in estimate once there are this many
+const MIN_LATENCY_SAMPLES: u64 = 5;
+
+/// Wraps a provider so every call's outcome, latency, tokens and cost land in the usage log
+/// behind `stats`, and its latency estimate reflects what was actually observed.
+pub struct MeteredProvider {
+    inner: Arc<dyn ModelProvider>,
+    memory: Arc<MemoryManager>,
+    pricing: PricingConfig,
+    default_price: Option<ModelPrice>,
+    metrics: Mutex<ModelMetrics>,
+}
+
+impl MeteredProvider {
+    /// `latency_history` holds earlier response times (see `MemoryManager::latency_history`) so
+    /// percentiles survive restarts. Calls are priced by the `pricing` entry for the model that
+    /// answered, else `default_price`; with neither their cost is left unknown.
+    pub fn wrap(
+        inner: Arc<dyn ModelProvider>,
+        memory: Arc<MemoryManager>,
+        pricing: PricingConfig,
+        default_price: Option<ModelPrice>,
+        latency_history: &[u64],
+    ) -> Arc<dyn ModelProvider> {
+        let mut metrics = ModelMetrics::default();
+        for &ms in latency_history {
+            metrics.latency.record(ms);
+        }
+        Arc::new(Self { inner, memory, pricing, default_price, metrics: Mutex::new(metrics) })
+    }
+}
+
+#[async_trait]
+impl ModelProvider for MeteredProvider {
+    async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
+        let start = Instant::now();
+        let result = self.inner.generate(context).await;
+
+        let usage = match &result {
+            Ok(response) => ModelUsage {
+                query_id: usage::current_query_id(),
+                provider: self.inner.name().to_string(),
+                model: response.model_used.clone(),
+                success: true,
+                latency_ms: response.response_time_ms,
+                tokens: response.tokens_used,
+                input_tokens: response.input_tokens,
+                cost_usd: self.pricing.price_for(&response.model_used)
+                    .or(self.default_price)
+                    .map(|price| price.cost_usd(response.input_tokens, response.tokens_used)),
+                error: None,
+            },
+            Err(e) => ModelUsage {
+                query_id: usage::current_query_id(),
+                provider: self.inner.name().to_string(),
+                model: self.inner.name().to_string(),
+                success: false,
+                latency_ms: start.elapsed().as_millis() as u64,
+                tokens: 0,
+                input_tokens: 0,
+                cost_usd: None,
+                error: Some(e.to_string()),
+            },
+        };
+        {
+            let mut metrics = self.metrics.lock().unwrap();
+            match &usage.error {
+                None => metrics.record_success(usage.latency_ms),
+                Some(error) => metrics.record_failure(error.clone()),
+            }
+        }
+        // Stats are best effort; never fail a query because the log couldn't be written
+        if let Err(e) = self.memory.record_model_usage(&usage).await {
+            debug!("Failed to record model usage: {}", e);
+        }
+
+        result
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn estimated_latency_ms(&self) -> u64 {
+        let metrics = self.metrics.lock().unwrap();
+        match metrics.latency.p50() {
+            Some(p50) if metrics.latency.count() >= MIN_LATENCY_SAMPLES => p50,
+            _ => self.inner.estimated_latency_ms(),
+        }
+    }
+
+    fn quality_score(&self) -> f32 {
+        self.inner.quality_score()
+    }
+
+    fn metrics(&self) -> Option<ModelMetrics> {
+        Some(self.metrics.lock().unwrap().clone())
+    }
+}
@@ -1,5 +1,21 @@
 pub mod cloud;
+#[cfg(feature = "native")]
 pub mod local;
+#[cfg(feature = "native")]
+pub mod metered;
+#[cfg(feature = "native")]
+pub mod queue;
+#[cfg(feature = "native")]
+pub mod ratelimit;
+#[cfg(feature = "native")]
+pub mod vcr;
 
 pub use cloud::{OpenAIProvider, AnthropicProvider, GeminiProvider, OpenRouterProvider};
+#[cfg(feature = "native")]
 pub use local::LocalProvider;
+#[cfg(feature = "native")]
+pub use metered::MeteredProvider;
+#[cfg(feature = "native")]
+pub use ratelimit::RateLimitedProvider;
+#[cfg(feature = "native")]
+pub use vcr::{Cassette, VcrProvider};
@@ -0,0 +1,95 @@
+//! Admission control for the local model. There is a single model instance, so when `air serve`
+//! or the daemon handle several queries at once they wait here in arrival order instead of all
+//! hitting the model together; with more than one slot, the requests let through are batched by
+//! the runtime's scheduler.
+
+use anyhow::{Result, anyhow};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+pub struct RequestQueue {
+    slots: Semaphore,
+    max_concurrent: usize,
+    max_waiting: usize,
+    waiting: AtomicUsize,
+}
+
+// Keeps the waiting count right when a queued request is cancelled (e.g. its client hung up)
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl RequestQueue {
+    /// `max_concurrent` requests run at once; beyond that up to `max_waiting` queue up and the
+    /// rest are turned away.
+    pub fn new(max_concurrent: usize, max_waiting: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            slots: Semaphore::new(max_concurrent),
+            max_concurrent,
+            max_waiting,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits for a generation slot; hold the permit for the whole generation. Slots are handed
+    /// out first come, first served.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>> {
+        if let Ok(permit) = self.slots.try_acquire() {
+            return Ok(permit);
+        }
+
+        let ahead = self.waiting.fetch_add(1, Ordering::SeqCst);
+        let _waiting = Waiting(&self.waiting);
+        if ahead >= self.max_waiting {
+            return Err(anyhow!("Local model is busy ({} requests running, {} queued); try again shortly",
+                self.in_flight(), ahead));
+        }
+        if ahead > 0 {
+            tracing::info!("⏳ Local model busy; {} requests ahead of this one", ahead);
+        }
+        self.slots.acquire().await.map_err(|_| anyhow!("Local model queue closed"))
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.max_concurrent - self.slots.available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_queue_serializes_and_rejects_overflow() {
+        let queue = Arc::new(RequestQueue::new(1, 1));
+        let running = queue.acquire().await.unwrap();
+        assert_eq!(queue.in_flight(), 1);
+
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire().await.map(|_| ()) })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(queue.waiting(), 1);
+
+        // Queue is full: turned away immediately
+        assert!(queue.acquire().await.is_err());
+        assert_eq!(queue.waiting(), 1);
+
+        drop(running);
+        waiter.await.unwrap().unwrap();
+        assert_eq!(queue.waiting(), 0);
+        assert_eq!(queue.in_flight(), 0);
+    }
+}
@@ -0,0 +1,191 @@
+//! Per-provider rate limits (`requests_per_minute`, `tokens_per_minute` on a cloud provider).
+//! Each limit is a token bucket refilled continuously over the minute. A provider at its limit,
+//! or cooling down after the API answered 429 (for its Retry-After, else a minute), reports
+//! itself unavailable so routing moves on to the next one instead of retrying into the limit.
+
+use crate::config::CloudProviderConfig;
+use crate::models::{ModelMetrics, ModelProvider, ModelResponse, ProviderError, QueryContext};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// How long a provider is skipped after a 429 that didn't say when to retry.
+const COOLDOWN_AFTER_429: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn per_minute(limit: u32, now: Instant) -> Self {
+        let capacity = limit.max(1) as f64;
+        Self { capacity, available: capacity, per_second: capacity / 60.0, refilled: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.refilled = now;
+    }
+
+    /// How long until `amount` is available. Amounts over the capacity only wait for a full
+    /// bucket, so an oversized request still runs eventually.
+    fn wait_for(&mut self, amount: f64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_second)
+        }
+    }
+
+    /// May leave the bucket in debt, which later calls wait off.
+    fn take(&mut self, amount: f64) {
+        self.available -= amount;
+    }
+}
+
+struct Limits {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    cooldown_until: Option<Instant>,
+}
+
+impl Limits {
+    /// How long until a call reserving `tokens` may start.
+    fn wait_for(&mut self, tokens: f64, now: Instant) -> Duration {
+        let cooldown = self.cooldown_until.map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+        let requests = self.requests.as_mut().map_or(Duration::ZERO, |b| b.wait_for(1.0, now));
+        let tokens = self.tokens.as_mut().map_or(Duration::ZERO, |b| b.wait_for(tokens, now));
+        cooldown.max(requests).max(tokens)
+    }
+
+    fn take(&mut self, tokens: f64) {
+        if let Some(bucket) = &mut self.requests {
+            bucket.take(1.0);
+        }
+        if let Some(bucket) = &mut self.tokens {
+            bucket.take(tokens);
+        }
+    }
+}
+
+/// Wraps a cloud provider to enforce its configured rate limits and back off after a 429.
+pub struct RateLimitedProvider {
+    inner: Arc<dyn ModelProvider>,
+    limits: Mutex<Limits>,
+}
+
+impl RateLimitedProvider {
+    pub fn wrap(inner: Arc<dyn ModelProvider>, config: &CloudProviderConfig) -> Arc<dyn ModelProvider> {
+        let now = Instant::now();
+        let limits = Limits {
+            requests: config.requests_per_minute.map(|limit| Bucket::per_minute(limit, now)),
+            tokens: config.tokens_per_minute.map(|limit| Bucket::per_minute(limit, now)),
+            cooldown_until: None,
+        };
+        Arc::new(Self { inner, limits: Mutex::new(limits) })
+    }
+
+    /// Waits for room under the limits and reserves it. Refuses straight away when the wait
+    /// would outlast the call's own timeout.
+    async fn reserve(&self, tokens: f64, timeout: Duration) -> Result<()> {
+        loop {
+            let wait = {
+                let mut limits = self.limits.lock().unwrap();
+                let wait = limits.wait_for(tokens, Instant::now());
+                if wait.is_zero() {
+                    limits.take(tokens);
+                    return Ok(());
+                }
+                wait
+            };
+            if wait > timeout {
+                return Err(ProviderError::RateLimited { provider: self.inner.name().to_string(), retry_after: Some(wait) }.into());
+            }
+            info!("⏳ {} at its rate limit; waiting {}ms", self.inner.name(), wait.as_millis());
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// What a call may use against `tokens_per_minute`: the prompt (about four characters a token)
+/// plus the whole completion allowance, which is how provider quotas count it too.
+fn reserved_tokens(context: &QueryContext) -> f64 {
+    let prompt_chars: usize = context.conversation().iter().map(|m| m.content.len()).sum();
+    (prompt_chars / 4) as f64 + context.max_tokens as f64
+}
+
+#[async_trait]
+impl ModelProvider for RateLimitedProvider {
+    async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
+        let reserved = reserved_tokens(context);
+        self.reserve(reserved, context.timeout).await?;
+
+        let result = self.inner.generate(context).await;
+        let mut limits = self.limits.lock().unwrap();
+        match &result {
+            // Settle the reservation against what the call actually used
+            Ok(response) if response.tokens_used > 0 => {
+                if let Some(bucket) = &mut limits.tokens {
+                    bucket.take(response.tokens_used as f64 - reserved);
+                }
+            }
+            Err(e) => {
+                if let Some(ProviderError::RateLimited { retry_after, .. }) = ProviderError::of(e) {
+                    let cooldown = retry_after.unwrap_or(COOLDOWN_AFTER_429);
+                    warn!("🚦 {} returned a rate limit error; skipping it for {}s", self.inner.name(), cooldown.as_secs());
+                    let until = Instant::now() + cooldown;
+                    limits.cooldown_until = Some(limits.cooldown_until.map_or(until, |current| current.max(until)));
+                }
+            }
+            _ => {}
+        }
+        result
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// False while the provider couldn't take a typical call without waiting.
+    fn is_available(&self) -> bool {
+        self.inner.is_available() && self.limits.lock().unwrap().wait_for(1.0, Instant::now()).is_zero()
+    }
+
+    fn estimated_latency_ms(&self) -> u64 {
+        self.inner.estimated_latency_ms()
+    }
+
+    fn quality_score(&self) -> f32 {
+        self.inner.quality_score()
+    }
+
+    fn metrics(&self) -> Option<ModelMetrics> {
+        self.inner.metrics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_over_the_minute() {
+        let start = Instant::now();
+        let mut bucket = Bucket::per_minute(60, start);
+        assert!(bucket.wait_for(60.0, start).is_zero());
+        bucket.take(60.0);
+        assert_eq!(bucket.wait_for(1.0, start), Duration::from_secs(1));
+        assert!(bucket.wait_for(1.0, start + Duration::from_secs(1)).is_zero());
+
+        // Oversized requests wait for a full bucket rather than forever
+        assert_eq!(bucket.wait_for(1000.0, start + Duration::from_secs(1)), Duration::from_secs(59));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::config::VcrMode;
+use crate::models::{ChatMessage, ImageInput, ModelMetrics, ModelProvider, ModelResponse, QueryContext};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info};
+
+/// What a provider was asked, as stored in a cassette. Replay matches on all of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<ChatMessage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageInput>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+}
+
+impl From<&QueryContext> for RecordedRequest {
+    fn from(context: &QueryContext) -> Self {
+        Self {
+            prompt: context.prompt.clone(),
+            messages: context.messages.clone(),
+            images: context.images.clone(),
+            max_tokens: context.max_tokens,
+            temperature: context.temperature,
+        }
+    }
+}
+
+/// One call and its outcome; failures are recorded too so fallback paths replay the same way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub provider: String,
+    pub request: RecordedRequest,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<ModelResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    // Replay hands out each interaction once, so repeated identical requests get their answers in recorded order
+    used: Vec<bool>,
+}
+
+/// A cassette file shared by every provider of an agent.
+pub struct Cassette {
+    path: PathBuf,
+    mode: VcrMode,
+    state: Mutex<CassetteState>,
+}
+
+impl Cassette {
+    /// Opens `path` for `mode`: replay reads the recorded interactions, record starts an empty
+    /// cassette that is written after every call.
+    pub fn open(path: impl AsRef<Path>, mode: VcrMode) -> Result<Arc<Self>> {
+        let path = path.as_ref().to_path_buf();
+        let interactions = match mode {
+            VcrMode::Replay => {
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow!("Cannot read cassette {}: {}", path.display(), e))?;
+                serde_json::from_str::<CassetteFile>(&text)
+                    .map_err(|e| anyhow!("Invalid cassette {}: {}", path.display(), e))?
+                    .interactions
+            }
+            VcrMode::Record | VcrMode::Off => Vec::new(),
+        };
+        info!("📼 {} cassette {} ({} interactions)", if mode == VcrMode::Replay { "Replaying" } else { "Recording to" },
+            path.display(), interactions.len());
+        let used = vec![false; interactions.len()];
+        Ok(Arc::new(Self { path, mode, state: Mutex::new(CassetteState { interactions, used }) }))
+    }
+
+    pub fn mode(&self) -> VcrMode {
+        self.mode
+    }
+
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.lock().unwrap().interactions.clone()
+    }
+
+    fn has_provider(&self, provider: &str) -> bool {
+        self.state.lock().unwrap().interactions.iter().any(|i| i.provider == provider)
+    }
+
+    /// The first unused recording of this exact request.
+    fn take(&self, provider: &str, request: &RecordedRequest) -> Option<Interaction> {
+        let mut state = self.state.lock().unwrap();
+        let CassetteState { interactions, used } = &mut *state;
+        let index = interactions.iter().enumerate()
+            .position(|(i, recorded)| !used[i] && recorded.provider == provider && &recorded.request == request)?;
+        used[index] = true;
+        Some(interactions[index].clone())
+    }
+
+    fn record(&self, interaction: Interaction) -> Result<()> {
+        let file = {
+            let mut state = self.state.lock().unwrap();
+            state.interactions.push(interaction);
+            state.used.push(true);
+            CassetteFile { interactions: state.interactions.clone() }
+        };
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+}
+
+/// Wraps a provider so its calls are recorded to, or answered from, a cassette.
+pub struct VcrProvider {
+    inner: Arc<dyn ModelProvider>,
+    cassette: Arc<Cassette>,
+}
+
+impl VcrProvider {
+    pub fn wrap(inner: Arc<dyn ModelProvider>, cassette: Arc<Cassette>) -> Arc<dyn ModelProvider> {
+        if cassette.mode() == VcrMode::Off {
+            return inner;
+        }
+        Arc::new(Self { inner, cassette })
+    }
+}
+
+#[async_trait]
+impl ModelProvider for VcrProvider {
+    async fn generate(&self, context: &QueryContext) -> Result<ModelResponse> {
+        let request = RecordedRequest::from(context);
+        match self.cassette.mode() {
+            VcrMode::Replay => {
+                let recorded = self.cassette.take(self.name(), &request).ok_or_else(|| anyhow!(
+                    "No recorded {} response for this request in {} (re-record with vcr.mode = \"record\")",
+                    self.name(), self.cassette.path.display()
+                ))?;
+                debug!("📼 Replaying {} response", self.name());
+                match (recorded.response, recorded.error) {
+                    (Some(response), _) => Ok(response),
+                    (None, error) => Err(anyhow!(error.unwrap_or_else(|| "Recorded failure".to_string()))),
+                }
+            }
+            VcrMode::Record => {
+                let result = self.inner.generate(context).await;
+                let interaction = Interaction {
+                    provider: self.name().to_string(),
+                    request,
+                    response: result.as_ref().ok().cloned(),
+                    error: result.as_ref().err().map(|e| e.to_string()),
+                };
+                self.cassette.record(interaction)?;
+                result
+            }
+            VcrMode::Off => self.inner.generate(context).await,
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// On replay a provider is available when the cassette has its answers, API key or not.
+    fn is_available(&self) -> bool {
+        match self.cassette.mode() {
+            VcrMode::Replay => self.cassette.has_provider(self.name()),
+            _ => self.inner.is_available(),
+        }
+    }
+
+    fn estimated_latency_ms(&self) -> u64 {
+        self.inner.estimated_latency_ms()
+    }
+
+    fn quality_score(&self) -> f32 {
+        self.inner.quality_score()
+    }
+
+    fn metrics(&self) -> Option<ModelMetrics> {
+        self.inner.metrics()
+    }
+}
@@ -0,0 +1,219 @@
+//! Splitting files into chunks for the knowledge base, by a configurable strategy (fixed-size
+//! with overlap, markdown sections, code definitions). Each chunk keeps its line range and
+//! heading or symbol, stored as metadata next to its vector so retrieved text can cite its origin.
+
+use super::code::{self, CODE_EXTENSIONS};
+use crate::config::{ChunkStrategy, KnowledgeConfig};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Chunks with less text than this (blank sections, lone separators) aren't indexed.
+const MIN_CHUNK_CHARS: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// First and last line, 1-based and inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Heading path (`Install > Linux`) for markdown, the defined symbol for code.
+    pub section: Option<String>,
+}
+
+/// What `auto` means for `path`: markdown for .md files, code for source files, fixed otherwise.
+pub fn strategy_for(path: &str, strategy: ChunkStrategy) -> ChunkStrategy {
+    if strategy != ChunkStrategy::Auto {
+        return strategy;
+    }
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "md" | "markdown" => ChunkStrategy::Markdown,
+        e if CODE_EXTENSIONS.contains(&e) => ChunkStrategy::Code,
+        _ => ChunkStrategy::Fixed,
+    }
+}
+
+pub fn chunk(path: &str, text: &str, config: &KnowledgeConfig) -> Vec<Chunk> {
+    chunk_as(strategy_for(path, config.chunking), path, text, config)
+}
+
+/// Splits with `strategy` whatever `config.chunking` says, e.g. for text extracted from a PDF.
+pub fn chunk_as(strategy: ChunkStrategy, path: &str, text: &str, config: &KnowledgeConfig) -> Vec<Chunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let chunks = match strategy {
+        ChunkStrategy::Markdown => chunk_markdown(&lines, config),
+        ChunkStrategy::Code => code::chunk_code(path, text).into_iter()
+            .map(|c| Chunk { text: c.text, start_line: c.start_line, end_line: c.end_line, section: c.symbol })
+            .collect(),
+        ChunkStrategy::Fixed | ChunkStrategy::Auto => chunk_fixed(&lines, 0, None, config),
+    };
+    chunks.into_iter().filter(|c| c.text.trim().len() >= MIN_CHUNK_CHARS).collect()
+}
+
+/// Runs of whole lines up to `chunk_size` characters (a longer line is a chunk of its own), each
+/// starting with up to `chunk_overlap` characters of lines from the end of the previous one.
+/// `first_line` is the index of `lines[0]` in the file.
+fn chunk_fixed(lines: &[&str], first_line: usize, section: Option<&str>, config: &KnowledgeConfig) -> Vec<Chunk> {
+    let size = config.chunk_size.max(1);
+    let overlap = config.chunk_overlap.min(size / 2);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start;
+        let mut len = 0;
+        while end < lines.len() && (end == start || len + lines[end].len() + 1 <= size) {
+            len += lines[end].len() + 1;
+            end += 1;
+        }
+        chunks.push(Chunk {
+            text: lines[start..end].join("\n"),
+            start_line: first_line + start + 1,
+            end_line: first_line + end,
+            section: section.map(str::to_string),
+        });
+        if end == lines.len() {
+            break;
+        }
+
+        // Always move forward by at least one line, however long the overlap
+        let mut next = end;
+        let mut repeated = 0;
+        while next > start + 1 && repeated + lines[next - 1].len() + 1 <= overlap {
+            repeated += lines[next - 1].len() + 1;
+            next -= 1;
+        }
+        start = next;
+    }
+    chunks
+}
+
+/// One chunk per section, starting at its heading; sections over `chunk_size` are split
+/// fixed-size. Lines inside code fences are never taken for headings.
+fn chunk_markdown(lines: &[&str], config: &KnowledgeConfig) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut section: Option<String> = None;
+    let mut start = 0;
+    let mut in_fence = false;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        let Some((level, title)) = heading(line).filter(|_| !in_fence) else {
+            continue;
+        };
+        push_section(&mut chunks, &lines[start..i], start, section.as_deref(), config);
+        headings.retain(|(l, _)| *l < level);
+        headings.push((level, title));
+        section = Some(headings.iter().map(|(_, t)| t.as_str()).collect::<Vec<_>>().join(" > "));
+        start = i;
+    }
+    push_section(&mut chunks, &lines[start..], start, section.as_deref(), config);
+    chunks
+}
+
+fn push_section(chunks: &mut Vec<Chunk>, lines: &[&str], first_line: usize, section: Option<&str>, config: &KnowledgeConfig) {
+    let text = lines.join("\n");
+    if text.trim().is_empty() {
+        return;
+    }
+    if text.len() <= config.chunk_size {
+        chunks.push(Chunk {
+            text,
+            start_line: first_line + 1,
+            end_line: first_line + lines.len(),
+            section: section.map(str::to_string),
+        });
+    } else {
+        chunks.extend(chunk_fixed(lines, first_line, section, config));
+    }
+}
+
+/// The level and title of an ATX heading (`## Install`).
+fn heading(line: &str) -> Option<(usize, String)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end().to_string()))
+}
+
+/// What's stored with a chunk's vector: `extra` plus its source, index, line range and section.
+pub fn metadata(source: &str, index: usize, chunk: &Chunk, extra: &Value) -> Value {
+    let mut metadata = match extra {
+        Value::Object(map) => map.clone(),
+        _ => serde_json::Map::new(),
+    };
+    metadata.insert("source".to_string(), json!(source));
+    metadata.insert("chunk_index".to_string(), json!(index));
+    metadata.insert("start_line".to_string(), json!(chunk.start_line));
+    metadata.insert("end_line".to_string(), json!(chunk.end_line));
+    if let Some(section) = &chunk.section {
+        metadata.insert("section".to_string(), json!(section));
+    }
+    Value::Object(metadata)
+}
+
+/// Where a retrieved chunk came from, e.g. `docs/guide.md:10-42 (Install > Linux)` or
+/// `manual.pdf, page 12`. Line ranges are only cited for files indexed as text, since in PDF,
+/// DOCX and HTML they count lines of the extracted text. `None` without a source.
+pub fn citation(metadata: &HashMap<String, Value>) -> Option<String> {
+    let mut citation = metadata.get("source")?.as_str()?.to_string();
+    let lines = (metadata.get("start_line").and_then(Value::as_u64), metadata.get("end_line").and_then(Value::as_u64));
+    let as_text = metadata.get("format").and_then(Value::as_str).map_or(true, |f| f == "text" || f == "markdown");
+    match (metadata.get("page").and_then(Value::as_u64), lines) {
+        (Some(page), _) => citation.push_str(&format!(", page {}", page)),
+        (None, (Some(start), Some(end))) if as_text => citation.push_str(&format!(":{}-{}", start, end)),
+        _ => {}
+    }
+    if let Some(section) = metadata.get("section").and_then(Value::as_str) {
+        citation.push_str(&format!(" ({})", section));
+    }
+    Some(citation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(chunking: ChunkStrategy, chunk_size: usize, chunk_overlap: usize) -> KnowledgeConfig {
+        KnowledgeConfig { chunking, chunk_size, chunk_overlap }
+    }
+
+    #[test]
+    fn test_fixed_chunks_overlap() {
+        let text = (1..=10).map(|n| format!("line number {:02}", n)).collect::<Vec<_>>().join("\n");
+        let chunks = chunk("notes.txt", &text, &config(ChunkStrategy::Auto, 60, 15));
+        let ranges: Vec<(usize, usize)> = chunks.iter().map(|c| (c.start_line, c.end_line)).collect();
+        assert_eq!(ranges, [(1, 4), (4, 7), (7, 10)]);
+        assert!(chunks[1].text.starts_with("line number 04"));
+    }
+
+    #[test]
+    fn test_markdown_chunks_by_section() {
+        let text = "# Guide\nIntro paragraph for the guide.\n## Install\n```sh\n# not a heading\n```\nRun the installer script.\n## Usage\nCall it with a file to index.";
+        let chunks = chunk("guide.md", text, &config(ChunkStrategy::Auto, 1500, 200));
+        let sections: Vec<(Option<&str>, usize, usize)> = chunks.iter()
+            .map(|c| (c.section.as_deref(), c.start_line, c.end_line))
+            .collect();
+        assert_eq!(sections, [
+            (Some("Guide"), 1, 2),
+            (Some("Guide > Install"), 3, 7),
+            (Some("Guide > Usage"), 8, 9),
+        ]);
+
+        let metadata = metadata("docs/guide.md", 1, &chunks[1], &json!({ "type": "file" }));
+        let metadata: HashMap<String, Value> = serde_json::from_value(metadata).unwrap();
+        assert_eq!(metadata["chunk_index"], 1);
+        assert_eq!(citation(&metadata).as_deref(), Some("docs/guide.md:3-7 (Guide > Install)"));
+
+        let pdf: HashMap<String, Value> = serde_json::from_value(json!({
+            "source": "manual.pdf", "format": "pdf", "page": 12, "start_line": 1, "end_line": 30
+        })).unwrap();
+        assert_eq!(citation(&pdf).as_deref(), Some("manual.pdf, page 12"));
+    }
+}
@@ -0,0 +1,360 @@
+//! Repository index behind `air code`: source files are split at top-level definitions,
+//! embedded into a knowledge store of their own, and retrieved with their file and line range so
+//! answers can cite `path:line`. Re-indexing only touches files whose contents changed.
+
+use super::store::KnowledgeStore;
+use crate::rag::langchain_embedding::CandleEmbedder;
+use anyhow::{Result, anyhow};
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Chunks longer than this are split even without a definition boundary.
+const MAX_CHUNK_LINES: usize = 60;
+/// Shorter chunks are folded into the previous one.
+const MIN_CHUNK_LINES: usize = 4;
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+/// The file tree in the prompt is cut after this many entries.
+const MAX_TREE_ENTRIES: usize = 200;
+const INDEX_FILE: &str = "code_index.json.gz";
+
+pub(crate) const CODE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "swift", "c", "h", "cc", "cpp", "hpp",
+    "cs", "rb", "php", "scala", "sh", "lua", "sql", "toml", "yaml", "yml", "json", "md", "proto",
+];
+/// Directories skipped when the repository isn't a git checkout.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "dist", "build", "vendor", "__pycache__"];
+/// Line starts that open a top-level definition, across the supported languages.
+const DEFINITION_KEYWORDS: &[&str] = &[
+    "fn ", "pub fn ", "pub(crate) fn ", "async fn ", "pub async fn ", "impl ", "impl<", "struct ",
+    "pub struct ", "enum ", "pub enum ", "trait ", "pub trait ", "mod ", "pub mod ", "macro_rules! ",
+    "def ", "async def ", "class ", "function ", "async function ", "export ", "func ", "interface ",
+    "type ", "public ", "private ", "protected ", "module ",
+];
+
+/// One retrieved piece of code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeChunk {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub symbol: Option<String>,
+    pub text: String,
+}
+
+impl CodeChunk {
+    /// `src/auth.rs:10-42`, the form answers cite.
+    pub fn location(&self) -> String {
+        format!("{}:{}-{}", self.path, self.start_line, self.end_line)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IndexStats {
+    pub files: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub chunks: usize,
+}
+
+pub struct CodeIndex {
+    root: PathBuf,
+    store: KnowledgeStore<CandleEmbedder>,
+}
+
+impl CodeIndex {
+    /// Opens the index for the repository at `root`: `.air/data` in an `air init` project,
+    /// otherwise a file per repository in the data directory.
+    pub async fn open(root: &Path) -> Result<Self> {
+        let path = match crate::utils::project::Project::find(root, dirs::home_dir().as_deref()) {
+            Some(project) => project.data_dir().join(INDEX_FILE),
+            None => {
+                let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let digest = format!("{:x}", md5::compute(root.to_string_lossy().as_bytes()));
+                crate::utils::paths::get_air_data_dir()?.join("code").join(format!("{}-{}.json.gz", name, &digest[..8]))
+            }
+        };
+        Ok(Self { root: root.to_path_buf(), store: KnowledgeStore::open(&path).await? })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Brings the index up to date with the files on disk; `rebuild` re-embeds everything.
+    pub async fn update(&self, rebuild: bool) -> Result<IndexStats> {
+        let files = list_files(&self.root)?;
+        if files.is_empty() {
+            return Err(anyhow!("No source files found under {}", self.root.display()));
+        }
+        let indexed = self.store.sources("hash").await;
+        let mut stats = IndexStats { files: files.len(), ..IndexStats::default() };
+
+        let current: HashSet<&str> = files.iter().map(|f| f.as_str()).collect();
+        for source in indexed.keys().filter(|s| !current.contains(s.as_str())) {
+            self.store.remove_source(source).await?;
+            stats.removed += 1;
+        }
+
+        for (done, file) in files.iter().enumerate() {
+            crate::jobs::report_progress(done as u64, Some(files.len() as u64), Some(file));
+            let Ok(text) = std::fs::read_to_string(self.root.join(file)) else { continue };
+            let hash = format!("{:x}", md5::compute(text.as_bytes()));
+            if !rebuild && indexed.get(file).and_then(|h| h.as_str()) == Some(hash.as_str()) {
+                continue;
+            }
+            self.store.remove_source(file).await?;
+            let items = chunk_code(file, &text).into_iter().map(|chunk| {
+                let content = format!("{}{}\n{}", chunk.location(),
+                    chunk.symbol.as_ref().map(|s| format!(" ({})", s)).unwrap_or_default(), chunk.text);
+                (content, json!({
+                    "source": chunk.path,
+                    "start_line": chunk.start_line,
+                    "end_line": chunk.end_line,
+                    "symbol": chunk.symbol,
+                    "hash": hash,
+                }))
+            }).collect::<Vec<_>>();
+            stats.chunks += items.len();
+            self.store.add_texts(items).await?;
+            stats.updated += 1;
+        }
+        Ok(stats)
+    }
+
+    pub async fn search(&self, question: &str, limit: usize) -> Result<Vec<(CodeChunk, f64)>> {
+        Ok(self.store.search(question, limit).await?.into_iter().map(|(doc, score)| {
+            let meta = &doc.metadata;
+            let line = |key: &str| meta.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            // The stored text starts with its location line
+            let text = doc.page_content.split_once('\n').map(|(_, body)| body).unwrap_or(&doc.page_content);
+            (CodeChunk {
+                path: meta.get("source").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                start_line: line("start_line"),
+                end_line: line("end_line"),
+                symbol: meta.get("symbol").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                text: text.to_string(),
+            }, score)
+        }).collect())
+    }
+}
+
+/// The top-level directory of the git checkout containing `dir`, if any.
+pub fn repository_root(dir: &Path) -> Option<PathBuf> {
+    let output = Command::new("git").arg("-C").arg(dir).args(["rev-parse", "--show-toplevel"]).output().ok()?;
+    output.status.success().then(|| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Source files under `root`, relative and sorted: `git ls-files` (so .gitignore applies) when
+/// it is a checkout, else a directory walk that skips hidden and build directories.
+pub fn list_files(root: &Path) -> Result<Vec<String>> {
+    let mut files = match git(root, &["ls-files", "--cached", "--others", "--exclude-standard"]) {
+        Some(output) => output.lines().map(|l| l.to_string()).collect(),
+        None => {
+            let mut files = Vec::new();
+            walk(root, root, &mut files)?;
+            files
+        }
+    };
+    files.retain(|f| {
+        let path = root.join(f);
+        Path::new(f).extension().and_then(|e| e.to_str()).is_some_and(|e| CODE_EXTENSIONS.contains(&e))
+            && std::fs::metadata(&path).map_or(false, |m| m.is_file() && m.len() <= MAX_FILE_BYTES)
+    });
+    files.sort();
+    Ok(files)
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+        if name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_str()) {
+            continue;
+        }
+        if path.is_dir() {
+            walk(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
+
+fn git(root: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").arg("-C").arg(root).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Branch, last commits and remote, for questions about the repository itself.
+pub fn git_summary(root: &Path) -> Option<String> {
+    let branch = git(root, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let mut summary = format!("Branch: {}\n", branch.trim());
+    if let Some(remote) = git(root, &["remote", "get-url", "origin"]) {
+        summary.push_str(&format!("Remote: {}\n", remote.trim()));
+    }
+    if let Some(log) = git(root, &["log", "-5", "--format=%h %s (%an, %ar)"]) {
+        summary.push_str(&format!("Recent commits:\n{}", log));
+    }
+    Some(summary)
+}
+
+/// An indented listing of `files`, cut at `MAX_TREE_ENTRIES`.
+pub fn file_tree(files: &[String]) -> String {
+    let mut tree = String::new();
+    let mut shown_dirs: HashSet<String> = HashSet::new();
+    let mut entries = 0;
+    for file in files {
+        let parts: Vec<&str> = file.split('/').collect();
+        for depth in 0..parts.len() - 1 {
+            let dir = parts[..=depth].join("/");
+            if shown_dirs.insert(dir) {
+                tree.push_str(&format!("{}{}/\n", "  ".repeat(depth), parts[depth]));
+                entries += 1;
+            }
+        }
+        tree.push_str(&format!("{}{}\n", "  ".repeat(parts.len() - 1), parts[parts.len() - 1]));
+        entries += 1;
+        if entries >= MAX_TREE_ENTRIES {
+            tree.push_str(&format!("… ({} files in total)\n", files.len()));
+            break;
+        }
+    }
+    tree
+}
+
+/// Splits a file at top-level definitions (keeping their doc comments and attributes with
+/// them), then caps every chunk at `MAX_CHUNK_LINES`.
+pub fn chunk_code(path: &str, text: &str) -> Vec<CodeChunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut starts = vec![0];
+    for (i, line) in lines.iter().enumerate().skip(1) {
+        if !is_definition(line) {
+            continue;
+        }
+        // Pull the comments and attributes directly above into the definition's chunk
+        let mut start = i;
+        while start > 0 && is_preamble(lines[start - 1]) {
+            start -= 1;
+        }
+        if start > *starts.last().unwrap() {
+            starts.push(start);
+        }
+    }
+
+    let mut chunks: Vec<CodeChunk> = Vec::new();
+    for (n, &start) in starts.iter().enumerate() {
+        let end = starts.get(n + 1).copied().unwrap_or(lines.len());
+        let mut from = start;
+        while from < end {
+            let to = (from + MAX_CHUNK_LINES).min(end);
+            let body = &lines[from..to];
+            let symbol = body.iter().find(|l| is_definition(l)).and_then(|l| symbol_name(l));
+            match chunks.last_mut() {
+                // Tiny pieces (a `use` block, a closing brace) ride along with the previous chunk
+                Some(previous) if to - from < MIN_CHUNK_LINES && previous.end_line - previous.start_line + 1 + (to - from) <= MAX_CHUNK_LINES => {
+                    previous.text.push('\n');
+                    previous.text.push_str(&body.join("\n"));
+                    previous.end_line = to;
+                }
+                _ => chunks.push(CodeChunk {
+                    path: path.to_string(),
+                    start_line: from + 1,
+                    end_line: to,
+                    symbol,
+                    text: body.join("\n"),
+                }),
+            }
+            from = to;
+        }
+    }
+    chunks.retain(|c| !c.text.trim().is_empty());
+    chunks
+}
+
+fn is_definition(line: &str) -> bool {
+    !line.starts_with(char::is_whitespace) && DEFINITION_KEYWORDS.iter().any(|k| line.starts_with(k))
+}
+
+fn is_preamble(line: &str) -> bool {
+    let line = line.trim_start();
+    ["//", "#", "@", "/*", "*", "\"\"\""].iter().any(|p| line.starts_with(p)) && !line.starts_with("#!")
+}
+
+/// The defined name: the first identifier after the keywords and generics, e.g. `login` in
+/// `pub async fn login(` or `Store` in `impl<T> Store<T> {`.
+fn symbol_name(line: &str) -> Option<String> {
+    const SKIP: &[&str] = &[
+        "pub", "pub(crate)", "async", "fn", "impl", "struct", "enum", "trait", "mod", "def", "class",
+        "function", "export", "default", "func", "interface", "type", "public", "private", "protected",
+        "static", "const", "module", "macro_rules!", "unsafe", "abstract", "final",
+    ];
+    let mut depth = 0usize;
+    let line: String = line.chars().filter(|&c| {
+        match c {
+            '<' => depth += 1,
+            '>' if depth > 0 => { depth -= 1; return false; }
+            _ => {}
+        }
+        depth == 0
+    }).collect();
+    line.split(|c: char| c.is_whitespace() || "({:;=".contains(c))
+        .filter(|w| !w.is_empty())
+        .find(|w| !SKIP.contains(w))
+        .map(|w| w.trim_end_matches('!').to_string())
+}
+
+/// The prompt for a question about the repository: layout, git state and the retrieved code.
+pub fn build_prompt(question: &str, tree: &str, git: Option<&str>, hits: &[(CodeChunk, f64)]) -> String {
+    let mut prompt = String::from(
+        "You are answering a question about a code repository. Use only the code below. \
+         Cite every claim with its location in the form `path:line` or `path:start-end`, \
+         and say so if the code shown doesn't answer the question.\n\n",
+    );
+    prompt.push_str(&format!("Repository layout:\n{}\n", tree));
+    if let Some(git) = git {
+        prompt.push_str(&format!("Git:\n{}\n", git));
+    }
+    prompt.push_str("Relevant code:\n");
+    for (chunk, _) in hits {
+        prompt.push_str(&format!("\n--- {}{}\n{}\n", chunk.location(),
+            chunk.symbol.as_ref().map(|s| format!(" ({})", s)).unwrap_or_default(), chunk.text));
+    }
+    prompt.push_str(&format!("\nQuestion: {}", question));
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_code_splits_at_definitions() {
+        let source = "use std::io;\n\n/// Logs a user in.\n#[inline]\npub fn login(user: &str) -> bool {\n    check(user)\n        && true\n}\n\nstruct Session {\n    id: u64,\n    user: String,\n}\n";
+        let chunks = chunk_code("src/auth.rs", source);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].start_line, 1);
+        assert_eq!(chunks[0].symbol.as_deref(), Some("login"));
+        assert!(chunks[0].text.contains("/// Logs a user in."));
+        assert_eq!(chunks[1].location(), "src/auth.rs:10-13");
+        assert_eq!(chunks[1].symbol.as_deref(), Some("Session"));
+    }
+
+    #[test]
+    fn test_long_chunks_are_capped() {
+        let body: String = (0..150).map(|i| format!("    line {}\n", i)).collect();
+        let chunks = chunk_code("big.py", &format!("def big():\n{}", body));
+        assert!(chunks.iter().all(|c| c.end_line - c.start_line < MAX_CHUNK_LINES));
+        assert_eq!(chunks.last().unwrap().end_line, 151);
+    }
+
+    #[test]
+    fn test_symbol_name_and_tree() {
+        assert_eq!(symbol_name("pub async fn handle_login(req: Request)").as_deref(), Some("handle_login"));
+        assert_eq!(symbol_name("impl<T> Store<T> {").as_deref(), Some("Store"));
+        assert_eq!(symbol_name("class AuthService:").as_deref(), Some("AuthService"));
+        let tree = file_tree(&["src/auth/mod.rs".to_string(), "src/main.rs".to_string()]);
+        assert_eq!(tree, "src/\n  auth/\n    mod.rs\n  main.rs\n");
+    }
+}
@@ -2,9 +2,83 @@ use anyhow::Result;
 use candle_core::{Device, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::models::bert::{BertModel, Config, DTYPE};
-use hf_hub::{api::sync::ApiBuilder, Repo, RepoType};
+use hf_hub::{api::sync::{ApiBuilder, ApiRepo}, Cache, Repo, RepoType};
+use std::path::PathBuf;
 use tokenizers::{PaddingParams, Tokenizer};
 
+/// all-MiniLM-L6-v2: small, fast, and good enough for knowledge recall.
+pub const EMBEDDING_MODEL_ID: &str = "sentence-transformers/all-MiniLM-L6-v2";
+const EMBEDDING_FILES: &[&str] = &["config.json", "tokenizer.json", "model.safetensors"];
+const EMBEDDING_DIMENSIONS: usize = 384;
+
+/// Whether the embedding model the knowledge store needs is on disk and usable.
+#[derive(Debug)]
+pub enum EmbeddingModelStatus {
+    Ready { dir: PathBuf, size_bytes: u64 },
+    Missing(Vec<&'static str>),
+    Broken(String),
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    // Explicit cache path to avoid environment issues
+    Ok(crate::utils::paths::get_air_data_dir()?.join("cache"))
+}
+
+fn repo() -> Repo {
+    Repo::new(EMBEDDING_MODEL_ID.to_string(), RepoType::Model)
+}
+
+fn api_repo() -> Result<ApiRepo> {
+    let api = ApiBuilder::new()
+        .with_cache_dir(cache_dir()?)
+        .build()
+        .map_err(|e| anyhow::anyhow!("Failed to init HF API: {}", e))?;
+    Ok(api.repo(repo()))
+}
+
+/// Downloads any missing embedding model files into the cache, then loads the model and embeds
+/// a sentence to prove the files are intact. Blocking; call from `spawn_blocking` in async code.
+pub fn download_embedding_model() -> Result<PathBuf> {
+    let repo = api_repo()?;
+    let mut dir = PathBuf::new();
+    for file in EMBEDDING_FILES {
+        let path = repo.get(file).map_err(|e| anyhow::anyhow!("Failed to download {}: {}", file, e))?;
+        dir = path.parent().map(PathBuf::from).unwrap_or_default();
+    }
+    verify_embedding_model()?;
+    Ok(dir)
+}
+
+fn verify_embedding_model() -> Result<()> {
+    let embedding = EmbeddingModel::new()?.embed("The quick brown fox")?;
+    if embedding.len() != EMBEDDING_DIMENSIONS || embedding.iter().any(|v| !v.is_finite()) {
+        return Err(anyhow::anyhow!("Embedding model produced an invalid vector ({} dimensions)", embedding.len()));
+    }
+    Ok(())
+}
+
+/// Checks the cached embedding model without touching the network.
+pub fn embedding_model_status() -> EmbeddingModelStatus {
+    let cache = match cache_dir() {
+        Ok(dir) => Cache::new(dir).repo(repo()),
+        Err(e) => return EmbeddingModelStatus::Broken(e.to_string()),
+    };
+    let found: Vec<(&'static str, Option<PathBuf>)> = EMBEDDING_FILES.iter().map(|f| (*f, cache.get(f))).collect();
+    let missing: Vec<&'static str> = found.iter().filter(|(_, p)| p.is_none()).map(|(f, _)| *f).collect();
+    if !missing.is_empty() {
+        return EmbeddingModelStatus::Missing(missing);
+    }
+
+    if let Err(e) = verify_embedding_model() {
+        return EmbeddingModelStatus::Broken(e.to_string());
+    }
+    let paths: Vec<PathBuf> = found.into_iter().filter_map(|(_, p)| p).collect();
+    EmbeddingModelStatus::Ready {
+        dir: paths[0].parent().map(PathBuf::from).unwrap_or_default(),
+        size_bytes: paths.iter().filter_map(|p| std::fs::metadata(p).ok()).map(|m| m.len()).sum(),
+    }
+}
+
 pub struct EmbeddingModel {
     model: BertModel,
     tokenizer: Tokenizer,
@@ -15,19 +89,7 @@ impl EmbeddingModel {
     pub fn new() -> Result<Self> {
         let device = Device::Cpu;
 
-        // Use all-MiniLM-L6-v2 for efficient local embeddings
-        let model_id = "sentence-transformers/all-MiniLM-L6-v2";
-        let _revision = "refs/pr/21";
-
-        // Set explicit cache path to avoid environment issues
-        let cache_path = crate::utils::paths::get_air_data_dir()?.join("cache");
-
-        let api = ApiBuilder::new()
-            .with_cache_dir(cache_path)
-            .build()
-            .map_err(|e| anyhow::anyhow!("Failed to init HF API: {}", e))?;
-
-        let repo = api.repo(Repo::new(model_id.to_string(), RepoType::Model));
+        let repo = api_repo()?;
 
         let config_filename = repo.get("config.json").map_err(|e| anyhow::anyhow!("Failed to get config: {}", e))?;
         let tokenizer_filename = repo.get("tokenizer.json").map_err(|e| anyhow::anyhow!("Failed to get tokenizer: {}", e))?;
@@ -0,0 +1,209 @@
+//! Document extraction for the knowledge base: PDF, DOCX, HTML, markdown and plain text files
+//! are turned into text before chunking. PDFs keep their pages apart so chunks can cite a page;
+//! DOCX and HTML headings become markdown headings so chunks can cite their section.
+
+use crate::config::ChunkStrategy;
+use crate::rag::chunking;
+use crate::tools::filesystem::decode_text;
+use crate::tools::readability;
+use anyhow::{Context, Result, anyhow};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentFormat {
+    Pdf,
+    Docx,
+    Html,
+    Markdown,
+    Text,
+}
+
+impl DocumentFormat {
+    /// By file extension; anything unrecognised is read as text.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+        match extension.as_str() {
+            "pdf" => DocumentFormat::Pdf,
+            "docx" => DocumentFormat::Docx,
+            "html" | "htm" | "xhtml" => DocumentFormat::Html,
+            "md" | "markdown" => DocumentFormat::Markdown,
+            _ => DocumentFormat::Text,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentFormat::Pdf => "pdf",
+            DocumentFormat::Docx => "docx",
+            DocumentFormat::Html => "html",
+            DocumentFormat::Markdown => "markdown",
+            DocumentFormat::Text => "text",
+        }
+    }
+
+    /// The strategy for text extracted from `path` in this format: `configured`, unless that is
+    /// `auto`, which splits PDF pages fixed-size and the formats with headings by section.
+    pub fn chunking(&self, path: &str, configured: ChunkStrategy) -> ChunkStrategy {
+        match (configured, self) {
+            (ChunkStrategy::Auto, DocumentFormat::Pdf) => ChunkStrategy::Fixed,
+            (ChunkStrategy::Auto, DocumentFormat::Docx | DocumentFormat::Html | DocumentFormat::Markdown) => ChunkStrategy::Markdown,
+            (configured, _) => chunking::strategy_for(path, configured),
+        }
+    }
+}
+
+/// A stretch of a document's text: one page of a PDF, or the whole of any other format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// 1-based page number, for formats that have pages.
+    pub number: Option<usize>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedDocument {
+    pub format: DocumentFormat,
+    pub title: Option<String>,
+    pub pages: Vec<Page>,
+}
+
+/// Reads and extracts `path`; parsing runs off the async runtime since large PDFs take a while.
+pub async fn load(path: &Path) -> Result<LoadedDocument> {
+    let format = DocumentFormat::from_path(path);
+    let bytes = tokio::fs::read(path).await
+        .with_context(|| format!("Couldn't read {}", path.display()))?;
+    tokio::task::spawn_blocking(move || parse(format, &bytes)).await?
+}
+
+pub fn parse(format: DocumentFormat, bytes: &[u8]) -> Result<LoadedDocument> {
+    let whole = |title: Option<String>, text: String| LoadedDocument {
+        format,
+        title,
+        pages: vec![Page { number: None, text }],
+    };
+    match format {
+        DocumentFormat::Pdf => {
+            let pages: Vec<Page> = pdf_extract::extract_text_from_mem_by_pages(bytes)
+                .map_err(|e| anyhow!("Couldn't extract text from the PDF: {}", e))?
+                .into_iter()
+                .enumerate()
+                .filter(|(_, text)| !text.trim().is_empty())
+                .map(|(i, text)| Page { number: Some(i + 1), text })
+                .collect();
+            if pages.is_empty() {
+                return Err(anyhow!("The PDF has no text layer (is it a scan?)"));
+            }
+            Ok(LoadedDocument { format, title: None, pages })
+        }
+        DocumentFormat::Docx => {
+            let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).context("Not a valid DOCX file")?;
+            let mut xml = String::new();
+            archive.by_name("word/document.xml")
+                .context("Not a valid DOCX file: word/document.xml is missing")?
+                .read_to_string(&mut xml)?;
+            Ok(whole(None, docx_text(&xml)?))
+        }
+        DocumentFormat::Html => {
+            let (html, _) = decode_text(bytes).ok_or_else(|| anyhow!("The HTML file isn't text"))?;
+            let page = readability::extract(&html, None);
+            Ok(whole(page.title, page.content))
+        }
+        DocumentFormat::Markdown | DocumentFormat::Text => {
+            let (text, _) = decode_text(bytes).ok_or_else(|| anyhow!("Binary files can't be indexed; supported documents are PDF, DOCX, HTML, markdown and text"))?;
+            Ok(whole(None, text))
+        }
+    }
+}
+
+/// The paragraphs of a DOCX `word/document.xml`, separated by blank lines, with heading styles
+/// as markdown headings and list items as `- ` lines.
+fn docx_text(xml: &str) -> Result<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut paragraphs = Vec::new();
+    let mut text = String::new();
+    let mut style: Option<String> = None;
+    let mut list_item = false;
+    let mut in_text = false;
+    let mut in_properties = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"w:t" => in_text = true,
+            Event::Start(e) | Event::Empty(e) => match e.name().as_ref() {
+                b"w:p" => {
+                    text.clear();
+                    style = None;
+                    list_item = false;
+                }
+                b"w:pPr" => in_properties = true,
+                b"w:pStyle" => {
+                    if let Some(value) = e.try_get_attribute("w:val")? {
+                        style = Some(value.unescape_value()?.into_owned());
+                    }
+                }
+                b"w:numPr" => list_item = true,
+                // Tab stops in the paragraph properties aren't text
+                b"w:tab" if !in_properties => text.push('\t'),
+                b"w:br" | b"w:cr" => text.push('\n'),
+                _ => {}
+            },
+            Event::Text(t) if in_text => text.push_str(&t.unescape()?),
+            Event::End(e) => match e.name().as_ref() {
+                b"w:t" => in_text = false,
+                b"w:pPr" => in_properties = false,
+                b"w:p" => {
+                    let paragraph = text.trim();
+                    if !paragraph.is_empty() {
+                        paragraphs.push(match style.as_deref().and_then(heading_level) {
+                            Some(level) => format!("{} {}", "#".repeat(level), paragraph.replace('\n', " ")),
+                            None if list_item => format!("- {}", paragraph),
+                            None => paragraph.to_string(),
+                        });
+                    }
+                    text.clear();
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(paragraphs.join("\n\n"))
+}
+
+/// `Heading2` (or `heading 2`) is level 2; `Title` is level 1.
+fn heading_level(style: &str) -> Option<usize> {
+    let style = style.to_lowercase().replace(' ', "");
+    if style == "title" {
+        return Some(1);
+    }
+    style.strip_prefix("heading")?.parse().ok().filter(|level| (1..=6).contains(level))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docx_text() {
+        let xml = r#"<w:document><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Heading1"/><w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr><w:r><w:t>Setup</w:t></w:r></w:p>
+            <w:p><w:r><w:t xml:space="preserve">Install the </w:t></w:r><w:r><w:t>tool &amp; run it.</w:t></w:r></w:p>
+            <w:p><w:pPr><w:numPr><w:ilvl w:val="0"/></w:numPr></w:pPr><w:r><w:t>First step</w:t></w:r></w:p>
+            <w:p/>
+        </w:body></w:document>"#;
+        assert_eq!(docx_text(xml).unwrap(), "# Setup\n\nInstall the tool & run it.\n\n- First step");
+    }
+
+    #[test]
+    fn test_format_chunking() {
+        let format = DocumentFormat::from_path(Path::new("manual.PDF"));
+        assert_eq!(format, DocumentFormat::Pdf);
+        assert_eq!(format.chunking("manual.PDF", ChunkStrategy::Auto), ChunkStrategy::Fixed);
+        assert_eq!(DocumentFormat::Docx.chunking("notes.docx", ChunkStrategy::Auto), ChunkStrategy::Markdown);
+        assert_eq!(DocumentFormat::Text.chunking("main.rs", ChunkStrategy::Auto), ChunkStrategy::Code);
+        assert_eq!(DocumentFormat::Html.chunking("page.html", ChunkStrategy::Fixed), ChunkStrategy::Fixed);
+    }
+}
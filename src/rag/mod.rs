@@ -1,3 +1,6 @@
+pub mod chunking;
+pub mod code;
 pub mod embeddings;
 pub mod store;
 pub mod langchain_embedding;
+pub mod loaders;
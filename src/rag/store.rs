@@ -1,8 +1,11 @@
 use anyhow::Result;
 use langchain_rust::schemas::Document;
 use langchain_rust::embedding::Embedder;
+use crate::config::KnowledgeConfig;
+use crate::rag::chunking;
+use crate::rag::loaders::LoadedDocument;
 use crate::rag::langchain_embedding::CandleEmbedder;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
 use std::collections::HashMap;
@@ -25,13 +28,23 @@ impl KnowledgeStore<CandleEmbedder> {
         let embedder = CandleEmbedder::new()?;
         Self::new_with_embedder(app_data, embedder).await
     }
+
+    /// A store kept in its own file, such as a repository's code index.
+    pub async fn open(path: &Path) -> Result<Self> {
+        let embedder = CandleEmbedder::new()?;
+        Self::open_with_embedder(path, embedder).await
+    }
 }
 
 impl<E: Embedder + Send + Sync + 'static> KnowledgeStore<E> {
     pub async fn new_with_embedder(app_data: &str, embedder: E) -> Result<Self> {
         // Use .gz extension for compressed storage
         let db_path = std::path::Path::new(app_data).join("air").join("knowledge.json.gz");
+        Self::open_with_embedder(&db_path, embedder).await
+    }
 
+    pub async fn open_with_embedder(db_path: &Path, embedder: E) -> Result<Self> {
+        let db_path = db_path.to_path_buf();
         if let Some(parent) = db_path.parent() {
             if !parent.exists() {
                 fs::create_dir_all(parent).await?;
@@ -79,8 +92,22 @@ impl<E: Embedder + Send + Sync + 'static> KnowledgeStore<E> {
                             }
                         }
 
+                        // Documents saved before ids existed get one now so get_document can find them
+                        let mut assigned_ids = false;
+                        for doc in new_docs.iter_mut() {
+                            if !doc.metadata.contains_key("id") {
+                                doc.metadata.insert("id".to_string(), serde_json::json!(uuid::Uuid::new_v4().to_string()));
+                                assigned_ids = true;
+                            }
+                        }
+
                         *docs = new_docs;
                         *embs = new_embeddings;
+                        drop(docs);
+                        drop(embs);
+                        if assigned_ids {
+                            store.save().await?;
+                        }
                     }
                 }
             }
@@ -97,6 +124,9 @@ impl<E: Embedder + Send + Sync + 'static> KnowledgeStore<E> {
             }
         }
 
+        meta_map.entry("id".to_string())
+            .or_insert_with(|| serde_json::json!(uuid::Uuid::new_v4().to_string()));
+
         let doc = Document::new(content.to_string()).with_metadata(meta_map);
 
         let embedding = self.embedder.embed_query(&content).await.map_err(|e| anyhow::anyhow!("Embedding failed: {:?}", e))?;
@@ -112,13 +142,109 @@ impl<E: Embedder + Send + Sync + 'static> KnowledgeStore<E> {
         Ok(())
     }
 
+    /// Adds several documents and saves once, for bulk indexing.
+    pub async fn add_texts(&self, items: Vec<(String, serde_json::Value)>) -> Result<()> {
+        let mut new_docs = Vec::with_capacity(items.len());
+        let mut new_embeddings = Vec::with_capacity(items.len());
+        for (content, metadata) in items {
+            let mut meta_map: HashMap<String, serde_json::Value> = match metadata {
+                serde_json::Value::Object(map) => map.into_iter().collect(),
+                _ => HashMap::new(),
+            };
+            meta_map.entry("id".to_string())
+                .or_insert_with(|| serde_json::json!(uuid::Uuid::new_v4().to_string()));
+            new_embeddings.push(self.embed(&content).await?);
+            new_docs.push(Document::new(content).with_metadata(meta_map));
+        }
+
+        {
+            let mut docs = self.documents.lock().await;
+            let mut embs = self.embeddings.lock().await;
+            docs.extend(new_docs);
+            embs.extend(new_embeddings);
+        }
+        self.save().await
+    }
+
+    /// Splits `document` page by page with `config`'s chunking strategy and indexes the chunks in
+    /// place of any earlier ones from `source`. Each chunk's metadata is `extra` plus its source,
+    /// format, index, page, line range and section (see `chunking::citation`). Returns how many
+    /// chunks were indexed.
+    pub async fn add_document(&self, source: &str, document: &LoadedDocument, config: &KnowledgeConfig, extra: serde_json::Value) -> Result<usize> {
+        let strategy = document.format.chunking(source, config.chunking);
+        let mut items: Vec<(String, serde_json::Value)> = Vec::new();
+        for page in &document.pages {
+            for chunk in chunking::chunk_as(strategy, source, &page.text, config) {
+                let mut metadata = chunking::metadata(source, items.len(), &chunk, &extra);
+                metadata["format"] = serde_json::json!(document.format.as_str());
+                if let Some(number) = page.number {
+                    metadata["page"] = serde_json::json!(number);
+                }
+                if let Some(title) = &document.title {
+                    metadata["title"] = serde_json::json!(title);
+                }
+                items.push((chunk.text, metadata));
+            }
+        }
+        let count = items.len();
+        self.remove_source(source).await?;
+        self.add_texts(items).await?;
+        Ok(count)
+    }
+
+    /// Drops every chunk indexed from `source`. Returns how many were removed.
+    pub async fn remove_source(&self, source: &str) -> Result<usize> {
+        let removed = {
+            let mut docs = self.documents.lock().await;
+            let mut embs = self.embeddings.lock().await;
+            let before = docs.len();
+            let keep: Vec<bool> = docs.iter()
+                .map(|doc| doc.metadata.get("source").and_then(|v| v.as_str()) != Some(source))
+                .collect();
+            let mut flags = keep.iter();
+            docs.retain(|_| *flags.next().unwrap());
+            let mut flags = keep.iter();
+            embs.retain(|_| *flags.next().unwrap());
+            before - docs.len()
+        };
+        if removed > 0 {
+            self.save().await?;
+        }
+        Ok(removed)
+    }
+
+    /// Each indexed source with the `key` metadata of its first chunk (null when absent).
+    pub async fn sources(&self, key: &str) -> HashMap<String, serde_json::Value> {
+        let docs = self.documents.lock().await;
+        let mut sources = HashMap::new();
+        for doc in docs.iter() {
+            if let Some(source) = doc.metadata.get("source").and_then(|v| v.as_str()) {
+                sources.entry(source.to_string())
+                    .or_insert_with(|| doc.metadata.get(key).cloned().unwrap_or(serde_json::Value::Null));
+            }
+        }
+        sources
+    }
+
+    /// Embeds `text` with the store's model, for callers that keep their own vectors.
+    pub async fn embed(&self, text: &str) -> Result<Vec<f64>> {
+        self.embedder.embed_query(text).await.map_err(|e| anyhow::anyhow!("Embedding failed: {:?}", e))
+    }
+
     pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<(Document, f64)>> {
+        self.search_filtered(query, limit, None).await
+    }
+
+    /// Like `search`, but only considers documents whose metadata matches every key in `filter`
+    /// (strings compare case-insensitively).
+    pub async fn search_filtered(&self, query: &str, limit: usize, filter: Option<&serde_json::Map<String, serde_json::Value>>) -> Result<Vec<(Document, f64)>> {
         let query_embedding = self.embedder.embed_query(query).await.map_err(|e| anyhow::anyhow!("Embedding failed: {:?}", e))?;
 
         let docs = self.documents.lock().await;
         let embs = self.embeddings.lock().await;
 
         let mut scores: Vec<(usize, f64)> = embs.iter().enumerate()
+            .filter(|(i, _)| filter.map_or(true, |f| metadata_matches(&docs[*i].metadata, f)))
             .map(|(i, emb)| {
                 let score = cosine_similarity(&query_embedding, emb);
                 (i, score)
@@ -135,6 +261,22 @@ impl<E: Embedder + Send + Sync + 'static> KnowledgeStore<E> {
         Ok(results)
     }
 
+    pub async fn get_document(&self, id: &str) -> Option<Document> {
+        let docs = self.documents.lock().await;
+        docs.iter()
+            .find(|doc| doc.metadata.get("id").and_then(|v| v.as_str()) == Some(id))
+            .cloned()
+    }
+
+    /// All chunks indexed from `source`, in the order they were added.
+    pub async fn documents_from_source(&self, source: &str) -> Vec<Document> {
+        let docs = self.documents.lock().await;
+        docs.iter()
+            .filter(|doc| doc.metadata.get("source").and_then(|v| v.as_str()) == Some(source))
+            .cloned()
+            .collect()
+    }
+
     async fn save(&self) -> Result<()> {
         let docs = self.documents.lock().await;
         let embs = self.embeddings.lock().await; // Lock embeddings too
@@ -156,7 +298,15 @@ impl<E: Embedder + Send + Sync + 'static> KnowledgeStore<E> {
     }
 }
 
-fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+fn metadata_matches(metadata: &HashMap<String, serde_json::Value>, filter: &serde_json::Map<String, serde_json::Value>) -> bool {
+    filter.iter().all(|(key, expected)| match (metadata.get(key), expected) {
+        (Some(serde_json::Value::String(actual)), serde_json::Value::String(expected)) => actual.eq_ignore_ascii_case(expected),
+        (Some(actual), expected) => actual == expected,
+        (None, _) => false,
+    })
+}
+
+pub(crate) fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
     let dot_product: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
     let norm_a: f64 = a.iter().map(|x| x * x).sum::<f64>().sqrt();
     let norm_b: f64 = b.iter().map(|x| x * x).sum::<f64>().sqrt();
@@ -0,0 +1,317 @@
+//! `air review <pr-url|range>`: reviews a GitHub pull request or a local commit range file by
+//! file. Each file's hunks are numbered with their new line numbers, sent to the model in chunks,
+//! and the findings are collected into one report, rendered as markdown or JSON.
+
+use crate::agent::{AIAgent, QueryMode, Validator};
+use crate::config::GitHubConfig;
+use crate::tools::{GitHubTool, GitTool};
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Diff text per review prompt; files with more are reviewed in several chunks.
+const MAX_CHUNK_CHARS: usize = 12_000;
+/// Generated files not worth a review.
+const SKIPPED_FILES: &[&str] = &["Cargo.lock", "package-lock.json", "yarn.lock", "pnpm-lock.yaml", "poetry.lock", "go.sum"];
+
+/// What to review.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    PullRequest { owner: String, repo: String, number: u64 },
+    /// A `git diff` range such as `main...HEAD` or `HEAD~3..HEAD`.
+    Range(String),
+}
+
+impl Target {
+    /// A pull request URL, a range, or a single ref meaning everything since it branched off.
+    pub fn parse(spec: &str) -> Self {
+        let pr = Regex::new(r"github\.com/([^/\s]+)/([^/\s]+)/pull/(\d+)").ok().and_then(|re| re.captures(spec));
+        if let Some(c) = pr {
+            if let Ok(number) = c[3].parse() {
+                return Target::PullRequest { owner: c[1].to_string(), repo: c[2].to_string(), number };
+            }
+        }
+        if spec.contains("..") {
+            Target::Range(spec.to_string())
+        } else {
+            Target::Range(format!("{}...HEAD", spec))
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Target::PullRequest { owner, repo, number } => format!("{}/{}#{}", owner, repo, number),
+            Target::Range(range) => range.clone(),
+        }
+    }
+}
+
+/// The diff under review, with the PR description when there is one.
+#[derive(Debug, Clone)]
+pub struct ReviewInput {
+    pub description: Option<String>,
+    pub diff: String,
+}
+
+/// Fetches the diff: from the GitHub API through the GitHub tool (its token covers private
+/// repositories) or from `git diff` in the current repository.
+pub async fn fetch(target: &Target, github: &GitHubConfig) -> Result<ReviewInput> {
+    match target {
+        Target::Range(range) => {
+            let diff = GitTool::new().git(&["diff", "--no-color", "--no-ext-diff", range]).await?;
+            Ok(ReviewInput { description: None, diff })
+        }
+        Target::PullRequest { owner, repo, number } => {
+            let github = GitHubTool::with_config(github);
+            let repo = format!("{}/{}", owner, repo);
+            let pr = github.pull_request(&repo, *number).await?;
+            let diff = github.pull_request_diff(&repo, *number).await?;
+            let description = format!(
+                "{}\n\n{}",
+                pr["title"].as_str().unwrap_or_default(),
+                pr["body"].as_str().unwrap_or_default()
+            );
+            Ok(ReviewInput { description: Some(description.trim().to_string()), diff })
+        }
+    }
+}
+
+/// One file's part of a unified diff.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    pub path: String,
+    pub hunks: Vec<String>,
+}
+
+/// Splits a unified diff into files and their hunks. Binary files have no hunks.
+pub fn parse_diff(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            // `a/old b/new`; the +++ line refines it unless the file was deleted
+            let path = header.rsplit_once(" b/").map(|(_, p)| p).unwrap_or(header);
+            files.push(FileDiff { path: path.to_string(), hunks: Vec::new() });
+            continue;
+        }
+        let Some(file) = files.last_mut() else { continue };
+        if let Some(path) = line.strip_prefix("+++ b/") {
+            file.path = path.to_string();
+        } else if line.starts_with("@@") {
+            file.hunks.push(format!("{}\n", line));
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.push_str(line);
+            hunk.push('\n');
+        }
+    }
+    files
+}
+
+/// The hunk with each kept or added line prefixed by its line number in the new file, so
+/// findings can point at it.
+pub fn annotate(hunk: &str) -> String {
+    let mut lines = hunk.lines();
+    let Some(header) = lines.next() else { return String::new() };
+    let mut line = Regex::new(r"\+(\d+)").ok()
+        .and_then(|re| re.captures(header))
+        .and_then(|c| c[1].parse::<u64>().ok())
+        .unwrap_or(1);
+    let mut out = format!("{}\n", header);
+    for text in lines {
+        if text.starts_with('-') || text.starts_with('\\') {
+            out.push_str(&format!("{:>6} {}\n", "", text));
+        } else {
+            out.push_str(&format!("{:>6} {}\n", line, text));
+            line += 1;
+        }
+    }
+    out
+}
+
+/// The file's annotated hunks, grouped into chunks of at most `MAX_CHUNK_CHARS` (a larger
+/// hunk is a chunk of its own).
+fn chunks(file: &FileDiff) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    for hunk in file.hunks.iter().map(|h| annotate(h)) {
+        match chunks.last_mut() {
+            Some(chunk) if chunk.len() + hunk.len() <= MAX_CHUNK_CHARS => chunk.push_str(&hunk),
+            _ => chunks.push(hunk),
+        }
+    }
+    chunks
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Critical,
+    Major,
+    Minor,
+    Nit,
+}
+
+impl Severity {
+    fn icon(self) -> &'static str {
+        match self {
+            Severity::Critical => "🔴",
+            Severity::Major => "🟠",
+            Severity::Minor => "🟡",
+            Severity::Nit => "⚪",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    pub severity: Severity,
+    #[serde(default)]
+    pub file: String,
+    #[serde(default)]
+    pub line: Option<u64>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub target: String,
+    pub files_reviewed: usize,
+    /// Files left out: lockfiles, binaries, or chunks the model's answer couldn't be read for.
+    pub skipped: Vec<String>,
+    pub findings: Vec<Finding>,
+}
+
+fn build_prompt(path: &str, chunk: &str, description: Option<&str>) -> String {
+    let mut prompt = String::from("You are reviewing a code change. ");
+    if let Some(description) = description.filter(|d| !d.is_empty()) {
+        prompt.push_str(&format!("The author describes it as:\n{}\n\n", description));
+    }
+    prompt.push_str(&format!(
+        "Here is the diff of {}; each kept or added line starts with its line number in the new file:\n```diff\n{}```\n\n\
+         Report bugs, security problems, missing error handling, performance issues and unclear code in the added lines. \
+         Don't comment on style a formatter would fix, and don't praise. \
+         Answer with only a JSON object: {{\"findings\": [{{\"severity\": \"critical|major|minor|nit\", \"line\": 12, \
+         \"message\": \"what is wrong and why\", \"suggestion\": \"the fix, as code or a sentence\"}}]}}. \
+         Use an empty list if there is nothing worth raising.",
+        path, chunk
+    ));
+    prompt
+}
+
+async fn review_chunk(agent: &AIAgent, mode: &QueryMode, path: &str, chunk: &str, description: Option<&str>) -> Result<Vec<Finding>> {
+    let schema = json!({
+        "type": "object",
+        "required": ["findings"],
+        "properties": {
+            "findings": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["severity", "message"],
+                    "properties": {
+                        "severity": {"type": "string", "enum": ["critical", "major", "minor", "nit"]},
+                        "line": {"type": "integer"},
+                        "message": {"type": "string"},
+                        "suggestion": {"type": "string"}
+                    }
+                }
+            }
+        }
+    });
+    let response = agent.query_validated(&build_prompt(path, chunk, description), mode, false, &[Validator::JsonSchema { schema }], 1).await?;
+    let value = crate::agent::validator::parse_json(&response.content).map_err(|e| anyhow!(e))?;
+    let mut findings: Vec<Finding> = serde_json::from_value(value["findings"].clone())?;
+    for finding in &mut findings {
+        finding.file = path.to_string();
+    }
+    Ok(findings)
+}
+
+/// Reviews every file in `input`, reporting progress on stderr.
+pub async fn review(agent: &AIAgent, mode: &QueryMode, target: &Target, input: &ReviewInput) -> Result<Report> {
+    let files = parse_diff(&input.diff);
+    if files.is_empty() {
+        return Err(anyhow!("{} has no changes to review", target.label()));
+    }
+    let mut report = Report { target: target.label(), files_reviewed: 0, skipped: Vec::new(), findings: Vec::new() };
+    for (i, file) in files.iter().enumerate() {
+        let name = file.path.rsplit('/').next().unwrap_or(&file.path);
+        if file.hunks.is_empty() || SKIPPED_FILES.contains(&name) {
+            report.skipped.push(file.path.clone());
+            continue;
+        }
+        eprintln!("🔍 [{}/{}] {}", i + 1, files.len(), file.path);
+        let mut reviewed = false;
+        for chunk in chunks(file) {
+            match review_chunk(agent, mode, &file.path, &chunk, input.description.as_deref()).await {
+                Ok(findings) => {
+                    reviewed = true;
+                    report.findings.extend(findings);
+                }
+                Err(e) => eprintln!("⚠️  Couldn't review part of {}: {}", file.path, e),
+            }
+        }
+        if reviewed {
+            report.files_reviewed += 1;
+        } else {
+            report.skipped.push(file.path.clone());
+        }
+    }
+    report.findings.sort_by(|a, b| a.severity.cmp(&b.severity).then(a.file.cmp(&b.file)).then(a.line.cmp(&b.line)));
+    Ok(report)
+}
+
+pub fn render_markdown(report: &Report) -> String {
+    let mut out = format!("# Review of {}\n\n", report.target);
+    out.push_str(&format!("{} file(s) reviewed, {} finding(s).\n", report.files_reviewed, report.findings.len()));
+    if !report.skipped.is_empty() {
+        out.push_str(&format!("Skipped: {}\n", report.skipped.join(", ")));
+    }
+    for finding in &report.findings {
+        let location = match finding.line {
+            Some(line) => format!("{}:{}", finding.file, line),
+            None => finding.file.clone(),
+        };
+        out.push_str(&format!("\n### {} {:?} · `{}`\n\n{}\n", finding.severity.icon(), finding.severity, location, finding.message.trim()));
+        if let Some(suggestion) = finding.suggestion.as_deref().filter(|s| !s.trim().is_empty()) {
+            out.push_str(&format!("\n**Suggestion:** {}\n", suggestion.trim()));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs\nindex 1..2 100644\n--- a/src/lib.rs\n+++ b/src/lib.rs\n\
+                        @@ -10,3 +10,4 @@ fn main() {\n     let a = 1;\n-    let b = 2;\n+    let b = 3;\n+    let c = 4;\n     a + b\n\
+                        diff --git a/logo.png b/logo.png\nBinary files a/logo.png and b/logo.png differ\n";
+
+    #[test]
+    fn test_parse_and_annotate() {
+        let files = parse_diff(DIFF);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/lib.rs");
+        assert_eq!(files[0].hunks.len(), 1);
+        assert!(files[1].hunks.is_empty());
+
+        let annotated = annotate(&files[0].hunks[0]);
+        let lines: Vec<&str> = annotated.lines().collect();
+        assert_eq!(lines[1], "    10      let a = 1;");
+        assert_eq!(lines[2], "       -    let b = 2;");
+        assert_eq!(lines[3], "    11 +    let b = 3;");
+        assert_eq!(lines[5], "    13      a + b");
+    }
+
+    #[test]
+    fn test_target_parse() {
+        assert_eq!(
+            Target::parse("https://github.com/chintu4/air/pull/42/files"),
+            Target::PullRequest { owner: "chintu4".into(), repo: "air".into(), number: 42 }
+        );
+        assert_eq!(Target::parse("HEAD~3..HEAD"), Target::Range("HEAD~3..HEAD".into()));
+        assert_eq!(Target::parse("main"), Target::Range("main...HEAD".into()));
+    }
+}
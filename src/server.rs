@@ -0,0 +1,410 @@
+//! HTTP API over a running [`AIAgent`] (`air serve`).
+//!
+//! Endpoints: `GET /health`, `GET /tools`, `GET /metrics`, `POST /query`, `/sessions` for multi-turn
+//! conversations, `/snapshot` to export or import the agent's session state and `/jobs` to start and
+//! follow background jobs (see [`crate::jobs`]). Every endpoint except `/health` requires the configured API key as
+//! `Authorization: Bearer <key>` or `X-API-Key: <key>`. Jobs started here run without tools.
+
+use crate::agent::{AIAgent, AgentSnapshot, QueryMode, Validator};
+use crate::config::ServerConfig;
+use crate::jobs::Job;
+use crate::utils::approval;
+use anyhow::{Result, anyhow};
+use axum::extract::{FromRef, Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+/// Sessions kept in memory; creating one more drops the oldest.
+const MAX_SESSIONS: usize = 1000;
+
+#[derive(Clone)]
+struct AppState {
+    agent: AIAgent,
+    api_key: Option<Arc<str>>,
+    limiter: Arc<Semaphore>,
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+    max_session_turns: usize,
+}
+
+impl FromRef<AppState> for Option<Arc<str>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.api_key.clone()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Turn {
+    prompt: String,
+    response: String,
+    model_used: String,
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Session {
+    id: String,
+    created_at: DateTime<Utc>,
+    turns: Vec<Turn>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self { id: uuid::Uuid::new_v4().to_string(), created_at: Utc::now(), turns: Vec::new() }
+    }
+
+    fn summary(&self) -> Value {
+        json!({
+            "id": self.id,
+            "created_at": self.created_at,
+            "turns": self.turns.len(),
+            "last_prompt": self.turns.last().map(|t| t.prompt.as_str()),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryBody {
+    prompt: String,
+    /// auto, local_only, cloud_only or pure_local; defaults to the configured default_mode.
+    mode: Option<QueryMode>,
+    /// Continue this session (earlier turns are given to the model as context).
+    session_id: Option<String>,
+    /// Let the model call tools (the ReAct loop). Defaults to true; ignored (always off) when the
+    /// server has no API key.
+    #[serde(default = "default_true")]
+    use_tools: bool,
+    /// Checks the answer must pass, e.g. `[{"type": "json_schema", "schema": {...}}]`; failing
+    /// answers are sent back for repair up to `max_repairs` times.
+    #[serde(default)]
+    validate: Vec<Validator>,
+    #[serde(default = "default_max_repairs")]
+    max_repairs: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct StartJobBody {
+    /// `air` arguments, starting with the command: `["batch", "prompts.txt"]`.
+    args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogsQuery {
+    #[serde(default = "default_log_lines")]
+    lines: usize,
+}
+
+fn default_log_lines() -> usize {
+    200
+}
+
+fn default_max_repairs() -> u32 {
+    2
+}
+
+fn default_true() -> bool {
+    true
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Serves the API until the process is stopped. Refuses to listen beyond loopback without an API key.
+pub async fn serve(agent: AIAgent, config: &ServerConfig) -> Result<()> {
+    // Nobody answers prompts on the server's terminal; tools needing approval are declined
+    approval::disable_prompts();
+    let addr: SocketAddr = format!("{}:{}", config.bind, config.port).parse()
+        .map_err(|e| anyhow!("Invalid server address {}:{}: {}", config.bind, config.port, e))?;
+    let api_key = config.api_key.as_deref().filter(|k| !k.is_empty());
+    if api_key.is_none() && !addr.ip().is_loopback() {
+        return Err(anyhow!(
+            "Refusing to listen on {} without an API key: set server.api_key (or AIR_SERVER__API_KEY)",
+            addr
+        ));
+    }
+    if api_key.is_none() {
+        warn!("No server.api_key set: only local Host headers are accepted, tools are disabled for /query and jobs can't be started");
+    }
+
+    let state = AppState {
+        agent,
+        api_key: api_key.map(Arc::from),
+        limiter: Arc::new(Semaphore::new(config.max_concurrent_requests.max(1))),
+        sessions: Arc::new(Mutex::new(HashMap::new())),
+        max_session_turns: config.max_session_turns,
+    };
+
+    let protected = Router::new()
+        .route("/query", post(query))
+        .route("/tools", get(tools))
+        .route("/metrics", get(metrics))
+        .route("/sessions", get(list_sessions).post(create_session))
+        .route("/sessions/:id", get(get_session).delete(delete_session))
+        .route("/snapshot", get(get_snapshot).put(put_snapshot))
+        .route("/jobs", get(list_jobs).post(start_job))
+        .route("/jobs/:id", get(get_job))
+        .route("/jobs/:id/cancel", post(cancel_job))
+        .route("/jobs/:id/logs", get(job_logs))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_api_key));
+
+    let app = Router::new()
+        .route("/health", get(health))
+        .merge(protected)
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("🌐 AIR API listening on http://{}", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn require_api_key(State(state): State<AppState>, headers: HeaderMap, request: Request, next: Next) -> Response {
+    let Some(expected) = &state.api_key else {
+        // Without a key anything on this machine may call in, but a web page must not: DNS
+        // rebinding reaches loopback with the attacker's host name in Host
+        if !is_local_host(headers.get("host").and_then(|v| v.to_str().ok())) {
+            return ApiError(StatusCode::FORBIDDEN, "Requests must be addressed to localhost unless server.api_key is set".to_string()).into_response();
+        }
+        return next.run(request).await;
+    };
+    let provided = headers.get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()));
+
+    match provided {
+        Some(key) if constant_time_eq(key.as_bytes(), expected.as_bytes()) => next.run(request).await,
+        _ => ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid API key".to_string()).into_response(),
+    }
+}
+
+/// Whether a Host header names this machine (`localhost`, `127.0.0.1` or `[::1]`, any port).
+fn is_local_host(host: Option<&str>) -> bool {
+    let Some(host) = host else { return false };
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(""),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    matches!(name.to_ascii_lowercase().as_str(), "localhost" | "127.0.0.1" | "::1")
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn health(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+        "available_slots": state.limiter.available_permits(),
+    }))
+}
+
+async fn tools(State(state): State<AppState>) -> Json<Value> {
+    Json(state.agent.tool_definitions())
+}
+
+async fn metrics(State(state): State<AppState>) -> Json<Value> {
+    Json(json!(state.agent.metrics()))
+}
+
+async fn query(State(state): State<AppState>, Json(body): Json<QueryBody>) -> Result<Json<Value>, ApiError> {
+    if body.prompt.trim().is_empty() {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "prompt must not be empty".to_string()));
+    }
+    // Model calls are expensive; shed load rather than queue without bound
+    let _permit = state.limiter.clone().try_acquire_owned()
+        .map_err(|_| ApiError(StatusCode::TOO_MANY_REQUESTS, "Too many concurrent queries; retry shortly".to_string()))?;
+
+    let (session_id, history) = match &body.session_id {
+        Some(id) => {
+            let sessions = state.sessions.lock().unwrap();
+            let session = sessions.get(id)
+                .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("Unknown session '{}'", id)))?;
+            (Some(id.clone()), session.turns.clone())
+        }
+        None => (None, Vec::new()),
+    };
+
+    let prompt = with_history(&history, &body.prompt);
+    let mode = body.mode.unwrap_or_else(|| state.agent.config().default_mode.clone());
+    // Tools act with the server's permissions, so only clients holding the key get them
+    let use_tools = body.use_tools && state.api_key.is_some();
+    let result = if !body.validate.is_empty() {
+        state.agent.query_validated(&prompt, &mode, use_tools, &body.validate, body.max_repairs).await
+    } else if use_tools {
+        state.agent.query_with_tools_in_mode(&prompt, &mode).await
+    } else {
+        state.agent.query_in_mode(&prompt, &mode).await
+    };
+    let response = result.map_err(|e| ApiError(StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    if let Some(id) = &session_id {
+        let mut sessions = state.sessions.lock().unwrap();
+        if let Some(session) = sessions.get_mut(id) {
+            session.turns.push(Turn {
+                prompt: body.prompt.clone(),
+                response: response.content.clone(),
+                model_used: response.model_used.clone(),
+                timestamp: Utc::now(),
+            });
+            let excess = session.turns.len().saturating_sub(state.max_session_turns);
+            session.turns.drain(..excess);
+        }
+    }
+
+    Ok(Json(json!({
+        "session_id": session_id,
+        "content": response.content,
+        "model_used": response.model_used,
+        "tokens_used": response.tokens_used,
+        "response_time_ms": response.response_time_ms,
+    })))
+}
+
+/// Prefixes the prompt with earlier turns of the session.
+fn with_history(turns: &[Turn], prompt: &str) -> String {
+    if turns.is_empty() {
+        return prompt.to_string();
+    }
+    let mut out = String::from("Conversation so far:\n");
+    for turn in turns {
+        out.push_str(&format!("User: {}\nAssistant: {}\n", turn.prompt, turn.response));
+    }
+    out.push_str(&format!("\nUser: {}", prompt));
+    out
+}
+
+async fn list_sessions(State(state): State<AppState>) -> Json<Value> {
+    let sessions = state.sessions.lock().unwrap();
+    let mut list: Vec<&Session> = sessions.values().collect();
+    list.sort_by_key(|s| s.created_at);
+    Json(json!({ "sessions": list.iter().map(|s| s.summary()).collect::<Vec<_>>() }))
+}
+
+async fn create_session(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let session = Session::new();
+    let summary = session.summary();
+    let mut sessions = state.sessions.lock().unwrap();
+    if sessions.len() >= MAX_SESSIONS {
+        let oldest = sessions.values().min_by_key(|s| s.created_at).map(|s| s.id.clone());
+        if let Some(oldest) = oldest {
+            sessions.remove(&oldest);
+        }
+    }
+    sessions.insert(session.id.clone(), session);
+    (StatusCode::CREATED, Json(summary))
+}
+
+async fn get_session(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<Session>, ApiError> {
+    state.sessions.lock().unwrap().get(&id).cloned()
+        .map(Json)
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("Unknown session '{}'", id)))
+}
+
+async fn delete_session(State(state): State<AppState>, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    match state.sessions.lock().unwrap().remove(&id) {
+        Some(_) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError(StatusCode::NOT_FOUND, format!("Unknown session '{}'", id))),
+    }
+}
+
+/// The agent's session state, for moving it to another instance with `PUT /snapshot`.
+async fn get_snapshot(State(state): State<AppState>) -> Result<Json<AgentSnapshot>, ApiError> {
+    state.agent.snapshot().await
+        .map(Json)
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+async fn put_snapshot(State(state): State<AppState>, Json(snapshot): Json<AgentSnapshot>) -> Result<StatusCode, ApiError> {
+    state.agent.restore(snapshot).await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+async fn list_jobs() -> Result<Json<Value>, ApiError> {
+    let jobs = Job::list().map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(json!({ "jobs": jobs })))
+}
+
+/// Jobs run restricted, in the server's own directory: a client-chosen one would move the sandbox.
+/// Without an API key anything on this machine could call in, so no jobs are started at all.
+async fn start_job(State(api_key): State<Option<Arc<str>>>, Json(body): Json<StartJobBody>) -> Result<(StatusCode, Json<Job>), ApiError> {
+    if api_key.is_none() {
+        return Err(ApiError(StatusCode::FORBIDDEN, "Jobs can only be started when server.api_key is set".to_string()));
+    }
+    let cwd = std::env::current_dir().map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Job::start(body.args, cwd, true)
+        .map(|job| (StatusCode::CREATED, Json(job)))
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+async fn get_job(Path(id): Path<String>) -> Result<Json<Job>, ApiError> {
+    Job::load(&id).map(Json).map_err(|e| ApiError(StatusCode::NOT_FOUND, e.to_string()))
+}
+
+async fn cancel_job(Path(id): Path<String>) -> Result<Json<Job>, ApiError> {
+    let mut job = Job::load(&id).map_err(|e| ApiError(StatusCode::NOT_FOUND, e.to_string()))?;
+    job.cancel().map_err(|e| ApiError(StatusCode::CONFLICT, e.to_string()))?;
+    Ok(Json(job))
+}
+
+async fn job_logs(Path(id): Path<String>, Query(query): Query<LogsQuery>) -> Result<Json<Value>, ApiError> {
+    let job = Job::load(&id).map_err(|e| ApiError(StatusCode::NOT_FOUND, e.to_string()))?;
+    let log = job.log_tail(query.lines).map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(json!({ "id": job.id, "status": job.status, "log": log })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_and_key_comparison() {
+        assert_eq!(with_history(&[], "hi"), "hi");
+        let turns = vec![Turn {
+            prompt: "2+2?".to_string(),
+            response: "4".to_string(),
+            model_used: "test".to_string(),
+            timestamp: Utc::now(),
+        }];
+        assert_eq!(with_history(&turns, "times 3?"), "Conversation so far:\nUser: 2+2?\nAssistant: 4\n\nUser: times 3?");
+
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+
+    #[test]
+    fn test_local_host_header() {
+        assert!(is_local_host(Some("localhost:8080")));
+        assert!(is_local_host(Some("127.0.0.1:8080")));
+        assert!(is_local_host(Some("[::1]:8080")));
+        assert!(is_local_host(Some("LOCALHOST")));
+        assert!(!is_local_host(Some("attacker.example.com:8080")));
+        assert!(!is_local_host(Some("localhost.attacker.example.com")));
+        assert!(!is_local_host(None));
+    }
+
+    #[tokio::test]
+    async fn test_jobs_need_an_api_key() {
+        let body = StartJobBody { args: vec!["batch".to_string(), "prompts.txt".to_string()] };
+        let err = start_job(State(None), Json(body)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+    }
+}
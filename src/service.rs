@@ -0,0 +1,320 @@
+//! `air service install|uninstall|status`: runs `air daemon` (or `air serve`) as a per-user login
+//! service, so task reminders and the HTTP API come back after a reboot.
+//!
+//! Linux gets a systemd user unit, macOS a launchd agent. Windows gets a Task Scheduler task that
+//! starts at logon: air is a console program, not an SCM service, so `sc create` would not start it.
+
+use anyhow::{Result, anyhow};
+use std::path::PathBuf;
+use std::process::Command;
+
+#[cfg(target_os = "linux")]
+const SERVICE_NAME: &str = "air";
+const LAUNCHD_LABEL: &str = "com.air.agent";
+/// Seconds before a crashed service is started again.
+const RESTART_DELAY_SECS: u32 = 5;
+
+/// What the service runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceKind {
+    Daemon,
+    Server,
+}
+
+impl ServiceKind {
+    fn subcommand(self) -> &'static str {
+        match self {
+            ServiceKind::Daemon => "daemon",
+            ServiceKind::Server => "serve",
+        }
+    }
+}
+
+/// Everything the unit file needs, resolved from the installing shell.
+#[derive(Debug, Clone)]
+pub struct ServiceSpec {
+    pub exe: PathBuf,
+    pub kind: ServiceKind,
+    /// Carried over because services don't start from the user's shell (PATH for the command
+    /// tool, AIR_PROFILE for the profile).
+    pub env: Vec<(String, String)>,
+    pub log_file: PathBuf,
+}
+
+impl ServiceSpec {
+    pub fn current(kind: ServiceKind) -> Result<Self> {
+        let exe = std::env::current_exe()?;
+        let env = ["PATH", "AIR_PROFILE"].iter()
+            .filter_map(|name| std::env::var(name).ok().map(|value| (name.to_string(), value)))
+            .collect();
+        let log_file = crate::utils::paths::get_air_data_dir()?.join("logs").join("service.log");
+        Ok(Self { exe, kind, env, log_file })
+    }
+}
+
+/// Writes the service definition, enables it and starts it. Returns a message for the user.
+pub fn install(spec: &ServiceSpec) -> Result<String> {
+    if cfg!(windows) && spec.kind == ServiceKind::Daemon {
+        return Err(anyhow!("air daemon is Unix-only; install the HTTP server instead with --server"));
+    }
+    platform::install(spec)
+}
+
+pub fn uninstall() -> Result<String> {
+    platform::uninstall()
+}
+
+/// The service manager's own report.
+pub fn status() -> Result<String> {
+    platform::status()
+}
+
+pub fn systemd_unit(spec: &ServiceSpec) -> String {
+    let mut unit = format!(
+        "[Unit]\nDescription=AIR {}\nAfter=network-online.target\n\n[Service]\nType=simple\nExecStart={} {}\n",
+        spec.kind.subcommand(), systemd_quote(&spec.exe.to_string_lossy()), spec.kind.subcommand()
+    );
+    for (name, value) in &spec.env {
+        unit.push_str(&format!("Environment={}\n", systemd_quote(&format!("{}={}", name, value))));
+    }
+    unit.push_str(&format!("Restart=on-failure\nRestartSec={}\n\n[Install]\nWantedBy=default.target\n", RESTART_DELAY_SECS));
+    unit
+}
+
+pub fn launchd_plist(spec: &ServiceSpec) -> String {
+    let env: String = spec.env.iter()
+        .map(|(name, value)| format!("        <key>{}</key>\n        <string>{}</string>\n", xml_escape(name), xml_escape(value)))
+        .collect();
+    let log = xml_escape(&spec.log_file.to_string_lossy());
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>{subcommand}</string>
+    </array>
+    <key>EnvironmentVariables</key>
+    <dict>
+{env}    </dict>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>ThrottleInterval</key>
+    <integer>{delay}</integer>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = LAUNCHD_LABEL,
+        exe = xml_escape(&spec.exe.to_string_lossy()),
+        subcommand = spec.kind.subcommand(),
+        env = env,
+        delay = RESTART_DELAY_SECS,
+        log = log,
+    )
+}
+
+fn systemd_quote(value: &str) -> String {
+    if value.chars().any(|c| c.is_whitespace() || c == '"' || c == '\\') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Runs a service manager command, failing with its stderr.
+fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program).args(args).output()
+        .map_err(|e| anyhow!("Can't run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(anyhow!("{} {} failed: {}", program, args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn write_file(path: &std::path::Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents).map_err(|e| anyhow!("Can't write {}: {}", path.display(), e))
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+
+    fn unit_path() -> Result<PathBuf> {
+        let config = dirs::config_dir().ok_or_else(|| anyhow!("No config directory for systemd user units"))?;
+        Ok(config.join("systemd").join("user").join(format!("{}.service", SERVICE_NAME)))
+    }
+
+    pub fn install(spec: &ServiceSpec) -> Result<String> {
+        let path = unit_path()?;
+        write_file(&path, &systemd_unit(spec))?;
+        let unit = format!("{}.service", SERVICE_NAME);
+        run("systemctl", &["--user", "daemon-reload"])?;
+        run("systemctl", &["--user", "enable", "--now", &unit])?;
+        Ok(format!(
+            "Installed {} and started it.\nUser services start at login; run `loginctl enable-linger $USER` to start it at boot.",
+            path.display()
+        ))
+    }
+
+    pub fn uninstall() -> Result<String> {
+        let path = unit_path()?;
+        if !path.exists() {
+            return Ok("The service is not installed".to_string());
+        }
+        run("systemctl", &["--user", "disable", "--now", &format!("{}.service", SERVICE_NAME)])?;
+        std::fs::remove_file(&path)?;
+        run("systemctl", &["--user", "daemon-reload"])?;
+        Ok(format!("Removed {}", path.display()))
+    }
+
+    pub fn status() -> Result<String> {
+        if !unit_path()?.exists() {
+            return Ok("The service is not installed".to_string());
+        }
+        // `status` exits non-zero for a stopped unit, which is still a report
+        let output = Command::new("systemctl")
+            .args(["--user", "status", "--no-pager", &format!("{}.service", SERVICE_NAME)])
+            .output()?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+
+    fn plist_path() -> Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| anyhow!("No home directory"))?;
+        Ok(home.join("Library").join("LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL)))
+    }
+
+    pub fn install(spec: &ServiceSpec) -> Result<String> {
+        let path = plist_path()?;
+        if let Some(dir) = spec.log_file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        if path.exists() {
+            run("launchctl", &["unload", &path.to_string_lossy()]).ok();
+        }
+        write_file(&path, &launchd_plist(spec))?;
+        run("launchctl", &["load", "-w", &path.to_string_lossy()])?;
+        Ok(format!("Installed {} and started it; logs go to {}", path.display(), spec.log_file.display()))
+    }
+
+    pub fn uninstall() -> Result<String> {
+        let path = plist_path()?;
+        if !path.exists() {
+            return Ok("The service is not installed".to_string());
+        }
+        run("launchctl", &["unload", "-w", &path.to_string_lossy()])?;
+        std::fs::remove_file(&path)?;
+        Ok(format!("Removed {}", path.display()))
+    }
+
+    pub fn status() -> Result<String> {
+        if !plist_path()?.exists() {
+            return Ok("The service is not installed".to_string());
+        }
+        run("launchctl", &["list", LAUNCHD_LABEL]).or_else(|_| Ok("Installed but not loaded".to_string()))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+
+    const TASK_NAME: &str = "AIR";
+
+    pub fn install(spec: &ServiceSpec) -> Result<String> {
+        let command = format!("\"{}\" {}", spec.exe.display(), spec.kind.subcommand());
+        run("schtasks", &["/Create", "/TN", TASK_NAME, "/TR", &command, "/SC", "ONLOGON", "/RL", "LIMITED", "/F"])?;
+        run("schtasks", &["/Run", "/TN", TASK_NAME])?;
+        Ok(format!("Created the '{}' logon task and started it", TASK_NAME))
+    }
+
+    pub fn uninstall() -> Result<String> {
+        if status().is_err() {
+            return Ok("The service is not installed".to_string());
+        }
+        run("schtasks", &["/End", "/TN", TASK_NAME]).ok();
+        run("schtasks", &["/Delete", "/TN", TASK_NAME, "/F"])?;
+        Ok(format!("Removed the '{}' logon task", TASK_NAME))
+    }
+
+    pub fn status() -> Result<String> {
+        run("schtasks", &["/Query", "/TN", TASK_NAME, "/V", "/FO", "LIST"])
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::*;
+
+    fn unsupported() -> anyhow::Error {
+        anyhow!("air service supports systemd, launchd and Windows Task Scheduler only")
+    }
+
+    pub fn install(_spec: &ServiceSpec) -> Result<String> {
+        Err(unsupported())
+    }
+
+    pub fn uninstall() -> Result<String> {
+        Err(unsupported())
+    }
+
+    pub fn status() -> Result<String> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> ServiceSpec {
+        ServiceSpec {
+            exe: PathBuf::from("/opt/my tools/air"),
+            kind: ServiceKind::Daemon,
+            env: vec![("AIR_PROFILE".to_string(), "work & play".to_string())],
+            log_file: PathBuf::from("/tmp/air/service.log"),
+        }
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let unit = systemd_unit(&spec());
+        assert!(unit.contains("ExecStart=\"/opt/my tools/air\" daemon\n"));
+        assert!(unit.contains("Environment=\"AIR_PROFILE=work & play\"\n"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+    }
+
+    #[test]
+    fn test_launchd_plist() {
+        let plist = launchd_plist(&ServiceSpec { kind: ServiceKind::Server, ..spec() });
+        assert!(plist.contains("<string>/opt/my tools/air</string>\n        <string>serve</string>"));
+        assert!(plist.contains("<string>work &amp; play</string>"));
+        assert!(plist.contains("<key>SuccessfulExit</key>\n        <false/>"));
+    }
+}
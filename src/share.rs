@@ -0,0 +1,264 @@
+//! Session reports (`air share`): the transcript of a past session (prompts, answers, tool
+//! calls, cited sources and spend) rendered as one self-contained HTML page, to hand results to
+//! a teammate as a file or a secret GitHub gist.
+
+use crate::agent::memory::{MemoryManager, SessionSummary, SessionTurn};
+use crate::config::GitHubConfig;
+use crate::tools::GitHubTool;
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::fmt::Write;
+
+/// Sessions searched when resolving an id prefix.
+const MAX_SESSIONS: usize = 500;
+
+const STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; max-width: 920px; margin: 2em auto; padding: 0 1em; color: #1f2328; line-height: 1.5; }
+header { border-bottom: 1px solid #d0d7de; margin-bottom: 1.5em; }
+.meta { color: #59636e; font-size: 0.9em; }
+section.turn { border: 1px solid #d0d7de; border-radius: 8px; padding: 1em 1.2em; margin-bottom: 1.5em; }
+h3 { font-size: 0.8em; text-transform: uppercase; letter-spacing: 0.05em; color: #59636e; margin: 1em 0 0.3em; }
+pre { white-space: pre-wrap; word-wrap: break-word; background: #f6f8fa; border-radius: 6px; padding: 0.8em; margin: 0; font-size: 0.9em; }
+.prompt { background: #ddf4ff; }
+.error { background: #ffebe9; }
+details { margin: 0.3em 0; }
+summary { cursor: pointer; font-family: monospace; }
+.ok { color: #1a7f37; }
+.failed { color: #d1242f; }
+";
+
+/// A session loaded for sharing.
+pub struct SharedSession {
+    pub summary: SessionSummary,
+    pub turns: Vec<SessionTurn>,
+}
+
+impl SharedSession {
+    /// `spec` is a session id, a unique prefix of one, or "last" for the newest session.
+    pub async fn load(memory: &MemoryManager, spec: &str) -> Result<Self> {
+        let sessions = memory.list_sessions(MAX_SESSIONS).await?;
+        let summary = if spec == "last" {
+            sessions.into_iter().next().ok_or_else(|| anyhow!("No sessions recorded yet"))?
+        } else {
+            let mut matches: Vec<SessionSummary> = sessions.into_iter().filter(|s| s.session_id.starts_with(spec)).collect();
+            match matches.len() {
+                0 => return Err(anyhow!("No session '{}'; `air share --list` shows the recorded ones", spec)),
+                1 => matches.remove(0),
+                n => return Err(anyhow!("'{}' matches {} sessions; give more of the id", spec, n)),
+            }
+        };
+        let turns = memory.session_turns(&summary.session_id).await?;
+        Ok(Self { summary, turns })
+    }
+
+    pub fn tool_calls(&self) -> usize {
+        self.turns.iter().map(|t| t.tool_calls.len()).sum()
+    }
+
+    pub fn tokens(&self) -> u64 {
+        self.turns.iter().map(|t| t.tokens).sum()
+    }
+
+    /// None when no call of the session had a configured price.
+    pub fn cost_usd(&self) -> Option<f64> {
+        self.turns.iter().filter_map(|t| t.cost_usd).reduce(|a, b| a + b)
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("air-session-{}.html", short_id(&self.summary.session_id))
+    }
+
+    /// The whole report as one HTML page with inline styles and no scripts or external assets.
+    pub fn render_html(&self) -> String {
+        let mut html = String::new();
+        let title = format!("air session {}", short_id(&self.summary.session_id));
+        writeln!(html, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">").ok();
+        writeln!(html, "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">").ok();
+        writeln!(html, "<title>{}</title>\n<style>{}</style>\n</head>\n<body>", escape_html(&title), STYLE).ok();
+
+        writeln!(html, "<header>\n<h1>{}</h1>", escape_html(&title)).ok();
+        writeln!(
+            html,
+            "<p class=\"meta\">Started {} UTC · {} turn(s) · {} tool call(s) · {} tokens · {}</p>\n</header>",
+            escape_html(&self.summary.started),
+            self.turns.len(),
+            self.tool_calls(),
+            self.tokens(),
+            format_cost(self.cost_usd()),
+        ).ok();
+
+        for (i, turn) in self.turns.iter().enumerate() {
+            render_turn(&mut html, i + 1, turn);
+        }
+
+        writeln!(html, "<p class=\"meta\">Session {}</p>\n</body>\n</html>", escape_html(&self.summary.session_id)).ok();
+        html
+    }
+
+    /// Uploads the report as a secret gist and returns its URL.
+    pub async fn upload_gist(&self, config: &GitHubConfig, html: &str) -> Result<String> {
+        let description = format!("air session {} ({} turns)", short_id(&self.summary.session_id), self.turns.len());
+        GitHubTool::with_config(config).create_gist(&description, &[(&self.file_name(), html)], false).await
+    }
+}
+
+fn render_turn(html: &mut String, number: usize, turn: &SessionTurn) {
+    writeln!(html, "<section class=\"turn\">\n<h2>Turn {}</h2>", number).ok();
+    writeln!(html, "<h3>Prompt</h3>\n<pre class=\"prompt\">{}</pre>", escape_html(&turn.prompt)).ok();
+
+    if !turn.tool_calls.is_empty() {
+        writeln!(html, "<h3>Tool calls</h3>").ok();
+        for call in &turn.tool_calls {
+            let (class, mark) = if call.success { ("ok", "✓") } else { ("failed", "✗") };
+            writeln!(
+                html,
+                "<details><summary><span class=\"{}\">{}</span> {}.{}</summary>\n<pre>{}</pre>\n<pre>{}</pre></details>",
+                class,
+                mark,
+                escape_html(&call.tool),
+                escape_html(&call.function),
+                escape_html(&serde_json::to_string_pretty(&call.args).unwrap_or_default()),
+                escape_html(&call.output),
+            ).ok();
+        }
+    }
+
+    match (&turn.response, &turn.error) {
+        (Some(response), _) => writeln!(html, "<h3>Answer</h3>\n<pre>{}</pre>", escape_html(response)).ok(),
+        (None, Some(error)) => writeln!(html, "<h3>Failed</h3>\n<pre class=\"error\">{}</pre>", escape_html(error)).ok(),
+        (None, None) => None,
+    };
+
+    let sources = citations(turn);
+    if !sources.is_empty() {
+        writeln!(html, "<h3>Sources</h3>\n<ul>").ok();
+        for source in &sources {
+            if source.starts_with("http://") || source.starts_with("https://") {
+                let source = escape_html(source);
+                writeln!(html, "<li><a href=\"{}\">{}</a></li>", source, source).ok();
+            } else {
+                writeln!(html, "<li><code>{}</code></li>", escape_html(source)).ok();
+            }
+        }
+        writeln!(html, "</ul>").ok();
+    }
+
+    writeln!(
+        html,
+        "<p class=\"meta\">{} · {} · {} tokens · {}</p>\n</section>",
+        escape_html(&turn.timestamp),
+        escape_html(turn.model_used.as_deref().unwrap_or("no model")),
+        turn.tokens,
+        format_cost(turn.cost_usd),
+    ).ok();
+}
+
+/// What a turn drew on: links in its answer, pages its tools fetched and files they read.
+pub fn citations(turn: &SessionTurn) -> Vec<String> {
+    let mut sources: Vec<String> = Vec::new();
+    let mut add = |source: &str| {
+        if !source.is_empty() && !sources.iter().any(|s| s == source) {
+            sources.push(source.to_string());
+        }
+    };
+
+    for call in &turn.tool_calls {
+        if let Some(url) = call.args["url"].as_str() {
+            add(url);
+        }
+        if call.tool == "filesystem" {
+            if let Some(path) = call.args["path"].as_str() {
+                add(path);
+            }
+        }
+    }
+    if let (Some(response), Ok(url)) = (&turn.response, Regex::new(r#"https?://[^\s<>"'`\)\]]+"#)) {
+        for found in url.find_iter(response) {
+            add(found.as_str().trim_end_matches(['.', ',', ';', ':']));
+        }
+    }
+    sources
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn short_id(session_id: &str) -> &str {
+    &session_id[..session_id.len().min(8)]
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    cost.map(|c| format!("${:.4}", c)).unwrap_or_else(|| "cost n/a".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::memory::ToolCallRecord;
+    use serde_json::json;
+
+    fn turn() -> SessionTurn {
+        SessionTurn {
+            query_id: "q1".to_string(),
+            prompt: "What does <script>alert(1)</script> do?".to_string(),
+            response: Some("See https://example.com/docs. Also https://example.com/docs".to_string()),
+            error: None,
+            model_used: Some("gpt-4o-mini".to_string()),
+            tool_calls: vec![
+                ToolCallRecord {
+                    tool: "web".to_string(),
+                    function: "fetch".to_string(),
+                    args: json!({"url": "https://example.org/page"}),
+                    success: true,
+                    output: "page text".to_string(),
+                },
+                ToolCallRecord {
+                    tool: "filesystem".to_string(),
+                    function: "read_file".to_string(),
+                    args: json!({"path": "src/main.rs"}),
+                    success: false,
+                    output: "not found".to_string(),
+                },
+            ],
+            timestamp: "2025-03-01 10:00:00".to_string(),
+            tokens: 120,
+            cost_usd: Some(0.002),
+        }
+    }
+
+    #[test]
+    fn test_citations() {
+        assert_eq!(citations(&turn()), vec!["https://example.org/page", "src/main.rs", "https://example.com/docs"]);
+    }
+
+    #[test]
+    fn test_render_html_escapes() {
+        let session = SharedSession {
+            summary: SessionSummary {
+                session_id: "0123456789abcdef".to_string(),
+                started: "2025-03-01 10:00:00".to_string(),
+                turns: 1,
+                first_prompt: String::new(),
+            },
+            turns: vec![turn()],
+        };
+        let html = session.render_html();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("<title>air session 01234567</title>"));
+        assert!(html.contains("$0.0020"));
+        assert_eq!(session.file_name(), "air-session-01234567.html");
+    }
+}
@@ -0,0 +1,216 @@
+//! `air test-fix`: runs the test command, hands the failures and the files they point at to
+//! `air apply`'s change flow, and reruns until the tests pass or the iterations run out. Every
+//! applied fix is its own change set, so `air changes rollback` undoes them one at a time.
+
+use crate::agent::{AIAgent, QueryMode};
+use crate::changes::{self, ChangeSet};
+use crate::config::Config;
+use crate::tools::command::{Shell, truncate_output};
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Source files from the output sent to the model, besides the failure text itself.
+const MAX_FILES: usize = 5;
+/// Failure names listed per iteration in the summary.
+const MAX_LISTED_FAILURES: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct TestRun {
+    pub passed: bool,
+    pub output: String,
+    pub duration: Duration,
+}
+
+/// What one failing run led to.
+#[derive(Debug, Clone)]
+pub struct Iteration {
+    pub failures: Vec<String>,
+    /// The change set applied for it; None when the model proposed nothing or all was declined.
+    pub change_set: Option<String>,
+    pub files_changed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub command: String,
+    pub passed: bool,
+    pub iterations: Vec<Iteration>,
+}
+
+impl Summary {
+    pub fn render(&self) -> String {
+        let mut out = format!("Test command: {}\n", self.command);
+        for (i, iteration) in self.iterations.iter().enumerate() {
+            let applied = match &iteration.change_set {
+                Some(id) => format!("fixed {} file(s) in change set {}", iteration.files_changed, id),
+                None => "no fix applied".to_string(),
+            };
+            out.push_str(&format!("  {}. {} failure(s), {}\n", i + 1, iteration.failures.len(), applied));
+            for name in iteration.failures.iter().take(MAX_LISTED_FAILURES) {
+                out.push_str(&format!("       - {}\n", name));
+            }
+        }
+        out.push_str(if self.passed { "Result: tests pass" } else { "Result: tests still failing" });
+        out
+    }
+}
+
+/// The usual test command for the project at `root`.
+pub fn detect_command(root: &Path) -> Option<String> {
+    let candidates = [
+        ("Cargo.toml", "cargo test"),
+        ("package.json", "npm test"),
+        ("pyproject.toml", "pytest"),
+        ("pytest.ini", "pytest"),
+        ("setup.py", "pytest"),
+        ("go.mod", "go test ./..."),
+    ];
+    candidates.iter().find(|(file, _)| root.join(file).exists()).map(|(_, command)| command.to_string())
+}
+
+pub async fn run_tests(command: &str, root: &Path, shell: Shell, timeout: Duration) -> Result<TestRun> {
+    let started = Instant::now();
+    let mut cmd = shell.command(command);
+    cmd.current_dir(root).kill_on_drop(true);
+    let output = tokio::time::timeout(timeout, cmd.output()).await
+        .map_err(|_| anyhow!("`{}` timed out after {}s", command, timeout.as_secs()))?
+        .map_err(|e| anyhow!("Can't run `{}`: {}", command, e))?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(TestRun { passed: output.status.success(), output: text, duration: started.elapsed() })
+}
+
+/// Failing tests and compile errors named in the output of cargo, pytest, go test or jest.
+pub fn failures(output: &str) -> Vec<String> {
+    let patterns = [
+        r"^test (\S+) \.\.\. FAILED",
+        r"^(error(?:\[E\d+\])?: .+)$",
+        r"^FAILED (\S+)",
+        r"^\s*--- FAIL: (\S+)",
+        r"^\s*● (.+)$",
+    ];
+    let patterns: Vec<Regex> = patterns.iter().filter_map(|p| Regex::new(p).ok()).collect();
+    let mut found: Vec<String> = Vec::new();
+    for line in output.lines() {
+        if let Some(name) = patterns.iter().find_map(|re| re.captures(line)).map(|c| c[1].trim().to_string()) {
+            // cargo repeats the final tally as "error: test failed, to rerun pass ..."
+            if !found.contains(&name) && !name.starts_with("error: test failed") && !name.starts_with("error: could not compile") {
+                found.push(name);
+            }
+        }
+    }
+    found
+}
+
+/// Files under `root` that the output points at (`src/lib.rs:10`, `tests/test_api.py:42`),
+/// in order of first mention.
+pub fn referenced_files(root: &Path, output: &str) -> Vec<String> {
+    let Ok(re) = Regex::new(r"([A-Za-z0-9_./\\-]+\.[A-Za-z]{1,4}):\d+") else { return Vec::new() };
+    let mut files: Vec<String> = Vec::new();
+    for capture in re.captures_iter(output) {
+        let path = capture[1].trim_start_matches("./").replace('\\', "/");
+        if files.len() < MAX_FILES && !files.contains(&path) && root.join(&path).is_file() {
+            files.push(path);
+        }
+    }
+    files
+}
+
+fn fix_request(command: &str, output: &str) -> String {
+    format!(
+        "`{}` fails. Fix the code so the tests pass; change a test only if the test itself is clearly wrong. \
+         Test output:\n```\n{}\n```",
+        command, output.trim_end()
+    )
+}
+
+/// Runs the test-fix loop from `root`. Fixes are reviewed hunk by hunk unless `yes`.
+pub async fn run(agent: &AIAgent, mode: &QueryMode, config: &Config, root: &Path, command: Option<String>, iterations: Option<u32>, yes: bool) -> Result<Summary> {
+    let settings = &config.test_fix;
+    let command = command.or_else(|| settings.command.clone()).or_else(|| detect_command(root))
+        .ok_or_else(|| anyhow!("No test command found; pass --command or set test_fix.command"))?;
+    let shell = match &config.command.shell {
+        Some(name) => Shell::parse(name)?,
+        None => Shell::platform_default(),
+    };
+    let timeout = Duration::from_secs(settings.timeout_seconds);
+    let max_iterations = iterations.unwrap_or(settings.max_iterations);
+
+    let mut summary = Summary { command: command.clone(), passed: false, iterations: Vec::new() };
+    loop {
+        println!("\n🧪 Running `{}`...", command);
+        let run = run_tests(&command, root, shell, timeout).await?;
+        if run.passed {
+            println!("✅ Tests pass ({:.1}s)", run.duration.as_secs_f64());
+            summary.passed = true;
+            return Ok(summary);
+        }
+        let failed = failures(&run.output);
+        println!("❌ {} failure(s) ({:.1}s)", failed.len(), run.duration.as_secs_f64());
+        if summary.iterations.len() as u32 >= max_iterations {
+            return Ok(summary);
+        }
+
+        let output = truncate_output(&run.output, settings.max_output_chars);
+        // Without file references (a failing assertion in a script, say) the code index picks the files
+        let referenced: Vec<_> = referenced_files(root, &run.output).iter().map(|f| root.join(f)).collect();
+        let query = if referenced.is_empty() { failed.join("\n") } else { String::new() };
+        let context = changes::context_files(root, &referenced, &query).await?;
+        let names: Vec<&str> = context.iter().map(|(p, _)| p.as_str()).collect();
+        println!("🔧 Asking for a fix (iteration {}/{}), looking at: {}", summary.iterations.len() + 1, max_iterations, names.join(", "));
+
+        let proposal = changes::propose(agent, mode, &fix_request(&command, &output), &context).await?;
+        let mut iteration = Iteration { failures: failed, change_set: None, files_changed: 0 };
+        let planned = match changes::plan(root, &proposal.edits) {
+            Ok(planned) => planned,
+            Err(e) => {
+                // Edits that don't match the files count as an attempt; the next one sees the same failures
+                println!("⚠️  The proposed fix doesn't apply: {}", e);
+                summary.iterations.push(iteration);
+                continue;
+            }
+        };
+        println!("\n💡 {}", proposal.summary);
+        let approved = changes::review(planned, yes);
+        if approved.is_empty() {
+            // Nothing proposed, or everything declined
+            summary.iterations.push(iteration);
+            return Ok(summary);
+        }
+        let set = ChangeSet::apply(&format!("test-fix: {}", command), &proposal.summary, approved)?;
+        iteration.change_set = Some(set.id.clone());
+        iteration.files_changed = set.files.len();
+        summary.iterations.push(iteration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures() {
+        let cargo = "running 2 tests\ntest config::tests::test_load ... FAILED\ntest ok ... ok\n\n\
+                     ---- config::tests::test_load stdout ----\nthread panicked at src/config.rs:12:5\n\
+                     error: test failed, to rerun pass `--lib`\n";
+        assert_eq!(failures(cargo), vec!["config::tests::test_load"]);
+
+        let rustc = "error[E0308]: mismatched types\n --> src/main.rs:4:9\nerror: could not compile `air`\n";
+        assert_eq!(failures(rustc), vec!["error[E0308]: mismatched types"]);
+        assert_eq!(failures("FAILED tests/test_api.py::test_login - assert 1 == 2"), vec!["tests/test_api.py::test_login"]);
+        assert_eq!(failures("    --- FAIL: TestParse (0.00s)"), vec!["TestParse"]);
+    }
+
+    #[test]
+    fn test_referenced_files_and_detection() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        let output = "thread panicked at src/lib.rs:3:5\n --> src/lib.rs:9\n at /rustc/abc/library/core.rs:1";
+        assert_eq!(referenced_files(dir.path(), output), vec!["src/lib.rs"]);
+        assert_eq!(detect_command(dir.path()).as_deref(), Some("cargo test"));
+    }
+}
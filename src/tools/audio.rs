@@ -0,0 +1,119 @@
+use anyhow::{Result, anyhow};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::SampleFormat;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+type SharedWriter = Arc<Mutex<Option<hound::WavWriter<BufWriter<File>>>>>;
+
+/// Records `duration` from the default input device into a 16-bit WAV file.
+/// Blocks the calling thread, so call it from `spawn_blocking`.
+pub fn record_wav(path: &Path, duration: Duration) -> Result<()> {
+    let host = cpal::default_host();
+    let device = host.default_input_device()
+        .ok_or_else(|| anyhow!("No audio input device available"))?;
+    let config = device.default_input_config()?;
+
+    let spec = hound::WavSpec {
+        channels: config.channels(),
+        sample_rate: config.sample_rate().0,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let writer: SharedWriter = Arc::new(Mutex::new(Some(hound::WavWriter::create(path, spec)?)));
+
+    let err_fn = |e| warn!("Audio input stream error: {}", e);
+    let stream_config = config.config();
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => {
+            let writer = writer.clone();
+            device.build_input_stream(&stream_config, move |data: &[f32], _: &_| {
+                write_samples(&writer, data.iter().map(|&s| f32_to_i16(s)))
+            }, err_fn, None)?
+        }
+        SampleFormat::I16 => {
+            let writer = writer.clone();
+            device.build_input_stream(&stream_config, move |data: &[i16], _: &_| {
+                write_samples(&writer, data.iter().copied())
+            }, err_fn, None)?
+        }
+        SampleFormat::U16 => {
+            let writer = writer.clone();
+            device.build_input_stream(&stream_config, move |data: &[u16], _: &_| {
+                write_samples(&writer, data.iter().map(|&s| u16_to_i16(s)))
+            }, err_fn, None)?
+        }
+        other => return Err(anyhow!("Unsupported input sample format: {:?}", other)),
+    };
+
+    stream.play()?;
+    std::thread::sleep(duration);
+    drop(stream);
+
+    let writer = writer.lock().unwrap().take()
+        .ok_or_else(|| anyhow!("Recording writer was already closed"))?;
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Plays an audio file (WAV, MP3, FLAC, OGG) on the default output device until it finishes.
+/// Blocks the calling thread, so call it from `spawn_blocking`.
+pub fn play_file(path: &Path) -> Result<()> {
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&handle)?;
+    let file = std::io::BufReader::new(File::open(path)?);
+    sink.append(rodio::Decoder::new(file)?);
+    sink.sleep_until_end();
+    Ok(())
+}
+
+/// Synthesizes `text` into a WAV file with a Piper voice model (the model's `.onnx.json` config).
+pub fn synthesize_piper(model_config: &Path, text: &str, output: &Path) -> Result<()> {
+    let model = piper_rs::from_config_path(model_config)
+        .map_err(|e| anyhow!("Failed to load Piper voice '{}': {}", model_config.display(), e))?;
+    let synth = piper_rs::synth::PiperSpeechSynthesizer::new(model)
+        .map_err(|e| anyhow!("Failed to initialize Piper: {}", e))?;
+    synth.synthesize_to_file(output, text.to_string(), None)
+        .map_err(|e| anyhow!("Piper synthesis failed: {}", e))?;
+    Ok(())
+}
+
+fn write_samples(writer: &SharedWriter, samples: impl Iterator<Item = i16>) {
+    if let Ok(mut guard) = writer.lock() {
+        if let Some(writer) = guard.as_mut() {
+            for sample in samples {
+                if writer.write_sample(sample).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn f32_to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+fn u16_to_i16(sample: u16) -> i16 {
+    (sample as i32 - 32768) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_conversion() {
+        assert_eq!(f32_to_i16(0.0), 0);
+        assert_eq!(f32_to_i16(1.0), i16::MAX);
+        assert_eq!(f32_to_i16(2.0), i16::MAX);
+        assert_eq!(f32_to_i16(-1.0), -i16::MAX);
+        assert_eq!(u16_to_i16(32768), 0);
+        assert_eq!(u16_to_i16(0), i16::MIN);
+        assert_eq!(u16_to_i16(u16::MAX), i16::MAX);
+    }
+}
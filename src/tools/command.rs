@@ -1,163 +1,304 @@
-use super::{Tool, ToolResult};
+use super::{PermissionLevel, Tool, ToolResult};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use serde_json::Value;
-use std::process::Command;
-use std::io::{self, Write};
-use std::collections::HashSet;
+use std::process::Stdio;
+use crate::utils::approval;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use crate::config::CommandConfig;
+use super::command_policy::{CommandDecision, CommandPolicy};
+use super::jobs::JobRegistry;
+use tracing::warn;
 
 pub struct CommandTool {
-    // Safe commands that don't require explicit permission
-    safe_commands: HashSet<String>,
+    // Rules deciding which commands are auto-approved, need permission or are refused
+    policy: CommandPolicy,
     // Whether to auto-approve safe commands
     auto_approve_safe: bool,
+    // Default time limit before a running command is killed
+    timeout: Duration,
+    // Maximum characters of stdout/stderr handed back to the model
+    max_output_chars: usize,
+    // Long-running commands started with run_background
+    jobs: JobRegistry,
+    // Session working directory, updated by `cd`
+    cwd: Mutex<PathBuf>,
+    // Shell used when a call doesn't pick one
+    default_shell: Shell,
+}
+
+/// Shell used to interpret a command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Sh,
+    Bash,
+    Zsh,
+    Cmd,
+    PowerShell,
+    Pwsh,
+}
+
+impl Shell {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "sh" => Ok(Shell::Sh),
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "cmd" => Ok(Shell::Cmd),
+            "powershell" => Ok(Shell::PowerShell),
+            "pwsh" => Ok(Shell::Pwsh),
+            other => Err(anyhow!("Unsupported shell '{}' (expected sh, bash, zsh, cmd, powershell or pwsh)", other)),
+        }
+    }
+
+    pub fn platform_default() -> Self {
+        if cfg!(target_os = "windows") {
+            Shell::PowerShell
+        } else {
+            Shell::Sh
+        }
+    }
+
+    /// `command` run through this shell.
+    pub fn command(self, command: &str) -> Command {
+        let (program, flag) = match self {
+            Shell::Sh => ("sh", "-c"),
+            Shell::Bash => ("bash", "-c"),
+            Shell::Zsh => ("zsh", "-c"),
+            Shell::Cmd => ("cmd", "/C"),
+            Shell::PowerShell => ("powershell", "-Command"),
+            Shell::Pwsh => ("pwsh", "-Command"),
+        };
+        let mut cmd = Command::new(program);
+        cmd.args([flag, command]);
+        cmd
+    }
+}
+
+/// Per-call execution settings: where to run, extra environment and which shell.
+struct ExecOptions {
+    cwd: PathBuf,
+    env: HashMap<String, String>,
+    shell: Shell,
+}
+
+impl ExecOptions {
+    fn build(&self, command: &str) -> Command {
+        let mut cmd = self.shell.command(command);
+        cmd.current_dir(&self.cwd).envs(&self.env);
+        cmd
+    }
+}
+
+/// Captured result of a streamed command run.
+struct CommandOutput {
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    timed_out: bool,
 }
 
 impl CommandTool {
     pub fn new() -> Self {
-        let mut safe_commands = HashSet::new();
-        
-        // Add commonly safe read-only commands
-        safe_commands.insert("dir".to_string());
-        safe_commands.insert("ls".to_string());
-        safe_commands.insert("pwd".to_string());
-        safe_commands.insert("cd".to_string());
-        safe_commands.insert("echo".to_string());
-        safe_commands.insert("type".to_string());
-        safe_commands.insert("cat".to_string());
-        safe_commands.insert("head".to_string());
-        safe_commands.insert("tail".to_string());
-        safe_commands.insert("find".to_string());
-        safe_commands.insert("grep".to_string());
-        safe_commands.insert("which".to_string());
-        safe_commands.insert("where".to_string());
-        safe_commands.insert("whoami".to_string());
-        safe_commands.insert("date".to_string());
-        safe_commands.insert("time".to_string());
-        safe_commands.insert("hostname".to_string());
-        safe_commands.insert("ping".to_string());
-        safe_commands.insert("git".to_string()); // Git commands are generally safe
-        safe_commands.insert("cargo".to_string()); // Cargo commands for Rust development
-        safe_commands.insert("node".to_string());
-        safe_commands.insert("npm".to_string());
-        safe_commands.insert("python".to_string());
-        safe_commands.insert("rustc".to_string());
+        Self::with_config(&CommandConfig::default())
+    }
+
+    pub fn with_config(config: &CommandConfig) -> Self {
+        let project_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let log_dir = crate::utils::paths::get_air_data_dir()
+            .unwrap_or_else(|_| std::env::temp_dir())
+            .join("jobs");
+        let default_shell = match config.shell.as_deref().map(Shell::parse) {
+            Some(Ok(shell)) => shell,
+            Some(Err(e)) => {
+                warn!("{}; falling back to the platform shell", e);
+                Shell::platform_default()
+            }
+            None => Shell::platform_default(),
+        };
         
         Self {
-            safe_commands,
+            policy: CommandPolicy::new(config, &project_dir),
             auto_approve_safe: true,
+            timeout: Duration::from_secs(config.timeout_seconds),
+            max_output_chars: config.max_output_chars,
+            jobs: JobRegistry::new(log_dir),
+            cwd: Mutex::new(project_dir),
+            default_shell,
         }
     }
     
-    fn is_safe_command(&self, command: &str) -> bool {
-        let parts: Vec<&str> = command.trim().split_whitespace().collect();
-        if parts.is_empty() {
-            return false;
-        }
-        
-        let base_command = parts[0].to_lowercase();
-        
-        // Check if it's in our safe list
-        if self.safe_commands.contains(&base_command) {
-            // Additional safety checks for specific commands
-            match base_command.as_str() {
-                "rm" | "del" | "rmdir" | "format" | "shutdown" | "reboot" => false,
-                "git" => {
-                    // Allow most git commands except potentially destructive ones
-                    if parts.len() > 1 {
-                        match parts[1] {
-                            "push" | "commit" | "add" | "merge" | "rebase" => {
-                                // These require permission as they modify state
-                                false
-                            }
-                            _ => true
-                        }
-                    } else {
-                        true
-                    }
-                }
-                "cargo" => {
-                    // Allow most cargo commands
-                    if parts.len() > 1 {
-                        match parts[1] {
-                            "publish" | "install" => false, // These can modify system
-                            _ => true
-                        }
-                    } else {
-                        true
-                    }
+    fn session_cwd(&self) -> PathBuf {
+        self.cwd.lock().unwrap().clone()
+    }
+
+    /// Reads the optional `cwd`, `env` and `shell` arguments of a call.
+    fn exec_options(&self, args: &Value) -> Result<ExecOptions> {
+        let session_cwd = self.session_cwd();
+        let cwd = match args.get("cwd").and_then(|v| v.as_str()) {
+            Some(dir) => {
+                let dir = resolve_dir(&session_cwd, dir);
+                if !dir.is_dir() {
+                    return Err(anyhow!("Working directory '{}' does not exist", dir.display()));
                 }
-                _ => true
+                dir
+            }
+            None => session_cwd,
+        };
+
+        let mut env = HashMap::new();
+        if let Some(vars) = args.get("env").and_then(|v| v.as_object()) {
+            for (key, value) in vars {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                env.insert(key.clone(), value);
             }
-        } else {
-            false
         }
+
+        let shell = match args.get("shell").and_then(|v| v.as_str()) {
+            Some(name) => Shell::parse(name)?,
+            None => self.default_shell,
+        };
+
+        Ok(ExecOptions { cwd, env, shell })
+    }
+
+    /// Handles a bare `cd <dir>` by moving the session directory instead of spawning a shell,
+    /// since a child process's `cd` would be lost as soon as it exits.
+    fn change_directory(&self, target: Option<&str>, base: &Path) -> ToolResult {
+        let dir = match target {
+            Some(target) => resolve_dir(base, target),
+            None => dirs::home_dir().unwrap_or_else(|| base.to_path_buf()),
+        };
+
+        if !dir.is_dir() {
+            return ToolResult {
+                success: false,
+                result: serde_json::json!(format!("cd: no such directory: {}", dir.display())),
+                metadata: None,
+            };
+        }
+
+        let dir = dir.canonicalize().unwrap_or(dir);
+        *self.cwd.lock().unwrap() = dir.clone();
+        println!("📁 Working directory: {}", dir.display());
+
+        ToolResult {
+            success: true,
+            result: serde_json::json!({
+                "cwd": dir.to_string_lossy()
+            }),
+            metadata: None,
+        }
+    }
+
+    fn is_safe_command(&self, command: &str) -> bool {
+        self.policy.evaluate(command) == CommandDecision::AutoApprove
     }
     
-    fn request_permission(&self, command: &str) -> Result<bool> {
+    fn request_permission(&self, command: &str, options: &ExecOptions) -> Result<bool> {
         println!("\n🔐 Command Execution Permission Required");
         println!("═══════════════════════════════════════");
         println!("📋 Command: {}", command);
+        println!("📁 Directory: {}", options.cwd.display());
+        if options.shell != self.default_shell {
+            println!("🐚 Shell: {:?}", options.shell);
+        }
+        let mut env: Vec<_> = options.env.iter().collect();
+        env.sort();
+        for (key, value) in env {
+            println!("🌱 Environment: {}={}", key, value);
+        }
         println!("⚠️  This command will be executed on your system.");
         println!("💡 Review the command carefully before proceeding.");
-        print!("\n❓ Do you want to execute this command? (y/N): ");
-        
-        io::stdout().flush()?;
-        
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        
-        let response = input.trim().to_lowercase();
-        Ok(response == "y" || response == "yes")
+        Ok(approval::confirm("\n❓ Do you want to execute this command?"))
     }
     
-    async fn execute_command(&self, command: &str) -> Result<ToolResult> {
-        // Determine if we need permission
-        let needs_permission = !self.is_safe_command(command) || !self.auto_approve_safe;
-        
-        if needs_permission {
+    /// Whether running `command` with `options` needs the user's approval. Extra environment
+    /// variables (PATH, LD_PRELOAD, GIT_EXTERNAL_DIFF, ...) or another shell can make an
+    /// auto-approved command run anything, so those calls are always asked about.
+    fn needs_permission(&self, decision: &CommandDecision, options: &ExecOptions) -> bool {
+        *decision != CommandDecision::AutoApprove
+            || !self.auto_approve_safe
+            || !options.env.is_empty()
+            || options.shell != self.default_shell
+    }
+
+    /// Applies the deny rules and asks for permission when needed.
+    /// Returns the result to report back when the command must not run.
+    fn authorize(&self, command: &str, options: &ExecOptions) -> Result<Option<ToolResult>> {
+        let decision = self.policy.evaluate(command);
+        if let CommandDecision::Deny(pattern) = &decision {
+            return Ok(Some(ToolResult {
+                success: false,
+                result: serde_json::json!(format!("Command '{}' is blocked by the deny rule '{}'.", command, pattern)),
+                metadata: Some(serde_json::json!({
+                    "denied": true,
+                    "rule": pattern,
+                    "command": command
+                })),
+            }));
+        }
+
+        if self.needs_permission(&decision, options) {
             println!("\n🤖 AI wants to execute: {}", command);
-            if !self.request_permission(command)? {
-                return Ok(ToolResult {
+            if !self.request_permission(command, options)? {
+                return Ok(Some(ToolResult {
                     success: false,
                     result: serde_json::json!("Command execution cancelled by user."),
                     metadata: Some(serde_json::json!({
                         "cancelled": true,
                         "command": command
                     })),
-                });
+                }));
             }
         }
+
+        Ok(None)
+    }
+
+    async fn execute_command(&self, command: &str, timeout: Option<Duration>, options: ExecOptions) -> Result<ToolResult> {
+        if let Some(target) = parse_cd(command) {
+            return Ok(self.change_directory(target, &options.cwd));
+        }
+
+        if let Some(blocked) = self.authorize(command, &options)? {
+            return Ok(blocked);
+        }
         
-        // Execute the command
+        let timeout = timeout.unwrap_or(self.timeout);
         println!("⚡ Executing: {}", command);
         
-        let output = if cfg!(target_os = "windows") {
-            Command::new("powershell")
-                .args(["-Command", command])
-                .output()
-        } else {
-            Command::new("sh")
-                .args(["-c", command])
-                .output()
-        };
-        
-        match output {
+        let cwd = options.cwd.clone();
+        match self.run_streaming(command, timeout, &options).await {
             Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                
+                if output.timed_out {
+                    println!("⏱️  Command timed out after {}s and was killed", timeout.as_secs());
+                }
+
                 let result_json = serde_json::json!({
-                    "stdout": stdout,
-                    "stderr": stderr,
-                    "exit_code": output.status.code()
+                    "stdout": truncate_output(&output.stdout, self.max_output_chars),
+                    "stderr": truncate_output(&output.stderr, self.max_output_chars),
+                    "exit_code": output.exit_code,
+                    "timed_out": output.timed_out
                 });
                 
                 Ok(ToolResult {
-                    success: output.status.success(),
+                    success: !output.timed_out && output.exit_code == Some(0),
                     result: result_json,
                     metadata: Some(serde_json::json!({
                         "command": command,
+                        "cwd": cwd.to_string_lossy(),
+                        "timeout_seconds": timeout.as_secs(),
+                        "stdout_bytes": output.stdout.len(),
+                        "stderr_bytes": output.stderr.len(),
                     })),
                 })
             }
@@ -173,6 +314,135 @@ impl CommandTool {
             }
         }
     }
+
+    async fn run_background(&self, command: &str, options: ExecOptions) -> Result<ToolResult> {
+        if let Some(blocked) = self.authorize(command, &options)? {
+            return Ok(blocked);
+        }
+
+        match self.jobs.spawn(command, options.build(command)).await {
+            Ok(job) => {
+                println!("🚀 Started background job {}: {}", job.id, command);
+                Ok(ToolResult {
+                    success: true,
+                    result: serde_json::to_value(&job)?,
+                    metadata: Some(serde_json::json!({
+                        "hint": "Use job_status, job_output or kill_job with this job id"
+                    })),
+                })
+            }
+            Err(e) => Ok(ToolResult {
+                success: false,
+                result: serde_json::json!(format!("Failed to start background job: {}", e)),
+                metadata: Some(serde_json::json!({
+                    "error": e.to_string(),
+                    "command": command
+                })),
+            }),
+        }
+    }
+
+    /// Spawns the command through the platform shell, echoing stdout/stderr to the
+    /// terminal line by line while capturing them, and kills it if `timeout` elapses.
+    async fn run_streaming(&self, command: &str, timeout: Duration, options: &ExecOptions) -> Result<CommandOutput> {
+        let mut child = options.build(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("Failed to capture stdout"))?;
+        let stderr = child.stderr.take().ok_or_else(|| anyhow!("Failed to capture stderr"))?;
+        let stdout_task = tokio::spawn(stream_lines(stdout, false));
+        let stderr_task = tokio::spawn(stream_lines(stderr, true));
+
+        let (exit_code, timed_out) = match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => (status?.code(), false),
+            Err(_) => {
+                let _ = child.kill().await;
+                (None, true)
+            }
+        };
+
+        // Grandchildren may keep the pipes open after a kill, so don't wait on them forever.
+        let grace = Duration::from_secs(2);
+        let stdout = tokio::time::timeout(grace, stdout_task).await
+            .ok().and_then(|r| r.ok()).unwrap_or_default();
+        let stderr = tokio::time::timeout(grace, stderr_task).await
+            .ok().and_then(|r| r.ok()).unwrap_or_default();
+
+        Ok(CommandOutput { stdout, stderr, exit_code, timed_out })
+    }
+}
+
+/// Returns the target of a bare `cd` command (`None` for plain `cd`), or `None` overall
+/// when the line is anything else, including `cd` chained with other commands.
+fn parse_cd(command: &str) -> Option<Option<&str>> {
+    let command = command.trim();
+    if command.contains(['\n', ';', '|', '&']) {
+        return None;
+    }
+    let rest = command.strip_prefix("cd")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let target = rest.trim().trim_matches(['"', '\'']);
+    Some(if target.is_empty() { None } else { Some(target) })
+}
+
+fn resolve_dir(base: &Path, dir: &str) -> PathBuf {
+    let expanded = match dir.strip_prefix('~') {
+        Some(rest) => match dirs::home_dir() {
+            Some(home) => home.join(rest.trim_start_matches(['/', '\\'])),
+            None => PathBuf::from(dir),
+        },
+        None => PathBuf::from(dir),
+    };
+    if expanded.is_absolute() {
+        expanded
+    } else {
+        base.join(expanded)
+    }
+}
+
+async fn stream_lines<R: AsyncRead + Unpin>(reader: R, is_stderr: bool) -> String {
+    let mut reader = BufReader::new(reader);
+    let mut captured = String::new();
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf);
+                let trimmed = line.trim_end_matches(['\r', '\n']);
+                if is_stderr {
+                    eprintln!("   {}", trimmed);
+                } else {
+                    println!("   {}", trimmed);
+                }
+                captured.push_str(trimmed);
+                captured.push('\n');
+            }
+        }
+    }
+
+    captured
+}
+
+/// Keeps the head and tail of long output so the model sees both the start and the final errors.
+pub fn truncate_output(output: &str, max_chars: usize) -> String {
+    let total = output.chars().count();
+    if max_chars == 0 || total <= max_chars {
+        return output.to_string();
+    }
+
+    let half = max_chars / 2;
+    let head: String = output.chars().take(half).collect();
+    let tail: String = output.chars().skip(total - half).collect();
+    format!("{}\n... [{} characters truncated] ...\n{}", head, total - 2 * half, tail)
 }
 
 #[async_trait]
@@ -182,7 +452,23 @@ impl Tool for CommandTool {
     }
     
     fn description(&self) -> &str {
-        "Execute operating system commands with user permission. Supports both safe commands (automatically approved) and potentially dangerous commands (requires explicit user permission)."
+        "Execute operating system commands with user permission. Supports both safe commands (automatically approved) and potentially dangerous commands (requires explicit user permission). Long-running commands can be started as background jobs and checked later. Calls accept optional 'cwd', 'env' and 'shell' (sh, bash, zsh, cmd, powershell, pwsh); a bare 'cd' changes the working directory for later commands."
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Confirm
+    }
+
+    // Background jobs are processes of this instance and can't move; the working directory can
+    async fn snapshot(&self) -> Option<Value> {
+        Some(serde_json::json!({ "cwd": self.session_cwd() }))
+    }
+
+    async fn restore(&self, state: Value) -> Result<()> {
+        if let Some(cwd) = state["cwd"].as_str().map(PathBuf::from).filter(|dir| dir.is_dir()) {
+            *self.cwd.lock().unwrap() = cwd;
+        }
+        Ok(())
     }
     
     fn available_functions(&self) -> Vec<String> {
@@ -190,6 +476,11 @@ impl Tool for CommandTool {
             "execute".to_string(),
             "execute_safe".to_string(),
             "list_safe_commands".to_string(),
+            "run_background".to_string(),
+            "job_status".to_string(),
+            "job_output".to_string(),
+            "kill_job".to_string(),
+            "get_working_directory".to_string(),
         ]
     }
     
@@ -199,16 +490,20 @@ impl Tool for CommandTool {
                 let command = args.get("command")
                     .and_then(|v| v.as_str())
                     .ok_or_else(|| anyhow!("Missing 'command' argument"))?;
+                let timeout = args.get("timeout_seconds").and_then(|v| v.as_u64()).map(Duration::from_secs);
+                let options = self.exec_options(&args)?;
                 
-                self.execute_command(command).await
+                self.execute_command(command, timeout, options).await
             }
             "execute_safe" => {
                 let command = args.get("command")
                     .and_then(|v| v.as_str())
                     .ok_or_else(|| anyhow!("Missing 'command' argument"))?;
+                let timeout = args.get("timeout_seconds").and_then(|v| v.as_u64()).map(Duration::from_secs);
+                let options = self.exec_options(&args)?;
                 
                 if self.is_safe_command(command) {
-                    self.execute_command(command).await
+                    self.execute_command(command, timeout, options).await
                 } else {
                     Ok(ToolResult {
                         success: false,
@@ -221,15 +516,104 @@ impl Tool for CommandTool {
                 }
             }
             "list_safe_commands" => {
-                let safe_list: Vec<String> = self.safe_commands.iter().cloned().collect();
                 Ok(ToolResult {
                     success: true,
                     result: serde_json::json!({
-                        "safe_commands": safe_list
+                        "safe_commands": self.policy.auto_approve(),
+                        "approval_required": self.policy.approval_required(),
+                        "always_deny": self.policy.always_deny()
+                    }),
+                    metadata: None,
+                })
+            }
+            "get_working_directory" => {
+                Ok(ToolResult {
+                    success: true,
+                    result: serde_json::json!({
+                        "cwd": self.session_cwd().to_string_lossy()
                     }),
                     metadata: None,
                 })
             }
+            "run_background" => {
+                let command = args.get("command")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow!("Missing 'command' argument"))?;
+                let options = self.exec_options(&args)?;
+                
+                self.run_background(command, options).await
+            }
+            "job_status" => {
+                match args.get("job_id").and_then(|v| v.as_u64()) {
+                    Some(id) => match self.jobs.get(id as u32).await {
+                        Some(job) => Ok(ToolResult {
+                            success: true,
+                            result: serde_json::to_value(&job)?,
+                            metadata: None,
+                        }),
+                        None => Ok(ToolResult {
+                            success: false,
+                            result: serde_json::json!(format!("No job with id {}", id)),
+                            metadata: None,
+                        }),
+                    },
+                    None => {
+                        let jobs = self.jobs.list().await;
+                        Ok(ToolResult {
+                            success: true,
+                            result: serde_json::json!({
+                                "jobs": jobs
+                            }),
+                            metadata: None,
+                        })
+                    }
+                }
+            }
+            "job_output" => {
+                let id = args.get("job_id")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| anyhow!("Missing 'job_id' argument"))? as u32;
+                let lines = args.get("lines").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
+                
+                match self.jobs.output(id, lines).await {
+                    Ok(output) => Ok(ToolResult {
+                        success: true,
+                        result: serde_json::json!(truncate_output(&output, self.max_output_chars)),
+                        metadata: Some(serde_json::json!({
+                            "job_id": id,
+                            "lines": lines
+                        })),
+                    }),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: serde_json::json!(format!("Failed to read job output: {}", e)),
+                        metadata: None,
+                    }),
+                }
+            }
+            "kill_job" => {
+                let id = args.get("job_id")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| anyhow!("Missing 'job_id' argument"))? as u32;
+                
+                match self.jobs.kill(id).await {
+                    Ok(true) => Ok(ToolResult {
+                        success: true,
+                        result: serde_json::json!(format!("Job {} killed", id)),
+                        metadata: None,
+                    }),
+                    Ok(false) => Ok(ToolResult {
+                        success: false,
+                        result: serde_json::json!(format!("Job {} is not running", id)),
+                        metadata: None,
+                    }),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: serde_json::json!(e.to_string()),
+                        metadata: None,
+                    }),
+                }
+            }
             _ => Err(anyhow!("Unknown function: {}", function))
         }
     }
@@ -240,3 +624,77 @@ impl Default for CommandTool {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_output_keeps_head_and_tail() {
+        let output = "a".repeat(10) + &"b".repeat(10);
+        let truncated = truncate_output(&output, 10);
+
+        assert!(truncated.starts_with("aaaaa\n"));
+        assert!(truncated.ends_with("\nbbbbb"));
+        assert!(truncated.contains("[10 characters truncated]"));
+        assert_eq!(truncate_output("short", 10), "short");
+    }
+
+    #[test]
+    fn test_parse_cd() {
+        assert_eq!(parse_cd("cd src"), Some(Some("src")));
+        assert_eq!(parse_cd("cd \"my dir\""), Some(Some("my dir")));
+        assert_eq!(parse_cd("cd"), Some(None));
+        assert_eq!(parse_cd("cd src && ls"), None);
+        assert_eq!(parse_cd("cdrom"), None);
+    }
+
+    #[test]
+    fn test_env_and_shell_need_permission() {
+        let tool = CommandTool::new();
+        let decision = tool.policy.evaluate("git diff");
+        assert_eq!(decision, CommandDecision::AutoApprove);
+
+        let plain = tool.exec_options(&serde_json::json!({})).unwrap();
+        assert!(!tool.needs_permission(&decision, &plain));
+
+        let env = tool.exec_options(&serde_json::json!({ "env": { "GIT_EXTERNAL_DIFF": "./evil.sh" } })).unwrap();
+        assert!(tool.needs_permission(&decision, &env));
+
+        let other = if tool.default_shell == Shell::Bash { "zsh" } else { "bash" };
+        let shell = tool.exec_options(&serde_json::json!({ "shell": other })).unwrap();
+        assert!(tool.needs_permission(&decision, &shell));
+    }
+
+    #[tokio::test]
+    async fn test_cd_persists_for_later_commands() {
+        if cfg!(target_os = "windows") {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let tool = CommandTool::new();
+
+        let options = tool.exec_options(&serde_json::json!({ "cwd": dir.path() })).unwrap();
+        let result = tool.execute_command("cd sub", None, options).await.unwrap();
+        assert!(result.success);
+
+        let options = tool.exec_options(&serde_json::json!({ "env": { "AIR_TEST_VAR": "hello" } })).unwrap();
+        let output = tool.run_streaming("pwd; echo $AIR_TEST_VAR", Duration::from_secs(5), &options).await.unwrap();
+        let expected = dir.path().join("sub").canonicalize().unwrap();
+        assert_eq!(output.stdout, format!("{}\nhello\n", expected.display()));
+    }
+
+    #[tokio::test]
+    async fn test_run_streaming_kills_on_timeout() {
+        if cfg!(target_os = "windows") {
+            return;
+        }
+        let tool = CommandTool::new();
+        let options = tool.exec_options(&Value::Null).unwrap();
+        let output = tool.run_streaming("echo start; sleep 5", Duration::from_millis(300), &options).await.unwrap();
+
+        assert!(output.timed_out);
+        assert_eq!(output.stdout, "start\n");
+    }
+}
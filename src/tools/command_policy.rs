@@ -0,0 +1,209 @@
+use serde::Deserialize;
+use std::path::Path;
+use tracing::{info, warn};
+use crate::config::CommandConfig;
+
+const PROJECT_RULES_FILE: &str = ".air/commands.toml";
+
+/// Outcome of checking a command line against the configured rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandDecision {
+    AutoApprove,
+    AskUser,
+    Deny(String),
+}
+
+/// Extra rules a project can ship in `.air/commands.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct ProjectCommandRules {
+    #[serde(default)]
+    auto_approve: Vec<String>,
+    #[serde(default)]
+    approval_required: Vec<String>,
+    #[serde(default)]
+    always_deny: Vec<String>,
+}
+
+/// Allow/deny rules deciding which commands run without confirmation.
+pub struct CommandPolicy {
+    auto_approve: Vec<String>,
+    approval_required: Vec<String>,
+    always_deny: Vec<String>,
+}
+
+impl CommandPolicy {
+    pub fn new(config: &CommandConfig, project_dir: &Path) -> Self {
+        let mut policy = Self {
+            auto_approve: config.auto_approve.clone(),
+            approval_required: config.approval_required.clone(),
+            always_deny: config.always_deny.clone(),
+        };
+
+        let rules_path = project_dir.join(PROJECT_RULES_FILE);
+        if rules_path.exists() {
+            match std::fs::read_to_string(&rules_path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(toml::from_str::<ProjectCommandRules>(&content)?))
+            {
+                Ok(rules) => {
+                    info!("Loaded project command rules from {}", rules_path.display());
+                    if config.trust_project_overrides {
+                        policy.auto_approve.extend(rules.auto_approve);
+                    } else if !rules.auto_approve.is_empty() {
+                        warn!(
+                            "Ignoring auto_approve entries in {} (set command.trust_project_overrides to enable)",
+                            rules_path.display()
+                        );
+                    }
+                    policy.approval_required.extend(rules.approval_required);
+                    policy.always_deny.extend(rules.always_deny);
+                }
+                Err(e) => warn!("Failed to load {}: {}", rules_path.display(), e),
+            }
+        }
+
+        policy
+    }
+
+    pub fn auto_approve(&self) -> &[String] {
+        &self.auto_approve
+    }
+
+    pub fn approval_required(&self) -> &[String] {
+        &self.approval_required
+    }
+
+    pub fn always_deny(&self) -> &[String] {
+        &self.always_deny
+    }
+
+    /// Every segment of a chained command (`a && b | c`) must be auto-approved for the
+    /// whole line to run unattended, and any denied segment rejects the whole line.
+    pub fn evaluate(&self, command: &str) -> CommandDecision {
+        let segments = split_segments(command);
+        if segments.is_empty() {
+            return CommandDecision::AskUser;
+        }
+
+        for segment in &segments {
+            if let Some(pattern) = self.always_deny.iter().find(|p| pattern_matches(p, segment)) {
+                return CommandDecision::Deny(pattern.clone());
+            }
+        }
+
+        // Substitutions can hide arbitrary commands inside an otherwise safe one.
+        if command.contains("$(") || command.contains('`') {
+            return CommandDecision::AskUser;
+        }
+
+        let all_approved = segments.iter().all(|segment| {
+            self.auto_approve.iter().any(|p| pattern_matches(p, segment))
+                && !self.approval_required.iter().any(|p| pattern_matches(p, segment))
+        });
+
+        if all_approved {
+            CommandDecision::AutoApprove
+        } else {
+            CommandDecision::AskUser
+        }
+    }
+}
+
+fn split_segments(command: &str) -> Vec<String> {
+    command
+        .split(['\n', ';', '|', '&'])
+        .map(|s| s.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Case-insensitive wildcard match where `*` matches any run of characters and `?` one
+/// character. A trailing ` *` also matches no arguments, so `cargo *` covers bare `cargo`.
+fn pattern_matches(pattern: &str, command: &str) -> bool {
+    let pattern = pattern.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let command = command.to_lowercase();
+
+    if let Some(prefix) = pattern.strip_suffix(" *") {
+        if command == prefix {
+            return true;
+        }
+    }
+    wildcard_match(pattern.as_bytes(), command.as_bytes())
+}
+
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> CommandPolicy {
+        let dir = tempfile::TempDir::new().unwrap();
+        CommandPolicy::new(&CommandConfig::default(), dir.path())
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        assert!(pattern_matches("cargo *", "cargo"));
+        assert!(pattern_matches("cargo *", "cargo build --release"));
+        assert!(pattern_matches("mkfs.*", "mkfs.ext4"));
+        assert!(!pattern_matches("cargo *", "cargonaut"));
+        assert!(!pattern_matches("git push *", "git pull"));
+    }
+
+    #[test]
+    fn test_exceptions_require_approval() {
+        let policy = policy();
+        assert_eq!(policy.evaluate("cargo build"), CommandDecision::AutoApprove);
+        assert_eq!(policy.evaluate("cargo publish"), CommandDecision::AskUser);
+        assert_eq!(policy.evaluate("git push origin main"), CommandDecision::AskUser);
+    }
+
+    #[test]
+    fn test_chained_commands_checked_per_segment() {
+        let policy = policy();
+        assert_eq!(policy.evaluate("git status && ls"), CommandDecision::AutoApprove);
+        assert_eq!(policy.evaluate("ls; curl example.com"), CommandDecision::AskUser);
+        assert_eq!(policy.evaluate("echo $(whoami)"), CommandDecision::AskUser);
+        assert!(matches!(policy.evaluate("ls && shutdown now"), CommandDecision::Deny(_)));
+    }
+
+    #[test]
+    fn test_project_rules_only_tighten_unless_trusted() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join(".air")).unwrap();
+        std::fs::write(
+            dir.path().join(PROJECT_RULES_FILE),
+            "auto_approve = [\"make *\"]\nalways_deny = [\"git clean *\"]\n",
+        ).unwrap();
+
+        let policy = CommandPolicy::new(&CommandConfig::default(), dir.path());
+        assert_eq!(policy.evaluate("make test"), CommandDecision::AskUser);
+        assert!(matches!(policy.evaluate("git clean -fdx"), CommandDecision::Deny(_)));
+
+        let trusted = CommandConfig { trust_project_overrides: true, ..CommandConfig::default() };
+        let policy = CommandPolicy::new(&trusted, dir.path());
+        assert_eq!(policy.evaluate("make test"), CommandDecision::AutoApprove);
+    }
+}
@@ -0,0 +1,326 @@
+use super::{PermissionLevel, Tool, ToolResult};
+use crate::config::AutomationConfig;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use enigo::{Axis, Button, Coordinate, Direction, Enigo, Key, Keyboard, Mouse, Settings};
+use serde_json::{json, Value};
+use crate::utils::approval;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// One input action. Each is shown to the user and needs its own approval.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    MoveMouse { x: i32, y: i32 },
+    /// Clicks at the point, or where the pointer is.
+    Click { at: Option<(i32, i32)>, button: MouseButton, double: bool },
+    Type { text: String },
+    /// Modifiers held while the last key is pressed: `ctrl+shift+t`.
+    Hotkey { keys: Vec<String> },
+    /// Positive scrolls down.
+    Scroll { amount: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+impl Action {
+    pub fn parse(function: &str, args: &Value) -> Result<Self> {
+        let point = || -> Result<(i32, i32)> {
+            match (args["x"].as_i64(), args["y"].as_i64()) {
+                (Some(x), Some(y)) => Ok((x as i32, y as i32)),
+                _ => Err(anyhow!("Missing 'x' and 'y' screen coordinates")),
+            }
+        };
+        Ok(match function {
+            "move_mouse" => {
+                let (x, y) = point()?;
+                Action::MoveMouse { x, y }
+            }
+            "click" => {
+                let button = match args["button"].as_str().unwrap_or("left") {
+                    "left" => MouseButton::Left,
+                    "right" => MouseButton::Right,
+                    "middle" => MouseButton::Middle,
+                    other => return Err(anyhow!("Unknown mouse button '{}'", other)),
+                };
+                let at = if args["x"].is_null() && args["y"].is_null() { None } else { Some(point()?) };
+                Action::Click { at, button, double: args["double"].as_bool().unwrap_or(false) }
+            }
+            "type_text" => {
+                let text = args["text"].as_str().ok_or_else(|| anyhow!("Missing 'text' parameter"))?;
+                Action::Type { text: text.to_string() }
+            }
+            "hotkey" => {
+                let combo = args["keys"].as_str().ok_or_else(|| anyhow!("Missing 'keys' parameter (e.g. \"ctrl+s\")"))?;
+                let keys: Vec<String> = combo.split('+').map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty()).collect();
+                for key in &keys {
+                    key_code(key)?;
+                }
+                if keys.is_empty() {
+                    return Err(anyhow!("No keys in '{}'", combo));
+                }
+                Action::Hotkey { keys }
+            }
+            "scroll" => Action::Scroll {
+                amount: args["amount"].as_i64().ok_or_else(|| anyhow!("Missing 'amount' parameter"))? as i32,
+            },
+            _ => return Err(anyhow!("Unknown function: {}", function)),
+        })
+    }
+
+    /// What the approval prompt shows.
+    pub fn describe(&self) -> String {
+        match self {
+            Action::MoveMouse { x, y } => format!("move the mouse to ({}, {})", x, y),
+            Action::Click { at, button, double } => {
+                let click = if *double { "double-click" } else { "click" };
+                match at {
+                    Some((x, y)) => format!("{} {:?} at ({}, {})", click, button, x, y).to_lowercase(),
+                    None => format!("{} {:?} where the pointer is", click, button).to_lowercase(),
+                }
+            }
+            Action::Type { text } => format!("type {:?}", text),
+            Action::Hotkey { keys } => format!("press {}", keys.join("+")),
+            Action::Scroll { amount } => format!("scroll {} by {}", if *amount < 0 { "up" } else { "down" }, amount.abs()),
+        }
+    }
+
+    /// Runs the action. Blocking: the input connection is opened per action.
+    fn perform(&self) -> Result<()> {
+        let mut enigo = Enigo::new(&Settings::default()).map_err(|e| anyhow!("Can't control input: {}", e))?;
+        match self {
+            Action::MoveMouse { x, y } => enigo.move_mouse(*x, *y, Coordinate::Abs)?,
+            Action::Click { at, button, double } => {
+                if let Some((x, y)) = at {
+                    enigo.move_mouse(*x, *y, Coordinate::Abs)?;
+                }
+                let button = match button {
+                    MouseButton::Left => Button::Left,
+                    MouseButton::Right => Button::Right,
+                    MouseButton::Middle => Button::Middle,
+                };
+                for _ in 0..if *double { 2 } else { 1 } {
+                    enigo.button(button, Direction::Click)?;
+                }
+            }
+            Action::Type { text } => enigo.text(text)?,
+            Action::Hotkey { keys } => {
+                let codes = keys.iter().map(|k| key_code(k)).collect::<Result<Vec<_>>>()?;
+                let (last, modifiers) = codes.split_last().ok_or_else(|| anyhow!("No keys to press"))?;
+                for key in modifiers {
+                    enigo.key(*key, Direction::Press)?;
+                }
+                let pressed = enigo.key(*last, Direction::Click);
+                // Release the modifiers even when the key failed, so none stays held down
+                for key in modifiers.iter().rev() {
+                    enigo.key(*key, Direction::Release)?;
+                }
+                pressed?;
+            }
+            Action::Scroll { amount } => enigo.scroll(*amount, Axis::Vertical)?,
+        }
+        Ok(())
+    }
+}
+
+/// A key name from a hotkey: modifiers, named keys, F1-F20 or a single character.
+pub fn key_code(name: &str) -> Result<Key> {
+    Ok(match name {
+        "ctrl" | "control" => Key::Control,
+        "alt" | "option" => Key::Alt,
+        "shift" => Key::Shift,
+        "super" | "win" | "cmd" | "command" | "meta" => Key::Meta,
+        "enter" | "return" => Key::Return,
+        "tab" => Key::Tab,
+        "esc" | "escape" => Key::Escape,
+        "space" => Key::Space,
+        "backspace" => Key::Backspace,
+        "delete" | "del" => Key::Delete,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Unicode(c),
+                _ => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=20) => function_key(n),
+                    _ => return Err(anyhow!("Unknown key '{}'", name)),
+                },
+            }
+        }
+    })
+}
+
+fn function_key(n: u8) -> Key {
+    const KEYS: [Key; 20] = [
+        Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10,
+        Key::F11, Key::F12, Key::F13, Key::F14, Key::F15, Key::F16, Key::F17, Key::F18, Key::F19, Key::F20,
+    ];
+    KEYS[n as usize - 1]
+}
+
+/// Mouse and keyboard control, checked afterwards with a screenshot and OCR. Off unless
+/// `[automation] enabled`, and every single action asks the user first.
+pub struct DesktopTool {
+    config: AutomationConfig,
+    /// Captures the screen for `read_screen` and `expect` checks (and describes it when OCR fails).
+    screenshot: Arc<dyn Tool>,
+}
+
+impl DesktopTool {
+    pub fn with_config(config: &AutomationConfig, screenshot: Arc<dyn Tool>) -> Self {
+        Self { config: config.clone(), screenshot }
+    }
+
+    fn ask_approval(&self, action: &Action) -> bool {
+        println!("\n🖱️  Desktop action: {}", action.describe());
+        approval::confirm("⚠️  Approve this action?")
+    }
+
+    /// Text on screen: OCR of a fresh screenshot, or the screenshot tool's description of it
+    /// when the OCR command isn't available.
+    async fn read_screen(&self) -> Result<Value> {
+        let capture = self.screenshot.execute("capture", json!({})).await?;
+        let path = capture.result["filepath"].as_str()
+            .ok_or_else(|| anyhow!("{}", capture.result.as_str().unwrap_or("Screenshot failed")))?
+            .to_string();
+        let text = match self.ocr(&path).await {
+            Ok(text) => json!({ "source": "ocr", "text": text }),
+            Err(ocr_error) => {
+                let prompt = "Transcribe all readable text on this screen, then list the visible windows, dialogs and controls.";
+                let described = self.screenshot.execute("analyze", json!({"filepath": path, "prompt": prompt})).await?;
+                let Some(description) = described.result["description"].as_str() else {
+                    std::fs::remove_file(&path).ok();
+                    return Err(anyhow!("OCR failed ({}) and no vision model is available", ocr_error));
+                };
+                json!({ "source": "vision", "text": description })
+            }
+        };
+        std::fs::remove_file(&path).ok();
+        Ok(text)
+    }
+
+    async fn ocr(&self, image: &str) -> Result<String> {
+        let command = self.config.ocr_command.replace("{image}", &shell_quote(image));
+        let mut cmd = crate::tools::command::Shell::platform_default().command(&command);
+        let output = cmd.output().await.map_err(|e| anyhow!("Can't run '{}': {}", command, e))?;
+        if !output.status.success() {
+            return Err(anyhow!("'{}' failed: {}", command, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    async fn act(&self, function: &str, args: &Value) -> Result<ToolResult> {
+        let action = Action::parse(function, args)?;
+        if let Action::Type { text } = &action {
+            if text.chars().count() > self.config.max_typed_chars {
+                return Err(anyhow!("Refusing to type more than {} characters at once", self.config.max_typed_chars));
+            }
+        }
+        if !self.ask_approval(&action) {
+            return Ok(ToolResult { success: false, result: json!(format!("Not approved: {}", action.describe())), metadata: None });
+        }
+        let performed = action.clone();
+        tokio::task::spawn_blocking(move || performed.perform()).await??;
+
+        let Some(expected) = args["expect"].as_str().filter(|e| !e.trim().is_empty()) else {
+            return Ok(ToolResult { success: true, result: json!({ "done": action.describe() }), metadata: None });
+        };
+        // Let the UI catch up before looking
+        tokio::time::sleep(Duration::from_millis(self.config.settle_ms)).await;
+        let screen = self.read_screen().await?;
+        let text = screen["text"].as_str().unwrap_or_default();
+        let found = contains_text(text, expected);
+        Ok(ToolResult {
+            success: found,
+            result: json!({
+                "done": action.describe(),
+                "expected": expected,
+                "verified": found,
+                "screen_text": super::command::truncate_output(text, 4_000),
+            }),
+            metadata: Some(json!({ "source": screen["source"] })),
+        })
+    }
+}
+
+/// Case- and whitespace-insensitive, since OCR splits and spaces text unpredictably.
+pub fn contains_text(screen: &str, expected: &str) -> bool {
+    let normalize = |s: &str| s.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ");
+    normalize(screen).contains(&normalize(expected))
+}
+
+fn shell_quote(path: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+#[async_trait]
+impl Tool for DesktopTool {
+    fn name(&self) -> &str {
+        "desktop"
+    }
+
+    fn description(&self) -> &str {
+        "Controls the mouse and keyboard: move_mouse (x, y), click (x, y, button left|right|middle, double), type_text (text), hotkey (keys like \"ctrl+shift+t\") and scroll (amount, positive is down). Each accepts 'expect': text that should be on screen afterwards, checked with a screenshot and OCR. read_screen returns the text currently on screen. The user approves every action; take a screenshot first to find coordinates."
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Confirm
+    }
+
+    fn available_functions(&self) -> Vec<String> {
+        ["move_mouse", "click", "type_text", "hotkey", "scroll", "read_screen"].map(String::from).to_vec()
+    }
+
+    async fn execute(&self, function: &str, args: Value) -> Result<ToolResult> {
+        if !self.config.enabled {
+            return Ok(ToolResult {
+                success: false,
+                result: json!("Desktop automation is off; set [automation] enabled = true to allow it"),
+                metadata: None,
+            });
+        }
+        let result = match function {
+            "read_screen" => self.read_screen().await.map(|screen| ToolResult { success: true, result: screen, metadata: None }),
+            _ => self.act(function, &args).await,
+        };
+        Ok(result.unwrap_or_else(|e| ToolResult { success: false, result: json!(e.to_string()), metadata: None }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_actions() {
+        let click = Action::parse("click", &json!({"x": 10, "y": 20, "double": true})).unwrap();
+        assert_eq!(click, Action::Click { at: Some((10, 20)), button: MouseButton::Left, double: true });
+        assert_eq!(click.describe(), "double-click left at (10, 20)");
+        let hotkey = Action::parse("hotkey", &json!({"keys": "Ctrl+Shift+T"})).unwrap();
+        assert_eq!(hotkey.describe(), "press ctrl+shift+t");
+        assert!(Action::parse("hotkey", &json!({"keys": "ctrl+hyper"})).is_err());
+        assert!(Action::parse("move_mouse", &json!({"x": 1})).is_err());
+    }
+
+    #[test]
+    fn test_contains_text() {
+        assert!(contains_text("Settings\n  Dark   mode  On", "dark mode on"));
+        assert!(!contains_text("Settings", "Dark mode"));
+    }
+}
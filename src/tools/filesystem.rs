@@ -1,27 +1,41 @@
-use super::{Tool, ToolResult};
+use super::{PermissionLevel, Tool, ToolResult};
+use super::sandbox::PathSandbox;
+use crate::config::FileSystemConfig;
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
 use serde_json::{json, Value};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
+use regex::RegexBuilder;
+use globset::{Glob, GlobMatcher};
+use similar::TextDiff;
 use tracing::info;
-use std::io::{self, Write};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use crate::utils::approval;
 
 pub struct FileSystemTool {
     base_directory: String,
+    sandbox: PathSandbox,
 }
 
 impl FileSystemTool {
     pub fn new(base_directory: Option<String>) -> Self {
+        Self::with_config(base_directory, &FileSystemConfig::default())
+    }
+
+    pub fn with_config(base_directory: Option<String>, config: &FileSystemConfig) -> Self {
         let base_dir = base_directory.unwrap_or_else(|| {
             std::env::current_dir()
                 .unwrap_or_default()
                 .to_string_lossy()
                 .to_string()
         });
-        
+
+        let sandbox = PathSandbox::new(config, Path::new(&base_dir));
+
         Self {
             base_directory: base_dir,
+            sandbox,
         }
     }
     
@@ -36,26 +50,388 @@ impl FileSystemTool {
         
         let path_buf = Path::new(path);
         
-        if path_buf.is_absolute() {
-            Ok(path_buf.to_path_buf())
+        let full_path = if path_buf.is_absolute() {
+            path_buf.to_path_buf()
         } else {
-            Ok(Path::new(&self.base_directory).join(path))
+            Path::new(&self.base_directory).join(path)
+        };
+
+        self.sandbox.check(&full_path)
+    }
+
+    /// Directories that are never worth descending into during a search.
+    const SKIPPED_DIRS: &'static [&'static str] = &[".git", "target", "node_modules", ".venv", "__pycache__"];
+
+    fn collect_search_files(&self, root: &Path, glob: Option<&GlobMatcher>) -> Vec<PathBuf> {
+        // A file given as the search path is searched on its own
+        if root.is_file() {
+            return vec![root.to_path_buf()];
+        }
+        let mut files = Vec::new();
+        let mut stack = vec![root.to_path_buf()];
+
+        while let Some(dir) = stack.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if self.sandbox.check(&path).is_err() {
+                    continue;
+                }
+                // Symlinked directories are never entered, so a link back up the tree can't loop
+                // the walk; symlinked files are searched like any other
+                let Ok(file_type) = entry.file_type() else { continue };
+                if file_type.is_symlink() {
+                    if path.is_file() && glob.map_or(true, |g| g.is_match(path.strip_prefix(root).unwrap_or(&path))) {
+                        files.push(path);
+                    }
+                    continue;
+                }
+
+                if file_type.is_dir() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if !Self::SKIPPED_DIRS.contains(&name.as_str()) {
+                        stack.push(path);
+                    }
+                } else if file_type.is_file() {
+                    let relative = path.strip_prefix(root).unwrap_or(&path);
+                    if glob.map_or(true, |g| g.is_match(relative)) {
+                        files.push(path);
+                    }
+                }
+            }
+        }
+
+        files.sort();
+        files
+    }
+
+    fn search_files(&self, root: &Path, regex: &regex::Regex, glob: Option<&GlobMatcher>, context_lines: usize, max_results: usize) -> (Vec<Value>, usize, bool) {
+        let mut matches = Vec::new();
+        let mut files_searched = 0;
+        // Paths are shown relative to the searched directory (or the searched file's directory)
+        let base = if root.is_file() { root.parent().unwrap_or(root) } else { root };
+
+        for file in self.collect_search_files(root, glob) {
+            let size = fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+            if self.sandbox.check_size(size).is_err() {
+                continue;
+            }
+
+            let bytes = match fs::read(&file) {
+                Ok(bytes) => bytes,
+                Err(_) => continue,
+            };
+            // Skip binary files
+            if bytes.iter().take(8000).any(|b| *b == 0) {
+                continue;
+            }
+            files_searched += 1;
+
+            let content = String::from_utf8_lossy(&bytes);
+            let lines: Vec<&str> = content.lines().collect();
+            let display_path = file.strip_prefix(base).unwrap_or(&file).to_string_lossy().to_string();
+
+            for (idx, line) in lines.iter().enumerate() {
+                if !regex.is_match(line) {
+                    continue;
+                }
+
+                if matches.len() >= max_results {
+                    return (matches, files_searched, true);
+                }
+
+                let before_start = idx.saturating_sub(context_lines);
+                let after_end = (idx + 1 + context_lines).min(lines.len());
+
+                matches.push(json!({
+                    "file": display_path,
+                    "line_number": idx + 1,
+                    "line": line,
+                    "before": lines[before_start..idx],
+                    "after": lines[idx + 1..after_end],
+                }));
+            }
+        }
+
+        (matches, files_searched, false)
+    }
+
+    /// Checks a copy or move before anything is asked or written: the destination may not sit
+    /// inside the source (or, when replacing, the source inside the destination), and every
+    /// entry must pass the sandbox and size limit. Symlinks are copied as links, not followed.
+    fn plan_transfer(&self, source: &Path, destination: &Path, overwrite: bool) -> Result<Vec<CopyStep>> {
+        if destination.starts_with(source) {
+            return Err(anyhow!("Cannot copy or move {} into itself", source.display()));
+        }
+        if overwrite && source.starts_with(destination) {
+            return Err(anyhow!("Cannot replace {}: it contains the source", destination.display()));
+        }
+
+        let mut steps = Vec::new();
+        let mut stack = vec![(source.to_path_buf(), destination.to_path_buf())];
+        while let Some((from, to)) = stack.pop() {
+            self.sandbox.check(&to)?;
+            let metadata = fs::symlink_metadata(&from)?;
+            if metadata.file_type().is_symlink() {
+                // Its directory was checked already; checking the link itself would follow it
+                steps.push(CopyStep::Link(fs::read_link(&from)?, to));
+                continue;
+            }
+            self.sandbox.check(&from)?;
+            if metadata.is_dir() {
+                steps.push(CopyStep::Dir(to.clone()));
+                for entry in fs::read_dir(&from)? {
+                    let entry = entry?;
+                    stack.push((entry.path(), to.join(entry.file_name())));
+                }
+            } else {
+                self.sandbox.check_size(metadata.len())?;
+                steps.push(CopyStep::File(from, to));
+            }
+        }
+        Ok(steps)
+    }
+
+    /// Copies or moves along a checked plan, first removing the destination when replacing it.
+    fn transfer(source: &Path, destination: &Path, steps: &[CopyStep], is_move: bool, overwrite: bool) -> io::Result<()> {
+        if overwrite {
+            match fs::symlink_metadata(destination) {
+                Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(destination)?,
+                Ok(_) => fs::remove_file(destination)?,
+                Err(_) => {}
+            }
+        }
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // rename() fails across filesystems, so fall back to copy + remove
+        if is_move && fs::rename(source, destination).is_ok() {
+            return Ok(());
+        }
+        for step in steps {
+            match step {
+                CopyStep::Dir(to) => fs::create_dir_all(to)?,
+                CopyStep::File(from, to) => {
+                    fs::copy(from, to)?;
+                }
+                CopyStep::Link(target, to) => copy_link(target, to)?,
+            }
+        }
+        if is_move {
+            if source.is_dir() {
+                fs::remove_dir_all(source)?;
+            } else {
+                fs::remove_file(source)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_lines(content: &str, line: usize, text: &str) -> Result<String> {
+        let mut lines: Vec<String> = content.split_inclusive('\n').map(String::from).collect();
+        if line == 0 || line > lines.len() + 1 {
+            return Err(anyhow!("Line {} is out of range (file has {} lines)", line, lines.len()));
+        }
+
+        let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+        let mut inserted = with_line_endings(content, text);
+        if !inserted.ends_with('\n') {
+            inserted.push_str(newline);
+        }
+
+        let index = line - 1;
+        if index == lines.len() {
+            if let Some(last) = lines.last_mut() {
+                if !last.ends_with('\n') {
+                    last.push_str(newline);
+                }
+            }
+        }
+        lines.insert(index, inserted);
+
+        Ok(lines.concat())
+    }
+
+    /// Shows a unified diff of the change, asks for approval and writes the new content.
+    fn write_with_diff(&self, path: &str, full_path: &Path, original: &str, updated: &str) -> Result<ToolResult> {
+        self.sandbox.check_size(updated.len() as u64)?;
+
+        let diff = TextDiff::from_lines(original, updated)
+            .unified_diff()
+            .context_radius(3)
+            .header(path, path)
+            .to_string();
+
+        println!("\n📝 Proposed changes to {}:\n{}", path, diff);
+
+        if !self.ask_confirmation("APPLY these changes to", path) {
+            return Ok(ToolResult {
+                success: false,
+                result: json!("Operation cancelled by user."),
+                metadata: Some(json!({"path": path, "diff": diff})),
+            });
+        }
+
+        match fs::write(full_path, updated) {
+            Ok(_) => Ok(ToolResult {
+                success: true,
+                result: json!({
+                    "message": format!("Successfully edited {}", path),
+                    "diff": diff
+                }),
+                metadata: Some(json!({
+                    "path": path,
+                    "bytes_written": updated.len()
+                })),
+            }),
+            Err(e) => Ok(ToolResult {
+                success: false,
+                result: json!(format!("Failed to write file: {}", e)),
+                metadata: None,
+            })
         }
     }
 
     fn ask_confirmation(&self, action: &str, path: &str) -> bool {
-        // In some environments (e.g. tests), stdin might not be interactive.
-        // But for this CLI tool, we assume it is.
-        print!("⚠️  Confirmation required: Do you want to {} '{}'? [y/N] ", action, path);
-        io::stdout().flush().unwrap_or(());
-
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_ok() {
-            let response = input.trim().to_lowercase();
-            return response == "y" || response == "yes";
+        approval::confirm(&format!("⚠️  Confirmation required: Do you want to {} '{}'?", action, path))
+    }
+}
+
+/// `text` with CRLF line endings when `content` uses them.
+fn with_line_endings(content: &str, text: &str) -> String {
+    if content.contains("\r\n") {
+        text.replace("\r\n", "\n").replace('\n', "\r\n")
+    } else {
+        text.to_string()
+    }
+}
+
+/// One entry of a checked copy, in the order it is written.
+enum CopyStep {
+    Dir(PathBuf),
+    File(PathBuf, PathBuf),
+    /// Link target, new link.
+    Link(PathBuf, PathBuf),
+}
+
+#[cfg(unix)]
+fn copy_link(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn copy_link(_target: &Path, link: &Path) -> io::Result<()> {
+    tracing::warn!("Skipping symlink {}", link.display());
+    Ok(())
+}
+
+const HEX_PREVIEW_BYTES: usize = 256;
+/// Upper bound on lines returned by a single `read_lines`/`tail` call.
+const MAX_RANGE_LINES: usize = 2000;
+/// Most of the file's end a `tail` call reads.
+const MAX_TAIL_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Reads lines `start..=end` (1-based) without loading the whole file.
+/// Returns the lines and whether the file continues past `end`.
+fn read_line_range(path: &Path, start: usize, end: usize) -> io::Result<(Vec<String>, bool)> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut lines = Vec::new();
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            return Ok((lines, false));
+        }
+        line_number += 1;
+        if line_number > end {
+            return Ok((lines, true));
         }
-        false
+        if line_number >= start {
+            let line = String::from_utf8_lossy(&buf);
+            lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+        }
+    }
+}
+
+/// Reads the last `n` lines by scanning backwards from the end of the file in blocks, reading
+/// at most `max_bytes`. A last line longer than that comes back as its final `max_bytes`.
+fn read_tail(path: &Path, n: usize, max_bytes: u64) -> io::Result<Vec<String>> {
+    const BLOCK: u64 = 64 * 1024;
+
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut pos = len;
+    // Newest block first
+    let mut blocks: Vec<Vec<u8>> = Vec::new();
+    let mut newlines = 0;
+
+    // Collect blocks until we have more than n newlines, the start of the file or the byte cap
+    while pos > 0 && newlines <= n && len - pos < max_bytes {
+        let read_size = BLOCK.min(pos).min(max_bytes - (len - pos));
+        pos -= read_size;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut block = vec![0u8; read_size as usize];
+        file.read_exact(&mut block)?;
+        newlines += block.iter().filter(|b| **b == b'\n').count();
+        blocks.push(block);
+    }
+
+    let data: Vec<u8> = blocks.into_iter().rev().flatten().collect();
+    let text = String::from_utf8_lossy(&data);
+    let mut lines: Vec<&str> = text.lines().collect();
+    // Stopped by the cap, the first line is only the end of one; keep it only when it's all there is
+    if pos > 0 && newlines <= n && lines.len() > 1 {
+        lines.remove(0);
+    }
+    let skip = lines.len().saturating_sub(n);
+    Ok(lines[skip..].iter().map(|l| l.to_string()).collect())
+}
+
+/// Decodes file bytes to text, returning the detected encoding name, or `None` for binary data.
+pub(crate) fn decode_text(bytes: &[u8]) -> Option<(String, &'static str)> {
+    if let Some((encoding, bom_len)) = encoding_rs::Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return Some((text.into_owned(), encoding.name()));
+    }
+
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Some((text.to_string(), "UTF-8"));
+    }
+
+    if bytes.iter().take(8000).any(|b| *b == 0) {
+        return None;
     }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    if had_errors {
+        return None;
+    }
+    Some((text.into_owned(), encoding.name()))
+}
+
+/// Classic `offset  hex bytes  |ascii|` dump of the first `limit` bytes.
+fn hex_preview(bytes: &[u8], limit: usize) -> String {
+    bytes[..bytes.len().min(limit)]
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk.iter()
+                .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+                .collect();
+            format!("{:08x}  {:<47}  |{}|", i * 16, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[async_trait]
@@ -65,7 +441,11 @@ impl Tool for FileSystemTool {
     }
     
     fn description(&self) -> &str {
-        "File system operations: read, write, list files and directories"
+        "File system operations: read, write, edit, copy, move, delete (to trash), list and search files and directories"
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Modifies
     }
     
     fn available_functions(&self) -> Vec<String> {
@@ -76,6 +456,14 @@ impl Tool for FileSystemTool {
             "file_exists".to_string(),
             "get_file_info".to_string(),
             "create_directory".to_string(),
+            "search_files".to_string(),
+            "edit_file".to_string(),
+            "insert_at_line".to_string(),
+            "copy".to_string(),
+            "move".to_string(),
+            "delete".to_string(),
+            "read_lines".to_string(),
+            "tail".to_string(),
         ]
     }
     
@@ -87,19 +475,92 @@ impl Tool for FileSystemTool {
                 
                 info!("Reading file: {}", path);
                 let full_path = self.get_full_path(path)?;
+                if let Ok(metadata) = fs::metadata(&full_path) {
+                    self.sandbox.check_size(metadata.len())
+                        .map_err(|e| anyhow!("{}. Use 'read_lines' or 'tail' to page through large files", e))?;
+                }
                 
-                match fs::read_to_string(&full_path) {
-                    Ok(content) => {
+                match fs::read(&full_path) {
+                    Ok(bytes) => match decode_text(&bytes) {
+                        Some((content, encoding)) => {
+                            let metadata = json!({
+                                "path": path,
+                                "size": bytes.len(),
+                                "lines": content.lines().count(),
+                                "encoding": encoding,
+                                "binary": false
+                            });
+                            
+                            Ok(ToolResult {
+                                success: true,
+                                result: json!({
+                                    "content": content,
+                                    "metadata": metadata
+                                }),
+                                metadata: Some(metadata),
+                            })
+                        }
+                        None => {
+                            let metadata = json!({
+                                "path": path,
+                                "size": bytes.len(),
+                                "binary": true
+                            });
+
+                            Ok(ToolResult {
+                                success: true,
+                                result: json!({
+                                    "message": "Binary file; showing a hex preview of the first bytes",
+                                    "hex_preview": hex_preview(&bytes, HEX_PREVIEW_BYTES),
+                                    "metadata": metadata
+                                }),
+                                metadata: Some(metadata),
+                            })
+                        }
+                    },
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Failed to read file: {}", e)),
+                        metadata: None,
+                    })
+                }
+            }
+            
+            "read_lines" => {
+                let path = args["path"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'path' parameter"))?;
+                let start = args["start"].as_u64().unwrap_or(1).max(1) as usize;
+                let requested_end = args["end"].as_u64()
+                    .map(|e| e as usize)
+                    .unwrap_or(start.saturating_add(199));
+                let end = requested_end.min(start.saturating_add(MAX_RANGE_LINES - 1));
+
+                if end < start {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Invalid range: end ({}) is before start ({})", end, start)),
+                        metadata: None,
+                    });
+                }
+
+                info!("Reading lines {}-{} of {}", start, end, path);
+                let full_path = self.get_full_path(path)?;
+
+                match read_line_range(&full_path, start, end) {
+                    Ok((lines, has_more)) => {
                         let metadata = json!({
                             "path": path,
-                            "size": content.len(),
-                            "lines": content.lines().count()
+                            "start": start,
+                            "end": start.saturating_add(lines.len().saturating_sub(1)),
+                            "returned_lines": lines.len(),
+                            "has_more": has_more,
+                            "clamped": end < requested_end
                         });
-                        
+
                         Ok(ToolResult {
                             success: true,
                             result: json!({
-                                "content": content,
+                                "content": lines.join("\n"),
                                 "metadata": metadata
                             }),
                             metadata: Some(metadata),
@@ -112,13 +573,48 @@ impl Tool for FileSystemTool {
                     })
                 }
             }
-            
+
+            "tail" => {
+                let path = args["path"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'path' parameter"))?;
+                let n = (args["n"].as_u64().unwrap_or(50) as usize).min(MAX_RANGE_LINES);
+
+                info!("Reading last {} lines of {}", n, path);
+                let full_path = self.get_full_path(path)?;
+
+                match read_tail(&full_path, n, MAX_TAIL_BYTES) {
+                    Ok(lines) => {
+                        let metadata = json!({
+                            "path": path,
+                            "returned_lines": lines.len()
+                        });
+
+                        Ok(ToolResult {
+                            success: true,
+                            result: json!({
+                                "content": lines.join("\n"),
+                                "metadata": metadata
+                            }),
+                            metadata: Some(metadata),
+                        })
+                    }
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Failed to read file: {}", e)),
+                        metadata: None,
+                    })
+                }
+            }
+
             "write_file" => {
                 let path = args["path"].as_str()
                     .ok_or_else(|| anyhow!("Missing 'path' parameter"))?;
                 let content = args["content"].as_str()
                     .ok_or_else(|| anyhow!("Missing 'content' parameter"))?;
                 
+                let full_path = self.get_full_path(path)?;
+                self.sandbox.check_size(content.len() as u64)?;
+
                 if !self.ask_confirmation("WRITE to file", path) {
                      return Ok(ToolResult {
                         success: false,
@@ -128,7 +624,6 @@ impl Tool for FileSystemTool {
                 }
 
                 info!("Writing file: {}", path);
-                let full_path = self.get_full_path(path)?;
                 
                 if let Some(parent) = full_path.parent() {
                     fs::create_dir_all(parent)?;
@@ -249,6 +744,8 @@ impl Tool for FileSystemTool {
                 let path = args["path"].as_str()
                     .ok_or_else(|| anyhow!("Missing 'path' parameter"))?;
                 
+                let full_path = self.get_full_path(path)?;
+
                 if !self.ask_confirmation("CREATE directory", path) {
                      return Ok(ToolResult {
                         success: false,
@@ -256,8 +753,6 @@ impl Tool for FileSystemTool {
                         metadata: None,
                     });
                 }
-
-                let full_path = self.get_full_path(path)?;
                 
                 match fs::create_dir_all(&full_path) {
                     Ok(_) => Ok(ToolResult {
@@ -273,7 +768,497 @@ impl Tool for FileSystemTool {
                 }
             }
             
+            "search_files" => {
+                let pattern = args["pattern"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'pattern' parameter"))?;
+                let path = args["path"].as_str().unwrap_or(".");
+                let context_lines = args["context_lines"].as_u64().unwrap_or(0) as usize;
+                let max_results = args["max_results"].as_u64().unwrap_or(50) as usize;
+                let case_insensitive = args["case_insensitive"].as_bool().unwrap_or(false);
+
+                let regex = match RegexBuilder::new(pattern).case_insensitive(case_insensitive).build() {
+                    Ok(regex) => regex,
+                    Err(e) => return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Invalid search pattern: {}", e)),
+                        metadata: None,
+                    }),
+                };
+
+                let glob = match args["glob"].as_str() {
+                    Some(g) => match Glob::new(g) {
+                        Ok(glob) => Some(glob.compile_matcher()),
+                        Err(e) => return Ok(ToolResult {
+                            success: false,
+                            result: json!(format!("Invalid glob: {}", e)),
+                            metadata: None,
+                        }),
+                    },
+                    None => None,
+                };
+
+                info!("Searching '{}' for /{}/", path, pattern);
+                let full_path = self.get_full_path(path)?;
+
+                let (matches, files_searched, truncated) = self.search_files(&full_path, &regex, glob.as_ref(), context_lines, max_results);
+
+                let metadata = json!({
+                    "pattern": pattern,
+                    "path": path,
+                    "files_searched": files_searched,
+                    "match_count": matches.len(),
+                    "truncated": truncated
+                });
+
+                Ok(ToolResult {
+                    success: true,
+                    result: json!({
+                        "matches": matches,
+                        "truncated": truncated
+                    }),
+                    metadata: Some(metadata),
+                })
+            }
+            
+            "edit_file" => {
+                let path = args["path"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'path' parameter"))?;
+                let old_text = args["old_text"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'old_text' parameter"))?;
+                let new_text = args["new_text"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'new_text' parameter"))?;
+                let replace_all = args["replace_all"].as_bool().unwrap_or(false);
+
+                if old_text.is_empty() {
+                    return Err(anyhow!("'old_text' must not be empty"));
+                }
+
+                info!("Editing file: {}", path);
+                let full_path = self.get_full_path(path)?;
+                if let Ok(metadata) = fs::metadata(&full_path) {
+                    self.sandbox.check_size(metadata.len())?;
+                }
+                let original = match fs::read_to_string(&full_path) {
+                    Ok(content) => content,
+                    Err(e) => return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Failed to read file: {}", e)),
+                        metadata: None,
+                    }),
+                };
+
+                // The model writes `\n`; a CRLF file needs its own line endings to match and stay consistent
+                let old_text = &with_line_endings(&original, old_text);
+                let new_text = &with_line_endings(&original, new_text);
+                let occurrences = original.matches(old_text.as_str()).count();
+                if occurrences == 0 {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("'old_text' was not found in {}", path)),
+                        metadata: Some(json!({"path": path, "occurrences": 0})),
+                    });
+                }
+                if occurrences > 1 && !replace_all {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("'old_text' occurs {} times in {}. Include more surrounding context or set 'replace_all': true", occurrences, path)),
+                        metadata: Some(json!({"path": path, "occurrences": occurrences})),
+                    });
+                }
+
+                let updated = if replace_all {
+                    original.replace(old_text.as_str(), new_text)
+                } else {
+                    original.replacen(old_text.as_str(), new_text, 1)
+                };
+
+                self.write_with_diff(path, &full_path, &original, &updated)
+            }
+
+            "insert_at_line" => {
+                let path = args["path"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'path' parameter"))?;
+                let line = args["line"].as_u64()
+                    .ok_or_else(|| anyhow!("Missing 'line' parameter"))? as usize;
+                let text = args["text"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'text' parameter"))?;
+
+                info!("Inserting into file: {} at line {}", path, line);
+                let full_path = self.get_full_path(path)?;
+                if let Ok(metadata) = fs::metadata(&full_path) {
+                    self.sandbox.check_size(metadata.len())?;
+                }
+                let original = match fs::read_to_string(&full_path) {
+                    Ok(content) => content,
+                    Err(e) => return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Failed to read file: {}", e)),
+                        metadata: None,
+                    }),
+                };
+
+                let updated = match Self::insert_lines(&original, line, text) {
+                    Ok(updated) => updated,
+                    Err(e) => return Ok(ToolResult {
+                        success: false,
+                        result: json!(e.to_string()),
+                        metadata: None,
+                    }),
+                };
+
+                self.write_with_diff(path, &full_path, &original, &updated)
+            }
+            
+            "copy" | "move" => {
+                let source = args["source"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'source' parameter"))?;
+                let destination = args["destination"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'destination' parameter"))?;
+                let overwrite = args["overwrite"].as_bool().unwrap_or(false);
+
+                let source_path = self.get_full_path(source)?;
+                let destination_path = self.get_full_path(destination)?;
+
+                if !source_path.exists() {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Source does not exist: {}", source)),
+                        metadata: None,
+                    });
+                }
+                if destination_path.exists() && !overwrite {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Destination already exists: {}. Set 'overwrite': true to replace it", destination)),
+                        metadata: None,
+                    });
+                }
+
+                let steps = match self.plan_transfer(&source_path, &destination_path, overwrite) {
+                    Ok(steps) => steps,
+                    Err(e) => return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Cannot {} {}: {}", function, source, e)),
+                        metadata: None,
+                    }),
+                };
+
+                let action = if function == "copy" { "COPY" } else { "MOVE" };
+                let action = if overwrite && destination_path.exists() { format!("{} (replacing it)", action) } else { action.to_string() };
+                if !self.ask_confirmation(&format!("{} '{}' to", action, source), destination) {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!("Operation cancelled by user."),
+                        metadata: None,
+                    });
+                }
+
+                info!("{} {} -> {}", action, source, destination);
+                let outcome = Self::transfer(&source_path, &destination_path, &steps, function == "move", overwrite);
+
+                match outcome {
+                    Ok(_) => Ok(ToolResult {
+                        success: true,
+                        result: json!(format!("{} {} to {}", if function == "copy" { "Copied" } else { "Moved" }, source, destination)),
+                        metadata: Some(json!({"source": source, "destination": destination})),
+                    }),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Failed to {} {}: {}", function, source, e)),
+                        metadata: None,
+                    })
+                }
+            }
+
+            "delete" => {
+                let path = args["path"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'path' parameter"))?;
+                let permanent = args["permanent"].as_bool().unwrap_or(false);
+
+                let full_path = self.get_full_path(path)?;
+                if !full_path.exists() {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Path does not exist: {}", path)),
+                        metadata: None,
+                    });
+                }
+
+                if permanent {
+                    if !self.ask_confirmation("PERMANENTLY DELETE (cannot be undone)", path) {
+                        return Ok(ToolResult {
+                            success: false,
+                            result: json!("Operation cancelled by user."),
+                            metadata: None,
+                        });
+                    }
+
+                    info!("Permanently deleting: {}", path);
+                    let outcome = if full_path.is_dir() {
+                        fs::remove_dir_all(&full_path)
+                    } else {
+                        fs::remove_file(&full_path)
+                    };
+
+                    return match outcome {
+                        Ok(_) => Ok(ToolResult {
+                            success: true,
+                            result: json!(format!("Permanently deleted {}", path)),
+                            metadata: Some(json!({"path": path, "permanent": true})),
+                        }),
+                        Err(e) => Ok(ToolResult {
+                            success: false,
+                            result: json!(format!("Failed to delete {}: {}", path, e)),
+                            metadata: None,
+                        })
+                    };
+                }
+
+                info!("Moving to trash: {}", path);
+                match trash::delete(&full_path) {
+                    Ok(_) => Ok(ToolResult {
+                        success: true,
+                        result: json!(format!("Moved {} to the trash", path)),
+                        metadata: Some(json!({"path": path, "permanent": false})),
+                    }),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Failed to move {} to the trash: {}. Use 'permanent': true to delete it outright", path, e)),
+                        metadata: None,
+                    })
+                }
+            }
+            
             _ => Err(anyhow!("Unknown function: {}", function))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_text_detects_utf8_and_bom() {
+        let (text, encoding) = decode_text("héllo".as_bytes()).unwrap();
+        assert_eq!(text, "héllo");
+        assert_eq!(encoding, "UTF-8");
+
+        // UTF-16LE with BOM
+        let bytes = [0xFF, 0xFE, b'h', 0x00, b'i', 0x00];
+        let (text, encoding) = decode_text(&bytes).unwrap();
+        assert_eq!(text, "hi");
+        assert_eq!(encoding, "UTF-16LE");
+    }
+
+    #[test]
+    fn test_decode_text_rejects_binary() {
+        let bytes = [0x89, b'P', b'N', b'G', 0x00, 0x00, 0x01, 0xFF];
+        assert!(decode_text(&bytes).is_none());
+
+        let preview = hex_preview(&bytes, 256);
+        assert!(preview.starts_with("00000000  89 50 4e 47"));
+        assert!(preview.ends_with("|.PNG....|"));
+    }
+
+    #[test]
+    fn test_line_range_and_tail() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("log.txt");
+        let content: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&path, content).unwrap();
+
+        let (lines, has_more) = read_line_range(&path, 10, 12).unwrap();
+        assert_eq!(lines, vec!["line 10", "line 11", "line 12"]);
+        assert!(has_more);
+
+        let tail = read_tail(&path, 2, MAX_TAIL_BYTES).unwrap();
+        assert_eq!(tail, vec!["line 99", "line 100"]);
+
+        // Huge start lines don't overflow
+        let (lines, has_more) = read_line_range(&path, usize::MAX - 5, usize::MAX).unwrap();
+        assert!(lines.is_empty());
+        assert!(!has_more);
+    }
+
+    #[test]
+    fn test_tail_is_capped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("one-line.txt");
+        fs::write(&path, "x".repeat(90) + "0123456789").unwrap();
+        assert_eq!(read_tail(&path, 5, 10).unwrap(), vec!["0123456789"]);
+
+        // The cap cuts into "line 97"; that partial line is dropped
+        let path = dir.path().join("log.txt");
+        let content: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&path, content).unwrap();
+        assert_eq!(read_tail(&path, 10, 28).unwrap(), vec!["line 98", "line 99", "line 100"]);
+    }
+
+    fn sandboxed_tool(root: &Path, denied: &[&str], max_file_size_bytes: u64) -> FileSystemTool {
+        let config = FileSystemConfig {
+            sandbox_enabled: true,
+            workspace_roots: vec![root.to_string_lossy().to_string()],
+            denied_globs: denied.iter().map(|g| g.to_string()).collect(),
+            max_file_size_bytes,
+        };
+        FileSystemTool::with_config(Some(root.to_string_lossy().to_string()), &config)
+    }
+
+    #[test]
+    fn test_insert_lines() {
+        assert_eq!(FileSystemTool::insert_lines("a\nb\n", 2, "x").unwrap(), "a\nx\nb\n");
+        assert_eq!(FileSystemTool::insert_lines("a\nb", 3, "x").unwrap(), "a\nb\nx\n");
+        assert_eq!(FileSystemTool::insert_lines("", 1, "x").unwrap(), "x\n");
+        assert!(FileSystemTool::insert_lines("a\nb\n", 0, "x").is_err());
+        assert!(FileSystemTool::insert_lines("a\nb\n", 4, "x").is_err());
+
+        // CRLF files keep CRLF, including for multi-line inserts
+        assert_eq!(FileSystemTool::insert_lines("a\r\nb", 2, "x\ny").unwrap(), "a\r\nx\r\ny\r\nb");
+        assert_eq!(FileSystemTool::insert_lines("a\r\nb", 3, "x").unwrap(), "a\r\nb\r\nx\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_edit_file_needs_one_match() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("main.rs"), "let a = 1;\nlet a = 1;\n").unwrap();
+        let tool = sandboxed_tool(&root, &[], 0);
+
+        let missing = tool.execute("edit_file", json!({ "path": "main.rs", "old_text": "let b", "new_text": "x" })).await.unwrap();
+        assert!(!missing.success);
+        assert_eq!(missing.metadata.unwrap()["occurrences"], 0);
+
+        let ambiguous = tool.execute("edit_file", json!({ "path": "main.rs", "old_text": "let a = 1;", "new_text": "x" })).await.unwrap();
+        assert!(!ambiguous.success);
+        assert_eq!(ambiguous.metadata.unwrap()["occurrences"], 2);
+        assert_eq!(fs::read_to_string(root.join("main.rs")).unwrap(), "let a = 1;\nlet a = 1;\n");
+
+        let out_of_range = tool.execute("insert_at_line", json!({ "path": "main.rs", "line": 10, "text": "x" })).await.unwrap();
+        assert!(!out_of_range.success);
+    }
+
+    #[tokio::test]
+    async fn test_edits_check_the_size_before_reading() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("big.txt"), "x".repeat(64)).unwrap();
+        let tool = sandboxed_tool(&root, &[], 16);
+
+        assert!(tool.execute("edit_file", json!({ "path": "big.txt", "old_text": "x", "new_text": "y" })).await.is_err());
+        assert!(tool.execute("insert_at_line", json!({ "path": "big.txt", "line": 1, "text": "y" })).await.is_err());
+        assert_eq!(fs::read_to_string(root.join("big.txt")).unwrap(), "x".repeat(64));
+    }
+
+    #[test]
+    fn test_line_endings_follow_the_file() {
+        let crlf = "fn main() {\r\n    run();\r\n}\r\n";
+        let old_text = with_line_endings(crlf, "fn main() {\n    run();");
+        assert_eq!(crlf.matches(old_text.as_str()).count(), 1);
+        assert_eq!(with_line_endings(crlf, "a\r\nb\nc"), "a\r\nb\r\nc");
+        assert_eq!(with_line_endings("unix\n", "a\nb"), "a\nb");
+    }
+
+    #[test]
+    fn test_search_single_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("notes.txt"), "alpha\nbeta\n").unwrap();
+        fs::write(root.join("other.txt"), "beta\n").unwrap();
+        let tool = sandboxed_tool(&root, &[], 0);
+
+        let regex = regex::Regex::new("beta").unwrap();
+        let (matches, files_searched, truncated) = tool.search_files(&root.join("notes.txt"), &regex, None, 0, 50);
+        assert_eq!(files_searched, 1);
+        assert!(!truncated);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0]["file"], "notes.txt");
+        assert_eq!(matches[0]["line_number"], 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_search_survives_symlink_loop() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn needle() {}\n").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("src/up")).unwrap();
+        let tool = sandboxed_tool(&root, &[], 0);
+
+        let regex = regex::Regex::new("needle").unwrap();
+        let (matches, files_searched, _) = tool.search_files(&root, &regex, None, 0, 50);
+        assert_eq!(files_searched, 1);
+        assert_eq!(matches.len(), 1);
+    }
+
+    #[test]
+    fn test_copy_into_itself_is_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        let tool = sandboxed_tool(&root, &[], 0);
+
+        assert!(tool.plan_transfer(&root.join("src"), &root.join("src/nested/copy"), false).is_err());
+        assert!(tool.plan_transfer(&root.join("src"), &root.join("src"), true).is_err());
+        assert!(tool.plan_transfer(&root.join("src/nested"), &root.join("src"), true).is_err());
+        assert!(tool.plan_transfer(&root.join("src"), &root.join("copy"), false).is_ok());
+    }
+
+    #[test]
+    fn test_copy_checks_every_entry() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("certs/deep")).unwrap();
+        fs::write(root.join("certs/readme.txt"), "hi").unwrap();
+        fs::write(root.join("certs/deep/server.pem"), "key").unwrap();
+
+        let tool = sandboxed_tool(&root, &["**/*.pem"], 0);
+        assert!(tool.plan_transfer(&root.join("certs"), &root.join("out"), false).is_err());
+
+        fs::remove_file(root.join("certs/deep/server.pem")).unwrap();
+        fs::write(root.join("certs/deep/big.bin"), vec![0u8; 64]).unwrap();
+        let tool = sandboxed_tool(&root, &[], 16);
+        assert!(tool.plan_transfer(&root.join("certs"), &root.join("out"), false).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_keeps_symlinks_as_links() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let outside = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let secret = outside.path().join("secret.txt");
+        fs::write(&secret, "secret").unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        std::os::unix::fs::symlink(&secret, root.join("src/link")).unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("src/loop")).unwrap();
+
+        let tool = sandboxed_tool(&root, &[], 0);
+        let steps = tool.plan_transfer(&root.join("src"), &root.join("out"), false).unwrap();
+        FileSystemTool::transfer(&root.join("src"), &root.join("out"), &steps, false, false).unwrap();
+
+        let copied = root.join("out/link");
+        assert!(fs::symlink_metadata(&copied).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&copied).unwrap(), secret);
+        assert!(fs::symlink_metadata(root.join("out/loop")).unwrap().file_type().is_symlink());
+    }
+
+    #[test]
+    fn test_overwrite_replaces_directory() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("new")).unwrap();
+        fs::write(root.join("new/a.txt"), "new").unwrap();
+        fs::create_dir_all(root.join("old")).unwrap();
+        fs::write(root.join("old/stale.txt"), "stale").unwrap();
+
+        let tool = sandboxed_tool(&root, &[], 0);
+        let steps = tool.plan_transfer(&root.join("new"), &root.join("old"), true).unwrap();
+        FileSystemTool::transfer(&root.join("new"), &root.join("old"), &steps, false, true).unwrap();
+
+        assert_eq!(fs::read_to_string(root.join("old/a.txt")).unwrap(), "new");
+        assert!(!root.join("old/stale.txt").exists());
+        assert!(root.join("new/a.txt").exists());
+    }
+}
@@ -0,0 +1,179 @@
+use super::{Tool, ToolResult};
+use super::command::truncate_output;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Diff text returned to the model by default; longer diffs keep their head and tail.
+const DEFAULT_MAX_CHARS: usize = 20_000;
+const DEFAULT_LOG_LIMIT: u64 = 10;
+
+/// Read-only git: status, diffs, history. Commits are made by `air commit`, not by the model.
+pub struct GitTool {
+    repo: PathBuf,
+}
+
+impl GitTool {
+    /// Works on the repository containing the current directory.
+    pub fn new() -> Self {
+        Self::in_dir(std::env::current_dir().unwrap_or_default())
+    }
+
+    pub fn in_dir(repo: impl Into<PathBuf>) -> Self {
+        Self { repo: repo.into() }
+    }
+
+    pub fn repo(&self) -> &Path {
+        &self.repo
+    }
+
+    /// Runs git with `args` in the repository, failing with its stderr.
+    pub async fn git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git").arg("-C").arg(&self.repo).args(args).output().await
+            .map_err(|e| anyhow!("Can't run git: {}", e))?;
+        if !output.status.success() {
+            return Err(anyhow!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    async fn status(&self) -> Result<Value> {
+        let porcelain = self.git(&["status", "--porcelain=v1", "--branch"]).await?;
+        Ok(parse_status(&porcelain))
+    }
+
+    /// The working tree (or, with `staged`, the index) against HEAD, optionally for one path.
+    pub async fn diff(&self, staged: bool, path: Option<&str>, max_chars: usize) -> Result<Value> {
+        let mut args = vec!["diff", "--no-color", "--no-ext-diff"];
+        if staged {
+            args.push("--cached");
+        }
+        let mut stat_args = args.clone();
+        stat_args.push("--stat");
+        if let Some(path) = path {
+            args.extend(["--", path]);
+            stat_args.extend(["--", path]);
+        }
+        let diff = self.git(&args).await?;
+        let stat = self.git(&stat_args).await?;
+        Ok(json!({
+            "staged": staged,
+            "stat": stat.trim_end(),
+            "diff": truncate_output(&diff, max_chars),
+            "truncated": diff.chars().count() > max_chars,
+            "empty": diff.trim().is_empty(),
+        }))
+    }
+
+    pub async fn log(&self, limit: u64, path: Option<&str>) -> Result<Value> {
+        let count = format!("-{}", limit);
+        let mut args = vec!["log", count.as_str(), "--format=%h%x09%an%x09%ad%x09%s", "--date=short"];
+        if let Some(path) = path {
+            args.extend(["--", path]);
+        }
+        let log = self.git(&args).await?;
+        let commits: Vec<Value> = log.lines().filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            Some(json!({
+                "hash": fields.next()?,
+                "author": fields.next()?,
+                "date": fields.next()?,
+                "subject": fields.next()?,
+            }))
+        }).collect();
+        Ok(json!(commits))
+    }
+}
+
+/// `git status --porcelain=v1 --branch` as branch, staged, unstaged and untracked paths.
+fn parse_status(porcelain: &str) -> Value {
+    let mut branch = None;
+    let (mut staged, mut unstaged, mut untracked) = (Vec::new(), Vec::new(), Vec::new());
+    for line in porcelain.lines() {
+        if let Some(header) = line.strip_prefix("## ") {
+            branch = Some(header.split("...").next().unwrap_or(header).to_string());
+            continue;
+        }
+        if line.len() < 4 {
+            continue;
+        }
+        let (code, path) = line.split_at(3);
+        let mut flags = code.chars();
+        let (index, worktree) = (flags.next().unwrap_or(' '), flags.next().unwrap_or(' '));
+        if index == '?' {
+            untracked.push(path.to_string());
+            continue;
+        }
+        if index != ' ' {
+            staged.push(json!({"path": path, "change": change_name(index)}));
+        }
+        if worktree != ' ' {
+            unstaged.push(json!({"path": path, "change": change_name(worktree)}));
+        }
+    }
+    json!({ "branch": branch, "staged": staged, "unstaged": unstaged, "untracked": untracked })
+}
+
+fn change_name(flag: char) -> &'static str {
+    match flag {
+        'M' => "modified",
+        'A' => "added",
+        'D' => "deleted",
+        'R' => "renamed",
+        'C' => "copied",
+        'U' => "conflict",
+        'T' => "type changed",
+        _ => "changed",
+    }
+}
+
+#[async_trait]
+impl Tool for GitTool {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn description(&self) -> &str {
+        "Reads the git repository in the working directory: status (branch, staged, unstaged and untracked files), diff (working tree or staged, optionally for one path) and log (recent commits)."
+    }
+
+    fn available_functions(&self) -> Vec<String> {
+        vec!["status".to_string(), "diff".to_string(), "log".to_string()]
+    }
+
+    async fn execute(&self, function: &str, args: Value) -> Result<ToolResult> {
+        let path = args["path"].as_str();
+        let result = match function {
+            "status" => self.status().await,
+            "diff" => {
+                let max_chars = args["max_chars"].as_u64().map_or(DEFAULT_MAX_CHARS, |n| n as usize);
+                self.diff(args["staged"].as_bool().unwrap_or(false), path, max_chars).await
+            }
+            "log" => self.log(args["limit"].as_u64().unwrap_or(DEFAULT_LOG_LIMIT), path).await,
+            _ => return Err(anyhow!("Unknown function: {}", function)),
+        };
+        Ok(match result {
+            Ok(result) => ToolResult { success: true, result, metadata: None },
+            Err(e) => ToolResult { success: false, result: json!(e.to_string()), metadata: None },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let status = parse_status("## main...origin/main [ahead 1]\nM  src/lib.rs\n M README.md\nMM src/main.rs\n?? notes.txt\n");
+        assert_eq!(status["branch"], "main");
+        assert_eq!(status["staged"], json!([
+            {"path": "src/lib.rs", "change": "modified"},
+            {"path": "src/main.rs", "change": "modified"},
+        ]));
+        assert_eq!(status["unstaged"][0]["path"], "README.md");
+        assert_eq!(status["untracked"], json!(["notes.txt"]));
+    }
+}
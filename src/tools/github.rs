@@ -0,0 +1,273 @@
+use super::{PermissionLevel, Tool, ToolResult};
+use super::command::truncate_output;
+use crate::config::GitHubConfig;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use crate::utils::approval;
+use std::time::Duration;
+
+const USER_AGENT: &str = "air-Agent/1.0";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_LIMIT: u64 = 10;
+/// Issue comments returned with `get_issue`, newest last.
+const MAX_COMMENTS: usize = 20;
+/// PR diff text returned to the model; `air review` reads the whole diff.
+const MAX_DIFF_CHARS: usize = 20_000;
+const MAX_BODY_CHARS: usize = 4_000;
+
+/// Issues and pull requests on GitHub. Reading is free; commenting and creating issues ask first.
+pub struct GitHubTool {
+    client: reqwest::Client,
+    api_url: String,
+    token: Option<String>,
+    default_repo: Option<String>,
+}
+
+impl GitHubTool {
+    pub fn with_config(config: &GitHubConfig) -> Self {
+        let token = config.access_token.clone().filter(|t| !t.is_empty())
+            .or_else(|| std::env::var("GITHUB_TOKEN").ok())
+            .or_else(|| std::env::var("GH_TOKEN").ok())
+            .or_else(|| crate::utils::secrets::get_api_key("GITHUB_TOKEN"));
+        Self {
+            client: reqwest::Client::builder().timeout(HTTP_TIMEOUT).user_agent(USER_AGENT).build().unwrap_or_default(),
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+            token,
+            default_repo: config.default_repo.clone(),
+        }
+    }
+
+    /// `repo` if given, else the `origin` remote of the working directory, else `default_repo`.
+    fn repo(&self, repo: Option<&str>) -> Result<String> {
+        if let Some(repo) = repo.filter(|r| r.contains('/')) {
+            return Ok(repo.to_string());
+        }
+        let origin = std::process::Command::new("git").args(["remote", "get-url", "origin"]).output().ok()
+            .filter(|o| o.status.success())
+            .and_then(|o| parse_repo(&String::from_utf8_lossy(&o.stdout)));
+        origin.or_else(|| self.default_repo.clone())
+            .ok_or_else(|| anyhow!("No repository given: pass 'repo' as owner/name or set github.default_repo"))
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str, accept: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, format!("{}{}", self.api_url, path))
+            .header("Accept", accept)
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        let message = body["message"].as_str().unwrap_or("request failed");
+        let hint = if self.token.is_none() && matches!(status.as_u16(), 401 | 403 | 404) {
+            " (set github.access_token or GITHUB_TOKEN for private repositories and writes)"
+        } else {
+            ""
+        };
+        Err(anyhow!("GitHub returned {}: {}{}", status, message, hint))
+    }
+
+    async fn get_json(&self, path: &str) -> Result<Value> {
+        Ok(self.send(self.request(reqwest::Method::GET, path, "application/vnd.github+json")).await?.json().await?)
+    }
+
+    async fn post_json(&self, path: &str, body: Value) -> Result<Value> {
+        Ok(self.send(self.request(reqwest::Method::POST, path, "application/vnd.github+json").json(&body)).await?.json().await?)
+    }
+
+    /// A pull request's title, description and state.
+    pub async fn pull_request(&self, repo: &str, number: u64) -> Result<Value> {
+        self.get_json(&format!("/repos/{}/pulls/{}", repo, number)).await
+    }
+
+    /// A pull request's full unified diff.
+    pub async fn pull_request_diff(&self, repo: &str, number: u64) -> Result<String> {
+        let request = self.request(reqwest::Method::GET, &format!("/repos/{}/pulls/{}", repo, number), "application/vnd.github.v3.diff");
+        Ok(self.send(request).await?.text().await?)
+    }
+
+    /// Creates a gist from `(file name, content)` pairs and returns its URL. Secret gists are
+    /// unlisted but readable by anyone with the link.
+    pub async fn create_gist(&self, description: &str, files: &[(&str, &str)], public: bool) -> Result<String> {
+        if self.token.is_none() {
+            return Err(anyhow!("Creating a gist needs a GitHub token with the gist scope (github.access_token or GITHUB_TOKEN)"));
+        }
+        let files: serde_json::Map<String, Value> = files.iter()
+            .map(|(name, content)| (name.to_string(), json!({ "content": content })))
+            .collect();
+        let gist = self.post_json("/gists", json!({ "description": description, "public": public, "files": files })).await?;
+        gist["html_url"].as_str().map(|url| url.to_string())
+            .ok_or_else(|| anyhow!("GitHub created the gist but returned no URL"))
+    }
+
+    async fn search_issues(&self, args: &Value) -> Result<Value> {
+        let mut query = args["query"].as_str().unwrap_or_default().to_string();
+        if !query.contains("repo:") {
+            query.push_str(&format!(" repo:{}", self.repo(args["repo"].as_str())?));
+        }
+        match args["state"].as_str() {
+            Some(state @ ("open" | "closed")) => query.push_str(&format!(" state:{}", state)),
+            _ => {}
+        }
+        match args["type"].as_str() {
+            Some("pr") | Some("pull_request") => query.push_str(" is:pr"),
+            Some("issue") => query.push_str(" is:issue"),
+            _ => {}
+        }
+        let limit = args["limit"].as_u64().unwrap_or(DEFAULT_LIMIT).min(100);
+        let request = self.request(reqwest::Method::GET, "/search/issues", "application/vnd.github+json")
+            .query(&[("q", query.trim()), ("per_page", &limit.to_string())]);
+        let results: Value = self.send(request).await?.json().await?;
+        let items: Vec<Value> = results["items"].as_array().into_iter().flatten().map(issue_summary).collect();
+        Ok(json!({ "query": query.trim(), "total_count": results["total_count"], "items": items }))
+    }
+
+    async fn get_issue(&self, repo: &str, number: u64) -> Result<Value> {
+        let issue = self.get_json(&format!("/repos/{}/issues/{}", repo, number)).await?;
+        let comments = self.get_json(&format!("/repos/{}/issues/{}/comments?per_page=100", repo, number)).await?;
+        let comments: Vec<Value> = comments.as_array().into_iter().flatten()
+            .rev().take(MAX_COMMENTS).collect::<Vec<_>>().into_iter().rev()
+            .map(|c| json!({
+                "author": c["user"]["login"],
+                "created_at": c["created_at"],
+                "body": truncate_output(c["body"].as_str().unwrap_or_default(), MAX_BODY_CHARS),
+            }))
+            .collect();
+        let mut result = issue_summary(&issue);
+        result["body"] = json!(truncate_output(issue["body"].as_str().unwrap_or_default(), MAX_BODY_CHARS));
+        result["comments"] = json!(comments);
+        Ok(result)
+    }
+
+    async fn get_pull_request(&self, repo: &str, number: u64, max_chars: usize) -> Result<Value> {
+        let pr = self.pull_request(repo, number).await?;
+        let diff = self.pull_request_diff(repo, number).await?;
+        Ok(json!({
+            "number": pr["number"],
+            "title": pr["title"],
+            "state": pr["state"],
+            "author": pr["user"]["login"],
+            "base": pr["base"]["ref"],
+            "head": pr["head"]["ref"],
+            "merged": pr["merged"],
+            "additions": pr["additions"],
+            "deletions": pr["deletions"],
+            "changed_files": pr["changed_files"],
+            "url": pr["html_url"],
+            "body": truncate_output(pr["body"].as_str().unwrap_or_default(), MAX_BODY_CHARS),
+            "diff": truncate_output(&diff, max_chars),
+            "diff_truncated": diff.chars().count() > max_chars,
+        }))
+    }
+
+    fn ask_confirmation(&self, action: &str, preview: &str) -> bool {
+        println!("\n🐙 GitHub: {}\n{}", action, preview);
+        approval::confirm("⚠️  Confirmation required: post this to GitHub?")
+    }
+}
+
+/// `owner/repo` from a GitHub remote URL (https or ssh).
+pub fn parse_repo(url: &str) -> Option<String> {
+    let url = url.trim().trim_end_matches('/').trim_end_matches(".git");
+    let path = url.split_once("github.com").map(|(_, rest)| rest.trim_start_matches([':', '/']))?;
+    let mut parts = path.splitn(3, '/');
+    let (owner, repo) = (parts.next()?, parts.next()?);
+    (!owner.is_empty() && !repo.is_empty()).then(|| format!("{}/{}", owner, repo))
+}
+
+fn issue_summary(issue: &Value) -> Value {
+    json!({
+        "number": issue["number"],
+        "title": issue["title"],
+        "state": issue["state"],
+        "author": issue["user"]["login"],
+        "labels": issue["labels"].as_array().into_iter().flatten().filter_map(|l| l["name"].as_str()).collect::<Vec<_>>(),
+        "comments": issue["comments"],
+        "is_pull_request": issue.get("pull_request").is_some(),
+        "updated_at": issue["updated_at"],
+        "url": issue["html_url"],
+    })
+}
+
+fn number(args: &Value) -> Result<u64> {
+    args["number"].as_u64().ok_or_else(|| anyhow!("Missing 'number' parameter"))
+}
+
+#[async_trait]
+impl Tool for GitHubTool {
+    fn name(&self) -> &str {
+        "github"
+    }
+
+    fn description(&self) -> &str {
+        "Works with GitHub issues and pull requests: search_issues (query, repo, state, type issue|pr, limit), get_issue (number, with comments), get_pull_request (number, with its diff), comment (number, body) and create_issue (title, body, labels). 'repo' is owner/name and defaults to the working directory's origin. Commenting and creating issues ask the user first."
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Confirm
+    }
+
+    fn available_functions(&self) -> Vec<String> {
+        ["search_issues", "get_issue", "get_pull_request", "comment", "create_issue"].map(String::from).to_vec()
+    }
+
+    async fn execute(&self, function: &str, args: Value) -> Result<ToolResult> {
+        let result = match function {
+            "search_issues" => self.search_issues(&args).await,
+            "get_issue" => self.get_issue(&self.repo(args["repo"].as_str())?, number(&args)?).await,
+            "get_pull_request" => {
+                let max_chars = args["max_chars"].as_u64().map_or(MAX_DIFF_CHARS, |n| n as usize);
+                self.get_pull_request(&self.repo(args["repo"].as_str())?, number(&args)?, max_chars).await
+            }
+            "comment" => {
+                let repo = self.repo(args["repo"].as_str())?;
+                let number = number(&args)?;
+                let body = args["body"].as_str().ok_or_else(|| anyhow!("Missing 'body' parameter"))?;
+                if !self.ask_confirmation(&format!("comment on {}#{}", repo, number), body) {
+                    return Ok(ToolResult { success: false, result: json!("Comment cancelled by user."), metadata: None });
+                }
+                self.post_json(&format!("/repos/{}/issues/{}/comments", repo, number), json!({"body": body})).await
+                    .map(|c| json!({"message": "Comment posted", "url": c["html_url"]}))
+            }
+            "create_issue" => {
+                let repo = self.repo(args["repo"].as_str())?;
+                let title = args["title"].as_str().ok_or_else(|| anyhow!("Missing 'title' parameter"))?;
+                let body = args["body"].as_str().unwrap_or_default();
+                let labels: Vec<&str> = args["labels"].as_array().into_iter().flatten().filter_map(|l| l.as_str()).collect();
+                let preview = format!("Title: {}\nLabels: {}\n\n{}", title, labels.join(", "), body);
+                if !self.ask_confirmation(&format!("create an issue in {}", repo), &preview) {
+                    return Ok(ToolResult { success: false, result: json!("Issue creation cancelled by user."), metadata: None });
+                }
+                self.post_json(&format!("/repos/{}/issues", repo), json!({"title": title, "body": body, "labels": labels})).await
+                    .map(|i| json!({"message": "Issue created", "number": i["number"], "url": i["html_url"]}))
+            }
+            _ => return Err(anyhow!("Unknown function: {}", function)),
+        };
+        Ok(match result {
+            Ok(result) => ToolResult { success: true, result, metadata: None },
+            Err(e) => ToolResult { success: false, result: json!(e.to_string()), metadata: None },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repo() {
+        assert_eq!(parse_repo("https://github.com/chintu4/air.git\n").as_deref(), Some("chintu4/air"));
+        assert_eq!(parse_repo("git@github.com:chintu4/air.git").as_deref(), Some("chintu4/air"));
+        assert_eq!(parse_repo("ssh://git@github.com/chintu4/air").as_deref(), Some("chintu4/air"));
+        assert_eq!(parse_repo("https://gitlab.com/chintu4/air"), None);
+    }
+}
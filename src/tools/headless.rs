@@ -0,0 +1,82 @@
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+// Executable names searched on PATH, in order of preference
+const BROWSER_NAMES: &[&str] = &[
+    "chromium", "chromium-browser", "google-chrome", "google-chrome-stable", "chrome", "microsoft-edge", "msedge",
+];
+
+// Well-known install locations that are usually not on PATH
+const BROWSER_PATHS: &[&str] = &[
+    "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+    "/Applications/Chromium.app/Contents/MacOS/Chromium",
+    "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
+    r"C:\Program Files\Google\Chrome\Application\chrome.exe",
+    r"C:\Program Files (x86)\Google\Chrome\Application\chrome.exe",
+    r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe",
+];
+
+/// Renders pages in a headless Chromium-family browser so script-built DOMs can be read.
+pub struct HeadlessRenderer {
+    binary: Option<PathBuf>,
+    timeout: Duration,
+}
+
+impl HeadlessRenderer {
+    /// Uses `browser_path` when given, otherwise the first Chrome/Chromium/Edge found on the system.
+    pub fn new(browser_path: Option<&str>, timeout: Duration) -> Self {
+        let binary = browser_path.map(PathBuf::from).or_else(find_browser);
+        Self { binary, timeout }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.binary.is_some()
+    }
+
+    /// Loads `url`, lets scripts run for a few seconds of virtual time and returns the serialized DOM.
+    pub async fn render(&self, url: &str) -> Result<String> {
+        let binary = self.binary.as_ref()
+            .ok_or_else(|| anyhow!("No headless browser found (install Chrome or Chromium, or set web.browser_path)"))?;
+
+        let output = Command::new(binary)
+            .args([
+                "--headless=new",
+                "--disable-gpu",
+                "--no-first-run",
+                "--no-default-browser-check",
+                "--hide-scrollbars",
+                "--mute-audio",
+                "--virtual-time-budget=5000",
+                "--dump-dom",
+                url,
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output();
+
+        let output = tokio::time::timeout(self.timeout, output).await
+            .map_err(|_| anyhow!("Headless render of {} timed out after {}s", url, self.timeout.as_secs()))??;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("Headless browser failed: {}", stderr.lines().last().unwrap_or("unknown error")));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+fn find_browser() -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    let dirs: Vec<PathBuf> = std::env::split_paths(&path_var).collect();
+    let exe_suffix = if cfg!(target_os = "windows") { ".exe" } else { "" };
+
+    BROWSER_NAMES.iter()
+        .flat_map(|name| dirs.iter().map(move |dir| dir.join(format!("{}{}", name, exe_suffix))))
+        .chain(BROWSER_PATHS.iter().map(PathBuf::from))
+        .find(|candidate| Path::new(candidate).is_file())
+}
@@ -0,0 +1,202 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::{Mutex, oneshot};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Exited { exit_code: Option<i32> },
+    Killed,
+}
+
+/// Snapshot of a background job as reported to the model.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: u32,
+    pub command: String,
+    pub pid: Option<u32>,
+    pub started_at: DateTime<Local>,
+    pub log_path: PathBuf,
+    #[serde(flatten)]
+    pub state: JobState,
+}
+
+struct Job {
+    info: JobInfo,
+    kill_tx: Option<oneshot::Sender<()>>,
+}
+
+/// Tracks long-running commands started with `run_background`, capturing their output to log files.
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<u32, Job>>>,
+    next_id: AtomicU32,
+    log_dir: PathBuf,
+}
+
+impl JobRegistry {
+    pub fn new(log_dir: PathBuf) -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU32::new(1),
+            log_dir,
+        }
+    }
+
+    /// Spawns `cmd` detached from the agent loop; stdout and stderr are appended to the job's log.
+    pub async fn spawn(&self, command: &str, mut cmd: Command) -> Result<JobInfo> {
+        tokio::fs::create_dir_all(&self.log_dir).await?;
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let log_path = self.log_dir.join(format!("job-{}.log", id));
+        let log = OpenOptions::new().create(true).write(true).truncate(true).open(&log_path).await?;
+        let log = Arc::new(Mutex::new(log));
+
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("Failed to capture stdout"))?;
+        let stderr = child.stderr.take().ok_or_else(|| anyhow!("Failed to capture stderr"))?;
+        tokio::spawn(append_to_log(stdout, log.clone()));
+        tokio::spawn(append_to_log(stderr, log));
+
+        let info = JobInfo {
+            id,
+            command: command.to_string(),
+            pid: child.id(),
+            started_at: Local::now(),
+            log_path,
+            state: JobState::Running,
+        };
+
+        let (kill_tx, kill_rx) = oneshot::channel();
+        self.jobs.lock().await.insert(id, Job { info: info.clone(), kill_tx: Some(kill_tx) });
+
+        let jobs = self.jobs.clone();
+        tokio::spawn(async move {
+            let state = tokio::select! {
+                status = child.wait() => JobState::Exited { exit_code: status.ok().and_then(|s| s.code()) },
+                _ = kill_rx => {
+                    let _ = child.kill().await;
+                    JobState::Killed
+                }
+            };
+            if let Some(job) = jobs.lock().await.get_mut(&id) {
+                job.info.state = state;
+                job.kill_tx = None;
+            }
+        });
+
+        Ok(info)
+    }
+
+    pub async fn get(&self, id: u32) -> Option<JobInfo> {
+        self.jobs.lock().await.get(&id).map(|job| job.info.clone())
+    }
+
+    pub async fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.jobs.lock().await.values().map(|job| job.info.clone()).collect();
+        jobs.sort_by_key(|job| job.id);
+        jobs
+    }
+
+    /// Returns the last `lines` lines of the job's captured output.
+    pub async fn output(&self, id: u32, lines: usize) -> Result<String> {
+        let info = self.get(id).await.ok_or_else(|| anyhow!("No job with id {}", id))?;
+        let content = tokio::fs::read(&info.log_path).await?;
+        let content = String::from_utf8_lossy(&content);
+        let all: Vec<&str> = content.lines().collect();
+        let start = all.len().saturating_sub(lines);
+        Ok(all[start..].join("\n"))
+    }
+
+    /// Signals a running job to stop. Returns false if the job had already finished.
+    pub async fn kill(&self, id: u32) -> Result<bool> {
+        let mut jobs = self.jobs.lock().await;
+        let job = jobs.get_mut(&id).ok_or_else(|| anyhow!("No job with id {}", id))?;
+        match job.kill_tx.take() {
+            Some(tx) => Ok(tx.send(()).is_ok()),
+            None => Ok(false),
+        }
+    }
+}
+
+async fn append_to_log<R: AsyncRead + Unpin>(reader: R, log: Arc<Mutex<tokio::fs::File>>) {
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let mut log = log.lock().await;
+                if log.write_all(&buf).await.is_err() {
+                    break;
+                }
+                let _ = log.flush().await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn shell(command: &str) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+
+    #[tokio::test]
+    async fn test_job_output_and_exit() {
+        if cfg!(target_os = "windows") {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        let registry = JobRegistry::new(dir.path().to_path_buf());
+
+        let job = registry.spawn("echo one; echo two", shell("echo one; echo two")).await.unwrap();
+        for _ in 0..50 {
+            if registry.get(job.id).await.unwrap().state != JobState::Running {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(registry.get(job.id).await.unwrap().state, JobState::Exited { exit_code: Some(0) });
+        assert_eq!(registry.output(job.id, 1).await.unwrap(), "two");
+    }
+
+    #[tokio::test]
+    async fn test_kill_job() {
+        if cfg!(target_os = "windows") {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        let registry = JobRegistry::new(dir.path().to_path_buf());
+
+        let job = registry.spawn("sleep 30", shell("sleep 30")).await.unwrap();
+        assert!(registry.kill(job.id).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(registry.get(job.id).await.unwrap().state, JobState::Killed);
+        assert!(!registry.kill(job.id).await.unwrap());
+    }
+}
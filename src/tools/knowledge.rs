@@ -1,20 +1,23 @@
-use super::{Tool, ToolResult};
+use super::{PermissionLevel, Tool, ToolResult};
+use crate::config::KnowledgeConfig;
+use crate::rag::chunking;
+use crate::rag::loaders::{self, Page};
 use crate::rag::store::KnowledgeStore;
 use crate::rag::langchain_embedding::CandleEmbedder;
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
 use serde_json::{json, Value};
 use std::sync::Arc;
-use tokio::fs;
 use tracing::warn;
 
 pub struct KnowledgeTool {
     store: Option<Arc<KnowledgeStore<CandleEmbedder>>>,
+    config: KnowledgeConfig,
 }
 
 impl KnowledgeTool {
     pub async fn new() -> Result<Self> {
-        let app_data = crate::utils::paths::get_air_data_dir()
+        let app_data = crate::utils::paths::get_air_memory_dir()
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_else(|_| std::env::temp_dir().to_string_lossy().to_string());
 
@@ -26,7 +29,18 @@ impl KnowledgeTool {
             }
         };
 
-        Ok(Self { store })
+        Ok(Self { store, config: KnowledgeConfig::default() })
+    }
+
+    /// Chunks added files as `config` says instead of by the defaults.
+    pub fn with_config(mut self, config: &KnowledgeConfig) -> Self {
+        self.config = config.clone();
+        self
+    }
+
+    /// Shared handle to the underlying store, for tools that index content directly.
+    pub fn store(&self) -> Option<Arc<KnowledgeStore<CandleEmbedder>>> {
+        self.store.clone()
     }
 
     pub async fn add_file(&self, path_str: &str) -> Result<String> {
@@ -36,25 +50,21 @@ impl KnowledgeTool {
                 return Err(anyhow!("File not found: {}", path_str));
             }
 
-            let content = fs::read_to_string(path).await?;
+            let document = loaders::load(path).await?;
             let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-
-            // Naive chunking: split by paragraphs
-            let chunks: Vec<&str> = content.split("\n\n").collect();
-            let mut added_chunks = 0;
-
-            for chunk in chunks {
-                if chunk.trim().len() < 20 { continue; } // Skip small chunks
-
-                store.add_text(chunk, json!({
-                    "source": path_str,
-                    "filename": filename,
-                    "type": "file"
-                })).await?;
-                added_chunks += 1;
-            }
-
-            Ok(format!("Indexed {} chunks from {}", added_chunks, path_str))
+            let strategy = document.format.chunking(path_str, self.config.chunking);
+
+            // Re-adding a file replaces the chunks indexed from it before
+            let added_chunks = store.add_document(path_str, &document, &self.config, json!({
+                "filename": filename,
+                "type": "file"
+            })).await?;
+
+            let pages = match document.pages.as_slice() {
+                [Page { number: Some(_), .. }, ..] => format!(", {} pages", document.pages.len()),
+                _ => String::new(),
+            };
+            Ok(format!("Indexed {} chunks from {} ({}{}, {} chunking)", added_chunks, path_str, document.format.as_str(), pages, strategy.as_str()))
         } else {
             Err(anyhow!("Knowledge store is not available."))
         }
@@ -68,11 +78,17 @@ impl Tool for KnowledgeTool {
     }
 
     fn description(&self) -> &str {
-        "Long-term memory and knowledge retrieval from indexed files"
+        "Long-term memory and knowledge retrieval from indexed files: search(query, k, filter) returns scored chunks with ids and citations, get_document(id) fetches one, add_knowledge(path) indexes a text, markdown, PDF, DOCX or HTML file"
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Modifies
     }
 
     fn available_functions(&self) -> Vec<String> {
         vec![
+            "search".to_string(),
+            "get_document".to_string(),
             "search_knowledge".to_string(),
             "add_knowledge".to_string(),
         ]
@@ -89,11 +105,14 @@ impl Tool for KnowledgeTool {
         let store = self.store.as_ref().unwrap();
 
         match function {
-            "search_knowledge" => {
+            "search" | "search_knowledge" => {
                 let query = args["query"].as_str()
                     .ok_or_else(|| anyhow!("Missing 'query' parameter"))?;
+                let default_k = if function == "search" { 5 } else { 3 };
+                let k = args["k"].as_u64().map(|k| k as usize).unwrap_or(default_k).clamp(1, 20);
+                let filter = args["filter"].as_object();
 
-                let results = store.search(query, 3).await?;
+                let results = store.search_filtered(query, k, filter).await?;
 
                 if results.is_empty() {
                     return Ok(ToolResult {
@@ -106,6 +125,8 @@ impl Tool for KnowledgeTool {
                 let mut result_items = Vec::new();
                 for (doc, score) in results {
                     result_items.push(json!({
+                        "id": doc.metadata.get("id"),
+                        "citation": chunking::citation(&doc.metadata),
                         "content": doc.page_content,
                         "metadata": doc.metadata,
                         "score": score
@@ -119,6 +140,40 @@ impl Tool for KnowledgeTool {
                 })
             }
 
+            "get_document" => {
+                let id = args["id"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'id' parameter"))?;
+
+                let Some(doc) = store.get_document(id).await else {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("No document with id {}", id)),
+                        metadata: None,
+                    });
+                };
+
+                // Optionally return every chunk from the same source so the model can read it in full
+                let source = doc.metadata.get("source").and_then(|v| v.as_str()).map(|s| s.to_string());
+                let full_source = match (&source, args["include_source"].as_bool().unwrap_or(false)) {
+                    (Some(source), true) => {
+                        let chunks = store.documents_from_source(source).await;
+                        Some(chunks.into_iter().map(|c| c.page_content).collect::<Vec<_>>().join("\n\n"))
+                    }
+                    _ => None,
+                };
+
+                Ok(ToolResult {
+                    success: true,
+                    result: json!({
+                        "id": id,
+                        "content": doc.page_content,
+                        "metadata": doc.metadata,
+                        "source_content": full_source,
+                    }),
+                    metadata: None,
+                })
+            }
+
             "add_knowledge" => {
                 let content = args["content"].as_str();
                 let path = args["path"].as_str();
@@ -1,10 +1,35 @@
-use super::{Tool, ToolResult, FileSystemTool, CalculatorTool, MemoryTool, PlannerTool, WebTool, CommandTool, ScreenshotTool, VoiceTool, KnowledgeTool, SystemTool, NewsTool};
+use super::{CacheStats, PermissionLevel, Tool, ToolResult, FileSystemTool, GitTool, GitHubTool, CalculatorTool, MemoryTool, PlannerTool, WebTool, CommandTool, ScreenshotTool, VoiceTool, KnowledgeTool, SystemTool, NewsTool, TrackerTool};
+use super::screenshot::VisionAnalyzer;
+use super::script;
+use crate::config::Config;
+use crate::agent::memory::MemoryManager;
 use anyhow::Result;
-use std::sync::Arc;
-use tracing::{info, debug};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{info, debug, warn};
+
+/// Call counts and timing for one tool since the agent started.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ToolStats {
+    pub calls: u64,
+    /// Calls that returned an error or an unsuccessful result.
+    pub failures: u64,
+    pub total_ms: u64,
+}
+
+impl ToolStats {
+    pub fn avg_ms(&self) -> u64 {
+        if self.calls == 0 { 0 } else { self.total_ms / self.calls }
+    }
+}
 
 pub struct ToolManager {
     filesystem: Arc<dyn Tool>,
+    git: Arc<dyn Tool>,
+    github: Arc<dyn Tool>,
     calculator: Arc<dyn Tool>,
     memory: Arc<dyn Tool>,
     planner: Arc<dyn Tool>,
@@ -15,37 +40,108 @@ pub struct ToolManager {
     knowledge: Arc<dyn Tool>,
     system: Arc<dyn Tool>,
     news: Arc<dyn Tool>,
+    tracker: Arc<dyn Tool>,
+    #[cfg(feature = "automation")]
+    desktop: Arc<dyn Tool>,
+    /// Rhai tools from the config directory, offered after the built-in ones.
+    scripts: Vec<Arc<dyn Tool>>,
+    stats: Arc<Mutex<HashMap<String, ToolStats>>>,
+    /// Tool names offered and runnable; None allows every tool (see `restricted_to`).
+    allowed: Option<Vec<String>>,
+    /// When set, the model is only offered read-only tools (see `execute_for_model`).
+    safe_mode: Arc<AtomicBool>,
 }
 
 impl ToolManager {
-    pub async fn new() -> Self {
-        Self {
-            filesystem: Arc::new(FileSystemTool::new(None)),
+    pub async fn new(config: &Config, memory_manager: Arc<MemoryManager>) -> Self {
+        Self::with_vision(config, memory_manager, None).await
+    }
+
+    /// Like `new`, with the screenshot tool (and desktop's `read_screen` fallback) describing
+    /// captures through `vision`.
+    pub async fn with_vision(config: &Config, memory_manager: Arc<MemoryManager>, vision: Option<Arc<dyn VisionAnalyzer>>) -> Self {
+        // KnowledgeTool::new() never fails; a broken store just leaves knowledge features disabled
+        let knowledge = KnowledgeTool::new().await.expect("KnowledgeTool::new() should not fail").with_config(&config.knowledge);
+        let web = match knowledge.store() {
+            Some(store) => WebTool::with_config(&config.web).with_sandbox(&config.filesystem).with_knowledge(store),
+            None => WebTool::with_config(&config.web).with_sandbox(&config.filesystem),
+        };
+        let planner: Arc<dyn Tool> = Arc::new(PlannerTool::with_memory(memory_manager.clone()).await);
+        let screenshot = match vision {
+            Some(analyzer) => {
+                info!("👁️  Screenshot analysis enabled via {}", analyzer.name());
+                ScreenshotTool::new(None).with_sandbox(&config.filesystem).with_vision(analyzer)
+            }
+            None => ScreenshotTool::new(None).with_sandbox(&config.filesystem),
+        };
+        let screenshot: Arc<dyn Tool> = Arc::new(screenshot);
+
+        let mut manager = Self {
+            filesystem: Arc::new(FileSystemTool::with_config(None, &config.filesystem)),
+            git: Arc::new(GitTool::new()),
+            github: Arc::new(GitHubTool::with_config(&config.github)),
             calculator: Arc::new(CalculatorTool::new()),
-            memory: Arc::new(MemoryTool::new(None)),
-            planner: Arc::new(PlannerTool::new()),
-            web: Arc::new(WebTool::new()),
-            command: Arc::new(CommandTool::new()),
-            screenshot: Arc::new(ScreenshotTool::new(None)),
-            voice: Arc::new(VoiceTool::new(None)),
-            knowledge: Arc::new(KnowledgeTool::new().await.unwrap_or_else(|_| {
-                // This branch should technically be unreachable now since new() handles errors internally,
-                // but just in case we return a dummy struct or panic safely?
-                // Actually KnowledgeTool::new() returns Result<Self>, so we can unwrap safely if we know it returns Ok.
-                // But wait, I changed it to return Ok even on error (just with None store).
-                // So unwrap() is fine.
-                // However, the signature is Result<Self, anyhow::Error>.
-                // I'll stick to unwrap() but I'll ensure KnowledgeTool::new() catches everything.
-                panic!("KnowledgeTool::new() should not fail")
-            })),
+            memory: Arc::new(MemoryTool::new(None).with_memory(memory_manager.clone())),
+            planner: planner.clone(),
+            web: Arc::new(web),
+            command: Arc::new(CommandTool::with_config(&config.command)),
+            #[cfg(feature = "automation")]
+            desktop: Arc::new(super::DesktopTool::with_config(&config.automation, screenshot.clone())),
+            screenshot,
+            voice: Arc::new(VoiceTool::with_config(None, &config.voice)),
+            knowledge: Arc::new(knowledge),
             system: Arc::new(SystemTool::new()),
-            news: Arc::new(NewsTool::new()),
+            news: Arc::new(NewsTool::new().with_memory(memory_manager)),
+            tracker: Arc::new(TrackerTool::with_config(&config.tracker).with_planner(planner)),
+            scripts: Vec::new(),
+            stats: Arc::new(Mutex::new(HashMap::new())),
+            allowed: None,
+            safe_mode: Arc::new(AtomicBool::new(false)),
+        };
+
+        for script in script::load_all(&config.scripting, &config.filesystem) {
+            if manager.tools().iter().any(|tool| tool.name().eq_ignore_ascii_case(script.name())) {
+                warn!("⚠️  Skipping script tool '{}': a tool with that name already exists", script.name());
+                continue;
+            }
+            manager.scripts.push(Arc::new(script));
+        }
+        manager
+    }
+
+    /// A view sharing these tools and their state but offering only `names`; calls to any other
+    /// tool are refused. Never widens: names this view already refuses stay refused.
+    pub fn restricted_to(&self, names: &[String]) -> Self {
+        Self {
+            filesystem: self.filesystem.clone(),
+            git: self.git.clone(),
+            github: self.github.clone(),
+            calculator: self.calculator.clone(),
+            memory: self.memory.clone(),
+            planner: self.planner.clone(),
+            web: self.web.clone(),
+            command: self.command.clone(),
+            screenshot: self.screenshot.clone(),
+            voice: self.voice.clone(),
+            knowledge: self.knowledge.clone(),
+            system: self.system.clone(),
+            news: self.news.clone(),
+            tracker: self.tracker.clone(),
+            #[cfg(feature = "automation")]
+            desktop: self.desktop.clone(),
+            scripts: self.scripts.clone(),
+            stats: self.stats.clone(),
+            allowed: Some(names.iter().filter(|name| self.is_allowed(name)).cloned().collect()),
+            safe_mode: self.safe_mode.clone(),
         }
     }
     
-    pub fn get_tool_definitions(&self) -> serde_json::Value {
-        let tools: Vec<&Arc<dyn Tool>> = vec![
+    /// Every registered tool, in the order they are offered to the model.
+    pub fn tools(&self) -> Vec<&Arc<dyn Tool>> {
+        let all = vec![
             &self.filesystem,
+            &self.git,
+            &self.github,
             &self.calculator,
             &self.memory,
             &self.planner,
@@ -56,25 +152,96 @@ impl ToolManager {
             &self.knowledge,
             &self.system,
             &self.news,
+            &self.tracker,
         ];
+        #[cfg(feature = "automation")]
+        let all = [all, vec![&self.desktop]].concat();
+        all.into_iter().chain(&self.scripts).filter(|tool| self.is_allowed(tool.name())).collect()
+    }
 
-        let definitions: Vec<serde_json::Value> = tools.iter().map(|tool| {
+    fn is_allowed(&self, name: &str) -> bool {
+        self.allowed.as_ref().map_or(true, |names| names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+    }
+
+    pub fn safe_mode(&self) -> bool {
+        self.safe_mode.load(Ordering::Relaxed)
+    }
+
+    /// Shared with every `restricted_to` view of these tools.
+    pub fn set_safe_mode(&self, enabled: bool) {
+        self.safe_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Definitions offered to the model; only the read-only tools while in safe mode.
+    pub fn get_tool_definitions(&self) -> serde_json::Value {
+        let safe_mode = self.safe_mode();
+        let definitions: Vec<serde_json::Value> = self.tools().iter()
+            .filter(|tool| !safe_mode || tool.permission_level() == PermissionLevel::ReadOnly)
+            .map(|tool| {
             serde_json::json!({
                 "name": tool.name(),
                 "description": tool.description(),
-                "functions": tool.available_functions()
+                "functions": tool.available_functions(),
+                "permission": tool.permission_level()
             })
         }).collect();
 
         serde_json::json!(definitions)
     }
 
+    /// The tools offered to providers with native function calling; like the definitions,
+    /// only read-only ones in safe mode.
+    pub fn tool_specs(&self) -> Vec<crate::models::ToolSpec> {
+        let safe_mode = self.safe_mode();
+        self.tools().iter()
+            .filter(|tool| !safe_mode || tool.permission_level() == PermissionLevel::ReadOnly)
+            .map(|tool| crate::models::ToolSpec {
+                name: tool.name().to_string(),
+                description: tool.description().to_string(),
+                functions: tool.available_functions(),
+            })
+            .collect()
+    }
+
+    /// A call the model asked for. Unlike `execute_tool` (the user's own calls), safe mode
+    /// refuses anything beyond read-only tools.
+    pub async fn execute_for_model(&self, tool_name: &str, function: &str, args: serde_json::Value) -> Result<ToolResult> {
+        if self.safe_mode() && self.lookup(tool_name)?.permission_level() != PermissionLevel::ReadOnly {
+            return Err(anyhow::anyhow!("Tool '{}' can change things and safe mode is on; only read-only tools may run", tool_name));
+        }
+        self.execute_tool(tool_name, function, args).await
+    }
+
     pub async fn execute_tool(&self, tool_name: &str, function: &str, args: serde_json::Value) -> Result<ToolResult> {
         info!("🔧 Executing tool: {} -> {}", tool_name, function);
         debug!("Tool arguments: {}", args);
         
-        let tool: &Arc<dyn Tool> = match tool_name {
+        let tool = self.lookup(tool_name)?;
+        if !self.is_allowed(tool.name()) {
+            return Err(anyhow::anyhow!("Tool '{}' is not available here", tool_name));
+        }
+        
+        let start = Instant::now();
+        let result = tool.execute(function, args.clone()).await;
+        crate::agent::usage::note_tool_call(tool.name(), function, &args, &result);
+
+        let mut stats = self.stats.lock().unwrap();
+        let entry = stats.entry(tool.name().to_string()).or_default();
+        entry.calls += 1;
+        entry.total_ms += start.elapsed().as_millis() as u64;
+        if !matches!(&result, Ok(r) if r.success) {
+            entry.failures += 1;
+        }
+        drop(stats);
+
+        result
+    }
+
+    fn lookup(&self, tool_name: &str) -> Result<&Arc<dyn Tool>> {
+        Ok(match tool_name {
             "filesystem" => &self.filesystem,
+            "git" => &self.git,
+            "github" => &self.github,
             "calculator" => &self.calculator,
             "memory" => &self.memory,
             "planner" => &self.planner,
@@ -85,9 +252,45 @@ impl ToolManager {
             "knowledge" => &self.knowledge,
             "system" => &self.system,
             "WebScraper" => &self.news,
-            _ => return Err(anyhow::anyhow!("Unknown tool: {}", tool_name)),
-        };
-        
-        tool.execute(function, args).await
+            "tracker" => &self.tracker,
+            #[cfg(feature = "automation")]
+            "desktop" => &self.desktop,
+            _ => self.scripts.iter().find(|tool| tool.name() == tool_name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown tool: {}", tool_name))?,
+        })
+    }
+
+    /// Per-tool call statistics, keyed by tool name.
+    pub fn stats(&self) -> HashMap<String, ToolStats> {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Hit/miss counters of every tool that caches, keyed by tool name.
+    pub fn cache_stats(&self) -> HashMap<String, CacheStats> {
+        self.tools().into_iter()
+            .filter_map(|tool| tool.cache_stats().map(|stats| (tool.name().to_string(), stats)))
+            .collect()
+    }
+
+    /// Session state of every stateful tool, keyed by tool name.
+    pub async fn snapshot(&self) -> HashMap<String, serde_json::Value> {
+        let mut states = HashMap::new();
+        for tool in self.tools() {
+            if let Some(state) = tool.snapshot().await {
+                states.insert(tool.name().to_string(), state);
+            }
+        }
+        states
+    }
+
+    /// Hands each tool its part of a snapshot; tools missing from `states` keep their current state.
+    pub async fn restore(&self, mut states: HashMap<String, serde_json::Value>) -> Result<()> {
+        for tool in self.tools() {
+            if let Some(state) = states.remove(tool.name()) {
+                tool.restore(state).await
+                    .map_err(|e| anyhow::anyhow!("Failed to restore {} state: {}", tool.name(), e))?;
+            }
+        }
+        Ok(())
     }
 }
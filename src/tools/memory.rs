@@ -1,4 +1,5 @@
-use super::{Tool, ToolResult};
+use super::{PermissionLevel, Tool, ToolResult};
+use crate::agent::memory::MemoryManager;
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
 use serde_json::{json, Value};
@@ -30,6 +31,7 @@ pub struct MemoryTool {
     conversations: Arc<Mutex<Vec<ConversationEntry>>>,
     session_data: Arc<Mutex<HashMap<String, Value>>>,
     max_history: usize,
+    memory: Option<Arc<MemoryManager>>,
 }
 
 impl MemoryTool {
@@ -38,8 +40,27 @@ impl MemoryTool {
             conversations: Arc::new(Mutex::new(Vec::new())),
             session_data: Arc::new(Mutex::new(HashMap::new())),
             max_history: max_history.unwrap_or(100),
+            memory: None,
         }
     }
+
+    /// Persists stored data in the memory database and reads conversation history from it,
+    /// so "remember X" survives restarts.
+    pub fn with_memory(mut self, memory: Arc<MemoryManager>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    async fn load_data(&self, key: &str) -> Result<Option<Value>> {
+        let cached = self.session_data.lock().unwrap().get(key).cloned();
+        if cached.is_some() {
+            return Ok(cached);
+        }
+        let Some(memory) = &self.memory else {
+            return Ok(None);
+        };
+        Ok(memory.get_persistent_memory(key).await?.map(|raw| parse_stored(&raw)))
+    }
     
     pub fn add_conversation(&self, user_input: String, ai_response: String, context: Option<String>, tools_used: Vec<String>) -> Result<String> {
         let entry = ConversationEntry {
@@ -111,6 +132,19 @@ impl MemoryTool {
     }
 }
 
+// Values are stored as JSON; fall back to a plain string for entries written elsewhere
+fn parse_stored(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| json!(raw))
+}
+
+fn conversation_json((user_input, ai_response, timestamp): (String, String, String)) -> Value {
+    json!({
+        "user_input": user_input,
+        "ai_response": ai_response,
+        "timestamp": timestamp,
+    })
+}
+
 #[async_trait]
 impl Tool for MemoryTool {
     fn name(&self) -> &str {
@@ -118,7 +152,25 @@ impl Tool for MemoryTool {
     }
     
     fn description(&self) -> &str {
-        "Conversation memory and context management"
+        "Conversation memory and context management; stored data persists across sessions"
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Modifies
+    }
+
+    async fn snapshot(&self) -> Option<Value> {
+        let conversations = self.conversations.lock().unwrap().clone();
+        let session_data = self.session_data.lock().unwrap().clone();
+        Some(json!({ "conversations": conversations, "session_data": session_data }))
+    }
+
+    async fn restore(&self, state: Value) -> Result<()> {
+        let conversations: Vec<ConversationEntry> = serde_json::from_value(state["conversations"].clone()).unwrap_or_default();
+        let session_data: HashMap<String, Value> = serde_json::from_value(state["session_data"].clone()).unwrap_or_default();
+        *self.conversations.lock().unwrap() = conversations;
+        self.session_data.lock().unwrap().extend(session_data);
+        Ok(())
     }
     
     fn available_functions(&self) -> Vec<String> {
@@ -128,6 +180,8 @@ impl Tool for MemoryTool {
             "get_summary".to_string(),
             "store_data".to_string(),
             "retrieve_data".to_string(),
+            "list_data".to_string(),
+            "delete_data".to_string(),
             "clear_history".to_string(),
         ]
     }
@@ -137,45 +191,78 @@ impl Tool for MemoryTool {
             "get_recent_history" => {
                 let limit = args["limit"].as_u64().unwrap_or(5) as usize;
                 
-                let conversations = self.conversations.lock().unwrap();
-                let recent: Vec<_> = conversations.iter()
-                    .rev()
-                    .take(limit)
-                    .cloned()
-                    .collect();
+                let (recent, total): (Vec<Value>, usize) = {
+                    let conversations = self.conversations.lock().unwrap();
+                    let recent = conversations.iter()
+                        .rev()
+                        .take(limit)
+                        .map(|entry| json!(entry))
+                        .collect();
+                    (recent, conversations.len())
+                };
+
+                // The agent records its exchanges in the memory database rather than here
+                let recent = match &self.memory {
+                    Some(memory) if recent.is_empty() => memory.get_recent_conversations(limit).await?
+                        .into_iter()
+                        .rev()
+                        .map(conversation_json)
+                        .collect(),
+                    _ => recent,
+                };
                 
                 Ok(ToolResult {
                     success: true,
-                    result: json!(recent),
                     metadata: Some(json!({
-                        "total_entries": conversations.len(),
+                        "total_entries": total.max(recent.len()),
                         "returned_entries": recent.len()
                     })),
+                    result: json!(recent),
                 })
             }
             
             "search_conversations" => {
                 let query = args["query"].as_str()
                     .ok_or_else(|| anyhow!("Missing 'query' parameter"))?;
-                
-                let conversations = self.conversations.lock().unwrap();
+                let limit = args["limit"].as_u64().unwrap_or(20) as usize;
                 let query_lower = query.to_lowercase();
                 
-                let matches: Vec<_> = conversations.iter()
-                    .filter(|entry| {
-                        entry.user_input.to_lowercase().contains(&query_lower) ||
-                        entry.ai_response.to_lowercase().contains(&query_lower)
-                    })
-                    .cloned()
-                    .collect();
+                let mut matches: Vec<Value> = {
+                    let conversations = self.conversations.lock().unwrap();
+                    conversations.iter()
+                        .filter(|entry| {
+                            entry.user_input.to_lowercase().contains(&query_lower) ||
+                            entry.ai_response.to_lowercase().contains(&query_lower)
+                        })
+                        .map(|entry| json!(entry))
+                        .collect()
+                };
+
+                let mut stored: Vec<Value> = Vec::new();
+                if let Some(memory) = &self.memory {
+                    if matches.is_empty() {
+                        matches = memory.search_conversations(query, limit).await?
+                            .into_iter()
+                            .map(conversation_json)
+                            .collect();
+                    }
+                    stored = memory.search_persistent_memory(query).await?
+                        .into_iter()
+                        .map(|(key, value)| json!({ "key": key, "value": parse_stored(&value) }))
+                        .collect();
+                }
                 
                 Ok(ToolResult {
                     success: true,
-                    result: json!(matches),
                     metadata: Some(json!({
                         "query": query,
-                        "matches_found": matches.len()
+                        "matches_found": matches.len(),
+                        "stored_matches": stored.len()
                     })),
+                    result: json!({
+                        "conversations": matches,
+                        "stored_data": stored
+                    }),
                 })
             }
             
@@ -194,15 +281,18 @@ impl Tool for MemoryTool {
                     .ok_or_else(|| anyhow!("Missing 'key' parameter"))?;
                 let value = args["value"].clone();
                 
-                let mut session_data = self.session_data.lock().unwrap();
-                session_data.insert(key.to_string(), value.clone());
+                self.session_data.lock().unwrap().insert(key.to_string(), value.clone());
+                if let Some(memory) = &self.memory {
+                    memory.store_persistent_memory(key, &value.to_string()).await?;
+                }
                 
                 Ok(ToolResult {
                     success: true,
                     result: json!({
                         "status": "stored",
                         "key": key,
-                        "value": value
+                        "value": value,
+                        "persistent": self.memory.is_some()
                     }),
                     metadata: None,
                 })
@@ -212,9 +302,7 @@ impl Tool for MemoryTool {
                 let key = args["key"].as_str()
                     .ok_or_else(|| anyhow!("Missing 'key' parameter"))?;
                 
-                let session_data = self.session_data.lock().unwrap();
-                
-                if let Some(value) = session_data.get(key) {
+                if let Some(value) = self.load_data(key).await? {
                     Ok(ToolResult {
                         success: true,
                         result: json!({
@@ -231,6 +319,46 @@ impl Tool for MemoryTool {
                     })
                 }
             }
+
+            "list_data" => {
+                let mut entries: HashMap<String, Value> = self.session_data.lock().unwrap().clone();
+                if let Some(memory) = &self.memory {
+                    for (key, value, _) in memory.list_persistent_memory().await? {
+                        entries.entry(key).or_insert_with(|| parse_stored(&value));
+                    }
+                }
+                let mut keys: Vec<&String> = entries.keys().collect();
+                keys.sort();
+                
+                Ok(ToolResult {
+                    success: true,
+                    result: json!(entries),
+                    metadata: Some(json!({
+                        "count": keys.len(),
+                        "keys": keys
+                    })),
+                })
+            }
+
+            "delete_data" => {
+                let key = args["key"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'key' parameter"))?;
+                
+                let mut deleted = self.session_data.lock().unwrap().remove(key).is_some();
+                if let Some(memory) = &self.memory {
+                    deleted |= memory.delete_persistent_memory(key).await?;
+                }
+                
+                Ok(ToolResult {
+                    success: deleted,
+                    result: json!(if deleted {
+                        format!("Deleted data for key: {}", key)
+                    } else {
+                        format!("No data found for key: {}", key)
+                    }),
+                    metadata: None,
+                })
+            }
             
             "clear_history" => {
                 let confirm = args["confirm"].as_bool().unwrap_or(false);
@@ -243,18 +371,28 @@ impl Tool for MemoryTool {
                     });
                 }
                 
-                let mut conversations = self.conversations.lock().unwrap();
-                let cleared_count = conversations.len();
-                conversations.clear();
-                
-                let mut session_data = self.session_data.lock().unwrap();
-                session_data.clear();
+                let cleared_count = {
+                    let mut conversations = self.conversations.lock().unwrap();
+                    let count = conversations.len();
+                    conversations.clear();
+                    count
+                };
+                self.session_data.lock().unwrap().clear();
+
+                // Remembered data outlives the conversation unless explicitly included
+                let cleared_data = match &self.memory {
+                    Some(memory) if args["clear_stored_data"].as_bool().unwrap_or(false) => {
+                        memory.clear_persistent_memory().await?
+                    }
+                    _ => 0,
+                };
                 
                 Ok(ToolResult {
                     success: true,
                     result: json!({
                         "status": "cleared",
-                        "cleared_conversations": cleared_count
+                        "cleared_conversations": cleared_count,
+                        "cleared_stored_data": cleared_data
                     }),
                     metadata: Some(json!({
                         "cleared_conversations": cleared_count
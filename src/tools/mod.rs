@@ -1,26 +1,36 @@
+pub mod audio;
+#[cfg(feature = "automation")]
+pub mod desktop;
 pub mod filesystem;
+pub mod git;
+pub mod github;
+pub mod sandbox;
+pub mod schedule;
 pub mod calculator;
 pub mod memory;
 pub mod planner;
+pub mod plan_graph;
 pub mod web;
+pub mod readability;
+pub mod web_policy;
+pub mod headless;
 pub mod command;
+pub mod command_policy;
+pub mod jobs;
 pub mod screenshot;
 pub mod voice;
 pub mod knowledge;
 pub mod system;
 pub mod news;
+pub mod script;
+pub mod tracker;
 pub mod manager;
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use async_trait::async_trait;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolCall {
-    pub tool_name: String,
-    pub function: String,
-    pub arguments: serde_json::Value,
-}
+pub use crate::models::ToolCall;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
@@ -29,23 +39,83 @@ pub struct ToolResult {
     pub metadata: Option<serde_json::Value>,
 }
 
+/// How far a tool can reach beyond answering a question; shown by `air tools list`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionLevel {
+    /// Only reads data or computes results
+    ReadOnly,
+    /// Can create, change or delete files and stored data
+    Modifies,
+    /// Asks the user before acting on anything outside its allow-list
+    Confirm,
+}
+
+impl std::fmt::Display for PermissionLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PermissionLevel::ReadOnly => "read-only",
+            PermissionLevel::Modifies => "modifies",
+            PermissionLevel::Confirm => "confirm",
+        })
+    }
+}
+
+/// Hit/miss counters of a tool's cache.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 { 0.0 } else { self.hits as f64 / total as f64 }
+    }
+}
+
 #[async_trait]
 pub trait Tool: Send + Sync {
     fn name(&self) -> &str;
     fn description(&self) -> &str;
     fn available_functions(&self) -> Vec<String>;
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::ReadOnly
+    }
+    /// Counters for tools that cache results, reported by `AIAgent::metrics()`.
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+    /// Session state worth carrying over to another process (see `AIAgent::snapshot`).
+    async fn snapshot(&self) -> Option<serde_json::Value> {
+        None
+    }
+    /// Takes back state produced by `snapshot`.
+    async fn restore(&self, _state: serde_json::Value) -> Result<()> {
+        Ok(())
+    }
     async fn execute(&self, function: &str, args: serde_json::Value) -> Result<ToolResult>;
 }
 
+#[cfg(feature = "automation")]
+pub use desktop::DesktopTool;
 pub use filesystem::FileSystemTool;
+pub use git::GitTool;
+pub use github::GitHubTool;
+pub use sandbox::PathSandbox;
 pub use calculator::CalculatorTool;
 pub use memory::MemoryTool;
 pub use planner::PlannerTool;
 pub use web::WebTool;
-pub use command::CommandTool;
-pub use screenshot::ScreenshotTool;
+pub use command::{CommandTool, Shell};
+pub use command_policy::{CommandDecision, CommandPolicy};
+pub use jobs::{JobInfo, JobRegistry, JobState};
+pub use screenshot::{ScreenshotTool, VisionAnalyzer};
 pub use voice::VoiceTool;
 pub use knowledge::KnowledgeTool;
 pub use system::SystemTool;
 pub use news::NewsTool;
-pub use manager::ToolManager;
+pub use script::ScriptTool;
+pub use tracker::TrackerTool;
+pub use manager::{ToolManager, ToolStats};
@@ -1,14 +1,21 @@
-use super::{Tool, ToolResult};
+use super::{PermissionLevel, Tool, ToolResult};
+use super::readability::truncate_chars;
+use crate::agent::memory::{FeedItem, MemoryManager};
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
 use serde_json::{json, Value};
 use reqwest::Client;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, warn};
 use scraper::{Html, Selector};
 
+const SUMMARY_CHARS: usize = 300;
+
 pub struct NewsTool {
     client: Client,
+    memory: Option<Arc<MemoryManager>>,
 }
 
 impl NewsTool {
@@ -19,7 +26,49 @@ impl NewsTool {
             .build()
             .unwrap();
 
-        Self { client }
+        Self { client, memory: None }
+    }
+
+    /// Enables feed subscriptions, stored in the persistent memory database.
+    pub fn with_memory(mut self, memory: Arc<MemoryManager>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    fn memory(&self) -> Result<&Arc<MemoryManager>> {
+        self.memory.as_ref().ok_or_else(|| anyhow!("Feed subscriptions need the memory database, which is unavailable"))
+    }
+
+    async fn fetch_feed(&self, url: &str) -> Result<feed_rs::model::Feed> {
+        let bytes = self.client.get(url).send().await?
+            .error_for_status()?
+            .bytes().await?;
+        feed_rs::parser::parse(bytes.as_ref()).map_err(|e| anyhow!("{} is not a valid RSS/Atom feed: {}", url, e))
+    }
+
+    /// Fetches every subscribed feed and stores items not seen before. Returns new-item counts per feed.
+    async fn refresh_feeds(&self) -> Result<Vec<Value>> {
+        let memory = self.memory()?;
+        let mut report = Vec::new();
+        for (url, title) in memory.list_news_feeds().await? {
+            let feed = match self.fetch_feed(&url).await {
+                Ok(feed) => feed,
+                Err(e) => {
+                    warn!("Failed to refresh feed {}: {}", url, e);
+                    report.push(json!({ "feed": url, "error": e.to_string() }));
+                    continue;
+                }
+            };
+
+            let mut new_items = 0;
+            for item in feed_items(&url, &feed) {
+                if memory.insert_news_item(&item, false).await? {
+                    new_items += 1;
+                }
+            }
+            report.push(json!({ "feed": url, "title": title, "new_items": new_items }));
+        }
+        Ok(report)
     }
 
     async fn scrape_google_news(&self, max_articles: usize) -> Result<Vec<Value>> {
@@ -60,6 +109,40 @@ impl NewsTool {
     }
 }
 
+fn feed_items(feed_url: &str, feed: &feed_rs::model::Feed) -> Vec<FeedItem> {
+    feed.entries.iter().filter_map(|entry| {
+        let link = entry.links.first().map(|l| l.href.clone());
+        // Some feeds omit ids; fall back to the link so items still dedupe
+        let entry_id = Some(entry.id.clone())
+            .filter(|id| !id.is_empty())
+            .or_else(|| link.clone())?;
+        let summary = entry.summary.as_ref()
+            .map(|text| plain_text(&text.content))
+            .or_else(|| entry.content.as_ref().and_then(|c| c.body.as_deref()).map(plain_text))
+            .filter(|s| !s.is_empty())
+            .map(|s| truncate_chars(&s, SUMMARY_CHARS).0);
+
+        Some(FeedItem {
+            feed_url: feed_url.to_string(),
+            entry_id,
+            title: entry.title.as_ref().map(|t| plain_text(&t.content)).unwrap_or_else(|| "(untitled)".to_string()),
+            link,
+            summary,
+            published: entry.published.or(entry.updated).map(|d| d.to_rfc3339()),
+        })
+    }).collect()
+}
+
+// Feed summaries are often HTML fragments
+fn plain_text(html: &str) -> String {
+    Html::parse_fragment(html).root_element().text()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[async_trait]
 impl Tool for NewsTool {
     fn name(&self) -> &str {
@@ -67,11 +150,22 @@ impl Tool for NewsTool {
     }
 
     fn description(&self) -> &str {
-        "Scrape news headlines from Google News. Useful for getting current events and top stories."
+        "Scrape news headlines from Google News, and manage RSS/Atom feed subscriptions with digests of unread items."
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Modifies
     }
 
     fn available_functions(&self) -> Vec<String> {
-        vec!["scrape_news".to_string()]
+        vec![
+            "scrape_news".to_string(),
+            "add_feed".to_string(),
+            "remove_feed".to_string(),
+            "list_feeds".to_string(),
+            "refresh_feeds".to_string(),
+            "digest".to_string(),
+        ]
     }
 
     async fn execute(&self, function: &str, args: Value) -> Result<ToolResult> {
@@ -100,7 +194,169 @@ impl Tool for NewsTool {
                     }
                 }
             }
+            "add_feed" => {
+                let url = args["url"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'url' parameter"))?;
+                let memory = self.memory()?;
+
+                // Validate the feed before subscribing, and seed it so the first digest isn't the whole backlog
+                let feed = match self.fetch_feed(url).await {
+                    Ok(feed) => feed,
+                    Err(e) => {
+                        return Ok(ToolResult {
+                            success: false,
+                            result: json!(format!("Failed to add feed: {}", e)),
+                            metadata: None,
+                        });
+                    }
+                };
+                let title = feed.title.as_ref().map(|t| plain_text(&t.content));
+                memory.add_news_feed(url, title.as_deref()).await?;
+
+                let items = feed_items(url, &feed);
+                for item in &items {
+                    memory.insert_news_item(item, true).await?;
+                }
+
+                Ok(ToolResult {
+                    success: true,
+                    result: json!({
+                        "url": url,
+                        "title": title,
+                        "items": items.len(),
+                        "message": format!("Subscribed to {}", title.as_deref().unwrap_or(url))
+                    }),
+                    metadata: None,
+                })
+            }
+
+            "remove_feed" => {
+                let url = args["url"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'url' parameter"))?;
+                let removed = self.memory()?.remove_news_feed(url).await?;
+                Ok(ToolResult {
+                    success: removed,
+                    result: json!(if removed {
+                        format!("Unsubscribed from {}", url)
+                    } else {
+                        format!("Not subscribed to {}", url)
+                    }),
+                    metadata: None,
+                })
+            }
+
+            "list_feeds" => {
+                let feeds = self.memory()?.list_news_feeds().await?;
+                let feeds: Vec<Value> = feeds.into_iter()
+                    .map(|(url, title)| json!({ "url": url, "title": title }))
+                    .collect();
+                Ok(ToolResult {
+                    success: true,
+                    metadata: Some(json!({ "count": feeds.len() })),
+                    result: json!(feeds),
+                })
+            }
+
+            "refresh_feeds" => {
+                let report = self.refresh_feeds().await?;
+                Ok(ToolResult {
+                    success: true,
+                    result: json!(report),
+                    metadata: None,
+                })
+            }
+
+            "digest" => {
+                let limit = args["limit"].as_u64().unwrap_or(30) as usize;
+                let mark_read = args["mark_read"].as_bool().unwrap_or(true);
+                let memory = self.memory()?;
+
+                let report = self.refresh_feeds().await?;
+                let items = memory.unread_news_items(limit).await?;
+                if items.is_empty() {
+                    return Ok(ToolResult {
+                        success: true,
+                        result: json!("No unread items in your subscribed feeds."),
+                        metadata: Some(json!({ "refresh": report })),
+                    });
+                }
+
+                let titles: BTreeMap<String, String> = memory.list_news_feeds().await?
+                    .into_iter()
+                    .map(|(url, title)| (url.clone(), title.unwrap_or(url)))
+                    .collect();
+                let mut by_feed: BTreeMap<String, Vec<&FeedItem>> = BTreeMap::new();
+                for item in &items {
+                    let feed = titles.get(&item.feed_url).cloned().unwrap_or_else(|| item.feed_url.clone());
+                    by_feed.entry(feed).or_default().push(item);
+                }
+
+                if mark_read {
+                    memory.mark_news_items_read(&items).await?;
+                }
+
+                Ok(ToolResult {
+                    success: true,
+                    result: json!({
+                        "instructions": "Summarize these unread feed items for the user as a short digest grouped by feed, highlighting the most important stories and including links.",
+                        "unread_count": items.len(),
+                        "feeds": by_feed,
+                    }),
+                    metadata: Some(json!({ "refresh": report, "marked_read": mark_read })),
+                })
+            }
+
             _ => Err(anyhow!("Unknown function: {}", function))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_items_from_rss() {
+        let rss = r#"<?xml version="1.0"?>
+            <rss version="2.0"><channel><title>Example</title>
+              <item><guid>1</guid><title>First</title><link>https://example.com/1</link>
+                <description>&lt;p&gt;Hello &lt;b&gt;world&lt;/b&gt;&lt;/p&gt;</description></item>
+              <item><title>No guid</title><link>https://example.com/2</link></item>
+            </channel></rss>"#;
+        let feed = feed_rs::parser::parse(rss.as_bytes()).unwrap();
+        let items = feed_items("https://example.com/feed", &feed);
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "First");
+        assert_eq!(items[0].summary.as_deref(), Some("Hello world"));
+        assert!(!items[1].entry_id.is_empty());
+    }
+
+    #[test]
+    fn test_feed_items_from_atom() {
+        let atom = r#"<?xml version="1.0"?>
+            <feed xmlns="http://www.w3.org/2005/Atom"><title>Example</title><id>urn:feed</id>
+              <entry><id>urn:a</id><title type="html">A &lt;b&gt;bold&lt;/b&gt; title</title>
+                <updated>2024-05-01T10:00:00Z</updated>
+                <content type="html">&lt;div&gt;Body
+                  text&lt;/div&gt;</content></entry>
+            </feed>"#;
+        let feed = feed_rs::parser::parse(atom.as_bytes()).unwrap();
+        let items = feed_items("https://example.com/atom", &feed);
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].entry_id, "urn:a");
+        assert_eq!(items[0].link, None);
+        assert_eq!(items[0].title, "A bold title");
+        assert_eq!(items[0].summary.as_deref(), Some("Body text"));
+        assert_eq!(items[0].published.as_deref(), Some("2024-05-01T10:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(plain_text("<p>Hello <b>world</b></p>\n<p>again</p>"), "Hello world again");
+        assert_eq!(plain_text("no markup,   just   spaces"), "no markup, just spaces");
+        assert_eq!(plain_text("Tom &amp; Jerry"), "Tom & Jerry");
+        assert_eq!(plain_text(""), "");
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::{Result, anyhow};
+use std::collections::{HashMap, HashSet, VecDeque};
+use super::planner::{Task, TaskStatus};
+
+/// Minutes assumed for tasks without an estimate when computing the critical path.
+pub const DEFAULT_TASK_MINUTES: u32 = 30;
+
+pub fn is_done(task: &Task) -> bool {
+    matches!(task.status, TaskStatus::Completed | TaskStatus::Cancelled)
+}
+
+/// Dependencies of `task` that are still open.
+pub fn open_blockers<'a>(tasks: &'a HashMap<String, Task>, task: &Task) -> Vec<&'a Task> {
+    task.dependencies.iter()
+        .filter_map(|id| tasks.get(id))
+        .filter(|dep| !is_done(dep))
+        .collect()
+}
+
+/// True if making `task_id` depend on `dependency_id` would close a cycle.
+pub fn would_create_cycle(tasks: &HashMap<String, Task>, task_id: &str, dependency_id: &str) -> bool {
+    // A cycle exists if task_id is already reachable from dependency_id via dependency edges
+    let mut stack = vec![dependency_id];
+    let mut seen = HashSet::new();
+    while let Some(id) = stack.pop() {
+        if id == task_id {
+            return true;
+        }
+        if !seen.insert(id) {
+            continue;
+        }
+        if let Some(task) = tasks.get(id) {
+            stack.extend(task.dependencies.iter().map(|d| d.as_str()));
+        }
+    }
+    false
+}
+
+/// Orders `ids` so every task comes after the tasks it depends on (edges outside `ids` are ignored).
+pub fn topological_order(tasks: &HashMap<String, Task>, ids: &[String]) -> Result<Vec<String>> {
+    let members: HashSet<&str> = ids.iter().map(|s| s.as_str()).collect();
+    let mut indegree: HashMap<&str, usize> = members.iter().map(|id| (*id, 0)).collect();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for id in ids {
+        let Some(task) = tasks.get(id) else { continue };
+        for dep in task.dependencies.iter().filter(|d| members.contains(d.as_str())) {
+            *indegree.get_mut(id.as_str()).unwrap() += 1;
+            dependents.entry(dep.as_str()).or_default().push(id.as_str());
+        }
+    }
+
+    // Seed in the caller's order so output is stable
+    let mut queue: VecDeque<&str> = ids.iter()
+        .map(|s| s.as_str())
+        .filter(|id| indegree[id] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(ids.len());
+    while let Some(id) = queue.pop_front() {
+        order.push(id.to_string());
+        for &next in dependents.get(id).into_iter().flatten() {
+            let degree = indegree.get_mut(next).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    if order.len() != members.len() {
+        return Err(anyhow!("Task dependencies contain a cycle"));
+    }
+    Ok(order)
+}
+
+/// Longest chain of open tasks by estimated minutes. Returns the chain (first task first) and its length.
+pub fn critical_path(tasks: &HashMap<String, Task>, ids: &[String]) -> Result<(Vec<String>, u32)> {
+    let order = topological_order(tasks, ids)?;
+    let mut best: HashMap<&str, (u32, Option<&str>)> = HashMap::new();
+
+    for id in &order {
+        let task = &tasks[id];
+        let own = if is_done(task) { 0 } else { task.estimated_duration.unwrap_or(DEFAULT_TASK_MINUTES) };
+        let (before, prev) = task.dependencies.iter()
+            .filter_map(|dep| best.get(dep.as_str()).map(|(total, _)| (*total, Some(dep.as_str()))))
+            .max_by_key(|(total, _)| *total)
+            .unwrap_or((0, None));
+        best.insert(id.as_str(), (before + own, prev));
+    }
+
+    let Some((end, (total, _))) = best.iter().max_by_key(|(_, (total, _))| *total) else {
+        return Ok((Vec::new(), 0));
+    };
+
+    let mut path = vec![end.to_string()];
+    let mut cursor = best[end].1;
+    while let Some(id) = cursor {
+        path.push(id.to_string());
+        cursor = best[id].1;
+    }
+    path.reverse();
+    Ok((path, *total))
+}
+
+pub fn to_mermaid(tasks: &HashMap<String, Task>, ids: &[String], critical: &HashSet<String>) -> String {
+    let mut out = String::from("graph TD\n");
+    for id in ids {
+        let Some(task) = tasks.get(id) else { continue };
+        out.push_str(&format!("    {}[\"{}\"]\n", node_id(id), escape_label(&label(task))));
+    }
+    for id in ids {
+        let Some(task) = tasks.get(id) else { continue };
+        for dep in task.dependencies.iter().filter(|d| ids.contains(*d)) {
+            out.push_str(&format!("    {} --> {}\n", node_id(dep), node_id(id)));
+        }
+    }
+    out.push_str("    classDef done fill:#d4edda,stroke:#28a745\n");
+    out.push_str("    classDef critical stroke:#dc3545,stroke-width:3px\n");
+    for id in ids {
+        let Some(task) = tasks.get(id) else { continue };
+        if is_done(task) {
+            out.push_str(&format!("    class {} done\n", node_id(id)));
+        } else if critical.contains(id) {
+            out.push_str(&format!("    class {} critical\n", node_id(id)));
+        }
+    }
+    out
+}
+
+pub fn to_dot(tasks: &HashMap<String, Task>, ids: &[String], critical: &HashSet<String>) -> String {
+    let mut out = String::from("digraph plan {\n    rankdir=LR;\n    node [shape=box, style=rounded];\n");
+    for id in ids {
+        let Some(task) = tasks.get(id) else { continue };
+        let style = if is_done(task) {
+            ", style=\"rounded,filled\", fillcolor=\"#d4edda\""
+        } else if critical.contains(id) {
+            ", color=\"#dc3545\", penwidth=3"
+        } else {
+            ""
+        };
+        out.push_str(&format!("    {} [label=\"{}\"{}];\n", node_id(id), escape_label(&label(task)), style));
+    }
+    for id in ids {
+        let Some(task) = tasks.get(id) else { continue };
+        for dep in task.dependencies.iter().filter(|d| ids.contains(*d)) {
+            out.push_str(&format!("    {} -> {};\n", node_id(dep), node_id(id)));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn label(task: &Task) -> String {
+    match task.estimated_duration {
+        Some(minutes) => format!("{} ({}m)", task.title, minutes),
+        None => task.title.clone(),
+    }
+}
+
+// Task ids are UUIDs; strip the dashes so they're valid identifiers in both formats.
+fn node_id(id: &str) -> String {
+    format!("t{}", id.replace('-', ""))
+}
+
+fn escape_label(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::planner::Priority;
+    use chrono::Utc;
+
+    fn task(id: &str, deps: &[&str], minutes: u32) -> Task {
+        Task {
+            id: id.to_string(),
+            title: id.to_uppercase(),
+            description: String::new(),
+            priority: Priority::Medium,
+            status: TaskStatus::NotStarted,
+            created_at: Utc::now(),
+            due_date: None,
+            subtasks: Vec::new(),
+            dependencies: deps.iter().map(|d| d.to_string()).collect(),
+            estimated_duration: Some(minutes),
+            tags: Vec::new(),
+            recurrence: None,
+        }
+    }
+
+    fn graph() -> (HashMap<String, Task>, Vec<String>) {
+        // a -> b -> d and a -> c -> d, with the c branch being longer
+        let tasks = vec![
+            task("a", &[], 10),
+            task("b", &["a"], 5),
+            task("c", &["a"], 40),
+            task("d", &["b", "c"], 15),
+        ];
+        let ids = tasks.iter().map(|t| t.id.clone()).collect();
+        (tasks.into_iter().map(|t| (t.id.clone(), t)).collect(), ids)
+    }
+
+    #[test]
+    fn test_topological_order_and_cycles() {
+        let (mut tasks, ids) = graph();
+        let order = topological_order(&tasks, &ids).unwrap();
+        let pos = |id: &str| order.iter().position(|x| x == id).unwrap();
+        assert!(pos("a") < pos("b") && pos("c") < pos("d"));
+
+        assert!(would_create_cycle(&tasks, "a", "d"));
+        assert!(!would_create_cycle(&tasks, "d", "a"));
+
+        tasks.get_mut("a").unwrap().dependencies.push("d".to_string());
+        assert!(topological_order(&tasks, &ids).is_err());
+    }
+
+    #[test]
+    fn test_critical_path_skips_completed_work() {
+        let (mut tasks, ids) = graph();
+        let (path, minutes) = critical_path(&tasks, &ids).unwrap();
+        assert_eq!(path, vec!["a", "c", "d"]);
+        assert_eq!(minutes, 65);
+
+        tasks.get_mut("a").unwrap().status = TaskStatus::Completed;
+        let (_, minutes) = critical_path(&tasks, &ids).unwrap();
+        assert_eq!(minutes, 55);
+    }
+
+    #[test]
+    fn test_exports_include_edges() {
+        let (tasks, ids) = graph();
+        let critical: HashSet<String> = ["a", "c", "d"].iter().map(|s| s.to_string()).collect();
+
+        let mermaid = to_mermaid(&tasks, &ids, &critical);
+        assert!(mermaid.starts_with("graph TD"));
+        assert!(mermaid.contains("ta --> tb"));
+        assert!(mermaid.contains("class tc critical"));
+
+        let dot = to_dot(&tasks, &ids, &critical);
+        assert!(dot.contains("tc -> td;"));
+    }
+}
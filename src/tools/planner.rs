@@ -1,10 +1,18 @@
-use super::{Tool, ToolResult};
+use super::{PermissionLevel, Tool, ToolResult};
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
 use serde_json::{json, Value};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Local, Utc};
+use tracing::warn;
+use crate::agent::memory::MemoryManager;
+use super::schedule::{parse_due_date, Recurrence};
+use super::plan_graph;
+
+const TASK_KIND: &str = "task";
+const PLAN_KIND: &str = "plan";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
@@ -19,6 +27,8 @@ pub struct Task {
     pub dependencies: Vec<String>,
     pub estimated_duration: Option<u32>, // minutes
     pub tags: Vec<String>,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,13 +59,52 @@ pub struct Plan {
 }
 
 pub struct PlannerTool {
-    tasks: std::sync::Arc<std::sync::Mutex<HashMap<String, Task>>>,
+    tasks: Arc<Mutex<HashMap<String, Task>>>,
+    plans: Arc<Mutex<HashMap<String, Plan>>>,
+    // Backing store; tasks only live for the session when absent
+    memory: Option<Arc<MemoryManager>>,
 }
 
 impl PlannerTool {
     pub fn new() -> Self {
         Self {
-            tasks: std::sync::Arc::new(std::sync::Mutex::new(HashMap::new())),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            plans: Arc::new(Mutex::new(HashMap::new())),
+            memory: None,
+        }
+    }
+
+    /// Creates a planner backed by the persistent memory database, loading saved tasks and plans.
+    pub async fn with_memory(memory: Arc<MemoryManager>) -> Self {
+        let tasks: HashMap<String, Task> = load_items(&memory, TASK_KIND).await
+            .into_iter()
+            .map(|task: Task| (task.id.clone(), task))
+            .collect();
+        let plans: HashMap<String, Plan> = load_items(&memory, PLAN_KIND).await
+            .into_iter()
+            .map(|plan: Plan| (plan.id.clone(), plan))
+            .collect();
+
+        Self {
+            tasks: Arc::new(Mutex::new(tasks)),
+            plans: Arc::new(Mutex::new(plans)),
+            memory: Some(memory),
+        }
+    }
+
+    async fn persist_task(&self, task: &Task) {
+        if let Some(memory) = &self.memory {
+            if let Err(e) = save_item(memory, TASK_KIND, &task.id, task).await {
+                warn!("Failed to save task {}: {}", task.id, e);
+            }
+        }
+    }
+
+    async fn persist_plan(&self, plan: &Plan) {
+        if let Some(memory) = &self.memory {
+            if let Err(e) = save_item(memory, PLAN_KIND, &plan.id, plan).await {
+                warn!("Failed to save plan {}: {}", plan.id, e);
+            }
         }
     }
     
@@ -80,6 +129,61 @@ impl PlannerTool {
         }
     }
     
+    fn parse_due_arg(&self, args: &Value) -> Result<Option<DateTime<Utc>>> {
+        match args["due"].as_str().or_else(|| args["due_date"].as_str()) {
+            Some(due) => Ok(Some(parse_due_date(due, Local::now())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn parse_recurrence_arg(&self, args: &Value) -> Result<Option<Recurrence>> {
+        args["recurrence"].as_str().map(Recurrence::parse).transpose()
+    }
+
+    /// Open tasks that are overdue or due before the end of today, in due order.
+    pub fn due_tasks(&self) -> (Vec<Task>, Vec<Task>) {
+        let now = Utc::now();
+        let end_of_today = parse_due_date("today", Local::now()).unwrap_or(now);
+        
+        let mut overdue = Vec::new();
+        let mut due_today = Vec::new();
+        for task in self.tasks.lock().unwrap().values() {
+            if matches!(task.status, TaskStatus::Completed | TaskStatus::Cancelled) {
+                continue;
+            }
+            match task.due_date {
+                Some(due) if due < now => overdue.push(task.clone()),
+                Some(due) if due <= end_of_today => due_today.push(task.clone()),
+                _ => {}
+            }
+        }
+        overdue.sort_by_key(|t| t.due_date);
+        due_today.sort_by_key(|t| t.due_date);
+        (overdue, due_today)
+    }
+
+    /// Looks up the plan named by `plan_id`, or `None` to mean all tasks.
+    fn resolve_plan(&self, args: &Value) -> Result<Option<Plan>> {
+        match args["plan_id"].as_str() {
+            Some(id) => self.plans.lock().unwrap().get(id).cloned()
+                .map(Some)
+                .ok_or_else(|| anyhow!("Plan not found: {}", id)),
+            None => Ok(None),
+        }
+    }
+
+    fn plan_task_ids(&self, plan: Option<&Plan>) -> Vec<String> {
+        let tasks = self.tasks.lock().unwrap();
+        match plan {
+            Some(plan) => plan.tasks.iter().filter(|id| tasks.contains_key(*id)).cloned().collect(),
+            None => {
+                let mut all: Vec<&Task> = tasks.values().collect();
+                all.sort_by_key(|t| t.created_at);
+                all.into_iter().map(|t| t.id.clone()).collect()
+            }
+        }
+    }
+
     fn break_down_complex_task(&self, description: &str) -> Vec<String> {
         // Simple heuristic to break down complex tasks
         let keywords = ["and", "then", "after", "also", "additionally", "furthermore"];
@@ -119,7 +223,32 @@ impl Tool for PlannerTool {
     }
     
     fn description(&self) -> &str {
-        "Task planning and breakdown: create, manage, and track tasks and plans"
+        "Task planning and breakdown: create, manage, and track tasks and plans. Tasks accept natural due dates ('next friday at 5pm'), recurrence ('weekly', 'every 2 days') and dependencies; plans can be exported as Mermaid or Graphviz diagrams"
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Modifies
+    }
+
+    async fn snapshot(&self) -> Option<Value> {
+        let tasks: Vec<Task> = self.tasks.lock().unwrap().values().cloned().collect();
+        let plans: Vec<Plan> = self.plans.lock().unwrap().values().cloned().collect();
+        Some(json!({ "tasks": tasks, "plans": plans }))
+    }
+
+    /// Merges snapshotted tasks and plans into the current ones (same ids are replaced) and saves them.
+    async fn restore(&self, state: Value) -> Result<()> {
+        let tasks: Vec<Task> = serde_json::from_value(state["tasks"].clone()).unwrap_or_default();
+        let plans: Vec<Plan> = serde_json::from_value(state["plans"].clone()).unwrap_or_default();
+        for task in tasks {
+            self.persist_task(&task).await;
+            self.tasks.lock().unwrap().insert(task.id.clone(), task);
+        }
+        for plan in plans {
+            self.persist_plan(&plan).await;
+            self.plans.lock().unwrap().insert(plan.id.clone(), plan);
+        }
+        Ok(())
     }
     
     fn available_functions(&self) -> Vec<String> {
@@ -132,6 +261,12 @@ impl Tool for PlannerTool {
             "suggest_next_action".to_string(),
             "get_task_status".to_string(),
             "estimate_completion".to_string(),
+            "delete_task".to_string(),
+            "due_tasks".to_string(),
+            "add_dependency".to_string(),
+            "remove_dependency".to_string(),
+            "export_plan".to_string(),
+            "list_plans".to_string(),
         ]
     }
     
@@ -146,6 +281,29 @@ impl Tool for PlannerTool {
                 let tags: Vec<String> = args["tags"].as_array()
                     .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
                     .unwrap_or_default();
+                let dependencies: Vec<String> = args["depends_on"].as_array()
+                    .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+                    .unwrap_or_default();
+                let unknown: Vec<String> = {
+                    let tasks = self.tasks.lock().unwrap();
+                    dependencies.iter().filter(|id| !tasks.contains_key(*id)).cloned().collect()
+                };
+                if !unknown.is_empty() {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Unknown dependency task ids: {}", unknown.join(", "))),
+                        metadata: None,
+                    });
+                }
+                let due_date = self.parse_due_arg(&args)?;
+                let recurrence = self.parse_recurrence_arg(&args)?;
+                if recurrence.is_some() && due_date.is_none() {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!("Recurring tasks need a 'due' date to repeat from"),
+                        metadata: None,
+                    });
+                }
                 
                 let task = Task {
                     id: uuid::Uuid::new_v4().to_string(),
@@ -154,23 +312,26 @@ impl Tool for PlannerTool {
                     priority: self.parse_priority(priority),
                     status: TaskStatus::NotStarted,
                     created_at: Utc::now(),
-                    due_date: None,
+                    due_date,
                     subtasks: Vec::new(),
-                    dependencies: Vec::new(),
+                    dependencies,
                     estimated_duration: args["duration"].as_u64().map(|d| d as u32),
                     tags,
+                    recurrence,
                 };
                 
                 let task_id = task.id.clone();
-                let mut tasks = self.tasks.lock().unwrap();
-                tasks.insert(task_id.clone(), task);
+                self.tasks.lock().unwrap().insert(task_id.clone(), task.clone());
+                self.persist_task(&task).await;
                 
                 Ok(ToolResult {
                     success: true,
                     result: json!({
                         "task_id": task_id,
                         "title": title,
-                        "status": "created"
+                        "status": "created",
+                        "due_date": due_date.map(|d| d.with_timezone(&Local).to_rfc3339()),
+                        "recurrence": recurrence
                     }),
                     metadata: Some(json!({
                         "task_id": task_id,
@@ -246,12 +407,12 @@ impl Tool for PlannerTool {
             "suggest_next_action" => {
                 let tasks = self.tasks.lock().unwrap();
                 
-                // Find highest priority, non-completed tasks
-                let mut pending_tasks: Vec<_> = tasks.values()
-                    .filter(|task| !matches!(task.status, TaskStatus::Completed | TaskStatus::Cancelled))
-                    .collect();
+                // Find highest priority, non-completed tasks whose dependencies are all done
+                let (mut pending_tasks, blocked_tasks): (Vec<_>, Vec<_>) = tasks.values()
+                    .filter(|task| !plan_graph::is_done(task))
+                    .partition(|task| plan_graph::open_blockers(&tasks, task).is_empty());
                 
-                // Sort by priority and creation date
+                // Sort by priority, then due date, then creation date
                 pending_tasks.sort_by(|a, b| {
                     use std::cmp::Ordering;
                     
@@ -265,8 +426,12 @@ impl Tool for PlannerTool {
                     let a_priority = priority_order(&a.priority);
                     let b_priority = priority_order(&b.priority);
                     
+                    // Tasks with a due date come before undated ones
+                    let due_order = |t: &&Task| (t.due_date.is_none(), t.due_date);
+                    
                     match a_priority.cmp(&b_priority) {
-                        Ordering::Equal => a.created_at.cmp(&b.created_at),
+                        Ordering::Equal => due_order(a).cmp(&due_order(b))
+                            .then_with(|| a.created_at.cmp(&b.created_at)),
                         other => other,
                     }
                 });
@@ -277,6 +442,7 @@ impl Tool for PlannerTool {
                         result: json!(next_task),
                         metadata: Some(json!({
                             "pending_tasks_count": pending_tasks.len(),
+                            "blocked_tasks_count": blocked_tasks.len(),
                             "next_task_id": next_task.id
                         })),
                     })
@@ -284,8 +450,13 @@ impl Tool for PlannerTool {
                     Ok(ToolResult {
                         success: true,
                         result: json!({
-                            "message": "No pending tasks found",
-                            "pending_tasks_count": 0
+                            "message": if blocked_tasks.is_empty() {
+                                "No pending tasks found"
+                            } else {
+                                "All pending tasks are blocked by unfinished dependencies (check for a dependency cycle)"
+                            },
+                            "pending_tasks_count": 0,
+                            "blocked_tasks_count": blocked_tasks.len()
                         }),
                         metadata: Some(json!({
                             "pending_tasks_count": 0,
@@ -299,25 +470,58 @@ impl Tool for PlannerTool {
                 let task_id = args["task_id"].as_str()
                     .ok_or_else(|| anyhow!("Missing 'task_id' parameter"))?;
                 
-                let mut tasks = self.tasks.lock().unwrap();
+                let due_date = self.parse_due_arg(&args)?;
+                let recurrence = self.parse_recurrence_arg(&args)?;
                 
-                if let Some(task) = tasks.get_mut(task_id) {
-                    let mut updated_fields = Vec::new();
-                    
-                    if let Some(status) = args["status"].as_str() {
-                        task.status = self.parse_status(status);
-                        updated_fields.push(format!("status: {:?}", task.status));
-                    }
-                    
-                    if let Some(priority) = args["priority"].as_str() {
-                        task.priority = self.parse_priority(priority);
-                        updated_fields.push(format!("priority: {:?}", task.priority));
-                    }
-                    
-                    if let Some(title) = args["title"].as_str() {
-                        task.title = title.to_string();
-                        updated_fields.push(format!("title: {}", title));
-                    }
+                let updated = {
+                    let mut tasks = self.tasks.lock().unwrap();
+                    tasks.get_mut(task_id).map(|task| {
+                        let mut updated_fields = Vec::new();
+                        
+                        if let Some(due) = due_date {
+                            task.due_date = Some(due);
+                            updated_fields.push(format!("due_date: {}", due.with_timezone(&Local).to_rfc3339()));
+                        }
+                        
+                        if let Some(rule) = recurrence {
+                            task.recurrence = Some(rule);
+                            updated_fields.push(format!("recurrence: every {} {:?}", rule.interval, rule.unit));
+                        }
+                        
+                        if let Some(status) = args["status"].as_str() {
+                            task.status = self.parse_status(status);
+                            updated_fields.push(format!("status: {:?}", task.status));
+                        }
+                        
+                        if let Some(priority) = args["priority"].as_str() {
+                            task.priority = self.parse_priority(priority);
+                            updated_fields.push(format!("priority: {:?}", task.priority));
+                        }
+                        
+                        if let Some(title) = args["title"].as_str() {
+                            task.title = title.to_string();
+                            updated_fields.push(format!("title: {}", title));
+                        }
+                        
+                        if let Some(tags) = args["tags"].as_array() {
+                            task.tags = tags.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect();
+                            updated_fields.push(format!("tags: {}", task.tags.join(", ")));
+                        }
+                        
+                        // Completing a recurring task schedules its next occurrence instead
+                        if let (TaskStatus::Completed, Some(rule), Some(due)) = (&task.status, task.recurrence, task.due_date) {
+                            let next = rule.next_after(due, Utc::now());
+                            task.due_date = Some(next);
+                            task.status = TaskStatus::NotStarted;
+                            updated_fields.push(format!("rescheduled to {}", next.with_timezone(&Local).to_rfc3339()));
+                        }
+                        
+                        (task.clone(), updated_fields)
+                    })
+                };
+                
+                if let Some((task, updated_fields)) = updated {
+                    self.persist_task(&task).await;
                     
                     Ok(ToolResult {
                         success: true,
@@ -340,7 +544,318 @@ impl Tool for PlannerTool {
                 }
             }
             
+            "due_tasks" => {
+                let (overdue, due_today) = self.due_tasks();
+                let notify = args["notify"].as_bool().unwrap_or(false);
+                
+                if notify && !(overdue.is_empty() && due_today.is_empty()) {
+                    if let Err(e) = send_reminder_notification(&overdue, &due_today) {
+                        warn!("Failed to show reminder notification: {}", e);
+                    }
+                }
+                
+                Ok(ToolResult {
+                    success: true,
+                    result: json!({
+                        "overdue": overdue,
+                        "due_today": due_today
+                    }),
+                    metadata: Some(json!({
+                        "overdue_count": overdue.len(),
+                        "due_today_count": due_today.len()
+                    })),
+                })
+            }
+            
+            "add_dependency" | "remove_dependency" => {
+                let task_id = args["task_id"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'task_id' parameter"))?;
+                let dependency_id = args["depends_on"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'depends_on' parameter"))?;
+                let adding = function == "add_dependency";
+                
+                let outcome: std::result::Result<Task, String> = {
+                    let mut tasks = self.tasks.lock().unwrap();
+                    if !tasks.contains_key(dependency_id) {
+                        Err(format!("Task not found: {}", dependency_id))
+                    } else if adding && (task_id == dependency_id || plan_graph::would_create_cycle(&tasks, task_id, dependency_id)) {
+                        Err(format!("Adding this dependency would create a cycle between {} and {}", task_id, dependency_id))
+                    } else {
+                        match tasks.get_mut(task_id) {
+                            Some(task) => {
+                                if adding {
+                                    if !task.dependencies.iter().any(|d| d == dependency_id) {
+                                        task.dependencies.push(dependency_id.to_string());
+                                    }
+                                } else {
+                                    task.dependencies.retain(|d| d != dependency_id);
+                                }
+                                Ok(task.clone())
+                            }
+                            None => Err(format!("Task not found: {}", task_id)),
+                        }
+                    }
+                };
+                
+                match outcome {
+                    Ok(task) => {
+                        self.persist_task(&task).await;
+                        Ok(ToolResult {
+                            success: true,
+                            result: json!({
+                                "task_id": task.id,
+                                "dependencies": task.dependencies
+                            }),
+                            metadata: None,
+                        })
+                    }
+                    Err(message) => Ok(ToolResult {
+                        success: false,
+                        result: json!(message),
+                        metadata: None,
+                    }),
+                }
+            }
+            
+            "estimate_completion" => {
+                let plan = match self.resolve_plan(&args) {
+                    Ok(plan) => plan,
+                    Err(e) => return Ok(ToolResult { success: false, result: json!(e.to_string()), metadata: None }),
+                };
+                let ids = self.plan_task_ids(plan.as_ref());
+                
+                let estimate = {
+                    let tasks = self.tasks.lock().unwrap();
+                    plan_graph::critical_path(&tasks, &ids).map(|(path, minutes)| {
+                        let titles: Vec<String> = path.iter().map(|id| tasks[id].title.clone()).collect();
+                        let unestimated = ids.iter()
+                            .filter(|id| !plan_graph::is_done(&tasks[*id]) && tasks[*id].estimated_duration.is_none())
+                            .count();
+                        (path, titles, minutes, unestimated)
+                    })
+                };
+                let (path, titles, minutes, unestimated) = match estimate {
+                    Ok(estimate) => estimate,
+                    Err(e) => return Ok(ToolResult { success: false, result: json!(e.to_string()), metadata: None }),
+                };
+                
+                let completion = Utc::now() + chrono::Duration::minutes(minutes as i64);
+                if let Some(mut plan) = plan {
+                    plan.estimated_completion = Some(completion);
+                    self.plans.lock().unwrap().insert(plan.id.clone(), plan.clone());
+                    self.persist_plan(&plan).await;
+                }
+                
+                Ok(ToolResult {
+                    success: true,
+                    result: json!({
+                        "critical_path": titles,
+                        "remaining_minutes": minutes,
+                        "estimated_completion": completion.with_timezone(&Local).to_rfc3339(),
+                        "unestimated_tasks": unestimated
+                    }),
+                    metadata: Some(json!({
+                        "critical_path_ids": path,
+                        "default_minutes_per_unestimated_task": plan_graph::DEFAULT_TASK_MINUTES
+                    })),
+                })
+            }
+            
+            "export_plan" => {
+                let plan = match self.resolve_plan(&args) {
+                    Ok(plan) => plan,
+                    Err(e) => return Ok(ToolResult { success: false, result: json!(e.to_string()), metadata: None }),
+                };
+                let format = args["format"].as_str().unwrap_or("mermaid").to_lowercase();
+                let ids = self.plan_task_ids(plan.as_ref());
+                
+                let tasks = self.tasks.lock().unwrap();
+                let critical: std::collections::HashSet<String> = plan_graph::critical_path(&tasks, &ids)
+                    .map(|(path, _)| path.into_iter().collect())
+                    .unwrap_or_default();
+                let diagram = match format.as_str() {
+                    "mermaid" => plan_graph::to_mermaid(&tasks, &ids, &critical),
+                    "dot" | "graphviz" => plan_graph::to_dot(&tasks, &ids, &critical),
+                    other => return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Unsupported format '{}' (use mermaid or dot)", other)),
+                        metadata: None,
+                    }),
+                };
+                
+                Ok(ToolResult {
+                    success: true,
+                    result: json!(diagram),
+                    metadata: Some(json!({
+                        "format": format,
+                        "plan_id": plan.map(|p| p.id),
+                        "task_count": ids.len()
+                    })),
+                })
+            }
+            
+            "get_task_status" => {
+                let task_id = args["task_id"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'task_id' parameter"))?;
+                
+                let found = {
+                    let tasks = self.tasks.lock().unwrap();
+                    tasks.get(task_id).map(|task| {
+                        let blockers: Vec<String> = plan_graph::open_blockers(&tasks, task)
+                            .iter()
+                            .map(|t| t.title.clone())
+                            .collect();
+                        (task.clone(), blockers)
+                    })
+                };
+                match found {
+                    Some((task, blocked_by)) => Ok(ToolResult {
+                        success: true,
+                        result: json!({
+                            "task_id": task.id,
+                            "title": task.title,
+                            "status": task.status,
+                            "blocked_by": blocked_by
+                        }),
+                        metadata: Some(json!(task)),
+                    }),
+                    None => Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Task not found: {}", task_id)),
+                        metadata: None,
+                    }),
+                }
+            }
+            
+            "delete_task" => {
+                let task_id = args["task_id"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'task_id' parameter"))?;
+                
+                let removed = self.tasks.lock().unwrap().remove(task_id);
+                if removed.is_none() {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Task not found: {}", task_id)),
+                        metadata: None,
+                    });
+                }
+                
+                if let Some(memory) = &self.memory {
+                    if let Err(e) = memory.delete_planner_item(TASK_KIND, task_id).await {
+                        warn!("Failed to delete task {}: {}", task_id, e);
+                    }
+                }
+                
+                Ok(ToolResult {
+                    success: true,
+                    result: json!({
+                        "task_id": task_id,
+                        "status": "deleted"
+                    }),
+                    metadata: None,
+                })
+            }
+            
+            "create_plan" => {
+                let name = args["name"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'name' parameter"))?;
+                let description = args["description"].as_str().unwrap_or(name);
+                let task_ids: Vec<String> = args["tasks"].as_array()
+                    .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+                    .unwrap_or_default();
+                
+                let unknown: Vec<String> = {
+                    let tasks = self.tasks.lock().unwrap();
+                    task_ids.iter().filter(|id| !tasks.contains_key(*id)).cloned().collect()
+                };
+                if !unknown.is_empty() {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Unknown task ids: {}", unknown.join(", "))),
+                        metadata: None,
+                    });
+                }
+                
+                let plan = Plan {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    tasks: task_ids,
+                    created_at: Utc::now(),
+                    estimated_completion: None,
+                };
+                
+                self.plans.lock().unwrap().insert(plan.id.clone(), plan.clone());
+                self.persist_plan(&plan).await;
+                
+                Ok(ToolResult {
+                    success: true,
+                    result: json!({
+                        "plan_id": plan.id,
+                        "name": plan.name,
+                        "task_count": plan.tasks.len(),
+                        "status": "created"
+                    }),
+                    metadata: Some(json!({
+                        "plan_id": plan.id
+                    })),
+                })
+            }
+            
+            "list_plans" => {
+                let mut plans: Vec<Plan> = self.plans.lock().unwrap().values().cloned().collect();
+                plans.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+                
+                Ok(ToolResult {
+                    success: true,
+                    result: json!(plans),
+                    metadata: Some(json!({
+                        "total_plans": plans.len()
+                    })),
+                })
+            }
+            
             _ => Err(anyhow!("Unknown function: {}", function))
         }
     }
 }
+
+impl Default for PlannerTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn send_reminder_notification(overdue: &[Task], due_today: &[Task]) -> Result<()> {
+    let mut lines: Vec<String> = overdue.iter().map(|t| format!("Overdue: {}", t.title)).collect();
+    lines.extend(due_today.iter().map(|t| format!("Due today: {}", t.title)));
+    
+    notify_rust::Notification::new()
+        .summary("AIR task reminders")
+        .body(&lines.join("\n"))
+        .show()?;
+    Ok(())
+}
+
+async fn save_item<T: Serialize>(memory: &MemoryManager, kind: &str, id: &str, item: &T) -> Result<()> {
+    let data = serde_json::to_string(item)?;
+    memory.save_planner_item(kind, id, &data).await
+}
+
+async fn load_items<T: serde::de::DeserializeOwned>(memory: &MemoryManager, kind: &str) -> Vec<T> {
+    match memory.load_planner_items(kind).await {
+        Ok(rows) => rows.iter()
+            .filter_map(|data| match serde_json::from_str(data) {
+                Ok(item) => Some(item),
+                Err(e) => {
+                    warn!("Skipping unreadable planner {}: {}", kind, e);
+                    None
+                }
+            })
+            .collect(),
+        Err(e) => {
+            warn!("Failed to load planner {}s: {}", kind, e);
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+// Elements that never hold article content.
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "nav", "header", "footer", "aside", "form", "iframe", "svg",
+    "button", "select", "template",
+];
+
+// class/id fragments that mark navigation, chrome and ads.
+const NEGATIVE_HINTS: &[&str] = &[
+    "nav", "menu", "footer", "sidebar", "comment", "cookie", "banner", "advert", "ad-", "share",
+    "social", "related", "promo", "subscribe", "newsletter", "breadcrumb", "popup", "modal",
+];
+
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PageLink {
+    pub text: String,
+    pub url: String,
+}
+
+/// Main content of an HTML page with its navigation and boilerplate removed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractedPage {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub content: String,
+    pub links: Vec<PageLink>,
+}
+
+pub fn extract(html: &str, base_url: Option<&Url>) -> ExtractedPage {
+    let document = Html::parse_document(html);
+
+    let content = find_main_node(&document)
+        .map(render_blocks)
+        .filter(|text| !text.trim().is_empty())
+        .unwrap_or_else(|| {
+            document.select(&selector("body")).next()
+                .map(render_blocks)
+                .unwrap_or_default()
+        });
+
+    ExtractedPage {
+        title: extract_title(&document),
+        description: meta_content(&document, "meta[name=\"description\"]")
+            .or_else(|| meta_content(&document, "meta[property=\"og:description\"]")),
+        content,
+        links: extract_links(&document, base_url),
+    }
+}
+
+fn selector(css: &str) -> Selector {
+    Selector::parse(css).expect("static selector is valid")
+}
+
+fn extract_title(document: &Html) -> Option<String> {
+    meta_content(document, "meta[property=\"og:title\"]")
+        .or_else(|| first_text(document, "title"))
+        .or_else(|| first_text(document, "h1"))
+}
+
+fn meta_content(document: &Html, css: &str) -> Option<String> {
+    document.select(&selector(css))
+        .filter_map(|el| el.value().attr("content"))
+        .map(|s| s.trim().to_string())
+        .find(|s| !s.is_empty())
+}
+
+fn first_text(document: &Html, css: &str) -> Option<String> {
+    document.select(&selector(css))
+        .map(|el| collapse_whitespace(&el.text().collect::<String>()))
+        .find(|s| !s.is_empty())
+}
+
+fn is_skipped(element: &ElementRef) -> bool {
+    let value = element.value();
+    if SKIPPED_TAGS.contains(&value.name()) {
+        return true;
+    }
+    if value.attr("hidden").is_some() || value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if value.attr("role").is_some_and(|r| r == "navigation" || r == "banner" || r == "contentinfo") {
+        return true;
+    }
+    let hints = format!("{} {}", value.attr("class").unwrap_or(""), value.attr("id").unwrap_or("")).to_lowercase();
+    NEGATIVE_HINTS.iter().any(|hint| hints.contains(hint))
+}
+
+fn is_inside_skipped(element: &ElementRef) -> bool {
+    element.ancestors().filter_map(ElementRef::wrap).any(|a| is_skipped(&a))
+}
+
+/// Picks the element most likely to hold the article: an explicit `<article>`/`<main>`,
+/// otherwise the container whose paragraphs carry the most text with the fewest links.
+fn find_main_node(document: &Html) -> Option<ElementRef<'_>> {
+    for css in ["article", "main", "[role=\"main\"]"] {
+        let best = document.select(&selector(css))
+            .filter(|el| !is_inside_skipped(el))
+            .max_by_key(|el| text_len(el));
+        if let Some(el) = best {
+            if text_len(&el) >= MIN_PARAGRAPH_CHARS * 4 {
+                return Some(el);
+            }
+        }
+    }
+
+    let mut scores: HashMap<_, (f64, ElementRef)> = HashMap::new();
+    for paragraph in document.select(&selector("p, pre, td, blockquote")) {
+        if is_inside_skipped(&paragraph) {
+            continue;
+        }
+        let text = collapse_whitespace(&paragraph.text().collect::<String>());
+        if text.len() < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (text.len() as f64 / 100.0).min(3.0);
+
+        let mut ancestors = paragraph.ancestors().filter_map(ElementRef::wrap);
+        if let Some(parent) = ancestors.next() {
+            scores.entry(parent.id()).or_insert((0.0, parent)).0 += score;
+        }
+        if let Some(grandparent) = ancestors.next() {
+            scores.entry(grandparent.id()).or_insert((0.0, grandparent)).0 += score / 2.0;
+        }
+    }
+
+    scores.into_values()
+        .map(|(score, el)| (score * (1.0 - link_density(&el)), el))
+        .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, el)| el)
+}
+
+fn text_len(element: &ElementRef) -> usize {
+    element.text().map(|t| t.trim().len()).sum()
+}
+
+fn link_density(element: &ElementRef) -> f64 {
+    let total = text_len(element);
+    if total == 0 {
+        return 1.0;
+    }
+    let linked: usize = element.select(&selector("a")).map(|a| text_len(&a)).sum();
+    linked as f64 / total as f64
+}
+
+/// Renders an element as plain text blocks: headings, paragraphs, list items and code.
+fn render_blocks(root: ElementRef) -> String {
+    let mut blocks = Vec::new();
+    collect_blocks(root, &mut blocks);
+    blocks.join("\n\n")
+}
+
+fn collect_blocks(element: ElementRef, blocks: &mut Vec<String>) {
+    if is_skipped(&element) {
+        return;
+    }
+
+    let name = element.value().name();
+    let block = match name {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = name[1..].parse::<usize>().unwrap_or(1);
+            Some(format!("{} {}", "#".repeat(level), collapse_whitespace(&element.text().collect::<String>())))
+        }
+        "p" | "blockquote" | "figcaption" | "dt" | "dd" => Some(collapse_whitespace(&element.text().collect::<String>())),
+        "li" => Some(format!("- {}", collapse_whitespace(&element.text().collect::<String>()))),
+        "pre" => Some(element.text().collect::<String>().trim_end().to_string()),
+        _ => None,
+    };
+
+    match block {
+        Some(text) => {
+            if !text.trim().trim_start_matches(['#', '-']).trim().is_empty() {
+                blocks.push(text);
+            }
+        }
+        None => {
+            let mut loose_text = String::new();
+            for child in element.children() {
+                if let Some(child_el) = ElementRef::wrap(child) {
+                    if is_inline(child_el.value().name()) {
+                        if !is_skipped(&child_el) {
+                            loose_text.push_str(&child_el.text().collect::<String>());
+                        }
+                        continue;
+                    }
+                    flush_text(&mut loose_text, blocks);
+                    collect_blocks(child_el, blocks);
+                } else if let Some(text) = child.value().as_text() {
+                    loose_text.push_str(text);
+                }
+            }
+            flush_text(&mut loose_text, blocks);
+        }
+    }
+}
+
+fn flush_text(text: &mut String, blocks: &mut Vec<String>) {
+    let collapsed = collapse_whitespace(text);
+    if !collapsed.is_empty() {
+        blocks.push(collapsed);
+    }
+    text.clear();
+}
+
+fn is_inline(name: &str) -> bool {
+    matches!(name, "a" | "span" | "strong" | "b" | "em" | "i" | "code" | "small" | "sub" | "sup" | "mark" | "abbr" | "time" | "br")
+}
+
+fn extract_links(document: &Html, base_url: Option<&Url>) -> Vec<PageLink> {
+    let mut seen = HashSet::new();
+    let mut links = Vec::new();
+    for anchor in document.select(&selector("a[href]")) {
+        let Some(href) = anchor.value().attr("href") else { continue };
+        let href = href.trim();
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") || href.starts_with("mailto:") {
+            continue;
+        }
+        let resolved = match base_url {
+            Some(base) => base.join(href).ok(),
+            None => Url::parse(href).ok(),
+        };
+        let Some(mut url) = resolved else { continue };
+        if url.scheme() != "http" && url.scheme() != "https" {
+            continue;
+        }
+        url.set_fragment(None);
+        if seen.insert(url.to_string()) {
+            links.push(PageLink {
+                text: collapse_whitespace(&anchor.text().collect::<String>()),
+                url: url.to_string(),
+            });
+        }
+    }
+    links
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Heuristic for single-page apps whose HTML is only a mount point plus scripts,
+/// so the readable content only appears after JavaScript runs.
+pub fn looks_like_js_shell(html: &str, page: &ExtractedPage) -> bool {
+    if page.content.chars().count() >= 200 {
+        return false;
+    }
+    let lower = html.to_lowercase();
+    let mount_point = ["id=\"root\"", "id=\"app\"", "id=\"__next\"", "id=\"__nuxt\"", "ng-app", "data-reactroot"]
+        .iter()
+        .any(|marker| lower.contains(marker));
+    let noscript_warning = Html::parse_document(html)
+        .select(&selector("noscript"))
+        .any(|el| el.text().collect::<String>().to_lowercase().contains("javascript"));
+    mount_point || noscript_warning || lower.matches("<script").count() >= 3
+}
+
+/// Cuts `text` to at most `max_chars` characters on a character boundary.
+pub fn truncate_chars(text: &str, max_chars: usize) -> (String, bool) {
+    match text.char_indices().nth(max_chars) {
+        Some((idx, _)) => (text[..idx].to_string(), true),
+        None => (text.to_string(), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"
+        <html>
+          <head><title>Fallback title</title><meta property="og:title" content="Rust 2.0 Released"></head>
+          <body>
+            <nav><a href="/">Home</a><a href="/about">About</a></nav>
+            <div class="sidebar"><p>Subscribe to our newsletter for the latest updates and offers!</p></div>
+            <div id="content">
+              <h1>Rust 2.0 Released</h1>
+              <p>The Rust team is happy to announce a new version of Rust, with faster builds, better errors, and more.</p>
+              <p>Read the <a href="notes.html#top">release notes</a> for details, including migration guidance.</p>
+              <ul><li>Faster compiles</li><li>Smaller binaries</li></ul>
+            </div>
+            <footer><p>Copyright 2025, all rights reserved, some more footer text here.</p></footer>
+            <script>var tracking = true;</script>
+          </body>
+        </html>"#;
+
+    #[test]
+    fn test_extracts_main_content_without_boilerplate() {
+        let base = Url::parse("https://blog.example.com/posts/rust").unwrap();
+        let page = extract(PAGE, Some(&base));
+
+        assert_eq!(page.title.as_deref(), Some("Rust 2.0 Released"));
+        assert!(page.content.contains("# Rust 2.0 Released"));
+        assert!(page.content.contains("faster builds"));
+        assert!(page.content.contains("- Smaller binaries"));
+        assert!(!page.content.contains("newsletter"));
+        assert!(!page.content.contains("Copyright"));
+        assert!(!page.content.contains("tracking"));
+    }
+
+    #[test]
+    fn test_links_are_resolved_and_deduplicated() {
+        let base = Url::parse("https://blog.example.com/posts/rust").unwrap();
+        let page = extract(PAGE, Some(&base));
+        let urls: Vec<&str> = page.links.iter().map(|l| l.url.as_str()).collect();
+
+        assert_eq!(urls, vec![
+            "https://blog.example.com/",
+            "https://blog.example.com/about",
+            "https://blog.example.com/posts/notes.html",
+        ]);
+    }
+
+    #[test]
+    fn test_detects_js_shell() {
+        let shell = r#"<html><head><title>App</title><script src="/main.js"></script></head>
+            <body><noscript>You need to enable JavaScript to run this app.</noscript><div id="root"></div></body></html>"#;
+        assert!(looks_like_js_shell(shell, &extract(shell, None)));
+        assert!(!looks_like_js_shell(PAGE, &extract(PAGE, None)));
+    }
+
+    #[test]
+    fn test_truncate_chars_respects_boundaries() {
+        assert_eq!(truncate_chars("héllo", 2), ("hé".to_string(), true));
+        assert_eq!(truncate_chars("hi", 5), ("hi".to_string(), false));
+    }
+}
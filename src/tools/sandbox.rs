@@ -0,0 +1,199 @@
+use anyhow::{Result, anyhow};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+use crate::config::FileSystemConfig;
+
+/// Allow/deny rules applied to every path the filesystem tool touches.
+pub struct PathSandbox {
+    enabled: bool,
+    roots: Vec<PathBuf>,
+    denied: GlobSet,
+    denied_patterns: Vec<String>,
+    max_file_size: u64,
+}
+
+impl PathSandbox {
+    pub fn new(config: &FileSystemConfig, base_directory: &Path) -> Self {
+        let mut roots: Vec<PathBuf> = config.workspace_roots.iter()
+            .map(|root| {
+                let root = expand_home(root);
+                if root.is_absolute() {
+                    normalize_path(&root)
+                } else {
+                    normalize_path(&base_directory.join(root))
+                }
+            })
+            .collect();
+
+        if roots.is_empty() {
+            roots.push(normalize_path(base_directory));
+        }
+
+        let mut builder = GlobSetBuilder::new();
+        let mut denied_patterns = Vec::new();
+        for pattern in &config.denied_globs {
+            let expanded = expand_home(pattern).to_string_lossy().to_string();
+            match Glob::new(&expanded) {
+                Ok(glob) => {
+                    builder.add(glob);
+                    denied_patterns.push(expanded);
+                }
+                Err(e) => warn!("Ignoring invalid denied glob '{}': {}", pattern, e),
+            }
+        }
+
+        let denied = builder.build().unwrap_or_else(|e| {
+            warn!("Failed to build denied path rules: {}", e);
+            GlobSet::empty()
+        });
+
+        Self {
+            enabled: config.sandbox_enabled,
+            roots,
+            denied,
+            denied_patterns,
+            max_file_size: config.max_file_size_bytes,
+        }
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub fn denied_patterns(&self) -> &[String] {
+        &self.denied_patterns
+    }
+
+    /// Resolves `path` and verifies it lies inside a workspace root and matches no denied rule.
+    pub fn check(&self, path: &Path) -> Result<PathBuf> {
+        let resolved = normalize_path(path);
+
+        if !self.enabled {
+            return Ok(resolved);
+        }
+
+        if !self.roots.iter().any(|root| resolved.starts_with(root)) {
+            return Err(anyhow!("Access denied: '{}' is outside the allowed workspace roots", path.display()));
+        }
+
+        if resolved.ancestors().any(|p| self.denied.is_match(p)) {
+            return Err(anyhow!("Access denied: '{}' matches a denied path rule", path.display()));
+        }
+
+        Ok(resolved)
+    }
+
+    /// Rejects sizes above the configured limit.
+    pub fn check_size(&self, size: u64) -> Result<()> {
+        if self.enabled && self.max_file_size > 0 && size > self.max_file_size {
+            return Err(anyhow!("File size {} bytes exceeds the sandbox limit of {} bytes", size, self.max_file_size));
+        }
+        Ok(())
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    if path == "~" {
+        return dirs::home_dir().unwrap_or_else(|| PathBuf::from(path));
+    }
+    if let Some(rest) = path.strip_prefix("~/").or_else(|| path.strip_prefix("~\\")) {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
+
+/// Resolves `.` and `..` lexically, then canonicalizes the longest existing prefix so
+/// paths that don't exist yet (e.g. a file about to be written) can still be checked.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut lexical = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => { lexical.pop(); }
+            Component::CurDir => {}
+            other => lexical.push(other.as_os_str()),
+        }
+    }
+
+    let mut existing = lexical.clone();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        match existing.file_name() {
+            Some(name) => {
+                missing.push(name.to_os_string());
+                existing.pop();
+            }
+            None => break,
+        }
+    }
+
+    let mut resolved = std::fs::canonicalize(&existing)
+        .map(strip_verbatim_prefix)
+        .unwrap_or(existing);
+    for name in missing.into_iter().rev() {
+        resolved.push(name);
+    }
+    resolved
+}
+
+// canonicalize() returns `\\?\C:\...` on Windows, which would never match user-written roots or globs.
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    let s = path.to_string_lossy();
+    match s.strip_prefix(r"\\?\") {
+        Some(stripped) => PathBuf::from(stripped),
+        None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox_for(root: &Path, denied: Vec<&str>) -> PathSandbox {
+        let config = FileSystemConfig {
+            sandbox_enabled: true,
+            workspace_roots: vec![root.to_string_lossy().to_string()],
+            denied_globs: denied.into_iter().map(String::from).collect(),
+            max_file_size_bytes: 16,
+        };
+        PathSandbox::new(&config, root)
+    }
+
+    #[test]
+    fn test_allows_paths_inside_root() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sandbox = sandbox_for(dir.path(), vec![]);
+
+        assert!(sandbox.check(&dir.path().join("notes.txt")).is_ok());
+        assert!(sandbox.check(&dir.path().join("new/nested/file.rs")).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_escape_via_parent_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sandbox = sandbox_for(dir.path(), vec![]);
+
+        assert!(sandbox.check(&dir.path().join("../outside.txt")).is_err());
+    }
+
+    #[test]
+    fn test_rejects_denied_globs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sandbox = sandbox_for(dir.path(), vec!["**/*.pem", "**/secrets/**"]);
+
+        assert!(sandbox.check(&dir.path().join("server.pem")).is_err());
+        assert!(sandbox.check(&dir.path().join("secrets/token.txt")).is_err());
+        assert!(sandbox.check(&dir.path().join("readme.md")).is_ok());
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sandbox = sandbox_for(dir.path(), vec![]);
+
+        assert!(sandbox.check_size(16).is_ok());
+        assert!(sandbox.check_size(17).is_err());
+    }
+}
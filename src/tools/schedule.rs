@@ -0,0 +1,256 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, Duration, Local, LocalResult, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecurrenceUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// Repeat rule for a task, e.g. every 2 weeks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recurrence {
+    pub unit: RecurrenceUnit,
+    pub interval: u32,
+}
+
+impl Recurrence {
+    /// Parses "daily", "weekly", "monthly", "yearly" or "every N days/weeks/months/years".
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim().to_lowercase();
+        let simple = |unit| Ok(Self { unit, interval: 1 });
+        match input.as_str() {
+            "daily" | "every day" => return simple(RecurrenceUnit::Day),
+            "weekly" | "every week" => return simple(RecurrenceUnit::Week),
+            "monthly" | "every month" => return simple(RecurrenceUnit::Month),
+            "yearly" | "annually" | "every year" => return simple(RecurrenceUnit::Year),
+            _ => {}
+        }
+
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        if let ["every", n, unit] = parts.as_slice() {
+            let interval: u32 = n.parse().map_err(|_| anyhow!("Invalid recurrence interval '{}'", n))?;
+            if interval == 0 {
+                return Err(anyhow!("Recurrence interval must be at least 1"));
+            }
+            if let Some(unit) = parse_unit(unit) {
+                return Ok(Self { unit, interval });
+            }
+        }
+
+        Err(anyhow!("Unrecognized recurrence '{}' (try daily, weekly, monthly or 'every 2 weeks')", input))
+    }
+
+    /// Advances `due` by whole intervals until it lies after `now`.
+    pub fn next_after(&self, due: DateTime<Utc>, now: DateTime<Utc>) -> DateTime<Utc> {
+        let mut next = self.advance(due);
+        while next <= now {
+            next = self.advance(next);
+        }
+        next
+    }
+
+    fn advance(&self, from: DateTime<Utc>) -> DateTime<Utc> {
+        let n = self.interval;
+        match self.unit {
+            RecurrenceUnit::Day => from + Duration::days(n as i64),
+            RecurrenceUnit::Week => from + Duration::weeks(n as i64),
+            RecurrenceUnit::Month => from.checked_add_months(Months::new(n)).unwrap_or(from + Duration::days(30 * n as i64)),
+            RecurrenceUnit::Year => from.checked_add_months(Months::new(12 * n)).unwrap_or(from + Duration::days(365 * n as i64)),
+        }
+    }
+}
+
+/// Parses a due date such as "tomorrow", "next friday at 5pm", "in 3 days" or "2025-03-01 14:00".
+/// Dates without a time are due at the end of that day.
+pub fn parse_due_date(input: &str, now: DateTime<Local>) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    let lower = input.to_lowercase();
+    let (date_part, time) = match lower.rsplit_once(" at ") {
+        Some((date, time)) => (date.trim().to_string(), Some(parse_time(time)?)),
+        None => (lower.clone(), None),
+    };
+
+    if let Some(dt) = parse_relative_offset(&date_part, now) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    let today = now.date_naive();
+    let mut default_time = end_of_day();
+    let date = match date_part.as_str() {
+        "today" => today,
+        "tonight" => {
+            default_time = NaiveTime::from_hms_opt(20, 0, 0).unwrap();
+            today
+        }
+        "tomorrow" => today + Duration::days(1),
+        "next week" => today + Duration::weeks(1),
+        "next month" => today.checked_add_months(Months::new(1)).unwrap_or(today + Duration::days(30)),
+        "end of week" => today + Duration::days(days_until(today.weekday(), Weekday::Sun, false)),
+        other => {
+            if let Ok(dt) = NaiveDateTime::parse_from_str(other, "%Y-%m-%d %H:%M") {
+                return to_utc(dt);
+            }
+            if let Ok(date) = NaiveDate::parse_from_str(other, "%Y-%m-%d") {
+                date
+            } else if let Some(name) = other.strip_prefix("next ") {
+                let weekday = parse_weekday(name).ok_or_else(|| unrecognized(input))?;
+                today + Duration::days(days_until(today.weekday(), weekday, true))
+            } else if let Some(weekday) = parse_weekday(other.strip_prefix("this ").unwrap_or(other)) {
+                today + Duration::days(days_until(today.weekday(), weekday, false))
+            } else {
+                return Err(unrecognized(input));
+            }
+        }
+    };
+
+    to_utc(date.and_time(time.unwrap_or(default_time)))
+}
+
+fn parse_relative_offset(input: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    let ["in", n, unit] = parts.as_slice() else {
+        return None;
+    };
+    let n: i64 = if *n == "a" || *n == "an" { 1 } else { n.parse().ok()? };
+    let unit = unit.trim_end_matches('s');
+    match unit {
+        "minute" | "min" => Some(now + Duration::minutes(n)),
+        "hour" => Some(now + Duration::hours(n)),
+        "day" => Some(now + Duration::days(n)),
+        "week" => Some(now + Duration::weeks(n)),
+        "month" => now.checked_add_months(Months::new(n as u32)),
+        _ => None,
+    }
+}
+
+fn parse_time(input: &str) -> Result<NaiveTime> {
+    let input = input.trim().replace(' ', "");
+    match input.as_str() {
+        "noon" => return Ok(NaiveTime::from_hms_opt(12, 0, 0).unwrap()),
+        "midnight" => return Ok(NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
+        _ => {}
+    }
+
+    let (clock, meridiem) = if let Some(t) = input.strip_suffix("am") {
+        (t, Some(false))
+    } else if let Some(t) = input.strip_suffix("pm") {
+        (t, Some(true))
+    } else {
+        (input.as_str(), None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>()?, m.parse::<u32>()?),
+        None => (clock.parse::<u32>()?, 0),
+    };
+    let hour = match meridiem {
+        Some(pm) if (1..=12).contains(&hour) => (hour % 12) + if pm { 12 } else { 0 },
+        Some(_) => return Err(anyhow!("Invalid hour '{}'", hour)),
+        None => hour,
+    };
+
+    NaiveTime::from_hms_opt(hour, minute, 0).ok_or_else(|| anyhow!("Invalid time '{}'", input))
+}
+
+fn parse_weekday(input: &str) -> Option<Weekday> {
+    match input.trim() {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_unit(input: &str) -> Option<RecurrenceUnit> {
+    match input.trim_end_matches('s') {
+        "day" => Some(RecurrenceUnit::Day),
+        "week" => Some(RecurrenceUnit::Week),
+        "month" => Some(RecurrenceUnit::Month),
+        "year" => Some(RecurrenceUnit::Year),
+        _ => None,
+    }
+}
+
+/// Days from `from` to the next `to`; `skip_today` forces a full week when they match.
+fn days_until(from: Weekday, to: Weekday, skip_today: bool) -> i64 {
+    let days = (to.num_days_from_monday() as i64 - from.num_days_from_monday() as i64).rem_euclid(7);
+    if days == 0 && skip_today { 7 } else { days }
+}
+
+fn end_of_day() -> NaiveTime {
+    NaiveTime::from_hms_opt(23, 59, 59).unwrap()
+}
+
+fn to_utc(naive: NaiveDateTime) -> Result<DateTime<Utc>> {
+    match Local.from_local_datetime(&naive) {
+        LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => Ok(dt.with_timezone(&Utc)),
+        LocalResult::None => Err(anyhow!("'{}' does not exist in the local time zone", naive)),
+    }
+}
+
+fn unrecognized(input: &str) -> anyhow::Error {
+    anyhow!("Could not understand due date '{}' (try 'tomorrow', 'next friday at 5pm', 'in 3 days' or YYYY-MM-DD)", input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wednesday 2025-01-15 10:00 local time
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 1, 15, 10, 0, 0).unwrap()
+    }
+
+    fn local(input: &str) -> NaiveDateTime {
+        parse_due_date(input, now()).unwrap().with_timezone(&Local).naive_local()
+    }
+
+    #[test]
+    fn test_parse_relative_days() {
+        assert_eq!(local("tomorrow").to_string(), "2025-01-16 23:59:59");
+        assert_eq!(local("today at 5pm").to_string(), "2025-01-15 17:00:00");
+        assert_eq!(local("in 2 hours").to_string(), "2025-01-15 12:00:00");
+    }
+
+    #[test]
+    fn test_parse_weekdays() {
+        assert_eq!(local("friday").date().to_string(), "2025-01-17");
+        assert_eq!(local("wednesday").date().to_string(), "2025-01-15");
+        assert_eq!(local("next wednesday").date().to_string(), "2025-01-22");
+        assert_eq!(local("next fri at 9:30am").to_string(), "2025-01-17 09:30:00");
+    }
+
+    #[test]
+    fn test_parse_absolute_and_invalid() {
+        assert_eq!(local("2025-03-01").to_string(), "2025-03-01 23:59:59");
+        assert_eq!(local("2025-03-01 14:00").to_string(), "2025-03-01 14:00:00");
+        assert!(parse_due_date("someday", now()).is_err());
+    }
+
+    #[test]
+    fn test_recurrence() {
+        let every_two_weeks = Recurrence::parse("every 2 weeks").unwrap();
+        assert_eq!(every_two_weeks, Recurrence { unit: RecurrenceUnit::Week, interval: 2 });
+        assert!(Recurrence::parse("sometimes").is_err());
+
+        let due = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2025, 1, 20, 0, 0, 0).unwrap();
+        assert_eq!(every_two_weeks.next_after(due, now), Utc.with_ymd_and_hms(2025, 1, 29, 9, 0, 0).unwrap());
+
+        let monthly = Recurrence::parse("monthly").unwrap();
+        assert_eq!(monthly.next_after(due, due), Utc.with_ymd_and_hms(2025, 2, 1, 9, 0, 0).unwrap());
+    }
+}
@@ -1,14 +1,29 @@
-use super::{Tool, ToolResult};
+use super::{PathSandbox, PermissionLevel, Tool, ToolResult};
+use crate::config::FileSystemConfig;
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use serde_json::Value;
 use std::process::Command;
 use std::path::Path;
+use std::sync::Arc;
 use chrono::Utc;
 use base64::{Engine as _, engine::general_purpose};
+use xcap::{Monitor, Window};
+
+const DEFAULT_ANALYSIS_PROMPT: &str = "Describe what you see in this screenshot. Include details about UI elements, text, colors, and any notable features.";
+
+/// A vision-capable model that can describe an image.
+#[async_trait]
+pub trait VisionAnalyzer: Send + Sync {
+    async fn describe_image(&self, image_base64: &str, mime_type: &str, prompt: &str) -> Result<String>;
+    fn name(&self) -> &str;
+}
 
 pub struct ScreenshotTool {
     output_dir: String,
+    vision: Option<Arc<dyn VisionAnalyzer>>,
+    // Images the model asks to analyze are uploaded to the vision provider, so they must pass it
+    sandbox: PathSandbox,
 }
 
 impl ScreenshotTool {
@@ -24,7 +39,20 @@ impl ScreenshotTool {
         // Create screenshots directory if it doesn't exist
         std::fs::create_dir_all(&output_dir).ok();
         
-        Self { output_dir }
+        let sandbox = PathSandbox::new(&FileSystemConfig::default(), &std::env::current_dir().unwrap_or_default());
+        Self { output_dir, vision: None, sandbox }
+    }
+
+    /// Checks images to analyze against the filesystem tool's workspace roots and denied paths.
+    pub fn with_sandbox(mut self, config: &FileSystemConfig) -> Self {
+        self.sandbox = PathSandbox::new(config, &std::env::current_dir().unwrap_or_default());
+        self
+    }
+
+    /// Sends captured images to `analyzer` so `analyze` and `capture_and_analyze` return descriptions.
+    pub fn with_vision(mut self, analyzer: Arc<dyn VisionAnalyzer>) -> Self {
+        self.vision = Some(analyzer);
+        self
     }
     
     fn generate_filename(&self, prefix: Option<&str>) -> String {
@@ -48,14 +76,18 @@ impl ScreenshotTool {
             { Err(anyhow!("Unsupported OS for screenshots")) }
         };
         
+        Ok(self.capture_result(result, &filepath, &filename))
+    }
+
+    fn capture_result(&self, result: Result<()>, filepath: &Path, filename: &str) -> ToolResult {
         match result {
             Ok(_) => {
-                let absolute_path = std::fs::canonicalize(&filepath)
-                    .unwrap_or(filepath)
+                let absolute_path = std::fs::canonicalize(filepath)
+                    .unwrap_or_else(|_| filepath.to_path_buf())
                     .to_string_lossy()
                     .to_string();
                     
-                Ok(ToolResult {
+                ToolResult {
                     success: true,
                     result: serde_json::json!({
                         "filepath": absolute_path,
@@ -66,19 +98,94 @@ impl ScreenshotTool {
                         "filepath": absolute_path,
                         "filename": filename,
                         "timestamp": Utc::now().to_rfc3339(),
-                        "vision_analysis_available": true
+                        "vision_analysis_available": self.vision.is_some()
                     })),
-                })
+                }
             }
-            Err(e) => Ok(ToolResult {
+            Err(e) => ToolResult {
                 success: false,
                 result: serde_json::json!(format!("Failed to take screenshot: {}", e)),
                 metadata: Some(serde_json::json!({
                     "error": e.to_string()
                 })),
-            })
+            }
         }
     }
+
+    fn list_displays(&self) -> Result<ToolResult> {
+        let monitors = Monitor::all()?;
+        let mut displays = Vec::new();
+        for (index, monitor) in monitors.iter().enumerate() {
+            displays.push(serde_json::json!({
+                "index": index,
+                "name": monitor.name()?,
+                "x": monitor.x()?,
+                "y": monitor.y()?,
+                "width": monitor.width()?,
+                "height": monitor.height()?,
+                "scale_factor": monitor.scale_factor()?,
+                "is_primary": monitor.is_primary()?
+            }));
+        }
+
+        Ok(ToolResult {
+            success: true,
+            result: serde_json::json!({
+                "displays": displays,
+                "count": displays.len()
+            }),
+            metadata: None,
+        })
+    }
+
+    async fn capture_display(&self, index: usize, filename: Option<String>) -> Result<ToolResult> {
+        let filename = filename.unwrap_or_else(|| self.generate_filename(Some(&format!("display{}", index))));
+        let filepath = Path::new(&self.output_dir).join(&filename);
+
+        let result = (|| -> Result<()> {
+            let monitors = Monitor::all()?;
+            let monitor = monitors.get(index).ok_or_else(|| {
+                anyhow!("Display {} not found ({} display(s) available, use list_displays)", index, monitors.len())
+            })?;
+            monitor.capture_image()?.save(&filepath)?;
+            Ok(())
+        })();
+
+        Ok(self.capture_result(result, &filepath, &filename))
+    }
+
+    async fn capture_window(&self, title: &str, filename: Option<String>) -> Result<ToolResult> {
+        let filename = filename.unwrap_or_else(|| self.generate_filename(Some("window")));
+        let filepath = Path::new(&self.output_dir).join(&filename);
+        let needle = title.to_lowercase();
+
+        let result = (|| -> Result<()> {
+            let mut fallback = None;
+            for window in Window::all()? {
+                let window_title = window.title()?;
+                if !window_title.to_lowercase().contains(&needle) || window.is_minimized()? {
+                    continue;
+                }
+                // Prefer an exact title match over the first partial one
+                if window_title.eq_ignore_ascii_case(title) {
+                    window.capture_image()?.save(&filepath)?;
+                    return Ok(());
+                }
+                if fallback.is_none() {
+                    fallback = Some(window);
+                }
+            }
+            match fallback {
+                Some(window) => {
+                    window.capture_image()?.save(&filepath)?;
+                    Ok(())
+                }
+                None => Err(anyhow!("No visible window with a title containing '{}'", title)),
+            }
+        })();
+
+        Ok(self.capture_result(result, &filepath, &filename))
+    }
     
     #[cfg(target_os = "windows")]
     async fn take_windows_screenshot(&self, filepath: &Path, region: Option<(i32, i32, i32, i32)>) -> Result<()> {
@@ -188,41 +295,100 @@ impl ScreenshotTool {
         Err(anyhow!("No screenshot tool found. Please install gnome-screenshot, scrot, or imagemagick"))
     }
     
-    #[allow(dead_code)]
     async fn analyze_screenshot(&self, filepath: &str, prompt: Option<&str>) -> Result<ToolResult> {
-        let path = Path::new(filepath);
-        if !path.exists() {
-            return Ok(ToolResult {
-                success: false,
-                result: serde_json::json!(format!("Screenshot not found: {}", filepath)),
-                metadata: None,
-            });
+        let failure = |message: String| Ok(ToolResult {
+            success: false,
+            result: serde_json::json!(message),
+            metadata: None,
+        });
+        let path = match self.sandbox.check(Path::new(filepath)) {
+            Ok(path) => path,
+            Err(e) => return failure(format!("Access denied: {}", e)),
+        };
+        if !path.is_file() {
+            return failure(format!("Screenshot not found: {}", filepath));
+        }
+        if mime_type_for(&path).is_none() {
+            return failure(format!("Not an image (PNG, JPEG, GIF or WebP): {}", filepath));
+        }
+        if let Err(e) = self.sandbox.check_size(std::fs::metadata(&path)?.len()) {
+            return failure(e.to_string());
         }
         
-        // Read and encode the image
-        let image_data = std::fs::read(path)?;
+        // Read and encode the image, trusting its contents over its name
+        let image_data = std::fs::read(&path)?;
+        let Some(mime_type) = sniff_image(&image_data) else {
+            return failure(format!("Not an image (PNG, JPEG, GIF or WebP): {}", filepath));
+        };
         let base64_image = general_purpose::STANDARD.encode(&image_data);
         
-        let analysis_prompt = prompt.unwrap_or("Describe what you see in this screenshot. Include details about UI elements, text, colors, and any notable features.");
+        let analysis_prompt = prompt.unwrap_or(DEFAULT_ANALYSIS_PROMPT);
         
-        // Return the encoded image and prompt for the agent to process
-        // The actual vision API call will be handled by the agent/cloud providers
-        Ok(ToolResult {
-            success: true,
-            result: serde_json::json!({
-                "filepath": filepath,
-                "status": "ready_for_analysis",
-                "prompt": analysis_prompt
+        let analyzer = match &self.vision {
+            Some(analyzer) => analyzer,
+            None => {
+                // No vision-capable provider: hand the encoded image back so the caller can route it
+                return Ok(ToolResult {
+                    success: true,
+                    result: serde_json::json!({
+                        "filepath": filepath,
+                        "status": "ready_for_analysis",
+                        "prompt": analysis_prompt,
+                        "note": "No vision-capable provider is configured, so the image was not analyzed."
+                    }),
+                    metadata: Some(serde_json::json!({
+                        "filepath": filepath,
+                        "base64_image": base64_image,
+                        "mime_type": mime_type,
+                        "prompt": analysis_prompt,
+                        "image_size": image_data.len(),
+                        "requires_vision_api": true
+                    })),
+                });
+            }
+        };
+        
+        println!("👁️  Analyzing screenshot with {}...", analyzer.name());
+        match analyzer.describe_image(&base64_image, mime_type, analysis_prompt).await {
+            Ok(description) => Ok(ToolResult {
+                success: true,
+                result: serde_json::json!({
+                    "filepath": filepath,
+                    "status": "analyzed",
+                    "description": description
+                }),
+                metadata: Some(serde_json::json!({
+                    "filepath": filepath,
+                    "prompt": analysis_prompt,
+                    "image_size": image_data.len(),
+                    "vision_provider": analyzer.name()
+                })),
             }),
-            metadata: Some(serde_json::json!({
-                "filepath": filepath,
-                "base64_image": base64_image,
-                "prompt": analysis_prompt,
-                "image_size": image_data.len(),
-                "requires_vision_api": true,
-                "estimated_cost": "$0.003"
-            })),
-        })
+            Err(e) => Ok(ToolResult {
+                success: false,
+                result: serde_json::json!(format!("Vision analysis failed: {}", e)),
+                metadata: Some(serde_json::json!({
+                    "filepath": filepath,
+                    "vision_provider": analyzer.name(),
+                    "error": e.to_string()
+                })),
+            }),
+        }
+    }
+
+    /// Takes a screenshot and immediately runs it through vision analysis.
+    async fn capture_and_analyze(&self, filename: Option<String>, prompt: Option<&str>) -> Result<ToolResult> {
+        let capture = self.take_screenshot(filename, None).await?;
+        if !capture.success {
+            return Ok(capture);
+        }
+
+        let filepath = capture.result.get("filepath")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("Screenshot capture did not report a file path"))?
+            .to_string();
+
+        self.analyze_screenshot(&filepath, prompt).await
     }
     
     async fn list_screenshots(&self) -> Result<ToolResult> {
@@ -288,7 +454,11 @@ impl Tool for ScreenshotTool {
     }
     
     fn description(&self) -> &str {
-        "Take screenshots of the screen or specific regions. Supports full screen capture and region selection on Windows, macOS, and Linux."
+        "Take screenshots of the screen, a specific display or window, or a region, and describe them with a vision model. Use capture_and_analyze to answer questions about what is currently on screen."
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Modifies
     }
     
     fn available_functions(&self) -> Vec<String> {
@@ -296,6 +466,11 @@ impl Tool for ScreenshotTool {
             "capture".to_string(),
             "capture_region".to_string(),
             "list_screenshots".to_string(),
+            "list_displays".to_string(),
+            "capture_display".to_string(),
+            "capture_window".to_string(),
+            "analyze".to_string(),
+            "capture_and_analyze".to_string(),
         ]
     }
     
@@ -329,13 +504,137 @@ impl Tool for ScreenshotTool {
             "list_screenshots" => {
                 self.list_screenshots().await
             }
+            "list_displays" => {
+                match self.list_displays() {
+                    Ok(result) => Ok(result),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: serde_json::json!(format!("Failed to list displays: {}", e)),
+                        metadata: None,
+                    }),
+                }
+            }
+            "capture_display" => {
+                let index = args.get("display")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| anyhow!("Missing 'display' argument"))? as usize;
+                let filename = args.get("filename")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                
+                self.capture_display(index, filename).await
+            }
+            "capture_window" => {
+                let title = args.get("title")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow!("Missing 'title' argument"))?;
+                let filename = args.get("filename")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                
+                self.capture_window(title, filename).await
+            }
+            "analyze" => {
+                let filepath = args.get("filepath")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow!("Missing 'filepath' argument"))?;
+                let prompt = args.get("prompt").and_then(|v| v.as_str());
+                
+                self.analyze_screenshot(filepath, prompt).await
+            }
+            "capture_and_analyze" => {
+                let filename = args.get("filename")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                let prompt = args.get("prompt").and_then(|v| v.as_str());
+                
+                self.capture_and_analyze(filename, prompt).await
+            }
             _ => Err(anyhow!("Unknown function: {}", function))
         }
     }
 }
 
+fn mime_type_for(path: &Path) -> Option<&'static str> {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("png") => Some("image/png"),
+        Some("jpg") | Some("jpeg") => Some("image/jpeg"),
+        Some("gif") => Some("image/gif"),
+        Some("webp") => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// The image type `data` starts with, if it is one the vision providers accept.
+fn sniff_image(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
 impl Default for ScreenshotTool {
     fn default() -> Self {
         Self::new(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_in(root: &Path) -> ScreenshotTool {
+        let config = FileSystemConfig {
+            sandbox_enabled: true,
+            workspace_roots: vec![root.to_string_lossy().to_string()],
+            denied_globs: vec!["**/.env".to_string()],
+            max_file_size_bytes: 0,
+        };
+        ScreenshotTool::new(Some(root.join("shots").to_string_lossy().to_string())).with_sandbox(&config)
+    }
+
+    #[test]
+    fn test_sniff_image() {
+        assert_eq!(sniff_image(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+        assert_eq!(sniff_image(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("image/jpeg"));
+        assert_eq!(sniff_image(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_image(b"OPENAI_API_KEY=sk-123"), None);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_checks_path_and_type() {
+        let workspace = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let tool = tool_in(workspace.path());
+
+        let secret = outside.path().join("shot.png");
+        std::fs::write(&secret, b"\x89PNG\r\n\x1a\nsecret").unwrap();
+        let result = tool.analyze_screenshot(&secret.to_string_lossy(), None).await.unwrap();
+        assert!(!result.success);
+        assert!(result.result.as_str().unwrap().starts_with("Access denied"), "{}", result.result);
+
+        let env = workspace.path().join(".env");
+        std::fs::write(&env, "OPENAI_API_KEY=sk-123").unwrap();
+        let result = tool.analyze_screenshot(&env.to_string_lossy(), None).await.unwrap();
+        assert!(!result.success);
+
+        let renamed = workspace.path().join("notes.png");
+        std::fs::write(&renamed, "plain text").unwrap();
+        let result = tool.analyze_screenshot(&renamed.to_string_lossy(), None).await.unwrap();
+        assert!(!result.success);
+        assert!(result.result.as_str().unwrap().starts_with("Not an image"), "{}", result.result);
+
+        let image = workspace.path().join("real.png");
+        std::fs::write(&image, b"\x89PNG\r\n\x1a\npixels").unwrap();
+        let result = tool.analyze_screenshot(&image.to_string_lossy(), None).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.metadata.unwrap()["mime_type"], "image/png");
+    }
+}
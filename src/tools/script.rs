@@ -0,0 +1,288 @@
+//! User-defined tools written in Rhai. Each `*.rhai` file in the config directory's `tools/`
+//! folder becomes a tool named after the file, offered to the model like the built-in ones:
+//!
+//! ```rhai
+//! // tools/weather.rhai
+//! const DESCRIPTION = "Current weather for a city";
+//!
+//! // Every public function taking one argument (the call's args object) is a tool function
+//! fn current(args) {
+//!     let report = http_get(`https://wttr.in/${args.city}?format=3`);
+//!     #{ city: args.city, report: report }
+//! }
+//! ```
+//!
+//! Scripts only reach the outside world through the host functions registered here:
+//! `http_get(url)`, `read_file(path)` (inside the filesystem sandbox), `now()`, `log(message)`,
+//! `from_json(text)` and `to_json(value)`. `import` and `eval` are disabled, and each call is
+//! bounded by `scripting.max_operations` and `scripting.timeout_seconds`. Loading only compiles
+//! a script, so `DESCRIPTION` and `PERMISSION` must be plain constants.
+
+use super::{PermissionLevel, PathSandbox, Tool, ToolResult};
+use crate::config::{FileSystemConfig, ScriptingConfig};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use rhai::{AST, Dynamic, Engine, EvalAltResult, FnAccess, Scope};
+use serde_json::{Value, json};
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const MAX_HTTP_BYTES: usize = 1024 * 1024;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+thread_local! {
+    // When the call running on this (blocking pool) thread must stop; checked by `on_progress`
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+pub struct ScriptTool {
+    name: String,
+    description: String,
+    functions: Vec<String>,
+    permission: PermissionLevel,
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    timeout: Duration,
+}
+
+impl ScriptTool {
+    /// Compiles `path` without running it. The tool is named after the file; `DESCRIPTION` and
+    /// `PERMISSION` ("read_only", "modifies" or "confirm") are read from the script's constants.
+    pub fn load(path: &Path, config: &ScriptingConfig, filesystem: &FileSystemConfig) -> Result<Self> {
+        let name = path.file_stem().and_then(|s| s.to_str())
+            .ok_or_else(|| anyhow!("Invalid script name: {}", path.display()))?
+            .to_string();
+        let engine = Arc::new(build_engine(config, filesystem));
+        let ast = engine.compile_file(path.to_path_buf())
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+        let functions: Vec<String> = ast.iter_functions()
+            .filter(|f| f.access != FnAccess::Private && f.params.len() == 1 && !f.name.starts_with('_'))
+            .map(|f| f.name.to_string())
+            .collect();
+        if functions.is_empty() {
+            return Err(anyhow!("{}: no public one-argument functions", path.display()));
+        }
+
+        let constant = |name: &str| ast.iter_literal_variables(true, false)
+            .find(|(var, _, _)| *var == name)
+            .and_then(|(_, _, value)| value.into_string().ok());
+        let description = constant("DESCRIPTION")
+            .unwrap_or_else(|| format!("User script {}", path.display()));
+        let permission = match constant("PERMISSION").as_deref() {
+            None | Some("read_only") => PermissionLevel::ReadOnly,
+            Some("modifies") => PermissionLevel::Modifies,
+            Some("confirm") => PermissionLevel::Confirm,
+            Some(other) => return Err(anyhow!("{}: unknown PERMISSION '{}'", path.display(), other)),
+        };
+
+        Ok(Self {
+            name,
+            description,
+            functions,
+            permission,
+            engine,
+            ast: Arc::new(ast),
+            timeout: Duration::from_secs(config.timeout_seconds.max(1)),
+        })
+    }
+}
+
+/// Loads every script in the config directory's `tools/` folder; broken scripts are skipped
+/// with a warning so one typo doesn't take the other tools down.
+pub fn load_all(config: &ScriptingConfig, filesystem: &FileSystemConfig) -> Vec<ScriptTool> {
+    if !config.enabled {
+        return Vec::new();
+    }
+    let Ok(dir) = scripts_dir() else { return Vec::new() };
+    let Ok(entries) = std::fs::read_dir(&dir) else { return Vec::new() };
+
+    let mut paths: Vec<PathBuf> = entries.flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "rhai"))
+        .collect();
+    paths.sort();
+
+    paths.iter().filter_map(|path| match ScriptTool::load(path, config, filesystem) {
+        Ok(tool) => {
+            info!("📜 Loaded script tool {} ({})", tool.name, tool.functions.join(", "));
+            Some(tool)
+        }
+        Err(e) => {
+            warn!("⚠️  Skipping script tool: {}", e);
+            None
+        }
+    }).collect()
+}
+
+pub fn scripts_dir() -> Result<PathBuf> {
+    Ok(crate::utils::paths::get_air_config_dir()?.join("tools"))
+}
+
+fn build_engine(config: &ScriptingConfig, filesystem: &FileSystemConfig) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(config.max_operations);
+    engine.set_max_call_levels(32);
+    engine.set_max_string_size(MAX_HTTP_BYTES * 4);
+    engine.set_max_array_size(100_000);
+    engine.set_max_map_size(100_000);
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.on_progress(|_| {
+        let expired = DEADLINE.with(|deadline| deadline.get().is_some_and(|at| Instant::now() >= at));
+        expired.then(|| Dynamic::from("timed out"))
+    });
+
+    let sandbox = Arc::new(PathSandbox::new(filesystem, &std::env::current_dir().unwrap_or_default()));
+    engine.register_fn("read_file", move |path: &str| -> std::result::Result<String, Box<EvalAltResult>> {
+        let resolved = sandbox.check(Path::new(path)).map_err(|e| e.to_string())?;
+        let size = std::fs::metadata(&resolved).map_err(|e| e.to_string())?.len();
+        sandbox.check_size(size).map_err(|e| e.to_string())?;
+        Ok(std::fs::read_to_string(&resolved).map_err(|e| e.to_string())?)
+    });
+    engine.register_fn("http_get", |url: &str| -> std::result::Result<String, Box<EvalAltResult>> {
+        http_get(url).map_err(|e| e.to_string().into())
+    });
+    engine.register_fn("now", || chrono::Utc::now().to_rfc3339());
+    engine.register_fn("log", |message: &str| info!("📜 {}", message));
+    engine.register_fn("from_json", |text: &str| -> std::result::Result<Dynamic, Box<EvalAltResult>> {
+        let value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        rhai::serde::to_dynamic(value)
+    });
+    engine.register_fn("to_json", |value: Dynamic| -> std::result::Result<String, Box<EvalAltResult>> {
+        let value: Value = rhai::serde::from_dynamic(&value)?;
+        Ok(value.to_string())
+    });
+    engine
+}
+
+/// Runs on the blocking pool (see `execute`), so it can wait on the async client.
+fn http_get(url: &str) -> Result<String> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(anyhow!("http_get only fetches http(s) URLs"));
+    }
+    tokio::runtime::Handle::current().block_on(async {
+        let response = reqwest::Client::builder().timeout(HTTP_TIMEOUT).build()?
+            .get(url)
+            .header("User-Agent", concat!("air/", env!("CARGO_PKG_VERSION")))
+            .send()
+            .await?
+            .error_for_status()?;
+        let bytes = response.bytes().await?;
+        if bytes.len() > MAX_HTTP_BYTES {
+            return Err(anyhow!("Response from {} is larger than {} bytes", url, MAX_HTTP_BYTES));
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    })
+}
+
+#[async_trait]
+impl Tool for ScriptTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn available_functions(&self) -> Vec<String> {
+        self.functions.clone()
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        self.permission
+    }
+
+    async fn execute(&self, function: &str, args: Value) -> Result<ToolResult> {
+        if !self.functions.iter().any(|f| f == function) {
+            return Err(anyhow!("Unknown function '{}' for script tool {}", function, self.name));
+        }
+        let (engine, ast, name) = (self.engine.clone(), self.ast.clone(), function.to_string());
+        let deadline = Instant::now() + self.timeout;
+        let call = tokio::task::spawn_blocking(move || -> std::result::Result<Value, String> {
+            DEADLINE.with(|d| d.set(Some(deadline)));
+            let output = rhai::serde::to_dynamic(args)
+                .and_then(|args| engine.call_fn::<Dynamic>(&mut Scope::new(), &ast, &name, (args,)));
+            DEADLINE.with(|d| d.set(None));
+            rhai::serde::from_dynamic(&output.map_err(|e| e.to_string())?).map_err(|e| e.to_string())
+        });
+
+        // The operation limit and the deadline stop the script itself (and free the blocking
+        // thread); the timeout also covers a host call that hangs
+        let outcome = tokio::time::timeout(self.timeout, call).await
+            .map_err(|_| anyhow!("Script {}::{} timed out after {}s", self.name, function, self.timeout.as_secs()))??;
+        Ok(match outcome {
+            Ok(result) => ToolResult { success: true, result, metadata: Some(json!({ "script": self.name })) },
+            Err(error) => ToolResult { success: false, result: json!(format!("Script error: {}", error)), metadata: None },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_script(dir: &Path, name: &str, body: &str) -> PathBuf {
+        let path = dir.join(format!("{}.rhai", name));
+        std::fs::write(&path, body).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_script_tool() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_script(dir.path(), "greeter", r#"
+            const DESCRIPTION = "Greets people";
+            fn hello(args) { #{ greeting: `Hello, ${args.name}!`, length: args.name.len() } }
+            fn _helper(x) { x }
+            private fn hidden(x) { x }
+        "#);
+        let tool = ScriptTool::load(&path, &ScriptingConfig::default(), &FileSystemConfig::default()).unwrap();
+        assert_eq!(tool.name(), "greeter");
+        assert_eq!(tool.description(), "Greets people");
+        assert_eq!(tool.available_functions(), vec!["hello"]);
+
+        let result = tool.execute("hello", json!({ "name": "Ada" })).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.result, json!({ "greeting": "Hello, Ada!", "length": 3 }));
+        assert!(tool.execute("_helper", json!({})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scripts_are_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_script(dir.path(), "spin", "fn forever(args) { loop { } }");
+        let config = ScriptingConfig { max_operations: 10_000, ..ScriptingConfig::default() };
+        let tool = ScriptTool::load(&path, &config, &FileSystemConfig::default()).unwrap();
+        let result = tool.execute("forever", json!({})).await.unwrap();
+        assert!(!result.success);
+
+        let path = write_script(dir.path(), "sneaky", r#"fn run(args) { eval("1 + 1") }"#);
+        assert!(ScriptTool::load(&path, &ScriptingConfig::default(), &FileSystemConfig::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_does_not_run_the_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_script(dir.path(), "busy", r#"
+            const DESCRIPTION = "Busy";
+            const PERMISSION = "modifies";
+            loop { }
+            fn wait(args) { loop { } }
+        "#);
+        let config = ScriptingConfig { max_operations: 0, timeout_seconds: 1, ..ScriptingConfig::default() };
+        let tool = ScriptTool::load(&path, &config, &FileSystemConfig::default()).unwrap();
+        assert_eq!(tool.description(), "Busy");
+        assert_eq!(tool.permission_level(), PermissionLevel::Modifies);
+
+        // Without an operation limit, the deadline still stops the script
+        let started = Instant::now();
+        let result = tool.execute("wait", json!({})).await;
+        assert!(!result.is_ok_and(|r| r.success));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,9 +1,13 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use serde_json::Value;
-use chrono::Local;
+use serde_json::{json, Value};
+use chrono::{Local, TimeZone};
+use sysinfo::{Disks, Networks, System};
 use super::{Tool, ToolResult};
 
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+const MIB: f64 = 1024.0 * 1024.0;
+
 pub struct SystemTool;
 
 impl SystemTool {
@@ -12,6 +16,143 @@ impl SystemTool {
     }
 }
 
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+fn percent(used: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { round1(used as f64 * 100.0 / total as f64) }
+}
+
+/// CPU usage is a delta between two refreshes, so this blocks for sysinfo's minimum interval.
+fn cpu_usage() -> Value {
+    let mut sys = System::new();
+    sys.refresh_cpu();
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    sys.refresh_cpu();
+
+    let cpus = sys.cpus();
+    let load = System::load_average();
+    json!({
+        "usage_percent": round1(sys.global_cpu_info().cpu_usage() as f64),
+        "cores": cpus.len(),
+        "physical_cores": sys.physical_core_count(),
+        "brand": cpus.first().map(|c| c.brand().trim().to_string()),
+        "frequency_mhz": cpus.first().map(|c| c.frequency()),
+        "per_core_percent": cpus.iter().map(|c| round1(c.cpu_usage() as f64)).collect::<Vec<_>>(),
+        "load_average": { "one": load.one, "five": load.five, "fifteen": load.fifteen },
+    })
+}
+
+fn memory_usage() -> Value {
+    let mut sys = System::new();
+    sys.refresh_memory();
+    json!({
+        "total_gb": round1(sys.total_memory() as f64 / GIB),
+        "used_gb": round1(sys.used_memory() as f64 / GIB),
+        "available_gb": round1(sys.available_memory() as f64 / GIB),
+        "used_percent": percent(sys.used_memory(), sys.total_memory()),
+        "swap_total_gb": round1(sys.total_swap() as f64 / GIB),
+        "swap_used_gb": round1(sys.used_swap() as f64 / GIB),
+    })
+}
+
+fn disk_usage() -> Value {
+    let disks = Disks::new_with_refreshed_list();
+    let disks: Vec<Value> = disks.list().iter().map(|disk| {
+        let total = disk.total_space();
+        let used = total.saturating_sub(disk.available_space());
+        json!({
+            "name": disk.name().to_string_lossy(),
+            "mount_point": disk.mount_point().display().to_string(),
+            "file_system": disk.file_system().to_string_lossy(),
+            "kind": format!("{:?}", disk.kind()),
+            "removable": disk.is_removable(),
+            "total_gb": round1(total as f64 / GIB),
+            "used_gb": round1(used as f64 / GIB),
+            "available_gb": round1(disk.available_space() as f64 / GIB),
+            "used_percent": percent(used, total),
+        })
+    }).collect();
+    json!(disks)
+}
+
+fn uptime() -> Value {
+    let seconds = System::uptime();
+    let boot_time = Local.timestamp_opt(System::boot_time() as i64, 0).single();
+    json!({
+        "uptime_seconds": seconds,
+        "formatted": format!("{}d {}h {}m", seconds / 86400, (seconds % 86400) / 3600, (seconds % 3600) / 60),
+        "boot_time": boot_time.map(|t| t.to_rfc3339()),
+        "os": System::long_os_version(),
+        "kernel": System::kernel_version(),
+        "hostname": System::host_name(),
+    })
+}
+
+/// Top processes by CPU or memory. Like `cpu_usage`, CPU figures need two samples.
+fn top_processes(limit: usize, sort_by: &str) -> Value {
+    let mut sys = System::new();
+    sys.refresh_processes();
+    sys.refresh_cpu();
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    sys.refresh_processes();
+    sys.refresh_memory();
+
+    let mut processes: Vec<_> = sys.processes().values().collect();
+    if sort_by == "memory" {
+        processes.sort_by_key(|p| std::cmp::Reverse(p.memory()));
+    } else {
+        processes.sort_by(|a, b| b.cpu_usage().partial_cmp(&a.cpu_usage()).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    let processes: Vec<Value> = processes.into_iter().take(limit).map(|p| json!({
+        "pid": p.pid().as_u32(),
+        "name": p.name(),
+        "cpu_percent": round1(p.cpu_usage() as f64),
+        "memory_mb": round1(p.memory() as f64 / MIB),
+        "memory_percent": percent(p.memory(), sys.total_memory()),
+        "run_time_seconds": p.run_time(),
+    })).collect();
+    json!(processes)
+}
+
+fn network_interfaces() -> Value {
+    let networks = Networks::new_with_refreshed_list();
+    let mut interfaces: Vec<Value> = networks.iter().map(|(name, data)| json!({
+        "name": name,
+        "mac_address": data.mac_address().to_string(),
+        "received_mb": round1(data.total_received() as f64 / MIB),
+        "transmitted_mb": round1(data.total_transmitted() as f64 / MIB),
+        "packets_received": data.total_packets_received(),
+        "packets_transmitted": data.total_packets_transmitted(),
+        "errors_received": data.total_errors_on_received(),
+        "errors_transmitted": data.total_errors_on_transmitted(),
+    })).collect();
+    interfaces.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    json!(interfaces)
+}
+
+fn battery_status() -> Result<Value> {
+    use starship_battery::units::ratio::percent;
+    use starship_battery::units::time::second;
+
+    let manager = starship_battery::Manager::new()?;
+    let batteries: Vec<Value> = manager.batteries()?
+        .filter_map(|battery| battery.ok())
+        .map(|battery| json!({
+            "charge_percent": round1(battery.state_of_charge().get::<percent>() as f64),
+            "state": battery.state().to_string(),
+            "health_percent": round1(battery.state_of_health().get::<percent>() as f64),
+            "time_to_full_minutes": battery.time_to_full().map(|t| (t.get::<second>() / 60.0).round()),
+            "time_to_empty_minutes": battery.time_to_empty().map(|t| (t.get::<second>() / 60.0).round()),
+            "vendor": battery.vendor(),
+            "model": battery.model(),
+        }))
+        .collect();
+    Ok(json!(batteries))
+}
+
 #[async_trait]
 impl Tool for SystemTool {
     fn name(&self) -> &str {
@@ -19,14 +160,24 @@ impl Tool for SystemTool {
     }
 
     fn description(&self) -> &str {
-        "Provides access to system information like time and date."
+        "Provides system information: time and date, CPU, memory and disk usage, battery, uptime, top processes and network interfaces."
     }
 
     fn available_functions(&self) -> Vec<String> {
-        vec!["get_system_time".to_string()]
+        vec![
+            "get_system_time".to_string(),
+            "cpu_usage".to_string(),
+            "memory_usage".to_string(),
+            "disk_usage".to_string(),
+            "battery_status".to_string(),
+            "uptime".to_string(),
+            "top_processes".to_string(),
+            "network_interfaces".to_string(),
+            "overview".to_string(),
+        ]
     }
 
-    async fn execute(&self, function: &str, _args: Value) -> Result<ToolResult> {
+    async fn execute(&self, function: &str, args: Value) -> Result<ToolResult> {
         match function {
             "get_system_time" => {
                 let now = Local::now();
@@ -43,7 +194,53 @@ impl Tool for SystemTool {
                     metadata: None,
                 })
             }
-            _ => Err(anyhow::anyhow!("Unknown function: {}", function)),
+            "battery_status" => {
+                match tokio::task::spawn_blocking(battery_status).await? {
+                    Ok(batteries) if batteries.as_array().is_some_and(|b| b.is_empty()) => Ok(ToolResult {
+                        success: true,
+                        result: json!("No battery detected (this is probably a desktop or server)"),
+                        metadata: None,
+                    }),
+                    Ok(batteries) => Ok(ToolResult {
+                        success: true,
+                        result: batteries,
+                        metadata: None,
+                    }),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Failed to read battery status: {}", e)),
+                        metadata: None,
+                    }),
+                }
+            }
+            "cpu_usage" | "memory_usage" | "disk_usage" | "uptime" | "top_processes" | "network_interfaces" | "overview" => {
+                let limit = args["limit"].as_u64().unwrap_or(10) as usize;
+                let sort_by = args["sort_by"].as_str().unwrap_or("cpu").to_string();
+                let function = function.to_string();
+
+                // sysinfo calls block (CPU sampling sleeps), so keep them off the async runtime
+                let result = tokio::task::spawn_blocking(move || match function.as_str() {
+                    "cpu_usage" => cpu_usage(),
+                    "memory_usage" => memory_usage(),
+                    "disk_usage" => disk_usage(),
+                    "uptime" => uptime(),
+                    "top_processes" => top_processes(limit, &sort_by),
+                    "network_interfaces" => network_interfaces(),
+                    _ => json!({
+                        "cpu": cpu_usage(),
+                        "memory": memory_usage(),
+                        "disks": disk_usage(),
+                        "uptime": uptime(),
+                    }),
+                }).await?;
+
+                Ok(ToolResult {
+                    success: true,
+                    result,
+                    metadata: None,
+                })
+            }
+            _ => Err(anyhow!("Unknown function: {}", function)),
         }
     }
 }
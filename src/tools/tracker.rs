@@ -0,0 +1,456 @@
+use super::{PermissionLevel, Tool, ToolResult};
+use super::command::truncate_output;
+use crate::config::{TrackerBackend, TrackerConfig};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use crate::utils::approval;
+use std::sync::Arc;
+use std::time::Duration;
+
+const USER_AGENT: &str = "air-Agent/1.0";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+const LINEAR_API: &str = "https://api.linear.app/graphql";
+const DEFAULT_LIMIT: u64 = 10;
+const MAX_COMMENTS: usize = 20;
+const MAX_BODY_CHARS: usize = 4_000;
+/// Planner tag linking a task to its tracker issue (`tracker:ENG-42`).
+const LINK_TAG: &str = "tracker:";
+
+/// The team's issue tracker (Jira or Linear). Reading is free; creating and moving issues ask first.
+pub struct TrackerTool {
+    client: reqwest::Client,
+    config: TrackerConfig,
+    token: Option<String>,
+    /// Used by `sync_task` to read and update planner tasks.
+    planner: Option<Arc<dyn Tool>>,
+}
+
+impl TrackerTool {
+    pub fn with_config(config: &TrackerConfig) -> Self {
+        let var = match config.backend {
+            Some(TrackerBackend::Linear) => "LINEAR_API_KEY",
+            _ => "JIRA_API_TOKEN",
+        };
+        let token = config.api_token.clone().filter(|t| !t.is_empty())
+            .or_else(|| std::env::var(var).ok())
+            .or_else(|| crate::utils::secrets::get_api_key(var));
+        Self {
+            client: reqwest::Client::builder().timeout(HTTP_TIMEOUT).user_agent(USER_AGENT).build().unwrap_or_default(),
+            config: config.clone(),
+            token,
+            planner: None,
+        }
+    }
+
+    pub fn with_planner(mut self, planner: Arc<dyn Tool>) -> Self {
+        self.planner = Some(planner);
+        self
+    }
+
+    fn backend(&self) -> Result<TrackerBackend> {
+        let backend = self.config.backend
+            .ok_or_else(|| anyhow!("No tracker configured: set [tracker] backend to \"jira\" or \"linear\""))?;
+        if self.token.is_none() {
+            return Err(anyhow!("No tracker API token: set tracker.api_token, JIRA_API_TOKEN or LINEAR_API_KEY"));
+        }
+        Ok(backend)
+    }
+
+    fn ask_confirmation(&self, action: &str, preview: &str) -> bool {
+        println!("\n📋 Tracker: {}\n{}", action, preview);
+        approval::confirm("⚠️  Confirmation required: apply this in the tracker?")
+    }
+
+    // Jira (REST API v2, which keeps descriptions and comments as plain text)
+
+    fn jira_site(&self) -> Result<&str> {
+        self.config.url.as_deref().map(|u| u.trim_end_matches('/'))
+            .ok_or_else(|| anyhow!("Set tracker.url to the Jira site (https://team.atlassian.net)"))
+    }
+
+    async fn jira(&self, method: reqwest::Method, path: &str, query: &[(&str, &str)], body: Option<Value>) -> Result<Value> {
+        let mut request = self.client.request(method, format!("{}/rest/api/2{}", self.jira_site()?, path))
+            .header("Accept", "application/json")
+            .query(query);
+        let token = self.token.as_deref().unwrap_or_default();
+        request = match &self.config.email {
+            Some(email) => request.basic_auth(email, Some(token)),
+            None => request.bearer_auth(token),
+        };
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            let body: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+            let messages: Vec<String> = body["errorMessages"].as_array().into_iter().flatten()
+                .filter_map(|m| m.as_str().map(|s| s.to_string()))
+                .chain(body["errors"].as_object().into_iter().flatten().map(|(k, v)| format!("{}: {}", k, v)))
+                .collect();
+            return Err(anyhow!("Jira returned {}: {}", status, messages.join("; ")));
+        }
+        // Transitions answer 204 with no body
+        Ok(serde_json::from_str(&text).unwrap_or(Value::Null))
+    }
+
+    fn jira_issue(&self, issue: &Value) -> Value {
+        let fields = &issue["fields"];
+        let key = issue["key"].as_str().unwrap_or_default();
+        json!({
+            "key": key,
+            "title": fields["summary"],
+            "status": fields["status"]["name"],
+            "category": jira_category(fields["status"]["statusCategory"]["key"].as_str().unwrap_or_default()),
+            "assignee": fields["assignee"]["displayName"],
+            "updated": fields["updated"],
+            "url": format!("{}/browse/{}", self.jira_site().unwrap_or_default(), key),
+        })
+    }
+
+    // Linear (GraphQL)
+
+    async fn linear(&self, query: &str, variables: Value) -> Result<Value> {
+        let response = self.client.post(LINEAR_API)
+            .header("Authorization", self.token.as_deref().unwrap_or_default())
+            .json(&json!({ "query": query, "variables": variables }))
+            .send().await?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        let errors: Vec<&str> = body["errors"].as_array().into_iter().flatten()
+            .filter_map(|e| e["message"].as_str())
+            .collect();
+        if !status.is_success() || !errors.is_empty() {
+            return Err(anyhow!("Linear returned {}: {}", status, errors.join("; ")));
+        }
+        Ok(body["data"].clone())
+    }
+
+    // Operations, normalized to the same fields for both backends
+
+    async fn query(&self, query: &str, limit: u64) -> Result<Value> {
+        let issues: Vec<Value> = match self.backend()? {
+            TrackerBackend::Jira => {
+                let jql = jql(query, self.config.project.as_deref());
+                let limit = limit.to_string();
+                let query = [("jql", jql.as_str()), ("maxResults", limit.as_str()), ("fields", "summary,status,assignee,updated")];
+                let results = self.jira(reqwest::Method::GET, "/search/jql", &query, None).await?;
+                results["issues"].as_array().into_iter().flatten().map(|i| self.jira_issue(i)).collect()
+            }
+            TrackerBackend::Linear => {
+                let filter = self.config.project.as_ref().map(|team| json!({"team": {"key": {"eq": team}}}));
+                let data = self.linear(
+                    "query($term: String!, $first: Int, $filter: IssueFilter) { searchIssues(term: $term, first: $first, filter: $filter) \
+                     { nodes { identifier title url updatedAt state { name type } assignee { name } } } }",
+                    json!({ "term": query, "first": limit, "filter": filter }),
+                ).await?;
+                data["searchIssues"]["nodes"].as_array().into_iter().flatten().map(linear_issue).collect()
+            }
+        };
+        Ok(json!({ "query": query, "count": issues.len(), "issues": issues }))
+    }
+
+    async fn get(&self, key: &str) -> Result<Value> {
+        match self.backend()? {
+            TrackerBackend::Jira => {
+                let fields = [("fields", "summary,status,assignee,updated,description,comment")];
+                let issue = self.jira(reqwest::Method::GET, &format!("/issue/{}", key), &fields, None).await?;
+                let comments = issue["fields"]["comment"]["comments"].as_array().cloned().unwrap_or_default();
+                let comments: Vec<Value> = comments.iter().rev().take(MAX_COMMENTS).rev()
+                    .map(|c| comment(&c["author"]["displayName"], &c["created"], &c["body"]))
+                    .collect();
+                let mut result = self.jira_issue(&issue);
+                result["description"] = json!(truncate_output(issue["fields"]["description"].as_str().unwrap_or_default(), MAX_BODY_CHARS));
+                result["comments"] = json!(comments);
+                Ok(result)
+            }
+            TrackerBackend::Linear => {
+                let data = self.linear(
+                    "query($id: String!) { issue(id: $id) { identifier title description url updatedAt state { name type } assignee { name } \
+                     comments(last: 20) { nodes { body createdAt user { name } } } } }",
+                    json!({ "id": key }),
+                ).await?;
+                let issue = &data["issue"];
+                let mut comments: Vec<Value> = issue["comments"]["nodes"].as_array().into_iter().flatten()
+                    .map(|c| comment(&c["user"]["name"], &c["createdAt"], &c["body"]))
+                    .collect();
+                comments.sort_by(|a, b| a["created"].as_str().cmp(&b["created"].as_str()));
+                let mut result = linear_issue(issue);
+                result["description"] = json!(truncate_output(issue["description"].as_str().unwrap_or_default(), MAX_BODY_CHARS));
+                result["comments"] = json!(comments);
+                Ok(result)
+            }
+        }
+    }
+
+    /// Creates the issue without asking; callers confirm first.
+    async fn create(&self, title: &str, description: &str, project: Option<&str>) -> Result<Value> {
+        let project = project.or(self.config.project.as_deref())
+            .ok_or_else(|| anyhow!("No project for the new issue: pass 'project' or set tracker.project"))?;
+        match self.backend()? {
+            TrackerBackend::Jira => {
+                let body = json!({"fields": {
+                    "project": {"key": project},
+                    "summary": title,
+                    "description": description,
+                    "issuetype": {"name": self.config.issue_type},
+                }});
+                let created = self.jira(reqwest::Method::POST, "/issue", &[], Some(body)).await?;
+                let key = created["key"].as_str().unwrap_or_default();
+                Ok(json!({ "key": key, "url": format!("{}/browse/{}", self.jira_site()?, key) }))
+            }
+            TrackerBackend::Linear => {
+                let teams = self.linear(
+                    "query($key: String!) { teams(filter: { key: { eq: $key } }) { nodes { id } } }",
+                    json!({ "key": project }),
+                ).await?;
+                let team = teams["teams"]["nodes"][0]["id"].as_str()
+                    .ok_or_else(|| anyhow!("No Linear team with key '{}'", project))?;
+                let data = self.linear(
+                    "mutation($input: IssueCreateInput!) { issueCreate(input: $input) { success issue { identifier url } } }",
+                    json!({ "input": { "teamId": team, "title": title, "description": description } }),
+                ).await?;
+                let issue = &data["issueCreate"]["issue"];
+                Ok(json!({ "key": issue["identifier"], "url": issue["url"] }))
+            }
+        }
+    }
+
+    /// Moves the issue to the state or transition named `target` (or one in the category
+    /// `todo`, `in_progress`, `done`). Callers confirm first.
+    async fn transition(&self, key: &str, target: &str) -> Result<Value> {
+        match self.backend()? {
+            TrackerBackend::Jira => {
+                let path = format!("/issue/{}/transitions", key);
+                let available = self.jira(reqwest::Method::GET, &path, &[], None).await?;
+                let transitions = available["transitions"].as_array().cloned().unwrap_or_default();
+                let choice = pick(&transitions, target, |t| {
+                    let category = jira_category(t["to"]["statusCategory"]["key"].as_str().unwrap_or_default());
+                    vec![t["name"].as_str().unwrap_or_default(), t["to"]["name"].as_str().unwrap_or_default(), category]
+                }).ok_or_else(|| anyhow!("No transition to '{}' from here; available: {}", target, names(&transitions, |t| &t["to"]["name"])))?;
+                self.jira(reqwest::Method::POST, &path, &[], Some(json!({"transition": {"id": choice["id"]}}))).await?;
+                Ok(json!({ "key": key, "status": choice["to"]["name"] }))
+            }
+            TrackerBackend::Linear => {
+                let data = self.linear(
+                    "query($id: String!) { issue(id: $id) { id team { states { nodes { id name type } } } } }",
+                    json!({ "id": key }),
+                ).await?;
+                let states = data["issue"]["team"]["states"]["nodes"].as_array().cloned().unwrap_or_default();
+                let choice = pick(&states, target, |s| {
+                    vec![s["name"].as_str().unwrap_or_default(), linear_category(s["type"].as_str().unwrap_or_default())]
+                }).ok_or_else(|| anyhow!("No state '{}' in this team; available: {}", target, names(&states, |s| &s["name"])))?;
+                self.linear(
+                    "mutation($id: String!, $state: String!) { issueUpdate(id: $id, input: { stateId: $state }) { success } }",
+                    json!({ "id": data["issue"]["id"], "state": choice["id"] }),
+                ).await?;
+                Ok(json!({ "key": key, "status": choice["name"] }))
+            }
+        }
+    }
+
+    /// Links a planner task to the tracker: a task without a `tracker:` tag gets a new issue (after
+    /// approval) and the tag; a linked task takes its status from the issue.
+    async fn sync_task(&self, task_id: &str) -> Result<Value> {
+        let planner = self.planner.as_ref().ok_or_else(|| anyhow!("The planner isn't available"))?;
+        let found = planner.execute("get_task_status", json!({"task_id": task_id})).await?;
+        if !found.success {
+            return Err(anyhow!("{}", found.result.as_str().unwrap_or("Task not found")));
+        }
+        let task = found.metadata.unwrap_or_default();
+        let mut tags: Vec<String> = task["tags"].as_array().into_iter().flatten()
+            .filter_map(|t| t.as_str().map(|s| s.to_string()))
+            .collect();
+        let linked = tags.iter().find_map(|t| t.strip_prefix(LINK_TAG)).map(|k| k.to_string());
+
+        let Some(key) = linked else {
+            let title = task["title"].as_str().unwrap_or(task_id);
+            let description = task["description"].as_str().unwrap_or_default();
+            if !self.ask_confirmation(&format!("create an issue for planner task {}", task_id), &format!("Title: {}\n\n{}", title, description)) {
+                return Ok(json!({ "task_id": task_id, "cancelled": true }));
+            }
+            let created = self.create(title, description, None).await?;
+            let key = created["key"].as_str().unwrap_or_default();
+            tags.push(format!("{}{}", LINK_TAG, key));
+            planner.execute("update_task", json!({"task_id": task_id, "tags": tags})).await?;
+            return Ok(json!({ "task_id": task_id, "created": true, "key": key, "url": created["url"] }));
+        };
+
+        let issue = self.get(&key).await?;
+        let current = task["status"].as_str().unwrap_or_default();
+        let status = planner_status(issue["category"].as_str().unwrap_or_default(), current);
+        if let Some(status) = status {
+            planner.execute("update_task", json!({"task_id": task_id, "status": status})).await?;
+        }
+        Ok(json!({
+            "task_id": task_id,
+            "key": key,
+            "tracker_status": issue["status"],
+            "planner_status": status.unwrap_or(current),
+            "updated": status.is_some(),
+        }))
+    }
+}
+
+/// JQL for `query`: passed through when it already is JQL, otherwise a text search, scoped to
+/// `project` unless the query names one.
+pub fn jql(query: &str, project: Option<&str>) -> String {
+    let query = query.trim();
+    let is_jql = ["=", "~", "order by"].iter().any(|op| query.to_lowercase().contains(op));
+    let base = if is_jql || query.is_empty() {
+        query.to_string()
+    } else {
+        format!("text ~ \"{}\"", query.replace('\\', "\\\\").replace('"', "\\\""))
+    };
+    // Split off ORDER BY so the scope wraps only the condition
+    let lower = base.to_lowercase();
+    let (condition, order) = match lower.find("order by") {
+        Some(at) => (base[..at].trim().to_string(), base[at..].to_string()),
+        None => (base.clone(), "ORDER BY updated DESC".to_string()),
+    };
+    let condition = match project {
+        Some(project) if !lower.contains("project") && condition.is_empty() => format!("project = \"{}\"", project),
+        Some(project) if !lower.contains("project") => format!("project = \"{}\" AND ({})", project, condition),
+        _ => condition,
+    };
+    format!("{} {}", condition, order).trim().to_string()
+}
+
+fn jira_category(key: &str) -> &'static str {
+    match key {
+        "new" => "todo",
+        "indeterminate" => "in_progress",
+        "done" => "done",
+        _ => "",
+    }
+}
+
+fn linear_category(state_type: &str) -> &'static str {
+    match state_type {
+        "triage" | "backlog" | "unstarted" => "todo",
+        "started" => "in_progress",
+        "completed" => "done",
+        "canceled" => "cancelled",
+        _ => "",
+    }
+}
+
+/// The planner status for an issue in `category`, or None when `current` (the task's status as
+/// the planner reports it) already matches. A blocked task stays blocked until the issue is closed.
+fn planner_status(category: &str, current: &str) -> Option<&'static str> {
+    let (status, reported) = match category {
+        "todo" => ("not_started", "NotStarted"),
+        "in_progress" => ("in_progress", "InProgress"),
+        "done" => ("completed", "Completed"),
+        "cancelled" => ("cancelled", "Cancelled"),
+        _ => return None,
+    };
+    let still_blocked = current == "Blocked" && matches!(category, "todo" | "in_progress");
+    (current != reported && !still_blocked).then_some(status)
+}
+
+fn linear_issue(issue: &Value) -> Value {
+    json!({
+        "key": issue["identifier"],
+        "title": issue["title"],
+        "status": issue["state"]["name"],
+        "category": linear_category(issue["state"]["type"].as_str().unwrap_or_default()),
+        "assignee": issue["assignee"]["name"],
+        "updated": issue["updatedAt"],
+        "url": issue["url"],
+    })
+}
+
+fn comment(author: &Value, created: &Value, body: &Value) -> Value {
+    json!({
+        "author": author,
+        "created": created,
+        "body": truncate_output(body.as_str().unwrap_or_default(), MAX_BODY_CHARS),
+    })
+}
+
+/// The first option whose labels include `target` (case-insensitive).
+fn pick<'a>(options: &'a [Value], target: &str, labels: impl Fn(&'a Value) -> Vec<&'a str>) -> Option<&'a Value> {
+    let target = target.trim().to_lowercase().replace(' ', "_");
+    options.iter().find(|o| labels(*o).iter().any(|l| !l.is_empty() && l.to_lowercase().replace(' ', "_") == target))
+}
+
+fn names<'a>(options: &'a [Value], name: impl Fn(&'a Value) -> &'a Value) -> String {
+    options.iter().filter_map(|o| name(o).as_str()).collect::<Vec<_>>().join(", ")
+}
+
+fn required<'a>(args: &'a Value, name: &str) -> Result<&'a str> {
+    args[name].as_str().ok_or_else(|| anyhow!("Missing '{}' parameter", name))
+}
+
+#[async_trait]
+impl Tool for TrackerTool {
+    fn name(&self) -> &str {
+        "tracker"
+    }
+
+    fn description(&self) -> &str {
+        "The team's issue tracker (Jira or Linear, set in [tracker]): query (query: text or JQL, limit), get (key such as ENG-42, with comments), create (title, description, project), transition (key, status: a state name or todo/in_progress/done) and sync_task (task_id: links a planner task to a new issue, or updates the task from its linked issue). Creating and moving issues ask the user first."
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Confirm
+    }
+
+    fn available_functions(&self) -> Vec<String> {
+        ["query", "get", "create", "transition", "sync_task"].map(String::from).to_vec()
+    }
+
+    async fn execute(&self, function: &str, args: Value) -> Result<ToolResult> {
+        let result = match function {
+            "query" => self.query(required(&args, "query")?, args["limit"].as_u64().unwrap_or(DEFAULT_LIMIT).min(50)).await,
+            "get" => self.get(required(&args, "key")?).await,
+            "create" => {
+                let title = required(&args, "title")?;
+                let description = args["description"].as_str().unwrap_or_default();
+                self.backend()?;
+                if !self.ask_confirmation("create an issue", &format!("Title: {}\n\n{}", title, description)) {
+                    return Ok(ToolResult { success: false, result: json!("Issue creation cancelled by user."), metadata: None });
+                }
+                self.create(title, description, args["project"].as_str()).await
+            }
+            "transition" => {
+                let key = required(&args, "key")?;
+                let status = required(&args, "status")?;
+                self.backend()?;
+                if !self.ask_confirmation(&format!("move {}", key), &format!("New status: {}", status)) {
+                    return Ok(ToolResult { success: false, result: json!("Transition cancelled by user."), metadata: None });
+                }
+                self.transition(key, status).await
+            }
+            "sync_task" => self.sync_task(required(&args, "task_id")?).await,
+            _ => return Err(anyhow!("Unknown function: {}", function)),
+        };
+        Ok(match result {
+            Ok(result) => ToolResult { success: true, result, metadata: None },
+            Err(e) => ToolResult { success: false, result: json!(e.to_string()), metadata: None },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jql() {
+        assert_eq!(jql("login crash", Some("ENG")), "project = \"ENG\" AND (text ~ \"login crash\") ORDER BY updated DESC");
+        assert_eq!(jql("assignee = currentUser() ORDER BY priority", None), "assignee = currentUser() ORDER BY priority");
+        assert_eq!(jql("project = OPS AND status = Done", Some("ENG")), "project = OPS AND status = Done ORDER BY updated DESC");
+    }
+
+    #[test]
+    fn test_planner_status() {
+        assert_eq!(planner_status("done", "InProgress"), Some("completed"));
+        assert_eq!(planner_status("in_progress", "InProgress"), None);
+        assert_eq!(planner_status("todo", "Blocked"), None);
+        assert_eq!(planner_status("done", "Blocked"), Some("completed"));
+    }
+}
@@ -2,17 +2,27 @@ use super::{Tool, ToolResult};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use serde_json::Value;
-use std::process::Command;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use chrono::Utc;
+use crate::config::VoiceConfig;
+use super::audio;
 
 pub struct VoiceTool {
     output_dir: String,
     temp_dir: String,
+    // Piper voice used when a request doesn't name one
+    piper_model: Option<PathBuf>,
+    // Whether generated speech is also played through the speakers
+    play_speech: bool,
 }
 
 impl VoiceTool {
     pub fn new(output_dir: Option<String>) -> Self {
+        Self::with_config(output_dir, &VoiceConfig::default())
+    }
+
+    pub fn with_config(output_dir: Option<String>, config: &VoiceConfig) -> Self {
         let output_dir = output_dir.unwrap_or_else(|| {
             std::env::current_dir()
                 .unwrap_or_default()
@@ -30,7 +40,12 @@ impl VoiceTool {
         std::fs::create_dir_all(&output_dir).ok();
         std::fs::create_dir_all(&temp_dir).ok();
         
-        Self { output_dir, temp_dir }
+        Self {
+            output_dir,
+            temp_dir,
+            piper_model: config.piper_model.as_ref().map(PathBuf::from),
+            play_speech: config.play_speech,
+        }
     }
     
     fn generate_filename(&self, prefix: &str, extension: &str) -> String {
@@ -38,23 +53,44 @@ impl VoiceTool {
         format!("{}_{}.{}", prefix, timestamp, extension)
     }
     
-    async fn text_to_speech(&self, text: &str, voice: Option<&str>) -> Result<ToolResult> {
+    async fn text_to_speech(&self, text: &str, voice: Option<&str>, play: Option<bool>) -> Result<ToolResult> {
         let filename = self.generate_filename("speech", "wav");
         let filepath = Path::new(&self.output_dir).join(&filename);
         
-        let result = {
-            #[cfg(target_os = "windows")]
-            { self.windows_text_to_speech(text, &filepath, voice).await }
-            #[cfg(target_os = "macos")]
-            { self.macos_text_to_speech(text, &filepath, voice).await }
-            #[cfg(target_os = "linux")]
-            { self.linux_text_to_speech(text, &filepath, voice).await }
-            #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-            { Err(anyhow!("Unsupported OS for text-to-speech")) }
+        let engine = "piper";
+        let result = match self.piper_voice(voice) {
+            Ok(model) => {
+                let (text, output) = (text.to_string(), filepath.clone());
+                tokio::task::spawn_blocking(move || audio::synthesize_piper(&model, &text, &output))
+                    .await
+                    .map_err(|e| anyhow!("Speech synthesis task failed: {}", e))
+                    .and_then(|r| r)
+            }
+            Err(e) => Err(e),
         };
         
+        let result = result.and_then(|_| {
+            if filepath.exists() {
+                Ok(())
+            } else {
+                Err(anyhow!("The speech engine did not produce an audio file"))
+            }
+        });
+        
         match result {
             Ok(_) => {
+                let played = if play.unwrap_or(self.play_speech) {
+                    match self.play_file(&filepath).await {
+                        Ok(_) => true,
+                        Err(e) => {
+                            tracing::warn!("Failed to play generated speech: {}", e);
+                            false
+                        }
+                    }
+                } else {
+                    false
+                };
+
                 let absolute_path = std::fs::canonicalize(&filepath)
                     .unwrap_or(filepath)
                     .to_string_lossy()
@@ -65,12 +101,14 @@ impl VoiceTool {
                     result: serde_json::json!({
                         "message": "Speech generated successfully",
                         "filepath": absolute_path,
+                        "played": played,
                         "timestamp": Utc::now().to_rfc3339()
                     }),
                     metadata: Some(serde_json::json!({
                         "filepath": absolute_path,
                         "text": text,
                         "voice": voice,
+                        "engine": engine,
                         "timestamp": Utc::now().to_rfc3339()
                     })),
                 })
@@ -80,108 +118,76 @@ impl VoiceTool {
                 result: serde_json::json!(format!("Failed to generate speech: {}", e)),
                 metadata: Some(serde_json::json!({
                     "error": e.to_string(),
+                    "engine": engine,
                     "text": text
                 })),
             })
         }
     }
-    
-    #[cfg(target_os = "windows")]
-    async fn windows_text_to_speech(&self, text: &str, filepath: &Path, voice: Option<&str>) -> Result<()> {
-        // Create a PowerShell script for TTS
-        let voice_selection = if let Some(v) = voice {
-            format!("$voice = $voices | Where-Object {{$_.Name -like '*{}*'}} | Select-Object -First 1", v)
-        } else {
-            "$voice = $voices | Select-Object -First 1".to_string()
-        };
-        
-        let script = format!(
-            r#"
-            Add-Type -AssemblyName System.Speech
-            $synth = New-Object System.Speech.Synthesis.SpeechSynthesizer
-            $voices = $synth.GetInstalledVoices()
-            {}
-            if ($voice) {{ $synth.SelectVoice($voice.VoiceInfo.Name) }}
-            $synth.SetOutputToWaveFile('{}')
-            $synth.Speak('{}')
-            $synth.Dispose()
-            "#,
-            voice_selection,
-            filepath.to_string_lossy().replace("'", "''"),
-            text.replace("'", "''")
-        );
-        
-        let output = Command::new("powershell")
-            .args(["-Command", &script])
-            .output()?;
-            
-        if !output.status.success() {
-            return Err(anyhow!("PowerShell TTS failed: {}", 
-                String::from_utf8_lossy(&output.stderr)));
-        }
-        
-        Ok(())
+
+    async fn play_file(&self, filepath: &Path) -> Result<()> {
+        let filepath = filepath.to_path_buf();
+        tokio::task::spawn_blocking(move || audio::play_file(&filepath))
+            .await
+            .map_err(|e| anyhow!("Audio playback task failed: {}", e))?
     }
-    
-    #[cfg(target_os = "macos")]
-    async fn macos_text_to_speech(&self, text: &str, filepath: &Path, voice: Option<&str>) -> Result<()> {
-        let mut cmd = Command::new("say");
-        
-        if let Some(v) = voice {
-            cmd.args(["-v", v]);
+
+    async fn play_audio(&self, file_path: &str) -> Result<ToolResult> {
+        let path = Path::new(file_path);
+        if !path.exists() {
+            return Ok(ToolResult {
+                success: false,
+                result: serde_json::json!(format!("Audio file not found: {}", file_path)),
+                metadata: None,
+            });
         }
-        
-        cmd.args(["-o", &*filepath.to_string_lossy(), text]);
-        
-        let output = cmd.output()?;
-        
-        if !output.status.success() {
-            return Err(anyhow!("macOS say command failed: {}", 
-                String::from_utf8_lossy(&output.stderr)));
+
+        match self.play_file(path).await {
+            Ok(_) => Ok(ToolResult {
+                success: true,
+                result: serde_json::json!(format!("Played {}", file_path)),
+                metadata: None,
+            }),
+            Err(e) => Ok(ToolResult {
+                success: false,
+                result: serde_json::json!(format!("Failed to play audio: {}", e)),
+                metadata: Some(serde_json::json!({
+                    "error": e.to_string()
+                })),
+            }),
         }
-        
-        Ok(())
     }
-    
-    #[cfg(target_os = "linux")]
-    async fn linux_text_to_speech(&self, text: &str, filepath: &Path, voice: Option<&str>) -> Result<()> {
-        // Try different TTS engines available on Linux
-        let filepath_str = filepath.to_string_lossy();
-        let tools = vec![
-            ("espeak", vec!["-w", &filepath_str, text]),
-            ("festival", vec!["--tts", text]),
-            ("spd-say", vec!["-w", &filepath_str, text]),
-        ];
-        
-        for (tool, mut args) in tools {
-            if Command::new("which").arg(tool).output().map(|o| o.status.success()).unwrap_or(false) {
-                if let Some(v) = voice {
-                    match tool {
-                        "espeak" => {
-                            args.insert(0, "-v");
-                            args.insert(1, v);
-                        }
-                        "festival" => {
-                            // Festival voice selection is more complex
-                        }
-                        "spd-say" => {
-                            args.insert(0, "-t");
-                            args.insert(1, v);
-                        }
-                        _ => {}
-                    }
-                }
-                
-                let output = Command::new(tool).args(&args).output()?;
-                if output.status.success() {
-                    return Ok(());
-                }
-            }
+
+    /// Piper voices (`*.onnx.json`) in the models folder's `voices/` directory, plus the configured one.
+    fn piper_voices(&self) -> Vec<PathBuf> {
+        let mut voices: Vec<PathBuf> = self.piper_model.iter().cloned().collect();
+        if let Ok(entries) = crate::utils::paths::get_air_models_dir().and_then(|dir| Ok(std::fs::read_dir(dir.join("voices"))?)) {
+            let mut found: Vec<PathBuf> = entries.flatten()
+                .map(|e| e.path())
+                .filter(|p| p.to_string_lossy().ends_with(".onnx.json") && !voices.contains(p))
+                .collect();
+            found.sort();
+            voices.extend(found);
         }
-        
-        Err(anyhow!("No TTS engine found. Please install espeak, festival, or speech-dispatcher"))
+        voices
     }
-    
+
+    /// The voice named `voice` (file name without `.onnx.json`), else `voice.piper_model`, else the
+    /// first one installed.
+    fn piper_voice(&self, voice: Option<&str>) -> Result<PathBuf> {
+        let voices = self.piper_voices();
+        if let Some(name) = voice {
+            return voices.into_iter()
+                .find(|path| voice_name(path).eq_ignore_ascii_case(name))
+                .ok_or_else(|| anyhow!("Unknown voice '{}' (see list_voices)", name));
+        }
+        voices.into_iter().next().ok_or_else(|| anyhow!(
+            "No Piper voice installed. Download a voice (the .onnx and .onnx.json files) from \
+             https://huggingface.co/rhasspy/piper-voices into {}, or set voice.piper_model to its .onnx.json",
+            crate::utils::paths::models_dir_path().join("voices").display()
+        ))
+    }
+
     async fn speech_to_text(&self, audio_file: Option<&str>, duration: Option<u32>) -> Result<ToolResult> {
         if let Some(file_path) = audio_file {
             // Process existing audio file
@@ -197,17 +203,14 @@ impl VoiceTool {
         let filename = self.generate_filename("recording", "wav");
         let filepath = Path::new(&self.temp_dir).join(&filename);
         
-        // Record audio
-        let record_result = {
-            #[cfg(target_os = "windows")]
-            { self.windows_record_audio(&filepath, duration).await }
-            #[cfg(target_os = "macos")]
-            { self.macos_record_audio(&filepath, duration).await }
-            #[cfg(target_os = "linux")]
-            { self.linux_record_audio(&filepath, duration).await }
-            #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-            { Err(anyhow!("Unsupported OS for audio recording")) }
-        };
+        // Record audio from the default input device
+        let record_path = filepath.clone();
+        let record_result = tokio::task::spawn_blocking(move || {
+            audio::record_wav(&record_path, Duration::from_secs(duration as u64))
+        })
+        .await
+        .map_err(|e| anyhow!("Recording task failed: {}", e))
+        .and_then(|r| r);
         
         match record_result {
             Ok(_) => {
@@ -260,83 +263,8 @@ impl VoiceTool {
         })
     }
     
-    #[cfg(target_os = "windows")]
-    async fn windows_record_audio(&self, filepath: &Path, duration: u32) -> Result<()> {
-        // Use SoundRecorder or PowerShell for recording
-        let script = format!(
-            r#"
-            $duration = {}
-            $outputFile = '{}'
-            # This is a simplified example - real implementation would need more complex audio recording
-            Write-Host "Recording for $duration seconds to $outputFile"
-            Start-Sleep -Seconds $duration
-            "#,
-            duration,
-            filepath.to_string_lossy()
-        );
-        
-        let output = Command::new("powershell")
-            .args(["-Command", &script])
-            .output()?;
-            
-        if !output.status.success() {
-            return Err(anyhow!("Windows audio recording failed"));
-        }
-        
-        Ok(())
-    }
-    
-    #[cfg(target_os = "macos")]
-    async fn macos_record_audio(&self, filepath: &Path, duration: u32) -> Result<()> {
-        let duration_str = duration.to_string();
-        let output = Command::new("rec")
-            .args([
-                &*filepath.to_string_lossy(),
-                "trim", "0", &duration_str
-            ])
-            .output()?;
-            
-        if !output.status.success() {
-            return Err(anyhow!("macOS audio recording failed"));
-        }
-        
-        Ok(())
-    }
-    
-    #[cfg(target_os = "linux")]
-    async fn linux_record_audio(&self, filepath: &Path, duration: u32) -> Result<()> {
-        // Try different recording tools
-        let duration_str = duration.to_string();
-        let filepath_str = filepath.to_string_lossy();
-        let tools = vec![
-            ("arecord", vec!["-d", &duration_str, &filepath_str]),
-            ("rec", vec![&filepath_str, "trim", "0", &duration_str]),
-        ];
-        
-        for (tool, args) in tools {
-            if Command::new("which").arg(tool).output().map(|o| o.status.success()).unwrap_or(false) {
-                let output = Command::new(tool).args(&args).output()?;
-                if output.status.success() {
-                    return Ok(());
-                }
-            }
-        }
-        
-        Err(anyhow!("No audio recording tool found. Please install alsa-utils or sox"))
-    }
-    
     async fn list_voices(&self) -> Result<ToolResult> {
-        let voices = {
-            #[cfg(target_os = "windows")]
-            { self.windows_list_voices().await? }
-            #[cfg(target_os = "macos")]
-            { self.macos_list_voices().await? }
-            #[cfg(target_os = "linux")]
-            { self.linux_list_voices().await? }
-            #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-            { vec!["Default".to_string()] }
-        };
-        
+        let voices: Vec<String> = self.piper_voices().iter().map(|path| voice_name(path)).collect();
         Ok(ToolResult {
             success: true,
             result: serde_json::json!({
@@ -347,70 +275,11 @@ impl VoiceTool {
             })),
         })
     }
-    
-    #[cfg(target_os = "windows")]
-    async fn windows_list_voices(&self) -> Result<Vec<String>> {
-        let script = r#"
-            Add-Type -AssemblyName System.Speech
-            $synth = New-Object System.Speech.Synthesis.SpeechSynthesizer
-            $voices = $synth.GetInstalledVoices()
-            $voices | ForEach-Object { $_.VoiceInfo.Name }
-            $synth.Dispose()
-        "#;
-        
-        let output = Command::new("powershell")
-            .args(["-Command", script])
-            .output()?;
-            
-        if output.status.success() {
-            let voices: Vec<String> = String::from_utf8_lossy(&output.stdout)
-                .lines()
-                .filter(|line| !line.trim().is_empty())
-                .map(|line| line.trim().to_string())
-                .collect();
-            Ok(voices)
-        } else {
-            Ok(vec!["Default".to_string()])
-        }
-    }
-    
-    #[cfg(target_os = "macos")]
-    async fn macos_list_voices(&self) -> Result<Vec<String>> {
-        let output = Command::new("say")
-            .args(["-v", "?"])
-            .output()?;
-            
-        if output.status.success() {
-            let voices: Vec<String> = String::from_utf8_lossy(&output.stdout)
-                .lines()
-                .filter_map(|line| {
-                    line.split_whitespace().next().map(|s| s.to_string())
-                })
-                .collect();
-            Ok(voices)
-        } else {
-            Ok(vec!["Alex".to_string()])
-        }
-    }
-    
-    #[cfg(target_os = "linux")]
-    async fn linux_list_voices(&self) -> Result<Vec<String>> {
-        // espeak voices
-        if let Ok(output) = Command::new("espeak").args(["--voices"]).output() {
-            if output.status.success() {
-                let voices: Vec<String> = String::from_utf8_lossy(&output.stdout)
-                    .lines()
-                    .skip(1) // Skip header
-                    .filter_map(|line| {
-                        line.split_whitespace().nth(4).map(|s| s.to_string())
-                    })
-                    .collect();
-                return Ok(voices);
-            }
-        }
-        
-        Ok(vec!["default".to_string()])
-    }
+}
+
+fn voice_name(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    name.trim_end_matches(".onnx.json").to_string()
 }
 
 #[async_trait]
@@ -420,7 +289,7 @@ impl Tool for VoiceTool {
     }
     
     fn description(&self) -> &str {
-        "Text-to-speech synthesis and speech-to-text recognition. Generate audio from text and transcribe audio to text."
+        "Text-to-speech synthesis with speaker playback, microphone recording and speech-to-text recognition. Generate and play audio from text and transcribe audio to text."
     }
     
     fn available_functions(&self) -> Vec<String> {
@@ -429,6 +298,7 @@ impl Tool for VoiceTool {
             "listen".to_string(),
             "transcribe_file".to_string(),
             "list_voices".to_string(),
+            "play_audio".to_string(),
         ]
     }
     
@@ -441,8 +311,9 @@ impl Tool for VoiceTool {
                     
                 let voice = args.get("voice")
                     .and_then(|v| v.as_str());
+                let play = args.get("play").and_then(|v| v.as_bool());
                 
-                self.text_to_speech(text, voice).await
+                self.text_to_speech(text, voice, play).await
             }
             "listen" => {
                 let duration = args.get("duration")
@@ -461,6 +332,13 @@ impl Tool for VoiceTool {
             "list_voices" => {
                 self.list_voices().await
             }
+            "play_audio" => {
+                let file_path = args.get("file_path")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow!("Missing 'file_path' argument"))?;
+                
+                self.play_audio(file_path).await
+            }
             _ => Err(anyhow!("Unknown function: {}", function))
         }
     }
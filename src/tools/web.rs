@@ -1,74 +1,474 @@
-use super::{Tool, ToolResult};
+use super::{CacheStats, PermissionLevel, Tool, ToolResult};
+use super::readability::{self, ExtractedPage, truncate_chars};
+use super::headless::HeadlessRenderer;
+use super::sandbox::PathSandbox;
+use super::web_policy::{CachedResponse, RateLimiter, ResponseCache, RobotsRules};
+use crate::config::{FileSystemConfig, WebConfig};
+use crate::rag::langchain_embedding::CandleEmbedder;
+use crate::rag::store::KnowledgeStore;
+use crate::utils::approval;
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
 use serde_json::{json, Value};
-use reqwest::Client;
-use std::time::Duration;
-use tracing::info;
+use reqwest::{Client, Url};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+const USER_AGENT: &str = "air-Agent/1.0";
+
+const DEFAULT_MAX_CHARS: usize = 10000;
+const DEFAULT_MAX_LINKS: usize = 50;
+const MAX_CRAWL_DEPTH: u64 = 3;
+const MAX_CRAWL_PAGES: u64 = 50;
+const CRAWL_PAGE_CHARS: usize = 2000;
+const KNOWLEDGE_CHUNK_CHARS: usize = 1000;
+
+// Links to these are downloads or media, not pages worth crawling
+const NON_PAGE_EXTENSIONS: &[&str] = &[
+    ".pdf", ".zip", ".gz", ".tar", ".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp", ".mp3", ".mp4",
+    ".avi", ".mov", ".exe", ".dmg", ".iso", ".css", ".js", ".xml", ".json",
+];
 
 pub struct WebTool {
     client: Client,
+    knowledge: Option<Arc<KnowledgeStore<CandleEmbedder>>>,
+    respect_robots_txt: bool,
+    // robots.txt rules per origin, fetched on first use
+    robots: Mutex<HashMap<String, RobotsRules>>,
+    rate_limiter: RateLimiter,
+    cache: ResponseCache,
+    max_download_bytes: u64,
+    render_js_fallback: bool,
+    renderer: HeadlessRenderer,
+    /// Checks download destinations outside the downloads folder.
+    sandbox: PathSandbox,
 }
 
 impl WebTool {
     pub fn new() -> Self {
+        Self::with_config(&WebConfig::default())
+    }
+
+    pub fn with_config(config: &WebConfig) -> Self {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
-            .user_agent("air-Agent/1.0")
+            .user_agent(USER_AGENT)
             .build()
             .unwrap();
             
-        Self { client }
+        Self {
+            client,
+            knowledge: None,
+            respect_robots_txt: config.respect_robots_txt,
+            robots: Mutex::new(HashMap::new()),
+            rate_limiter: RateLimiter::new(Duration::from_millis(config.min_request_interval_ms)),
+            cache: ResponseCache::new(config.cache_entries),
+            max_download_bytes: config.max_download_bytes,
+            render_js_fallback: config.render_js_fallback,
+            renderer: HeadlessRenderer::new(config.browser_path.as_deref(), Duration::from_secs(30)),
+            sandbox: PathSandbox::new(&FileSystemConfig::default(), &std::env::current_dir().unwrap_or_default()),
+        }
+    }
+
+    /// Checks download destinations against the filesystem tool's workspace roots and denied paths.
+    pub fn with_sandbox(mut self, config: &FileSystemConfig) -> Self {
+        self.sandbox = PathSandbox::new(config, &std::env::current_dir().unwrap_or_default());
+        self
+    }
+
+    /// Where a download may be written, and whether the user has to approve it. The downloads
+    /// folder is always fine; anywhere else the model picked, so the path must pass the sandbox.
+    fn check_destination(&self, dest: &Path) -> Result<(PathBuf, bool)> {
+        let downloads = crate::utils::paths::get_air_data_dir()?.join("downloads");
+        if dest.starts_with(&downloads) && !dest.components().any(|c| c == Component::ParentDir) {
+            return Ok((dest.to_path_buf(), false));
+        }
+
+        let dest = if dest.is_relative() { std::env::current_dir()?.join(dest) } else { dest.to_path_buf() };
+        Ok((self.sandbox.check(&dest)?, true))
+    }
+
+    fn ask_confirmation(&self, url: &Url, dest: &Path, replacing: bool) -> bool {
+        let replacing = if replacing { ", replacing the existing file" } else { "" };
+        approval::confirm(&format!("⚠️  Confirmation required: download {} to '{}'{}?", url, dest.display(), replacing))
+    }
+
+    /// Lets `crawl` index fetched pages into the knowledge store.
+    pub fn with_knowledge(mut self, store: Arc<KnowledgeStore<CandleEmbedder>>) -> Self {
+        self.knowledge = Some(store);
+        self
     }
     
     fn is_valid_url(&self, url: &str) -> bool {
         url.starts_with("http://") || url.starts_with("https://")
     }
     
-    fn extract_text_content(&self, html: &str) -> String {
-        // Simple HTML text extraction (for a more robust solution, use a proper HTML parser)
-        let mut text = html.to_string();
-        
-        // Remove script and style tags completely
-        while let Some(start) = text.find("<script") {
-            if let Some(end) = text[start..].find("</script>") {
-                text.replace_range(start..start + end + 9, "");
-            } else {
-                break;
+    /// robots.txt rules for the URL's origin. Sites without a readable robots.txt allow everything.
+    async fn robots_for(&self, url: &Url) -> RobotsRules {
+        let origin = url.origin().ascii_serialization();
+        if let Some(rules) = self.robots.lock().await.get(&origin) {
+            return rules.clone();
+        }
+
+        let robots_url = format!("{}/robots.txt", origin);
+        let rules = match self.client.get(&robots_url).send().await {
+            Ok(response) if response.status().is_success() => {
+                let content = response.text().await.unwrap_or_default();
+                RobotsRules::parse(&content, USER_AGENT)
+            }
+            Ok(_) => RobotsRules::default(),
+            Err(e) => {
+                debug!("Could not fetch {}: {}", robots_url, e);
+                RobotsRules::default()
+            }
+        };
+        self.robots.lock().await.insert(origin, rules.clone());
+        rules
+    }
+
+    /// Fetches `url` politely (robots.txt, per-host rate limit, conditional requests)
+    /// and extracts its readable content. Non-HTML bodies are returned as-is. With
+    /// `render_js`, pages that turn out to be empty JavaScript shells are re-rendered headless.
+    async fn fetch_page(&self, url: &str, render_js: bool) -> Result<FetchedPage> {
+        let parsed = Url::parse(url).map_err(|e| anyhow!("Invalid URL {}: {}", url, e))?;
+
+        let mut crawl_delay = None;
+        if self.respect_robots_txt {
+            let rules = self.robots_for(&parsed).await;
+            let path = match parsed.query() {
+                Some(query) => format!("{}?{}", parsed.path(), query),
+                None => parsed.path().to_string(),
+            };
+            if !rules.is_allowed(&path) {
+                return Err(anyhow!("Fetching {} is disallowed by the site's robots.txt", url));
             }
+            crawl_delay = rules.crawl_delay;
         }
-        
-        while let Some(start) = text.find("<style") {
-            if let Some(end) = text[start..].find("</style>") {
-                text.replace_range(start..start + end + 8, "");
+        self.rate_limiter.wait(parsed.host_str().unwrap_or(""), crawl_delay).await;
+
+        let cached = self.cache.get(url).await;
+        let mut request = self.client.get(url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request.send().await
+            .map_err(|e| anyhow!("Network error fetching {}: {}", url, e))?;
+        let status = response.status();
+
+        let (final_url, content_type, body, from_cache) = match cached {
+            Some(cached) if status == reqwest::StatusCode::NOT_MODIFIED => {
+                debug!("Using cached copy of {}", url);
+                (Url::parse(&cached.final_url).unwrap_or(parsed), cached.content_type, cached.body, true)
+            }
+            _ => {
+                if !status.is_success() {
+                    return Err(anyhow!("HTTP Error {}: Failed to fetch {}", status, url));
+                }
+                let final_url = response.url().clone();
+                let header = |name: reqwest::header::HeaderName| response.headers().get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|s| s.to_string());
+                let content_type = header(reqwest::header::CONTENT_TYPE);
+                let etag = header(reqwest::header::ETAG);
+                let last_modified = header(reqwest::header::LAST_MODIFIED);
+                let body = response.text().await
+                    .map_err(|e| anyhow!("Failed to read response body: {}", e))?;
+
+                self.cache.put(url, CachedResponse {
+                    etag,
+                    last_modified,
+                    content_type: content_type.clone(),
+                    final_url: final_url.to_string(),
+                    body: body.clone(),
+                }).await;
+                (final_url, content_type, body, false)
+            }
+        };
+        self.cache.record(from_cache);
+
+        let is_html = content_type.as_deref().map_or(true, |ct| ct.contains("html"));
+        let mut rendered = false;
+        let page = if is_html {
+            let page = readability::extract(&body, Some(&final_url));
+            if render_js && readability::looks_like_js_shell(&body, &page) {
+                match self.renderer.render(final_url.as_str()).await {
+                    Ok(dom) => {
+                        info!("Rendered JavaScript page {} headless", final_url);
+                        rendered = true;
+                        readability::extract(&dom, Some(&final_url))
+                    }
+                    Err(e) => {
+                        warn!("Headless render failed for {}: {}", final_url, e);
+                        page
+                    }
+                }
             } else {
-                break;
+                page
+            }
+        } else {
+            ExtractedPage { title: None, description: None, content: body.clone(), links: Vec::new() }
+        };
+
+        Ok(FetchedPage {
+            url: final_url.to_string(),
+            status_code: if from_cache { 200 } else { status.as_u16() },
+            from_cache,
+            rendered,
+            content_type,
+            content_length: body.len(),
+            page,
+        })
+    }
+
+    /// Streams `url` to `dest` without buffering it in memory. The file is written to a
+    /// `.part` sibling and only moved into place once the size limit and checksum pass.
+    async fn download(&self, url: &Url, dest: &Path, max_bytes: u64, expected_sha256: Option<&str>) -> Result<Value> {
+        self.rate_limiter.wait(url.host_str().unwrap_or(""), None).await;
+
+        // Downloads can legitimately take longer than the client's page timeout
+        let response = self.client.get(url.clone())
+            .timeout(Duration::from_secs(60 * 60))
+            .send().await
+            .map_err(|e| anyhow!("Network error downloading {}: {}", url, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!("HTTP Error {}: Failed to download {}", status, url));
+        }
+
+        let total = response.content_length();
+        if let Some(total) = total {
+            if max_bytes > 0 && total > max_bytes {
+                return Err(anyhow!("{} is {} bytes, over the {} byte download limit", url, total, max_bytes));
+            }
+        }
+
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let part_path = PathBuf::from(format!("{}.part", dest.display()));
+        let mut file = tokio::fs::File::create(&part_path).await?;
+
+        let started = Instant::now();
+        let mut hasher = Sha256::new();
+        let mut written: u64 = 0;
+        let mut last_report = Instant::now();
+        let mut reported = false;
+        let mut stream = response.bytes_stream();
+
+        let result: Result<()> = async {
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| anyhow!("Download interrupted: {}", e))?;
+                written += chunk.len() as u64;
+                if max_bytes > 0 && written > max_bytes {
+                    return Err(anyhow!("Download exceeded the {} byte limit", max_bytes));
+                }
+                hasher.update(&chunk);
+                file.write_all(&chunk).await?;
+
+                if last_report.elapsed() >= Duration::from_millis(500) {
+                    print_progress(dest, written, total);
+                    last_report = Instant::now();
+                    reported = true;
+                }
             }
+            file.flush().await?;
+            Ok(())
+        }.await;
+        if reported {
+            eprintln!();
         }
-        
-        // Remove HTML tags
-        let mut result = String::new();
-        let mut in_tag = false;
-        
-        for ch in text.chars() {
-            match ch {
-                '<' => in_tag = true,
-                '>' => in_tag = false,
-                _ if !in_tag => result.push(ch),
-                _ => {}
+
+        let sha256 = format!("{:x}", hasher.finalize());
+        let verified = match (&result, expected_sha256) {
+            (Ok(()), Some(expected)) if !expected.trim().eq_ignore_ascii_case(&sha256) => {
+                Err(anyhow!("Checksum mismatch for {}: expected {}, got {}", url, expected.trim(), sha256))
             }
+            _ => result,
+        };
+        if let Err(e) = verified {
+            drop(file);
+            let _ = tokio::fs::remove_file(&part_path).await;
+            return Err(e);
         }
-        
-        // Clean up whitespace
-        result
-            .lines()
-            .map(|line| line.trim())
-            .filter(|line| !line.is_empty())
-            .collect::<Vec<_>>()
-            .join("\n")
+
+        drop(file);
+        tokio::fs::rename(&part_path, dest).await?;
+        let elapsed = started.elapsed().as_secs_f64();
+        info!("Downloaded {} ({} bytes) to {}", url, written, dest.display());
+
+        Ok(json!({
+            "url": url.as_str(),
+            "path": dest.display().to_string(),
+            "bytes": written,
+            "sha256": sha256,
+            "checksum_verified": expected_sha256.is_some(),
+            "elapsed_seconds": (elapsed * 10.0).round() / 10.0,
+        }))
+    }
+
+    /// Breadth-first crawl of same-site pages starting at `start`, bounded by depth and page count.
+    async fn crawl(&self, start: Url, max_depth: u64, max_pages: u64, index: bool) -> Result<Value> {
+        let mut queue = VecDeque::from([(start.clone(), 0u64)]);
+        let mut seen = HashSet::from([start.to_string()]);
+        let mut pages = Vec::new();
+        let mut errors = Vec::new();
+        let mut indexed_chunks = 0;
+
+        while let Some((url, depth)) = queue.pop_front() {
+            if pages.len() as u64 >= max_pages {
+                break;
+            }
+            info!("Crawling {} (depth {})", url, depth);
+            crate::jobs::report_progress(pages.len() as u64, Some(max_pages), Some(url.as_str()));
+            let fetched = match self.fetch_page(url.as_str(), self.render_js_fallback).await {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    errors.push(json!({ "url": url.as_str(), "error": e.to_string() }));
+                    continue;
+                }
+            };
+
+            if depth < max_depth {
+                for link in crawl_candidates(&start, &fetched.page) {
+                    if seen.insert(link.to_string()) {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+
+            if index {
+                if let Some(store) = &self.knowledge {
+                    for chunk in chunk_paragraphs(&fetched.page.content, KNOWLEDGE_CHUNK_CHARS) {
+                        match store.add_text(&chunk, json!({
+                            "source": fetched.url,
+                            "title": fetched.page.title,
+                            "type": "web"
+                        })).await {
+                            Ok(()) => indexed_chunks += 1,
+                            Err(e) => warn!("Failed to index chunk from {}: {}", fetched.url, e),
+                        }
+                    }
+                }
+            }
+
+            let (content, truncated) = truncate_chars(&fetched.page.content, CRAWL_PAGE_CHARS);
+            pages.push(json!({
+                "url": fetched.url,
+                "depth": depth,
+                "title": fetched.page.title,
+                "content": content,
+                "truncated": truncated,
+            }));
+        }
+
+        Ok(json!({
+            "start_url": start.as_str(),
+            "pages_crawled": pages.len(),
+            "pages": pages,
+            "errors": errors,
+            "unvisited": queue.len(),
+            "indexed_chunks": if index { Some(indexed_chunks) } else { None },
+        }))
+    }
+}
+
+struct FetchedPage {
+    url: String,
+    status_code: u16,
+    from_cache: bool,
+    rendered: bool,
+    content_type: Option<String>,
+    content_length: usize,
+    page: ExtractedPage,
+}
+
+fn page_result(page: &ExtractedPage, max_chars: usize, max_links: usize) -> Value {
+    let (content, truncated) = truncate_chars(&page.content, max_chars);
+    json!({
+        "title": page.title,
+        "description": page.description,
+        "content": content,
+        "truncated": truncated,
+        "total_chars": page.content.chars().count(),
+        "links": page.links.iter().take(max_links).collect::<Vec<_>>(),
+        "link_count": page.links.len(),
+    })
+}
+
+/// Resolves where a download should be written: `dest` as a file, into `dest` when it is a
+/// directory, or into the data directory's downloads folder when omitted.
+fn download_destination(url: &Url, dest: Option<&str>) -> Result<PathBuf> {
+    let filename = url.path_segments()
+        .and_then(|segments| segments.filter(|s| !s.is_empty()).last())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| "download".to_string());
+
+    match dest {
+        Some(dest) if dest.ends_with('/') || dest.ends_with('\\') || Path::new(dest).is_dir() => {
+            Ok(Path::new(dest).join(filename))
+        }
+        Some(dest) => Ok(PathBuf::from(dest)),
+        None => Ok(crate::utils::paths::get_air_data_dir()?.join("downloads").join(filename)),
     }
 }
 
+fn print_progress(dest: &Path, written: u64, total: Option<u64>) {
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    let name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    match total {
+        Some(total) if total > 0 => eprint!("\r⬇️  {}: {:.1} / {:.1} MB ({:.0}%)", name, mb(written), mb(total), written as f64 * 100.0 / total as f64),
+        _ => eprint!("\r⬇️  {}: {:.1} MB", name, mb(written)),
+    }
+    let _ = std::io::stderr().flush();
+}
+
+/// Links on `page` that stay on the crawl's starting host and look like HTML pages.
+fn crawl_candidates(start: &Url, page: &ExtractedPage) -> Vec<Url> {
+    page.links.iter()
+        .filter_map(|link| Url::parse(&link.url).ok())
+        .filter(|url| url.host_str() == start.host_str())
+        .filter(|url| {
+            let path = url.path().to_lowercase();
+            !NON_PAGE_EXTENSIONS.iter().any(|ext| path.ends_with(ext))
+        })
+        .collect()
+}
+
+/// Groups paragraphs into chunks of roughly `max_chars` for embedding.
+fn chunk_paragraphs(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if !current.is_empty() && current.len() + paragraph.len() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    // Skip fragments too small to be useful, as add_file does
+    chunks.retain(|c| c.len() >= 20);
+    chunks
+}
+
 #[async_trait]
 impl Tool for WebTool {
     fn name(&self) -> &str {
@@ -76,7 +476,27 @@ impl Tool for WebTool {
     }
     
     fn description(&self) -> &str {
-        "Web operations: fetch pages, extract content, check status"
+        "Web operations: fetch pages, extract readable content and links, crawl sites, download files, check status"
+    }
+
+    fn permission_level(&self) -> PermissionLevel {
+        PermissionLevel::Modifies
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        Some(self.cache.stats())
+    }
+
+    async fn snapshot(&self) -> Option<Value> {
+        serde_json::to_value(self.cache.entries().await).ok()
+    }
+
+    async fn restore(&self, state: Value) -> Result<()> {
+        let entries: Vec<(String, CachedResponse)> = serde_json::from_value(state)?;
+        for (url, response) in entries {
+            self.cache.put(&url, response).await;
+        }
+        Ok(())
     }
     
     fn available_functions(&self) -> Vec<String> {
@@ -85,6 +505,8 @@ impl Tool for WebTool {
             "get_headers".to_string(),
             "check_status".to_string(),
             "extract_text".to_string(),
+            "crawl".to_string(),
+            "download".to_string(),
         ]
     }
     
@@ -103,69 +525,170 @@ impl Tool for WebTool {
                 }
                 
                 info!("Fetching URL: {}", url);
-                
-                match self.client.get(url).send().await {
-                    Ok(response) => {
-                        let status = response.status();
-                        let headers = response.headers().clone();
-                        
-                        if status.is_success() {
-                            match response.text().await {
-                                Ok(content) => {
-                                    let text_content = self.extract_text_content(&content);
-                                    let preview = if text_content.len() > 1000 {
-                                        format!("{}...\n\n[Content truncated - {} total characters]", 
-                                               &text_content[..1000], text_content.len())
-                                    } else {
-                                        text_content.clone()
-                                    };
-                                    
-                                    let metadata = json!({
-                                        "url": url,
-                                        "status_code": status.as_u16(),
-                                        "content_length": content.len(),
-                                        "text_length": text_content.len(),
-                                        "content_type": headers.get("content-type")
-                                            .and_then(|v| v.to_str().ok()),
-                                    });
-                                    
-                                    Ok(ToolResult {
-                                        success: true,
-                                        result: json!({
-                                            "url": url,
-                                            "content": text_content,
-                                            "status_code": status.as_u16(),
-                                            "truncated": text_content.len() > 10000
-                                        }),
-                                        metadata: Some(metadata),
-                                    })
-                                }
-                                Err(e) => Ok(ToolResult {
-                                    success: false,
-                                    result: json!(format!("Failed to read response body: {}", e)),
-                                    metadata: None,
-                                })
-                            }
-                        } else {
-                            Ok(ToolResult {
-                                success: false,
-                                result: json!(format!("HTTP Error {}: Failed to fetch {}", status, url)),
-                                metadata: Some(json!({
-                                    "url": url,
-                                    "status_code": status.as_u16(),
-                                    "status_text": status.canonical_reason()
-                                })),
-                            })
-                        }
+                let max_chars = args["max_chars"].as_u64().map(|n| n as usize).unwrap_or(DEFAULT_MAX_CHARS);
+                let max_links = args["max_links"].as_u64().map(|n| n as usize).unwrap_or(DEFAULT_MAX_LINKS);
+                let render_js = args["render_js"].as_bool().unwrap_or(self.render_js_fallback);
+
+                match self.fetch_page(url, render_js).await {
+                    Ok(fetched) => {
+                        let mut result = page_result(&fetched.page, max_chars, max_links);
+                        result["url"] = json!(fetched.url);
+                        result["status_code"] = json!(fetched.status_code);
+
+                        Ok(ToolResult {
+                            success: true,
+                            result,
+                            metadata: Some(json!({
+                                "url": url,
+                                "status_code": fetched.status_code,
+                                "content_length": fetched.content_length,
+                                "text_length": fetched.page.content.len(),
+                                "content_type": fetched.content_type,
+                                "from_cache": fetched.from_cache,
+                                "rendered_js": fetched.rendered,
+                            })),
+                        })
                     }
                     Err(e) => Ok(ToolResult {
                         success: false,
-                        result: json!(format!("Network error fetching {}: {}", url, e)),
+                        result: json!(e.to_string()),
+                        metadata: Some(json!({ "url": url })),
+                    })
+                }
+            }
+
+            "extract_text" => {
+                // Extract from raw HTML when given, otherwise fetch the URL
+                let max_chars = args["max_chars"].as_u64().map(|n| n as usize).unwrap_or(DEFAULT_MAX_CHARS);
+                let max_links = args["max_links"].as_u64().map(|n| n as usize).unwrap_or(DEFAULT_MAX_LINKS);
+
+                if let Some(html) = args["html"].as_str() {
+                    let base = args["url"].as_str().and_then(|u| reqwest::Url::parse(u).ok());
+                    let page = readability::extract(html, base.as_ref());
+                    return Ok(ToolResult {
+                        success: true,
+                        result: page_result(&page, max_chars, max_links),
+                        metadata: None,
+                    });
+                }
+
+                let url = args["url"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'url' or 'html' parameter"))?;
+                if !self.is_valid_url(url) {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Invalid URL format: {}. Must start with http:// or https://", url)),
                         metadata: None,
+                    });
+                }
+
+                let render_js = args["render_js"].as_bool().unwrap_or(self.render_js_fallback);
+                match self.fetch_page(url, render_js).await {
+                    Ok(fetched) => Ok(ToolResult {
+                        success: true,
+                        result: page_result(&fetched.page, max_chars, max_links),
+                        metadata: Some(json!({ "url": fetched.url })),
+                    }),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: json!(e.to_string()),
+                        metadata: Some(json!({ "url": url })),
                     })
                 }
             }
             
+            "crawl" => {
+                let url = args["url"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'url' parameter"))?;
+                let Some(start) = Url::parse(url).ok().filter(|_| self.is_valid_url(url)) else {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Invalid URL format: {}. Must start with http:// or https://", url)),
+                        metadata: None,
+                    });
+                };
+                let max_depth = args["max_depth"].as_u64().unwrap_or(1).min(MAX_CRAWL_DEPTH);
+                let max_pages = args["max_pages"].as_u64().unwrap_or(10).clamp(1, MAX_CRAWL_PAGES);
+                let index = args["index"].as_bool().unwrap_or(false);
+
+                if index && self.knowledge.is_none() {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!("Cannot index crawled pages: the knowledge store is unavailable"),
+                        metadata: None,
+                    });
+                }
+
+                let result = self.crawl(start, max_depth, max_pages, index).await?;
+                Ok(ToolResult {
+                    success: result["pages_crawled"].as_u64().unwrap_or(0) > 0,
+                    result,
+                    metadata: Some(json!({
+                        "max_depth": max_depth,
+                        "max_pages": max_pages,
+                        "indexed": index
+                    })),
+                })
+            }
+
+            "download" => {
+                let url = args["url"].as_str()
+                    .ok_or_else(|| anyhow!("Missing 'url' parameter"))?;
+                let Some(parsed) = Url::parse(url).ok().filter(|_| self.is_valid_url(url)) else {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("Invalid URL format: {}. Must start with http:// or https://", url)),
+                        metadata: None,
+                    });
+                };
+                let dest = download_destination(&parsed, args["dest"].as_str())?;
+                // The model may lower the configured limit but not raise it
+                let max_bytes = match (args["max_bytes"].as_u64(), self.max_download_bytes) {
+                    (Some(requested), 0) => requested,
+                    (Some(requested), limit) => requested.min(limit),
+                    (None, limit) => limit,
+                };
+
+                // Sandbox first, so a denied path never reveals whether something exists there
+                let (dest, needs_approval) = match self.check_destination(&dest) {
+                    Ok(checked) => checked,
+                    Err(e) => return Ok(ToolResult {
+                        success: false,
+                        result: json!(e.to_string()),
+                        metadata: Some(json!({ "url": url, "path": dest.display().to_string(), "denied": true })),
+                    }),
+                };
+                let exists = dest.exists();
+                if exists && !args["overwrite"].as_bool().unwrap_or(false) {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("{} already exists (pass overwrite: true to replace it)", dest.display())),
+                        metadata: None,
+                    });
+                }
+                if needs_approval && !self.ask_confirmation(&parsed, &dest, exists) {
+                    return Ok(ToolResult {
+                        success: false,
+                        result: json!(format!("The user declined saving the download to {}", dest.display())),
+                        metadata: Some(json!({ "url": url, "path": dest.display().to_string(), "denied": true })),
+                    });
+                }
+
+                info!("Downloading {} to {}", url, dest.display());
+                match self.download(&parsed, &dest, max_bytes, args["sha256"].as_str()).await {
+                    Ok(result) => Ok(ToolResult {
+                        success: true,
+                        result,
+                        metadata: None,
+                    }),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        result: json!(e.to_string()),
+                        metadata: Some(json!({ "url": url, "path": dest.display().to_string() })),
+                    })
+                }
+            }
+
             "check_status" => {
                 let url = args["url"].as_str()
                     .ok_or_else(|| anyhow!("Missing 'url' parameter"))?;
@@ -260,3 +783,66 @@ impl Tool for WebTool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::readability::PageLink;
+
+    #[test]
+    fn test_crawl_candidates_stay_on_site() {
+        let start = Url::parse("https://docs.example.com/guide/").unwrap();
+        let link = |url: &str| PageLink { text: String::new(), url: url.to_string() };
+        let page = ExtractedPage {
+            title: None,
+            description: None,
+            content: String::new(),
+            links: vec![
+                link("https://docs.example.com/guide/intro"),
+                link("https://other.example.com/"),
+                link("https://docs.example.com/manual.pdf"),
+            ],
+        };
+
+        let urls: Vec<String> = crawl_candidates(&start, &page).iter().map(|u| u.to_string()).collect();
+        assert_eq!(urls, vec!["https://docs.example.com/guide/intro"]);
+    }
+
+    #[test]
+    fn test_download_destination() {
+        let url = Url::parse("https://example.com/releases/tool-1.0.tar.gz?sig=abc").unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir_str = dir.path().to_string_lossy().to_string();
+
+        assert_eq!(download_destination(&url, Some(&dir_str)).unwrap(), dir.path().join("tool-1.0.tar.gz"));
+        assert_eq!(download_destination(&url, Some("out/file.tgz")).unwrap(), PathBuf::from("out/file.tgz"));
+    }
+
+    #[test]
+    fn test_download_outside_workspace_denied() {
+        let root = tempfile::TempDir::new().unwrap();
+        let config = FileSystemConfig {
+            sandbox_enabled: true,
+            workspace_roots: vec![root.path().to_string_lossy().to_string()],
+            denied_globs: vec!["**/.ssh/**".to_string()],
+            max_file_size_bytes: 0,
+        };
+        let tool = WebTool::new().with_sandbox(&config);
+
+        let outside = tempfile::TempDir::new().unwrap();
+        assert!(tool.check_destination(&outside.path().join("key")).is_err());
+        assert!(tool.check_destination(&root.path().join(".ssh/authorized_keys")).is_err());
+        let (inside, needs_approval) = tool.check_destination(&root.path().join("tool.tgz")).unwrap();
+        assert!(inside.ends_with("tool.tgz"));
+        assert!(needs_approval);
+    }
+
+    #[test]
+    fn test_chunk_paragraphs() {
+        let text = "First paragraph with enough text.\n\nSecond paragraph with enough text.\n\nok";
+        let chunks = chunk_paragraphs(text, 40);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[1].starts_with("Second"));
+        assert!(chunks[1].ends_with("ok"));
+    }
+}
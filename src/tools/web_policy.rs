@@ -0,0 +1,252 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use super::CacheStats;
+use serde::{Deserialize, Serialize};
+
+/// Rules from a site's robots.txt that apply to our user agent.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsRules {
+    // (pattern, allow)
+    rules: Vec<(String, bool)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    /// Parses robots.txt, keeping the group for `user_agent` if present and the `*` group otherwise.
+    pub fn parse(content: &str, user_agent: &str) -> Self {
+        let agent = user_agent.to_lowercase();
+        let mut specific = RobotsRules::default();
+        let mut wildcard = RobotsRules::default();
+        let mut found_specific = false;
+
+        // Agents named by the current group; consecutive User-agent lines share one group
+        let mut group_agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else { continue };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            if key == "user-agent" {
+                if in_rules {
+                    group_agents.clear();
+                    in_rules = false;
+                }
+                group_agents.push(value.to_lowercase());
+                continue;
+            }
+            in_rules = true;
+
+            let matches_specific = group_agents.iter().any(|a| a != "*" && agent.contains(a.as_str()));
+            let matches_wildcard = group_agents.iter().any(|a| a == "*");
+            found_specific |= matches_specific;
+
+            let mut apply = |rules: &mut RobotsRules| match key.as_str() {
+                "disallow" if !value.is_empty() => rules.rules.push((value.to_string(), false)),
+                "allow" if !value.is_empty() => rules.rules.push((value.to_string(), true)),
+                "crawl-delay" => {
+                    if let Ok(secs) = value.parse::<f64>() {
+                        rules.crawl_delay = Some(Duration::from_secs_f64(secs.clamp(0.0, 60.0)));
+                    }
+                }
+                _ => {}
+            };
+            if matches_specific {
+                apply(&mut specific);
+            }
+            if matches_wildcard {
+                apply(&mut wildcard);
+            }
+        }
+
+        if found_specific { specific } else { wildcard }
+    }
+
+    /// The longest matching rule wins; Allow wins ties. Paths with no matching rule are allowed.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules.iter()
+            .filter(|(pattern, _)| pattern_matches(pattern, path))
+            .max_by_key(|(pattern, allow)| (pattern.len(), *allow))
+            .map_or(true, |(_, allow)| *allow)
+    }
+}
+
+/// robots.txt matching: `*` matches any run of characters and a trailing `$` anchors the end.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !path.starts_with(first) {
+        return false;
+    }
+    let mut pos = first.len();
+    let rest: Vec<&str> = parts.collect();
+    for (i, part) in rest.iter().enumerate() {
+        if anchored && i == rest.len() - 1 {
+            return path.len() >= pos + part.len() && path.ends_with(part);
+        }
+        match path[pos..].find(part) {
+            Some(found) => pos += found + part.len(),
+            None => return false,
+        }
+    }
+    !anchored || pos == path.len()
+}
+
+/// Spaces out requests to the same host.
+pub struct RateLimiter {
+    min_interval: Duration,
+    next_allowed: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(min_interval: Duration) -> Self {
+        Self { min_interval, next_allowed: Mutex::new(HashMap::new()) }
+    }
+
+    /// Waits until a request to `host` is allowed, using the larger of the configured interval and `crawl_delay`.
+    pub async fn wait(&self, host: &str, crawl_delay: Option<Duration>) {
+        let interval = crawl_delay.map_or(self.min_interval, |d| d.max(self.min_interval));
+        // Reserve our slot under the lock, then sleep without holding it
+        let wait_until = {
+            let mut next_allowed = self.next_allowed.lock().await;
+            let now = Instant::now();
+            let slot = next_allowed.get(host).copied().filter(|t| *t > now).unwrap_or(now);
+            next_allowed.insert(host.to_string(), slot + interval);
+            slot
+        };
+        tokio::time::sleep_until(wait_until.into()).await;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_type: Option<String>,
+    pub final_url: String,
+    pub body: String,
+}
+
+/// Bounded in-memory cache of validated responses, keyed by URL and evicted oldest first.
+pub struct ResponseCache {
+    capacity: usize,
+    inner: Mutex<(HashMap<String, CachedResponse>, VecDeque<String>)>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new((HashMap::new(), VecDeque::new())),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a fetch as served from the cache (the server answered 304) or downloaded.
+    pub fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { hits: self.hits.load(Ordering::Relaxed), misses: self.misses.load(Ordering::Relaxed) }
+    }
+
+    /// Cached responses, oldest first.
+    pub async fn entries(&self) -> Vec<(String, CachedResponse)> {
+        let guard = self.inner.lock().await;
+        let (entries, order) = &*guard;
+        order.iter().filter_map(|url| entries.get(url).map(|r| (url.clone(), r.clone()))).collect()
+    }
+
+    pub async fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.inner.lock().await.0.get(url).cloned()
+    }
+
+    /// Stores a response if it carries a validator we can revalidate with later.
+    pub async fn put(&self, url: &str, response: CachedResponse) {
+        if self.capacity == 0 || (response.etag.is_none() && response.last_modified.is_none()) {
+            return;
+        }
+        let mut guard = self.inner.lock().await;
+        let (entries, order) = &mut *guard;
+        if entries.insert(url.to_string(), response).is_none() {
+            order.push_back(url.to_string());
+        }
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+        User-agent: *
+        Disallow: /private/
+        Allow: /private/public.html
+        Disallow: /*.pdf$
+
+        User-agent: BadBot
+        Disallow: /
+
+        User-agent: air-agent
+        User-agent: other
+        Disallow: /tmp/
+        Crawl-delay: 2
+    ";
+
+    #[test]
+    fn test_wildcard_group_rules() {
+        let rules = RobotsRules::parse(ROBOTS, "SomeCrawler/1.0");
+        assert!(rules.is_allowed("/index.html"));
+        assert!(!rules.is_allowed("/private/secret.html"));
+        assert!(rules.is_allowed("/private/public.html"));
+        assert!(!rules.is_allowed("/docs/manual.pdf"));
+        assert!(rules.is_allowed("/docs/manual.pdf.html"));
+        assert_eq!(rules.crawl_delay, None);
+    }
+
+    #[test]
+    fn test_specific_group_overrides_wildcard() {
+        let rules = RobotsRules::parse(ROBOTS, "air-Agent/1.0");
+        assert!(rules.is_allowed("/private/secret.html"));
+        assert!(!rules.is_allowed("/tmp/file"));
+        assert_eq!(rules.crawl_delay, Some(Duration::from_secs(2)));
+    }
+
+    #[tokio::test]
+    async fn test_cache_requires_validator_and_evicts_oldest() {
+        let cache = ResponseCache::new(1);
+        let response = |etag: Option<&str>| CachedResponse {
+            etag: etag.map(|s| s.to_string()),
+            last_modified: None,
+            content_type: None,
+            final_url: String::new(),
+            body: String::new(),
+        };
+
+        cache.put("a", response(None)).await;
+        assert!(cache.get("a").await.is_none());
+
+        cache.put("a", response(Some("\"1\""))).await;
+        cache.put("b", response(Some("\"2\""))).await;
+        assert!(cache.get("a").await.is_none());
+        assert_eq!(cache.get("b").await.unwrap().etag.as_deref(), Some("\"2\""));
+    }
+}
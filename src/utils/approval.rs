@@ -0,0 +1,47 @@
+//! Questions asked on the terminal before air does something the user has to approve. Processes
+//! with nobody at their terminal (the HTTP server, the daemon) call `disable_prompts`, after which
+//! every question is declined straight away instead of waiting on stdin.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::warn;
+
+static PROMPTS_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Declines every later question without asking.
+pub fn disable_prompts() {
+    PROMPTS_DISABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn prompts_enabled() -> bool {
+    !PROMPTS_DISABLED.load(Ordering::Relaxed)
+}
+
+/// Asks `question` with a `[y/N]` suffix; only y or yes approves.
+pub fn confirm(question: &str) -> bool {
+    matches!(answer(&format!("{} [y/N]", question)).as_deref(), Some("y" | "yes"))
+}
+
+/// Asks `question` (which lists its own options) and returns the first letter of the answer:
+/// 'n' for an empty answer, 'q' when nobody can answer.
+pub fn choice(question: &str) -> char {
+    match answer(question) {
+        Some(answer) => answer.chars().next().unwrap_or('n'),
+        None => 'q',
+    }
+}
+
+/// The trimmed, lowercased answer; `None` when prompts are disabled or stdin is closed.
+fn answer(question: &str) -> Option<String> {
+    if !prompts_enabled() {
+        warn!("Declined without asking, nobody is at this terminal: {}", question.trim());
+        return None;
+    }
+    print!("{} ", question);
+    io::stdout().flush().ok();
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(read) if read > 0 => Some(input.trim().to_lowercase()),
+        _ => None,
+    }
+}
@@ -0,0 +1,270 @@
+//! Choosing the chat template the local model's prompts are formatted with.
+//!
+//! TinyLlama, Mistral, Llama 3, Phi-3 and Qwen all expect different markers around each turn,
+//! and a model prompted in the wrong format rambles or answers its own questions. In order of
+//! preference the template comes from `local_model.chat_template`, a `tokenizer_config.json`
+//! shipped next to the model, the model's own GGUF metadata, and finally a built-in template
+//! picked by the model's file name.
+
+use crate::config::LocalModelConfig;
+use anyhow::{Result, anyhow};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+const GGUF_TEMPLATE_KEY: &str = "tokenizer.chat_template";
+
+/// Built-in templates in the Hugging Face Jinja format, by name.
+pub const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("zephyr", "{% for message in messages %}{{ '<|' + message['role'] + '|>\\n' + message['content'] + eos_token + '\\n' }}{% endfor %}{% if add_generation_prompt %}{{ '<|assistant|>\\n' }}{% endif %}"),
+    ("chatml", "{% for message in messages %}{{ '<|im_start|>' + message['role'] + '\\n' + message['content'] + '<|im_end|>\\n' }}{% endfor %}{% if add_generation_prompt %}{{ '<|im_start|>assistant\\n' }}{% endif %}"),
+    ("llama3", "{{ bos_token }}{% for message in messages %}{{ '<|start_header_id|>' + message['role'] + '<|end_header_id|>\\n\\n' + message['content'] | trim + '<|eot_id|>' }}{% endfor %}{% if add_generation_prompt %}{{ '<|start_header_id|>assistant<|end_header_id|>\\n\\n' }}{% endif %}"),
+    // Mistral has no system role; system text becomes an instruction of its own
+    ("mistral", "{{ bos_token }}{% for message in messages %}{% if message['role'] == 'assistant' %}{{ ' ' + message['content'] + eos_token }}{% else %}{{ '[INST] ' + message['content'] + ' [/INST]' }}{% endif %}{% endfor %}"),
+    ("phi3", "{% for message in messages %}{{ '<|' + message['role'] + '|>\\n' + message['content'] + '<|end|>\\n' }}{% endfor %}{% if add_generation_prompt %}{{ '<|assistant|>\\n' }}{% endif %}"),
+];
+
+/// Where a model's chat template comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateSource {
+    /// A built-in template, chosen in config or from the file name.
+    Builtin(&'static str),
+    /// A tokenizer_config.json or .jinja file.
+    File(PathBuf),
+    /// The template embedded in the GGUF file, which the runtime applies by itself.
+    Gguf,
+}
+
+impl std::fmt::Display for TemplateSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateSource::Builtin(name) => write!(f, "built-in '{}'", name),
+            TemplateSource::File(path) => write!(f, "{}", path.display()),
+            TemplateSource::Gguf => write!(f, "GGUF metadata"),
+        }
+    }
+}
+
+pub fn builtin(name: &str) -> Option<(&'static str, &'static str)> {
+    BUILTIN_TEMPLATES.iter().copied().find(|(n, _)| n.eq_ignore_ascii_case(name))
+}
+
+/// Decides which template the model at `config.model_path` should use.
+pub fn resolve(config: &LocalModelConfig) -> Result<Option<TemplateSource>> {
+    if let Some(choice) = config.chat_template.as_deref().filter(|c| !c.trim().is_empty()) {
+        if let Some((name, _)) = builtin(choice) {
+            return Ok(Some(TemplateSource::Builtin(name)));
+        }
+        let path = PathBuf::from(choice);
+        if path.exists() {
+            return Ok(Some(TemplateSource::File(path)));
+        }
+        let names: Vec<&str> = BUILTIN_TEMPLATES.iter().map(|(n, _)| *n).collect();
+        return Err(anyhow!("local_model.chat_template '{}' is neither a file nor one of: {}", choice, names.join(", ")));
+    }
+
+    let model_path = Path::new(&config.model_path);
+    if let Some(path) = tokenizer_config_for(model_path) {
+        return Ok(Some(TemplateSource::File(path)));
+    }
+    match read_gguf_chat_template(model_path) {
+        Ok(Some(_)) => return Ok(Some(TemplateSource::Gguf)),
+        Ok(None) => {}
+        Err(e) => warn!("Could not read GGUF metadata of {}: {}", model_path.display(), e),
+    }
+    Ok(guess_from_filename(model_path).map(TemplateSource::Builtin))
+}
+
+/// Path to hand the runtime for `source`; built-in templates are written out as a
+/// tokenizer_config.json under `cache_dir` first. None means the GGUF's own template.
+pub fn template_file(source: &TemplateSource, cache_dir: &Path) -> Result<Option<PathBuf>> {
+    match source {
+        TemplateSource::Gguf => Ok(None),
+        TemplateSource::File(path) => Ok(Some(path.clone())),
+        TemplateSource::Builtin(name) => {
+            let (_, template) = builtin(name).ok_or_else(|| anyhow!("Unknown chat template '{}'", name))?;
+            std::fs::create_dir_all(cache_dir)?;
+            let path = cache_dir.join(format!("{}.tokenizer_config.json", name));
+            std::fs::write(&path, serde_json::to_string_pretty(&serde_json::json!({ "chat_template": template }))?)?;
+            info!("💬 Using the built-in '{}' chat template", name);
+            Ok(Some(path))
+        }
+    }
+}
+
+/// `<model>.tokenizer_config.json` beside the model, or `tokenizer_config.json` when the model
+/// has a folder to itself (in a shared folder it could belong to any of the models).
+fn tokenizer_config_for(model_path: &Path) -> Option<PathBuf> {
+    let dir = model_path.parent()?;
+    let stem = model_path.file_stem()?.to_string_lossy();
+    let specific = dir.join(format!("{}.tokenizer_config.json", stem));
+    if has_chat_template(&specific) {
+        return Some(specific);
+    }
+
+    let shared = dir.join("tokenizer_config.json");
+    let ggufs = std::fs::read_dir(dir).ok()?
+        .flatten()
+        .filter(|e| e.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gguf")))
+        .count();
+    (ggufs == 1 && has_chat_template(&shared)).then_some(shared)
+}
+
+fn has_chat_template(path: &Path) -> bool {
+    std::fs::read_to_string(path).ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .is_some_and(|json| !json["chat_template"].is_null())
+}
+
+/// Picks a built-in template from well-known model family names in the file name.
+pub fn guess_from_filename(model_path: &Path) -> Option<&'static str> {
+    let name = model_path.file_name()?.to_string_lossy().to_lowercase();
+    let families: &[(&[&str], &str)] = &[
+        (&["tinyllama", "zephyr"], "zephyr"),
+        (&["llama-3", "llama3"], "llama3"),
+        (&["phi-3", "phi3"], "phi3"),
+        (&["qwen", "chatml", "hermes", "dolphin"], "chatml"),
+        (&["mistral", "mixtral"], "mistral"),
+    ];
+    families.iter()
+        .find(|(markers, _)| markers.iter().any(|m| name.contains(m)))
+        .map(|(_, template)| *template)
+}
+
+/// Reads `tokenizer.chat_template` from a GGUF file's metadata without loading the model.
+pub fn read_gguf_chat_template(path: &Path) -> Result<Option<String>> {
+    read_gguf_string(&mut BufReader::new(File::open(path)?), GGUF_TEMPLATE_KEY)
+}
+
+// GGUF metadata value types
+const GGUF_U8: u32 = 0;
+const GGUF_I8: u32 = 1;
+const GGUF_U16: u32 = 2;
+const GGUF_I16: u32 = 3;
+const GGUF_U32: u32 = 4;
+const GGUF_I32: u32 = 5;
+const GGUF_F32: u32 = 6;
+const GGUF_BOOL: u32 = 7;
+const GGUF_STRING: u32 = 8;
+const GGUF_ARRAY: u32 = 9;
+const GGUF_U64: u32 = 10;
+const GGUF_I64: u32 = 11;
+const GGUF_F64: u32 = 12;
+
+fn read_gguf_string<R: Read + Seek>(reader: &mut R, wanted: &str) -> Result<Option<String>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"GGUF" {
+        return Err(anyhow!("not a GGUF file"));
+    }
+    let version = read_u32(reader)?;
+    // Version 1 used 32-bit counts and lengths; later versions 64-bit
+    let wide = version >= 2;
+    let _tensor_count = read_len(reader, wide)?;
+    let kv_count = read_len(reader, wide)?;
+
+    for _ in 0..kv_count {
+        let key = read_string(reader, wide)?;
+        let value_type = read_u32(reader)?;
+        if key == wanted && value_type == GGUF_STRING {
+            return Ok(Some(read_string(reader, wide)?));
+        }
+        skip_value(reader, value_type, wide)?;
+    }
+    Ok(None)
+}
+
+fn skip_value<R: Read + Seek>(reader: &mut R, value_type: u32, wide: bool) -> Result<()> {
+    let size = match value_type {
+        GGUF_U8 | GGUF_I8 | GGUF_BOOL => 1,
+        GGUF_U16 | GGUF_I16 => 2,
+        GGUF_U32 | GGUF_I32 | GGUF_F32 => 4,
+        GGUF_U64 | GGUF_I64 | GGUF_F64 => 8,
+        GGUF_STRING => read_len(reader, wide)?,
+        GGUF_ARRAY => {
+            let item_type = read_u32(reader)?;
+            let count = read_len(reader, wide)?;
+            for _ in 0..count {
+                skip_value(reader, item_type, wide)?;
+            }
+            return Ok(());
+        }
+        other => return Err(anyhow!("unknown GGUF value type {}", other)),
+    };
+    reader.seek(SeekFrom::Current(size as i64))?;
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_len<R: Read>(reader: &mut R, wide: bool) -> Result<u64> {
+    if !wide {
+        return Ok(read_u32(reader)? as u64);
+    }
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string<R: Read>(reader: &mut R, wide: bool) -> Result<String> {
+    let len = read_len(reader, wide)?;
+    let mut buf = Vec::new();
+    reader.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(anyhow!("truncated GGUF metadata"));
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn gguf_string(s: &str) -> Vec<u8> {
+        let mut bytes = (s.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(s.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_read_gguf_chat_template() {
+        let mut file = b"GGUF".to_vec();
+        file.extend_from_slice(&3u32.to_le_bytes());
+        file.extend_from_slice(&0u64.to_le_bytes());
+        file.extend_from_slice(&3u64.to_le_bytes());
+        // general.name = "test"
+        file.extend(gguf_string("general.name"));
+        file.extend_from_slice(&GGUF_STRING.to_le_bytes());
+        file.extend(gguf_string("test"));
+        // tokenizer.ggml.tokens = ["a", "b"]
+        file.extend(gguf_string("tokenizer.ggml.tokens"));
+        file.extend_from_slice(&GGUF_ARRAY.to_le_bytes());
+        file.extend_from_slice(&GGUF_STRING.to_le_bytes());
+        file.extend_from_slice(&2u64.to_le_bytes());
+        file.extend(gguf_string("a"));
+        file.extend(gguf_string("b"));
+        file.extend(gguf_string(GGUF_TEMPLATE_KEY));
+        file.extend_from_slice(&GGUF_STRING.to_le_bytes());
+        file.extend(gguf_string("{{ messages }}"));
+
+        let template = read_gguf_string(&mut Cursor::new(&file), GGUF_TEMPLATE_KEY).unwrap();
+        assert_eq!(template.as_deref(), Some("{{ messages }}"));
+        assert_eq!(read_gguf_string(&mut Cursor::new(&file), "missing.key").unwrap(), None);
+        assert!(read_gguf_string(&mut Cursor::new(b"GGML...."), GGUF_TEMPLATE_KEY).is_err());
+    }
+
+    #[test]
+    fn test_guess_from_filename() {
+        assert_eq!(guess_from_filename(Path::new("tinyllama-1.1b-chat-v1.0.Q2_K.gguf")), Some("zephyr"));
+        assert_eq!(guess_from_filename(Path::new("Llama-3.2-3B-Instruct-Q4_K_M.gguf")), Some("llama3"));
+        assert_eq!(guess_from_filename(Path::new("Phi-3-mini-4k-instruct-q4.gguf")), Some("phi3"));
+        assert_eq!(guess_from_filename(Path::new("mistral-7b-instruct-v0.2.Q4_K_M.gguf")), Some("mistral"));
+        assert_eq!(guess_from_filename(Path::new("model.gguf")), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! `/copy` and `/save` in interactive mode: put the last answer, or its last code block, on the
+//! system clipboard or in a file. This is the user's shortcut, separate from any tool the model calls.
+
+use anyhow::{Result, anyhow};
+
+/// Kept for the whole session: on Linux the copied text is only served while it is alive.
+pub struct Clipboard {
+    inner: arboard::Clipboard,
+}
+
+impl Clipboard {
+    pub fn new() -> Result<Self> {
+        let inner = arboard::Clipboard::new().map_err(|e| anyhow!("Clipboard unavailable: {}", e))?;
+        Ok(Self { inner })
+    }
+
+    pub fn copy(&mut self, text: &str) -> Result<()> {
+        self.inner.set_text(text.to_string()).map_err(|e| anyhow!("Could not copy: {}", e))
+    }
+
+    /// The highlighted text: the primary selection on Linux, the clipboard elsewhere.
+    pub fn selection(&mut self) -> Result<String> {
+        #[cfg(target_os = "linux")]
+        let text = {
+            use arboard::{GetExtLinux, LinuxClipboardKind};
+            self.inner.get().clipboard(LinuxClipboardKind::Primary).text()
+        };
+        #[cfg(not(target_os = "linux"))]
+        let text = self.inner.get_text();
+        text.map_err(|e| anyhow!("Nothing selected: {}", e))
+    }
+}
+
+/// The answer without the routing banner local responses carry.
+pub fn answer_text(response: &str) -> &str {
+    response.strip_prefix("🏠 Local Model Response:\n").unwrap_or(response).trim()
+}
+
+/// The body of the answer's last fenced code block.
+pub fn last_code_block(response: &str) -> Option<&str> {
+    let mut last = None;
+    let mut rest = response;
+    while let Some(start) = rest.find("```") {
+        let after = &rest[start + 3..];
+        let Some(newline) = after.find('\n') else { break };
+        let body = &after[newline + 1..];
+        let Some(end) = body.find("```") else { break };
+        last = Some(body[..end].trim_end_matches('\n'));
+        rest = &body[end + 3..];
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_code_block() {
+        let answer = "First:\n```bash\ncargo build\n```\nThen:\n```rust\nfn main() {}\n```\nDone.";
+        assert_eq!(last_code_block(answer), Some("fn main() {}"));
+        assert_eq!(last_code_block("no code here"), None);
+        assert_eq!(last_code_block("```\nunterminated"), None);
+    }
+
+    #[test]
+    fn test_answer_text() {
+        assert_eq!(answer_text("🏠 Local Model Response:\nHello\n"), "Hello");
+        assert_eq!(answer_text(" Hi "), "Hi");
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Watches config.toml, `.env` and the `profiles/` directory for edits during an interactive session.
+pub struct ConfigWatcher {
+    // Kept alive for as long as we want events
+    _watcher: RecommendedWatcher,
+    events: Receiver<PathBuf>,
+    env_path: PathBuf,
+}
+
+impl ConfigWatcher {
+    pub fn new(config_dir: &Path) -> Result<Self> {
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            match result {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) => {
+                    for path in event.paths.into_iter().filter(|p| is_config_file(p)) {
+                        let _ = tx.send(path);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Config watcher error: {}", e),
+            }
+        })?;
+
+        // Watch the directory rather than the files so editors that replace files on save are still seen
+        watcher.watch(config_dir, RecursiveMode::NonRecursive)?;
+        let profiles_dir = config_dir.join("profiles");
+        if profiles_dir.is_dir() {
+            watcher.watch(&profiles_dir, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            env_path: config_dir.join(".env"),
+        })
+    }
+
+    /// Returns the files changed since the last call, waiting briefly so a burst of
+    /// writes from one save is reported once. Returns an empty list when nothing changed.
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self.events.try_iter().collect();
+        if changed.is_empty() {
+            return changed;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+        changed.extend(self.events.try_iter());
+        changed.sort();
+        changed.dedup();
+        debug!("Configuration files changed: {:?}", changed);
+        changed
+    }
+
+    /// Re-reads `.env`, overwriting variables that were loaded from it earlier.
+    pub fn reload_env(&self) -> Result<()> {
+        if !self.env_path.exists() {
+            return Ok(());
+        }
+        for item in dotenv::from_path_iter(&self.env_path)? {
+            let (key, value) = item?;
+            std::env::set_var(key, value);
+        }
+        Ok(())
+    }
+
+    pub fn is_env_file(&self, path: &Path) -> bool {
+        path.file_name() == self.env_path.file_name()
+    }
+}
+
+fn is_config_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    name == "config.toml" || name == ".env" || (name.ends_with(".toml") && path.parent().is_some_and(|p| p.ends_with("profiles")))
+}
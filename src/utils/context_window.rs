@@ -0,0 +1,108 @@
+//! Fitting prompts into a model's context window.
+//!
+//! Token counts are estimated at ~4 characters per token, which is close for English text with
+//! the tokenizers AIR's GGUF models use and errs on the safe side for code.
+
+use crate::models::{ChatMessage, Role};
+
+const CHARS_PER_TOKEN: usize = 4;
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Shortens `text` to about `budget` tokens by cutting from the middle: the start usually holds
+/// instructions and the end the actual question, so both are kept (a quarter and three quarters
+/// of the budget respectively).
+pub fn fit_text(text: &str, budget: usize) -> String {
+    if estimate_tokens(text) <= budget {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let keep = budget * CHARS_PER_TOKEN;
+    let head = keep / 4;
+    let tail = keep - head;
+    let omitted = chars.len() - head - tail;
+    format!(
+        "{}\n[... {} characters omitted to fit the context window ...]\n{}",
+        chars[..head].iter().collect::<String>(),
+        omitted,
+        chars[chars.len() - tail..].iter().collect::<String>()
+    )
+}
+
+/// Sliding window over a conversation: leading system messages and the latest message are
+/// always kept, and the oldest turns in between are dropped until the rest fits `budget`.
+/// If the kept messages alone are still too long, the latest message is shortened.
+pub fn fit_messages(messages: &[ChatMessage], budget: usize) -> Vec<ChatMessage> {
+    let total: usize = messages.iter().map(|m| estimate_tokens(&m.content)).sum();
+    if total <= budget || messages.is_empty() {
+        return messages.to_vec();
+    }
+
+    let system_count = messages.iter().take_while(|m| m.role == Role::System).count().min(messages.len() - 1);
+    let (system, rest) = messages.split_at(system_count);
+    let (history, latest) = rest.split_at(rest.len() - 1);
+    let latest = &latest[0];
+
+    let fixed: usize = system.iter().chain(std::iter::once(latest)).map(|m| estimate_tokens(&m.content)).sum();
+    let mut kept: Vec<&ChatMessage> = Vec::new();
+    let mut used = fixed;
+    for message in history.iter().rev() {
+        let tokens = estimate_tokens(&message.content);
+        if used + tokens > budget {
+            break;
+        }
+        used += tokens;
+        kept.push(message);
+    }
+    kept.reverse();
+
+    let mut fitted: Vec<ChatMessage> = system.to_vec();
+    fitted.extend(kept.into_iter().cloned());
+    let system_tokens: usize = system.iter().map(|m| estimate_tokens(&m.content)).sum();
+    fitted.push(ChatMessage {
+        content: fit_text(&latest.content, budget.saturating_sub(system_tokens).max(budget / 2)),
+        ..latest.clone()
+    });
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: Role, content: &str) -> ChatMessage {
+        ChatMessage::new(role, content)
+    }
+
+    #[test]
+    fn test_fit_text_keeps_head_and_tail() {
+        let text = format!("INSTRUCTIONS {} QUESTION?", "x".repeat(1000));
+        let fitted = fit_text(&text, 50);
+        assert!(fitted.starts_with("INSTRUCTIONS"));
+        assert!(fitted.ends_with("QUESTION?"));
+        assert!(fitted.contains("characters omitted"));
+        assert_eq!(fit_text("short", 50), "short");
+    }
+
+    #[test]
+    fn test_fit_messages_drops_oldest_turns() {
+        let long = "y".repeat(400); // ~100 tokens
+        let messages = vec![
+            message(Role::System, "You are AIR."),
+            message(Role::User, &long),
+            message(Role::Assistant, &long),
+            message(Role::User, &long),
+            message(Role::Assistant, "ok"),
+            message(Role::User, "latest question"),
+        ];
+        let fitted = fit_messages(&messages, 120);
+        assert_eq!(fitted.first().unwrap().content, "You are AIR.");
+        assert_eq!(fitted.last().unwrap().content, "latest question");
+        assert!(fitted.len() < messages.len());
+        assert!(fitted.iter().map(|m| estimate_tokens(&m.content)).sum::<usize>() <= 120);
+
+        assert_eq!(fit_messages(&messages, 10_000).len(), messages.len());
+    }
+}
@@ -31,7 +31,10 @@ fn show_help() {
     println!("🔹 Screenshot & Media:");
     println!("   • screenshot                - Take full screen capture");
     println!("   • screenshot region         - Capture specific screen region");
+    println!("   • screenshot display 2      - Capture a specific monitor");
+    println!("   • screenshot window <title> - Capture a single window");
     println!("   • list screenshots          - Show saved screenshots");
+    println!("   • what's on my screen?      - Capture and describe the screen");
     println!();
     println!("🔹 Voice Commands:");
     println!("   • speak [text]              - Text-to-speech synthesis");
@@ -0,0 +1,275 @@
+//! Large file downloads for `air setup --local`: streamed to disk, resumable, checksummed and
+//! retried against mirrors.
+
+use anyhow::{Result, anyhow};
+use futures::StreamExt;
+use reqwest::{Client, StatusCode};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+// Attempts per URL before moving on to the next mirror; each attempt resumes the last
+const ATTEMPTS_PER_URL: u32 = 3;
+// Files from anywhere else (the mirrors) are only accepted when their hash is known up front
+const TRUSTED_HOST: &str = "https://huggingface.co/";
+const PROGRESS_WIDTH: usize = 30;
+
+/// Hugging Face download URL for `repo`/`file`, plus the same file on a public mirror.
+pub fn huggingface_urls(repo: &str, file: &str) -> Vec<String> {
+    ["https://huggingface.co", "https://hf-mirror.com"].iter()
+        .map(|host| format!("{}/{}/resolve/main/{}", host, repo, file))
+        .collect()
+}
+
+/// Downloads the first of `urls` that succeeds to `dest`. Data goes to a `.part` file that later
+/// attempts resume from, and is only moved into place once its SHA-256 matches `expected_sha256`
+/// or, failing that, the hash Hugging Face publishes for the file. Mirrors are skipped when
+/// neither is known.
+pub async fn download(urls: &[String], dest: &Path, expected_sha256: Option<&str>) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let client = Client::builder()
+        .connect_timeout(Duration::from_secs(30))
+        .build()?;
+    let expected = match expected_sha256 {
+        Some(hash) => Some(hash.trim().to_lowercase()),
+        None => published_sha256(urls).await,
+    };
+
+    let mut last_error = anyhow!("No download URLs given");
+    for url in urls {
+        if expected.is_none() && !url.starts_with(TRUSTED_HOST) {
+            last_error = anyhow!("Not downloading from {} without a known SHA-256", url);
+            eprintln!("⚠️  {}", last_error);
+            continue;
+        }
+        for attempt in 1..=ATTEMPTS_PER_URL {
+            match download_once(&client, url, dest, expected.as_deref()).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    eprintln!("\n⚠️  {} (attempt {}/{}): {}", url, attempt, ATTEMPTS_PER_URL, e);
+                    let checksum_failed = e.to_string().contains("Checksum mismatch");
+                    last_error = e;
+                    // A corrupt file won't get better by retrying the same server
+                    if checksum_failed {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+                }
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// The SHA-256 Hugging Face publishes for an LFS file, as X-Linked-Etag on the redirect to its
+/// storage. Asked with redirects off, so the header can only come from Hugging Face itself.
+async fn published_sha256(urls: &[String]) -> Option<String> {
+    let url = urls.iter().find(|url| url.starts_with(TRUSTED_HOST))?;
+    let client = Client::builder()
+        .connect_timeout(Duration::from_secs(30))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .ok()?;
+    let response = client.head(url).send().await.ok()?;
+    response.headers().get("x-linked-etag")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim_matches('"').to_lowercase())
+        .filter(|v| v.len() == 64 && v.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.part", dest.display()))
+}
+
+async fn download_once(client: &Client, url: &str, dest: &Path, expected_sha256: Option<&str>) -> Result<()> {
+    let part = part_path(dest);
+    let existing = tokio::fs::metadata(&part).await.map(|m| m.len()).unwrap_or(0);
+
+    let mut request = client.get(url);
+    if existing > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+    }
+    let response = request.send().await?;
+    let status = response.status();
+
+    // The partial file is already the whole thing; go straight to verification
+    if status == StatusCode::RANGE_NOT_SATISFIABLE && existing > 0 {
+        return finish(&part, dest, expected_sha256, hash_file(&part, Sha256::new()).await?).await;
+    }
+    if !status.is_success() {
+        return Err(anyhow!("HTTP {}", status));
+    }
+
+    let resuming = status == StatusCode::PARTIAL_CONTENT;
+    let (mut file, mut hasher, offset) = if resuming {
+        eprintln!("↪️  Resuming {} at {:.1} MB", file_name(dest), mb(existing));
+        let hasher = hash_file(&part, Sha256::new()).await?;
+        (tokio::fs::OpenOptions::new().append(true).open(&part).await?, hasher, existing)
+    } else {
+        (tokio::fs::File::create(&part).await?, Sha256::new(), 0)
+    };
+    let total = response.content_length().map(|len| len + offset);
+
+    let mut written = offset;
+    let started = Instant::now();
+    let mut last_report = Instant::now() - Duration::from_secs(1);
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| anyhow!("Download interrupted: {}", e))?;
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+
+        if last_report.elapsed() >= Duration::from_millis(250) {
+            let rate = (written - offset) as f64 / started.elapsed().as_secs_f64().max(0.001);
+            print_progress(dest, written, total, rate);
+            last_report = Instant::now();
+        }
+    }
+    file.flush().await?;
+    drop(file);
+    let rate = (written - offset) as f64 / started.elapsed().as_secs_f64().max(0.001);
+    print_progress(dest, written, total, rate);
+    eprintln!();
+
+    if let Some(total) = total {
+        if written < total {
+            return Err(anyhow!("Connection closed after {:.1} of {:.1} MB", mb(written), mb(total)));
+        }
+    }
+    finish(&part, dest, expected_sha256, hasher).await
+}
+
+async fn finish(part: &Path, dest: &Path, expected: Option<&str>, hasher: Sha256) -> Result<()> {
+    let actual = format!("{:x}", hasher.finalize());
+    match expected {
+        Some(expected) if !expected.trim().eq_ignore_ascii_case(&actual) => {
+            tokio::fs::remove_file(part).await.ok();
+            return Err(anyhow!("Checksum mismatch: expected {}, got {}", expected.trim(), actual));
+        }
+        Some(_) => eprintln!("🔒 SHA-256 verified ({})", &actual[..12]),
+        None => eprintln!("⚠️  No checksum available to verify {}", file_name(dest)),
+    }
+    tokio::fs::rename(part, dest).await?;
+    Ok(())
+}
+
+/// Continues `hasher` over the contents of `path` (the already downloaded part of a file).
+async fn hash_file(path: &Path, mut hasher: Sha256) -> Result<Sha256> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(hasher);
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn print_progress(dest: &Path, written: u64, total: Option<u64>, bytes_per_sec: f64) {
+    let name = file_name(dest);
+    match total {
+        Some(total) if total > 0 => {
+            let fraction = (written as f64 / total as f64).min(1.0);
+            let filled = (fraction * PROGRESS_WIDTH as f64) as usize;
+            let eta = if bytes_per_sec > 0.0 { ((total - written.min(total)) as f64 / bytes_per_sec) as u64 } else { 0 };
+            eprint!("\r⬇️  {} [{}{}] {:>3.0}% {:.1}/{:.1} MB {:.1} MB/s ETA {}:{:02}  ",
+                name, "#".repeat(filled), "-".repeat(PROGRESS_WIDTH - filled), fraction * 100.0,
+                mb(written), mb(total), mb(bytes_per_sec as u64), eta / 60, eta % 60);
+        }
+        _ => eprint!("\r⬇️  {} {:.1} MB {:.1} MB/s  ", name, mb(written), mb(bytes_per_sec as u64)),
+    }
+    let _ = std::io::stderr().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::{HeaderMap, StatusCode as HttpStatus, header};
+    use axum::routing::get;
+    use std::sync::{Arc, Mutex};
+
+    const CONTENT: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    /// Serves `CONTENT` at /file, honouring `Range: bytes=N-`, and records the ranges asked for.
+    async fn serve() -> (String, Arc<Mutex<Vec<String>>>) {
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let seen = ranges.clone();
+        let app = Router::new().route("/file", get(move |headers: HeaderMap| {
+            let seen = seen.clone();
+            async move {
+                let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok()).map(str::to_string);
+                let start = range.as_deref()
+                    .and_then(|r| r.strip_prefix("bytes="))
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+                if let Some(range) = range {
+                    seen.lock().unwrap().push(range);
+                }
+                match start {
+                    Some(start) if start >= CONTENT.len() => (HttpStatus::RANGE_NOT_SATISFIABLE, Vec::new()),
+                    Some(start) => (HttpStatus::PARTIAL_CONTENT, CONTENT[start..].to_vec()),
+                    None => (HttpStatus::OK, CONTENT.to_vec()),
+                }
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.ok() });
+        (format!("http://{}/file", addr), ranges)
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    #[tokio::test]
+    async fn test_resumes_partial_download() {
+        let (url, ranges) = serve().await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("model.gguf");
+        std::fs::write(part_path(&dest), &CONTENT[..10]).unwrap();
+
+        download(&[url], &dest, Some(&sha256(CONTENT))).await.unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), CONTENT);
+        assert!(!part_path(&dest).exists());
+        assert_eq!(*ranges.lock().unwrap(), vec!["bytes=10-".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_discards_file() {
+        let (url, _) = serve().await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("model.gguf");
+
+        let err = download(&[url], &dest, Some(&sha256(b"something else"))).await.unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{}", err);
+        assert!(!dest.exists());
+        assert!(!part_path(&dest).exists());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_needs_known_hash() {
+        let (url, ranges) = serve().await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("model.gguf");
+
+        let err = download(&[url], &dest, None).await.unwrap_err();
+        assert!(err.to_string().contains("without a known SHA-256"), "{}", err);
+        assert!(!dest.exists());
+        assert!(ranges.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+//! Translations of the CLI's messages and the language answers are requested in.
+//!
+//! Messages live in `locales/<code>.toml`, compiled in, and are looked up by dotted key with
+//! `tr("repl.goodbye")` or `tr_args("repl.saved", &[path])`, where each `{}` takes the next
+//! argument. Keys missing from a catalog fall back to English.
+//!
+//! The language is `language` in config.toml (a code such as `es`), else the system locale
+//! (`LC_ALL`, `LC_MESSAGES`, `LANG`), else English.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// (code, English name, catalog)
+const LOCALES: &[(&str, &str, &str)] = &[
+    ("en", "English", include_str!("../../locales/en.toml")),
+    ("es", "Spanish", include_str!("../../locales/es.toml")),
+    ("fr", "French", include_str!("../../locales/fr.toml")),
+    ("de", "German", include_str!("../../locales/de.toml")),
+    ("hi", "Hindi", include_str!("../../locales/hi.toml")),
+];
+
+static LANGUAGE: OnceLock<&'static str> = OnceLock::new();
+static CATALOGS: OnceLock<HashMap<&'static str, HashMap<String, String>>> = OnceLock::new();
+
+/// Sets the language from config.toml's `language`; later calls are ignored. Without a call the
+/// system locale is used.
+pub fn init(language: Option<&str>) {
+    LANGUAGE.get_or_init(|| resolve(language));
+}
+
+/// The active language code.
+pub fn language() -> &'static str {
+    LANGUAGE.get_or_init(|| resolve(None))
+}
+
+/// Languages with a message catalog, as (code, English name).
+pub fn supported() -> impl Iterator<Item = (&'static str, &'static str)> {
+    LOCALES.iter().map(|(code, name, _)| (*code, *name))
+}
+
+/// The English name of `language` (a code or a name), for telling the model what to answer in.
+/// Unknown values are passed through, so any language the model knows works.
+pub fn language_name(language: &str) -> String {
+    let code = normalize(language);
+    LOCALES.iter()
+        .find(|(c, name, _)| *c == code || name.eq_ignore_ascii_case(language.trim()))
+        .map(|(_, name, _)| name.to_string())
+        .unwrap_or_else(|| language.trim().to_string())
+}
+
+/// The message for `key` in the active language.
+pub fn tr(key: &str) -> String {
+    lookup(language(), key)
+}
+
+/// Like `tr`, with each `{}` replaced by the next argument.
+pub fn tr_args(key: &str, args: &[&dyn std::fmt::Display]) -> String {
+    fill(&tr(key), args)
+}
+
+fn lookup(language: &str, key: &str) -> String {
+    let catalogs = CATALOGS.get_or_init(|| {
+        LOCALES.iter().map(|(code, _, text)| (*code, flatten(text))).collect()
+    });
+    catalogs.get(language).and_then(|c| c.get(key))
+        .or_else(|| catalogs["en"].get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+fn fill(template: &str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        out.push_str(&rest[..pos]);
+        match args.next() {
+            Some(arg) => out.push_str(&arg.to_string()),
+            None => out.push_str("{}"),
+        }
+        rest = &rest[pos + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn resolve(configured: Option<&str>) -> &'static str {
+    let candidates = configured.map(String::from).into_iter()
+        .chain(["LC_ALL", "LC_MESSAGES", "LANG"].iter().filter_map(|var| std::env::var(var).ok()));
+    for candidate in candidates {
+        let code = normalize(&candidate);
+        if let Some((code, _, _)) = LOCALES.iter().find(|(c, name, _)| *c == code || name.eq_ignore_ascii_case(candidate.trim())) {
+            return code;
+        }
+    }
+    "en"
+}
+
+/// "es_ES.UTF-8" -> "es"
+fn normalize(locale: &str) -> String {
+    locale.trim().split(['_', '-', '.']).next().unwrap_or_default().to_lowercase()
+}
+
+/// Turns `[repl]\ngoodbye = "..."` into `repl.goodbye`.
+fn flatten(text: &str) -> HashMap<String, String> {
+    fn walk(prefix: &str, value: &toml::Value, out: &mut HashMap<String, String>) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, value) in table {
+                    let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                    walk(&key, value, out);
+                }
+            }
+            toml::Value::String(s) => {
+                out.insert(prefix.to_string(), s.clone());
+            }
+            _ => {}
+        }
+    }
+    let mut out = HashMap::new();
+    if let Ok(value) = text.parse::<toml::Value>() {
+        walk("", &value, &mut out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogs_cover_english_keys() {
+        let english = flatten(LOCALES[0].2);
+        assert!(!english.is_empty());
+        for (code, _, text) in &LOCALES[1..] {
+            let catalog = flatten(text);
+            for key in english.keys() {
+                assert!(catalog.contains_key(key), "{} is missing {}", code, key);
+            }
+            for (key, message) in &catalog {
+                assert_eq!(message.matches("{}").count(), english[key].matches("{}").count(), "{}: {}", code, key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookup_and_names() {
+        assert_eq!(lookup("es", "repl.goodbye"), "\n👋 ¡Adiós! ¡Gracias por usar AIR!");
+        assert_eq!(lookup("xx", "repl.goodbye"), lookup("en", "repl.goodbye"));
+        assert_eq!(lookup("en", "no.such.key"), "no.such.key");
+        assert_eq!(fill("Saved to {} ({})", &[&"a.md", &3]), "Saved to a.md (3)");
+        assert_eq!(normalize("de_DE.UTF-8"), "de");
+        assert_eq!(language_name("fr"), "French");
+        assert_eq!(language_name("Japanese"), "Japanese");
+    }
+}
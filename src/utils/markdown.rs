@@ -0,0 +1,110 @@
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+use termimad::MadSkin;
+
+#[derive(Debug, PartialEq)]
+pub enum Block<'a> {
+    Text(String),
+    Code { lang: &'a str, code: String },
+}
+
+/// Whether responses should be rendered: not with `--plain`, `NO_COLOR`, or when stdout isn't a terminal.
+pub fn should_render(plain: bool) -> bool {
+    !plain && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+}
+
+/// Renders markdown for the terminal: termimad for prose and tables, syntect for fenced code.
+pub fn render(markdown: &str) -> String {
+    let skin = MadSkin::default();
+    let mut out = String::new();
+    for block in split_blocks(markdown) {
+        match block {
+            Block::Text(text) => out.push_str(&skin.term_text(&text).to_string()),
+            Block::Code { lang, code } => {
+                out.push_str(&highlight(lang, &code));
+                // Reset colours so the next block starts clean
+                out.push_str("\x1b[0m\n");
+            }
+        }
+    }
+    out
+}
+
+/// Splits markdown into prose and fenced code blocks. An unclosed fence runs to the end.
+pub fn split_blocks(markdown: &str) -> Vec<Block<'_>> {
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut code: Option<(&str, String)> = None;
+
+    for line in markdown.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        if let Some((lang, body)) = code.take() {
+            if fence.is_some() {
+                blocks.push(Block::Code { lang, code: body });
+            } else {
+                code = Some((lang, body + line + "\n"));
+            }
+        } else if let Some(lang) = fence {
+            if !text.is_empty() {
+                blocks.push(Block::Text(std::mem::take(&mut text)));
+            }
+            code = Some((lang.trim(), String::new()));
+        } else {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+
+    if let Some((lang, body)) = code {
+        blocks.push(Block::Code { lang, code: body });
+    }
+    if !text.is_empty() {
+        blocks.push(Block::Text(text));
+    }
+    blocks
+}
+
+fn highlighting() -> &'static (SyntaxSet, Theme) {
+    static ASSETS: OnceLock<(SyntaxSet, Theme)> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        let theme = themes.themes.remove("base16-ocean.dark").unwrap_or_default();
+        (SyntaxSet::load_defaults_newlines(), theme)
+    })
+}
+
+fn highlight(lang: &str, code: &str) -> String {
+    let (syntaxes, theme) = highlighting();
+    let syntax = syntaxes.find_syntax_by_token(lang)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    let mut out = String::new();
+    for line in LinesWithEndings::from(code) {
+        match highlighter.highlight_line(line, syntaxes) {
+            Ok(ranges) => out.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+            Err(_) => out.push_str(line),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_blocks() {
+        let blocks = split_blocks("Intro\n```rust\nfn main() {}\n```\nOutro\n```\nunclosed");
+        assert_eq!(blocks, vec![
+            Block::Text("Intro\n".to_string()),
+            Block::Code { lang: "rust", code: "fn main() {}\n".to_string() },
+            Block::Text("Outro\n".to_string()),
+            Block::Code { lang: "", code: "unclosed\n".to_string() },
+        ]);
+    }
+}
@@ -1,3 +1,30 @@
-pub mod doc;
 pub mod paths;
+pub mod project;
+pub mod context_window;
+pub mod model_catalog;
+#[cfg(feature = "native")]
+pub mod doc;
+#[cfg(feature = "native")]
 pub mod model_inspector;
+#[cfg(feature = "native")]
+pub mod config_watcher;
+#[cfg(feature = "native")]
+pub mod secrets;
+#[cfg(feature = "native")]
+pub mod multiline;
+#[cfg(feature = "native")]
+pub mod markdown;
+#[cfg(feature = "native")]
+pub mod piped_input;
+#[cfg(feature = "native")]
+pub mod download;
+#[cfg(feature = "native")]
+pub mod chat_template;
+#[cfg(feature = "native")]
+pub mod clipboard;
+#[cfg(feature = "native")]
+pub mod i18n;
+#[cfg(feature = "native")]
+pub mod report;
+#[cfg(feature = "native")]
+pub mod approval;
@@ -0,0 +1,334 @@
+//! Local model registry behind `air models`: a curated catalog of GGUF models known to run well
+//! on laptops, and a `registry.json` in the models directory recording what was pulled from where.
+
+#[cfg(feature = "native")]
+use crate::utils::download;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const REGISTRY_FILE: &str = "registry.json";
+
+/// A recommended model. Sizes are of the GGUF file; `min_ram_gb` leaves room for the context.
+#[derive(Debug, Clone, Copy)]
+pub struct CatalogEntry {
+    pub name: &'static str,
+    pub repo: &'static str,
+    pub file: &'static str,
+    pub size_mb: u64,
+    pub min_ram_gb: u64,
+    pub description: &'static str,
+    /// Repository with a matching tokenizer.json, for models that need one alongside.
+    pub tokenizer_repo: Option<&'static str>,
+}
+
+pub const CATALOG: &[CatalogEntry] = &[
+    CatalogEntry {
+        name: "tinyllama-1.1b-q2",
+        repo: "TheBloke/TinyLlama-1.1B-Chat-v1.0-GGUF",
+        file: "tinyllama-1.1b-chat-v1.0.Q2_K.gguf",
+        size_mb: 483,
+        min_ram_gb: 2,
+        description: "Smallest and fastest; fine for short answers and tool routing",
+        tokenizer_repo: Some("TinyLlama/TinyLlama-1.1B-Chat-v1.0"),
+    },
+    CatalogEntry {
+        name: "tinyllama-1.1b-q4",
+        repo: "TheBloke/TinyLlama-1.1B-Chat-v1.0-GGUF",
+        file: "tinyllama-1.1b-chat-v1.0.Q4_K_M.gguf",
+        size_mb: 669,
+        min_ram_gb: 2,
+        description: "TinyLlama at higher precision; noticeably fewer garbled answers",
+        tokenizer_repo: Some("TinyLlama/TinyLlama-1.1B-Chat-v1.0"),
+    },
+    CatalogEntry {
+        name: "qwen2.5-0.5b-q4",
+        repo: "Qwen/Qwen2.5-0.5B-Instruct-GGUF",
+        file: "qwen2.5-0.5b-instruct-q4_k_m.gguf",
+        size_mb: 491,
+        min_ram_gb: 2,
+        description: "Tiny multilingual model with a 32k context",
+        tokenizer_repo: None,
+    },
+    CatalogEntry {
+        name: "qwen2.5-1.5b-q4",
+        repo: "Qwen/Qwen2.5-1.5B-Instruct-GGUF",
+        file: "qwen2.5-1.5b-instruct-q4_k_m.gguf",
+        size_mb: 1117,
+        min_ram_gb: 4,
+        description: "Good all-rounder for 8 GB machines",
+        tokenizer_repo: None,
+    },
+    CatalogEntry {
+        name: "llama-3.2-3b-q4",
+        repo: "bartowski/Llama-3.2-3B-Instruct-GGUF",
+        file: "Llama-3.2-3B-Instruct-Q4_K_M.gguf",
+        size_mb: 2020,
+        min_ram_gb: 6,
+        description: "Strong general assistant; handles tool calls reliably",
+        tokenizer_repo: None,
+    },
+    CatalogEntry {
+        name: "phi-3-mini-q4",
+        repo: "microsoft/Phi-3-mini-4k-instruct-gguf",
+        file: "Phi-3-mini-4k-instruct-q4.gguf",
+        size_mb: 2393,
+        min_ram_gb: 6,
+        description: "Good at reasoning and code for its size",
+        tokenizer_repo: None,
+    },
+    CatalogEntry {
+        name: "mistral-7b-q4",
+        repo: "TheBloke/Mistral-7B-Instruct-v0.2-GGUF",
+        file: "mistral-7b-instruct-v0.2.Q4_K_M.gguf",
+        size_mb: 4370,
+        min_ram_gb: 8,
+        description: "Best quality in the catalog; needs a GPU or patience",
+        tokenizer_repo: None,
+    },
+];
+
+/// The model `air setup --local` installs and the default `local_model.model_path` points at.
+pub const DEFAULT_MODEL: &str = "tinyllama-1.1b-q2";
+
+/// A model offered in several quantizations, for `air setup --local` to pick from by RAM.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelFamily {
+    pub name: &'static str,
+    pub repo: &'static str,
+    /// File name with `{quant}` standing for the quantization label.
+    pub file_template: &'static str,
+    /// Quantization labels as they appear in file names, from smallest to largest, with file sizes in MB.
+    pub quants: &'static [(&'static str, u64)],
+    pub tokenizer_repo: Option<&'static str>,
+}
+
+pub const FAMILIES: &[ModelFamily] = &[
+    ModelFamily {
+        name: "tinyllama-1.1b",
+        repo: "TheBloke/TinyLlama-1.1B-Chat-v1.0-GGUF",
+        file_template: "tinyllama-1.1b-chat-v1.0.{quant}.gguf",
+        quants: &[("Q2_K", 483), ("Q3_K_M", 551), ("Q4_K_M", 669), ("Q5_K_M", 783), ("Q6_K", 904)],
+        tokenizer_repo: Some("TinyLlama/TinyLlama-1.1B-Chat-v1.0"),
+    },
+    ModelFamily {
+        name: "qwen2.5-1.5b",
+        repo: "Qwen/Qwen2.5-1.5B-Instruct-GGUF",
+        file_template: "qwen2.5-1.5b-instruct-{quant}.gguf",
+        quants: &[("q2_k", 676), ("q3_k_m", 824), ("q4_k_m", 1117), ("q5_k_m", 1285), ("q6_k", 1464)],
+        tokenizer_repo: None,
+    },
+    ModelFamily {
+        name: "llama-3.2-3b",
+        repo: "bartowski/Llama-3.2-3B-Instruct-GGUF",
+        file_template: "Llama-3.2-3B-Instruct-{quant}.gguf",
+        quants: &[("Q3_K_L", 1815), ("Q4_K_M", 2020), ("Q5_K_M", 2322), ("Q6_K", 2643)],
+        tokenizer_repo: None,
+    },
+    ModelFamily {
+        name: "mistral-7b",
+        repo: "TheBloke/Mistral-7B-Instruct-v0.2-GGUF",
+        file_template: "mistral-7b-instruct-v0.2.{quant}.gguf",
+        quants: &[("Q2_K", 3083), ("Q3_K_M", 3519), ("Q4_K_M", 4369), ("Q5_K_M", 5132), ("Q6_K", 5942)],
+        tokenizer_repo: None,
+    },
+];
+
+/// One quantization of a model family.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub family: &'static str,
+    pub quant: &'static str,
+    pub repo: &'static str,
+    pub file: String,
+    pub size_mb: u64,
+    pub tokenizer_repo: Option<&'static str>,
+}
+
+impl Variant {
+    /// Registry name, e.g. `tinyllama-1.1b-q4_k_m`.
+    pub fn name(&self) -> String {
+        format!("{}-{}", self.family, self.quant.to_lowercase())
+    }
+
+    /// RAM needed to run it: the weights plus roughly 20% and half a gigabyte for the
+    /// context cache and runtime.
+    pub fn ram_gb(&self) -> f64 {
+        self.size_mb as f64 * 1.2 / 1024.0 + 0.5
+    }
+}
+
+impl ModelFamily {
+    pub fn variants(&self) -> Vec<Variant> {
+        self.quants.iter().map(|(quant, size_mb)| Variant {
+            family: self.name,
+            quant: *quant,
+            repo: self.repo,
+            file: self.file_template.replace("{quant}", quant),
+            size_mb: *size_mb,
+            tokenizer_repo: self.tokenizer_repo,
+        }).collect()
+    }
+}
+
+/// Every quantization of every family, smallest model first.
+pub fn variants() -> Vec<Variant> {
+    FAMILIES.iter().flat_map(|f| f.variants()).collect()
+}
+
+/// Below 4 bits answers degrade noticeably, so those are only picked when nothing else fits.
+fn is_low_precision(quant: &str) -> bool {
+    let quant = quant.to_uppercase();
+    quant.starts_with("Q2") || quant.starts_with("Q3")
+}
+
+/// The variant to suggest for a machine with `available_gb` of free memory: the largest one
+/// using at most 70% of it, preferring 4-bit or better, and the smallest variant overall when
+/// even that doesn't fit.
+pub fn recommend(variants: &[Variant], available_gb: f64) -> Option<usize> {
+    let fits = |v: &Variant| v.ram_gb() <= available_gb * 0.7;
+    let largest = |good_only: bool| variants.iter().enumerate()
+        .filter(|(_, v)| fits(v) && (!good_only || !is_low_precision(v.quant)))
+        .max_by_key(|(_, v)| v.size_mb)
+        .map(|(i, _)| i);
+    largest(true)
+        .or_else(|| largest(false))
+        .or_else(|| variants.iter().enumerate().min_by_key(|(_, v)| v.size_mb).map(|(i, _)| i))
+}
+
+/// A model pulled with `air models pull` (or `air setup --local`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub name: String,
+    pub repo: String,
+    pub file: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub pulled_at: String,
+}
+
+pub fn find(name: &str) -> Option<&'static CatalogEntry> {
+    CATALOG.iter().find(|entry| entry.name.eq_ignore_ascii_case(name))
+}
+
+pub fn default_entry() -> &'static CatalogEntry {
+    find(DEFAULT_MODEL).expect("DEFAULT_MODEL is in the catalog")
+}
+
+/// Resolves what `air models pull` was given: a catalog name, or `owner/repo/file.gguf`
+/// (files may sit in subfolders of the repository). Returns (name, repo, file).
+pub fn resolve_pull_spec(spec: &str) -> Result<(String, String, String)> {
+    if let Some(entry) = find(spec) {
+        return Ok((entry.name.to_string(), entry.repo.to_string(), entry.file.to_string()));
+    }
+    // family:quant, e.g. mistral-7b:Q5_K_M
+    if let Some((family, quant)) = spec.split_once(':') {
+        if let Some(variant) = variants().into_iter()
+            .find(|v| v.family.eq_ignore_ascii_case(family) && v.quant.eq_ignore_ascii_case(quant)) {
+            return Ok((variant.name(), variant.repo.to_string(), variant.file));
+        }
+    }
+    let parts: Vec<&str> = spec.trim_matches('/').splitn(3, '/').collect();
+    match parts.as_slice() {
+        [owner, repo, file] if !owner.is_empty() && !repo.is_empty() && !file.is_empty() => {
+            let file_name = file.rsplit('/').next().unwrap_or(file);
+            // A catalog model pulled by its full path keeps its catalog name
+            let name = CATALOG.iter()
+                .find(|e| e.repo.eq_ignore_ascii_case(&format!("{}/{}", owner, repo)) && e.file == *file)
+                .map(|e| e.name.to_string())
+                .unwrap_or_else(|| file_name.trim_end_matches(".gguf").to_string());
+            Ok((name, format!("{}/{}", owner, repo), file.to_string()))
+        }
+        _ => Err(anyhow!("'{}' is neither a catalog model, <family>:<quant> nor <owner>/<repo>/<file>.gguf (see 'air models list')", spec)),
+    }
+}
+
+/// Downloads a model into `models_dir` and records it in the registry.
+#[cfg(feature = "native")]
+pub async fn pull(models_dir: &Path, name: &str, repo: &str, file: &str) -> Result<RegistryEntry> {
+    let file_name = file.rsplit('/').next().unwrap_or(file);
+    let path = models_dir.join(file_name);
+    if !path.exists() {
+        download::download(&download::huggingface_urls(repo, file), &path, None).await?;
+    }
+
+    let entry = RegistryEntry {
+        name: name.to_string(),
+        repo: repo.to_string(),
+        file: file.to_string(),
+        size_bytes: std::fs::metadata(&path)?.len(),
+        path,
+        pulled_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    let mut registry = load_registry(models_dir);
+    registry.retain(|e| e.name != entry.name && e.path != entry.path);
+    registry.push(entry.clone());
+    save_registry(models_dir, &registry)?;
+    Ok(entry)
+}
+
+/// Deletes a pulled model (by registry name or file name) and returns its entry.
+pub fn remove(models_dir: &Path, name: &str) -> Result<RegistryEntry> {
+    let mut registry = load_registry(models_dir);
+    let index = registry.iter()
+        .position(|e| e.name.eq_ignore_ascii_case(name) || e.path.file_name().is_some_and(|f| f.to_string_lossy() == name))
+        .ok_or_else(|| anyhow!("No installed model named '{}' (see 'air models list')", name))?;
+    let entry = registry.remove(index);
+    match std::fs::remove_file(&entry.path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(anyhow!("Cannot delete {}: {}", entry.path.display(), e)),
+    }
+    save_registry(models_dir, &registry)?;
+    Ok(entry)
+}
+
+/// Installed models, dropping registry entries whose file has since been deleted by hand.
+pub fn installed(models_dir: &Path) -> Vec<RegistryEntry> {
+    load_registry(models_dir).into_iter().filter(|e| e.path.exists()).collect()
+}
+
+fn load_registry(models_dir: &Path) -> Vec<RegistryEntry> {
+    std::fs::read_to_string(models_dir.join(REGISTRY_FILE)).ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_registry(models_dir: &Path, registry: &[RegistryEntry]) -> Result<()> {
+    std::fs::write(models_dir.join(REGISTRY_FILE), serde_json::to_string_pretty(registry)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_pull_spec() {
+        let (name, repo, file) = resolve_pull_spec("qwen2.5-0.5b-q4").unwrap();
+        assert_eq!((name.as_str(), repo.as_str(), file.as_str()),
+            ("qwen2.5-0.5b-q4", "Qwen/Qwen2.5-0.5B-Instruct-GGUF", "qwen2.5-0.5b-instruct-q4_k_m.gguf"));
+
+        let (name, repo, file) = resolve_pull_spec("someone/Some-GGUF/quants/model.Q5_K_M.gguf").unwrap();
+        assert_eq!((name.as_str(), repo.as_str(), file.as_str()), ("model.Q5_K_M", "someone/Some-GGUF", "quants/model.Q5_K_M.gguf"));
+
+        let (name, _, file) = resolve_pull_spec("mistral-7b:q5_k_m").unwrap();
+        assert_eq!((name.as_str(), file.as_str()), ("mistral-7b-q5_k_m", "mistral-7b-instruct-v0.2.Q5_K_M.gguf"));
+
+        assert!(resolve_pull_spec("not-a-model").is_err());
+        assert!(resolve_pull_spec("owner/repo").is_err());
+    }
+
+    #[test]
+    fn test_recommend() {
+        let all = variants();
+        let pick = |gb: f64| all[recommend(&all, gb).unwrap()].clone();
+        // Plenty of memory: the biggest model at the highest precision
+        assert_eq!(pick(64.0).name(), "mistral-7b-q6_k");
+        // 4 GB free: something 4-bit or better rather than a heavily quantized bigger model
+        let mid = pick(4.0);
+        assert!(!is_low_precision(mid.quant) && mid.ram_gb() <= 2.8);
+        // Nearly nothing free: still suggest the smallest download
+        assert_eq!(pick(0.5).name(), "tinyllama-1.1b-q2_k");
+    }
+}
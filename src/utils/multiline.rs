@@ -0,0 +1,82 @@
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::Helper;
+
+const TRIPLE_QUOTE: &str = "\"\"\"";
+
+/// True while the user is still composing: inside an unclosed `"""` block, or after a line ending in `\`.
+pub fn is_incomplete(input: &str) -> bool {
+    if input.matches(TRIPLE_QUOTE).count() % 2 == 1 {
+        return true;
+    }
+    input.lines().last().is_some_and(|line| line.trim_end().ends_with('\\'))
+}
+
+/// Turns continuation markup into the prompt text: `"""` fences are dropped (their contents are
+/// kept verbatim) and trailing `\` line continuations are removed.
+pub fn normalize(input: &str) -> String {
+    let mut out = Vec::new();
+    for (i, segment) in input.split(TRIPLE_QUOTE).enumerate() {
+        if i % 2 == 1 {
+            // Inside a fence: keep as is, minus the newline right after the opening quotes
+            out.push(segment.strip_prefix('\n').unwrap_or(segment).trim_end_matches('\n').to_string());
+            continue;
+        }
+        let lines: Vec<&str> = segment.lines()
+            .map(|line| line.trim_end().strip_suffix('\\').unwrap_or(line).trim_end())
+            .collect();
+        let text = lines.join("\n");
+        if !text.trim().is_empty() {
+            out.push(text.trim().to_string());
+        }
+    }
+    out.join("\n")
+}
+
+/// rustyline helper that keeps Enter inserting new lines until the input is complete,
+/// so pasted code and logs arrive as one prompt.
+#[derive(Default)]
+pub struct MultilineHelper;
+
+impl Validator for MultilineHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Completer for MultilineHelper {
+    type Candidate = String;
+}
+
+impl Hinter for MultilineHelper {
+    type Hint = String;
+}
+
+impl Highlighter for MultilineHelper {}
+
+impl Helper for MultilineHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_continuations() {
+        assert!(is_incomplete("explain this \\"));
+        assert!(is_incomplete("review:\n\"\"\"\nfn main() {}"));
+        assert!(!is_incomplete("review:\n\"\"\"\nfn main() {}\n\"\"\""));
+        assert!(!is_incomplete("plain question"));
+
+        assert_eq!(normalize("first line \\\nsecond line"), "first line\nsecond line");
+        assert_eq!(
+            normalize("review this:\n\"\"\"\nfn main() {\n    println!(\"hi\");\n}\n\"\"\""),
+            "review this:\nfn main() {\n    println!(\"hi\");\n}"
+        );
+    }
+}
@@ -1,17 +1,65 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
 use anyhow::Result;
 
-/// Returns the application data directory.
-/// Uses `dirs::data_dir()` + "air" (e.g., %APPDATA%/air or ~/.local/share/air).
+// Files that belong in the config directory rather than the data directory
+const CONFIG_ENTRIES: &[&str] = &["config.toml", ".env", "profiles"];
+
+static MIGRATE: Once = Once::new();
+
+/// Returns the application data directory (databases, knowledge base, models, logs).
+/// Follows platform conventions via `dirs::data_dir()`:
+/// `$XDG_DATA_HOME/air` (default ~/.local/share/air) on Linux,
+/// ~/Library/Application Support/air on macOS and %APPDATA%\air on Windows.
 /// Creates the directory if it doesn't exist.
 pub fn get_air_data_dir() -> Result<PathBuf> {
-    let app_data = dirs::data_dir()
-        .map(|p| p.to_string_lossy().to_string())
-        .or_else(|| std::env::var("APPDATA").ok())
-        .or_else(|| std::env::var("LOCALAPPDATA").ok())
-        .unwrap_or_else(|| std::env::temp_dir().to_string_lossy().to_string());
+    ensure_dir(data_dir_path())
+}
+
+/// Returns the directory holding config.toml, profiles/ and .env.
+/// This is `$XDG_CONFIG_HOME/air` (default ~/.config/air) on Linux and the data directory elsewhere.
+pub fn get_air_config_dir() -> Result<PathBuf> {
+    ensure_dir(config_dir_path())
+}
+
+/// Returns the directory for the memory database and knowledge index: the project's `.air/data`
+/// inside an `air init` project, otherwise the data directory.
+pub fn get_air_memory_dir() -> Result<PathBuf> {
+    match crate::utils::project::Project::detect() {
+        Some(project) => ensure_dir(project.data_dir()),
+        None => get_air_data_dir(),
+    }
+}
+
+/// Returns the directory `air setup --local` downloads models into.
+pub fn get_air_models_dir() -> Result<PathBuf> {
+    ensure_dir(models_dir_path())
+}
+
+/// Like `get_air_models_dir`, but without touching the filesystem (for defaults).
+pub fn models_dir_path() -> PathBuf {
+    data_dir_path().join("models")
+}
+
+fn data_dir_path() -> PathBuf {
+    dirs::data_dir()
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("air")
+}
 
-    let path = PathBuf::from(app_data).join("air");
+fn config_dir_path() -> PathBuf {
+    if cfg!(target_os = "linux") {
+        if let Some(config) = dirs::config_dir() {
+            return config.join("air");
+        }
+    }
+    data_dir_path()
+}
+
+fn ensure_dir(path: PathBuf) -> Result<PathBuf> {
+    MIGRATE.call_once(migrate_legacy_layout);
 
     if !path.exists() {
         std::fs::create_dir_all(&path)?;
@@ -19,3 +67,50 @@ pub fn get_air_data_dir() -> Result<PathBuf> {
 
     Ok(path)
 }
+
+/// Moves data from older layouts into the current directories, never overwriting anything:
+/// the `~/.air` folder used by early versions, and config files that used to live in the
+/// data directory on Linux.
+fn migrate_legacy_layout() {
+    let data_dir = data_dir_path();
+    let config_dir = config_dir_path();
+
+    if let Some(legacy) = dirs::home_dir().map(|home| home.join(".air")) {
+        if legacy.is_dir() && legacy != data_dir {
+            if let Ok(entries) = std::fs::read_dir(&legacy) {
+                for entry in entries.flatten() {
+                    let name = entry.file_name();
+                    let target_dir = if CONFIG_ENTRIES.iter().any(|c| name == *c) { &config_dir } else { &data_dir };
+                    move_entry(&entry.path(), &target_dir.join(&name));
+                }
+            }
+            // Only removed when everything moved; otherwise it stays for the user to sort out
+            std::fs::remove_dir(&legacy).ok();
+        }
+    }
+
+    if config_dir != data_dir {
+        for name in CONFIG_ENTRIES {
+            let old = data_dir.join(name);
+            if old.exists() {
+                move_entry(&old, &config_dir.join(name));
+            }
+        }
+    }
+}
+
+fn move_entry(from: &Path, to: &Path) {
+    if to.exists() {
+        tracing::warn!("Not migrating {}: {} already exists", from.display(), to.display());
+        return;
+    }
+    if let Some(parent) = to.parent() {
+        if std::fs::create_dir_all(parent).is_err() {
+            return;
+        }
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => tracing::info!("Moved {} to {}", from.display(), to.display()),
+        Err(e) => tracing::warn!("Could not move {} to {}: {}", from.display(), to.display(), e),
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use std::io::{IsTerminal, Read};
+use crate::tools::command::truncate_output;
+
+/// Most characters of piped input attached to a prompt; longer input keeps its head and tail,
+/// since logs usually start with context and end with the error.
+pub const MAX_PIPED_CHARS: usize = 60_000;
+
+/// Most bytes read from stdin; the rest of a larger input is dropped before truncation to
+/// `MAX_PIPED_CHARS`, so `air` doesn't buffer an endless stream.
+pub const MAX_PIPED_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Reads stdin when it's a pipe or file (`cat error.log | air ...`). Returns `None` for a terminal or empty input.
+pub fn read_piped_stdin() -> Result<Option<String>> {
+    let stdin = std::io::stdin();
+    if !is_piped(&stdin) {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    stdin.lock().take(MAX_PIPED_BYTES + 1).read_to_end(&mut bytes)?;
+    let truncated = bytes.len() as u64 > MAX_PIPED_BYTES;
+    bytes.truncate(MAX_PIPED_BYTES as usize);
+    let mut text = String::from_utf8_lossy(&bytes).into_owned();
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    if truncated {
+        text.push_str(&format!("\n[input cut off after {} MB]", MAX_PIPED_BYTES / (1024 * 1024)));
+    }
+    Ok(Some(text))
+}
+
+/// Only pipes and regular files are read. Other handles a launcher may leave open on stdin
+/// (sockets from IDEs and Node's child_process, character devices) can stay silent forever.
+#[cfg(unix)]
+fn is_piped(stdin: &std::io::Stdin) -> bool {
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::FileTypeExt;
+    if stdin.is_terminal() {
+        return false;
+    }
+    let Ok(fd) = stdin.as_fd().try_clone_to_owned() else { return false };
+    match std::fs::File::from(fd).metadata() {
+        Ok(metadata) => metadata.file_type().is_fifo() || metadata.is_file(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_piped(stdin: &std::io::Stdin) -> bool {
+    !stdin.is_terminal()
+}
+
+/// Combines the command-line prompt with piped input. Without a prompt the input is the prompt.
+pub fn build_prompt(prompt: Option<&str>, input: &str, max_chars: usize) -> String {
+    let input = truncate_output(input.trim_end(), max_chars);
+    match prompt {
+        Some(prompt) => format!("{}\n\nInput (piped from stdin):\n```\n{}\n```", prompt, input),
+        None => input,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_prompt() {
+        assert_eq!(build_prompt(None, "just this\n", 100), "just this");
+
+        let prompt = build_prompt(Some("what's wrong here"), &"x".repeat(50), 20);
+        assert!(prompt.starts_with("what's wrong here\n\nInput (piped from stdin):\n```\n"));
+        assert!(prompt.contains("[30 characters truncated]"));
+    }
+}
@@ -0,0 +1,283 @@
+//! Diagnostic reports for bug reports. A panic or a fatal error writes one to the data directory's
+//! `reports/` folder: versions, the failure with its backtrace, the config with secrets
+//! redacted, and the tail of `logs/air.log`. `air report` writes one on demand.
+
+use crate::config::Config;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// air.log is moved to air.log.old when it grows past this at start-up.
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+const LOG_TAIL_LINES: usize = 200;
+/// Older reports are deleted when a new one is written.
+const KEEP_REPORTS: usize = 5;
+const ISSUES_URL: &str = "https://github.com/chintu4/air/issues/new";
+const REDACTED: &str = "[redacted]";
+
+/// What went wrong, kept in `last_failure.json` so `air report` can include it later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    /// "panic" or "error".
+    pub kind: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: Option<String>,
+    pub command: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Failure {
+    fn new(kind: &str, message: String, location: Option<String>, backtrace: Option<String>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            message,
+            location,
+            backtrace,
+            command: std::env::args().collect::<Vec<_>>().join(" "),
+            at: Utc::now(),
+        }
+    }
+}
+
+pub fn log_path() -> Result<PathBuf> {
+    Ok(crate::utils::paths::get_air_data_dir()?.join("logs").join("air.log"))
+}
+
+/// The log file for this run, appended to.
+pub fn open_log_file() -> Result<File> {
+    let path = log_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if std::fs::metadata(&path).map_or(false, |m| m.len() > MAX_LOG_BYTES) {
+        std::fs::rename(&path, path.with_extension("log.old"))?;
+    }
+    Ok(OpenOptions::new().create(true).append(true).open(&path)?)
+}
+
+/// Writes a report for any panic, after the usual panic message.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => info.payload().downcast_ref::<String>().cloned().unwrap_or_else(|| "unknown panic".to_string()),
+        };
+        let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+        record(Failure::new("panic", message, info.location().map(|l| l.to_string()), Some(backtrace)));
+    }));
+}
+
+/// Writes a report for an error that ended the program.
+pub fn record_error(error: &anyhow::Error) {
+    // `{:?}` includes the cause chain and, with RUST_BACKTRACE=1, the backtrace
+    record(Failure::new("error", format!("{:#}", error), None, Some(format!("{:?}", error))));
+}
+
+fn record(failure: Failure) {
+    if let Ok(path) = failure_path() {
+        if let Ok(json) = serde_json::to_string_pretty(&failure) {
+            std::fs::write(path, json).ok();
+        }
+    }
+    match write_report(Some(&failure)) {
+        Ok(path) => eprintln!("{}", instructions(&path)),
+        Err(e) => eprintln!("Could not write a diagnostic report: {}", e),
+    }
+}
+
+/// How to hand a report over, for printing after it is written.
+pub fn instructions(path: &std::path::Path) -> String {
+    format!(
+        "📝 Diagnostic report saved to {}\n   To report the problem, open an issue at {} and attach it (check it for anything private first).",
+        path.display(), ISSUES_URL
+    )
+}
+
+fn failure_path() -> Result<PathBuf> {
+    Ok(crate::utils::paths::get_air_data_dir()?.join("last_failure.json"))
+}
+
+/// The failure recorded by the last panic or fatal error, if any.
+pub fn last_failure() -> Option<Failure> {
+    let text = std::fs::read_to_string(failure_path().ok()?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Writes a report to the data directory's `reports/` folder and returns its path.
+pub fn write_report(failure: Option<&Failure>) -> Result<PathBuf> {
+    let config = match Config::load() {
+        Ok(config) => redacted_config(&config),
+        Err(e) => format!("# Config failed to load: {}", e),
+    };
+    let log = log_path().ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|text| tail(&text, LOG_TAIL_LINES))
+        .unwrap_or_else(|| "(no log file)".to_string());
+
+    let dir = crate::utils::paths::get_air_data_dir()?.join("reports");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("report-{}.md", Utc::now().format("%Y%m%d-%H%M%S")));
+    std::fs::write(&path, render(failure, &config, &log))?;
+    prune_reports(&dir);
+    Ok(path)
+}
+
+fn prune_reports(dir: &std::path::Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut reports: Vec<PathBuf> = entries.flatten()
+        .map(|e| e.path())
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("report-")))
+        .collect();
+    reports.sort();
+    let excess = reports.len().saturating_sub(KEEP_REPORTS);
+    for old in &reports[..excess] {
+        std::fs::remove_file(old).ok();
+    }
+}
+
+/// Command lines, messages and logs can carry keys too (`air config set ... api_key sk-...`,
+/// a logged request header), so those are masked on the way into the report.
+fn render(failure: Option<&Failure>, config: &str, log: &str) -> String {
+    let mut report = format!(
+        "# AIR diagnostic report\n\nGenerated {}\n\n## Versions\n\n- air {}\n- {} ({})\n",
+        Utc::now().to_rfc3339(), env!("CARGO_PKG_VERSION"), std::env::consts::OS, std::env::consts::ARCH
+    );
+    report.push_str("\n## Failure\n\n");
+    match failure {
+        Some(failure) => {
+            report.push_str(&format!("{} at {}: {}\n\n", failure.kind, failure.at.to_rfc3339(), redact_text(&failure.message)));
+            report.push_str(&format!("Command: `{}`\n", redact_command(&failure.command)));
+            if let Some(location) = &failure.location {
+                report.push_str(&format!("Location: {}\n", location));
+            }
+            if let Some(backtrace) = &failure.backtrace {
+                report.push_str(&format!("\n```\n{}\n```\n", redact_text(backtrace.trim_end())));
+            }
+        }
+        None => report.push_str("None recorded\n"),
+    }
+    report.push_str(&format!("\n## Configuration (secrets redacted)\n\n```toml\n{}\n```\n", config.trim_end()));
+    report.push_str(&format!("\n## Log (last {} lines)\n\n```\n{}\n```\n", LOG_TAIL_LINES, redact_text(log.trim_end())));
+    report
+}
+
+fn redacted_config(config: &Config) -> String {
+    let Ok(mut value) = toml::Value::try_from(config) else {
+        return "# Config could not be serialized".to_string();
+    };
+    redact(&mut value);
+    toml::to_string_pretty(&value).unwrap_or_default()
+}
+
+/// Blanks every non-empty string under a key that looks like a credential.
+fn redact(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                match value {
+                    toml::Value::String(s) if is_secret_key(key) && !s.is_empty() => *s = REDACTED.to_string(),
+                    other => redact(other),
+                }
+            }
+        }
+        toml::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key.ends_with("_key") || key.ends_with("_token") || key.contains("secret") || key.contains("password")
+}
+
+/// Masks the argument after one named like a credential (`--api-key X`, `... github.access_token X`),
+/// plus anything `redact_text` catches.
+fn redact_command(command: &str) -> String {
+    let mut mask_next = false;
+    command.split(' ').map(|arg| {
+        let masked = if mask_next && !arg.is_empty() { REDACTED.to_string() } else { redact_text(arg) };
+        let name = arg.trim_start_matches('-').rsplit('.').next().unwrap_or_default().replace('-', "_");
+        mask_next = !arg.contains('=') && is_secret_key(&name);
+        masked
+    }).collect::<Vec<_>>().join(" ")
+}
+
+/// Masks `key=value`/`key: value` pairs whose key looks like a credential, bearer tokens and
+/// strings shaped like well-known API keys.
+fn redact_text(text: &str) -> String {
+    static PATTERNS: OnceLock<Vec<regex::Regex>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| [
+        r#"(?i)([\w-]*(?:_key|_token|api-key|secret|password)["']?\s*[=:]\s*["']?)[^\s"',;&}]+"#,
+        r"(?i)(bearer\s+)[\w.~+/=-]+",
+        r"()\b(?:sk-|ghp_|gho_|ghs_|github_pat_|xox[abp]-|AIza)[\w-]{8,}",
+    ].iter().map(|pattern| regex::Regex::new(pattern).unwrap()).collect());
+    let mut text = text.to_string();
+    for pattern in patterns {
+        text = pattern.replace_all(&text, format!("${{1}}{}", REDACTED)).into_owned();
+    }
+    text
+}
+
+fn tail(text: &str, lines: usize) -> String {
+    let all: Vec<&str> = text.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let mut value: toml::Value = toml::from_str(r#"
+            [server]
+            api_key = "hunter2"
+            port = 8787
+            [[cloud_providers]]
+            name = "openai"
+            api_key = "sk-123"
+            max_tokens = 1000
+            [profile.work.github]
+            access_token = "ghp_abc"
+        "#).unwrap();
+        redact(&mut value);
+        assert_eq!(value["server"]["api_key"].as_str(), Some(REDACTED));
+        assert_eq!(value["server"]["port"].as_integer(), Some(8787));
+        assert_eq!(value["cloud_providers"][0]["api_key"].as_str(), Some(REDACTED));
+        assert_eq!(value["cloud_providers"][0]["name"].as_str(), Some("openai"));
+        assert_eq!(value["profile"]["work"]["github"]["access_token"].as_str(), Some(REDACTED));
+    }
+
+    #[test]
+    fn test_redact_text_and_command() {
+        let log = "INFO request api_key=sk-abcdef123456 model=gpt\nDEBUG Authorization: Bearer eyJhbGciOi.xyz\n\"access_token\": \"ghp_1234567890abcdef\"";
+        let redacted = redact_text(log);
+        assert!(!redacted.contains("sk-abcdef123456"), "{}", redacted);
+        assert!(!redacted.contains("eyJhbGciOi"), "{}", redacted);
+        assert!(!redacted.contains("ghp_1234567890abcdef"), "{}", redacted);
+        assert!(redacted.contains("model=gpt"));
+        assert!(redacted.contains("Bearer [redacted]"));
+
+        assert_eq!(redact_command("air config set cloud_providers.0.api_key hunter2"),
+            "air config set cloud_providers.0.api_key [redacted]");
+        assert_eq!(redact_command("air serve --api-key hunter2 --port 80"), "air serve --api-key [redacted] --port 80");
+        assert_eq!(redact_command("air serve --api-key=hunter2"), "air serve --api-key=[redacted]");
+        assert_eq!(redact_command("air --mode local what time is it"), "air --mode local what time is it");
+    }
+
+    #[test]
+    fn test_render() {
+        let failure = Failure::new("panic", "boom".to_string(), Some("src/main.rs:1:1".to_string()), None);
+        let report = render(Some(&failure), "[server]\nport = 1", &tail("a\nb\nc", 2));
+        assert!(report.contains("panic at "));
+        assert!(report.contains("Location: src/main.rs:1:1"));
+        assert!(report.contains("```\nb\nc\n```"));
+        assert!(render(None, "", "").contains("None recorded"));
+    }
+}
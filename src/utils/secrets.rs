@@ -0,0 +1,124 @@
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+const SERVICE: &str = "air";
+
+/// Line `air login --export-env` leaves in `.env` so later runs don't move the keys back
+/// into the keyring.
+const PLAINTEXT_MARKER: &str = "# air: keys kept here by `air login --export-env`";
+
+/// Environment variables holding provider API keys, as read by `Config::load`.
+pub const API_KEY_VARS: &[&str] = &["OPENAI_API_KEY", "ANTHROPIC_API_KEY", "GEMINI_API_KEY", "OPEN_ROUTER", "AZURE_OPENAI_API_KEY"];
+
+fn entry(name: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(SERVICE, name).map_err(|e| anyhow!("OS keyring unavailable: {}", e))
+}
+
+/// Saves an API key in the OS keyring (Keychain, Credential Manager or Secret Service).
+pub fn store_api_key(name: &str, value: &str) -> Result<()> {
+    entry(name)?.set_password(value)
+        .map_err(|e| anyhow!("Failed to store {} in the OS keyring: {}", name, e))
+}
+
+pub fn get_api_key(name: &str) -> Option<String> {
+    match entry(name).and_then(|e| e.get_password().map_err(|e| anyhow!(e))) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            debug!("No keyring entry for {}: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Fills in API key variables that aren't already set from the keyring, so explicit
+/// environment variables and `.env` entries still take precedence.
+pub fn load_into_env() {
+    for name in API_KEY_VARS {
+        if std::env::var(name).is_ok() {
+            continue;
+        }
+        if let Some(value) = get_api_key(name) {
+            std::env::set_var(name, value);
+        }
+    }
+}
+
+/// Moves API keys from a plaintext `.env` file into the keyring, removing each line only after
+/// the key reads back from the keyring. Returns the names migrated. Files the user exported to
+/// with `air login --export-env` are left alone.
+pub fn migrate_env_file(env_path: &Path) -> Result<Vec<String>> {
+    if !env_path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(env_path)?;
+    if content.lines().any(|line| line == PLAINTEXT_MARKER) {
+        return Ok(Vec::new());
+    }
+    let mut migrated = Vec::new();
+    let mut kept = Vec::new();
+
+    for line in content.lines() {
+        let parsed = line.split_once('=')
+            .map(|(k, v)| (k.trim(), v.trim().trim_matches('"')))
+            .filter(|(k, v)| API_KEY_VARS.contains(k) && !v.is_empty());
+
+        match parsed {
+            Some((name, value)) => match store_api_key(name, value) {
+                Ok(()) if get_api_key(name).as_deref() == Some(value) => migrated.push(name.to_string()),
+                Ok(()) => {
+                    warn!("{} did not read back from the keyring; leaving it in .env", name);
+                    kept.push(line.to_string());
+                }
+                Err(e) => {
+                    // Keyring unusable (e.g. no Secret Service on a headless box): keep the file as is
+                    debug!("Skipping .env migration: {}", e);
+                    return Ok(Vec::new());
+                }
+            },
+            None => kept.push(line.to_string()),
+        }
+    }
+
+    if !migrated.is_empty() {
+        write_lines(env_path, &kept)?;
+        info!("🔐 Moved {} from .env into the OS keyring", migrated.join(", "));
+    }
+    Ok(migrated)
+}
+
+/// Marks `.env` as the chosen home for keys (`keep = true`) or hands them back to the keyring.
+pub fn set_plaintext_choice(env_path: &Path, keep: bool) -> Result<()> {
+    let content = if env_path.exists() { std::fs::read_to_string(env_path)? } else { String::new() };
+    let mut lines: Vec<String> = content.lines()
+        .filter(|line| *line != PLAINTEXT_MARKER)
+        .map(|line| line.to_string())
+        .collect();
+    if keep {
+        lines.insert(0, PLAINTEXT_MARKER.to_string());
+    } else if !env_path.exists() {
+        return Ok(());
+    }
+    write_lines(env_path, &lines)
+}
+
+/// Sets `name=value` in a `.env` file, replacing an existing entry.
+pub fn write_env_var(env_path: &Path, name: &str, value: &str) -> Result<()> {
+    let content = if env_path.exists() { std::fs::read_to_string(env_path)? } else { String::new() };
+    let prefix = format!("{}=", name);
+    let mut lines: Vec<String> = content.lines()
+        .filter(|line| !line.starts_with(&prefix))
+        .map(|line| line.to_string())
+        .collect();
+    lines.push(format!("{}={}", name, value));
+    write_lines(env_path, &lines)
+}
+
+fn write_lines(path: &Path, lines: &[String]) -> Result<()> {
+    let mut content = lines.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
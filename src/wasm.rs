@@ -0,0 +1,40 @@
+//! JavaScript bindings for wasm32 builds (built as a cdylib without the default features, see the README).
+//!
+//! ```js
+//! const agent = new AirAgent(configToml);
+//! const answer = await agent.query("What is 2+2?");
+//! ```
+
+use crate::edge::EdgeAgent;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(js_name = AirAgent)]
+pub struct WasmAgent {
+    inner: Rc<EdgeAgent>,
+}
+
+#[wasm_bindgen(js_class = AirAgent)]
+impl WasmAgent {
+    /// Creates an agent from config.toml text; cloud providers need their `api_key` set in it.
+    #[wasm_bindgen(constructor)]
+    pub fn new(config_toml: &str) -> Result<WasmAgent, JsError> {
+        let agent = EdgeAgent::from_toml(config_toml).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Self { inner: Rc::new(agent) })
+    }
+
+    /// Resolves to the answer text, or rejects with the last provider error.
+    pub fn query(&self, prompt: String) -> js_sys::Promise {
+        let agent = self.inner.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            agent.query(&prompt).await
+                .map(|response| JsValue::from_str(&response.content))
+                .map_err(|e| JsError::new(&e.to_string()).into())
+        })
+    }
+
+    #[wasm_bindgen(js_name = clearHistory)]
+    pub fn clear_history(&self) {
+        self.inner.clear_history();
+    }
+}
@@ -0,0 +1,390 @@
+//! Declarative workflows (`air workflow run <file>`): YAML or TOML files listing steps that
+//! prompt the model, call tools directly, run side by side, or run only when an earlier output
+//! matches, so repeatable pipelines need no Rust.
+//!
+//! ```yaml
+//! description: Summarize recent commits
+//! inputs:
+//!   count: "20"
+//! steps:
+//!   - id: log
+//!     tool: command
+//!     function: execute_safe
+//!     args: { command: "git log --oneline -{{count}}" }
+//!   - id: summary
+//!     prompt: "Summarize these commits for a changelog:\n{{log}}"
+//!   - id: extras
+//!     parallel:
+//!       - id: risks
+//!         prompt: "List risky changes in: {{summary}}"
+//!       - id: tweet
+//!         prompt: "Write a one-line announcement of: {{summary}}"
+//!   - id: warn
+//!     when: { var: risks, not_contains: "none" }
+//!     prompt: "Write a short warning for testers about: {{risks}}"
+//! output: "{{summary}}"
+//! ```
+//!
+//! `{{name}}` expands to an input or the output of an earlier step. Workflows are looked up by
+//! path, or by name in the config directory's `workflows/` folder.
+
+use crate::agent::{AIAgent, QueryMode};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::info;
+
+const EXTENSIONS: &[&str] = &["yaml", "yml", "toml"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Default values for variables; `--set name=value` overrides them.
+    #[serde(default)]
+    pub inputs: BTreeMap<String, String>,
+    pub steps: Vec<Step>,
+    /// Template for the final result; defaults to the last step's output.
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+/// One step: exactly one of `prompt`, `tool` or `parallel`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    /// Name its output is available under.
+    pub id: String,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Let the model call tools while answering `prompt`.
+    #[serde(default)]
+    pub use_tools: bool,
+    /// Query mode for `prompt`; unset uses the configured default.
+    #[serde(default)]
+    pub mode: Option<QueryMode>,
+    /// Call this tool directly, without a model.
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub function: Option<String>,
+    /// Tool arguments; string values are templates.
+    #[serde(default)]
+    pub args: Option<Value>,
+    /// Steps run concurrently; each one's output is available under its own id.
+    #[serde(default)]
+    pub parallel: Vec<Step>,
+    /// Skip the step unless this holds.
+    #[serde(default)]
+    pub when: Option<Condition>,
+}
+
+/// Checks a variable; every given test must pass. Comparisons ignore case and surrounding space.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Condition {
+    pub var: String,
+    #[serde(default)]
+    pub equals: Option<String>,
+    #[serde(default)]
+    pub contains: Option<String>,
+    #[serde(default)]
+    pub not_contains: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepRun {
+    pub id: String,
+    pub skipped: bool,
+    pub output: String,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkflowRun {
+    pub workflow: String,
+    pub steps: Vec<StepRun>,
+    pub output: String,
+}
+
+impl Workflow {
+    /// Loads a workflow file (.yaml, .yml or .toml), or a named workflow from the config directory.
+    pub fn load(name_or_path: &str) -> Result<Self> {
+        let path = resolve(name_or_path)?;
+        let text = std::fs::read_to_string(&path)?;
+        let mut workflow = Self::parse(&text, &path)?;
+        if workflow.name.is_empty() {
+            workflow.name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("workflow").to_string();
+        }
+        Ok(workflow)
+    }
+
+    /// Parses `text` as TOML when `path` ends in .toml, YAML otherwise.
+    pub fn parse(text: &str, path: &Path) -> Result<Self> {
+        let workflow: Self = if path.extension().is_some_and(|e| e == "toml") {
+            toml::from_str(text).map_err(|e| anyhow!("Invalid workflow {}: {}", path.display(), e))?
+        } else {
+            serde_yaml::from_str(text).map_err(|e| anyhow!("Invalid workflow {}: {}", path.display(), e))?
+        };
+        workflow.validate()?;
+        Ok(workflow)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.steps.is_empty() {
+            return Err(anyhow!("Workflow has no steps"));
+        }
+        let mut ids: Vec<&str> = Vec::new();
+        for step in &self.steps {
+            step.validate(false)?;
+            for id in std::iter::once(step.id.as_str()).chain(step.parallel.iter().map(|s| s.id.as_str())) {
+                if ids.contains(&id) {
+                    return Err(anyhow!("Step id '{}' is used twice", id));
+                }
+                ids.push(id);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Step {
+    fn validate(&self, nested: bool) -> Result<()> {
+        let kinds = [self.prompt.is_some(), self.tool.is_some(), !self.parallel.is_empty()];
+        if kinds.iter().filter(|k| **k).count() != 1 {
+            return Err(anyhow!("Step '{}' needs exactly one of prompt, tool or parallel", self.id));
+        }
+        if self.tool.is_some() && self.function.is_none() {
+            return Err(anyhow!("Step '{}' calls a tool but has no function", self.id));
+        }
+        if nested && !self.parallel.is_empty() {
+            return Err(anyhow!("Step '{}': parallel steps can't be nested", self.id));
+        }
+        self.parallel.iter().try_for_each(|step| step.validate(true))
+    }
+}
+
+impl Condition {
+    fn holds(&self, vars: &BTreeMap<String, String>) -> Result<bool> {
+        let value = vars.get(&self.var)
+            .ok_or_else(|| anyhow!("Condition refers to unknown variable '{}'", self.var))?
+            .trim()
+            .to_lowercase();
+        let norm = |s: &String| s.trim().to_lowercase();
+        Ok(self.equals.as_ref().map_or(true, |e| value == norm(e))
+            && self.contains.as_ref().map_or(true, |c| value.contains(&norm(c)))
+            && self.not_contains.as_ref().map_or(true, |c| !value.contains(&norm(c))))
+    }
+}
+
+/// Runs workflows on an agent.
+pub struct WorkflowEngine {
+    agent: AIAgent,
+}
+
+impl WorkflowEngine {
+    pub fn new(agent: AIAgent) -> Self {
+        Self { agent }
+    }
+
+    /// Runs every step in order. `inputs` override the workflow's input defaults.
+    pub async fn run(&self, workflow: &Workflow, inputs: BTreeMap<String, String>) -> Result<WorkflowRun> {
+        let mut vars = workflow.inputs.clone();
+        vars.extend(inputs);
+        let mut runs = Vec::new();
+
+        for step in &workflow.steps {
+            if !step.parallel.is_empty() {
+                if !self.should_run(step, &vars)? {
+                    for child in &step.parallel {
+                        vars.insert(child.id.clone(), String::new());
+                        runs.push(StepRun { id: child.id.clone(), skipped: true, output: String::new(), elapsed_ms: 0 });
+                    }
+                    continue;
+                }
+                let children = futures::future::join_all(step.parallel.iter().map(|child| self.run_step(child, &vars))).await;
+                for run in children {
+                    let run = run?;
+                    vars.insert(run.id.clone(), run.output.clone());
+                    runs.push(run);
+                }
+            } else {
+                let run = self.run_step(step, &vars).await?;
+                vars.insert(run.id.clone(), run.output.clone());
+                runs.push(run);
+            }
+        }
+
+        let output = match &workflow.output {
+            Some(template) => render(template, &vars)?,
+            None => runs.iter().rev().find(|r| !r.skipped).map(|r| r.output.clone()).unwrap_or_default(),
+        };
+        Ok(WorkflowRun { workflow: workflow.name.clone(), steps: runs, output })
+    }
+
+    fn should_run(&self, step: &Step, vars: &BTreeMap<String, String>) -> Result<bool> {
+        match &step.when {
+            Some(condition) => condition.holds(vars),
+            None => Ok(true),
+        }
+    }
+
+    async fn run_step(&self, step: &Step, vars: &BTreeMap<String, String>) -> Result<StepRun> {
+        if !self.should_run(step, vars)? {
+            info!("⏭️  Skipping step {}", step.id);
+            return Ok(StepRun { id: step.id.clone(), skipped: true, output: String::new(), elapsed_ms: 0 });
+        }
+        info!("▶️  Running step {}", step.id);
+        let start = Instant::now();
+
+        let output = if let Some(template) = &step.prompt {
+            let prompt = render(template, vars)?;
+            let mode = step.mode.clone().unwrap_or_else(|| self.agent.config().default_mode.clone());
+            let response = if step.use_tools {
+                self.agent.query_with_tools_in_mode(&prompt, &mode).await
+            } else {
+                self.agent.query_in_mode(&prompt, &mode).await
+            };
+            response.map_err(|e| anyhow!("Step '{}' failed: {}", step.id, e))?.content
+        } else {
+            let tool = step.tool.as_deref().unwrap_or_default();
+            let function = step.function.as_deref().unwrap_or_default();
+            let args = render_args(step.args.clone().unwrap_or(Value::Object(Default::default())), vars)?;
+            let result = self.agent.execute_tool(tool, function, args).await
+                .map_err(|e| anyhow!("Step '{}' failed: {}", step.id, e))?;
+            if !result.success {
+                return Err(anyhow!("Step '{}' failed: {}", step.id, result.result));
+            }
+            match result.result {
+                Value::String(text) => text,
+                other => serde_json::to_string_pretty(&other)?,
+            }
+        };
+
+        Ok(StepRun { id: step.id.clone(), skipped: false, output, elapsed_ms: start.elapsed().as_millis() as u64 })
+    }
+}
+
+/// Expands `{{name}}` placeholders; unknown names are an error rather than silently blank.
+pub fn render(template: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").ok_or_else(|| anyhow!("Unclosed '{{{{' in template"))? + start;
+        let name = rest[start + 2..end].trim();
+        let value = vars.get(name).ok_or_else(|| anyhow!("Unknown variable '{}' in template", name))?;
+        out.push_str(&rest[..start]);
+        out.push_str(value);
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn render_args(args: Value, vars: &BTreeMap<String, String>) -> Result<Value> {
+    Ok(match args {
+        Value::String(s) => Value::String(render(&s, vars)?),
+        Value::Array(items) => Value::Array(items.into_iter().map(|v| render_args(v, vars)).collect::<Result<_>>()?),
+        Value::Object(map) => Value::Object(
+            map.into_iter().map(|(k, v)| Ok((k, render_args(v, vars)?))).collect::<Result<_>>()?
+        ),
+        other => other,
+    })
+}
+
+fn resolve(name_or_path: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(name_or_path);
+    if path.is_file() {
+        return Ok(path);
+    }
+    let dir = workflows_dir()?;
+    EXTENSIONS.iter()
+        .map(|ext| dir.join(format!("{}.{}", name_or_path, ext)))
+        .find(|p| p.is_file())
+        .ok_or_else(|| anyhow!("No workflow file '{}' and no {}/{}.yaml", name_or_path, dir.display(), name_or_path))
+}
+
+fn workflows_dir() -> Result<PathBuf> {
+    Ok(crate::utils::paths::get_air_config_dir()?.join("workflows"))
+}
+
+/// Workflows saved in the config directory, by name.
+pub fn available() -> Vec<String> {
+    let mut names: Vec<String> = workflows_dir()
+        .and_then(|dir| Ok(std::fs::read_dir(dir)?))
+        .map(|entries| entries.flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().and_then(|e| e.to_str()).is_some_and(|e| EXTENSIONS.contains(&e)))
+            .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(String::from))
+            .collect())
+        .unwrap_or_default();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_render() {
+        let vars = vars(&[("name", "AIR"), ("n", "3")]);
+        assert_eq!(render("Hello {{name}}, {{ n }} times", &vars).unwrap(), "Hello AIR, 3 times");
+        assert!(render("{{missing}}", &vars).is_err());
+        let args = render_args(serde_json::json!({ "command": "git log -{{n}}", "flags": ["{{name}}"], "x": 1 }), &vars).unwrap();
+        assert_eq!(args, serde_json::json!({ "command": "git log -3", "flags": ["AIR"], "x": 1 }));
+    }
+
+    #[test]
+    fn test_condition() {
+        let vars = vars(&[("risks", "  None found ")]);
+        let cond = |equals: Option<&str>, contains: Option<&str>, not_contains: Option<&str>| Condition {
+            var: "risks".to_string(),
+            equals: equals.map(String::from),
+            contains: contains.map(String::from),
+            not_contains: not_contains.map(String::from),
+        };
+        assert!(cond(Some("none found"), None, None).holds(&vars).unwrap());
+        assert!(cond(None, Some("NONE"), None).holds(&vars).unwrap());
+        assert!(!cond(None, None, Some("none")).holds(&vars).unwrap());
+    }
+
+    #[test]
+    fn test_parse_yaml_and_toml() {
+        let yaml = r#"
+steps:
+  - id: a
+    prompt: "Say {{x}}"
+  - id: both
+    parallel:
+      - id: b
+        tool: calculator
+        function: calculate
+        args: { expression: "2+2" }
+      - id: c
+        prompt: "{{a}}"
+"#;
+        let workflow = Workflow::parse(yaml, Path::new("w.yaml")).unwrap();
+        assert_eq!(workflow.steps[1].parallel.len(), 2);
+
+        let toml = r#"
+[[steps]]
+id = "a"
+prompt = "hi"
+when = { var = "x", contains = "y" }
+"#;
+        let workflow = Workflow::parse(toml, Path::new("w.toml")).unwrap();
+        assert_eq!(workflow.steps[0].when.as_ref().unwrap().contains.as_deref(), Some("y"));
+
+        assert!(Workflow::parse("steps:\n  - id: a\n", Path::new("w.yaml")).is_err());
+        assert!(Workflow::parse("steps:\n  - id: a\n    prompt: x\n  - id: a\n    prompt: y\n", Path::new("w.yaml")).is_err());
+    }
+}
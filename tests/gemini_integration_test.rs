@@ -1,118 +1,95 @@
-// use air::models::{ModelProvider, QueryContext};
-// use air::providers::GeminiProvider;
-// use air::config::CloudProviderConfig;
-// use std::time::Duration;
+use air::config::{CloudProviderConfig, VcrMode};
+use air::models::{ModelProvider, QueryContext};
+use air::providers::{Cassette, GeminiProvider, VcrProvider};
+use std::sync::Arc;
+use std::time::Duration;
 
-// #[tokio::test]
-// async fn test_gemini_provider_creation() {
-//     let config = CloudProviderConfig {
-//         name: "gemini".to_string(),
-//         api_key: Some("test-key".to_string()),
-//         base_url: "https://generativelanguage.googleapis.com".to_string(),
-//         model: "gemini-1.5-flash".to_string(),
-//         max_tokens: 1000,
-//         temperature: 0.7,
-//         timeout_seconds: 30,
-//     };
+const CASSETTE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cassettes/gemini.json");
 
-//     let provider = GeminiProvider::new(config).unwrap();
-//     assert_eq!(provider.name(), "Gemini");
-//     assert!(provider.is_available());
-//     assert_eq!(provider.estimated_latency_ms(), 1000);
-//     assert_eq!(provider.quality_score(), 0.92);
-// }
+fn gemini_config(api_key: Option<String>) -> CloudProviderConfig {
+    CloudProviderConfig {
+        name: "gemini".to_string(),
+        api_key,
+        base_url: "https://generativelanguage.googleapis.com".to_string(),
+        model: "gemini-1.5-flash".to_string(),
+        max_tokens: 1000,
+        temperature: 0.7,
+        timeout_seconds: 30,
+        enabled: true,
+        auto_select_model: false,
+        native_tools: true,
+        structured_output: true,
+        api_version: None,
+        azure_ad: false,
+        extra: Default::default(),
+        headers: Default::default(),
+        quality_score: None,
+        latency_ms: None,
+        cost_per_million_tokens: None,
+        requests_per_minute: None,
+        tokens_per_minute: None,
+    }
+}
 
-// #[tokio::test]
-// async fn test_gemini_provider_without_api_key() {
-//     let config = CloudProviderConfig {
-//         name: "gemini".to_string(),
-//         api_key: None,
-//         base_url: "https://generativelanguage.googleapis.com".to_string(),
-//         model: "gemini-1.5-flash".to_string(),
-//         max_tokens: 1000,
-//         temperature: 0.7,
-//         timeout_seconds: 30,
-//     };
+fn context(prompt: &str) -> QueryContext {
+    QueryContext {
+        prompt: prompt.to_string(),
+        messages: Vec::new(),
+        tools: Vec::new(),
+        response_schema: None,
+        images: Vec::new(),
+        max_tokens: 50,
+        temperature: 0.1,
+        timeout: Duration::from_secs(30),
+        pure_mode: false,
+    }
+}
 
-//     let provider = GeminiProvider::new(config).unwrap();
-//     assert_eq!(provider.name(), "Gemini");
-//     assert!(!provider.is_available());
-// }
+#[tokio::test]
+async fn test_gemini_provider_creation() {
+    let provider = GeminiProvider::new(gemini_config(Some("test-key".to_string()))).unwrap();
+    assert_eq!(provider.name(), "Gemini");
+    assert!(provider.is_available());
+    assert_eq!(provider.estimated_latency_ms(), 1000);
+    assert_eq!(provider.quality_score(), 0.92);
+}
 
-// #[tokio::test]
-// async fn test_gemini_query_context() {
-//     let context = QueryContext {
-//         prompt: "Hello, how are you?".to_string(),
-//         max_tokens: 100,
-//         temperature: 0.5,
-//         timeout: Duration::from_secs(10),
-//         pure_mode: false,
-//     };
+#[tokio::test]
+async fn test_gemini_provider_without_api_key() {
+    let provider = GeminiProvider::new(gemini_config(None)).unwrap();
+    assert_eq!(provider.name(), "Gemini");
+    assert!(!provider.is_available());
+}
 
-//     assert_eq!(context.prompt, "Hello, how are you?");
-//     assert_eq!(context.max_tokens, 100);
-//     assert_eq!(context.temperature, 0.5);
-// }
+#[tokio::test]
+async fn test_gemini_replayed_call() {
+    let cassette = Cassette::open(CASSETTE, VcrMode::Replay).unwrap();
+    let provider = VcrProvider::wrap(Arc::new(GeminiProvider::new(gemini_config(None)).unwrap()), cassette);
+    // The cassette stands in for the API key
+    assert!(provider.is_available());
 
-// #[tokio::test]
-// async fn test_gemini_provider_metrics() {
-//     let config = CloudProviderConfig {
-//         name: "gemini".to_string(),
-//         api_key: Some("test-key".to_string()),
-//         base_url: "https://generativelanguage.googleapis.com".to_string(),
-//         model: "gemini-1.5-flash".to_string(),
-//         max_tokens: 1000,
-//         temperature: 0.7,
-//         timeout_seconds: 30,
-//     };
+    let response = provider.generate(&context("Say hello in exactly 3 words")).await.unwrap();
+    assert_eq!(response.content, "Hello there, friend!");
+    assert!(response.model_used.contains("Gemini"));
+    assert!(response.response_time_ms > 0);
 
-//     let provider = GeminiProvider::new(config).unwrap();
-    
-//     // Test provider properties
-//     assert!(provider.quality_score() > 0.9);
-//     assert!(provider.estimated_latency_ms() < 2000);
-//     assert_eq!(provider.name(), "Gemini");
-// }
+    let unrecorded = provider.generate(&context("Something else")).await;
+    assert!(unrecorded.unwrap_err().to_string().contains("No recorded Gemini response"));
+}
 
-// // Integration test - only runs if GEMINI_API_KEY environment variable is set
-// #[tokio::test]
-// #[ignore = "requires actual API key"]
-// async fn test_gemini_real_api_call() {
-//     let api_key = std::env::var("GEMINI_API_KEY").ok();
-//     if api_key.is_none() {
-//         println!("Skipping real API test - no GEMINI_API_KEY environment variable");
-//         return;
-//     }
+// Re-records the cassette used above: GEMINI_API_KEY=... cargo test -- --ignored
+#[tokio::test]
+#[ignore = "requires actual API key"]
+async fn test_gemini_real_api_call() {
+    let Ok(api_key) = std::env::var("GEMINI_API_KEY") else {
+        println!("Skipping real API test - no GEMINI_API_KEY environment variable");
+        return;
+    };
 
-//     let config = CloudProviderConfig {
-//         name: "gemini".to_string(),
-//         api_key,
-//         base_url: "https://generativelanguage.googleapis.com".to_string(),
-//         model: "gemini-1.5-flash".to_string(),
-//         max_tokens: 100,
-//         temperature: 0.7,
-//         timeout_seconds: 30,
-//     };
-
-//     let provider = GeminiProvider::new(config).unwrap();
-//     let context = QueryContext {
-//         prompt: "Say hello in exactly 3 words".to_string(),
-//         max_tokens: 50,
-//         temperature: 0.1,
-//         timeout: Duration::from_secs(30),
-//         pure_mode: false,
-//     };
-
-//     let result = provider.generate(&context).await;
-//     match result {
-//         Ok(response) => {
-//             println!("Gemini response: {}", response.content);
-//             assert!(!response.content.is_empty());
-//             assert!(response.model_used.contains("Gemini"));
-//             assert!(response.response_time_ms > 0);
-//         }
-//         Err(e) => {
-//             println!("API call failed (expected if no valid key): {}", e);
-//         }
-//     }
-// }
+    let cassette = Cassette::open(CASSETTE, VcrMode::Record).unwrap();
+    let provider = VcrProvider::wrap(Arc::new(GeminiProvider::new(gemini_config(Some(api_key))).unwrap()), cassette);
+    let response = provider.generate(&context("Say hello in exactly 3 words")).await.unwrap();
+    println!("Gemini response: {}", response.content);
+    assert!(!response.content.is_empty());
+    assert!(response.model_used.contains("Gemini"));
+}
@@ -1,109 +1,125 @@
-// use air::models::{ModelProvider, QueryContext};
-// use air::providers::OpenRouterProvider;
-// use air::config::CloudProviderConfig;
-// use std::time::Duration;
+use air::config::{CloudProviderConfig, VcrMode};
+use air::models::{ModelProvider, QueryContext};
+use air::providers::{Cassette, OpenRouterProvider, VcrProvider};
+use std::sync::Arc;
+use std::time::Duration;
 
-// #[tokio::test]
-// async fn test_openrouter_provider_creation() {
-//     let config = CloudProviderConfig {
-//         name: "openrouter".to_string(),
-//         api_key: Some("test-key".to_string()),
-//         base_url: "https://openrouter.ai/api/v1".to_string(),
-//         model: "anthropic/claude-3.5-haiku".to_string(),
-//         max_tokens: 1000,
-//         temperature: 0.7,
-//         timeout_seconds: 30,
-//     };
+const CASSETTE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cassettes/openrouter.json");
 
-//     let provider = OpenRouterProvider::new(config).unwrap();
-//     assert_eq!(provider.name(), "OpenRouter");
-//     assert!(provider.is_available());
-//     assert_eq!(provider.estimated_latency_ms(), 1200);
-//     assert_eq!(provider.quality_score(), 0.90);
-// }
+fn openrouter_config(api_key: Option<String>, model: &str) -> CloudProviderConfig {
+    CloudProviderConfig {
+        name: "openrouter".to_string(),
+        api_key,
+        base_url: "https://openrouter.ai/api/v1".to_string(),
+        model: model.to_string(),
+        max_tokens: 1000,
+        temperature: 0.7,
+        timeout_seconds: 30,
+        enabled: true,
+        auto_select_model: true,
+        native_tools: true,
+        structured_output: true,
+        api_version: None,
+        azure_ad: false,
+        extra: Default::default(),
+        headers: Default::default(),
+        quality_score: None,
+        latency_ms: None,
+        cost_per_million_tokens: None,
+        requests_per_minute: None,
+        tokens_per_minute: None,
+    }
+}
 
-// #[tokio::test]
-// async fn test_openrouter_provider_without_api_key() {
-//     let config = CloudProviderConfig {
-//         name: "openrouter".to_string(),
-//         api_key: None,
-//         base_url: "https://openrouter.ai/api/v1".to_string(),
-//         model: "anthropic/claude-3.5-haiku".to_string(),
-//         max_tokens: 1000,
-//         temperature: 0.7,
-//         timeout_seconds: 30,
-//     };
+fn context(prompt: &str) -> QueryContext {
+    QueryContext {
+        prompt: prompt.to_string(),
+        messages: Vec::new(),
+        tools: Vec::new(),
+        response_schema: None,
+        images: Vec::new(),
+        max_tokens: 50,
+        temperature: 0.1,
+        timeout: Duration::from_secs(30),
+        pure_mode: false,
+    }
+}
 
-//     let provider = OpenRouterProvider::new(config).unwrap();
-//     assert_eq!(provider.name(), "OpenRouter");
-//     assert!(!provider.is_available());
-// }
+fn replaying_provider() -> Arc<dyn ModelProvider> {
+    let cassette = Cassette::open(CASSETTE, VcrMode::Replay).unwrap();
+    let provider = OpenRouterProvider::new(openrouter_config(None, "anthropic/claude-3.5-haiku")).unwrap();
+    VcrProvider::wrap(Arc::new(provider), cassette)
+}
 
-// #[tokio::test]
-// async fn test_openrouter_different_models() {
-//     let models = [
-//         "anthropic/claude-3.5-haiku",
-//         "openai/gpt-4o-mini",
-//         "google/gemini-2.0-flash-exp",
-//         "meta-llama/llama-3.1-8b-instruct",
-//     ];
+#[tokio::test]
+async fn test_openrouter_provider_creation() {
+    let provider = OpenRouterProvider::new(openrouter_config(Some("test-key".to_string()), "anthropic/claude-3.5-haiku")).unwrap();
+    assert_eq!(provider.name(), "OpenRouter");
+    assert!(provider.is_available());
+    assert_eq!(provider.estimated_latency_ms(), 1200);
+    assert_eq!(provider.quality_score(), 0.90);
+}
 
-//     for model in &models {
-//         let config = CloudProviderConfig {
-//             name: "openrouter".to_string(),
-//             api_key: Some("test-key".to_string()),
-//             base_url: "https://openrouter.ai/api/v1".to_string(),
-//             model: model.to_string(),
-//             max_tokens: 1000,
-//             temperature: 0.7,
-//             timeout_seconds: 30,
-//         };
+#[tokio::test]
+async fn test_openrouter_provider_without_api_key() {
+    let provider = OpenRouterProvider::new(openrouter_config(None, "anthropic/claude-3.5-haiku")).unwrap();
+    assert_eq!(provider.name(), "OpenRouter");
+    assert!(!provider.is_available());
+}
 
-//         let provider = OpenRouterProvider::new(config).unwrap();
-//         assert!(provider.is_available());
-//         println!("✅ OpenRouter provider works with model: {}", model);
-//     }
-// }
+#[tokio::test]
+async fn test_openrouter_different_models() {
+    let models = [
+        "anthropic/claude-3.5-haiku",
+        "openai/gpt-4o-mini",
+        "google/gemini-2.0-flash-exp",
+        "meta-llama/llama-3.1-8b-instruct",
+    ];
 
-// // Integration test - only runs if OPEN_ROUTER environment variable is set
-// #[tokio::test]
-// #[ignore = "requires actual API key"]
-// async fn test_openrouter_real_api_call() {
-//     let api_key = std::env::var("OPEN_ROUTER").ok();
-//     if api_key.is_none() {
-//         println!("Skipping real API test - no OPEN_ROUTER environment variable");
-//         return;
-//     }
+    for model in &models {
+        let provider = OpenRouterProvider::new(openrouter_config(Some("test-key".to_string()), model)).unwrap();
+        assert!(provider.is_available());
+    }
+}
 
-//     let config = CloudProviderConfig {
-//         name: "openrouter".to_string(),
-//         api_key,
-//         base_url: "https://openrouter.ai/api/v1".to_string(),
-//         model: "anthropic/claude-3.5-haiku".to_string(),
-//         max_tokens: 100,
-//         temperature: 0.7,
-//         timeout_seconds: 30,
-//     };
+#[tokio::test]
+async fn test_openrouter_replayed_call() {
+    let provider = replaying_provider();
+    assert!(provider.is_available());
 
-//     let provider = OpenRouterProvider::new(config).unwrap();
-//     let context = QueryContext {
-//         prompt: "Say hello in exactly 3 words".to_string(),
-//         max_tokens: 50,
-//         temperature: 0.1,
-//         timeout: Duration::from_secs(30),
-//         pure_mode: false,
-//     };
+    let response = provider.generate(&context("Say hello in exactly 3 words")).await.unwrap();
+    assert_eq!(response.content, "Hello to you!");
+    assert!(response.model_used.contains("OpenRouter"));
 
-//     let result = provider.generate(&context).await;
-//     match result {
-//         Ok(response) => {
-//             println!("OpenRouter response: {}", response.content);
-//             assert!(!response.content.is_empty());
-//             assert!(response.model_used.contains("OpenRouter"));
-//             assert!(response.response_time_ms > 0);
-//         }
-//         Err(e) => {
-//             println!("API call failed (expected if no valid key): {}", e);
-//         }
-//     }
-// }
+    // Each recording is used once
+    assert!(provider.generate(&context("Say hello in exactly 3 words")).await.is_err());
+}
+
+#[tokio::test]
+async fn test_openrouter_replayed_failure() {
+    let error = replaying_provider().generate(&context("What is the capital of France?")).await.unwrap_err();
+    assert!(error.to_string().contains("429"));
+}
+
+// Re-records the cassette used above: OPEN_ROUTER=... cargo test -- --ignored
+#[tokio::test]
+#[ignore = "requires actual API key"]
+async fn test_openrouter_real_api_call() {
+    let Ok(api_key) = std::env::var("OPEN_ROUTER") else {
+        println!("Skipping real API test - no OPEN_ROUTER environment variable");
+        return;
+    };
+
+    let cassette = Cassette::open(CASSETTE, VcrMode::Record).unwrap();
+    let provider = OpenRouterProvider::new(openrouter_config(Some(api_key), "anthropic/claude-3.5-haiku")).unwrap();
+    let provider = VcrProvider::wrap(Arc::new(provider), cassette);
+    for prompt in ["Say hello in exactly 3 words", "What is the capital of France?"] {
+        match provider.generate(&context(prompt)).await {
+            Ok(response) => {
+                println!("OpenRouter response: {}", response.content);
+                assert!(response.model_used.contains("OpenRouter"));
+            }
+            Err(e) => println!("API call failed (recorded): {}", e),
+        }
+    }
+}